@@ -1,4 +1,4 @@
-use libc::c_char;
+use std::os::raw::c_char;
 use std::ptr::null;
 
 use super::rpu::rpu_data_header::RpuDataHeader as RuRpuDataHeader;
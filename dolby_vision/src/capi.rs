@@ -1,7 +1,8 @@
 #![deny(missing_docs)]
 
-use libc::{c_char, size_t};
+use libc::size_t;
 use std::{
+    os::raw::c_char,
     ptr::{null, null_mut},
     slice,
 };
@@ -29,7 +30,7 @@ pub struct RpuOpaque {
 pub unsafe extern "C" fn dovi_parse_rpu(buf: *const u8, len: size_t) -> *mut RpuOpaque {
     assert!(!buf.is_null());
 
-    let data = slice::from_raw_parts(buf, len as usize);
+    let data = slice::from_raw_parts(buf, len);
     let res = DoviRpu::parse_rpu(data);
 
     Box::into_raw(Box::new(RpuOpaque::from(res)))
@@ -44,7 +45,7 @@ pub unsafe extern "C" fn dovi_parse_rpu(buf: *const u8, len: size_t) -> *mut Rpu
 pub unsafe extern "C" fn dovi_parse_unspec62_nalu(buf: *const u8, len: size_t) -> *mut RpuOpaque {
     assert!(!buf.is_null());
 
-    let data = slice::from_raw_parts(buf, len as usize);
+    let data = slice::from_raw_parts(buf, len);
     let res = DoviRpu::parse_unspec62_nalu(data);
 
     Box::into_raw(Box::new(RpuOpaque::from(res)))
@@ -57,7 +58,7 @@ pub unsafe extern "C" fn dovi_parse_unspec62_nalu(buf: *const u8, len: size_t) -
 #[no_mangle]
 pub unsafe extern "C" fn dovi_rpu_free(ptr: *mut RpuOpaque) {
     if !ptr.is_null() {
-        Box::from_raw(ptr);
+        drop(Box::from_raw(ptr));
     }
 }
 
@@ -148,7 +149,7 @@ pub unsafe extern "C" fn dovi_write_unspec62_nalu(ptr: *mut RpuOpaque) -> *const
 
 /// # Safety
 /// The struct pointer should be valid.
-/// The mode should be between 0 and 3.
+/// The mode should be between 0 and 4.
 ///
 /// Converts the RPU to be compatible with a different Dolby Vision profile.
 /// Possible modes:
@@ -156,6 +157,7 @@ pub unsafe extern "C" fn dovi_write_unspec62_nalu(ptr: *mut RpuOpaque) -> *const
 ///     1: Converts the RPU to be MEL compatible
 ///     2: Converts the RPU to be profile 8.1 compatible
 ///     3: Converts profile 5 to 8
+///     4: Converts to profile 8.4, for a HLG base layer
 ///
 /// If an error occurs, it is logged to RpuOpaque.error.
 /// Returns 0 if successful, -1 otherwise.
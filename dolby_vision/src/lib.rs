@@ -1,11 +1,15 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
 /// Dolby Vision RPU (as found in HEVC type 62 NALUs) module
 pub mod rpu;
 
 /// SMPTE ST2094-10 metadata module
+#[cfg(feature = "std")]
 pub mod st2094_10;
 
 /// Various utils
 /// cbindgen:ignore
+#[cfg(feature = "std")]
 pub mod utils;
 
 /// Dolby Vision XML metadata module
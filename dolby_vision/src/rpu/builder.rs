@@ -0,0 +1,221 @@
+use anyhow::Result;
+
+use super::dovi_rpu::DoviRpu;
+use super::extension_metadata::blocks::*;
+use super::generate::{validate_trim_targets, GenerateConfig, GenerateProfile, TrimAlgorithm};
+use super::vdr_dm_data::CmVersion;
+
+/// Builds valid profile 8 RPUs from scratch, without a generator config.
+///
+/// The header, mapping and static metadata (L5, L6, L9, L11, L254) use the same defaults as
+/// `GenerateConfig`. The built RPUs are marked as modified, so the CRC32 and block lengths
+/// are computed when writing them.
+///
+/// The builder can be reused to create a sequence of RPUs, changing the per-frame metadata in between.
+#[derive(Debug, Clone, Default)]
+pub struct RpuBuilder {
+    config: GenerateConfig,
+    scene_cut: bool,
+
+    /// Per-frame metadata, replacing the static metadata of the same level
+    metadata_blocks: Vec<ExtMetadataBlock>,
+}
+
+impl RpuBuilder {
+    /// Profile 8.1, CM v4.0 builder, with zero L5 offsets and a 1000 nits L6
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Profile of the built RPUs, defaults to 8.1
+    pub fn profile(mut self, profile: GenerateProfile) -> Self {
+        self.config.profile = profile;
+        self
+    }
+
+    /// Content mapping version, defaults to v4.0
+    pub fn cm_version(mut self, cm_version: CmVersion) -> Self {
+        self.config.cm_version = cm_version;
+        self
+    }
+
+    /// Mastering display min/max luminance, as 12 bit PQ codes
+    pub fn source_levels(mut self, min_pq: u16, max_pq: u16) -> Self {
+        self.config.source_min_pq = Some(min_pq);
+        self.config.source_max_pq = Some(max_pq);
+        self
+    }
+
+    /// Active area offsets
+    pub fn level5(mut self, left: u16, right: u16, top: u16, bottom: u16) -> Self {
+        self.config.level5 = ExtMetadataBlockLevel5::from_offsets(left, right, top, bottom);
+        self
+    }
+
+    /// ST2086/HDR10 fallback metadata
+    pub fn level6(mut self, level6: ExtMetadataBlockLevel6) -> Self {
+        self.config.level6 = level6;
+        self
+    }
+
+    /// Target displays to generate L2/L8 trims for, from the L1 metadata.
+    /// Supported targets: 100, 600 and 1000 nits.
+    pub fn trim_targets(mut self, trim_targets: Vec<u16>, trim_algorithm: TrimAlgorithm) -> Self {
+        self.config.trim_targets = trim_targets;
+        self.config.trim_algorithm = trim_algorithm;
+        self
+    }
+
+    /// Whether the built RPU starts a new scene
+    pub fn scene_cut(mut self, scene_cut: bool) -> Self {
+        self.scene_cut = scene_cut;
+        self
+    }
+
+    /// Sets the L1 metadata of the frame
+    pub fn level1(self, min_pq: u16, max_pq: u16, avg_pq: u16) -> Self {
+        self.metadata_block(ExtMetadataBlock::Level1(
+            ExtMetadataBlockLevel1::from_stats(min_pq, max_pq, avg_pq),
+        ))
+    }
+
+    /// Sets a metadata block of the frame.
+    /// L2, L8 and L10 blocks only replace the block with the same target display.
+    pub fn metadata_block(mut self, block: ExtMetadataBlock) -> Self {
+        self.metadata_blocks.push(block);
+        self
+    }
+
+    /// Removes the per-frame metadata blocks, keeping the static metadata
+    pub fn clear_metadata_blocks(mut self) -> Self {
+        self.metadata_blocks.clear();
+        self
+    }
+
+    pub fn build(&self) -> Result<DoviRpu> {
+        let config = &self.config;
+
+        validate_trim_targets(&config.trim_targets)?;
+        config.trim_algorithm.validate()?;
+
+        let mut rpu = match config.profile {
+            GenerateProfile::Profile81 => DoviRpu::profile81_config(config)?,
+            GenerateProfile::Profile84 => DoviRpu::profile84_config(config)?,
+        };
+
+        if let Some(ref mut vdr_dm_data) = rpu.vdr_dm_data {
+            vdr_dm_data.set_scene_cut(self.scene_cut);
+
+            for block in &self.metadata_blocks {
+                vdr_dm_data.replace_metadata_block(block.clone())?;
+            }
+
+            vdr_dm_data.add_generated_trims(&config.trim_targets, config.trim_algorithm)?;
+            vdr_dm_data.validate()?;
+        }
+
+        Ok(rpu)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::rpu::{
+        dovi_rpu::DoviRpu, extension_metadata::blocks::*, generate::GenerateProfile,
+        generate::TrimAlgorithm, vdr_dm_data::CmVersion,
+    };
+
+    use super::RpuBuilder;
+
+    #[test]
+    fn builds_writable_rpus() -> Result<()> {
+        let builder = RpuBuilder::new()
+            .source_levels(62, 3696)
+            .level5(0, 0, 276, 276)
+            .level6(ExtMetadataBlockLevel6 {
+                max_display_mastering_luminance: 4000,
+                min_display_mastering_luminance: 50,
+                max_content_light_level: 1000,
+                max_frame_average_light_level: 400,
+            });
+
+        let rpus = [
+            builder
+                .clone()
+                .scene_cut(true)
+                .level1(0, 2081, 1229)
+                .build()?,
+            builder.clone().level1(0, 3000, 1200).build()?,
+        ];
+
+        for (i, rpu) in rpus.iter().enumerate() {
+            let parsed = DoviRpu::parse_unspec62_nalu(&rpu.write_hevc_unspec62_nalu()?)?;
+            assert_eq!(parsed.dovi_profile, 8);
+
+            let vdr_dm_data = parsed.vdr_dm_data.as_ref().unwrap();
+            assert_eq!(vdr_dm_data.scene_refresh_flag, (i == 0) as u64);
+            assert_eq!(vdr_dm_data.source_min_pq, 62);
+            assert_eq!(vdr_dm_data.source_max_pq, 3696);
+
+            if let Some(ExtMetadataBlock::Level5(level5)) = vdr_dm_data.get_block(5) {
+                assert_eq!(level5.get_offsets(), (0, 0, 276, 276));
+            } else {
+                panic!("No L5 metadata");
+            }
+
+            if let Some(ExtMetadataBlock::Level6(level6)) = vdr_dm_data.get_block(6) {
+                assert_eq!(level6.max_display_mastering_luminance, 4000);
+            } else {
+                panic!("No L6 metadata");
+            }
+
+            assert!(vdr_dm_data.get_block(9).is_some());
+            assert!(vdr_dm_data.get_block(254).is_some());
+        }
+
+        if let Some(ExtMetadataBlock::Level1(level1)) =
+            rpus[1].vdr_dm_data.as_ref().unwrap().get_block(1)
+        {
+            assert_eq!(
+                (level1.min_pq, level1.max_pq, level1.avg_pq),
+                (0, 3000, 1200)
+            );
+        } else {
+            panic!("No L1 metadata");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn profile84_cmv29_with_trims() -> Result<()> {
+        let rpu = RpuBuilder::new()
+            .profile(GenerateProfile::Profile84)
+            .cm_version(CmVersion::V29)
+            .trim_targets(vec![100], TrimAlgorithm::Highlights)
+            .build()?;
+
+        let parsed = DoviRpu::parse_unspec62_nalu(&rpu.write_hevc_unspec62_nalu()?)?;
+        assert_eq!(parsed.subprofile, rpu.subprofile);
+
+        let vdr_dm_data = parsed.vdr_dm_data.as_ref().unwrap();
+        assert!(vdr_dm_data.cmv40_metadata.is_none());
+        assert!(vdr_dm_data.get_block(1).is_some());
+        assert_eq!(vdr_dm_data.level_blocks_iter(2).count(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_metadata() {
+        let res = RpuBuilder::new().source_levels(0, 5000).build();
+        assert!(res.is_err());
+
+        let res = RpuBuilder::new()
+            .trim_targets(vec![400], TrimAlgorithm::Highlights)
+            .build();
+        assert!(res.is_err());
+    }
+}
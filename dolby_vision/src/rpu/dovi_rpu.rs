@@ -2,8 +2,8 @@ use anyhow::{bail, ensure, Result};
 use bitvec::prelude::*;
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
-#[cfg(feature = "serde_feature")]
-use serde::Serialize;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 use super::compute_crc32;
 use super::extension_metadata::blocks::{
@@ -11,9 +11,10 @@ use super::extension_metadata::blocks::{
 };
 use super::extension_metadata::{CmV40DmData, DmData};
 use super::generate::GenerateConfig;
+use super::profiles::profile84::{self, Profile84};
 use super::rpu_data_header::{rpu_data_header, RpuDataHeader};
 use super::rpu_data_mapping::RpuDataMapping;
-use super::rpu_data_nlq::RpuDataNlq;
+use super::rpu_data_nlq::{NlqParams, RpuDataNlq};
 use super::vdr_dm_data::VdrDmData;
 use super::{FEL_STR, MEL_STR};
 
@@ -27,89 +28,80 @@ use crate::utils::{
 const FINAL_BYTE: u8 = 0x80;
 
 #[derive(Default, Debug, Clone)]
-#[cfg_attr(feature = "serde_feature", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct DoviRpu {
     pub dovi_profile: u8,
 
-    #[cfg_attr(
-        feature = "serde_feature",
-        serde(skip_serializing_if = "Option::is_none")
-    )]
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub subprofile: Option<String>,
 
     pub header: RpuDataHeader,
 
-    #[cfg_attr(
-        feature = "serde_feature",
-        serde(skip_serializing_if = "Option::is_none")
-    )]
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub rpu_data_mapping: Option<RpuDataMapping>,
 
-    #[cfg_attr(
-        feature = "serde_feature",
-        serde(skip_serializing_if = "Option::is_none")
-    )]
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub rpu_data_nlq: Option<RpuDataNlq>,
 
-    #[cfg_attr(
-        feature = "serde_feature",
-        serde(skip_serializing_if = "Option::is_none")
-    )]
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub vdr_dm_data: Option<VdrDmData>,
 
     #[cfg_attr(
-        feature = "serde_feature",
+        feature = "serde",
         serde(
+            default,
             serialize_with = "crate::utils::bitvec_ser_bits",
+            deserialize_with = "crate::utils::bitvec_de_bits",
             skip_serializing_if = "BitVec::is_empty"
         )
     )]
     pub remaining: BitVec<u8, Msb0>,
     pub rpu_data_crc32: u32,
 
-    #[cfg_attr(feature = "serde_feature", serde(skip_serializing))]
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub trailing_bytes: Vec<u8>,
 
-    #[cfg_attr(feature = "serde_feature", serde(skip_serializing))]
+    /// Deserialized RPUs are considered modified, the CRC32 is computed when writing
+    #[cfg_attr(feature = "serde", serde(skip, default = "crate::utils::default_true"))]
     pub modified: bool,
 }
 
 impl DoviRpu {
+    /// Validates the RPU start bytes and returns the data starting at the `rpu_nal_prefix`
     pub fn validated_trimmed_data(data: &[u8]) -> Result<&[u8]> {
-        if data.len() < 25 {
-            bail!("Invalid RPU length: {}", &data.len());
-        }
-
-        // Including 0x7C01 prepended
-        let trimmed_data = match &data[..5] {
-            [0, 0, 0, 1, 25] => &data[4..],
-            [0, 0, 1, 25, 8] => &data[3..],
-            [0, 1, 25, 8, 9] | [124, 1, 25, 8, 9] => &data[2..],
-            [1, 25, 8, 9, _] => &data[1..],
-            [25, 8, 9, _, _] => data,
-            _ => bail!("Invalid RPU data start bytes\n{:?}", &data[..5]),
-        };
-
-        Ok(trimmed_data)
+        super::validated_trimmed_data(data)
     }
 
+    /// Parses an escaped RPU from a HEVC UNSPEC62 NAL unit.
+    /// The data can include the start code and/or the NAL header (0x7C01).
     pub fn parse_unspec62_nalu(data: &[u8]) -> Result<DoviRpu> {
         let trimmed_data = DoviRpu::validated_trimmed_data(data)?;
 
         // Clear start code emulation prevention 3 byte
         let bytes: Vec<u8> = clear_start_code_emulation_prevention_3_byte(trimmed_data);
 
-        DoviRpu::parse(&bytes)
+        DoviRpu::parse(&bytes, true)
+    }
+
+    /// Parses an escaped RPU from a HEVC UNSPEC62 NAL unit, without validating the CRC32.
+    ///
+    /// On CRC32 mismatch, the RPU is marked as `modified` so that writing it computes a valid CRC32.
+    pub fn parse_unspec62_nalu_ignore_crc32(data: &[u8]) -> Result<DoviRpu> {
+        let trimmed_data = DoviRpu::validated_trimmed_data(data)?;
+        let bytes: Vec<u8> = clear_start_code_emulation_prevention_3_byte(trimmed_data);
+
+        DoviRpu::parse(&bytes, false)
     }
 
+    /// Parses an unescaped RPU, without emulation prevention bytes.
     pub fn parse_rpu(data: &[u8]) -> Result<DoviRpu> {
         let trimmed_data = DoviRpu::validated_trimmed_data(data)?;
 
-        DoviRpu::parse(trimmed_data)
+        DoviRpu::parse(trimmed_data, true)
     }
 
     #[inline(always)]
-    fn parse(data: &[u8]) -> Result<DoviRpu> {
+    fn parse(data: &[u8], validate_crc32: bool) -> Result<DoviRpu> {
         let trailing_bytes: Vec<u8> = data
             .iter()
             .rev()
@@ -133,11 +125,14 @@ impl DoviRpu {
         let mut dovi_rpu = DoviRpu::read_rpu_data(data.to_owned(), trailing_bytes)?;
 
         if received_crc32 != dovi_rpu.rpu_data_crc32 {
-            bail!(
+            ensure!(
+                !validate_crc32,
                 "RPU CRC32 does not match the data. Received: {}, expected {}",
                 received_crc32,
                 dovi_rpu.rpu_data_crc32
             );
+
+            dovi_rpu.modified = true;
         }
 
         dovi_rpu.dovi_profile = dovi_rpu.header.get_dovi_profile();
@@ -198,6 +193,8 @@ impl DoviRpu {
         Ok(dovi_rpu)
     }
 
+    /// Writes the RPU as an escaped HEVC UNSPEC62 NAL unit, including the NAL header (0x7C01).
+    /// The start code is not included.
     pub fn write_hevc_unspec62_nalu(&self) -> Result<Vec<u8>> {
         let mut out = self.write_rpu_data()?;
         add_start_code_emulation_prevention_3_byte(&mut out);
@@ -209,6 +206,7 @@ impl DoviRpu {
         Ok(out)
     }
 
+    /// Writes the unescaped RPU, without NAL header.
     pub fn write_rpu(&self) -> Result<Vec<u8>> {
         self.write_rpu_data()
     }
@@ -315,6 +313,7 @@ impl DoviRpu {
     ///     1: Converts the RPU to be MEL compatible
     ///     2: Converts the RPU to be profile 8.1 compatible
     ///     3: Converts profile 5 to 8
+    ///     4: Converts to profile 8.4, for a HLG base layer
     ///
     /// noop when profile 8 and mode 2 is used
     pub fn convert_with_mode(&mut self, mode: u8) -> Result<()> {
@@ -330,6 +329,8 @@ impl DoviRpu {
             };
         } else if self.dovi_profile == 5 && mode == 3 {
             self.p5_to_p81()?;
+        } else if mode == 4 && matches!(self.dovi_profile, 5 | 7 | 8) {
+            self.convert_to_p84();
         } else if self.dovi_profile == 8 && (mode == 1 || mode == 2) {
             match mode {
                 1 => self.convert_to_mel()?,
@@ -370,6 +371,47 @@ impl DoviRpu {
         Ok(())
     }
 
+    /// Removes the NLQ data, so that the EL residual is not applied.
+    /// The RPU is then profile 8 compatible, the mapping and DM metadata are kept.
+    pub fn remove_nlq(&mut self) -> Result<()> {
+        ensure!(
+            self.rpu_data_nlq.is_some(),
+            "No NLQ data to remove, profile {}",
+            self.dovi_profile
+        );
+
+        self.modified = true;
+
+        let header = &mut self.header;
+
+        header.el_spatial_resampling_filter_flag = false;
+        header.disable_residual_flag = true;
+
+        header.nlq_method_idc = None;
+        header.nlq_num_pivots_minus2 = None;
+        header.nlq_pred_pivot_value = None;
+
+        self.rpu_data_nlq = None;
+
+        self.dovi_profile = self.header.get_dovi_profile();
+        self.subprofile = self.get_dovi_subprofile();
+
+        Ok(())
+    }
+
+    /// Sets the NLQ parameters, the subprofile is updated to MEL or FEL accordingly
+    pub fn set_nlq_params(&mut self, params: &NlqParams) -> Result<()> {
+        match self.rpu_data_nlq {
+            Some(ref mut rpu_data_nlq) => rpu_data_nlq.set_params(params, &self.header)?,
+            None => bail!("No NLQ data to edit, profile {}", self.dovi_profile),
+        }
+
+        self.modified = true;
+        self.subprofile = self.get_dovi_subprofile();
+
+        Ok(())
+    }
+
     fn convert_to_81(&mut self) {
         let header = &mut self.header;
 
@@ -391,6 +433,27 @@ impl DoviRpu {
         }
     }
 
+    /// Converts to profile 8.4, for a HLG base layer.
+    /// The mapping reshapes the HLG luma to PQ, assuming a 1000 nits HLG display.
+    fn convert_to_p84(&mut self) {
+        self.modified = true;
+
+        self.convert_to_81();
+
+        self.header.vdr_rpu_profile = 1;
+        self.header.bl_video_full_range_flag = false;
+        self.header.coefficient_data_type = 0;
+        self.header.coefficient_log2_denom = profile84::COEFFICIENT_LOG2_DENOM;
+
+        self.remove_mapping();
+
+        let luma_pivots = Profile84::luma_pred_pivot_value();
+        self.header.num_pivots_minus_2[0] = luma_pivots.len() as u64 - 2;
+        self.header.pred_pivot_value[0] = luma_pivots;
+
+        self.rpu_data_mapping = Some(Profile84::rpu_data_mapping());
+    }
+
     fn p5_to_p81(&mut self) -> Result<()> {
         self.modified = true;
 
@@ -426,6 +489,29 @@ impl DoviRpu {
         })
     }
 
+    /// Profile 8.4 RPU for a HLG base layer.
+    /// Without L1 metadata in the config, the static HLG L1 metadata is used.
+    pub fn profile84_config(config: &GenerateConfig) -> Result<Self> {
+        let mut rpu = Self::profile81_config(config)?;
+        rpu.convert_to_p84();
+
+        if let Some(ref mut vdr_dm_data) = rpu.vdr_dm_data {
+            if config.source_max_pq.is_none() {
+                vdr_dm_data.source_max_pq = Profile84::source_max_pq();
+            }
+
+            if vdr_dm_data.get_block(1).is_none() {
+                vdr_dm_data
+                    .replace_metadata_block(ExtMetadataBlock::Level1(Profile84::static_level1()))?;
+            }
+        }
+
+        rpu.dovi_profile = rpu.header.get_dovi_profile();
+        rpu.subprofile = rpu.get_dovi_subprofile();
+
+        Ok(rpu)
+    }
+
     /// Set existing L5 metadata to zero offsets
     /// If there is no L5 metadata, creates it with zero offsets
     pub fn crop(&mut self) -> Result<()> {
@@ -440,20 +526,40 @@ impl DoviRpu {
         Ok(())
     }
 
+    /// Replaces the polynomial/MMR mapping by an identity mapping.
+    ///
+    /// The pivots cover the base layer bit depth, and the coefficients keep the
+    /// coefficient data type, so the NLQ data is unchanged.
+    /// RPUs using the mapping of the previous RPU are left untouched.
     pub fn remove_mapping(&mut self) {
+        if self.header.use_prev_vdr_rpu_flag {
+            return;
+        }
+
         self.modified = true;
 
+        let max_pivot = (1 << (self.header.bl_bit_depth_minus8 + 8)) - 1;
+
         self.header.num_pivots_minus_2 = [0, 0, 0];
         self.header.pred_pivot_value.iter_mut().for_each(|v| {
             v.clear();
-            v.extend(&[0, 1023]);
+            v.extend(&[0, max_pivot]);
         });
 
         if let Some(ref mut rpu_data_mapping) = self.rpu_data_mapping {
             rpu_data_mapping.set_empty_p81_mapping();
+
+            // Floating point coefficients, the integer parts are not coded
+            if self.header.coefficient_data_type == 1 {
+                rpu_data_mapping
+                    .poly_coef
+                    .iter_mut()
+                    .for_each(|v| v[0] = vec![0, 1.0_f32.to_bits() as u64]);
+            }
         }
     }
 
+    /// Parses a list of UNSPEC62 NAL units, ignoring the invalid ones
     pub fn parse_list_of_unspec62_nalus(data: &[Vec<u8>]) -> Vec<DoviRpu> {
         data.iter()
             .map(|rpu| DoviRpu::parse_unspec62_nalu(rpu))
@@ -461,6 +567,7 @@ impl DoviRpu {
             .collect()
     }
 
+    /// Adds CM v4.0 metadata, if not already present
     pub fn convert_to_cmv40(&mut self) -> Result<()> {
         if let Some(ref mut vdr_dm_data) = self.vdr_dm_data {
             if vdr_dm_data.cmv40_metadata.is_none() {
@@ -480,4 +587,15 @@ impl DoviRpu {
 
         Ok(())
     }
+
+    /// Removes the CM v4.0 metadata, keeping the CM v2.9 metadata
+    pub fn convert_to_cmv29(&mut self) {
+        if let Some(ref mut vdr_dm_data) = self.vdr_dm_data {
+            if vdr_dm_data.cmv40_metadata.is_some() {
+                self.modified = true;
+
+                vdr_dm_data.cmv40_metadata = None;
+            }
+        }
+    }
 }
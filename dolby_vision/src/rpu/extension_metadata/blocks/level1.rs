@@ -1,9 +1,11 @@
 use anyhow::{ensure, Result};
+#[cfg(feature = "std")]
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
-#[cfg(feature = "serde_feature")]
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "std")]
 use super::{ExtMetadataBlock, ExtMetadataBlockInfo};
 
 /// cbindgen:ignore
@@ -18,7 +20,7 @@ pub const L1_AVG_PQ_MIN_VALUE: u16 = 819;
 /// Statistical analysis of the frame: min, max, avg brightness.
 #[repr(C)]
 #[derive(Debug, Default, Clone)]
-#[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct ExtMetadataBlockLevel1 {
     pub min_pq: u16,
     pub max_pq: u16,
@@ -26,6 +28,7 @@ pub struct ExtMetadataBlockLevel1 {
 }
 
 impl ExtMetadataBlockLevel1 {
+    #[cfg(feature = "std")]
     pub fn parse(reader: &mut BitVecReader) -> ExtMetadataBlock {
         ExtMetadataBlock::Level1(Self {
             min_pq: reader.get_n(12),
@@ -34,6 +37,7 @@ impl ExtMetadataBlockLevel1 {
         })
     }
 
+    #[cfg(feature = "std")]
     pub fn write(&self, writer: &mut BitVecWriter) -> Result<()> {
         self.validate()?;
 
@@ -65,6 +69,7 @@ impl ExtMetadataBlockLevel1 {
     }
 }
 
+#[cfg(feature = "std")]
 impl ExtMetadataBlockInfo for ExtMetadataBlockLevel1 {
     fn level(&self) -> u8 {
         1
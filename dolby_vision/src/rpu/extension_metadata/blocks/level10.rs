@@ -1,7 +1,7 @@
 use anyhow::{ensure, Result};
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
-#[cfg(feature = "serde_feature")]
+#[cfg(feature = "serde")]
 use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
 
 use super::{level6::MAX_PQ_LUMINANCE, ColorPrimaries, ExtMetadataBlock, ExtMetadataBlockInfo};
@@ -17,7 +17,7 @@ pub const PRESET_TARGET_DISPLAYS: &[u8] = &[1, 16, 18, 21, 27, 28, 37, 38, 42, 4
 ///     - 21: target_primary_{red,green,blue,white}_{x,y}
 #[repr(C)]
 #[derive(Debug, Clone)]
-#[cfg_attr(feature = "serde_feature", derive(Deserialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
 pub struct ExtMetadataBlockLevel10 {
     pub length: u64,
     pub target_display_index: u8,
@@ -25,21 +25,21 @@ pub struct ExtMetadataBlockLevel10 {
     pub target_min_pq: u16,
     pub target_primary_index: u8,
 
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub target_primary_red_x: u16,
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub target_primary_red_y: u16,
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub target_primary_green_x: u16,
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub target_primary_green_y: u16,
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub target_primary_blue_x: u16,
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub target_primary_blue_y: u16,
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub target_primary_white_x: u16,
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub target_primary_white_y: u16,
 }
 
@@ -166,7 +166,7 @@ impl Default for ExtMetadataBlockLevel10 {
     }
 }
 
-#[cfg(feature = "serde_feature")]
+#[cfg(feature = "serde")]
 impl Serialize for ExtMetadataBlockLevel10 {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -1,7 +1,7 @@
 use anyhow::{ensure, Result};
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
-#[cfg(feature = "serde_feature")]
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 use super::{ExtMetadataBlock, ExtMetadataBlockInfo};
@@ -11,18 +11,29 @@ const MAX_WHITEPOINT_VALUE: u8 = 15;
 /// Content type metadata level
 #[repr(C)]
 #[derive(Debug, Default, Clone)]
-#[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct ExtMetadataBlockLevel11 {
     pub content_type: u8,
     pub whitepoint: u8,
     pub reference_mode_flag: bool,
 
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub reserved_byte2: u8,
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub reserved_byte3: u8,
 }
 
+/// L11 content types, used by the displays to select the picture mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub enum ContentType {
+    Default = 0,
+    Cinema,
+    Game,
+    Sport,
+    UserGenerated,
+}
+
 impl ExtMetadataBlockLevel11 {
     pub fn parse(reader: &mut BitVecReader) -> ExtMetadataBlock {
         let mut l11 = Self {
@@ -67,6 +78,17 @@ impl ExtMetadataBlockLevel11 {
         Ok(())
     }
 
+    /// D65 whitepoint, reference mode for cinema content only
+    pub fn from_content_type(content_type: ContentType) -> Self {
+        Self {
+            content_type: content_type as u8,
+            whitepoint: 0,
+            reference_mode_flag: content_type == ContentType::Cinema,
+            reserved_byte2: 0,
+            reserved_byte3: 0,
+        }
+    }
+
     /// Cinema, reference mode, D65 whitepoint
     pub fn default_reference_cinema() -> Self {
         Self {
@@ -79,6 +101,29 @@ impl ExtMetadataBlockLevel11 {
     }
 }
 
+impl ContentType {
+    pub fn from_value(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Default),
+            1 => Some(Self::Cinema),
+            2 => Some(Self::Game),
+            3 => Some(Self::Sport),
+            4 => Some(Self::UserGenerated),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Default => "Default",
+            Self::Cinema => "Cinema",
+            Self::Game => "Game",
+            Self::Sport => "Sport",
+            Self::UserGenerated => "User generated content",
+        }
+    }
+}
+
 impl ExtMetadataBlockInfo for ExtMetadataBlockLevel11 {
     fn level(&self) -> u8 {
         11
@@ -92,3 +137,37 @@ impl ExtMetadataBlockInfo for ExtMetadataBlockLevel11 {
         32
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
+
+    use super::{ContentType, ExtMetadataBlock, ExtMetadataBlockLevel11};
+
+    #[test]
+    fn content_type_roundtrip() {
+        for value in 0..=4 {
+            let content_type = ContentType::from_value(value).unwrap();
+            let l11 = ExtMetadataBlockLevel11::from_content_type(content_type);
+
+            assert_eq!(l11.content_type, value);
+            assert_eq!(l11.reference_mode_flag, content_type == ContentType::Cinema);
+
+            let mut writer = BitVecWriter::new();
+            l11.write(&mut writer).unwrap();
+
+            let mut reader = BitVecReader::new(writer.as_slice().to_vec());
+
+            match ExtMetadataBlockLevel11::parse(&mut reader) {
+                ExtMetadataBlock::Level11(parsed) => {
+                    assert_eq!(parsed.content_type, l11.content_type);
+                    assert_eq!(parsed.whitepoint, 0);
+                    assert_eq!(parsed.reference_mode_flag, l11.reference_mode_flag);
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        assert!(ContentType::from_value(5).is_none());
+    }
+}
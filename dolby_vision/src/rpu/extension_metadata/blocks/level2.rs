@@ -1,17 +1,17 @@
 use anyhow::{ensure, Result};
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
-#[cfg(feature = "serde_feature")]
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-use crate::utils::nits_to_pq;
+use crate::utils::{nits_to_pq, pq_to_pq12};
 
 use super::{ExtMetadataBlock, ExtMetadataBlockInfo, MAX_12_BIT_VALUE};
 
 /// Creative intent trim passes per target display peak brightness
 #[repr(C)]
 #[derive(Debug, Clone)]
-#[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct ExtMetadataBlockLevel2 {
     pub target_max_pq: u16,
     pub trim_slope: u16,
@@ -69,7 +69,7 @@ impl ExtMetadataBlockLevel2 {
 
     pub fn from_nits(target_nits: u16) -> ExtMetadataBlockLevel2 {
         ExtMetadataBlockLevel2 {
-            target_max_pq: (nits_to_pq(target_nits.into()) * 4095.0).round() as u16,
+            target_max_pq: pq_to_pq12(nits_to_pq(target_nits.into())),
             ..Default::default()
         }
     }
@@ -1,7 +1,7 @@
 use anyhow::Result;
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
-#[cfg(feature = "serde_feature")]
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 use super::{ExtMetadataBlock, ExtMetadataBlockInfo};
@@ -9,7 +9,7 @@ use super::{ExtMetadataBlock, ExtMetadataBlockInfo};
 /// Metadata level present in CM v4.0
 #[repr(C)]
 #[derive(Debug, Default, Clone)]
-#[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct ExtMetadataBlockLevel254 {
     pub dm_mode: u8,
     pub dm_version_index: u8,
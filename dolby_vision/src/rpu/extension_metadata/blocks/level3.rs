@@ -1,15 +1,16 @@
 use anyhow::{ensure, Result};
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
-#[cfg(feature = "serde_feature")]
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 use super::{ExtMetadataBlock, ExtMetadataBlockInfo, MAX_12_BIT_VALUE};
 
 /// Level 1 offsets.
+/// The offsets are centered at 2048, no offset.
 #[repr(C)]
-#[derive(Debug, Default, Clone)]
-#[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize), serde(default))]
 pub struct ExtMetadataBlockLevel3 {
     pub min_pq_offset: u16,
     pub max_pq_offset: u16,
@@ -44,6 +45,17 @@ impl ExtMetadataBlockLevel3 {
     }
 }
 
+impl Default for ExtMetadataBlockLevel3 {
+    /// No offsets
+    fn default() -> Self {
+        Self {
+            min_pq_offset: 2048,
+            max_pq_offset: 2048,
+            avg_pq_offset: 2048,
+        }
+    }
+}
+
 impl ExtMetadataBlockInfo for ExtMetadataBlockLevel3 {
     fn level(&self) -> u8 {
         3
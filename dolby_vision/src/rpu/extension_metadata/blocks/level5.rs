@@ -1,9 +1,11 @@
 use anyhow::{ensure, Result};
+#[cfg(feature = "std")]
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
-#[cfg(feature = "serde_feature")]
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "std")]
 use super::{ExtMetadataBlock, ExtMetadataBlockInfo};
 
 const MAX_RESOLUTION_13_BITS: u16 = 8191;
@@ -11,7 +13,7 @@ const MAX_RESOLUTION_13_BITS: u16 = 8191;
 /// Active area of the picture (letterbox, aspect ratio)
 #[repr(C)]
 #[derive(Debug, Default, Clone)]
-#[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct ExtMetadataBlockLevel5 {
     pub active_area_left_offset: u16,
     pub active_area_right_offset: u16,
@@ -20,6 +22,7 @@ pub struct ExtMetadataBlockLevel5 {
 }
 
 impl ExtMetadataBlockLevel5 {
+    #[cfg(feature = "std")]
     pub fn parse(reader: &mut BitVecReader) -> ExtMetadataBlock {
         ExtMetadataBlock::Level5(Self {
             active_area_left_offset: reader.get_n(13),
@@ -29,6 +32,7 @@ impl ExtMetadataBlockLevel5 {
         })
     }
 
+    #[cfg(feature = "std")]
     pub fn write(&self, writer: &mut BitVecWriter) -> Result<()> {
         self.validate()?;
 
@@ -58,6 +62,7 @@ impl ExtMetadataBlockLevel5 {
         )
     }
 
+    #[cfg(feature = "std")]
     pub fn get_offsets_vec(&self) -> Vec<u16> {
         vec![
             self.active_area_left_offset,
@@ -91,6 +96,7 @@ impl ExtMetadataBlockLevel5 {
     }
 }
 
+#[cfg(feature = "std")]
 impl ExtMetadataBlockInfo for ExtMetadataBlockLevel5 {
     fn level(&self) -> u8 {
         5
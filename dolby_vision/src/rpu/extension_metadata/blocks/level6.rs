@@ -1,9 +1,11 @@
 use anyhow::{ensure, Result};
+#[cfg(feature = "std")]
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
-#[cfg(feature = "serde_feature")]
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "std")]
 use super::{ExtMetadataBlock, ExtMetadataBlockInfo};
 
 /// cbindgen:ignore
@@ -12,7 +14,7 @@ pub const MAX_PQ_LUMINANCE: u16 = 10_000;
 /// ST2086/HDR10 metadata fallback
 #[repr(C)]
 #[derive(Debug, Default, Clone)]
-#[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct ExtMetadataBlockLevel6 {
     pub max_display_mastering_luminance: u16,
     pub min_display_mastering_luminance: u16,
@@ -21,6 +23,7 @@ pub struct ExtMetadataBlockLevel6 {
 }
 
 impl ExtMetadataBlockLevel6 {
+    #[cfg(feature = "std")]
     pub fn parse(reader: &mut BitVecReader) -> ExtMetadataBlock {
         ExtMetadataBlock::Level6(Self {
             max_display_mastering_luminance: reader.get_n(16),
@@ -30,6 +33,7 @@ impl ExtMetadataBlockLevel6 {
         })
     }
 
+    #[cfg(feature = "std")]
     pub fn write(&self, writer: &mut BitVecWriter) -> Result<()> {
         self.validate()?;
 
@@ -73,6 +77,7 @@ impl ExtMetadataBlockLevel6 {
     }
 }
 
+#[cfg(feature = "std")]
 impl ExtMetadataBlockInfo for ExtMetadataBlockLevel6 {
     fn level(&self) -> u8 {
         6
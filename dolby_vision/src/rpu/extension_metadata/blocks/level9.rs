@@ -1,7 +1,7 @@
 use anyhow::{ensure, Result};
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
-#[cfg(feature = "serde_feature")]
+#[cfg(feature = "serde")]
 use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
 
 use crate::rpu::extension_metadata::MasteringDisplayPrimaries;
@@ -38,26 +38,26 @@ pub const PREDEFINED_REALDEVICE_PRIMARIES: &[[f64; 8]] = &[
 ///     - 17: source_primary_{red,green,blue,white}_{x,y}
 #[repr(C)]
 #[derive(Debug, Clone)]
-#[cfg_attr(feature = "serde_feature", derive(Deserialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
 pub struct ExtMetadataBlockLevel9 {
     pub length: u64,
     pub source_primary_index: u8,
 
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub source_primary_red_x: u16,
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub source_primary_red_y: u16,
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub source_primary_green_x: u16,
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub source_primary_green_y: u16,
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub source_primary_blue_x: u16,
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub source_primary_blue_y: u16,
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub source_primary_white_x: u16,
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub source_primary_white_y: u16,
 }
 
@@ -183,7 +183,7 @@ impl Default for ExtMetadataBlockLevel9 {
     }
 }
 
-#[cfg(feature = "serde_feature")]
+#[cfg(feature = "serde")]
 impl Serialize for ExtMetadataBlockLevel9 {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -1,7 +1,7 @@
 use anyhow::{ensure, Result};
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
-#[cfg(feature = "serde_feature")]
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 pub mod level1;
@@ -20,7 +20,7 @@ pub mod reserved;
 
 pub use level1::ExtMetadataBlockLevel1;
 pub use level10::ExtMetadataBlockLevel10;
-pub use level11::ExtMetadataBlockLevel11;
+pub use level11::{ContentType, ExtMetadataBlockLevel11};
 pub use level2::ExtMetadataBlockLevel2;
 pub use level254::ExtMetadataBlockLevel254;
 pub use level255::ExtMetadataBlockLevel255;
@@ -38,7 +38,7 @@ use super::{ColorPrimaries, WithExtMetadataBlocks};
 pub const MAX_12_BIT_VALUE: u16 = 4095;
 
 #[derive(Debug, Clone)]
-#[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub enum ExtMetadataBlock {
     Level1(ExtMetadataBlockLevel1),
     Level2(ExtMetadataBlockLevel2),
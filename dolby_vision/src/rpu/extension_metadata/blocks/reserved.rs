@@ -3,20 +3,23 @@ use anyhow::{bail, Result};
 use bitvec::{order::Msb0, prelude::BitVec};
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
-#[cfg(feature = "serde_feature")]
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 use super::{ExtMetadataBlock, ExtMetadataBlockInfo};
 
 #[derive(Debug, Default, Clone)]
-#[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct ReservedExtMetadataBlock {
     pub ext_block_length: u64,
     pub ext_block_level: u8,
 
     #[cfg_attr(
-        feature = "serde_feature",
-        serde(serialize_with = "crate::utils::bitvec_ser_bits", skip_deserializing)
+        feature = "serde",
+        serde(
+            serialize_with = "crate::utils::bitvec_ser_bits",
+            deserialize_with = "crate::utils::bitvec_de_bits"
+        )
     )]
     pub data: BitVec<u8, Msb0>,
 }
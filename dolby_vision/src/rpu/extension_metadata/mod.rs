@@ -1,8 +1,8 @@
 use anyhow::{ensure, Result};
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
-#[cfg(feature = "serde_feature")]
-use serde::{Deserialize, Serialize};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize};
 
 pub mod blocks;
 pub mod cmv29;
@@ -17,13 +17,29 @@ pub use cmv40::CmV40DmData;
 use blocks::ExtMetadataBlock;
 
 #[derive(Debug, Clone)]
-#[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
-#[cfg_attr(feature = "serde_feature", serde(untagged))]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
 pub enum DmData {
     V29(CmV29DmData),
     V40(CmV40DmData),
 }
 
+/// Both versions serialize to the same fields, the version is known from the `VdrDmData` field
+#[cfg(feature = "serde")]
+impl DmData {
+    pub(crate) fn deserialize_cmv29<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> std::result::Result<Option<DmData>, D::Error> {
+        Ok(Option::<CmV29DmData>::deserialize(d)?.map(DmData::V29))
+    }
+
+    pub(crate) fn deserialize_cmv40<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> std::result::Result<Option<DmData>, D::Error> {
+        Ok(Option::<CmV40DmData>::deserialize(d)?.map(DmData::V40))
+    }
+}
+
 pub trait ExtMetadata {
     fn parse(&mut self, reader: &mut BitVecReader) -> Result<()>;
     fn write(&self, writer: &mut BitVecWriter);
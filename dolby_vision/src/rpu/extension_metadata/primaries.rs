@@ -1,6 +1,6 @@
 use std::convert::TryInto;
 
-#[cfg(feature = "serde_feature")]
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 pub const PREDEFINED_COLORSPACE_PRIMARIES: &[[f64; 8]] = &[
@@ -16,7 +16,7 @@ pub const PREDEFINED_COLORSPACE_PRIMARIES: &[[f64; 8]] = &[
 ];
 
 #[derive(Debug, Default, Clone, Copy)]
-#[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct ColorPrimaries {
     pub red_x: u16,
     pub red_y: u16,
@@ -29,7 +29,7 @@ pub struct ColorPrimaries {
 }
 
 #[derive(Debug, Clone, Copy)]
-#[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub enum MasteringDisplayPrimaries {
     DCIP3D65 = 0,
     BT709,
@@ -1,46 +1,52 @@
+#[cfg(feature = "fs")]
 use std::{
     fs::File,
     io::{BufWriter, Write},
     path::Path,
 };
 
-use anyhow::{ensure, Result};
+use anyhow::{bail, ensure, Result};
 
-#[cfg(feature = "serde_feature")]
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 use crate::rpu::dovi_rpu::DoviRpu;
+use crate::utils::pq12_to_pq;
 
 use super::{extension_metadata::blocks, vdr_dm_data::CmVersion};
 use blocks::*;
 
+#[cfg(feature = "fs")]
 const OUT_NAL_HEADER: &[u8] = &[0, 0, 0, 1];
 
+/// Preset target displays that trims can be generated for: (peak nits, L8 target display index)
+pub(crate) const TRIM_TARGET_DISPLAYS: &[(u16, u8)] = &[(100, 1), (600, 27), (1000, 48)];
+
 /// Generic generation config struct.
-#[derive(Debug)]
-#[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct GenerateConfig {
     /// Content mapping version
     /// Optional, defaults to v4.0
-    #[cfg_attr(feature = "serde_feature", serde(default = "CmVersion::v40"))]
+    #[cfg_attr(feature = "serde", serde(default = "CmVersion::v40"))]
     pub cm_version: CmVersion,
 
     /// Number of RPU frames to generate.
     /// Required only when no shots are specified.
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub length: usize,
 
     /// Mastering display min luminance, as 12 bit PQ code.
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub source_min_pq: Option<u16>,
 
     /// Mastering display max luminance, as 12 bit PQ code.
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub source_max_pq: Option<u16>,
 
     /// Active area offsets.
     /// Defaults to zero offsets, should be present in RPU
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub level5: ExtMetadataBlockLevel5,
 
     /// ST2086/HDR10 fallback metadata.
@@ -50,29 +56,91 @@ pub struct GenerateConfig {
 
     /// In the case of XML generation, the L254 metadata can vary.
     /// Not allowed to be deserialized because it's handled by the lib.
-    #[cfg_attr(feature = "serde_feature", serde(skip))]
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub level254: Option<ExtMetadataBlockLevel254>,
 
     /// List of metadata blocks to use for every RPU generated.
     ///
     /// Per-shot or per-frame metadata replaces the default
     /// metadata blocks if there are conflicts.
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub default_metadata_blocks: Vec<ExtMetadataBlock>,
 
     /// List of shots to generate.
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub shots: Vec<VideoShot>,
+
+    /// Target displays peak brightness to generate trims for, in nits.
+    /// Supported targets: 100, 600 and 1000.
+    ///
+    /// The trims are computed from the L1 metadata, as L2 blocks and also L8 blocks for CM v4.0.
+    /// Trims already present in the metadata blocks are kept.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub trim_targets: Vec<u16>,
+
+    /// Algorithm used to compute the generated trims.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub trim_algorithm: TrimAlgorithm,
+
+    /// Profile of the generated RPUs.
+    /// Optional, defaults to 8.1
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub profile: GenerateProfile,
+}
+
+/// Profile of the generated RPUs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub enum GenerateProfile {
+    /// PQ base layer
+    #[cfg_attr(feature = "serde", serde(rename = "8.1"))]
+    Profile81,
+    /// HLG base layer, reshaped to PQ.
+    /// Without L1 metadata, static L1 metadata for a 1000 nits HLG display is used.
+    #[cfg_attr(feature = "serde", serde(rename = "8.4"))]
+    Profile84,
+}
+
+/// How the trims are computed from the L1 metadata.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub enum TrimAlgorithm {
+    /// Neutral trims, the display mapping is left untouched.
+    Neutral,
+    /// Compresses the highlights above the target display peak,
+    /// and lifts the midtones of the dark shots.
+    Highlights,
+    /// Compresses the range above a knee point, relative to the target display peak,
+    /// and restores the saturation lost in the compressed highlights.
+    ///
+    /// With the L1 values and the target display peak as normalized PQ:
+    /// - `compression = clamp((max - knee * target) / (1 - knee * target), 0, 1)`
+    /// - `slope = 1 - 0.25 * compression`
+    /// - `power = 1 - 0.25 * compression * (1 - min(avg / (knee * target), 1))`
+    /// - `saturation_gain = 1 + saturation * compression`
+    Knee {
+        /// Knee point, as a fraction of the target display peak in PQ.
+        /// Optional, defaults to 0.75
+        #[cfg_attr(feature = "serde", serde(default = "TrimAlgorithm::default_knee"))]
+        knee: f64,
+        /// Saturation gain at full compression.
+        /// Optional, defaults to 0.1
+        #[cfg_attr(
+            feature = "serde",
+            serde(default = "TrimAlgorithm::default_saturation")
+        )]
+        saturation: f64,
+    },
 }
 
 /// Struct defining a video shot.
 /// A shot is a group of frames that share the same metadata.
 #[derive(Default, Debug, Clone)]
-#[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct VideoShot {
     /// Optional (unused) ID of the shot.
     /// Only XML generation provides this.
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub id: String,
 
     /// Frame start offset of the shot.
@@ -83,17 +151,17 @@ pub struct VideoShot {
     pub duration: usize,
 
     /// List of metadata blocks.
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub metadata_blocks: Vec<ExtMetadataBlock>,
 
     /// List of per-frame metadata edits.
-    #[cfg_attr(feature = "serde_feature", serde(default))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub frame_edits: Vec<ShotFrameEdit>,
 }
 
 /// Struct to represent a list of metadata edits for a specific frame.
 #[derive(Default, Debug, Clone)]
-#[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct ShotFrameEdit {
     /// Frame offset within the parent shot.
     pub edit_offset: usize,
@@ -104,7 +172,13 @@ pub struct ShotFrameEdit {
 
 impl GenerateConfig {
     pub fn generate_rpu_list(&self) -> Result<Vec<DoviRpu>> {
-        let rpu = DoviRpu::profile81_config(self)?;
+        validate_trim_targets(&self.trim_targets)?;
+        self.trim_algorithm.validate()?;
+
+        let rpu = match self.profile {
+            GenerateProfile::Profile81 => DoviRpu::profile81_config(self)?,
+            GenerateProfile::Profile84 => DoviRpu::profile84_config(self)?,
+        };
         let mut list = Vec::with_capacity(self.length);
 
         let shots_length: usize = self.shots.iter().map(|s| s.duration).sum();
@@ -117,7 +191,23 @@ impl GenerateConfig {
             )
         );
 
-        for shot in &self.shots {
+        // Shots can be listed in any order
+        let mut shots: Vec<&VideoShot> = self.shots.iter().collect();
+        shots.sort_by_key(|shot| shot.start);
+
+        // The first shot can start at an offset, e.g. from a timecode
+        let mut expected_start = shots.first().map_or(0, |shot| shot.start);
+
+        for shot in shots {
+            ensure!(
+                shot.start == expected_start,
+                format!(
+                    "Shots are not contiguous: shot starts at frame {}, expected frame {}",
+                    shot.start, expected_start
+                )
+            );
+            expected_start += shot.duration;
+
             let end = shot.duration;
 
             for i in 0..end {
@@ -141,6 +231,8 @@ impl GenerateConfig {
                             vdr_dm_data.replace_metadata_block(block.clone())?;
                         }
                     }
+
+                    vdr_dm_data.add_generated_trims(&self.trim_targets, self.trim_algorithm)?;
                 }
 
                 list.push(frame_rpu)
@@ -171,6 +263,7 @@ impl GenerateConfig {
         encoded_rpus
     }
 
+    #[cfg(feature = "fs")]
     pub fn write_rpus(&self, path: &Path) -> Result<()> {
         let mut writer =
             BufWriter::with_capacity(100_000, File::create(path).expect("Can't create file"));
@@ -209,8 +302,130 @@ impl Default for GenerateConfig {
             },
             level254: Default::default(),
             shots: Default::default(),
+            trim_targets: Default::default(),
+            trim_algorithm: Default::default(),
+            profile: Default::default(),
+        }
+    }
+}
+
+impl TrimAlgorithm {
+    pub const DEFAULT_KNEE: f64 = 0.75;
+    pub const DEFAULT_SATURATION: f64 = 0.1;
+
+    /// Returns the (slope, power) trims for the target display, from the L1 metadata
+    pub fn trims(&self, level1: &ExtMetadataBlockLevel1, target_max_pq: u16) -> (u16, u16) {
+        let max = pq12_to_pq(level1.max_pq);
+        let avg = pq12_to_pq(level1.avg_pq);
+        let target = pq12_to_pq(target_max_pq);
+
+        let (slope, power) = match *self {
+            TrimAlgorithm::Neutral => return (2048, 2048),
+            TrimAlgorithm::Highlights => {
+                // How far the highlights exceed the target display, in PQ
+                let excess = (max - target).max(0.0);
+                let midtones = (avg / target).min(1.0);
+
+                (1.0 - 0.5 * excess, 1.0 - 0.5 * excess * (1.0 - midtones))
+            }
+            TrimAlgorithm::Knee { knee, .. } => {
+                let compression = Self::knee_compression(max, target, knee);
+                let midtones = (avg / (knee * target)).min(1.0);
+
+                (
+                    1.0 - 0.25 * compression,
+                    1.0 - 0.25 * compression * (1.0 - midtones),
+                )
+            }
+        };
+
+        (to_trim(slope), to_trim(power))
+    }
+
+    /// Returns the saturation gain trim for the target display, from the L1 metadata
+    pub fn saturation_gain(&self, level1: &ExtMetadataBlockLevel1, target_max_pq: u16) -> u16 {
+        match *self {
+            TrimAlgorithm::Knee { knee, saturation } => {
+                let max = pq12_to_pq(level1.max_pq);
+                let target = pq12_to_pq(target_max_pq);
+
+                to_trim(1.0 + saturation * Self::knee_compression(max, target, knee))
+            }
+            _ => 2048,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if let TrimAlgorithm::Knee { knee, saturation } = *self {
+            ensure!(
+                knee > 0.0 && knee <= 1.0,
+                "Invalid trim knee {}, expected a value in (0, 1]",
+                knee
+            );
+            ensure!(
+                (0.0..=1.0).contains(&saturation),
+                "Invalid trim saturation {}, expected a value in [0, 1]",
+                saturation
+            );
+        }
+
+        Ok(())
+    }
+
+    /// How much of the range above the knee is used by the shot
+    fn knee_compression(max: f64, target: f64, knee: f64) -> f64 {
+        let knee_pq = knee * target;
+
+        if knee_pq >= 1.0 {
+            return 0.0;
+        }
+
+        ((max - knee_pq) / (1.0 - knee_pq)).clamp(0.0, 1.0)
+    }
+
+    #[cfg(feature = "serde")]
+    fn default_knee() -> f64 {
+        Self::DEFAULT_KNEE
+    }
+
+    #[cfg(feature = "serde")]
+    fn default_saturation() -> f64 {
+        Self::DEFAULT_SATURATION
+    }
+}
+
+/// Trims are offset by 0.5, neutral at 2048
+fn to_trim(v: f64) -> u16 {
+    ((v - 0.5) * 4096.0).round().clamp(0.0, 4095.0) as u16
+}
+
+/// Checks that trims can be generated for the target displays
+pub fn validate_trim_targets(trim_targets: &[u16]) -> Result<()> {
+    for target_nits in trim_targets {
+        if !TRIM_TARGET_DISPLAYS
+            .iter()
+            .any(|(nits, _)| nits == target_nits)
+        {
+            bail!(
+                "Unsupported trim target {} nits, expected one of 100, 600, 1000",
+                target_nits
+            );
         }
     }
+
+    Ok(())
+}
+
+impl Default for TrimAlgorithm {
+    fn default() -> Self {
+        TrimAlgorithm::Highlights
+    }
+}
+
+impl Default for GenerateProfile {
+    fn default() -> Self {
+        GenerateProfile::Profile81
+    }
 }
 
 impl VideoShot {
@@ -287,10 +502,12 @@ mod tests {
     use std::path::PathBuf;
 
     use crate::{
-        rpu::{extension_metadata::blocks::ExtMetadataBlock, vdr_dm_data::CmVersion},
+        rpu::{extension_metadata::blocks::*, vdr_dm_data::CmVersion},
         xml::{CmXmlParser, XmlParserOpts},
     };
 
+    use super::{DoviRpu, GenerateConfig, GenerateProfile, TrimAlgorithm, VideoShot};
+
     #[test]
     fn config_with_frame_edits() -> Result<()> {
         let lib_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -565,4 +782,234 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn generated_trims() -> Result<()> {
+        let config = GenerateConfig {
+            length: 2,
+            trim_targets: vec![100, 600, 1000],
+            shots: vec![
+                VideoShot {
+                    start: 0,
+                    duration: 1,
+                    metadata_blocks: vec![
+                        ExtMetadataBlock::Level1(ExtMetadataBlockLevel1::from_stats(0, 3079, 1000)),
+                        ExtMetadataBlock::Level2(ExtMetadataBlockLevel2 {
+                            trim_slope: 1800,
+                            ..ExtMetadataBlockLevel2::from_nits(600)
+                        }),
+                    ],
+                    ..Default::default()
+                },
+                VideoShot {
+                    start: 1,
+                    duration: 1,
+                    metadata_blocks: vec![ExtMetadataBlock::Level1(
+                        ExtMetadataBlockLevel1::from_stats(0, 2081, 1000),
+                    )],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let rpus = config.generate_rpu_list()?;
+        let level2_trims = |frame: usize| -> Vec<(u16, u16, u16)> {
+            rpus[frame]
+                .vdr_dm_data
+                .as_ref()
+                .unwrap()
+                .level_blocks_iter(2)
+                .filter_map(|b| match b {
+                    ExtMetadataBlock::Level2(b) => {
+                        Some((b.target_max_pq, b.trim_slope, b.trim_power))
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // 1000 nits shot, the 600 nits trim from the shot is kept
+        assert_eq!(
+            level2_trims(0),
+            vec![(2081, 1549, 1789), (2851, 1800, 2048), (3079, 2048, 2048)]
+        );
+
+        // 100 nits shot, no highlights to compress
+        assert_eq!(
+            level2_trims(1),
+            vec![(2081, 2048, 2048), (2851, 2048, 2048), (3079, 2048, 2048)]
+        );
+
+        let shot1_l8: Vec<(u8, u16, u16)> = rpus[0]
+            .vdr_dm_data
+            .as_ref()
+            .unwrap()
+            .level_blocks_iter(8)
+            .filter_map(|b| match b {
+                ExtMetadataBlock::Level8(b) => {
+                    Some((b.target_display_index, b.trim_slope, b.trim_power))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            shot1_l8,
+            vec![(1, 1549, 1789), (27, 1934, 1974), (48, 2048, 2048)]
+        );
+
+        // Neutral trims
+        let config = GenerateConfig {
+            trim_algorithm: TrimAlgorithm::Neutral,
+            ..config
+        };
+        let rpus = config.generate_rpu_list()?;
+        assert!(rpus[0]
+            .vdr_dm_data
+            .as_ref()
+            .unwrap()
+            .level_blocks_iter(8)
+            .all(|b| matches!(b, ExtMetadataBlock::Level8(b) if b.trim_slope == 2048 && b.trim_power == 2048)));
+
+        // Unsupported target
+        let config = GenerateConfig {
+            trim_targets: vec![400],
+            ..config
+        };
+        assert!(config.generate_rpu_list().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn generated_knee_trims() -> Result<()> {
+        let config = GenerateConfig {
+            length: 2,
+            trim_targets: vec![100, 600],
+            trim_algorithm: TrimAlgorithm::Knee {
+                knee: TrimAlgorithm::DEFAULT_KNEE,
+                saturation: TrimAlgorithm::DEFAULT_SATURATION,
+            },
+            shots: vec![
+                VideoShot {
+                    start: 0,
+                    duration: 1,
+                    metadata_blocks: vec![ExtMetadataBlock::Level1(
+                        ExtMetadataBlockLevel1::from_stats(0, 3079, 1000),
+                    )],
+                    ..Default::default()
+                },
+                VideoShot {
+                    start: 1,
+                    duration: 1,
+                    metadata_blocks: vec![ExtMetadataBlock::Level1(
+                        ExtMetadataBlockLevel1::from_stats(0, 2081, 600),
+                    )],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let rpus = config.generate_rpu_list()?;
+        let level2_trims = |frame: usize| -> Vec<(u16, u16, u16, u16)> {
+            rpus[frame]
+                .vdr_dm_data
+                .as_ref()
+                .unwrap()
+                .level_blocks_iter(2)
+                .filter_map(|b| match b {
+                    ExtMetadataBlock::Level2(b) => Some((
+                        b.target_max_pq,
+                        b.trim_slope,
+                        b.trim_power,
+                        b.trim_saturation_gain,
+                    )),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // 1000 nits shot, compressed above the knee with more saturation
+        assert_eq!(
+            level2_trims(0),
+            vec![(2081, 1435, 1828, 2293), (2851, 1556, 1786, 2245)]
+        );
+
+        // 100 nits shot, only above the knee of the 100 nits target
+        assert_eq!(
+            level2_trims(1),
+            vec![(2081, 1838, 1948, 2132), (2851, 2048, 2048, 2048)]
+        );
+
+        let level8_saturation: Vec<(u8, u16)> = rpus[0]
+            .vdr_dm_data
+            .as_ref()
+            .unwrap()
+            .level_blocks_iter(8)
+            .filter_map(|b| match b {
+                ExtMetadataBlock::Level8(b) => {
+                    Some((b.target_display_index, b.trim_saturation_gain))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(level8_saturation, vec![(1, 2293), (27, 2245)]);
+
+        // Knee out of range
+        let config = GenerateConfig {
+            trim_algorithm: TrimAlgorithm::Knee {
+                knee: 1.5,
+                saturation: 0.1,
+            },
+            ..config
+        };
+        assert!(config.generate_rpu_list().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn profile84_static_l1() -> Result<()> {
+        let config = GenerateConfig {
+            length: 2,
+            profile: GenerateProfile::Profile84,
+            shots: vec![VideoShot {
+                start: 0,
+                duration: 2,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let rpus = config.generate_rpu_list()?;
+        assert_eq!(rpus.len(), 2);
+
+        let rpu = &rpus[1];
+        assert_eq!(rpu.dovi_profile, 8);
+        assert_eq!(rpu.header.vdr_rpu_profile, 1);
+        assert!(!rpu.header.bl_video_full_range_flag);
+        assert_eq!(rpu.header.coefficient_log2_denom, 23);
+
+        let vdr_dm_data = rpu.vdr_dm_data.as_ref().unwrap();
+        assert_eq!(vdr_dm_data.source_max_pq, 3079);
+
+        if let ExtMetadataBlock::Level1(level1) = vdr_dm_data.get_block(1).unwrap() {
+            assert_eq!(level1.min_pq, 0);
+            assert_eq!(level1.max_pq, 3079);
+            assert_eq!(level1.avg_pq, 2378);
+        } else {
+            panic!("No L1 metadata");
+        }
+
+        // Reparses as the same RPU
+        let data = rpu.write_hevc_unspec62_nalu()?;
+        let reparsed = DoviRpu::parse_unspec62_nalu(&data)?;
+        assert_eq!(
+            reparsed.header.pred_pivot_value,
+            rpu.header.pred_pivot_value
+        );
+
+        Ok(())
+    }
 }
@@ -1,19 +1,75 @@
+#[cfg(feature = "std")]
+pub mod builder;
+#[cfg(feature = "std")]
 pub mod dovi_rpu;
+#[cfg(feature = "std")]
 pub mod extension_metadata;
+#[cfg(feature = "std")]
 pub mod generate;
+#[cfg(feature = "std")]
+pub mod processor;
+#[cfg(feature = "std")]
 pub mod profiles;
+#[cfg(feature = "std")]
+pub mod reshaping;
+#[cfg(feature = "std")]
 pub mod rpu_data_header;
+#[cfg(feature = "std")]
 pub mod rpu_data_mapping;
+#[cfg(feature = "std")]
 pub mod rpu_data_nlq;
+#[cfg(feature = "std")]
+pub mod spec_check;
+#[cfg(feature = "std")]
 pub mod vdr_dm_data;
+pub mod view;
 
+/// Only the blocks read by `RpuView` without `std`
+#[cfg(not(feature = "std"))]
+pub mod extension_metadata {
+    pub mod blocks {
+        pub mod level1;
+        pub mod level5;
+        pub mod level6;
+
+        pub use level1::ExtMetadataBlockLevel1;
+        pub use level5::ExtMetadataBlockLevel5;
+        pub use level6::ExtMetadataBlockLevel6;
+    }
+}
+
+use anyhow::{bail, Result};
+#[cfg(feature = "std")]
 use crc::{Crc, CRC_32_MPEG_2};
 
 pub const NUM_COMPONENTS: usize = 3;
 
+/// Bits of the uncompressed `vdr_dm_data` fields, after the metadata IDs
+pub(crate) const VDR_DM_DATA_FIXED_BITS: usize = 509;
+
 pub const FEL_STR: &str = "FEL";
 pub const MEL_STR: &str = "MEL";
 
+/// The RPU data starting at the `rpu_nal_prefix`, without start code or NAL header
+pub(crate) fn validated_trimmed_data(data: &[u8]) -> Result<&[u8]> {
+    if data.len() < 25 {
+        bail!("Invalid RPU length: {}", &data.len());
+    }
+
+    // Including 0x7C01 prepended
+    let trimmed_data = match &data[..5] {
+        [0, 0, 0, 1, 25] => &data[4..],
+        [0, 0, 1, 25, 8] => &data[3..],
+        [0, 1, 25, 8, 9] | [124, 1, 25, 8, 9] => &data[2..],
+        [1, 25, 8, 9, _] => &data[1..],
+        [25, 8, 9, _, _] => data,
+        _ => bail!("Invalid RPU data start bytes\n{:?}", &data[..5]),
+    };
+
+    Ok(trimmed_data)
+}
+
+#[cfg(feature = "std")]
 #[inline(always)]
 fn compute_crc32(data: &[u8]) -> u32 {
     let crc = Crc::<u32>::new(&CRC_32_MPEG_2);
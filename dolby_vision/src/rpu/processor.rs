@@ -0,0 +1,167 @@
+use anyhow::{Context, Result};
+
+use super::dovi_rpu::DoviRpu;
+
+/// Custom per-frame transformation of the RPUs, run while converting them.
+///
+/// Closures taking a `&mut DoviRpu` implement the trait, and `RpuProcessorChain`
+/// runs several processors in order.
+pub trait RpuProcessor {
+    fn process_rpu(&mut self, rpu: &mut DoviRpu) -> Result<()>;
+}
+
+impl<F> RpuProcessor for F
+where
+    F: FnMut(&mut DoviRpu) -> Result<()>,
+{
+    fn process_rpu(&mut self, rpu: &mut DoviRpu) -> Result<()> {
+        self(rpu)
+    }
+}
+
+/// Processors run one after the other on every RPU.
+///
+/// The processed RPUs are marked as modified, so the CRC32 is computed when writing them.
+#[derive(Default)]
+pub struct RpuProcessorChain {
+    processors: Vec<Box<dyn RpuProcessor + Send>>,
+}
+
+impl RpuProcessorChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with<P: RpuProcessor + Send + 'static>(mut self, processor: P) -> Self {
+        self.push(processor);
+        self
+    }
+
+    pub fn push<P: RpuProcessor + Send + 'static>(&mut self, processor: P) {
+        self.processors.push(Box::new(processor));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.processors.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.processors.len()
+    }
+}
+
+impl RpuProcessor for RpuProcessorChain {
+    fn process_rpu(&mut self, rpu: &mut DoviRpu) -> Result<()> {
+        for processor in &mut self.processors {
+            processor.process_rpu(rpu)?;
+            rpu.modified = true;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for RpuProcessorChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RpuProcessorChain")
+            .field("processors", &self.processors.len())
+            .finish()
+    }
+}
+
+/// Runs the processor on a list of RPUs in order, marking them as modified
+pub fn process_rpus<P: RpuProcessor + ?Sized>(
+    rpus: &mut [DoviRpu],
+    processor: &mut P,
+) -> Result<()> {
+    for (frame, rpu) in rpus.iter_mut().enumerate() {
+        processor
+            .process_rpu(rpu)
+            .with_context(|| format!("Processing RPU of frame {}", frame))?;
+
+        rpu.modified = true;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{bail, Result};
+
+    use crate::rpu::{builder::RpuBuilder, dovi_rpu::DoviRpu};
+
+    use super::{process_rpus, RpuProcessor, RpuProcessorChain};
+
+    fn parsed_rpu() -> Result<DoviRpu> {
+        let rpu = RpuBuilder::new().source_levels(62, 3696).build()?;
+        DoviRpu::parse_unspec62_nalu(&rpu.write_hevc_unspec62_nalu()?)
+    }
+
+    #[test]
+    fn chain_in_order() -> Result<()> {
+        let mut rpu = parsed_rpu()?;
+        assert!(!rpu.modified);
+
+        let mut chain = RpuProcessorChain::new()
+            .with(|rpu: &mut DoviRpu| {
+                rpu.vdr_dm_data.as_mut().unwrap().source_max_pq = 3079;
+                Ok(())
+            })
+            .with(|rpu: &mut DoviRpu| {
+                let vdr_dm_data = rpu.vdr_dm_data.as_mut().unwrap();
+                vdr_dm_data.source_min_pq = vdr_dm_data.source_max_pq / 100;
+                Ok(())
+            });
+        assert_eq!(chain.len(), 2);
+
+        chain.process_rpu(&mut rpu)?;
+        assert!(rpu.modified);
+
+        // The CRC32 is computed for the edited RPU
+        let parsed = DoviRpu::parse_unspec62_nalu(&rpu.write_hevc_unspec62_nalu()?)?;
+        let vdr_dm_data = parsed.vdr_dm_data.as_ref().unwrap();
+        assert_eq!(vdr_dm_data.source_max_pq, 3079);
+        assert_eq!(vdr_dm_data.source_min_pq, 30);
+
+        Ok(())
+    }
+
+    #[test]
+    fn stateful_processor() -> Result<()> {
+        struct FrameCounter(u16);
+
+        impl RpuProcessor for FrameCounter {
+            fn process_rpu(&mut self, rpu: &mut DoviRpu) -> Result<()> {
+                rpu.vdr_dm_data.as_mut().unwrap().source_min_pq = self.0;
+                self.0 += 1;
+
+                if self.0 > 2 {
+                    bail!("Too many frames");
+                }
+
+                Ok(())
+            }
+        }
+
+        let mut rpus = vec![parsed_rpu()?, parsed_rpu()?];
+        process_rpus(&mut rpus, &mut FrameCounter(0))?;
+
+        for (frame, rpu) in rpus.iter().enumerate() {
+            assert!(rpu.modified);
+            assert_eq!(
+                rpu.vdr_dm_data.as_ref().unwrap().source_min_pq,
+                frame as u16
+            );
+        }
+
+        rpus.push(parsed_rpu()?);
+        let res = process_rpus(&mut rpus, &mut FrameCounter(0));
+        assert_eq!(
+            format!("{:#}", res.unwrap_err()),
+            "Processing RPU of frame 2: Too many frames"
+        );
+
+        Ok(())
+    }
+}
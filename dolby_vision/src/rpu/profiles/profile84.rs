@@ -0,0 +1,194 @@
+use std::cmp::Ordering;
+
+use crate::rpu::extension_metadata::blocks::ExtMetadataBlockLevel1;
+use crate::rpu::rpu_data_mapping::RpuDataMapping;
+use crate::utils::{nits_to_pq, pq_to_pq12};
+
+use super::profile81::Profile81;
+use super::{DoviProfile, VdrDmData};
+
+/// Profile 8.4: HLG base layer, reshaped to PQ by the RPU mapping
+pub struct Profile84 {}
+
+/// Luma pivots in 10 bit BL codewords, limited range.
+/// Denser near black where the PQ curve is steepest, with a pivot at HLG peak white.
+const LUMA_PIVOTS: [u64; 9] = [64, 80, 160, 320, 480, 640, 800, 940, 1023];
+
+/// Nominal peak luminance of the HLG reference display
+const HLG_PEAK_NITS: f64 = 1000.0;
+const HLG_SYSTEM_GAMMA: f64 = 1.2;
+
+/// BT.2408 HDR reference white
+const HLG_REFERENCE_WHITE_NITS: f64 = 203.0;
+
+pub const COEFFICIENT_LOG2_DENOM: u64 = 23;
+
+impl DoviProfile for Profile84 {
+    fn dm_data() -> VdrDmData {
+        // The reshaped signal is PQ, same as profile 8.1
+        Profile81::dm_data()
+    }
+}
+
+impl Profile84 {
+    /// Luma pivots, as coded in the RPU header: the first pivot, then the differences
+    pub fn luma_pred_pivot_value() -> Vec<u64> {
+        let mut pivots = vec![LUMA_PIVOTS[0]];
+        pivots.extend(LUMA_PIVOTS.windows(2).map(|w| w[1] - w[0]));
+
+        pivots
+    }
+
+    /// Luma HLG to PQ second order polynomial mapping, for each pivot.
+    /// Chroma is mapped with identity.
+    pub fn rpu_data_mapping() -> RpuDataMapping {
+        let mut mapping = RpuDataMapping::p8_default();
+
+        let segments = LUMA_PIVOTS.len() - 1;
+
+        mapping.mapping_idc[0] = vec![0; segments];
+        mapping.mapping_param_pred_flag[0] = vec![false; segments];
+        mapping.num_mapping_param_predictors[0] = vec![0; segments];
+        mapping.diff_pred_part_idx_mapping_minus1[0] = vec![0; segments];
+        mapping.poly_order_minus1[0] = vec![1; segments];
+        mapping.linear_interp_flag[0] = vec![false; segments];
+        mapping.pred_linear_interp_value_int[0] = vec![0; segments];
+        mapping.pred_linear_interp_value[0] = vec![0; segments];
+        mapping.mmr_order_minus1[0] = vec![0; segments];
+        mapping.mmr_constant_int[0] = vec![0; segments];
+        mapping.mmr_constant[0] = vec![0; segments];
+        mapping.mmr_coef_int[0] = vec![vec![]; segments];
+        mapping.mmr_coef[0] = vec![vec![]; segments];
+
+        let (coef_int, coef): (Vec<_>, Vec<_>) = LUMA_PIVOTS
+            .windows(2)
+            .map(|w| {
+                let coefs = fit_quadratic(w[0], w[1]);
+                coefs.iter().map(|c| encode_coefficient(*c)).unzip()
+            })
+            .unzip();
+
+        mapping.poly_coef_int[0] = coef_int;
+        mapping.poly_coef[0] = coef;
+
+        mapping
+    }
+
+    /// Source peak of the reshaped signal, as 12 bit PQ code
+    pub fn source_max_pq() -> u16 {
+        pq_to_pq12(nits_to_pq(HLG_PEAK_NITS))
+    }
+
+    /// Static L1 metadata for HLG content, which has no measurements:
+    /// the full range of the HLG display, averaging at reference white.
+    pub fn static_level1() -> ExtMetadataBlockLevel1 {
+        let avg_pq = pq_to_pq12(nits_to_pq(HLG_REFERENCE_WHITE_NITS));
+
+        ExtMetadataBlockLevel1::from_stats(0, Self::source_max_pq(), avg_pq)
+    }
+
+    /// Maps a normalized 10 bit HLG luma codeword to a normalized PQ codeword
+    pub fn hlg_to_pq(x: f64) -> f64 {
+        let codeword = x * 1024.0;
+        let hlg = ((codeword - 64.0) / 876.0).clamp(0.0, 1.0);
+
+        // Display luminance from the HLG OOTF, with zero black level
+        let scene_linear = hlg_inverse_oetf(hlg);
+        let nits = HLG_PEAK_NITS * scene_linear.powf(HLG_SYSTEM_GAMMA);
+
+        let pq = nits_to_pq(nits);
+
+        (64.0 + pq * 876.0) / 1024.0
+    }
+}
+
+/// BT.2100 HLG inverse OETF, normalized to [0, 1]
+fn hlg_inverse_oetf(e: f64) -> f64 {
+    const A: f64 = 0.17883277;
+    const B: f64 = 0.28466892;
+    const C: f64 = 0.55991073;
+
+    if e <= 0.5 {
+        e * e / 3.0
+    } else {
+        (((e - C) / A).exp() + B) / 12.0
+    }
+}
+
+/// Least squares fit of the HLG to PQ curve over the codewords of a segment.
+/// Returns the coefficients in increasing order.
+fn fit_quadratic(start: u64, end: u64) -> [f64; 3] {
+    // Normal equations sums
+    let mut s = [0.0; 5];
+    let mut t = [0.0; 3];
+
+    for codeword in start..=end {
+        let x = codeword as f64 / 1024.0;
+        let y = Profile84::hlg_to_pq(x);
+
+        let mut xp = 1.0;
+        for (i, sum) in s.iter_mut().enumerate() {
+            *sum += xp;
+
+            if i < 3 {
+                t[i] += xp * y;
+            }
+
+            xp *= x;
+        }
+    }
+
+    let mut m = [
+        [s[0], s[1], s[2], t[0]],
+        [s[1], s[2], s[3], t[1]],
+        [s[2], s[3], s[4], t[2]],
+    ];
+
+    // Gaussian elimination with partial pivoting
+    for col in 0..3 {
+        let pivot_row = (col..3)
+            .max_by(|a, b| {
+                m[*a][col]
+                    .abs()
+                    .partial_cmp(&m[*b][col].abs())
+                    .unwrap_or(Ordering::Equal)
+            })
+            .unwrap_or(col);
+        m.swap(col, pivot_row);
+
+        let pivot = m[col];
+
+        for row in m.iter_mut().skip(col + 1) {
+            let factor = row[col] / pivot[col];
+
+            row.iter_mut()
+                .zip(pivot.iter())
+                .skip(col)
+                .for_each(|(v, p)| *v -= factor * p);
+        }
+    }
+
+    let mut coefs = [0.0; 3];
+
+    for row in (0..3).rev() {
+        let sum: f64 = (row + 1..3).map(|k| m[row][k] * coefs[k]).sum();
+        coefs[row] = (m[row][3] - sum) / m[row][row];
+    }
+
+    coefs
+}
+
+/// Splits a coefficient into its integer part and fractional part with the coefficient denominator
+fn encode_coefficient(value: f64) -> (i64, u64) {
+    let denom = (1_u64 << COEFFICIENT_LOG2_DENOM) as f64;
+
+    let mut int = value.floor() as i64;
+    let mut frac = ((value - value.floor()) * denom).round() as u64;
+
+    if frac as f64 >= denom {
+        int += 1;
+        frac = 0;
+    }
+
+    (int, frac)
+}
@@ -0,0 +1,322 @@
+use anyhow::{bail, ensure, Result};
+
+use super::dovi_rpu::DoviRpu;
+use super::rpu_data_header::RpuDataHeader;
+use super::rpu_data_mapping::RpuDataMapping;
+
+use super::NUM_COMPONENTS;
+
+/// MMR terms of the three input components: `c0, c1, c2, c0*c1, c0*c2, c1*c2, c0*c1*c2`
+const MMR_TERMS: usize = 7;
+
+/// Evaluates the RPU mapping (reshaping) of a base layer signal to the VDR signal.
+///
+/// The values are normalized to [0, 1], as the code values divided by `2^bit_depth`.
+/// The NLQ residual of the enhancement layer is not applied.
+#[derive(Debug, Clone)]
+pub struct Reshaper {
+    components: [ComponentMapping; NUM_COMPONENTS],
+}
+
+#[derive(Debug, Clone, Default)]
+struct ComponentMapping {
+    /// Normalized pivot values, one more than the pieces
+    pivots: Vec<f64>,
+    pieces: Vec<MappingPiece>,
+}
+
+#[derive(Debug, Clone)]
+enum MappingPiece {
+    Polynomial(Vec<f64>),
+    Mmr {
+        constant: f64,
+        /// Coefficients of the terms raised to the power of the order, starting at order 1
+        coefs: Vec<[f64; MMR_TERMS]>,
+    },
+}
+
+impl DoviRpu {
+    /// Builds the evaluator of the RPU mapping, from the mapping coefficients and header pivots
+    pub fn reshaper(&self) -> Result<Reshaper> {
+        ensure!(
+            !self.header.use_prev_vdr_rpu_flag,
+            "Cannot evaluate the mapping of an RPU referencing a previous RPU"
+        );
+
+        match &self.rpu_data_mapping {
+            Some(mapping) => Reshaper::new(&self.header, mapping),
+            None => bail!("Cannot evaluate the mapping: no mapping data in the RPU"),
+        }
+    }
+}
+
+impl Reshaper {
+    pub fn new(header: &RpuDataHeader, mapping: &RpuDataMapping) -> Result<Self> {
+        let coef = |int: i64, frac: u64| -> Result<f64> {
+            match header.coefficient_data_type {
+                0 => Ok(int as f64 + frac as f64 / (1_u64 << header.coefficient_log2_denom) as f64),
+                1 => Ok(f32::from_bits(frac as u32) as f64),
+                v => bail!("Invalid coefficient_data_type value: {}", v),
+            }
+        };
+
+        let bl_scale = (1_u64 << (header.bl_bit_depth_minus8 + 8)) as f64;
+        let mut components: [ComponentMapping; NUM_COMPONENTS] = Default::default();
+
+        for (cmp, component) in components.iter_mut().enumerate() {
+            let pieces_count = header.num_pivots_minus_2[cmp] as usize + 1;
+
+            ensure!(
+                header.pred_pivot_value[cmp].len() == pieces_count + 1,
+                "Component {}: expected {} pivots, found {}",
+                cmp,
+                pieces_count + 1,
+                header.pred_pivot_value[cmp].len()
+            );
+
+            // The pivots are coded as differences
+            let mut pivot = 0;
+            component.pivots = header.pred_pivot_value[cmp]
+                .iter()
+                .map(|delta| {
+                    pivot += delta;
+                    pivot as f64 / bl_scale
+                })
+                .collect();
+
+            for piece in 0..pieces_count {
+                ensure!(
+                    !mapping.mapping_param_pred_flag[cmp][piece],
+                    "Component {}, piece {}: predicted mapping parameters are not supported",
+                    cmp,
+                    piece
+                );
+
+                let mapping_piece = match mapping.mapping_idc[cmp][piece] {
+                    // MAPPING_POLYNOMIAL
+                    0 => {
+                        let linear_interp = mapping.poly_order_minus1[cmp][piece] == 0
+                            && mapping.linear_interp_flag[cmp]
+                                .get(piece)
+                                .copied()
+                                .unwrap_or(false);
+
+                        ensure!(
+                            !linear_interp,
+                            "Component {}, piece {}: linear interpolation mapping is not supported",
+                            cmp,
+                            piece
+                        );
+
+                        let coefs = mapping.poly_coef_int[cmp][piece]
+                            .iter()
+                            .zip(&mapping.poly_coef[cmp][piece])
+                            .map(|(int, frac)| coef(*int, *frac))
+                            .collect::<Result<_>>()?;
+
+                        MappingPiece::Polynomial(coefs)
+                    }
+                    // MAPPING_MMR
+                    1 => {
+                        let order = mapping.mmr_order_minus1[cmp][piece] as usize + 1;
+                        let mut coefs = Vec::with_capacity(order);
+
+                        // The coefficients start at index 1
+                        for i in 1..=order {
+                            let mut order_coefs = [0.0; MMR_TERMS];
+
+                            for (j, value) in order_coefs.iter_mut().enumerate() {
+                                *value = coef(
+                                    mapping.mmr_coef_int[cmp][piece][i][j],
+                                    mapping.mmr_coef[cmp][piece][i][j],
+                                )?;
+                            }
+
+                            coefs.push(order_coefs);
+                        }
+
+                        MappingPiece::Mmr {
+                            constant: coef(
+                                mapping.mmr_constant_int[cmp][piece],
+                                mapping.mmr_constant[cmp][piece],
+                            )?,
+                            coefs,
+                        }
+                    }
+                    v => bail!("Component {}: invalid mapping_idc value: {}", cmp, v),
+                };
+
+                component.pieces.push(mapping_piece);
+            }
+        }
+
+        Ok(Self { components })
+    }
+
+    /// Maps normalized base layer components to the normalized VDR signal
+    pub fn reshape(&self, bl: [f64; NUM_COMPONENTS]) -> [f64; NUM_COMPONENTS] {
+        let input = bl.map(|v| v.clamp(0.0, 1.0));
+
+        let mut vdr = [0.0; NUM_COMPONENTS];
+
+        for (cmp, component) in self.components.iter().enumerate() {
+            let pivots = &component.pivots;
+            let last_pivot = pivots[pivots.len() - 1];
+
+            let x = input[cmp].clamp(pivots[0], last_pivot);
+
+            // Last piece starting at or before the value
+            let piece = pivots[1..pivots.len() - 1]
+                .iter()
+                .take_while(|pivot| x >= **pivot)
+                .count();
+
+            let value = match &component.pieces[piece] {
+                MappingPiece::Polynomial(coefs) => {
+                    coefs.iter().rev().fold(0.0, |acc, coef| acc * x + coef)
+                }
+                MappingPiece::Mmr { constant, coefs } => {
+                    let [c0, c1, c2] = input;
+                    let terms = [c0, c1, c2, c0 * c1, c0 * c2, c1 * c2, c0 * c1 * c2];
+
+                    let mut value = *constant;
+                    let mut powers = terms;
+
+                    for order_coefs in coefs {
+                        value += order_coefs
+                            .iter()
+                            .zip(&powers)
+                            .map(|(coef, term)| coef * term)
+                            .sum::<f64>();
+
+                        powers.iter_mut().zip(&terms).for_each(|(p, t)| *p *= t);
+                    }
+
+                    value
+                }
+            };
+
+            vdr[cmp] = value.clamp(0.0, 1.0);
+        }
+
+        vdr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::rpu::{
+        dovi_rpu::DoviRpu, rpu_data_header::RpuDataHeader, rpu_data_mapping::RpuDataMapping,
+    };
+
+    use super::Reshaper;
+
+    #[test]
+    fn p8_identity() -> Result<()> {
+        let header = RpuDataHeader::p8_default();
+        let reshaper = Reshaper::new(&header, &RpuDataMapping::p8_default())?;
+
+        for value in [0.1, 0.25, 0.5, 0.75, 0.9] {
+            let vdr = reshaper.reshape([value, 1.0 - value, value / 2.0]);
+            assert_eq!(vdr, [value, 1.0 - value, value / 2.0]);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn polynomial_pieces() -> Result<()> {
+        let mut header = RpuDataHeader::p8_default();
+        let mut mapping = RpuDataMapping::p8_default();
+
+        // Two pieces for luma: 0.25 + 0.5x, then 1 - x
+        header.num_pivots_minus_2[0] = 1;
+        header.pred_pivot_value[0] = vec![0, 512, 511];
+
+        mapping.mapping_idc[0] = vec![0, 0];
+        mapping.mapping_param_pred_flag[0] = vec![false, false];
+        mapping.poly_order_minus1[0] = vec![0, 0];
+        mapping.linear_interp_flag[0] = vec![false, false];
+        mapping.poly_coef_int[0] = vec![vec![0, 0], vec![1, -1]];
+        mapping.poly_coef[0] = vec![vec![1 << 21, 1 << 22], vec![0, 0]];
+
+        let reshaper = Reshaper::new(&header, &mapping)?;
+
+        assert_eq!(reshaper.reshape([0.5, 0.5, 0.5])[0], 0.5);
+        assert_eq!(reshaper.reshape([0.0, 0.5, 0.5])[0], 0.25);
+        assert_eq!(reshaper.reshape([0.75, 0.5, 0.5])[0], 0.25);
+
+        // Below the first pivot, clamped
+        header.pred_pivot_value[0] = vec![64, 448, 511];
+        let reshaper = Reshaper::new(&header, &mapping)?;
+        assert_eq!(
+            reshaper.reshape([0.0, 0.5, 0.5])[0],
+            0.25 + 0.5 * 64.0 / 1024.0
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn mmr_chroma() -> Result<()> {
+        let header = RpuDataHeader::p8_default();
+        let mut mapping = RpuDataMapping::p8_default();
+
+        // 0.1 + 0.5 * c0 * c1 + 0.25 * c2^2
+        mapping.mapping_idc[1] = vec![1];
+        mapping.mmr_order_minus1[1] = vec![1];
+        mapping.mmr_constant_int[1] = vec![0];
+        mapping.mmr_constant[1] = vec![(0.1 * (1 << 23) as f64).round() as u64];
+
+        let mut mmr_coef = vec![vec![0; 7]; 3];
+        mmr_coef[1][3] = 1 << 22;
+        mmr_coef[2][2] = 1 << 21;
+
+        mapping.mmr_coef_int[1] = vec![vec![vec![0; 7]; 3]];
+        mapping.mmr_coef[1] = vec![mmr_coef];
+
+        let reshaper = Reshaper::new(&header, &mapping)?;
+        let vdr = reshaper.reshape([0.5, 0.4, 0.8]);
+
+        assert_eq!(vdr[0], 0.5);
+        assert!((vdr[1] - (0.1 + 0.5 * 0.2 + 0.25 * 0.64)).abs() < 1e-6);
+        assert_eq!(vdr[2], 0.8);
+
+        Ok(())
+    }
+
+    #[test]
+    fn removed_mapping_identity() -> Result<()> {
+        let mut header = RpuDataHeader::p8_default();
+        let mut mapping = RpuDataMapping::p8_default();
+
+        // 12 bit base layer, floating point coefficients: 0.25 + 0.5x
+        header.bl_bit_depth_minus8 = 4;
+        header.pred_pivot_value = [vec![0, 4095], vec![0, 4095], vec![0, 4095]];
+        header.coefficient_data_type = 1;
+        mapping.poly_coef[0] = vec![vec![0.25_f32.to_bits() as u64, 0.5_f32.to_bits() as u64]];
+
+        let mut rpu = DoviRpu {
+            dovi_profile: 8,
+            header,
+            rpu_data_mapping: Some(mapping),
+            ..Default::default()
+        };
+
+        assert!((rpu.reshaper()?.reshape([0.8, 0.5, 0.5])[0] - 0.65).abs() < 1e-9);
+
+        rpu.remove_mapping();
+        assert_eq!(rpu.header.pred_pivot_value[0], vec![0, 4095]);
+
+        let reshaper = rpu.reshaper()?;
+
+        for value in [0.1, 0.25, 0.5, 0.75, 0.9] {
+            let vdr = reshaper.reshape([value, 1.0 - value, value / 2.0]);
+            assert_eq!(vdr, [value, 1.0 - value, value / 2.0]);
+        }
+
+        Ok(())
+    }
+}
@@ -1,15 +1,15 @@
 use anyhow::{ensure, Result};
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
-#[cfg(feature = "serde_feature")]
-use serde::Serialize;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 use super::{dovi_rpu::DoviRpu, NUM_COMPONENTS};
 
 const NLQ_NUM_PIVOTS: usize = 2;
 
 #[derive(Default, Debug, Clone)]
-#[cfg_attr(feature = "serde_feature", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct RpuDataHeader {
     pub rpu_nal_prefix: u8,
     pub rpu_type: u8,
@@ -230,14 +230,8 @@ impl RpuDataHeader {
 
     pub fn get_dovi_profile(&self) -> u8 {
         match self.vdr_rpu_profile {
-            0 => {
-                // Profile 5 is full range
-                if self.bl_video_full_range_flag {
-                    5
-                } else {
-                    0
-                }
-            }
+            // Profile 5 is full range
+            0 if self.bl_video_full_range_flag => 5,
             1 => {
                 // 4, 7 or 8
                 if self.el_spatial_resampling_filter_flag && !self.disable_residual_flag {
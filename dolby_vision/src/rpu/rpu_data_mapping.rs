@@ -1,8 +1,8 @@
 use anyhow::{bail, ensure, Result};
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
-#[cfg(feature = "serde_feature")]
-use serde::Serialize;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 use super::dovi_rpu::DoviRpu;
 use super::rpu_data_header::RpuDataHeader;
@@ -11,7 +11,7 @@ use super::rpu_data_nlq::RpuDataNlq;
 use super::NUM_COMPONENTS;
 
 #[derive(Debug, Default, Clone)]
-#[cfg_attr(feature = "serde_feature", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct RpuDataMapping {
     pub mapping_idc: [Vec<u64>; NUM_COMPONENTS],
     pub mapping_param_pred_flag: [Vec<bool>; NUM_COMPONENTS],
@@ -1,15 +1,15 @@
-use anyhow::{bail, Result};
+use anyhow::{bail, ensure, Result};
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
-#[cfg(feature = "serde_feature")]
-use serde::Serialize;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 use super::rpu_data_header::RpuDataHeader;
 
 use super::NUM_COMPONENTS;
 
 #[derive(Debug, Default, Clone)]
-#[cfg_attr(feature = "serde_feature", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct RpuDataNlq {
     pub num_nlq_param_predictors: Vec<[u64; NUM_COMPONENTS]>,
     pub nlq_param_pred_flag: Vec<[bool; NUM_COMPONENTS]>,
@@ -23,6 +23,41 @@ pub struct RpuDataNlq {
     pub linear_deadzone_threshold: Vec<[u64; NUM_COMPONENTS]>,
 }
 
+/// NLQ parameters to set, per component, for every pivot
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct NlqParams {
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub nlq_offset: Option<[u64; NUM_COMPONENTS]>,
+
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub linear_deadzone_slope_int: Option<[u64; NUM_COMPONENTS]>,
+
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub linear_deadzone_slope: Option<[u64; NUM_COMPONENTS]>,
+
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub linear_deadzone_threshold_int: Option<[u64; NUM_COMPONENTS]>,
+
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub linear_deadzone_threshold: Option<[u64; NUM_COMPONENTS]>,
+}
+
 impl RpuDataNlq {
     pub fn parse(reader: &mut BitVecReader, header: &mut RpuDataHeader) -> Result<RpuDataNlq> {
         let pivot_idx_count = if let Some(nlq_num_pivots_minus2) = header.nlq_num_pivots_minus2 {
@@ -164,6 +199,96 @@ impl RpuDataNlq {
         });
     }
 
+    /// Sets the parameters of the pivots that are not predicted
+    pub fn set_params(&mut self, params: &NlqParams, header: &RpuDataHeader) -> Result<()> {
+        let has_deadzone = params.linear_deadzone_slope_int.is_some()
+            || params.linear_deadzone_slope.is_some()
+            || params.linear_deadzone_threshold_int.is_some()
+            || params.linear_deadzone_threshold.is_some();
+
+        ensure!(
+            !has_deadzone || header.nlq_method_idc == Some(0),
+            "NLQ deadzone parameters require the linear deadzone NLQ method"
+        );
+
+        let has_int = params.linear_deadzone_slope_int.is_some()
+            || params.linear_deadzone_threshold_int.is_some();
+
+        ensure!(
+            !has_int || header.coefficient_data_type == 0,
+            "NLQ integer deadzone parameters require integer coefficients"
+        );
+
+        let offset_max = 1_u64 << (header.el_bit_depth_minus8 + 8);
+        let coef_max = if header.coefficient_data_type == 0 {
+            1_u64 << header.coefficient_log2_denom
+        } else {
+            1_u64 << 32
+        };
+
+        let check = |name: &str, values: &Option<[u64; NUM_COMPONENTS]>, max: u64| {
+            if let Some(values) = values {
+                if let Some(value) = values.iter().find(|v| **v >= max) {
+                    bail!("Invalid NLQ {} {}, should be < {}", name, value, max);
+                }
+            }
+
+            Ok(())
+        };
+
+        check("nlq_offset", &params.nlq_offset, offset_max)?;
+        check(
+            "linear_deadzone_slope",
+            &params.linear_deadzone_slope,
+            coef_max,
+        )?;
+        check(
+            "linear_deadzone_threshold",
+            &params.linear_deadzone_threshold,
+            coef_max,
+        )?;
+
+        let set = |target: &mut Vec<[u64; NUM_COMPONENTS]>,
+                   values: &Option<[u64; NUM_COMPONENTS]>,
+                   pred_flags: &[[bool; NUM_COMPONENTS]]| {
+            if let Some(values) = values {
+                for (pivot, pred_flag) in target.iter_mut().zip(pred_flags) {
+                    for cmp in 0..NUM_COMPONENTS {
+                        if !pred_flag[cmp] {
+                            pivot[cmp] = values[cmp];
+                        }
+                    }
+                }
+            }
+        };
+
+        let pred_flags = &self.nlq_param_pred_flag;
+
+        set(&mut self.nlq_offset, &params.nlq_offset, pred_flags);
+        set(
+            &mut self.linear_deadzone_slope_int,
+            &params.linear_deadzone_slope_int,
+            pred_flags,
+        );
+        set(
+            &mut self.linear_deadzone_slope,
+            &params.linear_deadzone_slope,
+            pred_flags,
+        );
+        set(
+            &mut self.linear_deadzone_threshold_int,
+            &params.linear_deadzone_threshold_int,
+            pred_flags,
+        );
+        set(
+            &mut self.linear_deadzone_threshold,
+            &params.linear_deadzone_threshold,
+            pred_flags,
+        );
+
+        Ok(())
+    }
+
     pub fn write(&self, writer: &mut BitVecWriter, header: &RpuDataHeader) -> Result<()> {
         let pivot_idx_count = if let Some(nlq_num_pivots_minus2) = header.nlq_num_pivots_minus2 {
             nlq_num_pivots_minus2 as usize + 1
@@ -217,7 +342,7 @@ impl RpuDataNlq {
                             );
 
                             if header.coefficient_data_type == 0 {
-                                writer.write_ue(self.linear_deadzone_slope_int[pivot_idx][cmp]);
+                                writer.write_ue(self.linear_deadzone_threshold_int[pivot_idx][cmp]);
                             }
 
                             writer.write_n(
@@ -0,0 +1,238 @@
+use std::fmt;
+
+use anyhow::Result;
+use bitvec_helpers::bitvec_writer::BitVecWriter;
+
+use super::dovi_rpu::DoviRpu;
+use super::extension_metadata::blocks::ExtMetadataBlock;
+use super::extension_metadata::{DmData, WithExtMetadataBlocks};
+use super::rpu_data_header::RpuDataHeader;
+use super::vdr_dm_data::VdrDmData;
+use super::VDR_DM_DATA_FIXED_BITS;
+
+/// Bits of the uncompressed `vdr_dm_data` fields before `source_min_pq`, after the metadata IDs
+const SOURCE_MIN_PQ_OFFSET: usize = 475;
+
+/// A value accepted by the parser, but reserved or unexpected according to the spec.
+#[derive(Debug, Clone)]
+pub struct SpecIssue {
+    /// Name of the field, prefixed by the structure containing it
+    pub field: &'static str,
+
+    /// Offset of the field in the unescaped RPU, starting from `rpu_nal_prefix`
+    pub bit_offset: usize,
+
+    pub message: String,
+}
+
+impl fmt::Display for SpecIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (bit offset {}): {}",
+            self.field, self.bit_offset, self.message
+        )
+    }
+}
+
+impl DoviRpu {
+    /// Lists the values that parse correctly, but are questionable for a valid stream:
+    /// reserved bits set, inconsistent PQ values and unknown data before the CRC32.
+    ///
+    /// Only the RPU itself is checked, not its consistency with the other RPUs of the stream.
+    pub fn spec_issues(&self) -> Result<Vec<SpecIssue>> {
+        let header = &self.header;
+        let mut issues = Vec::new();
+
+        // 1 signals compressed DM metadata
+        if header.reserved_zero_3bits > 1 {
+            issues.push(SpecIssue {
+                field: "header.reserved_zero_3bits",
+                bit_offset: reserved_zero_3bits_offset(header),
+                message: format!(
+                    "reserved bits set, should be 0 or 1 for compressed DM metadata, found {}",
+                    header.reserved_zero_3bits
+                ),
+            });
+        }
+
+        let mut writer = BitVecWriter::new();
+        header.write_header(&mut writer);
+
+        if header.rpu_type == 2 && !header.use_prev_vdr_rpu_flag {
+            if let Some(ref rpu_data_mapping) = self.rpu_data_mapping {
+                rpu_data_mapping.write(&mut writer, header)?;
+            }
+
+            if let Some(ref rpu_data_nlq) = self.rpu_data_nlq {
+                rpu_data_nlq.write(&mut writer, header)?;
+            }
+        }
+
+        let mut offset = writer.written_bits();
+
+        if let Some(ref vdr_dm_data) = self.vdr_dm_data {
+            offset = vdr_dm_data_issues(vdr_dm_data, offset, &mut issues);
+        }
+
+        if !self.remaining.is_empty() {
+            issues.push(SpecIssue {
+                field: "remaining",
+                // After rpu_alignment_zero_bit
+                bit_offset: byte_aligned(offset),
+                message: format!(
+                    "{} bits of unknown data before the CRC32",
+                    self.remaining.len()
+                ),
+            });
+        }
+
+        Ok(issues)
+    }
+}
+
+/// Checks the DM metadata starting at `offset`, returning the offset of its end
+fn vdr_dm_data_issues(
+    vdr_dm_data: &VdrDmData,
+    offset: usize,
+    issues: &mut Vec<SpecIssue>,
+) -> usize {
+    let mut offset = offset
+        + ue_bits(vdr_dm_data.affected_dm_metadata_id)
+        + ue_bits(vdr_dm_data.current_dm_metadata_id)
+        + ue_bits(vdr_dm_data.scene_refresh_flag);
+
+    if !vdr_dm_data.compressed {
+        if vdr_dm_data.source_min_pq >= vdr_dm_data.source_max_pq {
+            issues.push(SpecIssue {
+                field: "vdr_dm_data.source_min_pq",
+                bit_offset: offset + SOURCE_MIN_PQ_OFFSET,
+                message: format!(
+                    "should be lower than source_max_pq {}, found {}",
+                    vdr_dm_data.source_max_pq, vdr_dm_data.source_min_pq
+                ),
+            });
+        }
+
+        offset += VDR_DM_DATA_FIXED_BITS;
+    }
+
+    let dm_data_list = [&vdr_dm_data.cmv29_metadata, &vdr_dm_data.cmv40_metadata];
+
+    for dm_data in dm_data_list.into_iter().flatten() {
+        let (num_ext_blocks, blocks) = match dm_data {
+            DmData::V29(meta) => (meta.num_ext_blocks(), meta.blocks_ref()),
+            DmData::V40(meta) => (meta.num_ext_blocks(), meta.blocks_ref()),
+        };
+
+        // dm_alignment_zero_bit
+        offset = byte_aligned(offset + ue_bits(num_ext_blocks));
+
+        for block in blocks {
+            offset += ue_bits(block.length_bytes()) + 8;
+
+            block_issues(block, offset, issues);
+
+            offset += block.length_bits() as usize;
+        }
+    }
+
+    offset
+}
+
+/// Checks the PQ values of a metadata block, with its payload starting at `offset`
+fn block_issues(block: &ExtMetadataBlock, offset: usize, issues: &mut Vec<SpecIssue>) {
+    match block {
+        ExtMetadataBlock::Level1(b) => {
+            if b.min_pq > b.max_pq {
+                issues.push(SpecIssue {
+                    field: "L1.min_pq",
+                    bit_offset: offset,
+                    message: format!("should be at most max_pq {}, found {}", b.max_pq, b.min_pq),
+                });
+            } else if b.avg_pq < b.min_pq || b.avg_pq > b.max_pq {
+                issues.push(SpecIssue {
+                    field: "L1.avg_pq",
+                    bit_offset: offset + 24,
+                    message: format!(
+                        "should be between min_pq {} and max_pq {}, found {}",
+                        b.min_pq, b.max_pq, b.avg_pq
+                    ),
+                });
+            }
+        }
+        ExtMetadataBlock::Level6(b) => {
+            // In units of 0.0001 nits
+            let min_luminance = b.min_display_mastering_luminance as u32;
+            let max_luminance = b.max_display_mastering_luminance as u32 * 10_000;
+
+            if b.max_display_mastering_luminance > 0 && min_luminance >= max_luminance {
+                issues.push(SpecIssue {
+                    field: "L6.min_display_mastering_luminance",
+                    bit_offset: offset + 16,
+                    message: format!(
+                        "should be lower than max_display_mastering_luminance {} nits, found {}",
+                        b.max_display_mastering_luminance, b.min_display_mastering_luminance
+                    ),
+                });
+            }
+
+            if b.max_content_light_level > 0
+                && b.max_frame_average_light_level > b.max_content_light_level
+            {
+                issues.push(SpecIssue {
+                    field: "L6.max_frame_average_light_level",
+                    bit_offset: offset + 48,
+                    message: format!(
+                        "should be at most max_content_light_level {}, found {}",
+                        b.max_content_light_level, b.max_frame_average_light_level
+                    ),
+                });
+            }
+        }
+        ExtMetadataBlock::Level10(b) if b.target_min_pq >= b.target_max_pq => {
+            issues.push(SpecIssue {
+                field: "L10.target_min_pq",
+                bit_offset: offset + 20,
+                message: format!(
+                    "should be lower than target_max_pq {}, found {}",
+                    b.target_max_pq, b.target_min_pq
+                ),
+            });
+        }
+        _ => (),
+    }
+}
+
+fn reserved_zero_3bits_offset(header: &RpuDataHeader) -> usize {
+    // rpu_nal_prefix to vdr_seq_info_present_flag
+    let mut offset = 8 + 6 + 11 + 4 + 4 + 1;
+
+    // chroma_resampling_explicit_filter_flag, coefficient_data_type
+    offset += 1 + 2;
+
+    if header.coefficient_data_type == 0 {
+        offset += ue_bits(header.coefficient_log2_denom);
+    }
+
+    // vdr_rpu_normalized_idc, bl_video_full_range_flag
+    offset += 2 + 1;
+
+    offset += ue_bits(header.bl_bit_depth_minus8)
+        + ue_bits(header.el_bit_depth_minus8)
+        + ue_bits(header.vdr_bit_depth_minus_8);
+
+    // spatial_resampling_filter_flag
+    offset + 1
+}
+
+fn ue_bits(value: u64) -> usize {
+    let mut writer = BitVecWriter::new();
+    writer.write_ue(value);
+
+    writer.written_bits()
+}
+
+fn byte_aligned(offset: usize) -> usize {
+    (offset + 7) / 8 * 8
+}
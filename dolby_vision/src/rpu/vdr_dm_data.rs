@@ -1,22 +1,24 @@
 use anyhow::{bail, ensure, Result};
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
-#[cfg(feature = "serde_feature")]
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 use super::dovi_rpu::DoviRpu;
 use super::extension_metadata::blocks::{
-    ExtMetadataBlock, ExtMetadataBlockLevel11, ExtMetadataBlockLevel9,
+    ExtMetadataBlock, ExtMetadataBlockLevel11, ExtMetadataBlockLevel2, ExtMetadataBlockLevel8,
+    ExtMetadataBlockLevel9,
 };
 use super::extension_metadata::*;
-use super::generate::GenerateConfig;
+use super::generate::{GenerateConfig, TrimAlgorithm, TRIM_TARGET_DISPLAYS};
 use super::profiles::profile81::Profile81;
 use super::profiles::DoviProfile;
 
 use super::extension_metadata::WithExtMetadataBlocks;
+use crate::utils::{nits_to_pq, pq_to_pq12};
 
 #[derive(Debug, Default, Clone)]
-#[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct VdrDmData {
     pub compressed: bool,
 
@@ -58,19 +60,27 @@ pub struct VdrDmData {
     pub source_diagonal: u16,
 
     #[cfg_attr(
-        feature = "serde_feature",
-        serde(skip_serializing_if = "Option::is_none")
+        feature = "serde",
+        serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            deserialize_with = "DmData::deserialize_cmv29"
+        )
     )]
     pub cmv29_metadata: Option<DmData>,
     #[cfg_attr(
-        feature = "serde_feature",
-        serde(skip_serializing_if = "Option::is_none")
+        feature = "serde",
+        serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            deserialize_with = "DmData::deserialize_cmv40"
+        )
     )]
     pub cmv40_metadata: Option<DmData>,
 }
 
-#[derive(Debug, PartialEq)]
-#[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub enum CmVersion {
     V29,
     V40,
@@ -165,6 +175,11 @@ impl VdrDmData {
             "affected_dm_metadata_id should be <= 15"
         );
 
+        ensure!(
+            self.source_min_pq <= 4095 && self.source_max_pq <= 4095,
+            "source_min_pq and source_max_pq should be <= 4095"
+        );
+
         // FIXME: Compressed DM metadata, should be set from a state somehow
         if !self.compressed {
             ensure!(
@@ -387,13 +402,79 @@ impl VdrDmData {
             }
             ExtMetadataBlock::Level11(_) => self.replace_metadata_level(block),
             ExtMetadataBlock::Level254(_) => {
-                bail!("Cannot replace automatically generated Level254 block")
+                ensure!(
+                    self.cmv40_metadata.is_some(),
+                    "Cannot replace L254 metadata, no CM v4.0 DM data"
+                );
+
+                self.replace_metadata_level(block)
             }
             ExtMetadataBlock::Level255(_) => self.replace_metadata_level(block),
             ExtMetadataBlock::Reserved(_) => bail!("Cannot replace specific reserved block"),
         }
     }
 
+    /// Adds the trims computed from the L1 metadata, for the target displays that don't already have one.
+    ///
+    /// The trims are added as L2 blocks, and also as L8 blocks with CM v4.0 metadata.
+    /// Target displays other than 100, 600 and 1000 nits are ignored.
+    pub fn add_generated_trims(
+        &mut self,
+        trim_targets: &[u16],
+        trim_algorithm: TrimAlgorithm,
+    ) -> Result<()> {
+        let level1 = match self.get_block(1) {
+            Some(ExtMetadataBlock::Level1(level1)) => level1.clone(),
+            _ => return Ok(()),
+        };
+
+        for (target_nits, target_display_index) in TRIM_TARGET_DISPLAYS {
+            if !trim_targets.contains(target_nits) {
+                continue;
+            }
+
+            let target_max_pq = pq_to_pq12(nits_to_pq(*target_nits as f64));
+            let (trim_slope, trim_power) = trim_algorithm.trims(&level1, target_max_pq);
+            let trim_saturation_gain = trim_algorithm.saturation_gain(&level1, target_max_pq);
+
+            let has_level2 = self.level_blocks_iter(2).any(|b| match b {
+                ExtMetadataBlock::Level2(b) => b.target_max_pq == target_max_pq,
+                _ => false,
+            });
+
+            if !has_level2 {
+                self.replace_metadata_block(ExtMetadataBlock::Level2(ExtMetadataBlockLevel2 {
+                    target_max_pq,
+                    trim_slope,
+                    trim_power,
+                    trim_saturation_gain,
+                    ..Default::default()
+                }))?;
+            }
+
+            if self.cmv40_metadata.is_some() {
+                let has_level8 = self.level_blocks_iter(8).any(|b| match b {
+                    ExtMetadataBlock::Level8(b) => b.target_display_index == *target_display_index,
+                    _ => false,
+                });
+
+                if !has_level8 {
+                    self.replace_metadata_block(ExtMetadataBlock::Level8(
+                        ExtMetadataBlockLevel8 {
+                            target_display_index: *target_display_index,
+                            trim_slope,
+                            trim_power,
+                            trim_saturation_gain,
+                            ..Default::default()
+                        },
+                    ))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn set_p81_coeffs(&mut self) {
         self.ycc_to_rgb_coef0 = 9574;
         self.ycc_to_rgb_coef1 = 0;
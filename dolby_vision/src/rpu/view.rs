@@ -0,0 +1,607 @@
+use anyhow::{bail, ensure, Result};
+
+use super::extension_metadata::blocks::{
+    ExtMetadataBlockLevel1, ExtMetadataBlockLevel5, ExtMetadataBlockLevel6,
+};
+use super::{validated_trimmed_data, NUM_COMPONENTS, VDR_DM_DATA_FIXED_BITS};
+
+/// Borrowed view over the bytes of an RPU, exposing the header and the L1/L5/L6 metadata.
+///
+/// Only the header is decoded when creating the view, the mapping and NLQ data are skipped
+/// and the metadata blocks are decoded from the bytes when accessed.
+/// Nothing is allocated, and the CRC32 is not validated.
+///
+/// Parse with `DoviRpu` to access or edit the rest of the RPU.
+/// The view is also available without the `std` feature, for `no_std` targets.
+#[derive(Debug, Clone, Copy)]
+pub struct RpuView<'a> {
+    data: &'a [u8],
+    escaped: bool,
+
+    pub header: RpuViewHeader,
+
+    level1: Option<BitPosition>,
+    level5: Option<BitPosition>,
+    level6: Option<BitPosition>,
+}
+
+/// Fields of the `RpuDataHeader`, without the pivot values.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RpuViewHeader {
+    pub rpu_type: u8,
+    pub rpu_format: u16,
+    pub vdr_rpu_profile: u8,
+    pub vdr_rpu_level: u8,
+    pub vdr_seq_info_present_flag: bool,
+    pub coefficient_data_type: u8,
+    pub coefficient_log2_denom: u64,
+    pub bl_video_full_range_flag: bool,
+    pub bl_bit_depth_minus8: u64,
+    pub el_bit_depth_minus8: u64,
+    pub vdr_bit_depth_minus_8: u64,
+    pub reserved_zero_3bits: u8,
+    pub el_spatial_resampling_filter_flag: bool,
+    pub disable_residual_flag: bool,
+    pub vdr_dm_metadata_present_flag: bool,
+    pub use_prev_vdr_rpu_flag: bool,
+    pub num_pivots_minus_2: [u64; NUM_COMPONENTS],
+    pub nlq_method_idc: Option<u8>,
+}
+
+/// Position of the next byte and bit to read in the data
+#[derive(Debug, Default, Clone, Copy)]
+struct BitPosition {
+    byte: usize,
+    bit: u8,
+}
+
+/// Bit reader over the borrowed data, skipping the emulation prevention bytes of escaped data
+struct ViewReader<'a> {
+    data: &'a [u8],
+    escaped: bool,
+    pos: BitPosition,
+}
+
+impl<'a> RpuView<'a> {
+    /// Creates a view over an escaped RPU from a HEVC UNSPEC62 NAL unit.
+    /// The data can include the start code and/or the NAL header (0x7C01).
+    pub fn parse_unspec62_nalu(data: &'a [u8]) -> Result<RpuView<'a>> {
+        RpuView::parse(validated_trimmed_data(data)?, true)
+    }
+
+    /// Creates a view over an unescaped RPU, without emulation prevention bytes.
+    pub fn parse_rpu(data: &'a [u8]) -> Result<RpuView<'a>> {
+        RpuView::parse(validated_trimmed_data(data)?, false)
+    }
+
+    fn parse(data: &'a [u8], escaped: bool) -> Result<RpuView<'a>> {
+        let mut reader = ViewReader {
+            data,
+            escaped,
+            pos: BitPosition::default(),
+        };
+
+        let header = RpuViewHeader::parse(&mut reader)?;
+
+        let mut view = RpuView {
+            data,
+            escaped,
+            header,
+            level1: None,
+            level5: None,
+            level6: None,
+        };
+
+        if header.rpu_type != 2 {
+            return Ok(view);
+        }
+
+        if !header.use_prev_vdr_rpu_flag {
+            skip_rpu_data_mapping(&mut reader, &header)?;
+        }
+
+        if header.vdr_dm_metadata_present_flag {
+            view.find_cmv29_blocks(&mut reader)?;
+        }
+
+        Ok(view)
+    }
+
+    /// The escaped or unescaped data, starting at the `rpu_nal_prefix`
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Same as `RpuDataHeader::get_dovi_profile`
+    pub fn dovi_profile(&self) -> u8 {
+        let header = &self.header;
+
+        match header.vdr_rpu_profile {
+            0 if header.bl_video_full_range_flag => 5,
+            1 => {
+                if header.el_spatial_resampling_filter_flag && !header.disable_residual_flag {
+                    if header.vdr_bit_depth_minus_8 == 4 {
+                        7
+                    } else {
+                        4
+                    }
+                } else {
+                    8
+                }
+            }
+            _ => 0,
+        }
+    }
+
+    pub fn level1(&self) -> Option<ExtMetadataBlockLevel1> {
+        let mut reader = self.reader_at(self.level1?);
+
+        Some(ExtMetadataBlockLevel1 {
+            min_pq: reader.get_n(12).ok()? as u16,
+            max_pq: reader.get_n(12).ok()? as u16,
+            avg_pq: reader.get_n(12).ok()? as u16,
+        })
+    }
+
+    pub fn level5(&self) -> Option<ExtMetadataBlockLevel5> {
+        let mut reader = self.reader_at(self.level5?);
+
+        Some(ExtMetadataBlockLevel5 {
+            active_area_left_offset: reader.get_n(13).ok()? as u16,
+            active_area_right_offset: reader.get_n(13).ok()? as u16,
+            active_area_top_offset: reader.get_n(13).ok()? as u16,
+            active_area_bottom_offset: reader.get_n(13).ok()? as u16,
+        })
+    }
+
+    pub fn level6(&self) -> Option<ExtMetadataBlockLevel6> {
+        let mut reader = self.reader_at(self.level6?);
+
+        Some(ExtMetadataBlockLevel6 {
+            max_display_mastering_luminance: reader.get_n(16).ok()? as u16,
+            min_display_mastering_luminance: reader.get_n(16).ok()? as u16,
+            max_content_light_level: reader.get_n(16).ok()? as u16,
+            max_frame_average_light_level: reader.get_n(16).ok()? as u16,
+        })
+    }
+
+    fn reader_at(&self, pos: BitPosition) -> ViewReader<'a> {
+        ViewReader {
+            data: self.data,
+            escaped: self.escaped,
+            pos,
+        }
+    }
+
+    /// Skips the `vdr_dm_data` fields and stores the position of the first L1, L5 and L6 blocks
+    fn find_cmv29_blocks(&mut self, reader: &mut ViewReader) -> Result<()> {
+        // affected_dm_metadata_id, current_dm_metadata_id, scene_refresh_flag
+        (0..3).try_for_each(|_| reader.get_ue().map(|_| ()))?;
+
+        // Compressed DM data only has the metadata IDs
+        if self.header.reserved_zero_3bits != 1 {
+            reader.skip_n(VDR_DM_DATA_FIXED_BITS)?;
+        }
+
+        let num_ext_blocks = reader.get_ue()?;
+
+        while !reader.is_aligned() {
+            ensure!(!reader.get()?, "CM v2.9: dm_alignment_zero_bit != 0");
+        }
+
+        for _ in 0..num_ext_blocks {
+            let ext_block_length = reader.get_ue()?;
+            let ext_block_level = reader.get_n(8)?;
+
+            let block = match ext_block_level {
+                1 => Some(&mut self.level1),
+                5 => Some(&mut self.level5),
+                6 => Some(&mut self.level6),
+                _ => None,
+            };
+
+            match block {
+                Some(block) if block.is_none() => *block = Some(reader.pos),
+                _ => (),
+            }
+
+            reader.skip_n(ext_block_length as usize * 8)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl RpuViewHeader {
+    fn parse(reader: &mut ViewReader) -> Result<RpuViewHeader> {
+        let rpu_nal_prefix = reader.get_n(8)?;
+        ensure!(rpu_nal_prefix == 25, "rpu_nal_prefix should be 25");
+
+        let mut header = RpuViewHeader {
+            rpu_type: reader.get_n(6)? as u8,
+            rpu_format: reader.get_n(11)? as u16,
+            ..Default::default()
+        };
+
+        if header.rpu_type != 2 {
+            return Ok(header);
+        }
+
+        header.vdr_rpu_profile = reader.get_n(4)? as u8;
+        header.vdr_rpu_level = reader.get_n(4)? as u8;
+        header.vdr_seq_info_present_flag = reader.get()?;
+
+        if header.vdr_seq_info_present_flag {
+            // chroma_resampling_explicit_filter_flag
+            reader.skip_n(1)?;
+            header.coefficient_data_type = reader.get_n(2)? as u8;
+
+            if header.coefficient_data_type == 0 {
+                header.coefficient_log2_denom = reader.get_ue()?;
+            }
+
+            // vdr_rpu_normalized_idc
+            reader.skip_n(2)?;
+            header.bl_video_full_range_flag = reader.get()?;
+
+            if header.rpu_format & 0x700 == 0 {
+                header.bl_bit_depth_minus8 = reader.get_ue()?;
+                header.el_bit_depth_minus8 = reader.get_ue()?;
+                header.vdr_bit_depth_minus_8 = reader.get_ue()?;
+
+                // spatial_resampling_filter_flag
+                reader.skip_n(1)?;
+                header.reserved_zero_3bits = reader.get_n(3)? as u8;
+                header.el_spatial_resampling_filter_flag = reader.get()?;
+                header.disable_residual_flag = reader.get()?;
+            }
+        }
+
+        header.vdr_dm_metadata_present_flag = reader.get()?;
+        header.use_prev_vdr_rpu_flag = reader.get()?;
+
+        if header.use_prev_vdr_rpu_flag {
+            // prev_vdr_rpu_id
+            reader.get_ue()?;
+            return Ok(header);
+        }
+
+        // vdr_rpu_id, mapping_color_space, mapping_chroma_format_idc
+        (0..3).try_for_each(|_| reader.get_ue().map(|_| ()))?;
+
+        let bl_bit_depth = header.bl_bit_depth_minus8 as usize + 8;
+
+        for num_pivots_minus_2 in header.num_pivots_minus_2.iter_mut() {
+            *num_pivots_minus_2 = reader.get_ue()?;
+
+            // pred_pivot_value
+            reader.skip_n((*num_pivots_minus_2 as usize + 2) * bl_bit_depth)?;
+        }
+
+        // Profile 7 only
+        if header.rpu_format & 0x700 == 0 && !header.disable_residual_flag {
+            header.nlq_method_idc = Some(reader.get_n(3)? as u8);
+
+            // nlq_pred_pivot_value
+            reader.skip_n(2 * bl_bit_depth)?;
+        }
+
+        // num_x_partitions_minus1, num_y_partitions_minus1
+        reader.get_ue()?;
+        reader.get_ue()?;
+
+        Ok(header)
+    }
+
+    /// Bits of the fractional part of the coefficients
+    fn coefficient_length(&self) -> Result<usize> {
+        match self.coefficient_data_type {
+            0 => Ok(self.coefficient_log2_denom as usize),
+            1 => Ok(32),
+            v => bail!("Invalid coefficient_data_type value: {}", v),
+        }
+    }
+}
+
+/// Skips the fields read by `RpuDataMapping::parse` and `RpuDataNlq::parse`
+fn skip_rpu_data_mapping(reader: &mut ViewReader, header: &RpuViewHeader) -> Result<()> {
+    let coefficient_length = header.coefficient_length()?;
+    let int_coefficients = header.coefficient_data_type == 0;
+
+    for num_pivots_minus_2 in header.num_pivots_minus_2 {
+        let pivot_idx_count = num_pivots_minus_2 as usize + 1;
+
+        for pivot_idx in 0..pivot_idx_count {
+            let mapping_idc = reader.get_ue()?;
+
+            if mapping_idc == 0 {
+                // MAPPING_POLYNOMIAL
+                let poly_order_minus1 = reader.get_ue()?;
+                let linear_interp_flag = poly_order_minus1 == 0 && reader.get()?;
+
+                if linear_interp_flag {
+                    let values = if pivot_idx as u64 == num_pivots_minus_2 {
+                        2
+                    } else {
+                        1
+                    };
+
+                    for _ in 0..values {
+                        if int_coefficients {
+                            reader.get_ue()?;
+                        }
+
+                        reader.skip_n(coefficient_length)?;
+                    }
+                } else {
+                    for _ in 0..poly_order_minus1 + 2 {
+                        if int_coefficients {
+                            reader.get_ue()?;
+                        }
+
+                        reader.skip_n(coefficient_length)?;
+                    }
+                }
+            } else if mapping_idc == 1 {
+                // MAPPING_MMR
+                let mmr_order_minus1 = reader.get_n(2)?;
+                ensure!(mmr_order_minus1 <= 2);
+
+                // mmr_constant, then 7 coefficients per order
+                for _ in 0..1 + (mmr_order_minus1 + 1) * 7 {
+                    if int_coefficients {
+                        reader.get_ue()?;
+                    }
+
+                    reader.skip_n(coefficient_length)?;
+                }
+            }
+        }
+    }
+
+    if let Some(nlq_method_idc) = header.nlq_method_idc {
+        let el_bit_depth = header.el_bit_depth_minus8 as usize + 8;
+
+        for _ in 0..NUM_COMPONENTS {
+            // nlq_offset, vdr_in_max
+            reader.skip_n(el_bit_depth)?;
+
+            if int_coefficients {
+                reader.get_ue()?;
+            }
+
+            reader.skip_n(coefficient_length)?;
+
+            // NLQ_LINEAR_DZ: linear_deadzone_slope, linear_deadzone_threshold
+            if nlq_method_idc == 0 {
+                for _ in 0..2 {
+                    if int_coefficients {
+                        reader.get_ue()?;
+                    }
+
+                    reader.skip_n(coefficient_length)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+impl<'a> ViewReader<'a> {
+    /// Whether the byte is an emulation prevention byte, like `clear_start_code_emulation_prevention_3_byte`
+    #[inline(always)]
+    fn is_emulation_prevention(&self, index: usize) -> bool {
+        let data = self.data;
+
+        self.escaped
+            && index > 2
+            && index < data.len() - 2
+            && data[index - 2] == 0
+            && data[index - 1] == 0
+            && data[index] <= 3
+    }
+
+    #[inline(always)]
+    fn skip_emulation_prevention(&mut self) {
+        if self.pos.bit == 0 {
+            while self.pos.byte < self.data.len() && self.is_emulation_prevention(self.pos.byte) {
+                self.pos.byte += 1;
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn get(&mut self) -> Result<bool> {
+        self.skip_emulation_prevention();
+
+        let byte = match self.data.get(self.pos.byte) {
+            Some(byte) => *byte,
+            None => bail!("RpuView: out of bounds"),
+        };
+
+        let val = (byte >> (7 - self.pos.bit)) & 1 == 1;
+
+        if self.pos.bit == 7 {
+            self.pos.byte += 1;
+            self.pos.bit = 0;
+        } else {
+            self.pos.bit += 1;
+        }
+
+        Ok(val)
+    }
+
+    /// Reads up to 64 bits
+    #[inline(always)]
+    fn get_n(&mut self, n: usize) -> Result<u64> {
+        let mut val = 0;
+
+        for _ in 0..n {
+            val = (val << 1) | self.get()? as u64;
+        }
+
+        Ok(val)
+    }
+
+    /// Also skips signed values, `se(v)` are read as `ue(v)` codes
+    #[inline(always)]
+    fn get_ue(&mut self) -> Result<u64> {
+        let mut leading_zeroes = 0;
+
+        while !self.get()? {
+            leading_zeroes += 1;
+            ensure!(leading_zeroes < 64, "RpuView: invalid ue(v) value");
+        }
+
+        Ok((1 << leading_zeroes) - 1 + self.get_n(leading_zeroes)?)
+    }
+
+    fn skip_n(&mut self, mut n: usize) -> Result<()> {
+        while n > 0 {
+            if self.pos.bit == 0 && n >= 8 {
+                self.skip_emulation_prevention();
+                ensure!(self.pos.byte < self.data.len(), "RpuView: out of bounds");
+
+                self.pos.byte += 1;
+                n -= 8;
+            } else {
+                self.get()?;
+                n -= 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_aligned(&self) -> bool {
+        self.pos.bit == 0
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::path::PathBuf;
+
+    use anyhow::Result;
+
+    use crate::rpu::dovi_rpu::DoviRpu;
+    use crate::rpu::extension_metadata::blocks::ExtMetadataBlock;
+
+    use super::RpuView;
+
+    fn assert_view_matches(view: &RpuView, rpu: &DoviRpu) {
+        let header = &rpu.header;
+
+        assert_eq!(view.dovi_profile(), rpu.dovi_profile);
+        assert_eq!(view.header.rpu_format, header.rpu_format);
+        assert_eq!(view.header.vdr_rpu_profile, header.vdr_rpu_profile);
+        assert_eq!(view.header.bl_bit_depth_minus8, header.bl_bit_depth_minus8);
+        assert_eq!(view.header.el_bit_depth_minus8, header.el_bit_depth_minus8);
+        assert_eq!(view.header.num_pivots_minus_2, header.num_pivots_minus_2);
+        assert_eq!(view.header.nlq_method_idc, header.nlq_method_idc);
+        assert_eq!(
+            view.header.vdr_dm_metadata_present_flag,
+            header.vdr_dm_metadata_present_flag
+        );
+
+        let vdr_dm_data = rpu.vdr_dm_data.as_ref();
+
+        match (view.level1(), vdr_dm_data.and_then(|dm| dm.get_block(1))) {
+            (Some(l1), Some(ExtMetadataBlock::Level1(expected))) => {
+                assert_eq!(
+                    (l1.min_pq, l1.max_pq, l1.avg_pq),
+                    (expected.min_pq, expected.max_pq, expected.avg_pq)
+                );
+            }
+            (None, None) => (),
+            (l1, expected) => panic!("L1 mismatch: {:?}, expected {:?}", l1, expected),
+        }
+
+        match (view.level5(), vdr_dm_data.and_then(|dm| dm.get_block(5))) {
+            (Some(l5), Some(ExtMetadataBlock::Level5(expected))) => {
+                assert_eq!(l5.get_offsets(), expected.get_offsets(),);
+            }
+            (None, None) => (),
+            (l5, expected) => panic!("L5 mismatch: {:?}, expected {:?}", l5, expected),
+        }
+
+        match (view.level6(), vdr_dm_data.and_then(|dm| dm.get_block(6))) {
+            (Some(l6), Some(ExtMetadataBlock::Level6(expected))) => {
+                assert_eq!(
+                    (
+                        l6.max_display_mastering_luminance,
+                        l6.min_display_mastering_luminance,
+                        l6.max_content_light_level,
+                        l6.max_frame_average_light_level
+                    ),
+                    (
+                        expected.max_display_mastering_luminance,
+                        expected.min_display_mastering_luminance,
+                        expected.max_content_light_level,
+                        expected.max_frame_average_light_level
+                    )
+                );
+            }
+            (None, None) => (),
+            (l6, expected) => panic!("L6 mismatch: {:?}, expected {:?}", l6, expected),
+        }
+    }
+
+    /// NAL units of a RPU file, starting with the 0x00000001 start code
+    fn rpu_nalus(data: &[u8]) -> Vec<&[u8]> {
+        let mut starts: Vec<usize> = data
+            .windows(4)
+            .enumerate()
+            .filter(|(_, w)| *w == [0, 0, 0, 1])
+            .map(|(i, _)| i)
+            .collect();
+        starts.push(data.len());
+
+        starts.windows(2).map(|w| &data[w[0]..w[1]]).collect()
+    }
+
+    #[test]
+    fn matches_owned_parse() -> Result<()> {
+        let lib_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let assets_path = lib_path.parent().unwrap().join("assets/tests");
+
+        let files = [
+            "cmv2_9_xml_with_l5_rpu.bin",
+            "cmv40_full_rpu.bin",
+            "cmv4_0_2_xml_with_l5_rpu.bin",
+            "cmv4_2_510_xml_rpu.bin",
+            "data_before_crc32.bin",
+            "fel_orig.bin",
+            "fel_to_mel.bin",
+            "fix_se_write.bin",
+            "mel_orig.bin",
+            "mel_variable_l8_length13.bin",
+            "p8_001_end_crc32.bin",
+            "poly_coef_int_logic.bin",
+            "profile4.bin",
+            "profile5.bin",
+            "profile5-02.bin",
+            "profile8.bin",
+            "trailing_bytes_rpu.bin",
+            "unordered_l8_blocks.bin",
+        ];
+
+        for file in files {
+            let data = std::fs::read(assets_path.join(file))?;
+
+            for nalu in rpu_nalus(&data) {
+                let rpu = DoviRpu::parse_unspec62_nalu(nalu)?;
+
+                let view = RpuView::parse_unspec62_nalu(nalu)?;
+                assert_view_matches(&view, &rpu);
+
+                let unescaped = rpu.write_rpu()?;
+                let view = RpuView::parse_rpu(&unescaped)?;
+                assert_view_matches(&view, &rpu);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
-#[cfg(feature = "serde_feature")]
+#[cfg(feature = "serde")]
 use {
     bitvec::prelude::*,
-    serde::{ser::Serializer, Serialize},
+    serde::{de::Deserializer, ser::Serializer, Deserialize, Serialize},
 };
 
 pub const ST2084_Y_MAX: f64 = 10000.0;
@@ -20,6 +20,28 @@ pub fn nits_to_pq(nits: f64) -> f64 {
         .powf(ST2084_M2)
 }
 
+/// Helper function to calculate nits (cd/m2) values from PQ codes, normalized [0-1]
+#[inline(always)]
+pub fn pq_to_nits(pq: f64) -> f64 {
+    let ep = pq.powf(1.0 / ST2084_M2);
+
+    let y = ((ep - ST2084_C1).max(0.0) / (ST2084_C2 - ST2084_C3 * ep)).powf(1.0 / ST2084_M1);
+
+    y * ST2084_Y_MAX
+}
+
+/// Helper function to normalize [0-1] a 12 bit PQ code, as stored in the metadata blocks
+#[inline(always)]
+pub fn pq12_to_pq(pq: u16) -> f64 {
+    pq as f64 / 4095.0
+}
+
+/// Helper function to calculate the 12 bit PQ code of a normalized [0-1] PQ value
+#[inline(always)]
+pub fn pq_to_pq12(pq: f64) -> u16 {
+    (pq * 4095.0).round() as u16
+}
+
 /// Copied from hevc_parser for convenience, and to avoid a dependency
 /// Unescapes a byte slice from annexb.
 /// Allocates a new Vec.
@@ -47,7 +69,8 @@ pub fn add_start_code_emulation_prevention_3_byte(data: &mut Vec<u8>) {
     let mut i = 0;
 
     while i < count {
-        if i > 2 && i < count - 2 && data[i - 2] == 0 && data[i - 1] == 0 && data[i] <= 3 {
+        // Up to the last byte, a NAL unit may end with 0x000001 before the trailing bits
+        if i > 2 && data[i - 2] == 0 && data[i - 1] == 0 && data[i] <= 3 {
             data.insert(i, 3);
             count += 1;
         }
@@ -57,8 +80,20 @@ pub fn add_start_code_emulation_prevention_3_byte(data: &mut Vec<u8>) {
 }
 
 /// Serializing a bitvec as a vec of bits
-#[cfg(feature = "serde_feature")]
+#[cfg(feature = "serde")]
 pub fn bitvec_ser_bits<S: Serializer>(bitvec: &BitVec<u8, Msb0>, s: S) -> Result<S::Ok, S::Error> {
     let bits: Vec<u8> = bitvec.iter().map(|b| *b as u8).collect();
     bits.serialize(s)
 }
+
+/// Deserializing a bitvec from a vec of bits
+#[cfg(feature = "serde")]
+pub fn bitvec_de_bits<'de, D: Deserializer<'de>>(d: D) -> Result<BitVec<u8, Msb0>, D::Error> {
+    let bits: Vec<u8> = Vec::deserialize(d)?;
+    Ok(bits.into_iter().map(|b| b != 0).collect())
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn default_true() -> bool {
+    true
+}
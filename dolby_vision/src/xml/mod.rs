@@ -1,7 +1,11 @@
 /// XML metadata parser
 mod parser;
 
+/// XML metadata writer
+mod writer;
+
 #[cfg(test)]
 mod tests;
 
 pub use parser::{CmXmlParser, XmlParserOpts};
+pub use writer::{CmXmlWriter, XmlWriterOpts};
@@ -3,14 +3,14 @@ use roxmltree::{Document, Node};
 use std::cmp::min;
 use std::collections::HashMap;
 use std::convert::TryInto;
-use std::fs::File;
-use std::io::Read;
-use std::path::Path;
+
+#[cfg(feature = "fs")]
+use std::{fs::File, io::Read, path::Path};
 
 use crate::rpu::extension_metadata::{blocks::*, primaries};
 use crate::rpu::generate::{GenerateConfig, ShotFrameEdit, VideoShot};
 use crate::rpu::vdr_dm_data::CmVersion;
-use crate::utils::nits_to_pq;
+use crate::utils::{nits_to_pq, pq_to_pq12};
 
 use level10::PRESET_TARGET_DISPLAYS;
 use primaries::ColorPrimaries;
@@ -41,6 +41,7 @@ pub struct TargetDisplay {
 }
 
 impl CmXmlParser {
+    #[cfg(feature = "fs")]
     pub fn parse_file(file_path: &Path, opts: XmlParserOpts) -> Result<CmXmlParser> {
         let mut s = String::new();
         File::open(file_path)?.read_to_string(&mut s)?;
@@ -88,6 +89,7 @@ impl CmXmlParser {
 
                 parser.config.shots = parser.parse_shots(&video)?;
                 parser.config.shots.sort_by_key(|s| s.start);
+                parser.validate_shots_continuity()?;
 
                 // Add default L10 blocks
                 if parser.is_cmv4() {
@@ -437,6 +439,24 @@ impl CmXmlParser {
         shots
     }
 
+    /// Shots must follow each other without gaps or overlaps to be frame accurate
+    fn validate_shots_continuity(&self) -> Result<()> {
+        for shots in self.config.shots.windows(2) {
+            let (prev, shot) = (&shots[0], &shots[1]);
+            let expected_start = prev.start + prev.duration;
+
+            ensure!(
+                shot.start == expected_start,
+                format!(
+                    "Shot {} starts at frame {}, but previous shot {} ends at frame {}",
+                    shot.id, shot.start, prev.id, expected_start
+                )
+            );
+        }
+
+        Ok(())
+    }
+
     fn parse_shot_trims(&self, node: &Node) -> Result<Vec<ExtMetadataBlock>> {
         let mut metadata_blocks = Vec::new();
 
@@ -536,11 +556,8 @@ impl CmXmlParser {
             let mut block = ExtMetadataBlockLevel10 {
                 length,
                 target_display_index: target.id.parse::<u8>().unwrap(),
-                target_max_pq: min(
-                    4095,
-                    (nits_to_pq(target.peak_nits.into()) * 4095.0).round() as u16,
-                ),
-                target_min_pq: min(4095, (nits_to_pq(target.min_nits) * 4095.0).round() as u16),
+                target_max_pq: min(4095, pq_to_pq12(nits_to_pq(target.peak_nits.into()))),
+                target_min_pq: min(4095, pq_to_pq12(nits_to_pq(target.min_nits))),
                 target_primary_index: index,
                 ..Default::default()
             };
@@ -575,9 +592,9 @@ impl CmXmlParser {
             "invalid L1 trim: should be 3 values"
         );
 
-        let min_pq = (measurements[0].parse::<f32>().unwrap() * 4095.0).round() as u16;
-        let avg_pq = (measurements[1].parse::<f32>().unwrap() * 4095.0).round() as u16;
-        let max_pq = (measurements[2].parse::<f32>().unwrap() * 4095.0).round() as u16;
+        let min_pq = pq_to_pq12(measurements[0].parse::<f64>().unwrap());
+        let avg_pq = pq_to_pq12(measurements[1].parse::<f64>().unwrap());
+        let max_pq = pq_to_pq12(measurements[2].parse::<f64>().unwrap());
 
         Ok(ExtMetadataBlockLevel1::from_stats(min_pq, max_pq, avg_pq))
     }
@@ -1,8 +1,10 @@
+use std::fs::File;
+use std::io::Read;
 use std::path::PathBuf;
 
 use crate::rpu::{extension_metadata::blocks::ExtMetadataBlock, vdr_dm_data::CmVersion};
 
-use super::{CmXmlParser, XmlParserOpts};
+use super::{CmXmlParser, CmXmlWriter, XmlParserOpts, XmlWriterOpts};
 use anyhow::Result;
 
 fn assert_num_blocks_for_level(blocks: &[ExtMetadataBlock], level: u8, count: usize) {
@@ -292,3 +294,76 @@ fn parse_cmv4_2_xml_510() -> Result<()> {
 
     Ok(())
 }
+
+fn assert_xml_roundtrip(xml_file: &str) -> Result<()> {
+    let lib_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let assets_path = lib_path.parent().unwrap();
+
+    let opts = XmlParserOpts {
+        canvas_width: Some(3840),
+        canvas_height: Some(2160),
+    };
+    let parser = CmXmlParser::parse_file(&assets_path.join(xml_file), opts)?;
+    let rpus = parser.config.generate_rpu_list()?;
+
+    let writer_opts = XmlWriterOpts {
+        canvas_width: Some(3840),
+        canvas_height: Some(2160),
+    };
+    let writer = CmXmlWriter::new(&rpus, writer_opts)?;
+
+    let opts = XmlParserOpts {
+        canvas_width: Some(3840),
+        canvas_height: Some(2160),
+    };
+    let parser = CmXmlParser::new(writer.xml().to_string(), opts)?;
+    let written_rpus = parser.config.generate_rpu_list()?;
+
+    assert_eq!(rpus.len(), written_rpus.len());
+
+    for (rpu, written_rpu) in rpus.iter().zip(written_rpus.iter()) {
+        assert_eq!(
+            rpu.write_hevc_unspec62_nalu()?,
+            written_rpu.write_hevc_unspec62_nalu()?
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn write_cmv2_9_roundtrip() -> Result<()> {
+    assert_xml_roundtrip("assets/tests/cmv2_9.xml")
+}
+
+#[test]
+fn write_cmv4_0_2_roundtrip() -> Result<()> {
+    assert_xml_roundtrip("assets/tests/cmv4_0_2.xml")
+}
+
+#[test]
+fn write_cmv4_0_2_custom_displays_roundtrip() -> Result<()> {
+    assert_xml_roundtrip("assets/tests/cmv4_0_2_custom_displays.xml")
+}
+
+#[test]
+fn write_cmv4_2_roundtrip() -> Result<()> {
+    assert_xml_roundtrip("assets/tests/cmv4_2_xml_510.xml")
+}
+
+#[test]
+fn parse_non_contiguous_shots() -> Result<()> {
+    let lib_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let assets_path = lib_path.parent().unwrap();
+
+    let mut s = String::new();
+    File::open(assets_path.join("assets/tests/cmv4_0_2.xml"))?.read_to_string(&mut s)?;
+
+    // Create a gap between the first two shots
+    let s = s.replacen("<In>86520</In>", "<In>86521</In>", 1);
+
+    let res = CmXmlParser::new(s, XmlParserOpts::default());
+    assert!(res.is_err());
+
+    Ok(())
+}
@@ -0,0 +1,770 @@
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+
+#[cfg(feature = "fs")]
+use std::{fs::File, io::Write, path::Path};
+
+use crate::rpu::dovi_rpu::DoviRpu;
+use crate::rpu::extension_metadata::{blocks::*, primaries};
+use crate::rpu::vdr_dm_data::{CmVersion, VdrDmData};
+use crate::utils::{pq12_to_pq, pq_to_nits};
+
+use primaries::PREDEFINED_COLORSPACE_PRIMARIES;
+
+/// Preset target displays: ID, peak nits, min nits, primaries index, EOTF
+const KNOWN_TARGET_DISPLAYS: &[(u8, u16, f64, usize, &str)] = &[
+    (1, 100, 0.005, 1, "gamma_bt1886"),
+    (27, 600, 0.0, 0, "pq"),
+    (37, 2000, 0.0, 0, "pq"),
+    (48, 1000, 0.0, 0, "pq"),
+];
+
+/// First ID used for target displays that are not presets
+const CUSTOM_TARGET_DISPLAY_START_ID: u8 = 100;
+
+#[derive(Debug)]
+pub struct CmXmlWriter {
+    opts: XmlWriterOpts,
+    cm_version: CmVersion,
+    separator: char,
+
+    target_displays: BTreeMap<u8, WriterTargetDisplay>,
+
+    out: String,
+    depth: usize,
+}
+
+#[derive(Default, Debug)]
+pub struct XmlWriterOpts {
+    pub canvas_width: Option<u16>,
+    pub canvas_height: Option<u16>,
+}
+
+#[derive(Debug)]
+struct WriterTargetDisplay {
+    peak_nits: u16,
+    min_nits: f64,
+    primaries: [f64; 8],
+    eotf: &'static str,
+}
+
+#[derive(Debug)]
+struct XmlShot {
+    start: usize,
+    duration: usize,
+}
+
+impl CmXmlWriter {
+    /// Creates the Dolby Vision metadata XML from a list of RPUs.
+    /// The shots are split according to the scene cut flags.
+    pub fn new(rpus: &[DoviRpu], opts: XmlWriterOpts) -> Result<CmXmlWriter> {
+        let first_dm = match rpus.first().and_then(|rpu| rpu.vdr_dm_data.as_ref()) {
+            Some(dm) => dm,
+            None => bail!("No RPU with DM metadata to export"),
+        };
+
+        let cm_version = if first_dm.cmv40_metadata.is_some() {
+            CmVersion::V40
+        } else {
+            CmVersion::V29
+        };
+
+        let separator = if cm_version == CmVersion::V40 {
+            ' '
+        } else {
+            ','
+        };
+
+        let mut writer = CmXmlWriter {
+            opts,
+            cm_version,
+            separator,
+            target_displays: BTreeMap::new(),
+            out: String::new(),
+            depth: 0,
+        };
+
+        writer.target_displays = writer.find_target_displays(rpus)?;
+
+        writer.write_document(rpus, first_dm)?;
+
+        Ok(writer)
+    }
+
+    pub fn xml(&self) -> &str {
+        &self.out
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn write_file(&self, file_path: &Path) -> Result<()> {
+        let mut file = File::create(file_path)?;
+        file.write_all(self.out.as_bytes())?;
+
+        Ok(())
+    }
+
+    fn find_target_displays(&self, rpus: &[DoviRpu]) -> Result<BTreeMap<u8, WriterTargetDisplay>> {
+        let mut targets = BTreeMap::new();
+
+        for (id, peak_nits, min_nits, primaries_index, eotf) in KNOWN_TARGET_DISPLAYS {
+            targets.insert(
+                *id,
+                WriterTargetDisplay {
+                    peak_nits: *peak_nits,
+                    min_nits: *min_nits,
+                    primaries: PREDEFINED_COLORSPACE_PRIMARIES[*primaries_index],
+                    eotf,
+                },
+            );
+        }
+
+        let dm_iter = rpus.iter().filter_map(|rpu| rpu.vdr_dm_data.as_ref());
+
+        // Custom targets defined by L10 metadata
+        for dm in dm_iter.clone() {
+            for block in dm.level_blocks_iter(10) {
+                if let ExtMetadataBlock::Level10(l10) = block {
+                    let primaries = if l10.target_primary_index == 255 {
+                        int_primaries_to_f64(&[
+                            l10.target_primary_red_x,
+                            l10.target_primary_red_y,
+                            l10.target_primary_green_x,
+                            l10.target_primary_green_y,
+                            l10.target_primary_blue_x,
+                            l10.target_primary_blue_y,
+                            l10.target_primary_white_x,
+                            l10.target_primary_white_y,
+                        ])
+                    } else {
+                        match PREDEFINED_COLORSPACE_PRIMARIES.get(l10.target_primary_index as usize)
+                        {
+                            Some(primaries) => *primaries,
+                            None => bail!(
+                                "Invalid L10 target primary index {}",
+                                l10.target_primary_index
+                            ),
+                        }
+                    };
+
+                    targets.insert(
+                        l10.target_display_index,
+                        WriterTargetDisplay {
+                            peak_nits: target_nits_for_pq(l10.target_max_pq),
+                            min_nits: pq_code_to_nits(l10.target_min_pq),
+                            primaries,
+                            eotf: "pq",
+                        },
+                    );
+                }
+            }
+        }
+
+        // Targets only known from L2 metadata, assumed P3 D65
+        for dm in dm_iter.clone() {
+            for block in dm.level_blocks_iter(2) {
+                if let ExtMetadataBlock::Level2(l2) = block {
+                    let known_target = targets
+                        .values()
+                        .any(|t| target_max_pq(t.peak_nits) == l2.target_max_pq);
+
+                    if !known_target {
+                        let id = (CUSTOM_TARGET_DISPLAY_START_ID..=u8::MAX)
+                            .find(|id| !targets.contains_key(id));
+
+                        if let Some(id) = id {
+                            targets.insert(
+                                id,
+                                WriterTargetDisplay {
+                                    peak_nits: target_nits_for_pq(l2.target_max_pq),
+                                    min_nits: 0.0,
+                                    primaries: PREDEFINED_COLORSPACE_PRIMARIES[0],
+                                    eotf: "pq",
+                                },
+                            );
+                        } else {
+                            bail!("Too many target displays");
+                        }
+                    }
+                }
+            }
+        }
+
+        // L8 trims must refer to a known target display
+        for dm in dm_iter {
+            for block in dm.level_blocks_iter(8) {
+                if let ExtMetadataBlock::Level8(l8) = block {
+                    if !targets.contains_key(&l8.target_display_index) {
+                        bail!(
+                            "Unknown target display ID {} for L8 metadata",
+                            l8.target_display_index
+                        );
+                    }
+                }
+            }
+        }
+
+        Ok(targets)
+    }
+
+    fn write_document(&mut self, rpus: &[DoviRpu], first_dm: &VdrDmData) -> Result<()> {
+        self.out
+            .push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+
+        if self.is_cmv4() {
+            self.open(
+                "DolbyLabsMDF",
+                "xmlns=\"http://www.dolby.com/schemas/dvmd/4_0_2\"",
+            );
+            self.element("Version", "4.0.2");
+        } else {
+            self.open("DolbyLabsMDF", "version=\"2.0.5\"");
+        }
+
+        self.open("RevisionHistory", "");
+        self.open("Revision", "");
+        self.element("Software", "dovi_tool");
+        self.close("Revision");
+        self.close("RevisionHistory");
+
+        self.open("Outputs", "");
+
+        if self.is_cmv4() {
+            self.open("Output", "");
+            self.element("CompositionName", "dovi_tool");
+        } else {
+            self.open("Output", "name=\"dovi_tool\"");
+        }
+
+        self.element("NumberVideoTracks", "1");
+
+        if let Some((canvas_ar, image_ar)) = self.aspect_ratios(first_dm) {
+            self.element("CanvasAspectRatio", &format_float(canvas_ar));
+            self.element("ImageAspectRatio", &format_float(image_ar));
+        }
+
+        self.open("Video", "");
+
+        if self.is_cmv4() {
+            self.open("Track", "");
+            self.element("TrackName", "V1");
+        } else {
+            self.open("Track", "name=\"V1\"");
+        }
+
+        self.write_color_encoding();
+        self.write_level6(first_dm);
+        self.write_global_plugin_node(first_dm);
+
+        for (i, shot) in split_shots(rpus).iter().enumerate() {
+            self.write_shot(i, shot, &rpus[shot.start..shot.start + shot.duration])?;
+        }
+
+        self.close("Track");
+        self.close("Video");
+        self.close("Output");
+        self.close("Outputs");
+        self.close("DolbyLabsMDF");
+
+        Ok(())
+    }
+
+    fn write_color_encoding(&mut self) {
+        self.open("ColorEncoding", "");
+        self.write_primaries(&PREDEFINED_COLORSPACE_PRIMARIES[0]);
+        self.element("PeakBrightness", "10000");
+        self.element("MinimumBrightness", "0");
+        self.element("Encoding", "pq");
+        self.element("ColorSpace", "rgb");
+        self.element("SignalRange", "computer");
+        self.close("ColorEncoding");
+    }
+
+    fn write_level6(&mut self, dm: &VdrDmData) {
+        if let Some(ExtMetadataBlock::Level6(l6)) = dm.get_block(6) {
+            let attrs = if self.is_cmv4() { "level=\"6\"" } else { "" };
+
+            self.open("Level6", attrs);
+            self.element("MaxCLL", &l6.max_content_light_level.to_string());
+            self.element("MaxFALL", &l6.max_frame_average_light_level.to_string());
+            self.close("Level6");
+        }
+    }
+
+    fn write_global_plugin_node(&mut self, dm: &VdrDmData) {
+        self.open("PluginNode", "");
+
+        if self.is_cmv4() {
+            self.open("DVGlobalData", "level=\"0\"");
+        } else {
+            self.open("DolbyEDR", "");
+            self.open("Characteristics", "level=\"0\"");
+        }
+
+        self.write_mastering_display(dm);
+
+        let targets = std::mem::take(&mut self.target_displays);
+
+        for (id, target) in &targets {
+            let attrs = if self.is_cmv4() { "" } else { "level=\"0\"" };
+
+            self.open("TargetDisplay", attrs);
+            self.element("ID", &id.to_string());
+            self.element(
+                "Name",
+                &format!("{}-nit, {}", target.peak_nits, target.eotf),
+            );
+            self.write_primaries(&target.primaries);
+            self.element("PeakBrightness", &target.peak_nits.to_string());
+            self.element("MinimumBrightness", &format_float(target.min_nits));
+
+            if self.is_cmv4() {
+                self.element("EOTF", target.eotf);
+            } else {
+                self.element("Encoding", target.eotf);
+            }
+
+            self.close("TargetDisplay");
+        }
+
+        self.target_displays = targets;
+
+        if self.is_cmv4() {
+            self.close("DVGlobalData");
+
+            // The reference mode flag cannot be represented, skip the generation default
+            let default_l11 = ExtMetadataBlockLevel11::default_reference_cinema();
+            let l11 = match dm.get_block(11) {
+                Some(ExtMetadataBlock::Level11(l11))
+                    if l11.content_type != default_l11.content_type
+                        || l11.whitepoint != default_l11.whitepoint
+                        || l11.reference_mode_flag != default_l11.reference_mode_flag =>
+                {
+                    Some(l11)
+                }
+                _ => None,
+            };
+
+            if let Some(l11) = l11 {
+                self.open("Level11", "level=\"11\"");
+                self.element("ContentType", &l11.content_type.to_string());
+                self.element("IntendedWhitePoint", &l11.whitepoint.to_string());
+                self.close("Level11");
+            }
+
+            if let Some(ExtMetadataBlock::Level254(l254)) = dm.get_block(254) {
+                self.open("Level254", "level=\"254\"");
+                self.element("DMMode", &l254.dm_mode.to_string());
+                self.element("DMVersion", &l254.dm_version_index.to_string());
+                self.element("CMVersion", "4 0");
+                self.close("Level254");
+            }
+        } else {
+            self.close("Characteristics");
+            self.close("DolbyEDR");
+        }
+
+        self.close("PluginNode");
+    }
+
+    fn write_mastering_display(&mut self, dm: &VdrDmData) {
+        let (min_nits, peak_nits) = match dm.get_block(6) {
+            Some(ExtMetadataBlock::Level6(l6)) => (
+                l6.min_display_mastering_luminance as f64 / 10000.0,
+                l6.max_display_mastering_luminance,
+            ),
+            _ => (0.0, 1000),
+        };
+
+        let primaries = match dm.get_block(9) {
+            Some(ExtMetadataBlock::Level9(l9)) => level9_primaries(l9),
+            _ => None,
+        }
+        .unwrap_or(PREDEFINED_COLORSPACE_PRIMARIES[0]);
+
+        let attrs = if self.is_cmv4() { "" } else { "level=\"0\"" };
+
+        self.open("MasteringDisplay", attrs);
+        self.element("Name", &format!("{}-nit, ST.2084", peak_nits));
+        self.write_primaries(&primaries);
+        self.element("PeakBrightness", &peak_nits.to_string());
+        self.element("MinimumBrightness", &format_float(min_nits));
+        self.close("MasteringDisplay");
+    }
+
+    fn write_primaries(&mut self, primaries: &[f64; 8]) {
+        let pair =
+            |x: f64, y: f64, sep: char| format!("{}{}{}", format_float(x), sep, format_float(y));
+        let sep = self.separator;
+
+        self.open("Primaries", "");
+        self.element("Red", &pair(primaries[0], primaries[1], sep));
+        self.element("Green", &pair(primaries[2], primaries[3], sep));
+        self.element("Blue", &pair(primaries[4], primaries[5], sep));
+        self.close("Primaries");
+        self.element("WhitePoint", &pair(primaries[6], primaries[7], sep));
+    }
+
+    fn write_shot(&mut self, index: usize, shot: &XmlShot, rpus: &[DoviRpu]) -> Result<()> {
+        self.open("Shot", "");
+        self.element(
+            "UniqueID",
+            &format!("00000000-0000-0000-0000-{:012}", index),
+        );
+
+        self.open("Record", "");
+        self.element("In", &shot.start.to_string());
+        self.element("Duration", &shot.duration.to_string());
+        self.close("Record");
+
+        let shot_dynamic_data = self.dynamic_data(&rpus[0])?;
+        self.write_plugin_node(&shot_dynamic_data);
+
+        for (offset, rpu) in rpus.iter().enumerate().skip(1) {
+            let frame_dynamic_data = self.dynamic_data(rpu)?;
+
+            if frame_dynamic_data != shot_dynamic_data {
+                self.open("Frame", "");
+                self.element("EditOffset", &offset.to_string());
+                self.write_plugin_node(&frame_dynamic_data);
+                self.close("Frame");
+            }
+        }
+
+        self.close("Shot");
+
+        Ok(())
+    }
+
+    fn write_plugin_node(&mut self, dynamic_data: &[String]) {
+        self.open("PluginNode", "");
+
+        if self.is_cmv4() {
+            self.open("DVDynamicData", "");
+        }
+
+        for line in dynamic_data {
+            self.line(line);
+        }
+
+        if self.is_cmv4() {
+            self.close("DVDynamicData");
+        }
+
+        self.close("PluginNode");
+    }
+
+    /// Dynamic metadata of a frame, as a list of XML lines relative to the data node
+    fn dynamic_data(&self, rpu: &DoviRpu) -> Result<Vec<String>> {
+        let mut lines = Vec::new();
+
+        let dm = match &rpu.vdr_dm_data {
+            Some(dm) => dm,
+            None => return Ok(lines),
+        };
+
+        let levels: &[u8] = if self.is_cmv4() {
+            &[1, 2, 3, 5, 8, 9]
+        } else {
+            &[1, 2, 5]
+        };
+
+        for level in levels {
+            for block in dm.level_blocks_iter(*level) {
+                let values = match block {
+                    ExtMetadataBlock::Level1(l1) => vec![(
+                        "ImageCharacter",
+                        self.join(&[
+                            pq12_to_pq(l1.min_pq),
+                            pq12_to_pq(l1.avg_pq),
+                            pq12_to_pq(l1.max_pq),
+                        ]),
+                    )],
+                    ExtMetadataBlock::Level2(l2) => {
+                        let target_id = self.target_id_for_pq(l2.target_max_pq)?;
+
+                        let (lift, gain, gamma) =
+                            sop_to_lgg(l2.trim_slope, l2.trim_offset, l2.trim_power);
+
+                        vec![
+                            ("TID", target_id.to_string()),
+                            (
+                                "Trim",
+                                self.join(&[
+                                    0.0,
+                                    0.0,
+                                    0.0,
+                                    lift,
+                                    gain,
+                                    gamma,
+                                    offset_12bit(l2.trim_chroma_weight),
+                                    offset_12bit(l2.trim_saturation_gain),
+                                    (l2.ms_weight as f64 - 2048.0) / 2048.0,
+                                ]),
+                            ),
+                        ]
+                    }
+                    ExtMetadataBlock::Level3(l3) => vec![(
+                        "L1Offset",
+                        self.join(&[
+                            offset_12bit(l3.min_pq_offset),
+                            offset_12bit(l3.avg_pq_offset),
+                            offset_12bit(l3.max_pq_offset),
+                        ]),
+                    )],
+                    ExtMetadataBlock::Level5(l5) => match self.level5_aspect_ratios(l5) {
+                        Some((canvas_ar, image_ar)) => {
+                            vec![("AspectRatios", self.join(&[canvas_ar, image_ar]))]
+                        }
+                        None => continue,
+                    },
+                    ExtMetadataBlock::Level8(l8) => {
+                        let (lift, gain, gamma) =
+                            sop_to_lgg(l8.trim_slope, l8.trim_offset, l8.trim_power);
+
+                        let satvec = [
+                            l8.saturation_vector_field0,
+                            l8.saturation_vector_field1,
+                            l8.saturation_vector_field2,
+                            l8.saturation_vector_field3,
+                            l8.saturation_vector_field4,
+                            l8.saturation_vector_field5,
+                        ];
+                        let huevec = [
+                            l8.hue_vector_field0,
+                            l8.hue_vector_field1,
+                            l8.hue_vector_field2,
+                            l8.hue_vector_field3,
+                            l8.hue_vector_field4,
+                            l8.hue_vector_field5,
+                        ];
+
+                        vec![
+                            ("TID", l8.target_display_index.to_string()),
+                            (
+                                "L8Trim",
+                                self.join(&[
+                                    lift,
+                                    gain,
+                                    gamma,
+                                    offset_12bit(l8.trim_chroma_weight),
+                                    offset_12bit(l8.trim_saturation_gain),
+                                    offset_12bit(l8.ms_weight),
+                                ]),
+                            ),
+                            (
+                                "MidContrastBias",
+                                format_float(offset_12bit(l8.target_mid_contrast)),
+                            ),
+                            (
+                                "HighlightClipping",
+                                format_float(offset_12bit(l8.clip_trim)),
+                            ),
+                            ("SaturationVectorField", self.join(&satvec.map(offset_8bit))),
+                            ("HueVectorField", self.join(&huevec.map(offset_8bit))),
+                        ]
+                    }
+                    ExtMetadataBlock::Level9(l9) => match level9_primaries(l9) {
+                        Some(primaries) => vec![
+                            ("SourceColorModel", "255".to_string()),
+                            ("SourceColorPrimary", self.join(&primaries)),
+                        ],
+                        None => continue,
+                    },
+                    _ => continue,
+                };
+
+                let (tag, attrs) = if self.is_cmv4() {
+                    (format!("Level{}", level), format!(" level=\"{}\"", level))
+                } else {
+                    ("DolbyEDR".to_string(), format!(" level=\"{}\"", level))
+                };
+
+                lines.push(format!("<{}{}>", tag, attrs));
+
+                for (name, value) in values {
+                    lines.push(format!("  <{0}>{1}</{0}>", name, value));
+                }
+
+                lines.push(format!("</{}>", tag));
+            }
+        }
+
+        Ok(lines)
+    }
+
+    fn target_id_for_pq(&self, pq: u16) -> Result<u8> {
+        match self
+            .target_displays
+            .iter()
+            .find(|(_, t)| target_max_pq(t.peak_nits) == pq)
+        {
+            Some((id, _)) => Ok(*id),
+            None => bail!("No target display found for max PQ {}", pq),
+        }
+    }
+
+    fn aspect_ratios(&self, dm: &VdrDmData) -> Option<(f64, f64)> {
+        match dm.get_block(5) {
+            Some(ExtMetadataBlock::Level5(l5)) => self.level5_aspect_ratios(l5),
+            _ => self.level5_aspect_ratios(&ExtMetadataBlockLevel5::default()),
+        }
+    }
+
+    fn level5_aspect_ratios(&self, l5: &ExtMetadataBlockLevel5) -> Option<(f64, f64)> {
+        if let (Some(cw), Some(ch)) = (self.opts.canvas_width, self.opts.canvas_height) {
+            let (left, right, top, bottom) = l5.get_offsets();
+
+            let cw = cw as f64;
+            let ch = ch as f64;
+
+            let image_w = cw - (left + right) as f64;
+            let image_h = ch - (top + bottom) as f64;
+
+            Some((cw / ch, image_w / image_h))
+        } else {
+            None
+        }
+    }
+
+    fn join(&self, values: &[f64]) -> String {
+        values
+            .iter()
+            .map(|v| format_float(*v))
+            .collect::<Vec<String>>()
+            .join(&self.separator.to_string())
+    }
+
+    fn open(&mut self, tag: &str, attrs: &str) {
+        if attrs.is_empty() {
+            self.line(&format!("<{}>", tag));
+        } else {
+            self.line(&format!("<{} {}>", tag, attrs));
+        }
+
+        self.depth += 1;
+    }
+
+    fn close(&mut self, tag: &str) {
+        self.depth -= 1;
+        self.line(&format!("</{}>", tag));
+    }
+
+    fn element(&mut self, tag: &str, text: &str) {
+        self.line(&format!("<{0}>{1}</{0}>", tag, text));
+    }
+
+    fn line(&mut self, line: &str) {
+        writeln!(self.out, "{}{}", "  ".repeat(self.depth), line).ok();
+    }
+
+    pub fn is_cmv4(&self) -> bool {
+        self.cm_version == CmVersion::V40
+    }
+}
+
+/// Splits the RPU list into shots, according to the scene cut flags
+fn split_shots(rpus: &[DoviRpu]) -> Vec<XmlShot> {
+    let mut shots: Vec<XmlShot> = Vec::new();
+
+    for (i, rpu) in rpus.iter().enumerate() {
+        let is_scene_cut = rpu
+            .vdr_dm_data
+            .as_ref()
+            .map(|dm| dm.scene_refresh_flag == 1)
+            .unwrap_or(false);
+
+        match shots.last_mut() {
+            Some(shot) if !is_scene_cut => shot.duration += 1,
+            _ => shots.push(XmlShot {
+                start: i,
+                duration: 1,
+            }),
+        }
+    }
+
+    shots
+}
+
+fn level9_primaries(l9: &ExtMetadataBlockLevel9) -> Option<[f64; 8]> {
+    if l9.source_primary_index == 255 {
+        Some(int_primaries_to_f64(&[
+            l9.source_primary_red_x,
+            l9.source_primary_red_y,
+            l9.source_primary_green_x,
+            l9.source_primary_green_y,
+            l9.source_primary_blue_x,
+            l9.source_primary_blue_y,
+            l9.source_primary_white_x,
+            l9.source_primary_white_y,
+        ]))
+    } else {
+        let index = l9.source_primary_index as usize;
+
+        PREDEFINED_COLORSPACE_PRIMARIES
+            .get(index)
+            .copied()
+            .or_else(|| {
+                level9::PREDEFINED_REALDEVICE_PRIMARIES
+                    .get(index - PREDEFINED_COLORSPACE_PRIMARIES.len())
+                    .copied()
+            })
+    }
+}
+
+fn int_primaries_to_f64(primaries: &[u16; 8]) -> [f64; 8] {
+    primaries.map(|v| v as f64 / 32767.0)
+}
+
+fn pq_code_to_nits(pq: u16) -> f64 {
+    pq_to_nits(pq12_to_pq(pq))
+}
+
+/// Integer nits value which maps back to the same PQ code
+fn target_nits_for_pq(pq: u16) -> u16 {
+    let nits = pq_code_to_nits(pq).round() as u16;
+
+    [0, -1, 1, -2, 2]
+        .iter()
+        .map(|delta| (nits as i32 + delta) as u16)
+        .find(|n| target_max_pq(*n) == pq)
+        .unwrap_or(nits)
+}
+
+fn target_max_pq(nits: u16) -> u16 {
+    ExtMetadataBlockLevel2::from_nits(nits).target_max_pq
+}
+
+/// Value centered on 2048, normalized to [-1, 1]
+fn offset_12bit(value: u16) -> f64 {
+    (value as f64 - 2048.0) / 2048.0
+}
+
+/// Value centered on 128, normalized to [-1, 1]
+fn offset_8bit(value: u8) -> f64 {
+    (value as f64 - 128.0) / 128.0
+}
+
+/// Inverse of the lift/gain/gamma to slope/offset/power conversion from the XML parser
+fn sop_to_lgg(slope: u16, offset: u16, power: u16) -> (f64, f64, f64) {
+    let slope = offset_12bit(slope);
+    let offset = offset_12bit(offset);
+    let power = offset_12bit(power);
+
+    let gain = slope + offset;
+    let lift = 2.0 * offset / (gain + 2.0);
+    let gamma = 2.0 * (2.0 / (power + 2.0) - 1.0);
+
+    (lift, gain, gamma)
+}
+
+fn format_float(value: f64) -> String {
+    let s = format!("{:.6}", value);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+
+    if s == "-0" {
+        "0".to_string()
+    } else {
+        s.to_string()
+    }
+}
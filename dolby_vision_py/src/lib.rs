@@ -0,0 +1,393 @@
+use anyhow::Result;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use dolby_vision::rpu::dovi_rpu::DoviRpu;
+use dolby_vision::rpu::extension_metadata::blocks::{
+    ExtMetadataBlock, ExtMetadataBlockLevel1, ExtMetadataBlockLevel5, ExtMetadataBlockLevel6,
+};
+use dolby_vision::rpu::generate::GenerateConfig;
+use dolby_vision::xml::{CmXmlParser, CmXmlWriter, XmlParserOpts, XmlWriterOpts};
+
+/// Parsed Dolby Vision RPU
+#[pyclass(name = "DoviRpu", module = "dolby_vision")]
+#[derive(Clone)]
+pub struct PyDoviRpu {
+    rpu: DoviRpu,
+}
+
+#[pymethods]
+impl PyDoviRpu {
+    /// Parses an escaped RPU from a HEVC UNSPEC62 NAL unit.
+    /// The data can include the start code and/or the NAL header (0x7C01).
+    #[staticmethod]
+    fn parse_unspec62_nalu(data: &[u8]) -> PyResult<Self> {
+        Self::from_result(DoviRpu::parse_unspec62_nalu(data))
+    }
+
+    /// Parses an unescaped RPU, without emulation prevention bytes.
+    #[staticmethod]
+    fn parse_rpu(data: &[u8]) -> PyResult<Self> {
+        Self::from_result(DoviRpu::parse_rpu(data))
+    }
+
+    /// Deserializes an RPU from a JSON string, as written by `to_json`
+    #[staticmethod]
+    fn from_json(json: &str) -> PyResult<Self> {
+        let rpu = serde_json::from_str(json).map_err(|e| to_py_err(e.into()))?;
+
+        Ok(Self { rpu })
+    }
+
+    /// Dolby Vision profile guessed from the RPU
+    #[getter]
+    fn dovi_profile(&self) -> u8 {
+        self.rpu.dovi_profile
+    }
+
+    /// Profile 7 subprofile, FEL or MEL
+    #[getter]
+    fn subprofile(&self) -> Option<String> {
+        self.rpu.subprofile.clone()
+    }
+
+    /// Converts the RPU to be compatible with a different Dolby Vision profile.
+    /// See `dovi_tool convert --help` for the possible modes.
+    fn convert_with_mode(&mut self, mode: u8) -> PyResult<()> {
+        self.rpu.convert_with_mode(mode).map_err(to_py_err)
+    }
+
+    /// Sets the L5 active area offsets to zero
+    fn crop(&mut self) -> PyResult<()> {
+        self.rpu.crop().map_err(to_py_err)
+    }
+
+    /// Replaces the polynomial/MMR mapping by an identity mapping
+    fn remove_mapping(&mut self) {
+        self.rpu.remove_mapping()
+    }
+
+    /// Adds CM v4.0 metadata, if not already present
+    fn convert_to_cmv40(&mut self) -> PyResult<()> {
+        self.rpu.convert_to_cmv40().map_err(to_py_err)
+    }
+
+    /// L1 metadata block, if present
+    #[getter]
+    fn level1(&self) -> Option<PyLevel1> {
+        match self.get_block(1)? {
+            ExtMetadataBlock::Level1(block) => Some(PyLevel1::from(block)),
+            _ => None,
+        }
+    }
+
+    #[setter]
+    fn set_level1(&mut self, block: PyLevel1) -> PyResult<()> {
+        self.replace_block(ExtMetadataBlock::Level1(block.into()))
+    }
+
+    /// L5 metadata block, if present
+    #[getter]
+    fn level5(&self) -> Option<PyLevel5> {
+        match self.get_block(5)? {
+            ExtMetadataBlock::Level5(block) => Some(PyLevel5::from(block)),
+            _ => None,
+        }
+    }
+
+    #[setter]
+    fn set_level5(&mut self, block: PyLevel5) -> PyResult<()> {
+        self.replace_block(ExtMetadataBlock::Level5(block.into()))
+    }
+
+    /// L6 metadata block, if present
+    #[getter]
+    fn level6(&self) -> Option<PyLevel6> {
+        match self.get_block(6)? {
+            ExtMetadataBlock::Level6(block) => Some(PyLevel6::from(block)),
+            _ => None,
+        }
+    }
+
+    #[setter]
+    fn set_level6(&mut self, block: PyLevel6) -> PyResult<()> {
+        self.replace_block(ExtMetadataBlock::Level6(block.into()))
+    }
+
+    /// Serializes the RPU to a JSON string
+    fn to_json(&self) -> PyResult<String> {
+        serde_json::to_string(&self.rpu).map_err(|e| to_py_err(e.into()))
+    }
+
+    /// Writes the RPU as an escaped HEVC UNSPEC62 NAL unit, including the NAL header (0x7C01).
+    fn write_hevc_unspec62_nalu<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let data = self.rpu.write_hevc_unspec62_nalu().map_err(to_py_err)?;
+        Ok(PyBytes::new(py, &data))
+    }
+
+    /// Writes the unescaped RPU, without NAL header.
+    fn write_rpu<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let data = self.rpu.write_rpu().map_err(to_py_err)?;
+        Ok(PyBytes::new(py, &data))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("DoviRpu(dovi_profile={})", self.rpu.dovi_profile)
+    }
+}
+
+impl PyDoviRpu {
+    fn from_result(res: Result<DoviRpu>) -> PyResult<Self> {
+        res.map(|rpu| Self { rpu }).map_err(to_py_err)
+    }
+
+    fn get_block(&self, level: u8) -> Option<&ExtMetadataBlock> {
+        self.rpu.vdr_dm_data.as_ref()?.get_block(level)
+    }
+
+    /// Replaces the block of the same level, validated when writing the RPU
+    fn replace_block(&mut self, block: ExtMetadataBlock) -> PyResult<()> {
+        let vdr_dm_data = self
+            .rpu
+            .vdr_dm_data
+            .as_mut()
+            .ok_or_else(|| PyValueError::new_err("The RPU has no DM metadata"))?;
+
+        vdr_dm_data
+            .replace_metadata_block(block)
+            .map_err(to_py_err)?;
+        self.rpu.modified = true;
+
+        Ok(())
+    }
+}
+
+/// L1 metadata: min, max and average PQ of the frame, in 12-bit
+#[pyclass(name = "Level1", module = "dolby_vision")]
+#[derive(Clone)]
+pub struct PyLevel1 {
+    #[pyo3(get, set)]
+    min_pq: u16,
+    #[pyo3(get, set)]
+    max_pq: u16,
+    #[pyo3(get, set)]
+    avg_pq: u16,
+}
+
+#[pymethods]
+impl PyLevel1 {
+    #[new]
+    fn new(min_pq: u16, max_pq: u16, avg_pq: u16) -> Self {
+        Self {
+            min_pq,
+            max_pq,
+            avg_pq,
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Level1(min_pq={}, max_pq={}, avg_pq={})",
+            self.min_pq, self.max_pq, self.avg_pq
+        )
+    }
+}
+
+impl From<&ExtMetadataBlockLevel1> for PyLevel1 {
+    fn from(block: &ExtMetadataBlockLevel1) -> Self {
+        Self::new(block.min_pq, block.max_pq, block.avg_pq)
+    }
+}
+
+impl From<PyLevel1> for ExtMetadataBlockLevel1 {
+    fn from(block: PyLevel1) -> Self {
+        Self {
+            min_pq: block.min_pq,
+            max_pq: block.max_pq,
+            avg_pq: block.avg_pq,
+        }
+    }
+}
+
+/// L5 metadata: active area offsets, in pixels
+#[pyclass(name = "Level5", module = "dolby_vision")]
+#[derive(Clone)]
+pub struct PyLevel5 {
+    #[pyo3(get, set)]
+    left: u16,
+    #[pyo3(get, set)]
+    right: u16,
+    #[pyo3(get, set)]
+    top: u16,
+    #[pyo3(get, set)]
+    bottom: u16,
+}
+
+#[pymethods]
+impl PyLevel5 {
+    #[new]
+    fn new(left: u16, right: u16, top: u16, bottom: u16) -> Self {
+        Self {
+            left,
+            right,
+            top,
+            bottom,
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Level5(left={}, right={}, top={}, bottom={})",
+            self.left, self.right, self.top, self.bottom
+        )
+    }
+}
+
+impl From<&ExtMetadataBlockLevel5> for PyLevel5 {
+    fn from(block: &ExtMetadataBlockLevel5) -> Self {
+        let (left, right, top, bottom) = block.get_offsets();
+
+        Self::new(left, right, top, bottom)
+    }
+}
+
+impl From<PyLevel5> for ExtMetadataBlockLevel5 {
+    fn from(block: PyLevel5) -> Self {
+        Self::from_offsets(block.left, block.right, block.top, block.bottom)
+    }
+}
+
+/// L6 metadata: ST2086 mastering display and HDR10 content light levels, in nits
+#[pyclass(name = "Level6", module = "dolby_vision")]
+#[derive(Clone)]
+pub struct PyLevel6 {
+    #[pyo3(get, set)]
+    max_display_mastering_luminance: u16,
+    #[pyo3(get, set)]
+    min_display_mastering_luminance: u16,
+    #[pyo3(get, set)]
+    max_content_light_level: u16,
+    #[pyo3(get, set)]
+    max_frame_average_light_level: u16,
+}
+
+#[pymethods]
+impl PyLevel6 {
+    #[new]
+    fn new(
+        max_display_mastering_luminance: u16,
+        min_display_mastering_luminance: u16,
+        max_content_light_level: u16,
+        max_frame_average_light_level: u16,
+    ) -> Self {
+        Self {
+            max_display_mastering_luminance,
+            min_display_mastering_luminance,
+            max_content_light_level,
+            max_frame_average_light_level,
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Level6(max_display_mastering_luminance={}, min_display_mastering_luminance={}, max_content_light_level={}, max_frame_average_light_level={})",
+            self.max_display_mastering_luminance,
+            self.min_display_mastering_luminance,
+            self.max_content_light_level,
+            self.max_frame_average_light_level
+        )
+    }
+}
+
+impl From<&ExtMetadataBlockLevel6> for PyLevel6 {
+    fn from(block: &ExtMetadataBlockLevel6) -> Self {
+        Self::new(
+            block.max_display_mastering_luminance,
+            block.min_display_mastering_luminance,
+            block.max_content_light_level,
+            block.max_frame_average_light_level,
+        )
+    }
+}
+
+impl From<PyLevel6> for ExtMetadataBlockLevel6 {
+    fn from(block: PyLevel6) -> Self {
+        Self {
+            max_display_mastering_luminance: block.max_display_mastering_luminance,
+            min_display_mastering_luminance: block.min_display_mastering_luminance,
+            max_content_light_level: block.max_content_light_level,
+            max_frame_average_light_level: block.max_frame_average_light_level,
+        }
+    }
+}
+
+/// Generates a list of RPUs from a JSON generation config.
+/// The format is the same as `dovi_tool generate --json`.
+#[pyfunction]
+fn generate_rpus(config_json: &str) -> PyResult<Vec<PyDoviRpu>> {
+    let config: GenerateConfig =
+        serde_json::from_str(config_json).map_err(|e| to_py_err(e.into()))?;
+
+    wrap_rpus(config.generate_rpu_list())
+}
+
+/// Generates a list of RPUs from a Dolby Vision XML metadata string
+#[pyfunction]
+#[pyo3(signature = (xml, canvas_width=None, canvas_height=None))]
+fn generate_rpus_from_xml(
+    xml: String,
+    canvas_width: Option<u16>,
+    canvas_height: Option<u16>,
+) -> PyResult<Vec<PyDoviRpu>> {
+    let opts = XmlParserOpts {
+        canvas_width,
+        canvas_height,
+    };
+
+    let parser = CmXmlParser::new(xml, opts).map_err(to_py_err)?;
+
+    wrap_rpus(parser.config.generate_rpu_list())
+}
+
+/// Writes a list of RPUs as Dolby Vision XML metadata
+#[pyfunction]
+#[pyo3(signature = (rpus, canvas_width=None, canvas_height=None))]
+fn rpus_to_xml(
+    rpus: Vec<PyDoviRpu>,
+    canvas_width: Option<u16>,
+    canvas_height: Option<u16>,
+) -> PyResult<String> {
+    let rpus: Vec<DoviRpu> = rpus.into_iter().map(|e| e.rpu).collect();
+    let opts = XmlWriterOpts {
+        canvas_width,
+        canvas_height,
+    };
+
+    let writer = CmXmlWriter::new(&rpus, opts).map_err(to_py_err)?;
+
+    Ok(writer.xml().to_string())
+}
+
+fn wrap_rpus(res: Result<Vec<DoviRpu>>) -> PyResult<Vec<PyDoviRpu>> {
+    res.map(|rpus| rpus.into_iter().map(|rpu| PyDoviRpu { rpu }).collect())
+        .map_err(to_py_err)
+}
+
+fn to_py_err(e: anyhow::Error) -> PyErr {
+    PyValueError::new_err(format!("{:#}", e))
+}
+
+#[pymodule]
+#[pyo3(name = "dolby_vision")]
+fn dolby_vision_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyDoviRpu>()?;
+    m.add_class::<PyLevel1>()?;
+    m.add_class::<PyLevel5>()?;
+    m.add_class::<PyLevel6>()?;
+    m.add_function(wrap_pyfunction!(generate_rpus, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_rpus_from_xml, m)?)?;
+    m.add_function(wrap_pyfunction!(rpus_to_xml, m)?)?;
+
+    Ok(())
+}
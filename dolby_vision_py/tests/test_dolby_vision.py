@@ -0,0 +1,80 @@
+from pathlib import Path
+
+import pytest
+
+import dolby_vision
+
+ASSETS = Path(__file__).resolve().parents[2] / "assets"
+
+
+def fel_rpu():
+    data = (ASSETS / "tests" / "fel_orig.bin").read_bytes()
+    return dolby_vision.DoviRpu.parse_unspec62_nalu(data)
+
+
+def test_parse():
+    rpu = fel_rpu()
+
+    assert rpu.dovi_profile == 7
+    assert rpu.subprofile == "FEL"
+
+
+def test_json_round_trip():
+    rpu = fel_rpu()
+    parsed = dolby_vision.DoviRpu.from_json(rpu.to_json())
+
+    assert parsed.dovi_profile == rpu.dovi_profile
+    assert parsed.write_hevc_unspec62_nalu() == rpu.write_hevc_unspec62_nalu()
+
+
+def test_from_json_invalid():
+    with pytest.raises(ValueError):
+        dolby_vision.DoviRpu.from_json("{}")
+
+
+def test_level1():
+    rpu = fel_rpu()
+    assert rpu.level1 is not None
+
+    rpu.level1 = dolby_vision.Level1(min_pq=0, max_pq=2081, avg_pq=1000)
+
+    parsed = dolby_vision.DoviRpu.parse_unspec62_nalu(rpu.write_hevc_unspec62_nalu())
+    level1 = parsed.level1
+
+    assert (level1.min_pq, level1.max_pq, level1.avg_pq) == (0, 2081, 1000)
+
+
+def test_level5():
+    rpu = fel_rpu()
+
+    level5 = rpu.level5
+    level5.top = 276
+    level5.bottom = 276
+    rpu.level5 = level5
+
+    parsed = dolby_vision.DoviRpu.parse_unspec62_nalu(rpu.write_hevc_unspec62_nalu())
+    level5 = parsed.level5
+
+    assert (level5.left, level5.right, level5.top, level5.bottom) == (0, 0, 276, 276)
+
+
+def test_level6():
+    rpu = fel_rpu()
+
+    rpu.level6 = dolby_vision.Level6(1000, 1, 1000, 400)
+
+    parsed = dolby_vision.DoviRpu.parse_rpu(rpu.write_rpu())
+    level6 = parsed.level6
+
+    assert level6.max_display_mastering_luminance == 1000
+    assert level6.min_display_mastering_luminance == 1
+    assert level6.max_content_light_level == 1000
+    assert level6.max_frame_average_light_level == 400
+
+
+def test_invalid_block():
+    rpu = fel_rpu()
+    rpu.level1 = dolby_vision.Level1(min_pq=0, max_pq=4095, avg_pq=5000)
+
+    with pytest.raises(ValueError):
+        rpu.write_hevc_unspec62_nalu()
@@ -0,0 +1,111 @@
+use clap::{Args, ValueHint};
+use std::path::PathBuf;
+
+use crate::dovi::rpu_injector::MismatchFix;
+
+#[derive(Args, Debug)]
+pub struct AddDvArgs {
+    #[clap(
+        name = "input",
+        help = "Sets the input HDR10 HEVC file to use",
+        long,
+        short = 'i',
+        conflicts_with = "input_pos",
+        required_unless_present = "input_pos",
+        value_hint = ValueHint::FilePath,
+    )]
+    pub input: Option<PathBuf>,
+
+    #[clap(
+        name = "input_pos",
+        help = "Sets the input HDR10 HEVC file to use (positional)",
+        conflicts_with = "input",
+        required_unless_present = "input",
+        value_hint = ValueHint::FilePath
+    )]
+    pub input_pos: Option<PathBuf>,
+
+    #[clap(
+        long,
+        short = 'o',
+        help = "Output HEVC file location",
+        value_hint = ValueHint::FilePath
+    )]
+    pub output: Option<PathBuf>,
+
+    #[clap(
+        name = "json",
+        long,
+        short = 'j',
+        help = "Generator config JSON file, for the static metadata or the overrides of the HDR10+ or madVR metadata",
+        required_unless_present_any = &["hdr10plus-json", "madvr-file"],
+        value_hint = ValueHint::FilePath
+    )]
+    pub json_file: Option<PathBuf>,
+
+    #[clap(
+        name = "hdr10plus-json",
+        long,
+        help = "HDR10+ JSON file to generate from",
+        conflicts_with = "madvr-file",
+        value_hint = ValueHint::FilePath,
+    )]
+    pub hdr10plus_json: Option<PathBuf>,
+
+    #[clap(
+        name = "madvr-file",
+        long,
+        help = "madVR measurement file to generate from",
+        value_hint = ValueHint::FilePath
+    )]
+    pub madvr_file: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "madVR source: use custom per-frame target nits if available"
+    )]
+    pub use_custom_targets: bool,
+
+    #[clap(
+        long,
+        help = "Scene file to import the scene cuts from: Av1an scenes.json, x265 CSV log or frame list",
+        value_hint = ValueHint::FilePath
+    )]
+    pub scene_file: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Frame rate of the video for HH:MM:SS.mmm timecodes in the JSON config, e.g. 24000/1001"
+    )]
+    pub fps: Option<String>,
+
+    #[clap(
+        long,
+        conflicts_with = "fps",
+        help = "Matroska timestamps v2 file of a variable frame rate video, for HH:MM:SS.mmm timecodes in the JSON config",
+        value_hint = ValueHint::FilePath
+    )]
+    pub timestamps: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Chapters file for the chapter references in the JSON config, as OGM or Matroska XML chapters",
+        value_hint = ValueHint::FilePath
+    )]
+    pub chapters: Option<PathBuf>,
+
+    #[clap(
+        long,
+        takes_value = false,
+        help = "Disable adding AUD NALUs between frames"
+    )]
+    pub no_add_aud: bool,
+
+    #[clap(
+        arg_enum,
+        long,
+        help = "Fix for mismatched generated RPU and video frame counts. truncate: cuts the longer of the two, duplicate-last: repeats the last RPU for the missing frames, fail: reports where the counts diverge",
+        default_value = "duplicate-last"
+    )]
+    pub fix: MismatchFix,
+}
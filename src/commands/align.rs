@@ -0,0 +1,56 @@
+use clap::{Args, ValueHint};
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct AlignArgs {
+    #[clap(
+        name = "input",
+        help = "Sets the input RPU file to use",
+        long,
+        short = 'i',
+        conflicts_with = "input_pos",
+        required_unless_present = "input_pos",
+        value_hint = ValueHint::FilePath,
+    )]
+    pub input: Option<PathBuf>,
+
+    #[clap(
+        name = "input_pos",
+        help = "Sets the input RPU file to use (positional)",
+        conflicts_with = "input",
+        required_unless_present = "input",
+        value_hint = ValueHint::FilePath
+    )]
+    pub input_pos: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "RPU file of the target video, for its scene cuts and length",
+        conflicts_with = "scene-file",
+        required_unless_present = "scene-file",
+        value_hint = ValueHint::FilePath
+    )]
+    pub target_rpu: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Scene file of the target video: Av1an scenes.json, x265 CSV log or list of scene start frames",
+        value_hint = ValueHint::FilePath
+    )]
+    pub scene_file: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Number of frames of the target video, when the scene file doesn't specify it",
+        requires = "scene-file"
+    )]
+    pub length: Option<usize>,
+
+    #[clap(
+        long,
+        short = 'o',
+        help = "Aligned RPU output file location",
+        value_hint = ValueHint::FilePath
+    )]
+    pub rpu_out: Option<PathBuf>,
+}
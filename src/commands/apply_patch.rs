@@ -0,0 +1,28 @@
+use clap::{Args, ValueHint};
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct ApplyPatchArgs {
+    #[clap(
+        name = "input",
+        help = "Sets the original RPU file the patch was created from",
+        value_hint = ValueHint::FilePath
+    )]
+    pub input: PathBuf,
+
+    #[clap(
+        long,
+        short = 'p',
+        help = "Sets the patch file to apply",
+        value_hint = ValueHint::FilePath
+    )]
+    pub patch: PathBuf,
+
+    #[clap(
+        long,
+        short = 'o',
+        help = "Patched RPU output file location. Defaults to `RPU_patched.bin`",
+        value_hint = ValueHint::FilePath
+    )]
+    pub rpu_out: Option<PathBuf>,
+}
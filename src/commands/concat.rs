@@ -0,0 +1,22 @@
+use clap::{Args, ValueHint};
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct ConcatArgs {
+    #[clap(
+        name = "inputs",
+        help = "Sets the input RPU files to join, in order",
+        required = true,
+        min_values = 2,
+        value_hint = ValueHint::FilePath
+    )]
+    pub inputs: Vec<PathBuf>,
+
+    #[clap(
+        long,
+        short = 'o',
+        help = "Joined RPU output file location",
+        value_hint = ValueHint::FilePath
+    )]
+    pub rpu_out: Option<PathBuf>,
+}
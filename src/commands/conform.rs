@@ -0,0 +1,39 @@
+use clap::{Args, ValueHint};
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct ConformArgs {
+    #[clap(
+        name = "input",
+        help = "Sets the input RPU file to use",
+        long,
+        short = 'i',
+        conflicts_with = "input_pos",
+        required_unless_present = "input_pos",
+        value_hint = ValueHint::FilePath,
+    )]
+    pub input: Option<PathBuf>,
+
+    #[clap(
+        name = "input_pos",
+        help = "Sets the input RPU file to use (positional)",
+        conflicts_with = "input",
+        required_unless_present = "input",
+        value_hint = ValueHint::FilePath
+    )]
+    pub input_pos: Option<PathBuf>,
+
+    #[clap(
+        long,
+        short = 'o',
+        help = "Conformed RPU output file location",
+        value_hint = ValueHint::FilePath
+    )]
+    pub rpu_out: Option<PathBuf>,
+
+    #[clap(long, help = "Frame rate of the input RPU file, e.g. 25 or 24000/1001")]
+    pub from_fps: String,
+
+    #[clap(long, help = "Frame rate of the converted video, e.g. 30000/1001")]
+    pub to_fps: String,
+}
@@ -5,23 +5,25 @@ use std::path::PathBuf;
 pub struct ConvertArgs {
     #[clap(
         name = "input",
-        help = "Sets the input HEVC file to use, or piped with -",
+        help = "Sets the input HEVC files to use, or piped with -. File names can have * and ? wildcards",
         long,
         short = 'i',
+        multiple_occurrences = true,
         conflicts_with = "input_pos",
         required_unless_present = "input_pos",
         value_hint = ValueHint::FilePath,
     )]
-    pub input: Option<PathBuf>,
+    pub input: Vec<PathBuf>,
 
     #[clap(
         name = "input_pos",
-        help = "Sets the input HEVC file to use, or piped with - (positional)",
+        help = "Sets the input HEVC files to use, or piped with - (positional)",
+        multiple_values = true,
         conflicts_with = "input",
         required_unless_present = "input",
         value_hint = ValueHint::FilePath
     )]
-    pub input_pos: Option<PathBuf>,
+    pub input_pos: Vec<PathBuf>,
 
     #[clap(
         long,
@@ -31,6 +33,79 @@ pub struct ConvertArgs {
     )]
     pub output: Option<PathBuf>,
 
+    #[clap(
+        long,
+        conflicts_with = "output",
+        help = "Output file names for multiple inputs, {stem} is replaced by the input file name without extension, {dir} by its directory and {rel} by its directory relative to the input directory. Default: {stem}.BL_EL_RPU.hevc, or {stem}.BL_RPU.hevc when discarding the EL"
+    )]
+    pub output_template: Option<String>,
+
+    #[clap(
+        long,
+        help = "Also processes the files in the subdirectories of the input directories"
+    )]
+    pub recursive: bool,
+
+    #[clap(
+        long,
+        use_value_delimiter = true,
+        help = "Extensions of the files processed in the input directories. Comma separated, default: hevc,h265,265,h264,264,avc,h266,266,vvc"
+    )]
+    pub extensions: Vec<String>,
+
     #[clap(short = 'd', long, help = "Discard the EL stream")]
     pub discard: bool,
+
+    #[clap(
+        long,
+        help = "Writes the RPUs where found in the input. By default, the RPUs and EL NALUs found before the first slice of an access unit are moved to the end of the previous one"
+    )]
+    pub keep_rpu_position: bool,
+
+    #[clap(
+        long,
+        requires = "discard",
+        help = "Rewrites the VPS describing the discarded EL layers, to describe the BL only. By default, the inconsistent BL parameter sets are reported"
+    )]
+    pub rewrite_vps: bool,
+
+    #[clap(
+        long,
+        help = "Synthesizes the L2 trim for a target display peak brightness in nits, from the existing trims"
+    )]
+    pub retarget_trim: Option<u16>,
+
+    #[clap(
+        long,
+        help = "Inserts HDR10 MDCV and CLL SEI messages at every IRAP frame, derived from the RPU L6 metadata unless set"
+    )]
+    pub insert_hdr10_sei: bool,
+
+    #[clap(
+        long,
+        help = "Mastering display of the HDR10 SEI, x265 format: G(x,y)B(x,y)R(x,y)WP(x,y)L(max,min)",
+        requires = "insert-hdr10-sei"
+    )]
+    pub master_display: Option<String>,
+
+    #[clap(
+        long,
+        help = "Content light level of the HDR10 SEI, x265 format: MaxCLL,MaxFALL",
+        requires = "insert-hdr10-sei"
+    )]
+    pub max_cll: Option<String>,
+
+    #[clap(
+        long,
+        conflicts_with_all = &["output", "output-template"],
+        help = "Converts the RPUs without writing the output, and prints a summary of the changes by RPU section and level"
+    )]
+    pub dry_run: bool,
+
+    #[clap(
+        long,
+        help = "Writes a JSON report of the EL contribution per scene, to judge the loss of a mode 2 conversion of FEL. The EL residual is measured by the coded size of the EL pictures",
+        value_hint = ValueHint::FilePath
+    )]
+    pub el_report: Option<PathBuf>,
 }
@@ -0,0 +1,27 @@
+use clap::{Args, ValueHint};
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct CreatePatchArgs {
+    #[clap(
+        name = "input",
+        help = "Sets the original RPU file",
+        value_hint = ValueHint::FilePath
+    )]
+    pub input: PathBuf,
+
+    #[clap(
+        name = "input2",
+        help = "Sets the fixed RPU file",
+        value_hint = ValueHint::FilePath
+    )]
+    pub input2: PathBuf,
+
+    #[clap(
+        long,
+        short = 'o',
+        help = "Patch output file location. Defaults to `RPU_patch.json`",
+        value_hint = ValueHint::FilePath
+    )]
+    pub output: Option<PathBuf>,
+}
@@ -1,6 +1,8 @@
 use clap::{Args, ValueHint};
 use std::path::PathBuf;
 
+use crate::dovi::checkpoint::parse_checkpoint_interval;
+
 #[derive(Args, Debug)]
 pub struct DemuxArgs {
     #[clap(
@@ -41,4 +43,63 @@ pub struct DemuxArgs {
 
     #[clap(long, help = "Output the EL file only")]
     pub el_only: bool,
+
+    #[clap(
+        long,
+        help = "Keeps the EL NAL units untouched, with their UNSPEC63 (0x7E01) NAL header or their nuh_layer_id"
+    )]
+    pub keep_el_prefix: bool,
+
+    #[clap(
+        long,
+        help = "RPU output file location, the RPUs are also kept in the EL",
+        value_hint = ValueHint::FilePath
+    )]
+    pub rpu_out: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "HDR10+ JSON output file location, extracted from the BL",
+        value_hint = ValueHint::FilePath
+    )]
+    pub hdr10plus_out: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Dual-track MP4 output file location, with the BL and EL as separate tracks",
+        conflicts_with_all = &["bl-out", "el-out", "el-only", "rpu-out", "hdr10plus-out"],
+        value_hint = ValueHint::FilePath
+    )]
+    pub mp4_out: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Frame rate of the MP4 output, e.g. 24000/1001. Defaults to 24000/1001",
+        requires = "mp4-out"
+    )]
+    pub fps: Option<String>,
+
+    #[clap(
+        long,
+        help = "Demuxes ranges of GOPs in parallel with the number of threads. Requires a seekable Annex B input file",
+        conflicts_with_all = &["mp4-out", "hdr10plus-out"]
+    )]
+    pub threads: Option<usize>,
+
+    #[clap(
+        long,
+        help = "Saves the progress to the checkpoint file, and resumes the demux from it when it exists. \
+                Requires a seekable Annex B input file",
+        conflicts_with_all = &["mp4-out", "hdr10plus-out", "threads"],
+        value_hint = ValueHint::FilePath
+    )]
+    pub checkpoint: Option<PathBuf>,
+
+    #[clap(
+        long,
+        parse(try_from_str = parse_checkpoint_interval),
+        help = "Input bytes demuxed between checkpoints, in bytes. Accepts K, M and G suffixes. Defaults to 1G",
+        requires = "checkpoint"
+    )]
+    pub checkpoint_interval: Option<u64>,
 }
@@ -0,0 +1,54 @@
+use clap::{Args, ValueHint};
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct DetectActiveAreaArgs {
+    #[clap(
+        name = "input",
+        help = "Sets the input RPU file to use",
+        long,
+        short = 'i',
+        conflicts_with = "input_pos",
+        required_unless_present = "input_pos",
+        value_hint = ValueHint::FilePath,
+    )]
+    pub input: Option<PathBuf>,
+
+    #[clap(
+        name = "input_pos",
+        help = "Sets the input RPU file to use (positional)",
+        conflicts_with = "input",
+        required_unless_present = "input",
+        value_hint = ValueHint::FilePath
+    )]
+    pub input_pos: Option<PathBuf>,
+
+    #[clap(
+        long,
+        short = 'f',
+        help = "Decoded frames as raw 8 bit luma (gray pixel format), or piped with -",
+        value_hint = ValueHint::FilePath
+    )]
+    pub frames: PathBuf,
+
+    #[clap(long, help = "Width of the decoded frames")]
+    pub width: usize,
+
+    #[clap(long, help = "Height of the decoded frames")]
+    pub height: usize,
+
+    #[clap(
+        long,
+        help = "Maximum average luma of a black bar line",
+        default_value = "24"
+    )]
+    pub threshold: u8,
+
+    #[clap(
+        long,
+        short = 'o',
+        help = "Modified RPU output file location",
+        value_hint = ValueHint::FilePath
+    )]
+    pub rpu_out: Option<PathBuf>,
+}
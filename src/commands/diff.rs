@@ -0,0 +1,49 @@
+use clap::{Args, ValueHint};
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    #[clap(
+        name = "input",
+        help = "Sets the first RPU file to compare",
+        value_hint = ValueHint::FilePath
+    )]
+    pub input: PathBuf,
+
+    #[clap(
+        name = "input2",
+        help = "Sets the second RPU file to compare",
+        required_unless_present = "xml",
+        conflicts_with = "xml",
+        value_hint = ValueHint::FilePath
+    )]
+    pub input2: Option<PathBuf>,
+
+    #[clap(
+        short = 'x',
+        long,
+        help = "Compares the RPU file to an XML metadata file instead, reporting the mismatches per shot",
+        value_hint = ValueHint::FilePath
+    )]
+    pub xml: Option<PathBuf>,
+
+    #[clap(long, help = "Canvas width to compare the L5 metadata to the XML")]
+    pub canvas_width: Option<u16>,
+
+    #[clap(long, help = "Canvas height to compare the L5 metadata to the XML")]
+    pub canvas_height: Option<u16>,
+
+    #[clap(
+        long,
+        help = "Maximum difference to the XML values, as 12 bit codes",
+        default_value = "0"
+    )]
+    pub tolerance: u16,
+
+    #[clap(
+        long,
+        short = 's',
+        help = "Only prints the number of differing frames, per RPU section"
+    )]
+    pub summary: bool,
+}
@@ -1,6 +1,9 @@
 use clap::{Args, ValueHint};
 use std::path::PathBuf;
 
+use crate::dovi::edit_presets::EditPreset;
+use crate::dovi::l1_csv::L1CsvUnits;
+
 #[derive(Args, Debug)]
 pub struct EditorArgs {
     #[clap(
@@ -28,15 +31,80 @@ pub struct EditorArgs {
         long,
         short = 'j',
         help = "Sets the edit JSON file to use",
+        required_unless_present_any = &["l1-csv", "extend", "preset"],
         value_hint = ValueHint::FilePath
     )]
-    pub json_file: PathBuf,
+    pub json_file: Option<PathBuf>,
+
+    #[clap(
+        arg_enum,
+        long,
+        help = "Built-in edit config for a common fix. The JSON config fields replace the preset ones"
+    )]
+    pub preset: Option<EditPreset>,
 
     #[clap(
         long,
         short = 'o',
-        help = "Modified RPU output file location",
+        help = "Modified RPU output file location, or - to stream the RPUs to stdout",
         value_hint = ValueHint::FilePath
     )]
     pub rpu_out: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Scene file to import the scene cuts from: Av1an scenes.json, x265 CSV log or frame list",
+        value_hint = ValueHint::FilePath
+    )]
+    pub scene_file: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "CSV file of per-scene L1 metadata to import, with scene_start,min,mid,max rows",
+        value_hint = ValueHint::FilePath
+    )]
+    pub l1_csv: Option<PathBuf>,
+
+    #[clap(
+        arg_enum,
+        long,
+        help = "Units of the L1 CSV values: 12 bit PQ codes or nits",
+        default_value = "pq",
+        requires = "l1-csv"
+    )]
+    pub l1_csv_units: L1CsvUnits,
+
+    #[clap(
+        long,
+        help = "Frame rate of the video for HH:MM:SS.mmm timecodes in the JSON config, e.g. 24000/1001"
+    )]
+    pub fps: Option<String>,
+
+    #[clap(
+        long,
+        conflicts_with = "fps",
+        help = "Matroska timestamps v2 file of a variable frame rate video, for HH:MM:SS.mmm timecodes in the JSON config",
+        value_hint = ValueHint::FilePath
+    )]
+    pub timestamps: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Chapters file for the chapter references in the JSON config, as OGM or Matroska XML chapters",
+        value_hint = ValueHint::FilePath
+    )]
+    pub chapters: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Appends N copies of the last RPU, for frames padded at the end of the encode. Overrides the extend of the JSON config"
+    )]
+    pub extend: Option<usize>,
+
+    #[clap(
+        long,
+        conflicts_with = "rpu-out",
+        help = "Applies the edits without writing the output, and prints a summary of the changes by RPU section and level"
+    )]
+    pub dry_run: bool,
 }
@@ -1,6 +1,9 @@
 use clap::{Args, ValueHint};
 use std::path::PathBuf;
 
+use crate::dovi::exporter::ExportFormat;
+use crate::dovi::hdr10plus_metadata::Hdr10PlusProfile;
+
 #[derive(Args, Debug)]
 pub struct ExportArgs {
     #[clap(
@@ -27,8 +30,36 @@ pub struct ExportArgs {
         name = "output",
         long,
         short = 'o',
-        help = "Output JSON file name",
+        help = "Output file name",
         value_hint = ValueHint::FilePath
     )]
     pub output: Option<PathBuf>,
+
+    #[clap(
+        arg_enum,
+        long,
+        help = "Output format: JSON, Dolby Vision metadata XML, CSV with one row of statistics per frame, compact JSON of the L1/L2 metadata per frame, HDR10+ JSON metadata derived from the L1/L2 metadata, or SRT/ASS subtitles overlaying the L1, L2 and L5 metadata of every frame",
+        default_value = "json"
+    )]
+    pub format: ExportFormat,
+
+    #[clap(long, help = "Canvas width for L5 metadata in XML export")]
+    pub canvas_width: Option<u16>,
+
+    #[clap(long, help = "Canvas height for L5 metadata in XML export")]
+    pub canvas_height: Option<u16>,
+
+    #[clap(
+        arg_enum,
+        long,
+        help = "HDR10+ profile of the hdr10plus export: A for the luminance statistics only, B to also derive a tone mapping curve from the 100 nits trim",
+        default_value = "b"
+    )]
+    pub hdr10plus_profile: Hdr10PlusProfile,
+
+    #[clap(
+        long,
+        help = "Frame rate of the video for the srt and ass subtitle timings, e.g. 24000/1001"
+    )]
+    pub fps: Option<String>,
 }
@@ -1,33 +1,112 @@
 use clap::{Args, ValueHint};
 use std::path::PathBuf;
 
+use crate::dovi::profile_mix::NormalizeProfile;
+use crate::dovi::CrcMode;
+
 #[derive(Args, Debug)]
 pub struct ExtractRpuArgs {
     #[clap(
         name = "input",
-        help = "Sets the input HEVC file to use, or piped with -",
+        help = "Sets the input HEVC files to use, or piped with -. File names can have * and ? wildcards",
         long,
         short = 'i',
+        multiple_occurrences = true,
         conflicts_with = "input_pos",
         required_unless_present = "input_pos",
         value_hint = ValueHint::FilePath,
     )]
-    pub input: Option<PathBuf>,
+    pub input: Vec<PathBuf>,
 
     #[clap(
         name = "input_pos",
-        help = "Sets the input HEVC file to use, or piped with - (positional)",
+        help = "Sets the input HEVC files to use, or piped with - (positional)",
+        multiple_values = true,
         conflicts_with = "input",
         required_unless_present = "input",
         value_hint = ValueHint::FilePath
     )]
-    pub input_pos: Option<PathBuf>,
+    pub input_pos: Vec<PathBuf>,
 
     #[clap(
         long,
         short = 'o',
-        help = "RPU output file location",
+        help = "RPU output file location, or - to stream the RPUs to stdout",
         value_hint = ValueHint::FilePath
     )]
     pub rpu_out: Option<PathBuf>,
+
+    #[clap(
+        long,
+        conflicts_with = "rpu-out",
+        help = "Output file names for multiple inputs, {stem} is replaced by the input file name without extension, {dir} by its directory and {rel} by its directory relative to the input directory. Default: {stem}.rpu.bin"
+    )]
+    pub output_template: Option<String>,
+
+    #[clap(
+        long,
+        help = "Also processes the files in the subdirectories of the input directories"
+    )]
+    pub recursive: bool,
+
+    #[clap(
+        long,
+        use_value_delimiter = true,
+        help = "Extensions of the files processed in the input directories. Comma separated, default: hevc,h265,265,h264,264,avc,h266,266,vvc"
+    )]
+    pub extensions: Vec<String>,
+
+    #[clap(
+        long,
+        conflicts_with_all = &["rpu-out", "output-template"],
+        help = "Writes every RPU to its own file in the directory instead, named frame_000123.rpu by frame number",
+        value_hint = ValueHint::DirPath
+    )]
+    pub split_dir: Option<PathBuf>,
+
+    #[clap(
+        long,
+        requires = "split-dir",
+        help = "Also writes the parsed RPU as JSON next to each RPU file, as frame_000123.json"
+    )]
+    pub split_json: bool,
+
+    #[clap(
+        long,
+        help = "Repairs the RPU list: discards duplicate RPUs, and fills the frames missing an RPU from the previous frame"
+    )]
+    pub repair: bool,
+
+    #[clap(
+        long,
+        conflicts_with = "repair",
+        help = "Writes the RPUs as soon as their presentation order is known, instead of buffering all of them until the end"
+    )]
+    pub stream: bool,
+
+    #[clap(
+        arg_enum,
+        long,
+        conflicts_with = "stream",
+        help = "Verifies the CRC32 of every RPU. check: only reports the corrupt RPUs, fix: recomputes the CRC32, replace: replaces the corrupt RPUs with the previous valid RPU"
+    )]
+    pub crc: Option<CrcMode>,
+
+    #[clap(
+        long,
+        conflicts_with_all = &["stream", "repair"],
+        help = "Orders the RPUs with an external frame order instead of the slice headers. See --help for more info",
+        long_help = "Orders the RPUs with an external frame order instead of the slice headers.\n\
+                     Text file with one value per frame in decoding order: its presentation index or timestamp, \
+                     e.g. from `ffprobe -select_streams v:0 -show_entries packet=pts -of csv=p=0`",
+        value_hint = ValueHint::FilePath
+    )]
+    pub frame_order: Option<PathBuf>,
+
+    #[clap(
+        arg_enum,
+        long,
+        help = "Converts the RPUs of a mixed profile stream to a single profile, instead of --mode"
+    )]
+    pub normalize: Option<NormalizeProfile>,
 }
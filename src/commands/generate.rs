@@ -9,7 +9,7 @@ pub struct GenerateArgs {
         short = 'j',
         help = "Sets the generator config JSON file to use",
         conflicts_with = "xml",
-        required_unless_present = "xml",
+        required_unless_present_any = &["xml", "hlg", "static-content"],
         value_hint = ValueHint::FilePath
     )]
     pub json_file: Option<PathBuf>,
@@ -17,7 +17,7 @@ pub struct GenerateArgs {
     #[clap(
         long,
         short = 'o',
-        help = "Generated RPU output file location",
+        help = "Generated RPU output file location, or - to stream the RPUs to stdout",
         value_hint = ValueHint::FilePath
     )]
     pub rpu_out: Option<PathBuf>,
@@ -35,8 +35,8 @@ pub struct GenerateArgs {
         short = 'x',
         long,
         help = "XML metadata file to generate from",
-        conflicts_with_all = &["json", "hdr10plus-json", "madvr-file"],
-        required_unless_present = "json",
+        conflicts_with_all = &["json", "hdr10plus-json", "madvr-file", "hlg"],
+        required_unless_present_any = &["json", "hlg", "static-content"],
         value_hint = ValueHint::FilePath
     )]
     pub xml: Option<PathBuf>,
@@ -60,4 +60,74 @@ pub struct GenerateArgs {
         help = "madVR source: use custom per-frame target nits if available"
     )]
     pub use_custom_targets: bool,
+
+    #[clap(
+        long,
+        help = "Scene file to import the scene cuts from: Av1an scenes.json, x265 CSV log or frame list",
+        value_hint = ValueHint::FilePath
+    )]
+    pub scene_file: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Generates profile 8.4 RPUs for a HLG base layer, with static L1 metadata unless set in the JSON config"
+    )]
+    pub hlg: bool,
+
+    #[clap(
+        long,
+        help = "Number of frames to generate, when no JSON config is used",
+        conflicts_with = "json"
+    )]
+    pub length: Option<usize>,
+
+    #[clap(
+        long,
+        help = "Generates RPUs for static content such as graphics, test patterns or slideshows, from the flags only: fixed L1 metadata and no scene cuts",
+        conflicts_with_all = &["json", "xml", "hdr10plus-json", "madvr-file", "scene-file", "hlg"],
+        requires = "length"
+    )]
+    pub static_content: bool,
+
+    #[clap(
+        long,
+        help = "Static content L1 metadata in nits: min,avg,max. Default: 0, MaxFALL or 203 nits, MaxCLL or the mastering display max",
+        requires = "static-content"
+    )]
+    pub l1: Option<String>,
+
+    #[clap(
+        long,
+        help = "Static content L5 active area offsets in pixels: left,right,top,bottom",
+        requires = "static-content"
+    )]
+    pub l5: Option<String>,
+
+    #[clap(
+        long,
+        help = "Static content L6 metadata in nits: MaxMDL,MinMDL,MaxCLL,MaxFALL. Default: 1000,0.0001,0,0",
+        requires = "static-content"
+    )]
+    pub l6: Option<String>,
+
+    #[clap(
+        long,
+        help = "Frame rate of the video for HH:MM:SS.mmm timecodes in the JSON config, e.g. 24000/1001"
+    )]
+    pub fps: Option<String>,
+
+    #[clap(
+        long,
+        conflicts_with = "fps",
+        help = "Matroska timestamps v2 file of a variable frame rate video, for HH:MM:SS.mmm timecodes in the JSON config",
+        value_hint = ValueHint::FilePath
+    )]
+    pub timestamps: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Chapters file for the chapter references in the JSON config, as OGM or Matroska XML chapters",
+        value_hint = ValueHint::FilePath
+    )]
+    pub chapters: Option<PathBuf>,
 }
@@ -0,0 +1,76 @@
+use clap::{Args, ValueHint};
+use std::path::PathBuf;
+
+use crate::dovi::lut_generator::LutTransfer;
+
+#[derive(Args, Debug)]
+pub struct GenerateLutArgs {
+    #[clap(
+        name = "input",
+        help = "Sets the input RPU file to use",
+        long,
+        short = 'i',
+        conflicts_with = "input_pos",
+        required_unless_present = "input_pos",
+        value_hint = ValueHint::FilePath,
+    )]
+    pub input: Option<PathBuf>,
+
+    #[clap(
+        name = "input_pos",
+        help = "Sets the input RPU file to use (positional)",
+        conflicts_with = "input",
+        required_unless_present = "input",
+        value_hint = ValueHint::FilePath
+    )]
+    pub input_pos: Option<PathBuf>,
+
+    #[clap(
+        long,
+        short = 'o',
+        help = "Output directory of the per-shot LUTs, or output file with --average",
+        value_hint = ValueHint::AnyPath
+    )]
+    pub output: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Target display peak brightness, in nits. The L2 trims of the target are applied",
+        default_value = "100"
+    )]
+    pub target_nits: u16,
+
+    #[clap(
+        long,
+        help = "Number of points of each LUT dimension",
+        default_value = "33"
+    )]
+    pub size: usize,
+
+    #[clap(
+        long,
+        help = "Writes a single LUT, averaged over the shots weighted by their frame count"
+    )]
+    pub average: bool,
+
+    #[clap(
+        arg_enum,
+        long,
+        help = "Output signal of the LUTs. See --help for more info",
+        long_help = "Output signal of the LUTs.\n  \
+                     pq: BT.2020 primaries, PQ transfer\n  \
+                     sdr: BT.709 primaries, BT.1886 gamma 2.4, with the target peak brightness as reference white",
+        default_value = "pq"
+    )]
+    pub transfer: LutTransfer,
+
+    #[clap(
+        long,
+        conflicts_with_all = &["target-nits", "transfer"],
+        help = "Only applies the RPU mapping and color conversion, without display management. See --help for more info",
+        long_help = "Only applies the RPU mapping and color conversion, without display management.\n\
+                     The LUTs output the reconstructed BT.2020 PQ signal. For a profile 5 input, they convert \
+                     the IPTPQc2 base layer, to re-encode it for the RPU converted with mode 3"
+    )]
+    pub reshape_only: bool,
+}
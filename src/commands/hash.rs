@@ -0,0 +1,33 @@
+use clap::{Args, ValueHint};
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct HashArgs {
+    #[clap(
+        name = "input",
+        help = "Sets the input RPU file to use",
+        long,
+        short = 'i',
+        conflicts_with = "input_pos",
+        required_unless_present = "input_pos",
+        value_hint = ValueHint::FilePath,
+    )]
+    pub input: Option<PathBuf>,
+
+    #[clap(
+        name = "input_pos",
+        help = "Sets the input RPU file to use (positional)",
+        conflicts_with = "input",
+        required_unless_present = "input",
+        value_hint = ValueHint::FilePath
+    )]
+    pub input_pos: Option<PathBuf>,
+
+    #[clap(
+        long,
+        short = 'o',
+        help = "Writes the hash of every frame to a text file, one line per frame",
+        value_hint = ValueHint::FilePath
+    )]
+    pub output: Option<PathBuf>,
+}
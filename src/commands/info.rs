@@ -5,7 +5,7 @@ use std::path::PathBuf;
 pub struct InfoArgs {
     #[clap(
         name = "input",
-        help = "Sets the input RPU file to use",
+        help = "Sets the input RPU file to use, or a HEVC file with --summary or --scenes",
         long,
         short = 'i',
         conflicts_with = "input_pos",
@@ -16,7 +16,7 @@ pub struct InfoArgs {
 
     #[clap(
         name = "input_pos",
-        help = "Sets the input RPU file to use (positional)",
+        help = "Sets the input RPU file to use, or a HEVC file with --summary or --scenes (positional)",
         conflicts_with = "input",
         required_unless_present = "input",
         value_hint = ValueHint::FilePath
@@ -30,4 +30,36 @@ pub struct InfoArgs {
         help = "Frame number to show info for"
     )]
     pub frame: Option<usize>,
+
+    #[clap(
+        long,
+        short = 's',
+        conflicts_with = "frame",
+        help = "Prints a summary of the metadata: profile, CM version, frame and scene count, L6, L11, L254 and HDR10+. With a HEVC input, also the VUI and HDR10 SEI values"
+    )]
+    pub summary: bool,
+
+    #[clap(
+        long,
+        requires = "summary",
+        help = "Analyzes the EL pictures of a dual layer HEVC file, to tell if the EL is a MEL or FEL"
+    )]
+    pub analyze_el: bool,
+
+    #[clap(
+        long,
+        conflicts_with_all = &["frame", "summary"],
+        help = "Lists the scenes from the scene cut flags, with their start frame, length and L1 metadata summary"
+    )]
+    pub scenes: bool,
+
+    #[clap(long, requires = "scenes", help = "Prints the scene list as JSON")]
+    pub json: bool,
+
+    #[clap(
+        long,
+        requires = "scenes",
+        help = "Frame rate of the video for the scene timestamps and durations, e.g. 24000/1001"
+    )]
+    pub fps: Option<String>,
 }
@@ -1,11 +1,13 @@
 use clap::{Args, ValueHint};
 use std::path::PathBuf;
 
+use crate::dovi::rpu_injector::MismatchFix;
+
 #[derive(Args, Debug)]
 pub struct InjectRpuArgs {
     #[clap(
         name = "input",
-        help = "Sets the input HEVC file to use",
+        help = "Sets the input HEVC file to use, or piped with -",
         long,
         short = 'i',
         conflicts_with = "input_pos",
@@ -16,20 +18,20 @@ pub struct InjectRpuArgs {
 
     #[clap(
         name = "input_pos",
-        help = "Sets the input HEVC file to use (positional)",
+        help = "Sets the input HEVC file to use, or piped with - (positional)",
         conflicts_with = "input",
         required_unless_present = "input",
         value_hint = ValueHint::FilePath
     )]
     pub input_pos: Option<PathBuf>,
 
-    #[clap(long, short = 'r', help = "Sets the input RPU file to use", value_hint = ValueHint::FilePath)]
+    #[clap(long, short = 'r', help = "Sets the input RPU file to use, or the RPU stream piped with -", value_hint = ValueHint::FilePath)]
     pub rpu_in: PathBuf,
 
     #[clap(
         long,
         short = 'o',
-        help = "Output HEVC file location",
+        help = "Output HEVC file location, or - to write the injected stream to stdout",
         value_hint = ValueHint::FilePath
     )]
     pub output: Option<PathBuf>,
@@ -40,4 +42,30 @@ pub struct InjectRpuArgs {
         help = "Disable adding AUD NALUs between frames"
     )]
     pub no_add_aud: bool,
+
+    #[clap(
+        arg_enum,
+        long,
+        help = "Fix for mismatched RPU and video frame counts. truncate: cuts the longer of the two, not supported with a piped input, duplicate-last: repeats the last RPU for the missing frames, fail: reports where the counts diverge",
+        default_value = "duplicate-last"
+    )]
+    pub fix: MismatchFix,
+
+    #[clap(
+        long,
+        takes_value = false,
+        help = "Reads back the output, checking that every frame has exactly one RPU, equal to the injected RPU of the frame"
+    )]
+    pub verify: bool,
+
+    #[clap(
+        long,
+        conflicts_with_all = &["output", "no-add-aud"],
+        help = "Replaces the RPUs of a Dolby Vision file directly in the input file, instead of writing a new file. See --help for more info",
+        long_help = "Replaces the RPUs of a Dolby Vision file directly in the input file, instead of writing a new file.\n\
+                     Every frame must already have an RPU. Only the RPU NAL units are rewritten, padded with zero bytes when \
+                     the new RPU is smaller. When an RPU is larger, the rest of the file after it is shifted.\n\
+                     The input file is modified, an interruption leaves it partially updated"
+    )]
+    pub in_place: bool,
 }
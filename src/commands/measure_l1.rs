@@ -0,0 +1,86 @@
+use clap::{Args, ValueHint};
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct MeasureL1Args {
+    #[clap(
+        name = "input",
+        help = "Sets the input RPU file to validate or overwrite. A new RPU is generated otherwise",
+        long,
+        short = 'i',
+        conflicts_with = "input_pos",
+        value_hint = ValueHint::FilePath,
+    )]
+    pub input: Option<PathBuf>,
+
+    #[clap(
+        name = "input_pos",
+        help = "Sets the input RPU file to validate or overwrite (positional)",
+        conflicts_with = "input",
+        value_hint = ValueHint::FilePath
+    )]
+    pub input_pos: Option<PathBuf>,
+
+    #[clap(
+        long,
+        short = 'f',
+        help = "Decoded frames as raw 10 bit luma (gray10le pixel format), or piped with -",
+        conflicts_with = "video",
+        required_unless_present = "video",
+        value_hint = ValueHint::FilePath
+    )]
+    pub frames: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Video file to decode with ffmpeg, which must be in the PATH",
+        value_hint = ValueHint::FilePath
+    )]
+    pub video: Option<PathBuf>,
+
+    #[clap(long, help = "Width of the decoded frames")]
+    pub width: usize,
+
+    #[clap(long, help = "Height of the decoded frames")]
+    pub height: usize,
+
+    #[clap(
+        long,
+        help = "The decoded frames are full range, instead of limited range"
+    )]
+    pub full_range: bool,
+
+    #[clap(
+        long,
+        help = "Overwrites the L1 metadata of the input RPU, instead of validating it"
+    )]
+    pub overwrite: bool,
+
+    #[clap(
+        long,
+        help = "Maximum difference between the measured and existing L1 values, as 12 bit PQ codes",
+        default_value = "64"
+    )]
+    pub tolerance: u16,
+
+    #[clap(
+        long,
+        help = "Validates per scene, measuring only one frame every N. Flags the scenes whose L1 differs from the measurements"
+    )]
+    pub sample: Option<usize>,
+
+    #[clap(
+        long,
+        help = "Scene file for the generated RPU: Av1an scenes.json, x265 CSV log or frame list",
+        value_hint = ValueHint::FilePath
+    )]
+    pub scene_file: Option<PathBuf>,
+
+    #[clap(
+        long,
+        short = 'o',
+        help = "RPU output file location",
+        value_hint = ValueHint::FilePath
+    )]
+    pub rpu_out: Option<PathBuf>,
+}
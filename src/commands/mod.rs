@@ -1,35 +1,103 @@
 use clap::Parser;
 
+mod add_dv;
+mod align;
+mod apply_patch;
+mod concat;
+mod conform;
 mod convert;
+mod create_patch;
 mod demux;
+mod detect_active_area;
+mod diff;
 mod editor;
 mod export;
 mod extract_rpu;
 mod generate;
+mod generate_lut;
+mod hash;
 mod info;
 mod inject_rpu;
+mod measure_l1;
 mod mux;
+mod plot;
+mod preview;
+mod remove;
+mod split;
+mod verify;
 
+pub use add_dv::AddDvArgs;
+pub use align::AlignArgs;
+pub use apply_patch::ApplyPatchArgs;
+pub use concat::ConcatArgs;
+pub use conform::ConformArgs;
 pub use convert::ConvertArgs;
+pub use create_patch::CreatePatchArgs;
 pub use demux::DemuxArgs;
+pub use detect_active_area::DetectActiveAreaArgs;
+pub use diff::DiffArgs;
 pub use editor::EditorArgs;
 pub use export::ExportArgs;
 pub use extract_rpu::ExtractRpuArgs;
 pub use generate::GenerateArgs;
+pub use generate_lut::GenerateLutArgs;
+pub use hash::HashArgs;
 pub use info::InfoArgs;
 pub use inject_rpu::InjectRpuArgs;
+pub use measure_l1::MeasureL1Args;
 pub use mux::MuxArgs;
+pub use plot::PlotArgs;
+pub use preview::PreviewArgs;
+pub use remove::RemoveArgs;
+pub use split::SplitArgs;
+pub use verify::VerifyArgs;
 
 #[derive(Parser, Debug)]
 pub enum Command {
+    #[clap(
+        about = "Adds Dolby Vision to a HDR10 HEVC file, generating the RPUs from HDR10+, madVR or static metadata and injecting them"
+    )]
+    AddDv(AddDvArgs),
+
+    #[clap(
+        about = "Aligns a binary RPU file to a target video of another release, matching their scene cuts"
+    )]
+    Align(AlignArgs),
+
+    #[clap(
+        about = "Applies a patch created by create-patch to the original RPU file, verifying the result"
+    )]
+    ApplyPatch(ApplyPatchArgs),
+
+    #[clap(about = "Joins multiple binary RPU files, setting scene cuts at the joins")]
+    Concat(ConcatArgs),
+
+    #[clap(
+        about = "Conforms a binary RPU file to another frame rate, duplicating or dropping frames within the scenes"
+    )]
+    Conform(ConformArgs),
+
     #[clap(about = "Converts RPU within a single layer HEVC file")]
     Convert(ConvertArgs),
 
+    #[clap(
+        about = "Creates a patch of the metadata changes from an original to a fixed RPU file, to share small fixes"
+    )]
+    CreatePatch(CreatePatchArgs),
+
     #[clap(
         about = "Demuxes single track dual layer Dolby Vision into Base layer and Enhancement layer files"
     )]
     Demux(DemuxArgs),
 
+    #[clap(
+        about = "Detects the letterbox bars from decoded frames and sets the L5 active area offsets"
+    )]
+    DetectActiveArea(DetectActiveAreaArgs),
+
+    #[clap(about = "Compares two binary RPU files frame by frame, or an RPU file to XML metadata")]
+    Diff(DiffArgs),
+
     #[clap(about = "Edits a binary RPU according to a JSON config")]
     Editor(EditorArgs),
 
@@ -45,9 +113,43 @@ pub enum Command {
     #[clap(about = "Generates a binary RPU from different sources")]
     Generate(GenerateArgs),
 
-    #[clap(about = "Prints the parsed RPU data as JSON for a specific frame")]
+    #[clap(
+        about = "Generates 3D LUTs from the RPU mapping and an approximated display management, per shot or averaged"
+    )]
+    GenerateLut(GenerateLutArgs),
+
+    #[clap(
+        about = "Hashes the parsed RPU metadata per frame and for the whole file, independently of the binary encoding"
+    )]
+    Hash(HashArgs),
+
+    #[clap(
+        about = "Prints the parsed RPU data as JSON for a specific frame, or a summary of the metadata"
+    )]
     Info(InfoArgs),
 
+    #[clap(
+        about = "Measures the L1 brightness metadata from decoded frames, to generate or validate RPUs"
+    )]
+    MeasureL1(MeasureL1Args),
+
     #[clap(about = "Interleaves the enhancement layer into a base layer HEVC bitstream")]
     Mux(MuxArgs),
+
+    #[clap(about = "Plots the L1 dynamic brightness metadata, L2 trims or L5 offsets")]
+    Plot(PlotArgs),
+
+    #[clap(
+        about = "Renders a test pattern through the RPU mapping and an approximated display management, per frame"
+    )]
+    Preview(PreviewArgs),
+
+    #[clap(about = "Removes the RPU and enhancement layer NAL units, keeping only the base layer")]
+    Remove(RemoveArgs),
+
+    #[clap(about = "Splits a binary RPU file into multiple parts")]
+    Split(SplitArgs),
+
+    #[clap(about = "Verifies the conformance of every RPU in a binary RPU file")]
+    Verify(VerifyArgs),
 }
@@ -0,0 +1,60 @@
+use clap::{Args, ValueHint};
+use std::path::PathBuf;
+
+use crate::dovi::plotter::PlotType;
+
+#[derive(Args, Debug)]
+pub struct PlotArgs {
+    #[clap(
+        name = "input",
+        help = "Sets the input RPU file to use",
+        long,
+        short = 'i',
+        conflicts_with = "input_pos",
+        required_unless_present = "input_pos",
+        value_hint = ValueHint::FilePath,
+    )]
+    pub input: Option<PathBuf>,
+
+    #[clap(
+        name = "input_pos",
+        help = "Sets the input RPU file to use (positional)",
+        conflicts_with = "input",
+        required_unless_present = "input",
+        value_hint = ValueHint::FilePath
+    )]
+    pub input_pos: Option<PathBuf>,
+
+    #[clap(
+        name = "output",
+        long,
+        short = 'o',
+        help = "Output file name. The format is chosen from the extension: PNG, SVG or HTML",
+        value_hint = ValueHint::FilePath
+    )]
+    pub output: Option<PathBuf>,
+
+    #[clap(
+        name = "title",
+        long,
+        short = 't',
+        help = "The title to set at the top of the plot"
+    )]
+    pub title: Option<String>,
+
+    #[clap(
+        arg_enum,
+        long,
+        short = 'p',
+        help = "The metadata to plot: L1 brightness, L2 trims or L5 active area offsets",
+        default_value = "l1"
+    )]
+    pub plot_type: PlotType,
+
+    #[clap(
+        long,
+        help = "Target display peak brightness of the L2 trims to plot, in nits",
+        default_value = "100"
+    )]
+    pub target_nits: u16,
+}
@@ -0,0 +1,68 @@
+use clap::{Args, ValueHint};
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct PreviewArgs {
+    #[clap(
+        name = "input",
+        help = "Sets the input RPU file to use",
+        long,
+        short = 'i',
+        conflicts_with = "input_pos",
+        required_unless_present = "input_pos",
+        value_hint = ValueHint::FilePath,
+    )]
+    pub input: Option<PathBuf>,
+
+    #[clap(
+        name = "input_pos",
+        help = "Sets the input RPU file to use (positional)",
+        conflicts_with = "input",
+        required_unless_present = "input",
+        value_hint = ValueHint::FilePath
+    )]
+    pub input_pos: Option<PathBuf>,
+
+    #[clap(
+        long,
+        short = 'o',
+        help = "Output directory of the PNG images. Defaults to `preview`",
+        value_hint = ValueHint::DirPath
+    )]
+    pub output_dir: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Frame numbers to render. Comma separated, defaults to the first frame of every scene",
+        use_value_delimiter = true
+    )]
+    pub frames: Vec<usize>,
+
+    #[clap(
+        long,
+        help = "Target display peak brightness, in nits. The L2 trims of the target are applied",
+        default_value = "100"
+    )]
+    pub target_nits: u16,
+
+    #[clap(
+        long,
+        help = "Second RPU file rendered next to the input, e.g. before and after an edit",
+        value_hint = ValueHint::FilePath
+    )]
+    pub compare: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Width of the test pattern, in pixels",
+        default_value = "960"
+    )]
+    pub width: u32,
+
+    #[clap(
+        long,
+        help = "Height of the test pattern, in pixels",
+        default_value = "540"
+    )]
+    pub height: u32,
+}
@@ -0,0 +1,63 @@
+use clap::{Args, ValueHint};
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct SplitArgs {
+    #[clap(
+        name = "input",
+        help = "Sets the input RPU file to use",
+        long,
+        short = 'i',
+        conflicts_with = "input_pos",
+        required_unless_present = "input_pos",
+        value_hint = ValueHint::FilePath,
+    )]
+    pub input: Option<PathBuf>,
+
+    #[clap(
+        name = "input_pos",
+        help = "Sets the input RPU file to use (positional)",
+        conflicts_with = "input",
+        required_unless_present = "input",
+        value_hint = ValueHint::FilePath
+    )]
+    pub input_pos: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Frame numbers starting a new part. Comma separated",
+        use_value_delimiter = true,
+        conflicts_with_all = &["chapters", "scenes"],
+        required_unless_present_any = &["chapters", "scenes"]
+    )]
+    pub frames: Vec<usize>,
+
+    #[clap(
+        long,
+        help = "Chapters file to split at, as OGM or Matroska XML chapters",
+        conflicts_with = "scenes",
+        requires = "fps",
+        value_hint = ValueHint::FilePath
+    )]
+    pub chapters: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Frame rate of the video for chapter timestamps, e.g. 24000/1001"
+    )]
+    pub fps: Option<String>,
+
+    #[clap(
+        long,
+        help = "Number of scenes per part, according to the scene cut flags"
+    )]
+    pub scenes: Option<usize>,
+
+    #[clap(
+        long,
+        short = 'o',
+        help = "Output directory for the parts. Defaults to the current directory",
+        value_hint = ValueHint::DirPath
+    )]
+    pub output_dir: Option<PathBuf>,
+}
@@ -0,0 +1,213 @@
+use std::fs::File;
+use std::io::{stdin, stdout, BufReader, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, ensure, Result};
+
+use dolby_vision::rpu::extension_metadata::blocks::{ExtMetadataBlock, ExtMetadataBlockLevel5};
+use dolby_vision::rpu::generate::GenerateConfig;
+use utilities_dovi::parse_rpu_file;
+
+use crate::commands::DetectActiveAreaArgs;
+
+use super::{input_from_either, write_rpu_file, DoviRpu};
+
+pub struct ActiveAreaDetector {
+    input: PathBuf,
+    frames: PathBuf,
+    width: usize,
+    height: usize,
+    threshold: u8,
+    rpu_out: PathBuf,
+}
+
+/// Letterbox bars of a frame, in pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ActiveAreaOffsets {
+    left: usize,
+    right: usize,
+    top: usize,
+    bottom: usize,
+}
+
+impl ActiveAreaDetector {
+    pub fn detect(args: DetectActiveAreaArgs) -> Result<()> {
+        let DetectActiveAreaArgs {
+            input,
+            input_pos,
+            frames,
+            width,
+            height,
+            threshold,
+            rpu_out,
+        } = args;
+
+        let input = input_from_either("detect-active-area", input, input_pos)?;
+
+        ensure!(
+            width > 0 && height > 0,
+            "detect-active-area: invalid frame size {}x{}",
+            width,
+            height
+        );
+
+        let rpu_out = rpu_out.unwrap_or_else(|| {
+            PathBuf::from(format!(
+                "{}{}",
+                input.file_stem().unwrap().to_str().unwrap(),
+                "_active_area.bin"
+            ))
+        });
+
+        let detector = ActiveAreaDetector {
+            input,
+            frames,
+            width,
+            height,
+            threshold,
+            rpu_out,
+        };
+
+        println!("Parsing RPU file...");
+        stdout().flush().ok();
+
+        if let Some(mut rpus) = parse_rpu_file(&detector.input)? {
+            detector.execute(&mut rpus)?;
+        }
+
+        Ok(())
+    }
+
+    fn execute(&self, rpus: &mut [DoviRpu]) -> Result<()> {
+        println!("Detecting letterbox bars...");
+        stdout().flush().ok();
+
+        let frame_offsets = self.detect_frame_offsets(rpus.len())?;
+
+        let mut scene_start = 0;
+        let mut scenes_count = 0;
+
+        for i in 1..=rpus.len() {
+            let is_scene_end = i == rpus.len() || is_scene_cut(&rpus[i]);
+
+            if is_scene_end {
+                let offsets = scene_offsets(&frame_offsets[scene_start..i]);
+
+                for rpu in rpus[scene_start..i].iter_mut() {
+                    set_offsets(rpu, &offsets)?;
+                }
+
+                scene_start = i;
+                scenes_count += 1;
+            }
+        }
+
+        println!("Set active area offsets for {} scenes", scenes_count);
+
+        let data = GenerateConfig::encode_rpus(rpus);
+        write_rpu_file(&self.rpu_out, data)
+    }
+
+    /// Reads one frame per RPU and detects its letterbox bars.
+    /// Frames that are entirely black have no offsets.
+    fn detect_frame_offsets(&self, frame_count: usize) -> Result<Vec<Option<ActiveAreaOffsets>>> {
+        let mut reader: Box<dyn Read> = if self.frames == Path::new("-") {
+            Box::new(BufReader::new(stdin()))
+        } else {
+            Box::new(BufReader::new(File::open(&self.frames)?))
+        };
+
+        let mut frame = vec![0; self.width * self.height];
+        let mut offsets = Vec::with_capacity(frame_count);
+
+        for i in 0..frame_count {
+            if let Err(e) = reader.read_exact(&mut frame) {
+                if e.kind() == ErrorKind::UnexpectedEof {
+                    bail!("mismatched frame count: frames {}, RPU {}", i, frame_count);
+                }
+
+                return Err(e.into());
+            }
+
+            offsets.push(self.frame_offsets(&frame));
+        }
+
+        Ok(offsets)
+    }
+
+    fn frame_offsets(&self, frame: &[u8]) -> Option<ActiveAreaOffsets> {
+        let (width, height) = (self.width, self.height);
+
+        let row_is_black = |y: usize| self.is_black(frame[y * width..(y + 1) * width].iter());
+        let col_is_black = |x: usize| self.is_black(frame.iter().skip(x).step_by(width));
+
+        let top = (0..height).take_while(|y| row_is_black(*y)).count();
+
+        if top == height {
+            return None;
+        }
+
+        let bottom = (0..height).rev().take_while(|y| row_is_black(*y)).count();
+        let left = (0..width).take_while(|x| col_is_black(*x)).count();
+        let right = (0..width).rev().take_while(|x| col_is_black(*x)).count();
+
+        Some(ActiveAreaOffsets {
+            left,
+            right,
+            top,
+            bottom,
+        })
+    }
+
+    fn is_black<'a>(&self, line: impl Iterator<Item = &'a u8>) -> bool {
+        let (sum, count) = line.fold((0_u64, 0_u64), |(sum, count), v| {
+            (sum + *v as u64, count + 1)
+        });
+
+        count > 0 && sum / count <= self.threshold as u64
+    }
+}
+
+/// The smallest bars of the scene, to never crop any active picture
+fn scene_offsets(frame_offsets: &[Option<ActiveAreaOffsets>]) -> ActiveAreaOffsets {
+    frame_offsets
+        .iter()
+        .flatten()
+        .copied()
+        .reduce(|acc, o| ActiveAreaOffsets {
+            left: acc.left.min(o.left),
+            right: acc.right.min(o.right),
+            top: acc.top.min(o.top),
+            bottom: acc.bottom.min(o.bottom),
+        })
+        .unwrap_or(ActiveAreaOffsets {
+            left: 0,
+            right: 0,
+            top: 0,
+            bottom: 0,
+        })
+}
+
+fn is_scene_cut(rpu: &DoviRpu) -> bool {
+    rpu.vdr_dm_data
+        .as_ref()
+        .map(|dm| dm.scene_refresh_flag == 1)
+        .unwrap_or(false)
+}
+
+fn set_offsets(rpu: &mut DoviRpu, offsets: &ActiveAreaOffsets) -> Result<()> {
+    if let Some(ref mut vdr_dm_data) = rpu.vdr_dm_data {
+        rpu.modified = true;
+
+        vdr_dm_data.replace_metadata_block(ExtMetadataBlock::Level5(
+            ExtMetadataBlockLevel5::from_offsets(
+                offsets.left as u16,
+                offsets.right as u16,
+                offsets.top as u16,
+                offsets.bottom as u16,
+            ),
+        ))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,350 @@
+use std::io::{stdout, Write};
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+
+use dolby_vision::rpu::generate::GenerateConfig;
+use utilities_dovi::parse_rpu_file;
+
+use crate::commands::AlignArgs;
+
+use super::conformer::ConformedFrame;
+use super::scene_file::SceneCuts;
+use super::splitter::scene_starts;
+use super::{input_from_either, write_rpu_file, DoviRpu};
+
+pub struct Aligner {
+    input: PathBuf,
+    target: AlignTarget,
+    rpu_out: PathBuf,
+}
+
+enum AlignTarget {
+    Rpu(PathBuf),
+    SceneFile {
+        path: PathBuf,
+        length: Option<usize>,
+    },
+}
+
+/// Frames of the target mapped to the source with the same offset, between two edit points
+#[derive(Debug, PartialEq, Eq)]
+pub struct AlignedSegment {
+    pub target_start: usize,
+    pub target_end: usize,
+    pub source_start: usize,
+    pub source_end: usize,
+    /// Target frames without source frame, duplicating the last source frame of the segment
+    pub padded: usize,
+}
+
+#[derive(Debug)]
+pub struct Alignment {
+    /// Target scenes matched to a source scene
+    pub matched_scenes: usize,
+    pub segments: Vec<AlignedSegment>,
+    pub frames: Vec<ConformedFrame>,
+}
+
+impl Aligner {
+    pub fn align(args: AlignArgs) -> Result<()> {
+        let AlignArgs {
+            input,
+            input_pos,
+            target_rpu,
+            scene_file,
+            length,
+            rpu_out,
+        } = args;
+
+        let input = input_from_either("align", input, input_pos)?;
+
+        let target = match (target_rpu, scene_file) {
+            (Some(path), None) => AlignTarget::Rpu(path),
+            (None, Some(path)) => AlignTarget::SceneFile { path, length },
+            _ => bail!("align: either --target-rpu or --scene-file is required"),
+        };
+
+        let aligner = Aligner {
+            input,
+            target,
+            rpu_out: rpu_out.unwrap_or_else(|| PathBuf::from("RPU_aligned.bin")),
+        };
+
+        println!("Parsing RPU file...");
+        stdout().flush().ok();
+
+        let rpus = match parse_rpu_file(&aligner.input)? {
+            Some(rpus) if !rpus.is_empty() => rpus,
+            _ => bail!("No RPU to align"),
+        };
+
+        aligner.execute(&rpus)
+    }
+
+    /// Scene cuts and length of the target video
+    fn target_scenes(&self) -> Result<(Vec<usize>, usize)> {
+        match &self.target {
+            AlignTarget::Rpu(path) => match parse_rpu_file(path)? {
+                Some(rpus) if !rpus.is_empty() => Ok((scene_starts(&rpus).collect(), rpus.len())),
+                _ => bail!("No RPU found in the target RPU file {}", path.display()),
+            },
+            AlignTarget::SceneFile { path, length } => {
+                let scene_cuts = SceneCuts::from_path(path)?;
+
+                let length = match length.or(scene_cuts.frame_count) {
+                    Some(length) => length,
+                    None => bail!("align: the scene file has no frame count, use --length"),
+                };
+
+                let frames = scene_cuts
+                    .frames
+                    .into_iter()
+                    .filter(|frame| *frame < length)
+                    .collect();
+
+                Ok((frames, length))
+            }
+        }
+    }
+
+    fn execute(&self, rpus: &[DoviRpu]) -> Result<()> {
+        let (target_cuts, target_length) = self.target_scenes()?;
+        let source_cuts: Vec<usize> = scene_starts(rpus).collect();
+
+        let alignment = align_frames(&source_cuts, rpus.len(), &target_cuts, target_length)?;
+
+        println!(
+            "Matched {} of {} target scenes to the source",
+            alignment.matched_scenes,
+            target_cuts.len()
+        );
+
+        for segment in &alignment.segments {
+            print!(
+                "  Target frames {}-{}: source frames {}-{}",
+                segment.target_start, segment.target_end, segment.source_start, segment.source_end
+            );
+
+            if segment.padded > 0 {
+                print!(", padded {} frames", segment.padded);
+            }
+
+            println!();
+        }
+
+        let used: usize = alignment
+            .segments
+            .iter()
+            .map(|segment| segment.source_end + 1 - segment.source_start)
+            .sum();
+
+        println!(
+            "Trimmed {} source frames, padded {} target frames",
+            rpus.len().saturating_sub(used),
+            alignment
+                .segments
+                .iter()
+                .map(|segment| segment.padded)
+                .sum::<usize>()
+        );
+
+        let mut aligned: Vec<DoviRpu> = alignment
+            .frames
+            .iter()
+            .map(|frame| {
+                let mut rpu = rpus[frame.source].clone();
+
+                if let Some(ref mut vdr_dm_data) = rpu.vdr_dm_data {
+                    if (vdr_dm_data.scene_refresh_flag == 1) != frame.scene_cut {
+                        vdr_dm_data.set_scene_cut(frame.scene_cut);
+                        rpu.modified = true;
+                    }
+                }
+
+                rpu
+            })
+            .collect();
+
+        let data = GenerateConfig::encode_rpus(&mut aligned);
+        write_rpu_file(&self.rpu_out, data)?;
+
+        Ok(())
+    }
+}
+
+/// Maps every target frame to a source frame, from the scene cuts of both.
+///
+/// The scenes are matched by their lengths, with the longest common sequence of scene lengths,
+/// and split at the edit points, where the offset between the target and the source changes.
+/// Source frames missing from the target are trimmed, and target frames missing from the source
+/// duplicate the source frame before them, or the first one at the start.
+pub fn align_frames(
+    source_cuts: &[usize],
+    source_length: usize,
+    target_cuts: &[usize],
+    target_length: usize,
+) -> Result<Alignment> {
+    let anchors = match_scenes(source_cuts, source_length, target_cuts, target_length);
+
+    if anchors.is_empty() {
+        bail!("No matching scenes between the RPU and the target, can't align them");
+    }
+
+    // Runs of matched scenes with the same offset
+    let mut runs: Vec<Vec<(usize, usize)>> = Vec::new();
+
+    for anchor in anchors {
+        match runs.last_mut() {
+            Some(run) if offset(run[0]) == offset(anchor) => run.push(anchor),
+            _ => runs.push(vec![anchor]),
+        }
+    }
+
+    // A single scene matched by chance, between two runs of the same offset
+    let outliers: Vec<bool> = (0..runs.len())
+        .map(|i| {
+            runs[i].len() == 1
+                && i > 0
+                && i + 1 < runs.len()
+                && offset(runs[i - 1][0]) == offset(runs[i + 1][0])
+        })
+        .collect();
+    let mut outliers = outliers.into_iter();
+    runs.retain(|_| !outliers.next().unwrap());
+
+    let mut starts: Vec<(usize, usize)> = Vec::new();
+
+    for run in &runs {
+        match starts.last() {
+            Some(start) if offset(*start) == offset(run[0]) => (),
+            _ => starts.push(run[0]),
+        }
+    }
+
+    let matched_scenes = runs.iter().map(Vec::len).sum();
+
+    let source_cut_set: Vec<bool> = (0..source_length)
+        .map(|frame| source_cuts.binary_search(&frame).is_ok())
+        .collect();
+
+    let mut segments = Vec::with_capacity(starts.len());
+    let mut frames: Vec<ConformedFrame> = Vec::with_capacity(target_length);
+
+    for (i, (target, source)) in starts.iter().enumerate() {
+        let target_start = if i == 0 { 0 } else { *target };
+        let target_end = match starts.get(i + 1) {
+            Some((next_target, _)) => *next_target,
+            None => target_length,
+        };
+
+        // The source frames until the next segment
+        let source_limit = match starts.get(i + 1) {
+            Some((_, next_source)) => *next_source,
+            None => source_length,
+        };
+
+        if target_start >= target_end {
+            continue;
+        }
+
+        let mut padded = 0;
+        let mut mapped = Vec::with_capacity(target_end - target_start);
+
+        for frame in target_start..target_end {
+            let source_frame = (frame + source).checked_sub(*target);
+
+            let source_frame = match source_frame {
+                Some(source_frame) if source_frame < source_limit => source_frame,
+                Some(_) => {
+                    padded += 1;
+                    source_limit - 1
+                }
+                None => {
+                    padded += 1;
+                    0
+                }
+            };
+
+            mapped.push(source_frame);
+        }
+
+        segments.push(AlignedSegment {
+            target_start,
+            target_end: target_end - 1,
+            source_start: *mapped.iter().min().unwrap(),
+            source_end: *mapped.iter().max().unwrap(),
+            padded,
+        });
+
+        for source in mapped {
+            let scene_cut = match frames.last() {
+                None => true,
+                Some(previous) if previous.source == source => false,
+                Some(previous) => previous.source + 1 != source || source_cut_set[source],
+            };
+
+            frames.push(ConformedFrame { source, scene_cut });
+        }
+    }
+
+    Ok(Alignment {
+        matched_scenes,
+        segments,
+        frames,
+    })
+}
+
+/// Target frame minus source frame of a matched scene cut
+fn offset((target, source): (usize, usize)) -> i64 {
+    target as i64 - source as i64
+}
+
+/// Pairs of target and source scene starts, from the longest common sequence of scene lengths
+fn match_scenes(
+    source_cuts: &[usize],
+    source_length: usize,
+    target_cuts: &[usize],
+    target_length: usize,
+) -> Vec<(usize, usize)> {
+    let source = scene_lengths(source_cuts, source_length);
+    let target = scene_lengths(target_cuts, target_length);
+
+    // Length of the common sequence of the scenes from every pair of scenes to the end
+    let width = source.len() + 1;
+    let mut table = vec![0_u32; (target.len() + 1) * width];
+
+    for i in (0..target.len()).rev() {
+        for j in (0..source.len()).rev() {
+            table[i * width + j] = if target[i] == source[j] {
+                table[(i + 1) * width + j + 1] + 1
+            } else {
+                table[(i + 1) * width + j].max(table[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < target.len() && j < source.len() {
+        if target[i] == source[j] {
+            pairs.push((target_cuts[i], source_cuts[j]));
+            i += 1;
+            j += 1;
+        } else if table[(i + 1) * width + j] >= table[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    pairs
+}
+
+fn scene_lengths(cuts: &[usize], length: usize) -> Vec<usize> {
+    cuts.iter()
+        .zip(cuts.iter().skip(1).chain(std::iter::once(&length)))
+        .map(|(start, end)| end - start)
+        .collect()
+}
@@ -0,0 +1,177 @@
+use std::fmt;
+
+/// Maximum number of frame ranges listed in the report
+const MAX_LISTED_RANGES: usize = 20;
+
+/// Access unit alignment of the BL, EL and RPU NALUs of a dual layer stream.
+///
+/// The access units are counted in decoded order, split at the AUDs and at the frames
+/// of the parser, so that an access unit without BL picture is also counted.
+/// Only reported once EL NALUs are found, single layer streams never have any.
+#[derive(Debug, Default)]
+pub struct AuAlignment {
+    current: Option<AccessUnit>,
+    count: u64,
+    el_found: bool,
+    issues: Vec<(u64, AlignmentIssue)>,
+}
+
+/// Kind of NALU relevant to the alignment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuNal {
+    Aud,
+    BlSlice,
+    El,
+    Rpu,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlignmentIssue {
+    /// BL picture without EL
+    MissingEl,
+    /// EL without BL picture
+    ExtraEl,
+    /// RPU before the BL slices, belonging to the previous access unit
+    MisplacedRpu,
+}
+
+#[derive(Debug)]
+struct AccessUnit {
+    number: u64,
+    decoded_index: u64,
+    bl_slices: bool,
+    el: bool,
+    rpu_before_slices: bool,
+}
+
+impl AuAlignment {
+    pub fn push(&mut self, decoded_index: u64, nal: AuNal) {
+        if nal == AuNal::Aud
+            || self
+                .current
+                .as_ref()
+                .is_none_or(|au| au.decoded_index != decoded_index)
+        {
+            self.end_access_unit();
+
+            self.current = Some(AccessUnit {
+                number: self.count,
+                decoded_index,
+                bl_slices: false,
+                el: false,
+                rpu_before_slices: false,
+            });
+            self.count += 1;
+        }
+
+        if let Some(au) = self.current.as_mut() {
+            match nal {
+                AuNal::BlSlice => au.bl_slices = true,
+                AuNal::El => {
+                    au.el = true;
+                    self.el_found = true;
+                }
+                AuNal::Rpu if !au.bl_slices => au.rpu_before_slices = true,
+                _ => (),
+            }
+        }
+    }
+
+    pub fn finish(&mut self) {
+        self.end_access_unit();
+    }
+
+    /// Appends the access units of the following part of the stream, once finished
+    pub fn append(&mut self, other: AuAlignment) {
+        let offset = self.count;
+
+        self.issues.extend(
+            other
+                .issues
+                .into_iter()
+                .map(|(number, issue)| (number + offset, issue)),
+        );
+        self.count += other.count;
+        self.el_found |= other.el_found;
+    }
+
+    fn end_access_unit(&mut self) {
+        if let Some(au) = self.current.take() {
+            let index = au.number;
+
+            if au.bl_slices && !au.el {
+                self.issues.push((index, AlignmentIssue::MissingEl));
+            } else if au.el && !au.bl_slices {
+                self.issues.push((index, AlignmentIssue::ExtraEl));
+            }
+
+            if au.rpu_before_slices {
+                self.issues.push((index, AlignmentIssue::MisplacedRpu));
+            }
+        }
+    }
+
+    /// Consecutive decoded frames with the same issue, inclusive
+    pub fn issue_ranges(&self) -> Vec<(u64, u64, AlignmentIssue)> {
+        if !self.el_found {
+            return Vec::new();
+        }
+
+        let mut ranges: Vec<(u64, u64, AlignmentIssue)> = Vec::new();
+
+        for &(index, issue) in &self.issues {
+            let extended = ranges
+                .iter_mut()
+                .rev()
+                .find(|(_, _, range_issue)| *range_issue == issue)
+                .filter(|(_, end, _)| *end + 1 == index)
+                .map(|range| range.1 = index)
+                .is_some();
+
+            if !extended {
+                ranges.push((index, index, issue));
+            }
+        }
+
+        ranges.sort_by_key(|(start, _, _)| *start);
+        ranges
+    }
+
+    pub fn report(&self) -> Option<String> {
+        let ranges = self.issue_ranges();
+
+        if ranges.is_empty() {
+            return None;
+        }
+
+        let mut report = String::from("BL/EL access unit misalignment, in decoded order:");
+
+        for (start, end, issue) in ranges.iter().take(MAX_LISTED_RANGES) {
+            if start == end {
+                report.push_str(&format!("\n  frame {}: {}", start, issue));
+            } else {
+                report.push_str(&format!("\n  frames {}-{}: {}", start, end, issue));
+            }
+        }
+
+        if ranges.len() > MAX_LISTED_RANGES {
+            report.push_str("\n  ...");
+        }
+
+        Some(report)
+    }
+}
+
+impl fmt::Display for AlignmentIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingEl => write!(f, "missing EL"),
+            Self::ExtraEl => write!(f, "EL without BL picture"),
+            Self::MisplacedRpu => write!(
+                f,
+                "RPU before the BL slices, belonging to the previous access unit"
+            ),
+        }
+    }
+}
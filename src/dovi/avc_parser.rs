@@ -0,0 +1,358 @@
+use anyhow::{bail, Result};
+use bitvec_helpers::bitvec_reader::BitVecReader;
+
+use hevc_parser::hevc::{Frame, NALUnit};
+
+use super::nal_parser::{nal_reader, FrameOrder, NalParser};
+
+pub const NAL_AVC_SLICE: u8 = 1;
+pub const NAL_AVC_IDR: u8 = 5;
+pub const NAL_AVC_SPS: u8 = 7;
+pub const NAL_AVC_PPS: u8 = 8;
+pub const NAL_AVC_AUD: u8 = 9;
+pub const NAL_AVC_END_OF_SEQ: u8 = 10;
+pub const NAL_AVC_END_OF_STREAM: u8 = 11;
+pub const NAL_AVC_FILLER: u8 = 12;
+
+/// Dolby Vision RPU, in an unspecified NAL unit type
+pub const NAL_AVC_DV_RPU: u8 = 28;
+/// Dolby Vision enhancement layer, in an unspecified NAL unit type
+pub const NAL_AVC_DV_EL: u8 = 30;
+
+/// NAL header for written RPUs: nal_ref_idc 3, nal_unit_type 28
+pub const AVC_RPU_NAL_HEADER: u8 = 0x7C;
+
+// We don't want to parse large slices because the memory is copied
+const MAX_PARSE_SIZE: usize = 2048;
+
+const HIGH_PROFILES: &[u8] = &[100, 110, 122, 244, 44, 83, 86, 118, 128, 138, 139, 134, 135];
+
+/// Parses AVC Annex B streams, for frame decoding and presentation order.
+///
+/// Only progressive streams using `pic_order_cnt_type` 0 or 2 are supported.
+#[derive(Default)]
+pub struct AvcParser {
+    sps: Vec<AvcSps>,
+    pps: Vec<AvcPps>,
+
+    frame_order: FrameOrder,
+
+    prev_poc_msb: i64,
+    prev_poc_lsb: i64,
+}
+
+#[derive(Debug, Clone)]
+struct AvcSps {
+    sps_id: u64,
+    separate_colour_plane_flag: bool,
+    log2_max_frame_num: usize,
+    pic_order_cnt_type: u64,
+    log2_max_pic_order_cnt_lsb: usize,
+    frame_mbs_only_flag: bool,
+}
+
+#[derive(Debug, Clone)]
+struct AvcPps {
+    pps_id: u64,
+    sps_id: u64,
+}
+
+#[derive(Debug)]
+struct AvcSliceHeader {
+    first_mb_in_slice: u64,
+    frame_type: u64,
+    pic_order_cnt_lsb: i64,
+    pic_order_cnt_type: u64,
+    log2_max_pic_order_cnt_lsb: usize,
+}
+
+impl AvcParser {
+    fn parse_nal_data(&mut self, data: &[u8], nal: &mut NALUnit) -> Result<()> {
+        let (start, end) = (nal.start, nal.end);
+
+        if start >= end {
+            return Ok(());
+        }
+
+        let header = data[start];
+        nal.nal_type = header & 0x1F;
+        let nal_ref_idc = (header >> 5) & 0x3;
+
+        let parsing_end = end.min(start + MAX_PARSE_SIZE);
+
+        match nal.nal_type {
+            NAL_AVC_SLICE | NAL_AVC_IDR => {
+                let mut reader = nal_reader(&data[start + 1..parsing_end]);
+                let slice = self.parse_slice_header(&mut reader, nal.nal_type)?;
+
+                if slice.first_mb_in_slice == 0 {
+                    self.frame_order.add_current_frame();
+
+                    if nal.nal_type == NAL_AVC_IDR {
+                        self.frame_order.reorder_frames();
+                    }
+
+                    let poc = self.compute_poc(&slice, nal.nal_type, nal_ref_idc)?;
+                    self.frame_order.start_frame(poc, slice.frame_type);
+                }
+            }
+            NAL_AVC_DV_RPU
+            | NAL_AVC_DV_EL
+            | NAL_AVC_END_OF_SEQ
+            | NAL_AVC_END_OF_STREAM
+            | NAL_AVC_FILLER => {
+                // Dolby NALs are suffixed to the slices
+                // And end of sequence/stream are contained within the current AU
+            }
+            _ => {
+                // Any other NAL starts a new access unit
+                self.frame_order.add_current_frame();
+
+                match nal.nal_type {
+                    NAL_AVC_SPS => {
+                        let mut reader = nal_reader(&data[start + 1..parsing_end]);
+                        self.parse_sps(&mut reader)?;
+                    }
+                    NAL_AVC_PPS => {
+                        let mut reader = nal_reader(&data[start + 1..parsing_end]);
+                        self.parse_pps(&mut reader)?;
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse_sps(&mut self, reader: &mut BitVecReader) -> Result<()> {
+        let profile_idc: u8 = reader.get_n(8);
+        reader.skip_n(16); // constraint flags, level_idc
+
+        let sps_id = reader.get_ue()?;
+
+        let mut separate_colour_plane_flag = false;
+
+        if HIGH_PROFILES.contains(&profile_idc) {
+            let chroma_format_idc = reader.get_ue()?;
+
+            if chroma_format_idc == 3 {
+                separate_colour_plane_flag = reader.get()?;
+            }
+
+            reader.get_ue()?; // bit_depth_luma_minus8
+            reader.get_ue()?; // bit_depth_chroma_minus8
+            reader.get()?; // qpprime_y_zero_transform_bypass_flag
+
+            let seq_scaling_matrix_present_flag = reader.get()?;
+
+            if seq_scaling_matrix_present_flag {
+                let count = if chroma_format_idc != 3 { 8 } else { 12 };
+
+                for i in 0..count {
+                    let seq_scaling_list_present_flag = reader.get()?;
+
+                    if seq_scaling_list_present_flag {
+                        skip_scaling_list(reader, if i < 6 { 16 } else { 64 })?;
+                    }
+                }
+            }
+        }
+
+        let log2_max_frame_num = reader.get_ue()? as usize + 4;
+        let pic_order_cnt_type = reader.get_ue()?;
+
+        let mut log2_max_pic_order_cnt_lsb = 0;
+
+        match pic_order_cnt_type {
+            0 => log2_max_pic_order_cnt_lsb = reader.get_ue()? as usize + 4,
+            1 => {
+                reader.get()?; // delta_pic_order_always_zero_flag
+                reader.get_se()?; // offset_for_non_ref_pic
+                reader.get_se()?; // offset_for_top_to_bottom_field
+
+                let num_ref_frames_in_pic_order_cnt_cycle = reader.get_ue()?;
+                for _ in 0..num_ref_frames_in_pic_order_cnt_cycle {
+                    reader.get_se()?;
+                }
+            }
+            _ => (),
+        }
+
+        reader.get_ue()?; // max_num_ref_frames
+        reader.get()?; // gaps_in_frame_num_value_allowed_flag
+        reader.get_ue()?; // pic_width_in_mbs_minus1
+        reader.get_ue()?; // pic_height_in_map_units_minus1
+
+        let frame_mbs_only_flag = reader.get()?;
+
+        let sps = AvcSps {
+            sps_id,
+            separate_colour_plane_flag,
+            log2_max_frame_num,
+            pic_order_cnt_type,
+            log2_max_pic_order_cnt_lsb,
+            frame_mbs_only_flag,
+        };
+
+        self.sps.retain(|e| e.sps_id != sps_id);
+        self.sps.push(sps);
+
+        Ok(())
+    }
+
+    fn parse_pps(&mut self, reader: &mut BitVecReader) -> Result<()> {
+        let pps_id = reader.get_ue()?;
+        let sps_id = reader.get_ue()?;
+
+        self.pps.retain(|e| e.pps_id != pps_id);
+        self.pps.push(AvcPps { pps_id, sps_id });
+
+        Ok(())
+    }
+
+    fn parse_slice_header(
+        &self,
+        reader: &mut BitVecReader,
+        nal_type: u8,
+    ) -> Result<AvcSliceHeader> {
+        let first_mb_in_slice = reader.get_ue()?;
+        let slice_type = reader.get_ue()?;
+        let pps_id = reader.get_ue()?;
+
+        let pps = match self.pps.iter().find(|pps| pps.pps_id == pps_id) {
+            Some(pps) => pps,
+            None => bail!("AVC slice references a missing PPS, id {}", pps_id),
+        };
+
+        let sps = match self.sps.iter().find(|sps| sps.sps_id == pps.sps_id) {
+            Some(sps) => sps,
+            None => bail!("AVC PPS references a missing SPS, id {}", pps.sps_id),
+        };
+
+        if sps.pic_order_cnt_type == 1 {
+            bail!("Unsupported AVC stream: pic_order_cnt_type 1");
+        }
+
+        if sps.separate_colour_plane_flag {
+            reader.skip_n(2); // colour_plane_id
+        }
+
+        reader.skip_n(sps.log2_max_frame_num); // frame_num
+
+        if !sps.frame_mbs_only_flag {
+            let field_pic_flag = reader.get()?;
+
+            if field_pic_flag {
+                bail!("Unsupported AVC stream: interlaced field pictures");
+            }
+        }
+
+        if nal_type == NAL_AVC_IDR {
+            reader.get_ue()?; // idr_pic_id
+        }
+
+        let pic_order_cnt_lsb = if sps.pic_order_cnt_type == 0 {
+            reader.get_n::<u64>(sps.log2_max_pic_order_cnt_lsb) as i64
+        } else {
+            0
+        };
+
+        // Same as HEVC frame types: 2 = I, 1 = P, 0 = B
+        let frame_type = match slice_type % 5 {
+            2 | 4 => 2,
+            0 | 3 => 1,
+            _ => 0,
+        };
+
+        Ok(AvcSliceHeader {
+            first_mb_in_slice,
+            frame_type,
+            pic_order_cnt_lsb,
+            pic_order_cnt_type: sps.pic_order_cnt_type,
+            log2_max_pic_order_cnt_lsb: sps.log2_max_pic_order_cnt_lsb,
+        })
+    }
+
+    fn compute_poc(
+        &mut self,
+        slice: &AvcSliceHeader,
+        nal_type: u8,
+        nal_ref_idc: u8,
+    ) -> Result<i64> {
+        if slice.pic_order_cnt_type != 0 {
+            // Output order is the decoding order
+            return Ok(self.frame_order.pending_frames() as i64);
+        }
+
+        if nal_type == NAL_AVC_IDR {
+            self.prev_poc_msb = 0;
+            self.prev_poc_lsb = 0;
+        }
+
+        let max_lsb = 1_i64 << slice.log2_max_pic_order_cnt_lsb;
+        let lsb = slice.pic_order_cnt_lsb;
+
+        let msb = if lsb < self.prev_poc_lsb && (self.prev_poc_lsb - lsb) >= max_lsb / 2 {
+            self.prev_poc_msb + max_lsb
+        } else if lsb > self.prev_poc_lsb && (lsb - self.prev_poc_lsb) > max_lsb / 2 {
+            self.prev_poc_msb - max_lsb
+        } else {
+            self.prev_poc_msb
+        };
+
+        if nal_ref_idc != 0 {
+            self.prev_poc_msb = msb;
+            self.prev_poc_lsb = lsb;
+        }
+
+        Ok(msb + lsb)
+    }
+}
+
+impl NalParser for AvcParser {
+    fn parse_nal(&mut self, data: &[u8], mut nal: NALUnit) -> Result<NALUnit> {
+        self.parse_nal_data(data, &mut nal)?;
+        self.frame_order.push_nal(&mut nal);
+
+        Ok(nal)
+    }
+
+    fn finish(&mut self) {
+        self.frame_order.finish();
+    }
+
+    fn frame_order(&self) -> &FrameOrder {
+        &self.frame_order
+    }
+}
+
+fn skip_scaling_list(reader: &mut BitVecReader, size: usize) -> Result<()> {
+    let mut last_scale = 8;
+    let mut next_scale = 8;
+
+    for _ in 0..size {
+        if next_scale != 0 {
+            let delta_scale = reader.get_se()?;
+            next_scale = (last_scale + delta_scale + 256) % 256;
+        }
+
+        if next_scale != 0 {
+            last_scale = next_scale;
+        }
+    }
+
+    Ok(())
+}
+
+/// Access unit delimiter NAL unit, without start code
+pub fn avc_aud_for_frame(frame: &Frame) -> Vec<u8> {
+    let primary_pic_type: u8 = match frame.frame_type {
+        2 => 0, // I
+        1 => 1, // P, I
+        _ => 2, // B, P, I
+    };
+
+    // primary_pic_type, then rbsp_stop_one_bit
+    vec![NAL_AVC_AUD, (primary_pic_type << 5) | 0x10]
+}
@@ -0,0 +1,372 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, ensure, Result};
+use indicatif::{ProgressBar, ProgressStyle};
+use regex::Regex;
+
+use super::interrupt::is_interrupted;
+use super::{codec, http_input, initialize_progress_bar, InputCodec};
+use hevc_parser::io::IoFormat;
+
+/// Extensions of the files taken from the input directories, by default
+const DEFAULT_EXTENSIONS: &[&str] = &[
+    "hevc", "h265", "265", "h264", "264", "avc", "h266", "266", "vvc",
+];
+
+/// Inputs of a command, with the options for the input directories
+pub struct BatchInputs {
+    pub paths: Vec<PathBuf>,
+    /// Walks the subdirectories of the input directories
+    pub recursive: bool,
+    /// Extensions of the files taken from the input directories, case sensitive.
+    /// `DEFAULT_EXTENSIONS` if empty
+    pub extensions: Vec<String>,
+}
+
+/// An input file, with its directory relative to the input directory it was found in
+struct InputFile {
+    path: PathBuf,
+    rel_dir: PathBuf,
+}
+
+/// An input and its output, processed by a command
+pub struct BatchJob {
+    pub format: IoFormat,
+    pub input: PathBuf,
+    pub output: PathBuf,
+    /// The output is named from the template, its missing directories are created
+    pub templated: bool,
+}
+
+/// Resolves the inputs of a command to jobs.
+///
+/// Inputs with `*` or `?` wildcards in their file name are expanded,
+/// and input directories are replaced by their files with a matching extension.
+/// A single input is written to `output`, or `default_output`.
+/// Multiple inputs are named from `output_template`, or `default_template`.
+pub fn jobs_from_inputs(
+    cmd: &str,
+    inputs: BatchInputs,
+    output: Option<PathBuf>,
+    output_template: Option<String>,
+    default_output: &str,
+    default_template: &str,
+    input_codec: InputCodec,
+) -> Result<Vec<BatchJob>> {
+    let inputs = expand_inputs(cmd, inputs)?;
+
+    if inputs.len() > 1 {
+        ensure!(
+            output.is_none(),
+            "{}: --output can't be used with multiple inputs, use --output-template",
+            cmd
+        );
+        ensure!(
+            !inputs.iter().any(|input| input.path == Path::new("-")),
+            "{}: piped input can't be used with multiple inputs",
+            cmd
+        );
+    }
+
+    let template = match output_template {
+        Some(template) => Some(template),
+        None if inputs.len() > 1 => Some(default_template.to_string()),
+        None => None,
+    };
+
+    let jobs = inputs
+        .into_iter()
+        .map(
+            |InputFile {
+                 path: input,
+                 rel_dir,
+             }| {
+                let format = codec::format_from_path(&input, input_codec)?;
+
+                let output = match (&template, &output) {
+                    (Some(template), _) => output_from_template(template, &input, &rel_dir),
+                    (None, Some(output)) => output.clone(),
+                    (None, None) => PathBuf::from(default_output),
+                };
+
+                Ok(BatchJob {
+                    format,
+                    input,
+                    output,
+                    templated: template.is_some(),
+                })
+            },
+        )
+        .collect::<Result<Vec<_>>>()?;
+
+    for (i, job) in jobs.iter().enumerate() {
+        if let Some(other) = jobs[..i].iter().find(|other| other.output == job.output) {
+            bail!(
+                "{}: {} and {} would both be written to {}, use {{stem}} in the output template",
+                cmd,
+                other.input.display(),
+                job.input.display(),
+                job.output.display()
+            );
+        }
+    }
+
+    Ok(jobs)
+}
+
+/// Runs every job, with a progress bar prefixed by the job index.
+///
+/// A failed job doesn't stop the batch, the errors are reported at the end.
+/// A single job is run as is, returning its error.
+pub fn run_jobs<F>(jobs: &[BatchJob], mut process: F) -> Result<()>
+where
+    F: FnMut(&BatchJob, ProgressBar) -> Result<()>,
+{
+    if let [job] = jobs {
+        create_output_dir(job)?;
+
+        let pb = initialize_progress_bar(&job.format, &job.input)?;
+        return process(job, pb);
+    }
+
+    let mut failed = Vec::new();
+    let mut processed = 0;
+
+    for (i, job) in jobs.iter().enumerate() {
+        if is_interrupted() {
+            break;
+        }
+
+        println!("[{}/{}] {}", i + 1, jobs.len(), job.input.display());
+
+        let res = create_output_dir(job)
+            .and_then(|_| initialize_progress_bar(&job.format, &job.input))
+            .and_then(|pb| {
+                pb.set_style(ProgressStyle::default_bar().template(
+                "{prefix} [{elapsed_precise}] {bar:60.cyan} {percent}% {bytes_per_sec} ETA {eta}",
+            ));
+                pb.set_prefix(format!("[{}/{}]", i + 1, jobs.len()));
+
+                process(job, pb)
+            });
+
+        if let Err(e) = res {
+            eprintln!("Error processing {}: {:#}", job.input.display(), e);
+            failed.push(&job.input);
+        }
+
+        processed += 1;
+    }
+
+    let skipped = jobs.len() - processed;
+
+    println!(
+        "Processed {} of {} inputs: {} succeeded, {} failed",
+        processed,
+        jobs.len(),
+        processed - failed.len(),
+        failed.len()
+    );
+
+    if !failed.is_empty() {
+        let list = failed
+            .iter()
+            .map(|input| format!("  {}", input.display()))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        bail!(
+            "{} of {} inputs failed:\n{}",
+            failed.len(),
+            jobs.len(),
+            list
+        );
+    }
+
+    ensure!(
+        skipped == 0,
+        "Interrupted, {} inputs were not processed",
+        skipped
+    );
+
+    Ok(())
+}
+
+/// Replaces `{stem}` with the input file name without extension, `{dir}` with the input directory,
+/// and `{rel}` with the input directory relative to the input directory it was found in
+pub fn output_from_template(template: &str, input: &Path, rel_dir: &Path) -> PathBuf {
+    let stem = input
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+
+    let dir = match input.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_string_lossy(),
+        _ => ".".into(),
+    };
+
+    let rel = match rel_dir.as_os_str().is_empty() {
+        true => ".".into(),
+        false => rel_dir.to_string_lossy(),
+    };
+
+    PathBuf::from(
+        template
+            .replace("{stem}", &stem)
+            .replace("{dir}", &dir)
+            .replace("{rel}", &rel),
+    )
+}
+
+/// Creates the missing directories of a templated output, such as `{rel}` in another directory
+fn create_output_dir(job: &BatchJob) -> Result<()> {
+    match job.output.parent() {
+        Some(parent) if job.templated && !parent.as_os_str().is_empty() && !parent.exists() => {
+            fs::create_dir_all(parent)?;
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Expands the `*` and `?` wildcards of the input file names, sorted by name,
+/// and the files of the input directories
+fn expand_inputs(cmd: &str, inputs: BatchInputs) -> Result<Vec<InputFile>> {
+    let BatchInputs {
+        paths,
+        recursive,
+        extensions,
+    } = inputs;
+
+    ensure!(
+        !paths.is_empty(),
+        "No input file provided. See `dovi_tool {} --help`",
+        cmd
+    );
+
+    let extensions: Vec<String> = if extensions.is_empty() {
+        DEFAULT_EXTENSIONS
+            .iter()
+            .map(|ext| ext.to_string())
+            .collect()
+    } else {
+        extensions
+            .iter()
+            .map(|ext| ext.trim_start_matches('.').to_string())
+            .collect()
+    };
+
+    let mut expanded = Vec::with_capacity(paths.len());
+
+    for input in paths {
+        if input.is_dir() {
+            let mut files = Vec::new();
+            walk_dir(&input, Path::new(""), recursive, &extensions, &mut files)?;
+
+            ensure!(
+                !files.is_empty(),
+                "No input file with extension {} in {}{}",
+                extensions.join(", "),
+                input.display(),
+                if recursive {
+                    ""
+                } else {
+                    ", use --recursive for its subdirectories"
+                }
+            );
+
+            expanded.extend(files);
+            continue;
+        }
+
+        let file_name = input
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        if input.exists() || http_input::is_url(&input) || !file_name.contains(['*', '?']) {
+            expanded.push(InputFile {
+                path: input,
+                rel_dir: PathBuf::new(),
+            });
+            continue;
+        }
+
+        let pattern = regex::escape(&file_name)
+            .replace("\\*", ".*")
+            .replace("\\?", ".");
+        let re = Regex::new(&format!("^{}$", pattern))?;
+
+        let dir = match input.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        let mut matches = fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_file())
+            .filter(|entry| re.is_match(&entry.file_name().to_string_lossy()))
+            .map(|entry| input.with_file_name(entry.file_name()))
+            .collect::<Vec<_>>();
+
+        ensure!(
+            !matches.is_empty(),
+            "No input file matches {}",
+            input.display()
+        );
+
+        matches.sort();
+        expanded.extend(matches.into_iter().map(|path| InputFile {
+            path,
+            rel_dir: PathBuf::new(),
+        }));
+    }
+
+    Ok(expanded)
+}
+
+/// Adds the files of a directory with one of the extensions, sorted by name.
+/// The files of a directory come before its subdirectories.
+fn walk_dir(
+    dir: &Path,
+    rel_dir: &Path,
+    recursive: bool,
+    extensions: &[String],
+    files: &mut Vec<InputFile>,
+) -> Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .collect::<Vec<_>>();
+    entries.sort();
+
+    let (subdirs, dir_files): (Vec<_>, Vec<_>) =
+        entries.into_iter().partition(|path| path.is_dir());
+
+    for path in dir_files {
+        let matches = path
+            .extension()
+            .is_some_and(|ext| extensions.contains(&ext.to_string_lossy().into_owned()));
+
+        if matches {
+            files.push(InputFile {
+                path,
+                rel_dir: rel_dir.to_path_buf(),
+            });
+        }
+    }
+
+    if recursive {
+        for subdir in subdirs {
+            let rel_subdir = match subdir.file_name() {
+                Some(name) => rel_dir.join(name),
+                None => continue,
+            };
+
+            walk_dir(&subdir, &rel_subdir, recursive, extensions, files)?;
+        }
+    }
+
+    Ok(())
+}
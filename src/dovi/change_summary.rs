@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use anyhow::Result;
+
+use super::differ::diff_rpus;
+use super::DoviRpu;
+
+/// Maximum number of distinct field changes listed for each section
+const MAX_LISTED_CHANGES: usize = 5;
+
+/// Changes made to the RPUs by the edits or the conversion, for a dry run.
+///
+/// The RPUs are compared by section as with `diff`: header, mapping, NLQ, DM data and each DM level.
+#[derive(Debug, Default)]
+pub struct ChangeSummary {
+    frames: usize,
+    removed_frames: usize,
+    output_frames: Option<usize>,
+    changed_frames: usize,
+    /// Sections in order of first change
+    sections: Vec<SectionChanges>,
+}
+
+#[derive(Debug)]
+struct SectionChanges {
+    section: String,
+    frames: usize,
+    /// Distinct changes of the fields, in order of first change
+    changes: Vec<FieldChange>,
+    /// Index of the changes, by field path and values
+    index: HashMap<(String, String, String), usize>,
+}
+
+#[derive(Debug)]
+struct FieldChange {
+    path: String,
+    old: String,
+    new: String,
+    first_frame: usize,
+    frames: usize,
+}
+
+impl ChangeSummary {
+    /// Adds the RPU of a frame, with the changed RPU or `None` when the frame is removed
+    pub fn add(&mut self, frame: usize, rpu: &DoviRpu, changed: Option<&DoviRpu>) -> Result<()> {
+        self.frames += 1;
+
+        let changed = match changed {
+            Some(changed) => changed,
+            None => {
+                self.removed_frames += 1;
+                return Ok(());
+            }
+        };
+
+        let diffs = diff_rpus(rpu, changed)?;
+
+        if diffs.is_empty() {
+            return Ok(());
+        }
+
+        self.changed_frames += 1;
+
+        let mut frame_sections: Vec<usize> = Vec::new();
+
+        for diff in diffs {
+            let section_index = match self
+                .sections
+                .iter()
+                .position(|section| section.section == diff.section)
+            {
+                Some(i) => i,
+                None => {
+                    self.sections.push(SectionChanges {
+                        section: diff.section,
+                        frames: 0,
+                        changes: Vec::new(),
+                        index: HashMap::new(),
+                    });
+
+                    self.sections.len() - 1
+                }
+            };
+
+            if !frame_sections.contains(&section_index) {
+                frame_sections.push(section_index);
+            }
+
+            let section = &mut self.sections[section_index];
+            let key = (diff.path, diff.value.to_string(), diff.value2.to_string());
+
+            match section.index.get(&key) {
+                Some(&i) => section.changes[i].frames += 1,
+                None => {
+                    section.index.insert(key.clone(), section.changes.len());
+
+                    let (path, old, new) = key;
+                    section.changes.push(FieldChange {
+                        path,
+                        old,
+                        new,
+                        first_frame: frame,
+                        frames: 1,
+                    });
+                }
+            }
+        }
+
+        for i in frame_sections {
+            self.sections[i].frames += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Number of frames written, when different from the remaining frames
+    pub fn set_output_frames(&mut self, output_frames: usize) {
+        self.output_frames = Some(output_frames);
+    }
+}
+
+impl fmt::Display for ChangeSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Dry run, no output written. Summary of the changes:")?;
+
+        let output_frames = self
+            .output_frames
+            .unwrap_or(self.frames - self.removed_frames);
+
+        if output_frames != self.frames {
+            write!(f, "\n  Frames: {} -> {}", self.frames, output_frames)?;
+        }
+
+        if self.removed_frames > 0 {
+            write!(f, "\n  Removed frames: {}", self.removed_frames)?;
+        }
+
+        if self.changed_frames == 0 {
+            return write!(f, "\n  No metadata changed in the {} frames", self.frames);
+        }
+
+        write!(
+            f,
+            "\n  Changed frames: {} of {}",
+            self.changed_frames, self.frames
+        )?;
+
+        for section in &self.sections {
+            write!(f, "\n  {}: {} frames", section.section, section.frames)?;
+
+            for change in section.changes.iter().take(MAX_LISTED_CHANGES) {
+                write!(
+                    f,
+                    "\n    {}: {} -> {}, {} frames from frame {}",
+                    change.path, change.old, change.new, change.frames, change.first_frame
+                )?;
+            }
+
+            if section.changes.len() > MAX_LISTED_CHANGES {
+                write!(
+                    f,
+                    "\n    ... {} more changes",
+                    section.changes.len() - MAX_LISTED_CHANGES
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+use std::fs::read_to_string;
+use std::path::Path;
+
+use anyhow::{bail, ensure, Result};
+use regex::Regex;
+
+use super::timecode::{parse_timestamp, FrameClock};
+
+/// Chapters of a video, from an OGM or Matroska XML chapters file
+#[derive(Debug, Clone)]
+pub struct Chapters {
+    /// Sorted by start time
+    chapters: Vec<Chapter>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chapter {
+    pub name: Option<String>,
+    /// Start time in seconds
+    pub start: f64,
+    /// End time in seconds, only in Matroska chapters
+    pub end: Option<f64>,
+}
+
+impl Chapters {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let content = read_to_string(path)?;
+        let chapters = Self::parse(&content)?;
+
+        ensure!(
+            !chapters.chapters.is_empty(),
+            "No chapter timestamps found in {}",
+            path.display()
+        );
+
+        Ok(chapters)
+    }
+
+    /// Parses Matroska XML chapters, or OGM chapters otherwise.
+    ///
+    /// OGM lines without a `HH:MM:SS.sss` timestamp are ignored, and `CHAPTERxxNAME` lines name the chapters.
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut chapters = if content.trim_start().starts_with('<') {
+            parse_matroska(content)?
+        } else {
+            parse_ogm(content)
+        };
+
+        chapters.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap());
+
+        Ok(Self { chapters })
+    }
+
+    /// Number of the chapter referenced by its name, or as `Chapter N` numbered from 1
+    pub fn find(&self, value: &str) -> Option<usize> {
+        let value = value.trim();
+
+        self.chapters
+            .iter()
+            .position(|chapter| {
+                chapter
+                    .name
+                    .as_deref()
+                    .is_some_and(|name| name.eq_ignore_ascii_case(value))
+            })
+            .map(|i| i + 1)
+            .or_else(|| parse_chapter_ref(value))
+    }
+
+    /// Start frames of every chapter
+    pub fn start_frames(&self, clock: &FrameClock) -> Result<Vec<usize>> {
+        self.chapters
+            .iter()
+            .map(|chapter| clock.frame_at(chapter.start))
+            .collect()
+    }
+
+    /// Start frame of a chapter, numbered from 1
+    pub fn start_frame(&self, number: usize, clock: &FrameClock) -> Result<usize> {
+        let chapter = self.get(number)?;
+
+        clock.frame_at(chapter.start)
+    }
+
+    /// Last frame of a chapter, numbered from 1: the frame before the next chapter,
+    /// or before the end time of the last chapter
+    pub fn end_frame(&self, number: usize, clock: &FrameClock) -> Result<usize> {
+        let chapter = self.get(number)?;
+
+        let end = match self.chapters.get(number) {
+            Some(next) => next.start,
+            None => match chapter.end {
+                Some(end) => end,
+                None => bail!(
+                    "Chapter {} is the last one and has no end time, use its frames instead",
+                    number
+                ),
+            },
+        };
+
+        Ok(clock
+            .frame_at(end)?
+            .saturating_sub(1)
+            .max(clock.frame_at(chapter.start)?))
+    }
+
+    fn get(&self, number: usize) -> Result<&Chapter> {
+        match number.checked_sub(1).and_then(|i| self.chapters.get(i)) {
+            Some(chapter) => Ok(chapter),
+            None => bail!(
+                "Invalid chapter {}, the chapters file has {} chapters",
+                number,
+                self.chapters.len()
+            ),
+        }
+    }
+}
+
+/// Chapter number of a `Chapter N` reference, case insensitive
+pub fn parse_chapter_ref(value: &str) -> Option<usize> {
+    let value = value.trim();
+
+    match value.get(..7) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("chapter") => value[7..].trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// OGM format: `CHAPTER01=00:00:00.000` and `CHAPTER01NAME=Intro`
+fn parse_ogm(content: &str) -> Vec<Chapter> {
+    let mut chapters: Vec<(String, Chapter)> = Vec::new();
+    let mut names: Vec<(String, String)> = Vec::new();
+
+    for line in content.lines() {
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim().to_ascii_uppercase(), value.trim()),
+            None => (String::new(), line.trim()),
+        };
+
+        if let Some(id) = key.strip_suffix("NAME") {
+            names.push((id.to_string(), value.to_string()));
+        } else if let Some(start) = parse_timestamp(value) {
+            let chapter = Chapter {
+                name: None,
+                start,
+                end: None,
+            };
+
+            chapters.push((key, chapter));
+        }
+    }
+
+    chapters
+        .into_iter()
+        .map(|(id, mut chapter)| {
+            chapter.name = names
+                .iter()
+                .find(|(name_id, _)| !id.is_empty() && name_id == &id)
+                .map(|(_, name)| name.clone());
+
+            chapter
+        })
+        .collect()
+}
+
+/// Matroska XML chapters, as written by mkvextract
+fn parse_matroska(content: &str) -> Result<Vec<Chapter>> {
+    let atom_re = Regex::new(r"(?s)<ChapterAtom>(.*?)</ChapterAtom>")?;
+    let start_re = Regex::new(r"<ChapterTimeStart>\s*([^<]+?)\s*</ChapterTimeStart>")?;
+    let end_re = Regex::new(r"<ChapterTimeEnd>\s*([^<]+?)\s*</ChapterTimeEnd>")?;
+    let name_re = Regex::new(r"<ChapterString>([^<]*)</ChapterString>")?;
+
+    atom_re
+        .captures_iter(content)
+        .map(|atom| {
+            let atom = &atom[1];
+
+            let timestamp = |re: &Regex| -> Result<Option<f64>> {
+                match re.captures(atom) {
+                    Some(captures) => match parse_timestamp(&captures[1]) {
+                        Some(seconds) => Ok(Some(seconds)),
+                        None => bail!("Invalid chapter timestamp {}", &captures[1]),
+                    },
+                    None => Ok(None),
+                }
+            };
+
+            let start = match timestamp(&start_re)? {
+                Some(start) => start,
+                None => bail!("Invalid Matroska chapters: ChapterAtom without ChapterTimeStart"),
+            };
+
+            Ok(Chapter {
+                name: name_re
+                    .captures(atom)
+                    .map(|captures| captures[1].trim().to_string()),
+                start,
+                end: timestamp(&end_re)?,
+            })
+        })
+        .collect()
+}
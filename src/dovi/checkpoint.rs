@@ -0,0 +1,159 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::{create_output_file, parse_byte_size};
+
+/// Input bytes demuxed between checkpoints, when not set with `--checkpoint-interval`
+pub const DEFAULT_CHECKPOINT_INTERVAL: u64 = 1024 * 1024 * 1024;
+
+/// Progress of a demux, saved after every segment of the input.
+///
+/// The segments start at random access points, so that a resumed demux starts
+/// at the checkpoint offset as a new stream, appending to the outputs.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    input: PathBuf,
+    /// Size of the input, to detect a different input
+    input_size: u64,
+    /// Input bytes demuxed, up to the next random access point
+    offset: u64,
+    /// Outputs with their size at the checkpoint
+    outputs: Vec<CheckpointOutput>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CheckpointOutput {
+    path: PathBuf,
+    size: u64,
+}
+
+impl Checkpoint {
+    /// Starts a demux, creating the empty outputs
+    pub fn new(input: &Path, input_size: u64, outputs: &[&Path]) -> Result<Self> {
+        for path in outputs {
+            create_output_file(path)?;
+        }
+
+        Ok(Self {
+            input: input.to_path_buf(),
+            input_size,
+            offset: 0,
+            outputs: outputs
+                .iter()
+                .map(|path| CheckpointOutput {
+                    path: path.to_path_buf(),
+                    size: 0,
+                })
+                .collect(),
+        })
+    }
+
+    /// Reads the checkpoint file, if it exists
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let file = File::open(path)?;
+        let checkpoint = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("Invalid checkpoint file {}", path.display()))?;
+
+        Ok(Some(checkpoint))
+    }
+
+    /// Checks that the checkpoint is for the same demux, and truncates the outputs
+    /// to their size at the checkpoint, discarding the data of the interrupted segment
+    pub fn resume(&self, input: &Path, input_size: u64, outputs: &[&Path]) -> Result<()> {
+        ensure!(
+            self.input == input && self.input_size == input_size,
+            "The checkpoint is for a different input: {} of {} bytes",
+            self.input.display(),
+            self.input_size
+        );
+
+        let checkpoint_outputs: Vec<&Path> = self
+            .outputs
+            .iter()
+            .map(|output| output.path.as_path())
+            .collect();
+
+        ensure!(
+            checkpoint_outputs == outputs,
+            "The checkpoint is for different outputs: {}",
+            checkpoint_outputs
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        for output in &self.outputs {
+            let file = match OpenOptions::new().write(true).open(&output.path) {
+                Ok(file) => file,
+                Err(e) => bail!("Can't resume the output {}: {}", output.path.display(), e),
+            };
+
+            ensure!(
+                file.metadata()?.len() >= output.size,
+                "Can't resume the output {}, it is smaller than at the checkpoint",
+                output.path.display()
+            );
+
+            file.set_len(output.size)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Appends the segment files to the outputs, removing them, then saves the checkpoint.
+    /// The checkpoint file is replaced once fully written.
+    pub fn commit_segment(
+        &mut self,
+        path: &Path,
+        segment_end: u64,
+        segment_path: impl Fn(&Path) -> PathBuf,
+    ) -> Result<()> {
+        for output in &mut self.outputs {
+            let segment = segment_path(&output.path);
+
+            let mut file = OpenOptions::new().append(true).open(&output.path)?;
+            io::copy(&mut File::open(&segment)?, &mut file)?;
+            file.flush()?;
+
+            fs::remove_file(segment)?;
+
+            output.size = file.metadata()?.len();
+        }
+
+        self.offset = segment_end;
+
+        let mut temp_file_name = path.file_name().unwrap_or_default().to_os_string();
+        temp_file_name.push(".tmp");
+        let temp_path = path.with_file_name(temp_file_name);
+
+        let mut writer = BufWriter::new(create_output_file(&temp_path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.flush()?;
+        drop(writer);
+
+        fs::rename(&temp_path, path)?;
+
+        Ok(())
+    }
+}
+
+/// Parses the checkpoint interval in bytes, with an optional K, M or G suffix
+pub fn parse_checkpoint_interval(value: &str) -> Result<u64> {
+    match parse_byte_size(value) {
+        Some(size) if size > 0 => Ok(size),
+        _ => bail!("Invalid checkpoint interval {}", value),
+    }
+}
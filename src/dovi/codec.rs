@@ -0,0 +1,302 @@
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::Result;
+
+use hevc_parser::hevc::{
+    Frame, NALUnit, NAL_AUD, NAL_EOB_NUT, NAL_EOS_NUT, NAL_UNSPEC62, NAL_UNSPEC63, NAL_VPS,
+};
+use hevc_parser::io::FOUR_SIZED_NALU_TYPES;
+use hevc_parser::NALUStartCode;
+
+use super::avc_parser::{
+    avc_aud_for_frame, AVC_RPU_NAL_HEADER, NAL_AVC_AUD, NAL_AVC_DV_EL, NAL_AVC_DV_RPU,
+    NAL_AVC_END_OF_SEQ, NAL_AVC_END_OF_STREAM, NAL_AVC_IDR, NAL_AVC_PPS, NAL_AVC_SLICE,
+    NAL_AVC_SPS,
+};
+use super::vvc_parser::{
+    vvc_aud, vvc_aud_for_frame, NAL_VVC_AUD, NAL_VVC_DV_EL, NAL_VVC_DV_RPU, NAL_VVC_EOB,
+    NAL_VVC_EOS, NAL_VVC_GDR, NAL_VVC_IDR_W_RADL, NAL_VVC_PH, NAL_VVC_PPS, NAL_VVC_RSV_IRAP_11,
+    NAL_VVC_SPS, NAL_VVC_VPS, VVC_RPU_NAL_HEADER,
+};
+use super::{http_input, DoviRpu, InputCodec, IoFormat, WriteStartCodePreset};
+
+const HEVC_RPU_NAL_HEADER: [u8; 2] = [0x7C, 0x01];
+
+const AVC_FOUR_SIZED_NAL_TYPES: &[u8] = &[NAL_AVC_SPS, NAL_AVC_PPS, NAL_AVC_AUD, NAL_AVC_DV_RPU];
+const VVC_FOUR_SIZED_NAL_TYPES: &[u8] = &[
+    NAL_VVC_VPS,
+    NAL_VVC_SPS,
+    NAL_VVC_PPS,
+    NAL_VVC_AUD,
+    NAL_VVC_DV_RPU,
+];
+
+const AVC_EXTENSIONS: &[&str] = &["h264", "264", "avc", "jsv"];
+const VVC_EXTENSIONS: &[&str] = &["h266", "266", "vvc"];
+
+/// Video codec of the bitstream carrying the Dolby Vision NAL units
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Codec {
+    Hevc,
+    Avc,
+    Vvc,
+}
+
+/// Same as `hevc_parser::io::format_from_path`, also accepting raw AVC and VVC files.
+///
+/// URLs are read as raw bitstreams.
+pub fn format_from_path(input: &Path, input_codec: InputCodec) -> Result<IoFormat> {
+    let raw_file = Codec::from_input(input_codec, input) != Codec::Hevc && input.is_file();
+
+    if raw_file || http_input::is_url(input) {
+        Ok(IoFormat::Raw)
+    } else {
+        hevc_parser::io::format_from_path(input)
+    }
+}
+
+fn codec_from_extension(input: &Path) -> Option<Codec> {
+    let ext = input.extension()?.to_str()?.to_lowercase();
+
+    if AVC_EXTENSIONS.contains(&ext.as_str()) {
+        Some(Codec::Avc)
+    } else if VVC_EXTENSIONS.contains(&ext.as_str()) {
+        Some(Codec::Vvc)
+    } else {
+        None
+    }
+}
+
+impl Codec {
+    /// Resolves the codec, from the file extension when set to `auto`
+    pub fn from_input(input_codec: InputCodec, input: &Path) -> Self {
+        match input_codec {
+            InputCodec::Hevc => Codec::Hevc,
+            InputCodec::Avc => Codec::Avc,
+            InputCodec::Vvc => Codec::Vvc,
+            InputCodec::Auto => codec_from_extension(input).unwrap_or(Codec::Hevc),
+        }
+    }
+
+    pub fn rpu_nal_type(&self) -> u8 {
+        match self {
+            Codec::Hevc => NAL_UNSPEC62,
+            Codec::Avc => NAL_AVC_DV_RPU,
+            Codec::Vvc => NAL_VVC_DV_RPU,
+        }
+    }
+
+    pub fn el_nal_type(&self) -> u8 {
+        match self {
+            Codec::Hevc => NAL_UNSPEC63,
+            Codec::Avc => NAL_AVC_DV_EL,
+            Codec::Vvc => NAL_VVC_DV_EL,
+        }
+    }
+
+    pub fn aud_nal_type(&self) -> u8 {
+        match self {
+            Codec::Hevc => NAL_AUD,
+            Codec::Avc => NAL_AVC_AUD,
+            Codec::Vvc => NAL_VVC_AUD,
+        }
+    }
+
+    /// NAL unit types written with a 4 bytes start code by the `AnnexB` preset
+    fn four_sized_nal_types(&self) -> &'static [u8] {
+        match self {
+            Codec::Hevc => FOUR_SIZED_NALU_TYPES,
+            Codec::Avc => AVC_FOUR_SIZED_NAL_TYPES,
+            Codec::Vvc => VVC_FOUR_SIZED_NAL_TYPES,
+        }
+    }
+
+    /// Same as `NALUnit::write_with_preset`, with the parameter set types of the codec.
+    ///
+    /// The `Four` preset always writes 4 bytes start codes.
+    /// The `AnnexB` and `Ts` presets only write the zero_byte for the parameter sets and the first NAL unit of an access unit.
+    pub fn write_nal(
+        &self,
+        writer: &mut dyn Write,
+        data: &[u8],
+        preset: WriteStartCodePreset,
+        nal_type: u8,
+        first_nal: bool,
+    ) -> Result<()> {
+        let start_code = match preset {
+            WriteStartCodePreset::Four => NALUStartCode::Length4,
+            WriteStartCodePreset::AnnexB | WriteStartCodePreset::Ts
+                if first_nal || self.four_sized_nal_types().contains(&nal_type) =>
+            {
+                NALUStartCode::Length4
+            }
+            WriteStartCodePreset::AnnexB | WriteStartCodePreset::Ts => NALUStartCode::Length3,
+        };
+
+        writer.write_all(start_code.slice())?;
+        writer.write_all(data)?;
+
+        Ok(())
+    }
+
+    /// NAL unit header of the written RPUs
+    fn rpu_nal_header(&self) -> &'static [u8] {
+        match self {
+            Codec::Hevc => &HEVC_RPU_NAL_HEADER,
+            Codec::Avc => &[AVC_RPU_NAL_HEADER],
+            Codec::Vvc => &VVC_RPU_NAL_HEADER,
+        }
+    }
+
+    /// Size of the NAL unit header in bytes
+    pub fn nal_header_len(&self) -> usize {
+        self.rpu_nal_header().len()
+    }
+
+    pub fn is_slice(&self, nal_type: u8) -> bool {
+        match self {
+            Codec::Hevc => NALUnit::is_type_slice(nal_type),
+            Codec::Avc => matches!(nal_type, NAL_AVC_SLICE | NAL_AVC_IDR),
+            Codec::Vvc => nal_type <= NAL_VVC_RSV_IRAP_11,
+        }
+    }
+
+    /// NAL unit type from the NAL header, without start code
+    pub fn nal_type(&self, data: &[u8]) -> u8 {
+        match self {
+            Codec::Hevc => (data[0] >> 1) & 0x3F,
+            Codec::Avc => data[0] & 0x1F,
+            Codec::Vvc => data[1] >> 3,
+        }
+    }
+
+    /// Layer of the NAL unit from the NAL header, without start code
+    pub fn nuh_layer_id(&self, data: &[u8]) -> u8 {
+        match self {
+            Codec::Hevc => ((data[0] & 0x01) << 5) | (data[1] >> 3),
+            Codec::Avc => 0,
+            Codec::Vvc => data[0] & 0x3F,
+        }
+    }
+
+    /// Copy of the NAL unit moved to the base layer, with `nuh_layer_id` 0
+    pub fn base_layer_nal(&self, data: &[u8]) -> Vec<u8> {
+        let mut nal = data.to_vec();
+
+        match self {
+            Codec::Hevc => {
+                nal[0] &= 0xFE;
+                nal[1] &= 0x07;
+            }
+            Codec::Avc => (),
+            Codec::Vvc => nal[0] &= 0xC0,
+        }
+
+        nal
+    }
+
+    /// Whether the NAL unit starts a new picture, from the first bit after the NAL header.
+    /// The data must include at least one byte after the header.
+    ///
+    /// The pictures of the enhancement layers are part of the base layer's access unit.
+    pub fn starts_picture(&self, nal_type: u8, data: &[u8]) -> bool {
+        let first_bit = data[self.nal_header_len()] & 0x80 != 0;
+
+        if self.nuh_layer_id(data) > 0 {
+            return false;
+        }
+
+        match self {
+            // first_slice_segment_in_pic_flag
+            Codec::Hevc => self.is_slice(nal_type) && first_bit,
+            // first_mb_in_slice == 0
+            Codec::Avc => self.is_slice(nal_type) && first_bit,
+            // Picture header NAL unit, or sh_picture_header_in_slice_header_flag
+            Codec::Vvc => nal_type == NAL_VVC_PH || (self.is_slice(nal_type) && first_bit),
+        }
+    }
+
+    /// Whether the NAL unit starts a new access unit: an AUD, the parameter sets or the first slice of a picture.
+    /// The data must include at least one byte after the header.
+    pub fn starts_access_unit(&self, nal_type: u8, data: &[u8]) -> bool {
+        self.nuh_layer_id(data) == 0
+            && (nal_type == self.aud_nal_type()
+                || self.is_parameter_set_start(nal_type)
+                || self.starts_picture(nal_type, data))
+    }
+
+    /// First parameter set of a random access point, decoding can start from its access unit
+    pub fn is_parameter_set_start(&self, nal_type: u8) -> bool {
+        match self {
+            Codec::Hevc => nal_type == NAL_VPS,
+            Codec::Avc => nal_type == NAL_AVC_SPS,
+            // The VPS is optional
+            Codec::Vvc => matches!(nal_type, NAL_VVC_VPS | NAL_VVC_SPS),
+        }
+    }
+
+    /// End of sequence or end of bitstream NAL units
+    pub fn is_end_of_stream(&self, nal_type: u8) -> bool {
+        match self {
+            Codec::Hevc => matches!(nal_type, NAL_EOS_NUT | NAL_EOB_NUT),
+            Codec::Avc => matches!(nal_type, NAL_AVC_END_OF_SEQ | NAL_AVC_END_OF_STREAM),
+            Codec::Vvc => matches!(nal_type, NAL_VVC_EOS | NAL_VVC_EOB),
+        }
+    }
+
+    /// Access unit delimiter NAL unit for the frame, without start code
+    pub fn aud_for_frame(&self, frame: &Frame) -> Vec<u8> {
+        match self {
+            Codec::Hevc => hevc_parser::utils::aud_for_frame(frame, None),
+            Codec::Avc => avc_aud_for_frame(frame),
+            Codec::Vvc => vvc_aud_for_frame(frame),
+        }
+    }
+
+    /// Access unit delimiter NAL unit allowing any slice type, without start code.
+    /// For access units not parsed yet, from the available NAL units starting with its first one.
+    pub fn aud_for_nals(&self, nals: &[NALUnit]) -> Vec<u8> {
+        match self {
+            Codec::Vvc => {
+                let decoded_index = nals.first().map(|nal| nal.decoded_frame_index);
+
+                // Unknown when the first slice is not available yet
+                let irap_or_gdr_flag = nals
+                    .iter()
+                    .take_while(|nal| Some(nal.decoded_frame_index) == decoded_index)
+                    .find(|nal| self.is_slice(nal.nal_type))
+                    .is_some_and(|nal| (NAL_VVC_IDR_W_RADL..=NAL_VVC_GDR).contains(&nal.nal_type));
+
+                // B, P and I slices
+                vvc_aud(irap_or_gdr_flag, 2)
+            }
+            // Defaults to B, P and I slices
+            _ => self.aud_for_frame(&Frame::default()),
+        }
+    }
+
+    /// Parses an escaped RPU NAL unit, including the NAL header
+    pub fn parse_rpu_nalu(&self, data: &[u8]) -> Result<DoviRpu> {
+        DoviRpu::parse_unspec62_nalu(&data[self.nal_header_len()..])
+    }
+
+    /// Writes the RPU as an escaped NAL unit, including the NAL header.
+    /// The start code is not included.
+    pub fn write_rpu_nalu(&self, dovi_rpu: &DoviRpu) -> Result<Vec<u8>> {
+        let data = dovi_rpu.write_hevc_unspec62_nalu()?;
+
+        if *self == Codec::Hevc {
+            return Ok(data);
+        }
+
+        // Replace the HEVC 0x7C01 header
+        let header = self.rpu_nal_header();
+
+        let mut nal = Vec::with_capacity(header.len() + data.len() - 2);
+        nal.extend_from_slice(header);
+        nal.extend_from_slice(&data[2..]);
+
+        Ok(nal)
+    }
+}
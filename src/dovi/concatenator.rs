@@ -0,0 +1,69 @@
+use std::io::{stdout, Write};
+use std::path::PathBuf;
+
+use anyhow::{bail, ensure, Result};
+
+use dolby_vision::rpu::generate::GenerateConfig;
+use utilities_dovi::parse_rpu_file;
+
+use crate::commands::ConcatArgs;
+
+use super::{set_scene_cut, write_rpu_file, DoviRpu};
+
+pub struct Concatenator {
+    inputs: Vec<PathBuf>,
+    rpu_out: PathBuf,
+}
+
+impl Concatenator {
+    pub fn concat(args: ConcatArgs) -> Result<()> {
+        let ConcatArgs { inputs, rpu_out } = args;
+
+        let concatenator = Concatenator {
+            inputs,
+            rpu_out: rpu_out.unwrap_or_else(|| PathBuf::from("RPU_concat.bin")),
+        };
+
+        concatenator.execute()
+    }
+
+    fn execute(&self) -> Result<()> {
+        let mut rpus: Vec<DoviRpu> = Vec::new();
+
+        for input in &self.inputs {
+            println!("Parsing RPU file {}...", input.display());
+            stdout().flush().ok();
+
+            let mut part = match parse_rpu_file(input)? {
+                Some(part) if !part.is_empty() => part,
+                _ => bail!("No RPU found in file {}", input.display()),
+            };
+
+            if let (Some(previous), Some(first)) = (rpus.last(), part.first()) {
+                ensure!(
+                    previous.dovi_profile == first.dovi_profile,
+                    "Mismatched profile: {} is profile {}, expected {}",
+                    input.display(),
+                    first.dovi_profile,
+                    previous.dovi_profile
+                );
+            }
+
+            // The first frame of every part must start a new scene
+            set_scene_cut(&mut part[0]);
+
+            rpus.extend(part);
+        }
+
+        println!(
+            "Joined {} RPU files, {} frames",
+            self.inputs.len(),
+            rpus.len()
+        );
+
+        let data = GenerateConfig::encode_rpus(&mut rpus);
+        write_rpu_file(&self.rpu_out, data)?;
+
+        Ok(())
+    }
+}
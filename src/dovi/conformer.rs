@@ -0,0 +1,133 @@
+use std::io::{stdout, Write};
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+
+use dolby_vision::rpu::generate::GenerateConfig;
+use utilities_dovi::parse_rpu_file;
+
+use crate::commands::ConformArgs;
+
+use super::timecode::parse_fps;
+use super::{input_from_either, write_rpu_file, DoviRpu};
+
+pub struct Conformer {
+    input: PathBuf,
+    rpu_out: PathBuf,
+    from_fps: f64,
+    to_fps: f64,
+}
+
+/// Output frame of the conformed sequence
+#[derive(Debug, PartialEq, Eq)]
+pub struct ConformedFrame {
+    /// Input frame index of the metadata
+    pub source: usize,
+    pub scene_cut: bool,
+}
+
+impl Conformer {
+    pub fn conform(args: ConformArgs) -> Result<()> {
+        let ConformArgs {
+            input,
+            input_pos,
+            rpu_out,
+            from_fps,
+            to_fps,
+        } = args;
+
+        let input = input_from_either("conform", input, input_pos)?;
+
+        let conformer = Conformer {
+            input,
+            rpu_out: rpu_out.unwrap_or_else(|| PathBuf::from("RPU_conformed.bin")),
+            from_fps: parse_fps(&from_fps)?,
+            to_fps: parse_fps(&to_fps)?,
+        };
+
+        println!("Parsing RPU file...");
+        stdout().flush().ok();
+
+        if let Some(rpus) = parse_rpu_file(&conformer.input)? {
+            conformer.execute(&rpus)?;
+        }
+
+        Ok(())
+    }
+
+    fn execute(&self, rpus: &[DoviRpu]) -> Result<()> {
+        if rpus.is_empty() {
+            bail!("No RPU to conform");
+        }
+
+        let scene_cuts: Vec<bool> = rpus
+            .iter()
+            .map(|rpu| {
+                rpu.vdr_dm_data
+                    .as_ref()
+                    .is_some_and(|dm| dm.scene_refresh_flag == 1)
+            })
+            .collect();
+
+        let frames = conform_frames(&scene_cuts, self.from_fps, self.to_fps);
+
+        println!(
+            "Conforming {} frames at {:.3} fps to {} frames at {:.3} fps",
+            rpus.len(),
+            self.from_fps,
+            frames.len(),
+            self.to_fps
+        );
+
+        let mut conformed: Vec<DoviRpu> = frames
+            .iter()
+            .map(|frame| {
+                let mut rpu = rpus[frame.source].clone();
+
+                if let Some(ref mut vdr_dm_data) = rpu.vdr_dm_data {
+                    if (vdr_dm_data.scene_refresh_flag == 1) != frame.scene_cut {
+                        vdr_dm_data.set_scene_cut(frame.scene_cut);
+                        rpu.modified = true;
+                    }
+                }
+
+                rpu
+            })
+            .collect();
+
+        let data = GenerateConfig::encode_rpus(&mut conformed);
+        write_rpu_file(&self.rpu_out, data)?;
+
+        Ok(())
+    }
+}
+
+/// Maps every output frame to the input frame displayed at the same time.
+///
+/// Frames are duplicated or dropped at regular intervals, sampling the input at the
+/// middle of every output frame. An output frame starts a scene if any input scene
+/// starts between the previous output frame and its own source, so the scene cuts
+/// of dropped frames move to the next frame.
+pub fn conform_frames(scene_cuts: &[bool], from_fps: f64, to_fps: f64) -> Vec<ConformedFrame> {
+    if scene_cuts.is_empty() {
+        return Vec::new();
+    }
+
+    let last_frame = scene_cuts.len() - 1;
+    let output_len = ((scene_cuts.len() as f64 * to_fps / from_fps).round() as usize).max(1);
+
+    let mut next_source = 0;
+
+    (0..output_len)
+        .map(|i| {
+            let source = (((i as f64 + 0.5) * from_fps / to_fps).floor() as usize).min(last_frame);
+
+            // Duplicated frames continue the scene
+            let scene_cut =
+                source >= next_source && scene_cuts[next_source..=source].contains(&true);
+            next_source = next_source.max(source + 1);
+
+            ConformedFrame { source, scene_cut }
+        })
+        .collect()
+}
@@ -1,17 +1,20 @@
-use anyhow::{bail, Result};
+use anyhow::{bail, ensure, Result};
 use indicatif::ProgressBar;
-use std::path::PathBuf;
+use log::warn;
 
 use crate::commands::ConvertArgs;
 
-use super::{general_read_write, input_from_either, CliOptions, IoFormat};
+use super::batch::{self, BatchInputs, BatchJob};
+use super::codec::Codec;
+use super::hdr10_sei::Hdr10SeiOpts;
+use super::sei_filter::SeiPayload;
+use super::{general_read_write, CliOptions, IoFormat};
 
 use general_read_write::{DoviProcessor, DoviWriter};
 
 pub struct Converter {
-    format: IoFormat,
-    input: PathBuf,
-    output: PathBuf,
+    jobs: Vec<BatchJob>,
+    dry_run: bool,
 }
 
 impl Converter {
@@ -20,27 +23,104 @@ impl Converter {
             input,
             input_pos,
             output,
+            output_template,
+            recursive,
+            extensions,
             discard,
+            keep_rpu_position,
+            rewrite_vps,
+            retarget_trim,
+            insert_hdr10_sei,
+            master_display,
+            max_cll,
+            dry_run,
+            el_report,
         } = args;
 
         options.discard_el = discard;
+        options.keep_rpu_position = keep_rpu_position;
+        options.rewrite_vps = rewrite_vps;
 
-        let input = input_from_either("convert", input, input_pos)?;
-        let format = hevc_parser::io::format_from_path(&input)?;
+        if let Some(target_nits) = retarget_trim {
+            ensure!(
+                (1..=10_000).contains(&target_nits),
+                "Invalid retarget trim {} nits, must be between 1 and 10000",
+                target_nits
+            );
 
-        let output = match output {
-            Some(path) => path,
-            None => match options.discard_el {
-                true => PathBuf::from("BL_RPU.hevc"),
-                false => PathBuf::from("BL_EL_RPU.hevc"),
-            },
+            options.retarget_trim = Some(target_nits);
+
+            // Same as the other edits, mode 0 converts the RPUs without changing the profile
+            if options.mode.is_none() {
+                options.mode = Some(0);
+            }
+        }
+
+        let inputs = BatchInputs {
+            paths: if input.is_empty() { input_pos } else { input },
+            recursive,
+            extensions,
         };
 
-        Ok(Self {
-            format,
-            input,
+        let (default_output, default_template) = match options.discard_el {
+            true => ("BL_RPU.hevc", "{stem}.BL_RPU.hevc"),
+            false => ("BL_EL_RPU.hevc", "{stem}.BL_EL_RPU.hevc"),
+        };
+
+        let jobs = batch::jobs_from_inputs(
+            "convert",
+            inputs,
             output,
-        })
+            output_template,
+            default_output,
+            default_template,
+            options.input_codec,
+        )?;
+
+        if insert_hdr10_sei {
+            ensure!(
+                jobs.iter()
+                    .all(|job| Codec::from_input(options.input_codec, &job.input) == Codec::Hevc),
+                "Converter: HDR10 SEI insertion only supports HEVC inputs"
+            );
+
+            options.hdr10_sei = Some(Hdr10SeiOpts {
+                mastering_display: master_display.as_deref().map(str::parse).transpose()?,
+                content_light_level: max_cll.as_deref().map(str::parse).transpose()?,
+            });
+
+            // Replaced by the inserted messages
+            options.sei_filter.add(SeiPayload::Mdcv);
+            options.sei_filter.add(SeiPayload::Cll);
+        }
+
+        if el_report.is_some() {
+            ensure!(
+                options.mode == Some(2),
+                "convert: --el-report requires --mode 2"
+            );
+            ensure!(
+                jobs.len() == 1,
+                "convert: --el-report only supports a single input, got {}",
+                jobs.len()
+            );
+            ensure!(
+                Codec::from_input(options.input_codec, &jobs[0].input) == Codec::Hevc,
+                "convert: --el-report only supports HEVC inputs"
+            );
+
+            options.el_report = el_report;
+        }
+
+        // Only the RPU header and DM coefficients are converted
+        if options.mode == Some(3) {
+            warn!(
+"mode 3 only converts the RPUs. The profile 5 base layer is IPTPQc2, \
+                 it must be converted to BT.2020 PQ and re-encoded, see `generate-lut --reshape-only`."
+            );
+        }
+
+        Ok(Self { jobs, dry_run })
     }
 
     pub fn convert(args: ConvertArgs, mut options: CliOptions) -> Result<()> {
@@ -49,18 +129,33 @@ impl Converter {
     }
 
     fn process_input(&self, options: CliOptions) -> Result<()> {
-        let pb = super::initialize_progress_bar(&self.format, &self.input)?;
-
-        match self.format {
+        batch::run_jobs(&self.jobs, |job, pb| match job.format {
             IoFormat::Matroska => bail!("Converter: Matroska input is unsupported"),
-            _ => self.convert_raw_hevc(pb, options),
-        }
+            _ if self.dry_run => Self::dry_run_raw_hevc(job, pb, options.clone()),
+            _ => Self::convert_raw_hevc(job, pb, options.clone()),
+        })
+    }
+
+    fn convert_raw_hevc(job: &BatchJob, pb: ProgressBar, options: CliOptions) -> Result<()> {
+        let dovi_writer = DoviWriter::new(
+            None,
+            None,
+            None,
+            Some(&job.output),
+            None,
+            options.write_buffer,
+        )?;
+        let mut dovi_processor = DoviProcessor::new(options, job.input.clone(), dovi_writer, pb);
+
+        dovi_processor.read_write_from_io(&job.format)
     }
 
-    fn convert_raw_hevc(&self, pb: ProgressBar, options: CliOptions) -> Result<()> {
-        let dovi_writer = DoviWriter::new(None, None, None, Some(&self.output));
-        let mut dovi_processor = DoviProcessor::new(options, self.input.clone(), dovi_writer, pb);
+    /// Only the RPUs are converted, to summarize the changes
+    fn dry_run_raw_hevc(job: &BatchJob, pb: ProgressBar, options: CliOptions) -> Result<()> {
+        let dovi_writer = DoviWriter::new(None, None, None, None, None, options.write_buffer)?;
+        let mut dovi_processor =
+            DoviProcessor::new(options, job.input.clone(), dovi_writer, pb).with_change_summary();
 
-        dovi_processor.read_write_from_io(&self.format)
+        dovi_processor.read_write_from_io(&job.format)
     }
 }
@@ -1,10 +1,25 @@
-use anyhow::{bail, Result};
+use anyhow::{bail, ensure, Result};
 use indicatif::ProgressBar;
-use std::path::PathBuf;
+use log::{debug, warn};
+use rayon::prelude::*;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
 use crate::commands::DemuxArgs;
 
-use super::{general_read_write, input_from_either, CliOptions, IoFormat};
+use super::au_alignment::AuAlignment;
+use super::checkpoint::{Checkpoint, DEFAULT_CHECKPOINT_INTERVAL};
+use super::codec::{self, Codec};
+use super::input_skip::find_random_access;
+use super::length_prefixed::is_length_prefixed;
+use super::mp4_writer::{DualTrackMp4Writer, FrameRate};
+use super::rpu_stream::{is_stdio, status_out};
+use super::{
+    create_output_file, general_read_write, input_from_either, CliOptions, InputFormat, IoFormat,
+    ProgressFormat,
+};
 
 use general_read_write::{DoviProcessor, DoviWriter};
 
@@ -14,20 +29,95 @@ pub struct Demuxer {
     bl_out: PathBuf,
     el_out: PathBuf,
     el_only: bool,
+    rpu_out: Option<PathBuf>,
+    hdr10plus_out: Option<PathBuf>,
+    mp4_out: Option<PathBuf>,
+    frame_rate: FrameRate,
+    threads: Option<usize>,
+    checkpoint: Option<PathBuf>,
+    checkpoint_interval: u64,
 }
 
 impl Demuxer {
-    pub fn from_args(args: DemuxArgs) -> Result<Self> {
+    pub fn from_args(args: DemuxArgs, options: &mut CliOptions) -> Result<Self> {
         let DemuxArgs {
             input,
             input_pos,
             bl_out,
             el_out,
             el_only,
+            keep_el_prefix,
+            rpu_out,
+            hdr10plus_out,
+            mp4_out,
+            fps,
+            threads,
+            checkpoint,
+            checkpoint_interval,
         } = args;
 
+        options.keep_el_prefix = keep_el_prefix;
+
         let input = input_from_either("demux", input, input_pos)?;
-        let format = hevc_parser::io::format_from_path(&input)?;
+        let format = codec::format_from_path(&input, options.input_codec)?;
+
+        let frame_rate = match fps {
+            Some(fps) => fps.parse()?,
+            None => FrameRate::default(),
+        };
+
+        if mp4_out.is_some() {
+            ensure!(
+                Codec::from_input(options.input_codec, &input) == Codec::Hevc,
+                "Demuxer: the MP4 output only supports HEVC inputs"
+            );
+        }
+
+        if let Some(threads) = threads {
+            ensure!(threads > 0, "Demuxer: invalid number of threads 0");
+        }
+
+        // Both demux the input by segments, starting at random access points
+        let segmented = if threads.is_some() {
+            Some("--threads")
+        } else if checkpoint.is_some() {
+            Some("--checkpoint")
+        } else {
+            None
+        };
+
+        if let Some(flag) = segmented {
+            ensure!(
+                format == IoFormat::Raw,
+                "Demuxer: {} requires a seekable raw input file",
+                flag
+            );
+            ensure!(
+                options.input_format != InputFormat::Hvcc,
+                "Demuxer: {} requires an Annex B input",
+                flag
+            );
+            ensure!(
+                options.skip_bytes == 0 && options.skip_frames == 0 && options.limit.is_none(),
+                "Demuxer: {} cannot be used with --skip-bytes, --skip-frames or --limit",
+                flag
+            );
+            ensure!(
+                options.frame_order.is_none() && !options.stream_rpus,
+                "Demuxer: {} cannot be used with --frame-order or --stream-rpus",
+                flag
+            );
+            ensure!(
+                !options.stats && options.progress != ProgressFormat::Json,
+                "Demuxer: {} cannot be used with --stats or --progress json",
+                flag
+            );
+            ensure!(
+                rpu_out.as_deref().is_none_or(|path| !is_stdio(path)),
+                "Demuxer: {} cannot stream the RPUs on stdout",
+                flag
+            );
+        }
 
         let bl_out = match bl_out {
             Some(path) => path,
@@ -45,11 +135,18 @@ impl Demuxer {
             bl_out,
             el_out,
             el_only,
+            rpu_out,
+            hdr10plus_out,
+            mp4_out,
+            frame_rate,
+            threads,
+            checkpoint,
+            checkpoint_interval: checkpoint_interval.unwrap_or(DEFAULT_CHECKPOINT_INTERVAL),
         })
     }
 
-    pub fn demux(args: DemuxArgs, options: CliOptions) -> Result<()> {
-        let demuxer = Demuxer::from_args(args)?;
+    pub fn demux(args: DemuxArgs, mut options: CliOptions) -> Result<()> {
+        let demuxer = Demuxer::from_args(args, &mut options)?;
         demuxer.process_input(options)
     }
 
@@ -63,15 +160,344 @@ impl Demuxer {
     }
 
     fn demux_raw_hevc(&self, pb: ProgressBar, options: CliOptions) -> Result<()> {
+        if let Some(mp4_out) = &self.mp4_out {
+            return DualTrackMp4Writer::write(
+                &self.format,
+                &self.input,
+                mp4_out,
+                self.frame_rate,
+                &options,
+                pb,
+            );
+        }
+
+        if let Some(threads) = self.threads {
+            return self.demux_parallel(pb, options, threads);
+        }
+
+        if let Some(checkpoint) = &self.checkpoint {
+            return self.demux_checkpointed(pb, options, checkpoint);
+        }
+
         let bl_out = if self.el_only {
             None
         } else {
             Some(self.bl_out.as_path())
         };
 
-        let dovi_writer = DoviWriter::new(bl_out, Some(self.el_out.as_path()), None, None);
+        let dovi_writer = DoviWriter::new(
+            bl_out,
+            Some(self.el_out.as_path()),
+            self.rpu_out.as_deref(),
+            None,
+            self.hdr10plus_out.as_deref(),
+            options.write_buffer,
+        )?;
         let mut dovi_processor = DoviProcessor::new(options, self.input.clone(), dovi_writer, pb);
 
         dovi_processor.read_write_from_io(&self.format)
     }
+
+    /// Demuxes ranges of GOPs in parallel, to segment files concatenated once all are done.
+    ///
+    /// The input is split in equal parts, moved to the next random access point.
+    /// Every segment starts at an access unit with parameter sets, so it is demuxed as a stream.
+    fn demux_parallel(&self, pb: ProgressBar, options: CliOptions, threads: usize) -> Result<()> {
+        let codec = Codec::from_input(options.input_codec, &self.input);
+        let segments = self.split_segments(codec, threads, options.read_buffer)?;
+
+        let bl_out = self.bl_out();
+        let outputs = self.outputs();
+
+        writeln!(
+            status_out(),
+            "Demuxing {} segments on {} threads...",
+            segments.len(),
+            threads
+        )
+        .ok();
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()?;
+
+        let res: Result<Vec<Option<AuAlignment>>> = pool.install(|| {
+            segments
+                .par_iter()
+                .enumerate()
+                .map(|(i, byte_range)| {
+                    let segment_path = |path: &Path| segment_path(path, i);
+
+                    let dovi_writer = DoviWriter::new(
+                        bl_out.map(segment_path).as_deref(),
+                        Some(segment_path(&self.el_out).as_path()),
+                        self.rpu_out.as_deref().map(segment_path).as_deref(),
+                        None,
+                        None,
+                        options.write_buffer,
+                    )?;
+
+                    let mut dovi_processor = DoviProcessor::new(
+                        options.clone(),
+                        self.input.clone(),
+                        dovi_writer,
+                        pb.clone(),
+                    )
+                    .with_byte_range(byte_range.clone());
+
+                    dovi_processor.read_write_from_io(&self.format)?;
+
+                    Ok(dovi_processor.take_alignment())
+                })
+                .collect()
+        });
+
+        pb.finish_and_clear();
+
+        let alignments = match res {
+            Ok(alignments) => alignments,
+            Err(e) => {
+                for path in &outputs {
+                    for i in 0..segments.len() {
+                        fs::remove_file(segment_path(path, i)).ok();
+                    }
+                }
+
+                return Err(e);
+            }
+        };
+
+        for path in outputs {
+            concat_segments(path, segments.len())?;
+        }
+
+        let mut alignment = AuAlignment::default();
+        alignments
+            .into_iter()
+            .flatten()
+            .for_each(|segment| alignment.append(segment));
+
+        if let Some(report) = alignment.report() {
+            warn!("{}", report);
+        }
+
+        Ok(())
+    }
+
+    /// Demuxes the input by segments of the checkpoint interval, appended to the outputs.
+    ///
+    /// The checkpoint is saved after every segment, and the demux resumes from it when it exists:
+    /// the outputs are truncated to their size at the checkpoint, and the input is demuxed from its offset.
+    /// The checkpoint file is removed once the demux is complete.
+    fn demux_checkpointed(
+        &self,
+        pb: ProgressBar,
+        options: CliOptions,
+        checkpoint_path: &Path,
+    ) -> Result<()> {
+        let codec = Codec::from_input(options.input_codec, &self.input);
+
+        let mut file = File::open(&self.input)?;
+        let len = file.metadata()?.len();
+
+        ensure!(
+            !is_length_prefixed(&mut BufReader::new(&mut file))?,
+            "Demuxer: --checkpoint requires an Annex B input"
+        );
+
+        let bl_out = self.bl_out();
+        let outputs = self.outputs();
+
+        let mut checkpoint = match Checkpoint::load(checkpoint_path)? {
+            Some(checkpoint) => {
+                checkpoint.resume(&self.input, len, &outputs)?;
+
+                writeln!(
+                    status_out(),
+                    "Resuming from the checkpoint at byte {} of {}...",
+                    checkpoint.offset(),
+                    len
+                )
+                .ok();
+
+                checkpoint
+            }
+            None => Checkpoint::new(&self.input, len, &outputs)?,
+        };
+
+        pb.set_position(checkpoint.offset());
+
+        let mut alignment = AuAlignment::default();
+
+        while checkpoint.offset() < len {
+            let start = checkpoint.offset();
+
+            let end = match start.checked_add(self.checkpoint_interval) {
+                Some(offset) if offset < len => {
+                    random_access_after(&mut file, offset, codec, options.read_buffer)?
+                        .unwrap_or(len)
+                }
+                _ => len,
+            };
+
+            let segment_path = |path: &Path| segment_path(path, 0);
+
+            let res = DoviWriter::new(
+                bl_out.map(segment_path).as_deref(),
+                Some(segment_path(&self.el_out).as_path()),
+                self.rpu_out.as_deref().map(segment_path).as_deref(),
+                None,
+                None,
+                options.write_buffer,
+            )
+            .and_then(|dovi_writer| {
+                let mut dovi_processor = DoviProcessor::new(
+                    options.clone(),
+                    self.input.clone(),
+                    dovi_writer,
+                    pb.clone(),
+                )
+                .with_byte_range(start..end);
+
+                dovi_processor.read_write_from_io(&self.format)?;
+
+                Ok(dovi_processor.take_alignment())
+            });
+
+            match res {
+                Ok(segment_alignment) => {
+                    if let Some(segment_alignment) = segment_alignment {
+                        alignment.append(segment_alignment);
+                    }
+                }
+                Err(e) => {
+                    pb.finish_and_clear();
+
+                    for path in &outputs {
+                        fs::remove_file(segment_path(path)).ok();
+                    }
+
+                    if start > 0 {
+                        warn!(
+                            "Demux stopped, the outputs are complete up to byte {} of the input. \
+                             Run the same command to resume from the checkpoint {}",
+                            start,
+                            checkpoint_path.display()
+                        );
+                    }
+
+                    return Err(e);
+                }
+            }
+
+            checkpoint.commit_segment(checkpoint_path, end, segment_path)?;
+            debug!("Checkpoint saved at byte {} of {}", end, len);
+        }
+
+        pb.finish_and_clear();
+
+        fs::remove_file(checkpoint_path)?;
+
+        if let Some(report) = alignment.report() {
+            warn!("{}", report);
+        }
+
+        Ok(())
+    }
+
+    fn bl_out(&self) -> Option<&Path> {
+        if self.el_only {
+            None
+        } else {
+            Some(self.bl_out.as_path())
+        }
+    }
+
+    /// Output files of the segments, in order
+    fn outputs(&self) -> Vec<&Path> {
+        self.bl_out()
+            .into_iter()
+            .chain([self.el_out.as_path()])
+            .chain(self.rpu_out.as_deref())
+            .collect()
+    }
+
+    /// Byte ranges of the segments, starting at random access points
+    fn split_segments(
+        &self,
+        codec: Codec,
+        count: usize,
+        read_buffer: usize,
+    ) -> Result<Vec<Range<u64>>> {
+        let mut file = File::open(&self.input)?;
+        let len = file.metadata()?.len();
+
+        ensure!(
+            !is_length_prefixed(&mut BufReader::new(&mut file))?,
+            "Demuxer: --threads requires an Annex B input"
+        );
+
+        let mut starts = vec![0];
+
+        for i in 1..count {
+            let offset = len * i as u64 / count as u64;
+
+            // The previous split can be after this one, with long GOPs
+            if offset <= *starts.last().unwrap() {
+                continue;
+            }
+
+            match random_access_after(&mut file, offset, codec, read_buffer)? {
+                Some(start) => starts.push(start),
+                None => break,
+            }
+        }
+
+        starts.dedup();
+
+        let ends = starts.iter().skip(1).copied().chain([len]);
+
+        Ok(starts
+            .iter()
+            .zip(ends)
+            .map(|(&start, end)| start..end)
+            .collect())
+    }
+}
+
+/// Offset of the first random access point of the input from the offset
+fn random_access_after(
+    file: &mut File,
+    offset: u64,
+    codec: Codec,
+    read_buffer: usize,
+) -> Result<Option<u64>> {
+    file.seek(SeekFrom::Start(offset))?;
+    let reader = Box::new(BufReader::new(file.try_clone()?));
+
+    Ok(find_random_access(reader, codec, read_buffer)?.map(|start| offset + start))
+}
+
+/// Output file of a segment, next to the output
+fn segment_path(path: &Path, index: usize) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(format!(".part{}", index));
+
+    path.with_file_name(file_name)
+}
+
+/// Joins the segment files to the output, removing them
+fn concat_segments(path: &Path, count: usize) -> Result<()> {
+    let mut output = create_output_file(path)?;
+
+    for i in 0..count {
+        let segment = segment_path(path, i);
+
+        io::copy(&mut File::open(&segment)?, &mut output)?;
+        fs::remove_file(segment)?;
+    }
+
+    output.flush()?;
+
+    Ok(())
 }
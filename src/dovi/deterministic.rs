@@ -0,0 +1,50 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+use super::rpu_stream::status_out;
+use super::sha256::Sha256;
+
+thread_local! {
+    /// Output files created with `--deterministic`, per thread for the jobs of `--serve`
+    static OUTPUTS: RefCell<Option<Vec<PathBuf>>> = const { RefCell::new(None) };
+}
+
+/// Starts recording the output files created, for their SHA-256 at the end of the command
+pub fn enable() {
+    OUTPUTS.with(|outputs| *outputs.borrow_mut() = Some(Vec::new()));
+}
+
+pub fn record_output(path: &Path) {
+    OUTPUTS.with(|outputs| {
+        if let Some(outputs) = outputs.borrow_mut().as_mut() {
+            if !outputs.iter().any(|output| output == path) {
+                outputs.push(path.to_path_buf());
+            }
+        }
+    });
+}
+
+/// Prints the SHA-256 of the output files, in creation order, and stops recording.
+///
+/// Temporary files removed before the end, such as the demux segments, are left out.
+pub fn print_output_hashes() -> Result<()> {
+    let outputs = OUTPUTS.with(|outputs| outputs.borrow_mut().take().unwrap_or_default());
+    let outputs: Vec<PathBuf> = outputs.into_iter().filter(|path| path.is_file()).collect();
+
+    if outputs.is_empty() {
+        writeln!(status_out(), "No output file to hash").ok();
+        return Ok(());
+    }
+
+    writeln!(status_out(), "Output SHA-256:").ok();
+
+    for path in outputs {
+        let digest = Sha256::file_hex_digest(&path)?;
+        writeln!(status_out(), "  {}  {}", digest, path.display()).ok();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,268 @@
+use std::io::{stdout, Write};
+use std::path::PathBuf;
+
+use anyhow::Result;
+use serde_json::Value;
+
+use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlock;
+use dolby_vision::xml::XmlParserOpts;
+use utilities_dovi::parse_rpu_file;
+
+use super::{xml_differ, DoviRpu};
+use crate::commands::DiffArgs;
+
+pub struct Differ {
+    input: PathBuf,
+    input2: PathBuf,
+    summary: bool,
+}
+
+/// A differing field, with the path from the RPU section
+pub struct FieldDiff {
+    pub section: String,
+    pub path: String,
+    pub value: Value,
+    pub value2: Value,
+}
+
+impl Differ {
+    pub fn diff(args: DiffArgs) -> Result<()> {
+        let DiffArgs {
+            input,
+            input2,
+            summary,
+            xml,
+            canvas_width,
+            canvas_height,
+            tolerance,
+        } = args;
+
+        if let Some(xml) = xml {
+            let parser_opts = XmlParserOpts {
+                canvas_width,
+                canvas_height,
+            };
+
+            return xml_differ::diff_xml(&input, &xml, parser_opts, tolerance, summary);
+        }
+
+        // Required by clap without --xml
+        let input2 = input2.unwrap();
+
+        let differ = Differ {
+            input,
+            input2,
+            summary,
+        };
+
+        differ.execute()
+    }
+
+    fn execute(&self) -> Result<()> {
+        println!("Parsing RPU files...");
+        stdout().flush().ok();
+
+        let rpus = parse_rpu_file(&self.input)?.unwrap_or_default();
+        let rpus2 = parse_rpu_file(&self.input2)?.unwrap_or_default();
+
+        if rpus.len() != rpus2.len() {
+            println!(
+                "Frame count differs: {} and {}, comparing the first {} frames",
+                rpus.len(),
+                rpus2.len(),
+                rpus.len().min(rpus2.len())
+            );
+        }
+
+        // Sections in order of first difference, with the number of differing frames
+        let mut sections: Vec<(String, usize)> = Vec::new();
+        let mut differing_frames = 0;
+
+        for (frame, (rpu, rpu2)) in rpus.iter().zip(rpus2.iter()).enumerate() {
+            let diffs = diff_rpus(rpu, rpu2)?;
+
+            if diffs.is_empty() {
+                continue;
+            }
+
+            differing_frames += 1;
+
+            if !self.summary {
+                println!("Frame {}:", frame);
+            }
+
+            let mut frame_sections: Vec<&str> = Vec::new();
+
+            for diff in &diffs {
+                if !self.summary {
+                    println!("  {}: {} -> {}", diff.path, diff.value, diff.value2);
+                }
+
+                if !frame_sections.contains(&diff.section.as_str()) {
+                    frame_sections.push(&diff.section);
+                }
+            }
+
+            for section in frame_sections {
+                match sections.iter_mut().find(|(s, _)| s == section) {
+                    Some((_, count)) => *count += 1,
+                    None => sections.push((section.to_string(), 1)),
+                }
+            }
+        }
+
+        if differing_frames == 0 && rpus.len() == rpus2.len() {
+            println!("The RPU files are identical.");
+        } else {
+            println!("Summary:");
+            println!("  Differing frames: {}", differing_frames);
+
+            sections
+                .iter()
+                .for_each(|(section, count)| println!("  {}: {} frames", section, count));
+        }
+
+        Ok(())
+    }
+}
+
+/// Compares the RPUs by section: header, mapping, NLQ, DM data and each DM level
+pub fn diff_rpus(rpu: &DoviRpu, rpu2: &DoviRpu) -> Result<Vec<FieldDiff>> {
+    let mut diffs = Vec::new();
+
+    let sections = [
+        (
+            "dovi_profile",
+            serde_json::to_value(rpu.dovi_profile)?,
+            serde_json::to_value(rpu2.dovi_profile)?,
+        ),
+        (
+            "header",
+            serde_json::to_value(&rpu.header)?,
+            serde_json::to_value(&rpu2.header)?,
+        ),
+        (
+            "rpu_data_mapping",
+            serde_json::to_value(&rpu.rpu_data_mapping)?,
+            serde_json::to_value(&rpu2.rpu_data_mapping)?,
+        ),
+        (
+            "rpu_data_nlq",
+            serde_json::to_value(&rpu.rpu_data_nlq)?,
+            serde_json::to_value(&rpu2.rpu_data_nlq)?,
+        ),
+        ("vdr_dm_data", dm_data_value(rpu)?, dm_data_value(rpu2)?),
+    ];
+
+    for (section, value, value2) in sections {
+        diff_values(section, section, &value, &value2, &mut diffs);
+    }
+
+    let mut levels: Vec<u8> = metadata_levels(rpu)
+        .into_iter()
+        .chain(metadata_levels(rpu2))
+        .collect();
+    levels.sort_unstable();
+    levels.dedup();
+
+    for level in levels {
+        let section = format!("L{}", level);
+        let value = level_blocks_value(rpu, level)?;
+        let value2 = level_blocks_value(rpu2, level)?;
+
+        diff_values(&section, &section, &value, &value2, &mut diffs);
+    }
+
+    Ok(diffs)
+}
+
+/// The DM data, without the extension metadata blocks
+fn dm_data_value(rpu: &DoviRpu) -> Result<Value> {
+    let mut value = serde_json::to_value(&rpu.vdr_dm_data)?;
+
+    if let Some(map) = value.as_object_mut() {
+        map.remove("cmv29_metadata");
+        map.remove("cmv40_metadata");
+    }
+
+    Ok(value)
+}
+
+fn metadata_blocks(rpu: &DoviRpu) -> impl Iterator<Item = &ExtMetadataBlock> {
+    rpu.vdr_dm_data.iter().flat_map(|vdr_dm_data| {
+        // CM v2.9 and CM v4.0 blocks
+        [1, 3]
+            .into_iter()
+            .filter_map(|level| vdr_dm_data.metadata_blocks(level))
+            .flat_map(|blocks| blocks.iter())
+    })
+}
+
+fn metadata_levels(rpu: &DoviRpu) -> Vec<u8> {
+    metadata_blocks(rpu).map(|block| block.level()).collect()
+}
+
+fn level_blocks_value(rpu: &DoviRpu, level: u8) -> Result<Value> {
+    let blocks = metadata_blocks(rpu)
+        .filter(|block| block.level() == level)
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<Value>, _>>()?;
+
+    // Blocks are serialized tagged by level, keep the fields only
+    let blocks: Vec<Value> = blocks
+        .into_iter()
+        .map(|block| match block {
+            Value::Object(map) if map.len() == 1 => map.into_iter().next().unwrap().1,
+            block => block,
+        })
+        .collect();
+
+    // Most levels only have one block
+    Ok(if blocks.len() == 1 {
+        blocks.into_iter().next().unwrap()
+    } else {
+        Value::Array(blocks)
+    })
+}
+
+fn diff_values(
+    section: &str,
+    path: &str,
+    value: &Value,
+    value2: &Value,
+    diffs: &mut Vec<FieldDiff>,
+) {
+    match (value, value2) {
+        (Value::Object(map), Value::Object(map2)) => {
+            for (key, field) in map {
+                let field2 = map2.get(key).unwrap_or(&Value::Null);
+                diff_values(section, &format!("{}.{}", path, key), field, field2, diffs);
+            }
+
+            for (key, field2) in map2.iter().filter(|(key, _)| !map.contains_key(*key)) {
+                diff_values(
+                    section,
+                    &format!("{}.{}", path, key),
+                    &Value::Null,
+                    field2,
+                    diffs,
+                );
+            }
+        }
+        (Value::Array(list), Value::Array(list2)) if list.len() == list2.len() => {
+            for (i, (item, item2)) in list.iter().zip(list2.iter()).enumerate() {
+                diff_values(section, &format!("{}[{}]", path, i), item, item2, diffs);
+            }
+        }
+        _ => {
+            if value != value2 {
+                diffs.push(FieldDiff {
+                    section: section.to_string(),
+                    path: path.to_string(),
+                    value: value.clone(),
+                    value2: value2.clone(),
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use anyhow::{bail, Result};
+
+use crate::commands::{AddDvArgs, GenerateArgs};
+
+use super::codec;
+use super::generator::Generator;
+use super::rpu_injector::RpuInjector;
+use super::{input_from_either, CliOptions, IoFormat};
+
+/// Adds Dolby Vision to a HDR10 video in a single pass:
+/// generates the RPUs in memory, then injects them.
+pub struct DoviAdder;
+
+impl DoviAdder {
+    pub fn add_dv(args: AddDvArgs, cli_options: CliOptions) -> Result<()> {
+        let AddDvArgs {
+            input,
+            input_pos,
+            output,
+            json_file,
+            hdr10plus_json,
+            madvr_file,
+            use_custom_targets,
+            scene_file,
+            fps,
+            timestamps,
+            chapters,
+            no_add_aud,
+            fix,
+        } = args;
+
+        let input = input_from_either("add-dv", input, input_pos)?;
+        let format = codec::format_from_path(&input, cli_options.input_codec)?;
+
+        if !matches!(format, IoFormat::Raw) {
+            bail!("add-dv: Must be a raw HEVC, AVC or VVC bitstream file");
+        }
+
+        let mut generator = Generator::from_args(GenerateArgs {
+            json_file,
+            rpu_out: None,
+            hdr10plus_json,
+            xml: None,
+            canvas_width: None,
+            canvas_height: None,
+            madvr_file,
+            use_custom_targets,
+            scene_file,
+            hlg: false,
+            length: None,
+            static_content: false,
+            l1: None,
+            l5: None,
+            l6: None,
+            fps,
+            timestamps,
+            chapters,
+        })?;
+
+        let mut injector = RpuInjector::new(input, output, no_add_aud, fix, cli_options)?;
+        injector.process_input()?;
+
+        // Without a length in the sources, one RPU per video frame
+        let rpus = generator.generate_rpus(injector.frame_count())?;
+        injector.set_rpus(rpus)?;
+
+        injector.interleave_rpu_nals()
+    }
+}
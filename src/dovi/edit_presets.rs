@@ -0,0 +1,42 @@
+use anyhow::{bail, Result};
+use serde_json::{json, Value};
+
+/// Built-in editor configs for common fixes, overridden by the JSON config
+#[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum EditPreset {
+    /// Sets the L6 MaxCLL and MaxFALL from the L1 metadata
+    FixL6FromL1,
+    /// Removes the CM v4.0 metadata, for players failing on it
+    StripCmv4,
+    /// Profile 8.1 with the L6 MaxCLL and MaxFALL from the L1 metadata, for HDR10 fallback
+    PlexCompat,
+}
+
+impl EditPreset {
+    pub fn config(&self) -> Value {
+        match self {
+            EditPreset::FixL6FromL1 => json!({
+                "level6_from_l1": true
+            }),
+            EditPreset::StripCmv4 => json!({
+                "remove_cmv4": true
+            }),
+            EditPreset::PlexCompat => json!({
+                "mode": 2,
+                "level6_from_l1": true
+            }),
+        }
+    }
+
+    /// The preset config, with the top-level fields of the JSON config replacing its own
+    pub fn config_with(&self, overrides: Value) -> Result<Value> {
+        let mut config = self.config();
+
+        match (config.as_object_mut(), overrides) {
+            (Some(fields), Value::Object(overrides)) => fields.extend(overrides),
+            _ => bail!("The edit JSON config must be an object"),
+        }
+
+        Ok(config)
+    }
+}
@@ -1,25 +1,48 @@
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::{stdout, Write};
-use std::{collections::HashMap, path::PathBuf};
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 use anyhow::{bail, ensure, Result};
+use dolby_vision::rpu::extension_metadata::blocks::level10::PRESET_TARGET_DISPLAYS;
 use dolby_vision::rpu::extension_metadata::blocks::{
-    ExtMetadataBlock, ExtMetadataBlockLevel11, ExtMetadataBlockLevel5, ExtMetadataBlockLevel6,
-    ExtMetadataBlockLevel9,
+    ContentType, ExtMetadataBlock, ExtMetadataBlockLevel1, ExtMetadataBlockLevel10,
+    ExtMetadataBlockLevel11, ExtMetadataBlockLevel2, ExtMetadataBlockLevel254,
+    ExtMetadataBlockLevel3, ExtMetadataBlockLevel4, ExtMetadataBlockLevel5, ExtMetadataBlockLevel6,
+    ExtMetadataBlockLevel8, ExtMetadataBlockLevel9,
 };
 use dolby_vision::rpu::extension_metadata::MasteringDisplayPrimaries;
-use dolby_vision::rpu::generate::GenerateConfig;
+use dolby_vision::rpu::generate::{validate_trim_targets, GenerateConfig, TrimAlgorithm};
+use dolby_vision::rpu::rpu_data_nlq::NlqParams;
+use dolby_vision::utils::{nits_to_pq, pq12_to_pq, pq_to_nits, pq_to_pq12};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use utilities_dovi::parse_rpu_file;
 
+use super::change_summary::ChangeSummary;
+use super::edit_presets::EditPreset;
+use super::l1_csv::{L1Csv, L1CsvUnits};
+use super::l1_smoothing::{L1Smoothing, L1SmoothingMethod};
+use super::rpu_stream::{self, status_out};
+use super::scene_file::SceneCuts;
+use super::timecode::{parse_fps, TimecodeResolver};
 use super::{input_from_either, write_rpu_file, DoviRpu};
 use crate::commands::EditorArgs;
 
 pub struct Editor {
     input: PathBuf,
-    json_file: PathBuf,
+    json_file: Option<PathBuf>,
+    preset: Option<EditPreset>,
     rpu_out: PathBuf,
+    scene_file: Option<PathBuf>,
+    l1_csv: Option<PathBuf>,
+    l1_csv_units: L1CsvUnits,
+    fps: Option<f64>,
+    timestamps: Option<PathBuf>,
+    chapters: Option<PathBuf>,
+    extend: Option<usize>,
+    dry_run: bool,
 
     rpus: Option<Vec<Option<DoviRpu>>>,
 }
@@ -32,9 +55,15 @@ pub struct EditConfig {
     #[serde(default)]
     convert_to_cmv4: bool,
 
+    #[serde(default)]
+    remove_cmv4: bool,
+
     #[serde(default)]
     remove_mapping: bool,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nlq: Option<NlqEdit>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     active_area: Option<ActiveArea>,
 
@@ -44,15 +73,118 @@ pub struct EditConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     duplicate: Option<Vec<DuplicateMetadata>>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    replace: Option<Vec<ReplaceMetadata>>,
+
+    #[serde(default)]
+    shift: i64,
+
+    #[serde(default)]
+    extend: usize,
+
     #[serde(default)]
     min_pq: Option<u16>,
 
     #[serde(default)]
     max_pq: Option<u16>,
 
+    #[serde(default)]
+    l1_scale: Option<f64>,
+
+    #[serde(default)]
+    l1_max_nits: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    brightness_offset: Option<BrightnessOffset>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    l1_smoothing: Option<L1Smoothing>,
+
+    /// L1 offsets, for every frame or per frame range
+    #[serde(skip_serializing_if = "Option::is_none")]
+    level3: Option<HashMap<String, ExtMetadataBlockLevel3>>,
+
+    /// Global dimming anchors, for every frame or per frame range
+    #[serde(skip_serializing_if = "Option::is_none")]
+    level4: Option<HashMap<String, ExtMetadataBlockLevel4>>,
+
     level6: Option<ExtMetadataBlockLevel6>,
-    level9: Option<MasteringDisplayPrimaries>,
-    level11: Option<ExtMetadataBlockLevel11>,
+
+    #[serde(default)]
+    drop_l6: bool,
+
+    /// Sets the L6 MaxCLL and MaxFALL from the final L1 metadata
+    #[serde(default)]
+    level6_from_l1: bool,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    level8: Option<Vec<ExtMetadataBlockLevel8>>,
+
+    level9: Option<Level9Metadata>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    level10: Option<Vec<ExtMetadataBlockLevel10>>,
+
+    /// Custom target displays, defined with their L10 and L8 trims
+    #[serde(skip_serializing_if = "Option::is_none")]
+    targets: Option<Vec<TargetDisplay>>,
+
+    level11: Option<Level11Metadata>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    level254: Option<ExtMetadataBlockLevel254>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    remove_levels: Option<RemoveLevels>,
+
+    #[serde(default)]
+    trim_targets: Vec<u16>,
+
+    #[serde(default)]
+    trim_algorithm: TrimAlgorithm,
+}
+
+/// L9 metadata, either a preset primaries name or a complete block
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Level9Metadata {
+    Preset(MasteringDisplayPrimaries),
+    Block(ExtMetadataBlockLevel9),
+}
+
+/// L11 metadata, either a content type name or a complete block
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Level11Metadata {
+    Preset(ContentType),
+    Block(ExtMetadataBlockLevel11),
+}
+
+/// Custom target display, written as L10 for every frame,
+/// with its L8 trims for every frame or per frame range
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TargetDisplay {
+    target_display_index: u8,
+    max_nits: f64,
+
+    #[serde(default)]
+    min_nits: f64,
+
+    /// Defaults to DCI-P3 D65
+    #[serde(skip_serializing_if = "Option::is_none")]
+    primaries: Option<MasteringDisplayPrimaries>,
+
+    /// The target display index of the trims is the one of the target
+    #[serde(default)]
+    trims: HashMap<String, ExtMetadataBlockLevel8>,
+}
+
+/// Extension metadata levels to remove, for every frame or per frame range
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum RemoveLevels {
+    All(Vec<u8>),
+    Ranges(HashMap<String, Vec<u8>>),
 }
 
 #[derive(Serialize, Deserialize, Default, Debug, Clone)]
@@ -63,11 +195,46 @@ pub struct ActiveArea {
     #[serde(skip_serializing_if = "Option::is_none")]
     drop_l5: Option<String>,
 
+    /// Canvas resolution, to compute the offsets of the aspect ratio presets
+    #[serde(skip_serializing_if = "Option::is_none")]
+    canvas_width: Option<u16>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    canvas_height: Option<u16>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     presets: Option<Vec<ActiveAreaOffsets>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    edits: Option<HashMap<String, u16>>,
+    edits: Option<HashMap<String, ActiveAreaEdit>>,
+}
+
+/// Offsets of an edit, either a preset ID or an aspect ratio preset,
+/// or a gradual transition between two of them over the frame range
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ActiveAreaEdit {
+    Id(u16),
+    AspectRatio {
+        preset: AspectRatioPreset,
+    },
+    Transition {
+        from: Box<ActiveAreaEdit>,
+        to: Box<ActiveAreaEdit>,
+    },
+}
+
+/// Named aspect ratios, letterboxed or pillarboxed in the canvas
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum AspectRatioPreset {
+    #[serde(rename = "2.39")]
+    Scope,
+    #[serde(rename = "1.90")]
+    Imax,
+    #[serde(rename = "1.85")]
+    Flat,
+    #[serde(rename = "full")]
+    FullFrame,
 }
 
 #[derive(Serialize, Deserialize, Default, Debug, Clone)]
@@ -86,16 +253,59 @@ pub struct DuplicateMetadata {
     length: usize,
 }
 
+/// Removal or parameters of the NLQ data, for profile 4 and 7 RPUs
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct NlqEdit {
+    #[serde(default)]
+    remove: bool,
+
+    #[serde(flatten)]
+    params: NlqParams,
+}
+
+/// Signed shift of the L1 metadata in nits, for content mastered too dark or too bright
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct BrightnessOffset {
+    nits: f64,
+
+    /// Also shifts the L6 MaxCLL and MaxFALL
+    #[serde(default)]
+    level6: bool,
+}
+
+/// Frames replaced by the RPUs of another file
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct ReplaceMetadata {
+    source_rpu: PathBuf,
+
+    /// First and last frame to replace (inclusive)
+    range: [usize; 2],
+
+    /// First frame of the source RPU file, defaults to the start of the range
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source_start: Option<usize>,
+}
+
 impl Editor {
     pub fn from_args(args: EditorArgs) -> Result<Self> {
         let EditorArgs {
             input,
             input_pos,
             json_file,
+            preset,
             rpu_out,
+            scene_file,
+            l1_csv,
+            l1_csv_units,
+            fps,
+            timestamps,
+            chapters,
+            extend,
+            dry_run,
         } = args;
 
         let input = input_from_either("editor", input, input_pos)?;
+        let fps = fps.as_deref().map(parse_fps).transpose()?;
 
         let out_path = if let Some(out_path) = rpu_out {
             out_path
@@ -107,10 +317,23 @@ impl Editor {
             ))
         };
 
+        if rpu_stream::is_stdio(&out_path) {
+            rpu_stream::use_stdout();
+        }
+
         Ok(Self {
             input,
             json_file,
+            preset,
             rpu_out: out_path,
+            scene_file,
+            l1_csv,
+            l1_csv_units,
+            fps,
+            timestamps,
+            chapters,
+            extend,
+            dry_run,
             rpus: None,
         })
     }
@@ -118,20 +341,108 @@ impl Editor {
     pub fn edit(args: EditorArgs) -> Result<()> {
         let mut editor = Editor::from_args(args)?;
 
-        let mut config: EditConfig = EditConfig::from_path(&editor.json_file)?;
+        let value = match (editor.preset, &editor.json_file) {
+            (Some(preset), Some(json_file)) => Some(preset.config_with(read_json(json_file)?)?),
+            (Some(preset), None) => Some(preset.config()),
+            (None, Some(json_file)) => Some(read_json(json_file)?),
+            (None, None) => None,
+        };
 
-        println!("{:#?}", config);
+        let mut config: EditConfig = match value {
+            Some(value) => EditConfig::from_value_with_timing(
+                value,
+                editor.fps,
+                editor.timestamps.as_deref(),
+                editor.chapters.as_deref(),
+            )?,
+            None => EditConfig::default(),
+        };
 
-        println!("Parsing RPU file...");
-        stdout().flush().ok();
+        if let Some(extend) = editor.extend {
+            config.extend = extend;
+        }
+
+        writeln!(status_out(), "{:#?}", config).ok();
+
+        let scene_cuts = editor
+            .scene_file
+            .as_deref()
+            .map(SceneCuts::from_path)
+            .transpose()?;
+
+        let l1_csv = editor
+            .l1_csv
+            .as_deref()
+            .map(|path| L1Csv::from_path(path, editor.l1_csv_units))
+            .transpose()?;
+
+        writeln!(status_out(), "Parsing RPU file...").ok();
+        status_out().flush().ok();
 
         editor.rpus =
             parse_rpu_file(&editor.input)?.map(|rpus| rpus.into_iter().map(Some).collect());
 
         if let Some(ref mut rpus) = editor.rpus {
+            // Compared to the edited RPUs for the dry run summary
+            let original_rpus = editor.dry_run.then(|| rpus.clone());
+
             config.execute(rpus)?;
 
-            let mut data = GenerateConfig::encode_option_rpus(rpus);
+            // From the final L1 metadata
+            if !config.trim_targets.is_empty() {
+                writeln!(status_out(), "Generating trims from the L1 metadata...").ok();
+            }
+
+            let mut summary = None;
+
+            let mut data = if scene_cuts.is_some() || l1_csv.is_some() {
+                let remaining: Vec<bool> = rpus.iter().map(Option::is_some).collect();
+
+                // Scene frame numbers are relative to the remaining RPUs
+                let mut rpus: Vec<DoviRpu> = rpus.drain(..).flatten().collect();
+
+                if let Some(scene_cuts) = &scene_cuts {
+                    scene_cuts.apply(&mut rpus)?;
+                }
+
+                // The imported L1 metadata replaces the one computed from the scene cuts
+                if let Some(l1_csv) = &l1_csv {
+                    l1_csv.apply(&mut rpus)?;
+                }
+
+                for rpu in rpus.iter_mut() {
+                    config.generate_trims(rpu)?;
+                }
+
+                config.set_level6_from_level1(&mut rpus.iter_mut().collect::<Vec<_>>())?;
+
+                if let Some(original_rpus) = &original_rpus {
+                    let mut edited_rpus = rpus.iter();
+                    let edited_rpus = remaining
+                        .iter()
+                        .map(|&remaining| remaining.then(|| edited_rpus.next()).flatten());
+
+                    summary = Some(summarize_changes(original_rpus, edited_rpus)?);
+                }
+
+                GenerateConfig::encode_rpus(&mut rpus)
+            } else {
+                for rpu in rpus.iter_mut().flatten() {
+                    config.generate_trims(rpu)?;
+                }
+
+                config
+                    .set_level6_from_level1(&mut rpus.iter_mut().flatten().collect::<Vec<_>>())?;
+
+                if let Some(original_rpus) = &original_rpus {
+                    summary = Some(summarize_changes(
+                        original_rpus,
+                        rpus.iter().map(Option::as_ref),
+                    )?);
+                }
+
+                GenerateConfig::encode_option_rpus(rpus)
+            };
 
             if let Some(ref mut to_duplicate) = config.duplicate {
                 to_duplicate.sort_by_key(|meta| meta.offset);
@@ -142,7 +453,22 @@ impl Editor {
                 config.duplicate_metadata(to_duplicate, &mut data)?;
             }
 
-            println!("Final metadata length: {}", data.len());
+            if config.shift != 0 {
+                config.shift_metadata(&mut data)?;
+            }
+
+            if config.extend > 0 {
+                config.extend_metadata(&mut data)?;
+            }
+
+            writeln!(status_out(), "Final metadata length: {}", data.len()).ok();
+
+            if let Some(mut summary) = summary {
+                summary.set_output_frames(data.len());
+                writeln!(status_out(), "{}", summary).ok();
+
+                return Ok(());
+            }
 
             write_rpu_file(&editor.rpu_out, data)?;
         }
@@ -151,47 +477,405 @@ impl Editor {
     }
 }
 
+fn read_json(path: &Path) -> Result<Value> {
+    let json_file = File::open(path)?;
+
+    Ok(serde_json::from_reader(&json_file)?)
+}
+
+/// Summary of the edits, from the edited RPU of every original frame
+fn summarize_changes<'a>(
+    original_rpus: &[Option<DoviRpu>],
+    edited_rpus: impl Iterator<Item = Option<&'a DoviRpu>>,
+) -> Result<ChangeSummary> {
+    let mut summary = ChangeSummary::default();
+
+    for (frame, (rpu, edited_rpu)) in original_rpus.iter().zip(edited_rpus).enumerate() {
+        if let Some(rpu) = rpu {
+            summary.add(frame, rpu, edited_rpu)?;
+        }
+    }
+
+    Ok(summary)
+}
+
 impl EditConfig {
-    pub fn from_path(path: &PathBuf) -> Result<Self> {
-        let json_file = File::open(path)?;
-        let mut config: EditConfig = serde_json::from_reader(&json_file)?;
+    pub fn from_path(path: &Path) -> Result<Self> {
+        Self::from_path_with_timing(path, None, None, None)
+    }
+
+    /// Parses the config, converting the timecodes and chapters of the frame ranges
+    /// with the frame rate or timestamps file and the chapters file given,
+    /// or the `fps`, `timestamps` and `chapters` of the config.
+    pub fn from_path_with_timing(
+        path: &Path,
+        fps: Option<f64>,
+        timestamps: Option<&Path>,
+        chapters: Option<&Path>,
+    ) -> Result<Self> {
+        Self::from_value_with_timing(read_json(path)?, fps, timestamps, chapters)
+    }
+
+    /// Same as `from_path_with_timing`, for a config already read
+    pub fn from_value_with_timing(
+        mut value: Value,
+        fps: Option<f64>,
+        timestamps: Option<&Path>,
+        chapters: Option<&Path>,
+    ) -> Result<Self> {
+        TimecodeResolver::new(fps, timestamps, chapters, &value)?
+            .resolve_edit_config(&mut value)?;
+
+        let mut config: EditConfig = serde_json::from_value(value)?;
 
         // Override to CM v4.0
         if !config.convert_to_cmv4 {
-            config.convert_to_cmv4 = config.level11.is_some() || config.level9.is_some();
+            config.convert_to_cmv4 = config.level3.is_some()
+                || config.level8.is_some()
+                || config.level9.is_some()
+                || config.level10.is_some()
+                || config.targets.is_some()
+                || config.level11.is_some()
+                || config.level254.is_some();
         }
 
+        config.validate()?;
+
         Ok(config)
     }
 
+    fn validate(&self) -> Result<()> {
+        ensure!(
+            !(self.drop_l6 && (self.level6.is_some() || self.level6_from_l1)),
+            "Cannot both set and drop the L6 metadata"
+        );
+
+        ensure!(
+            !(self.remove_cmv4 && self.convert_to_cmv4),
+            "Cannot both remove and set CM v4.0 metadata"
+        );
+
+        if let Some(nlq) = &self.nlq {
+            let params = &nlq.params;
+            let has_params = params.nlq_offset.is_some()
+                || params.linear_deadzone_slope_int.is_some()
+                || params.linear_deadzone_slope.is_some()
+                || params.linear_deadzone_threshold_int.is_some()
+                || params.linear_deadzone_threshold.is_some();
+
+            ensure!(
+                !(nlq.remove && has_params),
+                "Cannot both remove and edit the NLQ parameters"
+            );
+        }
+
+        if let Some(scale) = self.l1_scale {
+            ensure!(
+                scale.is_finite() && scale > 0.0,
+                "Invalid L1 scale {}, must be greater than 0",
+                scale
+            );
+        }
+
+        if let Some(max_nits) = self.l1_max_nits {
+            ensure!(
+                max_nits.is_finite() && max_nits > 0.0,
+                "Invalid L1 max nits {}, must be greater than 0",
+                max_nits
+            );
+        }
+
+        if let Some(offset) = &self.brightness_offset {
+            ensure!(
+                offset.nits.is_finite() && offset.nits.abs() <= 10_000.0,
+                "Invalid brightness offset {} nits, must be between -10000 and 10000",
+                offset.nits
+            );
+        }
+
+        if let Some(l1_smoothing) = &self.l1_smoothing {
+            l1_smoothing.validate()?;
+        }
+
+        if let Some(level3) = &self.level3 {
+            for (range, block) in level3 {
+                if range.to_lowercase() != "all" {
+                    EditConfig::range_string_to_tuple(range)?;
+                }
+
+                ensure!(
+                    block.validate().is_ok(),
+                    "Invalid L3 offsets for {}, the values must fit in 12 bits",
+                    range
+                );
+            }
+        }
+
+        if let Some(level4) = &self.level4 {
+            for (range, block) in level4 {
+                if range.to_lowercase() != "all" {
+                    EditConfig::range_string_to_tuple(range)?;
+                }
+
+                ensure!(
+                    block.validate().is_ok(),
+                    "Invalid L4 anchors for {}, the values must fit in 12 bits",
+                    range
+                );
+            }
+        }
+
+        validate_trim_targets(&self.trim_targets)?;
+        self.trim_algorithm.validate()?;
+
+        if let Some(level8) = &self.level8 {
+            for block in level8 {
+                ensure!(
+                    matches!(block.length, 10 | 12 | 13 | 19 | 25),
+                    "Invalid L8 length {}, must be one of 10, 12, 13, 19 or 25",
+                    block.length
+                );
+                block.validate()?;
+            }
+        }
+
+        if let Some(Level9Metadata::Block(block)) = &self.level9 {
+            ensure!(
+                matches!(block.length, 1 | 17),
+                "Invalid L9 length {}, must be 1 or 17",
+                block.length
+            );
+            block.validate()?;
+        }
+
+        if let Some(level10) = &self.level10 {
+            for block in level10 {
+                ensure!(
+                    matches!(block.length, 5 | 21),
+                    "Invalid L10 length {}, must be 5 or 21",
+                    block.length
+                );
+                block.validate()?;
+            }
+        }
+
+        if let Some(targets) = &self.targets {
+            self.validate_targets(targets)?;
+        }
+
+        if let Some(Level11Metadata::Block(level11)) = &self.level11 {
+            level11.validate()?;
+        }
+
+        if let Some(active_area) = &self.active_area {
+            active_area.validate()?;
+        }
+
+        if let Some(remove_levels) = &self.remove_levels {
+            self.validate_remove_levels(remove_levels)?;
+        }
+
+        Ok(())
+    }
+
+    fn validate_targets(&self, targets: &[TargetDisplay]) -> Result<()> {
+        for (i, target) in targets.iter().enumerate() {
+            let index = target.target_display_index;
+
+            ensure!(
+                !PRESET_TARGET_DISPLAYS.contains(&index),
+                "Invalid target display index {}, it is a preset target display",
+                index
+            );
+            ensure!(
+                !targets[..i]
+                    .iter()
+                    .any(|other| other.target_display_index == index),
+                "Target display {} is defined more than once",
+                index
+            );
+
+            let set_by_level10 = self
+                .level10
+                .as_ref()
+                .is_some_and(|level10| level10.iter().any(|b| b.target_display_index == index));
+            let set_by_level8 = self
+                .level8
+                .as_ref()
+                .is_some_and(|level8| level8.iter().any(|b| b.target_display_index == index));
+
+            ensure!(
+                !set_by_level10 && !set_by_level8,
+                "Target display {} is also set by the level8 or level10 blocks",
+                index
+            );
+
+            ensure!(
+                target.max_nits.is_finite() && target.max_nits > 0.0 && target.max_nits <= 10_000.0,
+                "Invalid max nits {} for target display {}, must be between 0 and 10000",
+                target.max_nits,
+                index
+            );
+            ensure!(
+                target.min_nits.is_finite()
+                    && target.min_nits >= 0.0
+                    && target.min_nits < target.max_nits,
+                "Invalid min nits {} for target display {}, must be between 0 and the max nits",
+                target.min_nits,
+                index
+            );
+
+            target.level10().validate()?;
+
+            for (range, trim) in &target.trims {
+                if range.to_lowercase() != "all" {
+                    EditConfig::range_string_to_tuple(range)?;
+                }
+
+                let block = target.level8(trim);
+
+                ensure!(
+                    matches!(block.length, 10 | 12 | 13 | 19 | 25),
+                    "Invalid L8 length {} for target display {}, must be one of 10, 12, 13, 19 or 25",
+                    block.length,
+                    index
+                );
+                block.validate()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_remove_levels(&self, remove_levels: &RemoveLevels) -> Result<()> {
+        let set_levels = [
+            (3, self.level3.is_some()),
+            (4, self.level4.is_some()),
+            (6, self.level6.is_some()),
+            (8, self.level8.is_some() || self.targets.is_some()),
+            (9, self.level9.is_some()),
+            (10, self.level10.is_some() || self.targets.is_some()),
+            (11, self.level11.is_some()),
+        ];
+
+        for level in remove_levels.levels() {
+            ensure!(
+                matches!(level, 1..=6 | 8..=11 | 255),
+                "Invalid extension metadata level to remove: {}",
+                level
+            );
+
+            if let Some((_, true)) = set_levels.iter().find(|(l, _)| *l == level) {
+                bail!("Cannot both set and remove the L{} metadata", level);
+            }
+        }
+
+        if let RemoveLevels::Ranges(ranges) = remove_levels {
+            for range in ranges.keys().filter(|r| r.to_lowercase() != "all") {
+                EditConfig::range_string_to_tuple(range)?;
+            }
+        }
+
+        Ok(())
+    }
+
     fn execute(&self, rpus: &mut [Option<DoviRpu>]) -> Result<()> {
+        // Replace before any edit, with the original frame numbers
+        if let Some(replacements) = &self.replace {
+            self.replace_frames(replacements, rpus)?;
+        }
+
         // Drop metadata frames
         if let Some(ranges) = &self.remove {
             self.remove_frames(ranges, rpus)?;
         }
 
         if self.convert_to_cmv4 {
-            println!("Converting to CMv4.0...");
+            writeln!(status_out(), "Converting to CMv4.0...").ok();
+        }
+
+        if self.remove_cmv4 {
+            writeln!(status_out(), "Removing CM v4.0 metadata...").ok();
         }
 
         if self.mode > 0 {
-            println!("Converting with mode {}...", self.mode);
+            writeln!(status_out(), "Converting with mode {}...", self.mode).ok();
         }
 
         if self.remove_mapping {
-            println!("Removing polynomial/MMR mapping...");
+            writeln!(status_out(), "Removing polynomial/MMR mapping...").ok();
+        }
+
+        if let Some(nlq) = &self.nlq {
+            if nlq.remove {
+                writeln!(status_out(), "Removing NLQ data...").ok();
+            } else {
+                writeln!(status_out(), "Editing NLQ parameters...").ok();
+            }
+        }
+
+        if let Some(offset) = &self.brightness_offset {
+            let levels = if offset.level6 { "L1 and L6" } else { "L1" };
+            writeln!(
+                status_out(),
+                "Offsetting {} metadata by {:+} nits...",
+                levels,
+                offset.nits
+            )
+            .ok();
+        }
+
+        if let Some(scale) = self.l1_scale {
+            writeln!(status_out(), "Scaling L1 metadata by {}...", scale).ok();
+        }
+
+        if let Some(max_nits) = self.l1_max_nits {
+            writeln!(status_out(), "Clamping L1 max to {} nits...", max_nits).ok();
+        }
+
+        if self.level3.is_some() {
+            writeln!(status_out(), "Setting L3 offsets...").ok();
+        }
+
+        if self.level4.is_some() {
+            writeln!(status_out(), "Setting L4 anchors...").ok();
+        }
+
+        if let Some(targets) = &self.targets {
+            writeln!(
+                status_out(),
+                "Setting {} custom target displays...",
+                targets.len()
+            )
+            .ok();
+        }
+
+        if self.drop_l6 {
+            writeln!(status_out(), "Dropping L6 metadata...").ok();
+        }
+
+        if let Some(remove_levels) = &self.remove_levels {
+            let levels = remove_levels
+                .levels()
+                .iter()
+                .map(|level| format!("L{}", level))
+                .collect::<Vec<_>>();
+
+            writeln!(status_out(), "Removing {} metadata...", levels.join(", ")).ok();
         }
 
         if let Some(active_area) = &self.active_area {
             if active_area.crop {
-                println!("Cropping...");
+                writeln!(status_out(), "Cropping...").ok();
             }
 
             if let Some(drop_opt) = &active_area.drop_l5 {
-                println!(
+                writeln!(
+                    status_out(),
                     "Dropping L5 metadata with opt '{}'",
                     drop_opt.to_lowercase()
-                );
+                )
+                .ok();
             }
         }
 
@@ -199,47 +883,250 @@ impl EditConfig {
             self.execute_single_rpu(rpu)?;
         }
 
+        // Within the scenes, requires complete list
+        if let Some(l1_smoothing) = &self.l1_smoothing {
+            let mut present_rpus: Vec<&mut DoviRpu> =
+                rpus.iter_mut().filter_map(|e| e.as_mut()).collect();
+            let scenes = l1_smoothing.apply(&mut present_rpus);
+
+            let method = match l1_smoothing.method() {
+                L1SmoothingMethod::Average => "average",
+                L1SmoothingMethod::Median => "median",
+            };
+
+            writeln!(
+                status_out(),
+                "Smoothed the L1 metadata of {} scenes, {}",
+                scenes,
+                method
+            )
+            .ok();
+        }
+
         // Specific ranges only, requires complete list
         if let Some(active_area) = &self.active_area {
             active_area.execute(rpus)?;
         }
 
+        if let Some(level3) = &self.level3 {
+            set_range_blocks(rpus, level3, |block| {
+                ExtMetadataBlock::Level3(block.clone())
+            })?;
+        }
+
+        if let Some(level4) = &self.level4 {
+            set_range_blocks(rpus, level4, |block| {
+                ExtMetadataBlock::Level4(block.clone())
+            })?;
+        }
+
+        for target in self.targets.iter().flatten() {
+            set_range_blocks(rpus, &target.trims, |trim| {
+                ExtMetadataBlock::Level8(target.level8(trim))
+            })?;
+        }
+
+        if let Some(RemoveLevels::Ranges(ranges)) = &self.remove_levels {
+            let specific_ranges = ranges.iter().filter(|e| e.0.to_lowercase() != "all");
+
+            for (range, levels) in specific_ranges {
+                let (start, end) = EditConfig::range_string_to_tuple(range)?;
+                ensure!(
+                    end < rpus.len(),
+                    "Invalid range: {} > {} available RPUs",
+                    end,
+                    rpus.len()
+                );
+
+                for rpu in rpus[start..=end].iter_mut().filter_map(|e| e.as_mut()) {
+                    remove_metadata_levels(rpu, levels);
+                }
+            }
+        }
+
         Ok(())
     }
 
     pub fn execute_single_rpu(&self, rpu: &mut DoviRpu) -> Result<()> {
+        // Before the mode conversion, which can remove the NLQ data
+        if let Some(nlq) = &self.nlq {
+            if nlq.remove {
+                rpu.remove_nlq()?;
+            } else {
+                rpu.set_nlq_params(&nlq.params)?;
+            }
+        }
+
         if self.convert_to_cmv4 {
             rpu.convert_to_cmv40()?;
         }
 
+        if self.remove_cmv4 {
+            rpu.convert_to_cmv29();
+        }
+
         if self.mode > 0 {
             rpu.convert_with_mode(self.mode)?;
         }
 
-        if self.min_pq.is_some() || self.max_pq.is_some() {
-            self.change_source_levels(rpu);
+        if self.min_pq.is_some() || self.max_pq.is_some() {
+            self.change_source_levels(rpu);
+        }
+
+        if self.l1_scale.is_some() || self.l1_max_nits.is_some() || self.brightness_offset.is_some()
+        {
+            self.edit_level1_metadata(rpu);
+        }
+
+        if let Some(offset) = self
+            .brightness_offset
+            .as_ref()
+            .filter(|offset| offset.level6)
+        {
+            offset_level6(rpu, offset.nits);
         }
 
         if self.remove_mapping {
             rpu.remove_mapping();
         }
 
+        // Allow passing "all" instead of a range
+        if let Some(block) = self.level3.as_ref().and_then(all_frames_block) {
+            set_metadata_block(rpu, ExtMetadataBlock::Level3(block.clone()))?;
+        }
+
+        if let Some(block) = self.level4.as_ref().and_then(all_frames_block) {
+            set_metadata_block(rpu, ExtMetadataBlock::Level4(block.clone()))?;
+        }
+
         if let Some(l6) = &self.level6 {
             self.set_level6_metadata(rpu, l6)?;
         }
 
+        if self.drop_l6 {
+            drop_level6_metadata(rpu);
+        }
+
+        if let Some(l8) = &self.level8 {
+            self.set_level8_metadata(rpu, l8)?;
+        }
+
         if let Some(l9) = &self.level9 {
             self.set_level9_metadata(rpu, l9)?;
         }
 
+        if let Some(l10) = &self.level10 {
+            self.set_level10_metadata(rpu, l10)?;
+        }
+
+        for target in self.targets.iter().flatten() {
+            set_metadata_block(rpu, ExtMetadataBlock::Level10(target.level10()))?;
+
+            if let Some(trim) = all_frames_block(&target.trims) {
+                set_metadata_block(rpu, ExtMetadataBlock::Level8(target.level8(trim)))?;
+            }
+        }
+
         if let Some(l11) = &self.level11 {
             self.set_level11_metadata(rpu, l11)?;
         }
 
+        if let Some(l254) = &self.level254 {
+            self.set_level254_metadata(rpu, l254)?;
+        }
+
         if let Some(active_area) = &self.active_area {
             active_area.execute_single_rpu(rpu)?;
         }
 
+        // Allow passing "all" instead of a range
+        match &self.remove_levels {
+            Some(RemoveLevels::All(levels)) => remove_metadata_levels(rpu, levels),
+            Some(RemoveLevels::Ranges(ranges)) => {
+                for (range, levels) in ranges {
+                    if range.to_lowercase() == "all" {
+                        remove_metadata_levels(rpu, levels);
+                    }
+                }
+            }
+            None => (),
+        }
+
+        Ok(())
+    }
+
+    /// Sets the L6 MaxCLL and MaxFALL to the highest L1 max and avg of all the RPUs.
+    /// RPUs without L6 metadata get a block with the source mastering display luminance.
+    pub fn set_level6_from_level1(&self, rpus: &mut [&mut DoviRpu]) -> Result<()> {
+        if !self.level6_from_l1 {
+            return Ok(());
+        }
+
+        let light_levels = rpus
+            .iter()
+            .filter_map(|rpu| match rpu.vdr_dm_data.as_ref()?.get_block(1)? {
+                ExtMetadataBlock::Level1(level1) => Some((level1.max_pq, level1.avg_pq)),
+                _ => None,
+            })
+            .map(|(max_pq, avg_pq)| {
+                let nits = |pq: u16| pq_to_nits(pq12_to_pq(pq)).round().min(10_000.0);
+
+                (nits(max_pq), nits(avg_pq))
+            })
+            .fold(None, |acc: Option<(f64, f64)>, (max, avg)| match acc {
+                Some((acc_max, acc_avg)) => Some((acc_max.max(max), acc_avg.max(avg))),
+                None => Some((max, avg)),
+            });
+
+        let (max_cll, max_fall) = match light_levels {
+            Some(light_levels) => light_levels,
+            None => bail!("No L1 metadata to set the L6 metadata from"),
+        };
+
+        writeln!(
+            status_out(),
+            "Setting L6 MaxCLL {} nits and MaxFALL {} nits from the L1 metadata...",
+            max_cll,
+            max_fall
+        )
+        .ok();
+
+        for rpu in rpus.iter_mut() {
+            if let Some(ref mut vdr_dm_data) = rpu.vdr_dm_data {
+                rpu.modified = true;
+
+                let mut level6 = match vdr_dm_data.get_block(6) {
+                    Some(ExtMetadataBlock::Level6(level6)) => level6.clone(),
+                    _ => ExtMetadataBlockLevel6 {
+                        max_display_mastering_luminance: pq_to_nits(pq12_to_pq(
+                            vdr_dm_data.source_max_pq,
+                        ))
+                        .round() as u16,
+                        min_display_mastering_luminance: (pq_to_nits(pq12_to_pq(
+                            vdr_dm_data.source_min_pq,
+                        )) * 10_000.0)
+                            .round()
+                            as u16,
+                        ..Default::default()
+                    },
+                };
+
+                level6.max_content_light_level = max_cll as u16;
+                level6.max_frame_average_light_level = max_fall as u16;
+
+                vdr_dm_data.replace_metadata_block(ExtMetadataBlock::Level6(level6))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds the trims for the target displays without one, from the final L1 metadata
+    pub fn generate_trims(&self, rpu: &mut DoviRpu) -> Result<()> {
+        if let Some(vdr_dm_data) = rpu.vdr_dm_data.as_mut() {
+            vdr_dm_data.add_generated_trims(&self.trim_targets, self.trim_algorithm)?;
+        }
+
         Ok(())
     }
 
@@ -290,7 +1177,74 @@ impl EditConfig {
             }
         }
 
-        println!("Removed {} metadata frames.", amount);
+        writeln!(status_out(), "Removed {} metadata frames.", amount).ok();
+
+        Ok(())
+    }
+
+    fn replace_frames(
+        &self,
+        replacements: &[ReplaceMetadata],
+        rpus: &mut [Option<DoviRpu>],
+    ) -> Result<()> {
+        let mut sources: HashMap<&PathBuf, Vec<DoviRpu>> = HashMap::new();
+
+        for replacement in replacements {
+            let [start, end] = replacement.range;
+            let source_start = replacement.source_start.unwrap_or(start);
+
+            ensure!(start <= end, "invalid replace range {}-{}", start, end);
+            ensure!(end < rpus.len(), "invalid replace end range {}", end);
+
+            if !sources.contains_key(&replacement.source_rpu) {
+                writeln!(
+                    status_out(),
+                    "Parsing replacement RPU file {}...",
+                    replacement.source_rpu.display()
+                )
+                .ok();
+
+                let source_rpus = parse_rpu_file(&replacement.source_rpu)?.unwrap_or_default();
+                sources.insert(&replacement.source_rpu, source_rpus);
+            }
+
+            let source_rpus = &sources[&replacement.source_rpu];
+            let source_end = source_start + (end - start);
+
+            ensure!(
+                source_end < source_rpus.len(),
+                "invalid replace range: frame {} is out of bounds for {} ({} RPUs)",
+                source_end,
+                replacement.source_rpu.display(),
+                source_rpus.len()
+            );
+
+            for (rpu, source_rpu) in rpus[start..=end]
+                .iter_mut()
+                .zip(&source_rpus[source_start..=source_end])
+            {
+                if let Some(rpu) = rpu {
+                    ensure!(
+                        rpu.dovi_profile == source_rpu.dovi_profile,
+                        "Cannot replace profile {} RPUs with profile {} RPUs from {}",
+                        rpu.dovi_profile,
+                        source_rpu.dovi_profile,
+                        replacement.source_rpu.display()
+                    );
+                }
+
+                *rpu = Some(source_rpu.clone());
+            }
+
+            writeln!(
+                status_out(),
+                "Replaced frames {}-{} from {}",
+                start,
+                end,
+                replacement.source_rpu.display()
+            )
+            .ok();
+        }
 
         Ok(())
     }
@@ -300,7 +1254,12 @@ impl EditConfig {
         to_duplicate: &[DuplicateMetadata],
         data: &mut Vec<Vec<u8>>,
     ) -> Result<()> {
-        println!("Duplicating metadata. Initial metadata len {}", data.len());
+        writeln!(
+            status_out(),
+            "Duplicating metadata. Initial metadata len {}",
+            data.len()
+        )
+        .ok();
 
         for meta in to_duplicate {
             ensure!(
@@ -312,13 +1271,68 @@ impl EditConfig {
             let source = data[meta.source].clone();
             data.splice(
                 meta.offset..meta.offset,
-                std::iter::repeat(source).take(meta.length),
+                std::iter::repeat_n(source, meta.length),
             );
         }
 
         Ok(())
     }
 
+    /// Delays the metadata by padding with the first RPU, or advances it by trimming the first RPUs
+    fn shift_metadata(&self, data: &mut Vec<Vec<u8>>) -> Result<()> {
+        ensure!(!data.is_empty(), "No metadata to shift");
+
+        let amount = self.shift.unsigned_abs() as usize;
+
+        if self.shift > 0 {
+            writeln!(status_out(), "Delaying metadata by {} frames.", amount).ok();
+
+            // Only the first padded frame is a scene cut
+            let padding = rpu_data_with_scene_cut(&data[0], false)?;
+
+            data.splice(
+                0..0,
+                std::iter::once(rpu_data_with_scene_cut(&data[0], true)?)
+                    .chain(std::iter::repeat_n(padding, amount - 1)),
+            );
+        } else {
+            ensure!(
+                amount < data.len(),
+                "invalid shift {}, only {} metadata frames",
+                self.shift,
+                data.len()
+            );
+
+            writeln!(status_out(), "Advancing metadata by {} frames.", amount).ok();
+
+            data.drain(..amount);
+
+            // The first frame always starts a scene
+            data[0] = rpu_data_with_scene_cut(&data[0], true)?;
+        }
+
+        Ok(())
+    }
+
+    /// Pads the end of the metadata with the last RPU, continuing its scene
+    fn extend_metadata(&self, data: &mut Vec<Vec<u8>>) -> Result<()> {
+        let last = match data.last() {
+            Some(last) => rpu_data_with_scene_cut(last, false)?,
+            None => bail!("No metadata to extend"),
+        };
+
+        writeln!(
+            status_out(),
+            "Extending metadata by {} frames.",
+            self.extend
+        )
+        .ok();
+
+        data.extend(std::iter::repeat_n(last, self.extend));
+
+        Ok(())
+    }
+
     fn change_source_levels(&self, rpu: &mut DoviRpu) {
         rpu.modified = true;
 
@@ -327,6 +1341,20 @@ impl EditConfig {
         }
     }
 
+    /// Offsets and scales the L1 values in linear light, then clamps the max to the nits ceiling.
+    /// L1 is constant within a scene, so the scenes keep consistent values.
+    fn edit_level1_metadata(&self, rpu: &mut DoviRpu) {
+        let offset = self.brightness_offset.as_ref().map(|offset| offset.nits);
+
+        if let Some(ref mut vdr_dm_data) = rpu.vdr_dm_data {
+            if let Some(ExtMetadataBlock::Level1(level1)) = vdr_dm_data.get_block_mut(1) {
+                rpu.modified = true;
+
+                scale_clamp_level1(level1, offset, self.l1_scale, self.l1_max_nits);
+            }
+        }
+    }
+
     fn set_level6_metadata(
         &self,
         rpu: &mut DoviRpu,
@@ -341,19 +1369,32 @@ impl EditConfig {
         Ok(())
     }
 
-    fn set_level9_metadata(
+    fn set_level8_metadata(
         &self,
         rpu: &mut DoviRpu,
-        primaries: &MasteringDisplayPrimaries,
+        level8: &[ExtMetadataBlockLevel8],
     ) -> Result<()> {
-        let primary_index = *primaries as u8;
+        rpu.modified = true;
+
+        if let Some(ref mut vdr_dm_data) = rpu.vdr_dm_data {
+            for block in level8 {
+                vdr_dm_data.replace_metadata_block(ExtMetadataBlock::Level8(block.clone()))?;
+            }
+        }
+
+        Ok(())
+    }
 
+    fn set_level9_metadata(&self, rpu: &mut DoviRpu, level9: &Level9Metadata) -> Result<()> {
         rpu.modified = true;
 
-        let level9 = ExtMetadataBlockLevel9 {
-            length: 1,
-            source_primary_index: primary_index,
-            ..Default::default()
+        let level9 = match level9 {
+            Level9Metadata::Preset(primaries) => ExtMetadataBlockLevel9 {
+                length: 1,
+                source_primary_index: *primaries as u8,
+                ..Default::default()
+            },
+            Level9Metadata::Block(block) => block.clone(),
         };
 
         if let Some(ref mut vdr_dm_data) = rpu.vdr_dm_data {
@@ -363,21 +1404,69 @@ impl EditConfig {
         Ok(())
     }
 
-    fn set_level11_metadata(
+    fn set_level10_metadata(
+        &self,
+        rpu: &mut DoviRpu,
+        level10: &[ExtMetadataBlockLevel10],
+    ) -> Result<()> {
+        rpu.modified = true;
+
+        if let Some(ref mut vdr_dm_data) = rpu.vdr_dm_data {
+            for block in level10 {
+                vdr_dm_data.replace_metadata_block(ExtMetadataBlock::Level10(block.clone()))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn set_level11_metadata(&self, rpu: &mut DoviRpu, level11: &Level11Metadata) -> Result<()> {
+        rpu.modified = true;
+
+        let level11 = match level11 {
+            Level11Metadata::Preset(content_type) => {
+                ExtMetadataBlockLevel11::from_content_type(*content_type)
+            }
+            Level11Metadata::Block(block) => block.clone(),
+        };
+
+        if let Some(ref mut vdr_dm_data) = rpu.vdr_dm_data {
+            vdr_dm_data.replace_metadata_block(ExtMetadataBlock::Level11(level11))?;
+        }
+
+        Ok(())
+    }
+
+    fn set_level254_metadata(
         &self,
         rpu: &mut DoviRpu,
-        level11: &ExtMetadataBlockLevel11,
+        level254: &ExtMetadataBlockLevel254,
     ) -> Result<()> {
         rpu.modified = true;
 
         if let Some(ref mut vdr_dm_data) = rpu.vdr_dm_data {
-            vdr_dm_data.replace_metadata_block(ExtMetadataBlock::Level11(level11.clone()))?;
+            vdr_dm_data.replace_metadata_block(ExtMetadataBlock::Level254(level254.clone()))?;
         }
 
         Ok(())
     }
 }
 
+impl RemoveLevels {
+    /// Sorted levels removed from any frame
+    fn levels(&self) -> Vec<u8> {
+        let mut levels: Vec<u8> = match self {
+            RemoveLevels::All(levels) => levels.clone(),
+            RemoveLevels::Ranges(ranges) => ranges.values().flatten().copied().collect(),
+        };
+
+        levels.sort_unstable();
+        levels.dedup();
+
+        levels
+    }
+}
+
 impl ActiveArea {
     fn execute(&self, rpus: &mut [Option<DoviRpu>]) -> Result<()> {
         if let Some(edits) = &self.edits {
@@ -400,16 +1489,11 @@ impl ActiveArea {
 
         // Allow passing "all" instead of a range
         // Do "all" presets before specific ranges
-        if let (Some(presets), Some(edits)) = (&self.presets, &self.edits) {
-            for edit in edits {
-                let preset_id = *edit.1;
-
-                if edit.0.to_lowercase() == "all" {
-                    if let Some(active_area_offsets) = presets.iter().find(|e| e.id == preset_id) {
-                        self.set_offsets(rpu, active_area_offsets)?;
-                    } else {
-                        bail!("Invalid preset ID: {}", preset_id);
-                    }
+        if let Some(edits) = &self.edits {
+            for (range, edit) in edits {
+                if range.to_lowercase() == "all" {
+                    let offsets = self.edit_offsets(edit)?;
+                    self.set_offsets(rpu, offsets)?;
                 }
             }
         }
@@ -417,26 +1501,37 @@ impl ActiveArea {
         Ok(())
     }
 
-    fn do_edits(&self, edits: &HashMap<String, u16>, rpus: &mut [Option<DoviRpu>]) -> Result<()> {
-        if let Some(presets) = &self.presets {
-            println!("Editing active area offsets...");
+    fn do_edits(
+        &self,
+        edits: &HashMap<String, ActiveAreaEdit>,
+        rpus: &mut [Option<DoviRpu>],
+    ) -> Result<()> {
+        writeln!(status_out(), "Editing active area offsets...").ok();
 
-            let specific_edits = edits.iter().filter(|e| e.0.to_lowercase() != "all");
+        let specific_edits = edits.iter().filter(|e| e.0.to_lowercase() != "all");
 
-            for edit in specific_edits {
-                let (start, end) = EditConfig::range_string_to_tuple(edit.0)?;
-                let preset_id = *edit.1;
+        for (range, edit) in specific_edits {
+            let (start, end) = EditConfig::range_string_to_tuple(range)?;
 
-                if end as usize > rpus.len() {
-                    bail!("Invalid range: {} > {} available RPUs", end, rpus.len());
-                }
+            if end > rpus.len() {
+                bail!("Invalid range: {} > {} available RPUs", end, rpus.len());
+            }
 
-                if let Some(active_area_offsets) = presets.iter().find(|e| e.id == preset_id) {
-                    for rpu in rpus[start..=end].iter_mut().filter_map(|e| e.as_mut()) {
-                        self.set_offsets(rpu, active_area_offsets)?;
+            if let ActiveAreaEdit::Transition { from, to } = edit {
+                let from = self.edit_offsets(from)?;
+                let to = self.edit_offsets(to)?;
+
+                for (i, rpu) in rpus[start..=end].iter_mut().enumerate() {
+                    if let Some(rpu) = rpu {
+                        let offsets = interpolate_offsets(from, to, i, end - start);
+                        self.set_offsets(rpu, offsets)?;
                     }
-                } else {
-                    bail!("Invalid preset ID: {}", preset_id);
+                }
+            } else {
+                let offsets = self.edit_offsets(edit)?;
+
+                for rpu in rpus[start..=end].iter_mut().filter_map(|e| e.as_mut()) {
+                    self.set_offsets(rpu, offsets)?;
                 }
             }
         }
@@ -444,20 +1539,59 @@ impl ActiveArea {
         Ok(())
     }
 
-    fn set_offsets(
-        &self,
-        rpu: &mut DoviRpu,
-        active_area_offsets: &ActiveAreaOffsets,
-    ) -> Result<()> {
-        rpu.modified = true;
+    /// Left, right, top and bottom offsets of an edit
+    fn edit_offsets(&self, edit: &ActiveAreaEdit) -> Result<(u16, u16, u16, u16)> {
+        match edit {
+            ActiveAreaEdit::Id(preset_id) => {
+                let preset = self.presets.iter().flatten().find(|e| e.id == *preset_id);
+
+                match preset {
+                    Some(p) => Ok((p.left, p.right, p.top, p.bottom)),
+                    None => bail!("Invalid preset ID: {}", preset_id),
+                }
+            }
+            ActiveAreaEdit::AspectRatio { preset } => {
+                match (self.canvas_width, self.canvas_height) {
+                    (Some(width), Some(height)) => Ok(preset.offsets(width, height)),
+                    _ => bail!(
+                        "The {} aspect ratio preset requires the canvas_width and canvas_height",
+                        preset.name()
+                    ),
+                }
+            }
+            ActiveAreaEdit::Transition { .. } => {
+                bail!("Active area transitions require a frame range, not \"all\"")
+            }
+        }
+    }
 
-        let (left, right, top, bottom) = (
-            active_area_offsets.left,
-            active_area_offsets.right,
-            active_area_offsets.top,
-            active_area_offsets.bottom,
+    fn validate(&self) -> Result<()> {
+        ensure!(
+            self.canvas_width != Some(0) && self.canvas_height != Some(0),
+            "Invalid active area canvas, the width and height must be greater than 0"
         );
 
+        // Fail early instead of after parsing the RPUs
+        for (range, edit) in self.edits.iter().flatten() {
+            match edit {
+                ActiveAreaEdit::Transition { from, to } if range.to_lowercase() != "all" => {
+                    self.edit_offsets(from)?;
+                    self.edit_offsets(to)?;
+                }
+                _ => {
+                    self.edit_offsets(edit)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn set_offsets(&self, rpu: &mut DoviRpu, offsets: (u16, u16, u16, u16)) -> Result<()> {
+        rpu.modified = true;
+
+        let (left, right, top, bottom) = offsets;
+
         if let Some(ref mut vdr_dm_data) = rpu.vdr_dm_data {
             vdr_dm_data.replace_metadata_block(ExtMetadataBlock::Level5(
                 ExtMetadataBlockLevel5::from_offsets(left, right, top, bottom),
@@ -494,3 +1628,306 @@ impl ActiveArea {
         Ok(())
     }
 }
+
+/// Removes the L6 metadata block, the lengths and CRC32 are recomputed when writing
+impl AspectRatioPreset {
+    fn name(&self) -> &'static str {
+        match self {
+            AspectRatioPreset::Scope => "2.39",
+            AspectRatioPreset::Imax => "1.90",
+            AspectRatioPreset::Flat => "1.85",
+            AspectRatioPreset::FullFrame => "full",
+        }
+    }
+
+    /// Left, right, top and bottom offsets in the canvas.
+    /// The active area is rounded to a multiple of 4 pixels, centered in the canvas.
+    fn offsets(&self, canvas_width: u16, canvas_height: u16) -> (u16, u16, u16, u16) {
+        let ratio = match self {
+            AspectRatioPreset::Scope => 2.39,
+            AspectRatioPreset::Imax => 1.90,
+            AspectRatioPreset::Flat => 1.85,
+            AspectRatioPreset::FullFrame => return (0, 0, 0, 0),
+        };
+
+        let round = |size: f64| ((size / 4.0).round() * 4.0) as u16;
+        let canvas_ratio = canvas_width as f64 / canvas_height as f64;
+
+        if ratio > canvas_ratio {
+            let bars = canvas_height.saturating_sub(round(canvas_width as f64 / ratio));
+            (0, 0, bars / 2, bars - bars / 2)
+        } else {
+            let bars = canvas_width.saturating_sub(round(canvas_height as f64 * ratio));
+            (bars / 2, bars - bars / 2, 0, 0)
+        }
+    }
+}
+
+/// Removes every block of the levels, the block count and CRC32 are updated when writing
+pub fn remove_metadata_levels(rpu: &mut DoviRpu, levels: &[u8]) {
+    if let Some(ref mut vdr_dm_data) = rpu.vdr_dm_data {
+        rpu.modified = true;
+
+        for level in levels {
+            vdr_dm_data.remove_metadata_level(*level);
+        }
+    }
+}
+
+pub fn drop_level6_metadata(rpu: &mut DoviRpu) {
+    if let Some(ref mut vdr_dm_data) = rpu.vdr_dm_data {
+        rpu.modified = true;
+
+        vdr_dm_data.remove_metadata_level(6);
+    }
+}
+
+/// Synthesizes the L2 trim for the target display peak brightness, from the existing trims.
+///
+/// The trim parameters are interpolated in PQ between the nearest lower and higher targets.
+/// Above the highest trim, the upper bound is a neutral trim at the source max PQ.
+/// Below the lowest trim, it is copied as is.
+/// RPUs without L2 metadata or already having a trim for the target are left untouched.
+pub fn retarget_level2_trim(rpu: &mut DoviRpu, target_nits: u16) -> Result<()> {
+    if let Some(ref mut vdr_dm_data) = rpu.vdr_dm_data {
+        let target_max_pq = ExtMetadataBlockLevel2::from_nits(target_nits).target_max_pq;
+
+        let trims: Vec<ExtMetadataBlockLevel2> = vdr_dm_data
+            .level_blocks_iter(2)
+            .filter_map(|block| match block {
+                ExtMetadataBlock::Level2(level2) => Some(level2.clone()),
+                _ => None,
+            })
+            .collect();
+
+        if trims.is_empty() || trims.iter().any(|t| t.target_max_pq == target_max_pq) {
+            return Ok(());
+        }
+
+        let lower = trims
+            .iter()
+            .filter(|t| t.target_max_pq < target_max_pq)
+            .max_by_key(|t| t.target_max_pq);
+        let higher = trims
+            .iter()
+            .filter(|t| t.target_max_pq > target_max_pq)
+            .min_by_key(|t| t.target_max_pq);
+
+        let neutral = ExtMetadataBlockLevel2 {
+            target_max_pq: vdr_dm_data.source_max_pq,
+            ..Default::default()
+        };
+
+        let synthesized = match (lower, higher) {
+            (Some(lower), Some(higher)) => interpolate_level2(lower, higher, target_max_pq),
+            (Some(lower), None) if target_max_pq < neutral.target_max_pq => {
+                interpolate_level2(lower, &neutral, target_max_pq)
+            }
+            // No tone mapping at or above the source peak
+            (Some(_), None) => return Ok(()),
+            (None, Some(higher)) => ExtMetadataBlockLevel2 {
+                target_max_pq,
+                ..higher.clone()
+            },
+            (None, None) => unreachable!(),
+        };
+
+        rpu.modified = true;
+        vdr_dm_data.replace_metadata_block(ExtMetadataBlock::Level2(synthesized))?;
+    }
+
+    Ok(())
+}
+
+fn interpolate_level2(
+    lower: &ExtMetadataBlockLevel2,
+    higher: &ExtMetadataBlockLevel2,
+    target_max_pq: u16,
+) -> ExtMetadataBlockLevel2 {
+    let ratio = (target_max_pq - lower.target_max_pq) as f64
+        / (higher.target_max_pq - lower.target_max_pq) as f64;
+
+    let lerp = |a: u16, b: u16| (a as f64 + (b as f64 - a as f64) * ratio).round() as u16;
+
+    // -1 means the default weight, which can't be interpolated
+    let ms_weight = if lower.ms_weight >= 0 && higher.ms_weight >= 0 {
+        lerp(lower.ms_weight as u16, higher.ms_weight as u16) as i16
+    } else {
+        lower.ms_weight
+    };
+
+    ExtMetadataBlockLevel2 {
+        target_max_pq,
+        trim_slope: lerp(lower.trim_slope, higher.trim_slope),
+        trim_offset: lerp(lower.trim_offset, higher.trim_offset),
+        trim_power: lerp(lower.trim_power, higher.trim_power),
+        trim_chroma_weight: lerp(lower.trim_chroma_weight, higher.trim_chroma_weight),
+        trim_saturation_gain: lerp(lower.trim_saturation_gain, higher.trim_saturation_gain),
+        ms_weight,
+    }
+}
+
+/// Recomputes the 12 bits L1 PQ codes, keeping min <= avg <= max.
+/// The offset is added before scaling.
+fn scale_clamp_level1(
+    level1: &mut ExtMetadataBlockLevel1,
+    offset: Option<f64>,
+    scale: Option<f64>,
+    max_nits: Option<f64>,
+) {
+    let scale_code = |code: u16| -> u16 {
+        if offset.is_none() && scale.is_none() {
+            return code;
+        }
+
+        let nits = (pq_to_nits(pq12_to_pq(code)) + offset.unwrap_or(0.0)) * scale.unwrap_or(1.0);
+
+        pq_to_pq12(nits_to_pq(nits.clamp(0.0, 10_000.0)))
+    };
+
+    let mut min_pq = scale_code(level1.min_pq);
+    let mut max_pq = scale_code(level1.max_pq);
+    let mut avg_pq = scale_code(level1.avg_pq);
+
+    if let Some(max_nits) = max_nits {
+        let ceiling = pq_to_pq12(nits_to_pq(max_nits.min(10_000.0)));
+
+        max_pq = max_pq.min(ceiling);
+        avg_pq = avg_pq.min(max_pq);
+        min_pq = min_pq.min(avg_pq);
+    }
+
+    level1.min_pq = min_pq;
+    level1.max_pq = max_pq;
+    level1.avg_pq = avg_pq;
+}
+
+impl TargetDisplay {
+    /// L10 block with preset primaries
+    fn level10(&self) -> ExtMetadataBlockLevel10 {
+        let to_pq = |nits: f64| pq_to_pq12(nits_to_pq(nits));
+        let primaries = self
+            .primaries
+            .unwrap_or(MasteringDisplayPrimaries::DCIP3D65);
+
+        ExtMetadataBlockLevel10 {
+            length: 5,
+            target_display_index: self.target_display_index,
+            target_max_pq: to_pq(self.max_nits),
+            target_min_pq: to_pq(self.min_nits),
+            target_primary_index: primaries as u8,
+            ..Default::default()
+        }
+    }
+
+    fn level8(&self, trim: &ExtMetadataBlockLevel8) -> ExtMetadataBlockLevel8 {
+        ExtMetadataBlockLevel8 {
+            target_display_index: self.target_display_index,
+            ..trim.clone()
+        }
+    }
+}
+
+/// Block of the "all" key, for the levels set for every frame or per frame range
+fn all_frames_block<T>(blocks: &HashMap<String, T>) -> Option<&T> {
+    blocks
+        .iter()
+        .find(|(range, _)| range.to_lowercase() == "all")
+        .map(|(_, block)| block)
+}
+
+/// Sets the blocks of the frame ranges, the later start taking precedence for overlapping ranges
+fn set_range_blocks<T>(
+    rpus: &mut [Option<DoviRpu>],
+    blocks: &HashMap<String, T>,
+    to_block: impl Fn(&T) -> ExtMetadataBlock,
+) -> Result<()> {
+    let mut ranges = blocks
+        .iter()
+        .filter(|(range, _)| range.to_lowercase() != "all")
+        .map(|(range, block)| Ok((EditConfig::range_string_to_tuple(range)?, to_block(block))))
+        .collect::<Result<Vec<_>>>()?;
+
+    ranges.sort_by_key(|(range, _)| *range);
+
+    for ((start, end), block) in ranges {
+        ensure!(
+            end < rpus.len(),
+            "Invalid L{} range: {} > {} available RPUs",
+            block.level(),
+            end,
+            rpus.len()
+        );
+
+        for rpu in rpus[start..=end].iter_mut().filter_map(|e| e.as_mut()) {
+            set_metadata_block(rpu, block.clone())?;
+        }
+    }
+
+    Ok(())
+}
+
+fn set_metadata_block(rpu: &mut DoviRpu, block: ExtMetadataBlock) -> Result<()> {
+    rpu.modified = true;
+
+    if let Some(ref mut vdr_dm_data) = rpu.vdr_dm_data {
+        vdr_dm_data.replace_metadata_block(block)?;
+    }
+
+    Ok(())
+}
+
+/// Shifts the L6 MaxCLL and MaxFALL, unset (zero) values are kept
+fn offset_level6(rpu: &mut DoviRpu, offset: f64) {
+    if let Some(ref mut vdr_dm_data) = rpu.vdr_dm_data {
+        if let Some(ExtMetadataBlock::Level6(level6)) = vdr_dm_data.get_block_mut(6) {
+            rpu.modified = true;
+
+            let shift = |value: u16| -> u16 {
+                if value == 0 {
+                    0
+                } else {
+                    (value as f64 + offset).round().clamp(1.0, 10_000.0) as u16
+                }
+            };
+
+            level6.max_content_light_level = shift(level6.max_content_light_level);
+            level6.max_frame_average_light_level = shift(level6.max_frame_average_light_level);
+        }
+    }
+}
+
+/// Offsets of the frame `index` of a transition lasting `length` frames after the first one
+fn interpolate_offsets(
+    from: (u16, u16, u16, u16),
+    to: (u16, u16, u16, u16),
+    index: usize,
+    length: usize,
+) -> (u16, u16, u16, u16) {
+    let t = if length > 0 {
+        index as f64 / length as f64
+    } else {
+        1.0
+    };
+
+    let lerp = |a: u16, b: u16| (a as f64 + (b as f64 - a as f64) * t).round() as u16;
+
+    (
+        lerp(from.0, to.0),
+        lerp(from.1, to.1),
+        lerp(from.2, to.2),
+        lerp(from.3, to.3),
+    )
+}
+
+/// Encoded RPU with the scene refresh flag set or cleared
+fn rpu_data_with_scene_cut(data: &[u8], is_scene_cut: bool) -> Result<Vec<u8>> {
+    let mut rpu = DoviRpu::parse_unspec62_nalu(data)?;
+
+    if let Some(ref mut vdr_dm_data) = rpu.vdr_dm_data {
+        vdr_dm_data.set_scene_cut(is_scene_cut);
+        rpu.modified = true;
+    }
+
+    rpu.write_hevc_unspec62_nalu()
+}
@@ -0,0 +1,169 @@
+use std::io::{self, Write};
+
+use anyhow::Result;
+use hevc_parser::hevc::NAL_SPS;
+
+use dolby_vision::rpu::dovi_rpu::DoviRpu;
+
+use super::hevc_sps::SpsInfo;
+
+/// Maximum size of an EL picture coded without residual data, in bits per pixel.
+/// Flat pictures only code the slice headers and a few bits per CTB.
+const MAX_EMPTY_PICTURE_BPP: f64 = 0.005;
+
+/// Allowance for the slice header of each slice, in bytes
+const MAX_SLICE_HEADER_SIZE: usize = 64;
+
+/// Last VCL NAL unit type
+const MAX_VCL_NAL_TYPE: u8 = 31;
+
+/// Sizes of the enhancement layer pictures, to tell a MEL from a FEL without decoding the EL
+#[derive(Default)]
+pub struct ElAnalysis {
+    sps: Option<SpsInfo>,
+
+    /// EL pictures, in decoding order
+    pictures: Vec<ElPicture>,
+}
+
+/// Slice bytes and slice count of an EL picture, with the decoded frame of its first slice
+pub struct ElPicture {
+    pub decoded_index: u64,
+    pub size: usize,
+    pub slices: usize,
+}
+
+/// How the EL is used, from the RPU NLQ data and the EL pictures
+#[derive(Debug, PartialEq, Eq)]
+pub enum ElType {
+    /// No NLQ data in the RPUs, the EL is not used
+    Unused,
+    /// MEL NLQ parameters, the EL residual is discarded
+    Mel,
+    /// FEL NLQ parameters, but no residual data is coded in the EL pictures
+    EmptyFel,
+    /// FEL NLQ parameters, with residual data in some EL pictures
+    Fel,
+}
+
+impl ElAnalysis {
+    /// Adds a NAL unit of the EL of a decoded frame, with its own NAL header
+    pub fn add_nal(&mut self, decoded_index: u64, data: &[u8]) -> Result<()> {
+        if data.len() < 3 {
+            return Ok(());
+        }
+
+        let nal_type = (data[0] >> 1) & 0x3F;
+
+        if nal_type == NAL_SPS && self.sps.is_none() {
+            self.sps = Some(SpsInfo::parse(data)?);
+        } else if nal_type <= MAX_VCL_NAL_TYPE {
+            let first_slice_segment_in_pic_flag = data[2] & 0x80 != 0;
+
+            if first_slice_segment_in_pic_flag || self.pictures.is_empty() {
+                self.pictures.push(ElPicture {
+                    decoded_index,
+                    size: 0,
+                    slices: 0,
+                });
+            }
+
+            if let Some(picture) = self.pictures.last_mut() {
+                picture.size += data.len() - 2;
+                picture.slices += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pictures.is_empty()
+    }
+
+    pub fn pictures(&self) -> &[ElPicture] {
+        &self.pictures
+    }
+
+    /// Pixels of an EL picture, from the EL SPS
+    pub fn pixels(&self) -> Option<f64> {
+        self.sps
+            .as_ref()
+            .map(|sps| sps.width as f64 * sps.height as f64)
+    }
+
+    /// Whether the EL picture is too large to be coded without residual data.
+    /// Without the EL SPS, every picture is assumed to have residual data
+    pub fn has_residual(&self, picture: &ElPicture) -> bool {
+        match self.pixels() {
+            Some(pixels) => {
+                let max_data_size = (pixels * MAX_EMPTY_PICTURE_BPP / 8.0) as usize;
+
+                picture.size > picture.slices * MAX_SLICE_HEADER_SIZE + max_data_size
+            }
+            None => true,
+        }
+    }
+
+    /// Number of EL pictures too large to be coded without residual data
+    pub fn residual_pictures(&self) -> usize {
+        self.pictures
+            .iter()
+            .filter(|picture| self.has_residual(picture))
+            .count()
+    }
+
+    pub fn el_type(&self, rpus: &[DoviRpu]) -> ElType {
+        let mut nlq_rpus = rpus
+            .iter()
+            .filter_map(|rpu| rpu.rpu_data_nlq.as_ref())
+            .peekable();
+
+        if nlq_rpus.peek().is_none() {
+            ElType::Unused
+        } else if nlq_rpus.all(|nlq| nlq.is_mel()) {
+            ElType::Mel
+        } else if self.residual_pictures() == 0 {
+            ElType::EmptyFel
+        } else {
+            ElType::Fel
+        }
+    }
+
+    pub fn print_summary(&self, rpus: &[DoviRpu], out: &mut dyn Write) -> io::Result<()> {
+        if self.is_empty() {
+            return writeln!(out, "  EL: None");
+        }
+
+        let resolution = match &self.sps {
+            Some(sps) => format!("{}x{}", sps.width, sps.height),
+            None => String::from("unknown resolution"),
+        };
+
+        let total_size: usize = self.pictures.iter().map(|picture| picture.size).sum();
+
+        writeln!(
+            out,
+            "  EL: {} pictures, {}, average {} bytes per picture",
+            self.pictures.len(),
+            resolution,
+            total_size / self.pictures.len()
+        )?;
+        writeln!(
+            out,
+            "  EL pictures with residual data: {}",
+            self.residual_pictures()
+        )?;
+
+        let el_type = match self.el_type(rpus) {
+            ElType::Unused => "unused, no NLQ data in the RPUs. Discarding the EL is lossless",
+            ElType::Mel => "MEL. Discarding the EL is lossless",
+            ElType::EmptyFel => {
+                "FEL NLQ data, but no residual coded in the EL. Discarding the EL is lossless"
+            }
+            ElType::Fel => "FEL. Discarding the EL is lossy",
+        };
+
+        writeln!(out, "  EL type: {}", el_type)
+    }
+}
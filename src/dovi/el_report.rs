@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use hevc_parser::hevc::Frame;
+
+use super::codec::Codec;
+use super::el_analysis::ElAnalysis;
+use super::rpu_stream::status_out;
+use super::DoviRpu;
+
+/// Scenes listed in the summary, by highest EL contribution
+const MAX_LISTED_SCENES: usize = 5;
+
+/// Contribution of the EL discarded by a mode 2 conversion, per scene.
+///
+/// The EL is not decoded: the residual of a frame is measured by the coded size of its EL picture,
+/// and only counts for the frames with FEL NLQ parameters. The scenes are split by the scene cut flags.
+pub struct ElReport {
+    path: PathBuf,
+    codec: Codec,
+    el: ElAnalysis,
+    /// RPUs before conversion, by decoded frame
+    rpus: HashMap<u64, FrameRpu>,
+}
+
+/// Metadata of a frame RPU used by the report
+struct FrameRpu {
+    scene_cut: bool,
+    /// NLQ parameters of the first pivot, for FEL RPUs only
+    nlq: Option<FrameNlq>,
+}
+
+struct FrameNlq {
+    nlq_offset: [u64; 3],
+    vdr_in_max: [f64; 3],
+}
+
+#[derive(Serialize)]
+struct Report {
+    frames: usize,
+    fel_frames: usize,
+    residual_frames: usize,
+    /// Average coded size of the EL pictures, in bits per pixel
+    el_bits_per_pixel: Option<f64>,
+    lossy_scenes: usize,
+    scenes: Vec<SceneContribution>,
+}
+
+#[derive(Serialize)]
+struct SceneContribution {
+    scene: usize,
+    start_frame: usize,
+    frames: usize,
+    /// Frames with FEL NLQ parameters
+    fel_frames: usize,
+    /// FEL frames with residual data coded in their EL picture
+    residual_frames: usize,
+    el_bits_per_pixel: Option<f64>,
+    /// Min and max NLQ offset of the FEL frames, per component
+    nlq_offset: Option<[[u64; 2]; 3]>,
+    /// Max NLQ input range of the FEL frames, per component
+    vdr_in_max: Option<[f64; 3]>,
+    lossless: bool,
+
+    #[serde(skip)]
+    el_bits: f64,
+    #[serde(skip)]
+    el_pictures: usize,
+}
+
+impl ElReport {
+    pub fn new(path: &Path, codec: Codec) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            codec,
+            el: ElAnalysis::default(),
+            rpus: HashMap::new(),
+        }
+    }
+
+    /// Adds the RPU NALU of a decoded frame, before conversion.
+    /// RPUs that can't be parsed are left out, as frames without RPU
+    pub fn add_rpu(&mut self, decoded_index: u64, data: &[u8]) {
+        if let Ok(rpu) = self.codec.parse_rpu_nalu(data) {
+            self.rpus.insert(decoded_index, FrameRpu::new(&rpu));
+        }
+    }
+
+    /// Adds a NAL unit of the EL of a decoded frame, with its own NAL header
+    pub fn add_nal(&mut self, decoded_index: u64, data: &[u8]) -> Result<()> {
+        self.el.add_nal(decoded_index, data)
+    }
+
+    /// Writes the report, with the frames in presentation order, and prints a summary of the lossy scenes
+    pub fn write(&self, frames: &[Frame]) -> Result<()> {
+        let mut frames: Vec<&Frame> = frames.iter().collect();
+        frames.sort_by_key(|frame| frame.presentation_number);
+
+        let report = self.report(&frames);
+
+        let mut writer = BufWriter::new(super::create_output_file(&self.path)?);
+        serde_json::to_writer_pretty(&mut writer, &report)?;
+        writer.flush()?;
+
+        let mut out = status_out();
+
+        if report.lossy_scenes == 0 {
+            writeln!(
+                out,
+                "EL contribution: no residual data in the EL of the FEL frames, the conversion is lossless"
+            )?;
+
+            return Ok(());
+        }
+
+        writeln!(
+            out,
+            "EL contribution: {} of {} scenes are lossy, with EL residual data in {} of {} frames",
+            report.lossy_scenes,
+            report.scenes.len(),
+            report.residual_frames,
+            report.frames
+        )?;
+
+        let mut lossy: Vec<&SceneContribution> = report
+            .scenes
+            .iter()
+            .filter(|scene| !scene.lossless)
+            .collect();
+        lossy.sort_by(|a, b| {
+            b.el_bits_per_pixel
+                .partial_cmp(&a.el_bits_per_pixel)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        for scene in lossy.iter().take(MAX_LISTED_SCENES) {
+            writeln!(
+                out,
+                "  Scene {} (frames {}-{}): {} residual frames, {:.4} EL bits per pixel",
+                scene.scene,
+                scene.start_frame,
+                scene.start_frame + scene.frames - 1,
+                scene.residual_frames,
+                scene.el_bits_per_pixel.unwrap_or(0.0)
+            )?;
+        }
+
+        if lossy.len() > MAX_LISTED_SCENES {
+            writeln!(out, "  ...")?;
+        }
+
+        Ok(())
+    }
+
+    fn report(&self, frames: &[&Frame]) -> Report {
+        let pictures: HashMap<u64, _> = self
+            .el
+            .pictures()
+            .iter()
+            .map(|picture| (picture.decoded_index, picture))
+            .collect();
+        let pixels = self.el.pixels();
+
+        let mut scenes: Vec<SceneContribution> = Vec::new();
+
+        for (frame, decoded) in frames.iter().enumerate() {
+            let rpu = self.rpus.get(&decoded.decoded_number);
+            let scene_cut = rpu.is_some_and(|rpu| rpu.scene_cut);
+
+            if scene_cut || scenes.is_empty() {
+                scenes.push(SceneContribution {
+                    scene: scenes.len(),
+                    start_frame: frame,
+                    frames: 0,
+                    fel_frames: 0,
+                    residual_frames: 0,
+                    el_bits_per_pixel: None,
+                    nlq_offset: None,
+                    vdr_in_max: None,
+                    lossless: true,
+                    el_bits: 0.0,
+                    el_pictures: 0,
+                });
+            }
+
+            let scene = scenes.last_mut().unwrap();
+            scene.frames += 1;
+
+            let picture = pictures.get(&decoded.decoded_number);
+
+            if let Some(picture) = picture {
+                scene.el_bits += picture.size as f64 * 8.0;
+                scene.el_pictures += 1;
+            }
+
+            let nlq = match rpu.and_then(|rpu| rpu.nlq.as_ref()) {
+                Some(nlq) => nlq,
+                None => continue,
+            };
+
+            scene.fel_frames += 1;
+
+            if picture.is_some_and(|picture| self.el.has_residual(picture)) {
+                scene.residual_frames += 1;
+            }
+
+            scene.add_nlq_ranges(nlq);
+        }
+
+        let mut report = Report {
+            frames: frames.len(),
+            fel_frames: 0,
+            residual_frames: 0,
+            el_bits_per_pixel: None,
+            lossy_scenes: 0,
+            scenes,
+        };
+
+        let mut el_bits = 0.0;
+        let mut el_pictures = 0;
+
+        for scene in &mut report.scenes {
+            scene.lossless = scene.residual_frames == 0;
+            scene.el_bits_per_pixel = bits_per_pixel(scene.el_bits, scene.el_pictures, pixels);
+
+            report.fel_frames += scene.fel_frames;
+            report.residual_frames += scene.residual_frames;
+
+            if !scene.lossless {
+                report.lossy_scenes += 1;
+            }
+
+            el_bits += scene.el_bits;
+            el_pictures += scene.el_pictures;
+        }
+
+        report.el_bits_per_pixel = bits_per_pixel(el_bits, el_pictures, pixels);
+
+        report
+    }
+}
+
+impl FrameRpu {
+    fn new(rpu: &DoviRpu) -> Self {
+        let scene_cut = rpu
+            .vdr_dm_data
+            .as_ref()
+            .is_some_and(|dm| dm.scene_refresh_flag == 1);
+
+        let nlq = rpu
+            .rpu_data_nlq
+            .as_ref()
+            .filter(|nlq| !nlq.is_mel())
+            .and_then(|nlq| {
+                let offsets = nlq.nlq_offset.first()?;
+                let int_parts = nlq.vdr_in_max_int.first()?;
+                let fractions = nlq.vdr_in_max.first()?;
+
+                let denom_bits = if rpu.header.coefficient_data_type == 0 {
+                    rpu.header.coefficient_log2_denom
+                } else {
+                    32
+                };
+                let denom = (1_u64 << denom_bits) as f64;
+
+                let mut vdr_in_max = [0.0; 3];
+
+                for (cmp, value) in vdr_in_max.iter_mut().enumerate() {
+                    *value = int_parts[cmp] as f64 + fractions[cmp] as f64 / denom;
+                }
+
+                Some(FrameNlq {
+                    nlq_offset: *offsets,
+                    vdr_in_max,
+                })
+            });
+
+        Self { scene_cut, nlq }
+    }
+}
+
+impl SceneContribution {
+    fn add_nlq_ranges(&mut self, nlq: &FrameNlq) {
+        let nlq_offset = self.nlq_offset.get_or_insert([[u64::MAX, 0]; 3]);
+        let vdr_in_max = self.vdr_in_max.get_or_insert([0.0; 3]);
+
+        for cmp in 0..3 {
+            nlq_offset[cmp][0] = nlq_offset[cmp][0].min(nlq.nlq_offset[cmp]);
+            nlq_offset[cmp][1] = nlq_offset[cmp][1].max(nlq.nlq_offset[cmp]);
+            vdr_in_max[cmp] = vdr_in_max[cmp].max(nlq.vdr_in_max[cmp]);
+        }
+    }
+}
+
+fn bits_per_pixel(bits: f64, pictures: usize, pixels: Option<f64>) -> Option<f64> {
+    match pixels {
+        Some(pixels) if pictures > 0 => {
+            Some((bits / pictures as f64 / pixels * 10_000.0).round() / 10_000.0)
+        }
+        _ => None,
+    }
+}
@@ -1,43 +1,124 @@
-use std::fs::File;
 use std::io::{stdout, BufWriter, Write};
 use std::path::PathBuf;
 
-use anyhow::Result;
-use serde::ser::SerializeSeq;
-use serde::Serializer;
+use anyhow::{bail, ensure, Result};
+use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlock;
+use dolby_vision::utils::{pq12_to_pq, pq_to_nits};
+use dolby_vision::xml::{CmXmlWriter, XmlWriterOpts};
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Serialize, Serializer};
+
+use hdr10plus::metadata::Hdr10PlusMetadata;
+use hdr10plus::metadata_json::generate_json;
 
 use utilities_dovi::parse_rpu_file;
 
 use crate::commands::ExportArgs;
 use crate::dovi::input_from_either;
 
+use super::hdr10plus_metadata::{hdr10plus_from_rpus, Hdr10PlusProfile};
+use super::overlay_subtitles::{write_overlay, SubtitleFormat};
+use super::timecode::parse_fps;
 use super::DoviRpu;
 
 pub struct Exporter {
     input: PathBuf,
     output: PathBuf,
+    format: ExportFormat,
+    xml_opts: XmlWriterOpts,
+    hdr10plus_profile: Hdr10PlusProfile,
+    fps: Option<f64>,
     rpus: Option<Vec<DoviRpu>>,
 }
 
+#[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Json,
+    Xml,
+    Csv,
+    /// Compact JSON of the L1 and L2 dynamic metadata, keyed by frame
+    DmJson,
+    /// HDR10+ JSON metadata derived from the L1 and L2 metadata, as written by hdr10plus_tool
+    Hdr10plus,
+    /// SRT subtitles overlaying the L1, L2 and L5 metadata of every frame
+    Srt,
+    /// Same as SRT, as ASS subtitles with a boxed monospace style
+    Ass,
+}
+
+/// Dynamic metadata of a frame, for the DM JSON export
+#[derive(Serialize)]
+struct FrameDm {
+    scene_cut: bool,
+
+    /// min, avg, max PQ
+    #[serde(skip_serializing_if = "Option::is_none")]
+    l1: Option<[u16; 3]>,
+
+    /// Trims keyed by target max PQ:
+    /// slope, offset, power, chroma_weight, saturation_gain, ms_weight
+    #[serde(skip_serializing_if = "Vec::is_empty", serialize_with = "serialize_l2")]
+    l2: Vec<(u16, [i32; 6])>,
+}
+
+fn serialize_l2<S: Serializer>(l2: &[(u16, [i32; 6])], s: S) -> Result<S::Ok, S::Error> {
+    let mut map = s.serialize_map(Some(l2.len()))?;
+
+    for (target, trims) in l2 {
+        map.serialize_entry(&target.to_string(), trims)?;
+    }
+
+    SerializeMap::end(map)
+}
+
 impl Exporter {
     pub fn export(args: ExportArgs) -> Result<()> {
         let ExportArgs {
             input,
             input_pos,
             output,
+            format,
+            canvas_width,
+            canvas_height,
+            hdr10plus_profile,
+            fps,
         } = args;
 
         let input = input_from_either("editor", input, input_pos)?;
 
+        let fps = match (format, fps) {
+            (ExportFormat::Srt | ExportFormat::Ass, Some(fps)) => Some(parse_fps(&fps)?),
+            (ExportFormat::Srt | ExportFormat::Ass, None) => {
+                bail!("export: --fps is required for the srt and ass formats")
+            }
+            (_, Some(_)) => bail!("export: --fps is only used by the srt and ass formats"),
+            (_, None) => None,
+        };
+
         let out_path = if let Some(out_path) = output {
             out_path
         } else {
-            PathBuf::from("RPU_export.json".to_string())
+            match format {
+                ExportFormat::Json => PathBuf::from("RPU_export.json".to_string()),
+                ExportFormat::Xml => PathBuf::from("RPU_export.xml".to_string()),
+                ExportFormat::Csv => PathBuf::from("RPU_export.csv".to_string()),
+                ExportFormat::DmJson => PathBuf::from("RPU_export_dm.json".to_string()),
+                ExportFormat::Hdr10plus => PathBuf::from("RPU_export_hdr10plus.json".to_string()),
+                ExportFormat::Srt => PathBuf::from("RPU_export.srt".to_string()),
+                ExportFormat::Ass => PathBuf::from("RPU_export.ass".to_string()),
+            }
         };
 
         let mut exporter = Exporter {
             input,
             output: out_path,
+            format,
+            xml_opts: XmlWriterOpts {
+                canvas_width,
+                canvas_height,
+            },
+            hdr10plus_profile,
+            fps,
             rpus: None,
         };
 
@@ -52,14 +133,24 @@ impl Exporter {
         Ok(())
     }
 
-    fn execute(&self) -> Result<()> {
+    fn execute(self) -> Result<()> {
         println!("Exporting metadata...");
 
+        match self.format {
+            ExportFormat::Json => self.export_json(),
+            ExportFormat::Xml => self.export_xml(),
+            ExportFormat::Csv => self.export_csv(),
+            ExportFormat::DmJson => self.export_dm_json(),
+            ExportFormat::Hdr10plus => self.export_hdr10plus(),
+            ExportFormat::Srt => self.export_subtitles(SubtitleFormat::Srt),
+            ExportFormat::Ass => self.export_subtitles(SubtitleFormat::Ass),
+        }
+    }
+
+    fn export_json(&self) -> Result<()> {
         if let Some(rpus) = &self.rpus {
-            let writer = BufWriter::with_capacity(
-                100_000,
-                File::create(&self.output).expect("Can't create file"),
-            );
+            let writer =
+                BufWriter::with_capacity(100_000, super::create_output_file(&self.output)?);
 
             let mut ser = serde_json::Serializer::new(writer);
             let mut seq = ser.serialize_seq(Some(rpus.len()))?;
@@ -67,9 +158,243 @@ impl Exporter {
             for rpu in rpus {
                 seq.serialize_element(&rpu)?;
             }
-            seq.end()?;
+            SerializeSeq::end(seq)?;
+        }
+
+        Ok(())
+    }
+
+    /// Only the L1, L2 and scene cut metadata, as an object keyed by frame.
+    /// Frames without DM data are omitted.
+    fn export_dm_json(&self) -> Result<()> {
+        if let Some(rpus) = &self.rpus {
+            let mut writer =
+                BufWriter::with_capacity(100_000, super::create_output_file(&self.output)?);
+
+            let mut ser = serde_json::Serializer::new(&mut writer);
+            let mut map = ser.serialize_map(None)?;
+
+            for (frame, rpu) in rpus.iter().enumerate() {
+                let vdr_dm_data = match &rpu.vdr_dm_data {
+                    Some(vdr_dm_data) => vdr_dm_data,
+                    None => continue,
+                };
+
+                let l1 = match vdr_dm_data.get_block(1) {
+                    Some(ExtMetadataBlock::Level1(l1)) => Some([l1.min_pq, l1.avg_pq, l1.max_pq]),
+                    _ => None,
+                };
+
+                let l2 = vdr_dm_data
+                    .level_blocks_iter(2)
+                    .filter_map(|block| match block {
+                        ExtMetadataBlock::Level2(l2) => Some((
+                            l2.target_max_pq,
+                            [
+                                l2.trim_slope as i32,
+                                l2.trim_offset as i32,
+                                l2.trim_power as i32,
+                                l2.trim_chroma_weight as i32,
+                                l2.trim_saturation_gain as i32,
+                                l2.ms_weight as i32,
+                            ],
+                        )),
+                        _ => None,
+                    })
+                    .collect();
+
+                let frame_dm = FrameDm {
+                    scene_cut: vdr_dm_data.scene_refresh_flag == 1,
+                    l1,
+                    l2,
+                };
+
+                map.serialize_entry(&frame.to_string(), &frame_dm)?;
+            }
+
+            SerializeMap::end(map)?;
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Per scene HDR10+ statistics and tone mapping curve, one metadata per frame
+    fn export_hdr10plus(&self) -> Result<()> {
+        if let Some(rpus) = &self.rpus {
+            ensure!(!rpus.is_empty(), "No RPUs to export to HDR10+ metadata");
+
+            let metadata = hdr10plus_from_rpus(rpus, self.hdr10plus_profile)?;
+
+            let list: Vec<&Hdr10PlusMetadata> = metadata.iter().collect();
+            let json = generate_json(&list, env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+
+            let writer =
+                BufWriter::with_capacity(100_000, super::create_output_file(&self.output)?);
+            serde_json::to_writer_pretty(writer, &json)?;
+        }
+
+        Ok(())
+    }
+
+    /// Timed metadata overlay, for QC in any player
+    fn export_subtitles(&self, format: SubtitleFormat) -> Result<()> {
+        if let (Some(rpus), Some(fps)) = (&self.rpus, self.fps) {
+            let mut writer =
+                BufWriter::with_capacity(100_000, super::create_output_file(&self.output)?);
+
+            write_overlay(rpus, fps, format, &mut writer)?;
+        }
+
+        Ok(())
+    }
+
+    fn export_xml(self) -> Result<()> {
+        if let Some(rpus) = &self.rpus {
+            let writer = CmXmlWriter::new(rpus, self.xml_opts)?;
+            writer.write_file(&self.output)?;
+        }
+
+        Ok(())
+    }
+
+    /// One row per frame, with the L1 values in nits, the L3 offsets when present,
+    /// the L2 trims per target and the L5 offsets
+    fn export_csv(&self) -> Result<()> {
+        if let Some(rpus) = &self.rpus {
+            let mut writer =
+                BufWriter::with_capacity(100_000, super::create_output_file(&self.output)?);
+
+            // Every L2 target present in the file gets its own columns
+            let mut l2_targets: Vec<u16> = rpus
+                .iter()
+                .filter_map(|rpu| rpu.vdr_dm_data.as_ref())
+                .flat_map(|vdr_dm_data| vdr_dm_data.level_blocks_iter(2))
+                .filter_map(|block| match block {
+                    ExtMetadataBlock::Level2(l2) => Some(l2.target_max_pq),
+                    _ => None,
+                })
+                .collect();
+            l2_targets.sort_unstable();
+            l2_targets.dedup();
+
+            let has_l3 = rpus
+                .iter()
+                .filter_map(|rpu| rpu.vdr_dm_data.as_ref())
+                .any(|vdr_dm_data| vdr_dm_data.get_block(3).is_some());
+
+            let mut header = vec![
+                String::from("frame"),
+                String::from("scene_cut"),
+                String::from("l1_min_nits"),
+                String::from("l1_avg_nits"),
+                String::from("l1_max_nits"),
+            ];
+
+            if has_l3 {
+                header.extend(
+                    ["l3_min_offset", "l3_avg_offset", "l3_max_offset"]
+                        .iter()
+                        .map(|s| s.to_string()),
+                );
+            }
+
+            // Named by target max PQ, to keep the targets distinct
+            for target in &l2_targets {
+                header.extend(
+                    L2_TRIM_FIELDS
+                        .iter()
+                        .map(|field| format!("l2_{}_{}", target, field)),
+                );
+            }
+
+            header.extend(
+                ["l5_left", "l5_right", "l5_top", "l5_bottom"]
+                    .iter()
+                    .map(|s| s.to_string()),
+            );
+
+            writeln!(writer, "{}", header.join(","))?;
+
+            for (frame, rpu) in rpus.iter().enumerate() {
+                let mut row = vec![frame.to_string()];
+
+                if let Some(vdr_dm_data) = &rpu.vdr_dm_data {
+                    row.push(vdr_dm_data.scene_refresh_flag.to_string());
+
+                    match vdr_dm_data.get_block(1) {
+                        Some(ExtMetadataBlock::Level1(l1)) => row.extend(
+                            [l1.min_pq, l1.avg_pq, l1.max_pq]
+                                .iter()
+                                .map(|pq| format!("{:.4}", pq_to_nits(pq12_to_pq(*pq)))),
+                        ),
+                        _ => row.extend(vec![String::new(); 3]),
+                    }
+
+                    if has_l3 {
+                        match vdr_dm_data.get_block(3) {
+                            Some(ExtMetadataBlock::Level3(l3)) => row.extend(
+                                [l3.min_pq_offset, l3.avg_pq_offset, l3.max_pq_offset]
+                                    .iter()
+                                    .map(|v| v.to_string()),
+                            ),
+                            _ => row.extend(vec![String::new(); 3]),
+                        }
+                    }
+
+                    for target in &l2_targets {
+                        let l2 = vdr_dm_data
+                            .level_blocks_iter(2)
+                            .find_map(|block| match block {
+                                ExtMetadataBlock::Level2(l2) if l2.target_max_pq == *target => {
+                                    Some(l2)
+                                }
+                                _ => None,
+                            });
+
+                        match l2 {
+                            Some(l2) => row.extend(
+                                [
+                                    l2.trim_slope as i32,
+                                    l2.trim_offset as i32,
+                                    l2.trim_power as i32,
+                                    l2.trim_chroma_weight as i32,
+                                    l2.trim_saturation_gain as i32,
+                                    l2.ms_weight as i32,
+                                ]
+                                .iter()
+                                .map(|v| v.to_string()),
+                            ),
+                            None => row.extend(vec![String::new(); L2_TRIM_FIELDS.len()]),
+                        }
+                    }
+
+                    match vdr_dm_data.get_block(5) {
+                        Some(ExtMetadataBlock::Level5(l5)) => {
+                            row.extend(l5.get_offsets_vec().iter().map(|v| v.to_string()))
+                        }
+                        _ => row.extend(vec![String::new(); 4]),
+                    }
+                } else {
+                    // No DM data, empty values
+                    row.resize(header.len(), String::new());
+                }
+
+                writeln!(writer, "{}", row.join(","))?;
+            }
+
+            writer.flush()?;
         }
 
         Ok(())
     }
 }
+
+const L2_TRIM_FIELDS: &[&str] = &[
+    "slope",
+    "offset",
+    "power",
+    "chroma_weight",
+    "saturation_gain",
+    "ms_weight",
+];
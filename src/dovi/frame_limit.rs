@@ -0,0 +1,119 @@
+use std::io::{self, Cursor, Read};
+
+use super::codec::Codec;
+use super::start_code::get_offsets;
+
+const NAL_START_CODE_3_LEN: usize = 3;
+
+/// Ends the stream after `limit` decoded frames, before the start of the next access unit.
+///
+/// The frames are counted from the NAL headers only, without parsing the slices.
+pub struct FrameLimitReader {
+    inner: Box<dyn Read>,
+    codec: Codec,
+    limit: u64,
+    chunk_size: usize,
+
+    frames: u64,
+    finished: bool,
+
+    /// Data read from the input, not yet scanned
+    chunk: Vec<u8>,
+    offsets: Vec<usize>,
+
+    /// Scanned data, to be returned
+    ready: Cursor<Vec<u8>>,
+}
+
+impl FrameLimitReader {
+    pub fn new(inner: Box<dyn Read>, codec: Codec, limit: u64, chunk_size: usize) -> Self {
+        Self {
+            inner,
+            codec,
+            limit,
+            chunk_size,
+            frames: 0,
+            finished: false,
+            chunk: Vec::new(),
+            offsets: Vec::with_capacity(2048),
+            ready: Cursor::new(Vec::new()),
+        }
+    }
+
+    /// Reads and scans the next chunk of the input
+    fn fill(&mut self) -> io::Result<()> {
+        let header_len = self.codec.nal_header_len();
+
+        let mut buf = vec![0; self.chunk_size];
+        let read_bytes = self.inner.read(&mut buf)?;
+
+        if read_bytes == 0 {
+            self.ready = Cursor::new(std::mem::take(&mut self.chunk));
+            self.finished = true;
+
+            return Ok(());
+        }
+
+        self.chunk.extend_from_slice(&buf[..read_bytes]);
+        get_offsets(&self.chunk, &mut self.offsets);
+
+        // Offset of the first NAL unit with an incomplete header
+        let mut incomplete_offset = None;
+
+        for &offset in &self.offsets {
+            let nal_start = offset + NAL_START_CODE_3_LEN;
+
+            // NAL header and first payload byte
+            if nal_start + header_len >= self.chunk.len() {
+                incomplete_offset = Some(offset);
+                break;
+            }
+
+            let data = &self.chunk[nal_start..];
+            let nal_type = self.codec.nal_type(data);
+            let starts_picture = self.codec.starts_picture(nal_type, data);
+
+            if self.frames >= self.limit && self.codec.starts_access_unit(nal_type, data) {
+                // Also drop the zero_byte of 4 bytes start codes
+                let end = if offset > 0 && self.chunk[offset - 1] == 0 {
+                    offset - 1
+                } else {
+                    offset
+                };
+
+                self.chunk.truncate(end);
+                self.ready = Cursor::new(std::mem::take(&mut self.chunk));
+                self.finished = true;
+
+                return Ok(());
+            }
+
+            if starts_picture {
+                self.frames += 1;
+            }
+        }
+
+        // Keep the NAL unit not yet handled, and a possible start code split between chunks
+        let keep_offset = incomplete_offset
+            .unwrap_or_else(|| self.chunk.len().saturating_sub(NAL_START_CODE_3_LEN))
+            .saturating_sub(1);
+
+        self.ready = Cursor::new(self.chunk.drain(..keep_offset).collect());
+
+        Ok(())
+    }
+}
+
+impl Read for FrameLimitReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let read_bytes = self.ready.read(buf)?;
+
+            if read_bytes > 0 || self.finished || buf.is_empty() {
+                return Ok(read_bytes);
+            }
+
+            self.fill()?;
+        }
+    }
+}
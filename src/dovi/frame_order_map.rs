@@ -0,0 +1,81 @@
+use std::cmp::Ordering;
+use std::fs::read_to_string;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Result};
+
+/// Decode to presentation order of the frames, imported from an external parser.
+///
+/// The file has one value per frame, in decoding order: the presentation index or the
+/// timestamp of the frame, e.g. the packet PTS listed by
+/// `ffprobe -select_streams v:0 -show_entries packet=pts -of csv=p=0`.
+/// The frames are presented in increasing value order.
+#[derive(Debug, Clone, Default)]
+pub struct FrameOrderMap {
+    /// Presentation number of every frame, by decoded index
+    presentation_numbers: Vec<usize>,
+}
+
+impl FrameOrderMap {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        Self::parse(&read_to_string(path)?)
+    }
+
+    pub fn parse(content: &str) -> Result<Self> {
+        let values = content
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(i, line)| {
+                // ffprobe CSV lines can have trailing fields
+                let value = line.split(',').next().unwrap_or_default().trim();
+
+                value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|v| v.is_finite())
+                    .ok_or_else(|| anyhow!("Invalid frame order, line {}: {}", i + 1, line))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if values.is_empty() {
+            bail!("Invalid frame order: no frames");
+        }
+
+        let mut decoded_indices: Vec<usize> = (0..values.len()).collect();
+        // The values are finite
+        decoded_indices.sort_by(|a, b| {
+            values[*a]
+                .partial_cmp(&values[*b])
+                .unwrap_or(Ordering::Equal)
+        });
+
+        let mut presentation_numbers = vec![0; values.len()];
+
+        for (presentation_number, pair) in decoded_indices.windows(2).enumerate() {
+            if values[pair[0]] == values[pair[1]] {
+                bail!(
+                    "Invalid frame order: decoded frames {} and {} have the same value {}",
+                    pair[0].min(pair[1]),
+                    pair[0].max(pair[1]),
+                    values[pair[0]]
+                );
+            }
+
+            presentation_numbers[pair[1]] = presentation_number + 1;
+        }
+
+        Ok(Self {
+            presentation_numbers,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.presentation_numbers.len()
+    }
+
+    pub fn presentation_number(&self, decoded_index: usize) -> Option<usize> {
+        self.presentation_numbers.get(decoded_index).copied()
+    }
+}
@@ -1,41 +1,148 @@
-use std::io::{stdout, BufRead, BufReader, BufWriter, Write};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::{BufWriter, Read, Write};
+use std::ops::Range;
+use std::path::Path;
 use std::path::PathBuf;
-use std::{fs::File, path::Path};
+use std::time::{Duration, Instant};
 
-use anyhow::{bail, Result};
-use indicatif::ProgressBar;
+use anyhow::{anyhow, bail, Error, Result};
+use indicatif::{ProgressBar, ProgressDrawTarget};
+use log::{debug, trace, warn};
+use rayon::prelude::*;
 
-use hevc_parser::hevc::{NALUnit, NAL_SEI_PREFIX, NAL_UNSPEC62, NAL_UNSPEC63};
+use hevc_parser::hevc::{Frame, NALUnit, NAL_BLA_W_LP, NAL_IRAP_VCL23, NAL_SEI_PREFIX};
 use hevc_parser::io::{processor, IoFormat, IoProcessor};
 use hevc_parser::HevcParser;
-use processor::{HevcProcessor, HevcProcessorOpts};
+use processor::HevcProcessorOpts;
 
-use super::{convert_encoded_from_opts, is_st2094_40_sei, CliOptions, WriteStartCodePreset};
+use hdr10plus::metadata::Hdr10PlusMetadata;
+use hdr10plus::metadata_json::generate_json;
+
+use super::au_alignment::{AuAlignment, AuNal};
+use super::avc_parser::AvcParser;
+use super::change_summary::ChangeSummary;
+use super::codec::Codec;
+use super::el_report::ElReport;
+use super::frame_limit::FrameLimitReader;
+use super::hevc_processor::HevcProcessor;
+use super::input_skip::skip_to_random_access;
+use super::interrupt::{is_interrupted, set_interrupt_handler, InterruptibleReader};
+use super::lenient::{duplicated_rpu_data, LenientHevcProcessor, LenientParser};
+use super::nal_parser::{FrameOrder, NalIoProcessor, NalProcessor};
+use super::parameter_sets::ParameterSetCheck;
+use super::profile_mix::{ProfileMix, ProfileVariant};
+use super::progress::{write_json_event, JsonProgress};
+use super::rpu_stream::{status_out, RpuWriter};
+use super::run_stats::RunStats;
+use super::strict::StrictChecker;
+use super::threaded_writer::ThreadedWriter;
+use super::vvc_parser::VvcParser;
+use super::{
+    convert_encoded_from_opts, create_output_file, is_st2094_40_sei, open_hevc_input_at,
+    parse_st2094_40_sei, CliOptions, CrcMode, DoviRpu, ProgressFormat, WriteStartCodePreset,
+};
+
+// Number of RPUs converted in parallel, when writing the video
+const RPU_BATCH_SIZE: usize = 64;
+
+/// HEVC UNSPEC62 NAL header, the extracted RPUs are always stored as HEVC NALUs
+const HEVC_RPU_HEADER: &[u8] = &[0x7C, 0x01];
 
 pub struct DoviProcessor {
     input: PathBuf,
+    // Bytes of the input to process, for a segment demuxed in parallel
+    byte_range: Option<Range<u64>>,
     options: CliOptions,
+    codec: Codec,
     rpu_nals: Vec<RpuNal>,
+    hdr10plus_seis: Vec<Hdr10PlusSei>,
 
     payload_count: usize,
     previous_frame_index: u64,
     previous_rpu_index: u64,
+    // Number of presentation ordered frames already written, when streaming
+    streamed_frames: usize,
+
+    // NALUs written after the RPUs waiting to be converted
+    pending_nals: Vec<PendingNal>,
+    pending_rpus: usize,
+
+    // HDR10 SEI NALU inserted at every IRAP frame, once derived from the first RPU
+    hdr10_sei_nal: Option<Vec<u8>>,
+    hdr10_sei_frame: Option<u64>,
+
+    // Single layer NALUs of the access unit before its first slice, held while the
+    // Dolby NALUs of the previous frame can still follow, such as RPUs attached to the EL
+    held_nals: Option<Vec<PendingNal>>,
+    moved_rpus: usize,
+
+    // BL/EL access unit alignment, when demuxing
+    alignment: Option<AuAlignment>,
+
+    // Changes of the converted RPUs, for a dry run without output
+    change_summary: Option<ChangeSummary>,
+
+    // BL parameter sets of the single layer output, when discarding the EL
+    parameter_sets: Option<ParameterSetCheck>,
+
+    // EL contribution per scene, for a mode 2 conversion
+    el_report: Option<ElReport>,
+
+    // RPUs found in the input, for the JSON progress
+    rpus_found: usize,
+    json_progress: Option<JsonProgress>,
+    lenient: Option<LenientParser>,
+    strict: Option<StrictChecker>,
+    stats: RunStats,
 
     progress_bar: ProgressBar,
     dovi_writer: DoviWriter,
 }
 
 pub struct DoviWriter {
-    bl_writer: Option<BufWriter<File>>,
-    el_writer: Option<BufWriter<File>>,
-    rpu_writer: Option<BufWriter<File>>,
-    sl_writer: Option<BufWriter<File>>,
+    bl_writer: Option<ThreadedWriter>,
+    el_writer: Option<ThreadedWriter>,
+    rpu_writer: Option<RpuWriter>,
+    sl_writer: Option<ThreadedWriter>,
+    hdr10plus_out: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct Hdr10PlusSei {
+    decoded_index: usize,
+    metadata: Hdr10PlusMetadata,
+}
+
+#[derive(Debug)]
+enum RpuCrcError {
+    Mismatch,
+    Invalid(String),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum NalTarget {
+    El,
+    SingleLayer,
+}
+
+#[derive(Debug)]
+struct PendingNal {
+    target: NalTarget,
+    data: Vec<u8>,
+    nal_type: u8,
+    start_code: WriteStartCodePreset,
+    first_nal: bool,
+    convert_rpu: bool,
+    // Placeholder for the HDR10 SEI NALU, not yet derived
+    hdr10_sei: bool,
 }
 
 #[derive(Debug)]
 pub struct RpuNal {
     decoded_index: usize,
     presentation_number: usize,
+    /// HEVC UNSPEC62 NALU, including the 0x7C01 header
     data: Vec<u8>,
 }
 
@@ -45,43 +152,58 @@ impl DoviWriter {
         el_out: Option<&Path>,
         rpu_out: Option<&Path>,
         single_layer_out: Option<&Path>,
-    ) -> DoviWriter {
-        let chunk_size = 100_000;
-        let bl_writer = bl_out.map(|bl_out| {
-            BufWriter::with_capacity(
-                chunk_size,
-                File::create(bl_out).expect("Can't create file for BL"),
-            )
-        });
-
-        let el_writer = el_out.map(|el_out| {
-            BufWriter::with_capacity(
-                chunk_size,
-                File::create(el_out).expect("Can't create file for EL"),
-            )
-        });
+        hdr10plus_out: Option<&Path>,
+        write_buffer: usize,
+    ) -> Result<DoviWriter> {
+        let create_writer = |path: Option<&Path>| -> Result<Option<ThreadedWriter>> {
+            path.map(|path| {
+                Ok(ThreadedWriter::with_capacity(
+                    write_buffer,
+                    create_output_file(path)?,
+                )?)
+            })
+            .transpose()
+        };
 
-        let rpu_writer = rpu_out.map(|rpu_out| {
-            BufWriter::with_capacity(
-                chunk_size,
-                File::create(rpu_out).expect("Can't create file for RPU"),
-            )
-        });
+        let bl_writer = create_writer(bl_out)?;
+        let el_writer = create_writer(el_out)?;
+        let rpu_writer = rpu_out
+            .map(|path| RpuWriter::with_capacity(path, write_buffer))
+            .transpose()?;
+        let sl_writer = create_writer(single_layer_out)?;
 
-        let sl_writer = single_layer_out.map(|single_layer_out| {
-            BufWriter::with_capacity(
-                chunk_size,
-                File::create(single_layer_out).expect("Can't create file for SL output"),
-            )
-        });
-
-        DoviWriter {
+        Ok(DoviWriter {
             bl_writer,
             el_writer,
             rpu_writer,
             sl_writer,
+            hdr10plus_out: hdr10plus_out.map(Path::to_path_buf),
+        })
+    }
+
+    /// Replaces the RPU output, for writers not created from a path
+    pub fn with_rpu_writer(mut self, rpu_writer: RpuWriter) -> Self {
+        self.rpu_writer = Some(rpu_writer);
+        self
+    }
+}
+
+impl DoviWriter {
+    fn target_writer(&mut self, target: NalTarget) -> Option<&mut ThreadedWriter> {
+        match target {
+            NalTarget::El => self.el_writer.as_mut(),
+            NalTarget::SingleLayer => self.sl_writer.as_mut(),
         }
     }
+
+    /// Time spent waiting for the video writers
+    fn wait_time(&self) -> Duration {
+        [&self.bl_writer, &self.el_writer, &self.sl_writer]
+            .iter()
+            .filter_map(|writer| writer.as_ref())
+            .map(ThreadedWriter::wait_time)
+            .sum()
+    }
 }
 
 impl DoviProcessor {
@@ -91,119 +213,392 @@ impl DoviProcessor {
         dovi_writer: DoviWriter,
         progress_bar: ProgressBar,
     ) -> DoviProcessor {
+        let codec = Codec::from_input(options.input_codec, &input);
+        debug!("Input codec: {:?}", codec);
+
+        let json_progress = if options.progress == ProgressFormat::Json {
+            progress_bar.set_draw_target(ProgressDrawTarget::hidden());
+            Some(JsonProgress::default())
+        } else {
+            None
+        };
+
+        let lenient = options.lenient.then(|| LenientParser::new(codec));
+        let strict = options.strict.then(|| StrictChecker::new(codec));
+
+        let alignment = (dovi_writer.el_writer.is_some() && dovi_writer.sl_writer.is_none())
+            .then(AuAlignment::default);
+
+        let parameter_sets =
+            (options.discard_el && codec == Codec::Hevc && dovi_writer.sl_writer.is_some())
+                .then(|| ParameterSetCheck::new(options.rewrite_vps));
+
+        let el_report = options
+            .el_report
+            .as_deref()
+            .map(|path| ElReport::new(path, codec));
+
+        let hdr10_sei_nal = options
+            .hdr10_sei
+            .as_ref()
+            .and_then(|hdr10_sei| hdr10_sei.static_sei_nal());
+
         DoviProcessor {
             input,
+            byte_range: None,
             options,
+            codec,
             rpu_nals: Vec::new(),
+            hdr10plus_seis: Vec::new(),
             payload_count: 0,
             previous_frame_index: 0,
             previous_rpu_index: 0,
+            streamed_frames: 0,
+            pending_nals: Vec::new(),
+            pending_rpus: 0,
+            hdr10_sei_nal,
+            hdr10_sei_frame: None,
+            held_nals: None,
+            moved_rpus: 0,
+            alignment,
+            change_summary: None,
+            parameter_sets,
+            el_report,
+            rpus_found: 0,
+            lenient,
+            strict,
+            stats: RunStats::new(),
+            json_progress,
             progress_bar,
             dovi_writer,
         }
     }
 
+    /// Converts the RPUs without writing them, printing a summary of the changes
+    pub fn with_change_summary(mut self) -> Self {
+        self.change_summary = Some(ChangeSummary::default());
+        self
+    }
+
+    /// Only processes the bytes of the input in the range, starting at an access unit.
+    /// The progress bar is shared with the other segments, and left unfinished.
+    pub fn with_byte_range(mut self, byte_range: Range<u64>) -> Self {
+        self.byte_range = Some(byte_range);
+        self
+    }
+
+    /// BL/EL access unit alignment of the processed input, when demuxing
+    pub fn take_alignment(&mut self) -> Option<AuAlignment> {
+        self.alignment.take()
+    }
+
     pub fn read_write_from_io(&mut self, format: &IoFormat) -> Result<()> {
-        let chunk_size = 100_000;
+        let chunk_size = self.options.read_buffer;
 
-        let processor_opts = HevcProcessorOpts {
-            parse_nals: true,
-            ..Default::default()
+        let skip_bytes = match &self.byte_range {
+            Some(byte_range) => byte_range.start,
+            None => self.options.skip_bytes,
         };
-        let mut processor = HevcProcessor::new(format.clone(), processor_opts, chunk_size);
 
-        let stdin = std::io::stdin();
-        let mut reader = Box::new(stdin.lock()) as Box<dyn BufRead>;
+        let mut reader = open_hevc_input_at(
+            format,
+            &self.input,
+            self.options.input_format,
+            &self.progress_bar,
+            skip_bytes,
+            chunk_size,
+        )?;
 
-        if let IoFormat::Raw = format {
-            let file = File::open(&self.input)?;
-            reader = Box::new(BufReader::with_capacity(100_000, file));
+        if let Some(byte_range) = &self.byte_range {
+            reader = Box::new(reader.take(byte_range.end - byte_range.start));
         }
 
-        processor.process_io(&mut reader, self)
+        reader = self.stats.timed_reader(reader);
+
+        if self.options.skip_bytes > 0 || self.options.skip_frames > 0 {
+            reader =
+                skip_to_random_access(reader, self.codec, self.options.skip_frames, chunk_size)?;
+        }
+
+        if let Some(limit) = self.options.limit {
+            reader = Box::new(FrameLimitReader::new(reader, self.codec, limit, chunk_size));
+        }
+
+        // On Ctrl-C, the input ends at the next access unit and the outputs are finalized
+        set_interrupt_handler()?;
+        let mut reader = InterruptibleReader::new(reader, self.codec, chunk_size);
+
+        let res = match self.codec {
+            Codec::Hevc if self.options.lenient => {
+                LenientHevcProcessor::new(chunk_size).process_io(&mut reader, self)
+            }
+            Codec::Hevc => {
+                let processor_opts = HevcProcessorOpts {
+                    parse_nals: true,
+                    ..Default::default()
+                };
+                let mut processor = HevcProcessor::new(format.clone(), processor_opts, chunk_size);
+
+                processor.process_io(&mut reader, self)
+            }
+            Codec::Avc => NalProcessor::<AvcParser>::new(chunk_size).process_io(&mut reader, self),
+            Codec::Vvc => NalProcessor::<VvcParser>::new(chunk_size).process_io(&mut reader, self),
+        };
+
+        if res.is_ok() && self.options.stats {
+            self.write_stats();
+        }
+
+        if res.is_ok() && is_interrupted() {
+            bail!("Interrupted, the outputs only contain the frames processed until then");
+        }
+
+        res
     }
 
     pub fn write_nals(&mut self, chunk: &[u8], nals: &[NALUnit]) -> Result<()> {
+        let rpu_nal_type = self.codec.rpu_nal_type();
+        let el_nal_type = self.codec.el_nal_type();
+        let header_len = self.codec.nal_header_len();
+        let convert_rpus = self.options.converts_rpus();
+
         for (i, nal) in nals.iter().enumerate() {
-            if self.options.drop_hdr10plus
+            if self.dovi_writer.hdr10plus_out.is_some()
+                && self.codec == Codec::Hevc
                 && nal.nal_type == NAL_SEI_PREFIX
                 && is_st2094_40_sei(&chunk[nal.start..nal.end])?
             {
-                continue;
+                let decoded_index = nal.decoded_frame_index as usize;
+
+                // A repeated SEI within the same frame is ignored
+                if self.hdr10plus_seis.last().map(|sei| sei.decoded_index) != Some(decoded_index) {
+                    let metadata = parse_st2094_40_sei(&chunk[nal.start..nal.end])?;
+
+                    if let Err(e) = metadata.validate() {
+                        bail!(
+                            "Invalid HDR10+ metadata for decoded frame {}: {}",
+                            decoded_index,
+                            e
+                        );
+                    }
+
+                    self.hdr10plus_seis.push(Hdr10PlusSei {
+                        decoded_index,
+                        metadata,
+                    });
+                }
             }
 
+            let nal_data = match self
+                .options
+                .sei_filter
+                .filter_nal(nal.nal_type, &chunk[nal.start..nal.end])?
+            {
+                Some(data) => data,
+                None => continue,
+            };
+
             // Skip duplicate NALUs if they are after a first RPU for the frame
             if self.previous_rpu_index > 0
-                && nal.nal_type == NAL_UNSPEC62
+                && nal.nal_type == rpu_nal_type
                 && nal.decoded_frame_index == self.previous_rpu_index
             {
-                println!(
-                    "Warning: Unexpected RPU NALU found for frame {}. Discarding.",
+                warn!(
+                    "Unexpected RPU NALU found for frame {}. Discarding.",
                     self.previous_rpu_index
                 );
 
                 continue;
             }
 
+            if nal.nal_type == rpu_nal_type {
+                self.rpus_found += 1;
+
+                if let Some(ref mut strict) = self.strict {
+                    strict.check(nal.decoded_frame_index, &nal_data)?;
+                }
+            }
+
             // First NAL of stream, or frame
-            let first_nal_of_frame =
-                if i == 0 && self.payload_count == 0 && self.previous_frame_index == 0 {
-                    true
-                } else if self.previous_frame_index != nal.decoded_frame_index {
-                    self.previous_frame_index = nal.decoded_frame_index;
+            let stream_start = i == 0 && self.payload_count == 0 && self.previous_frame_index == 0;
+            let first_nal_of_frame = if stream_start {
+                true
+            } else if self.previous_frame_index != nal.decoded_frame_index {
+                self.previous_frame_index = nal.decoded_frame_index;
 
-                    true
-                } else {
-                    false
-                };
+                true
+            } else {
+                false
+            };
 
-            if let Some(ref mut sl_writer) = self.dovi_writer.sl_writer {
-                if nal.nal_type == NAL_UNSPEC63 && self.options.discard_el {
-                    continue;
+            if let Some(el_report) = self.el_report.as_mut() {
+                // EL wrapped in UNSPEC63 NAL units, or carried in its own layer
+                if nal.nal_type == el_nal_type {
+                    el_report.add_nal(nal.decoded_frame_index, &nal_data[2..])?;
+                } else if nal.nuh_layer_id > 0 {
+                    el_report.add_nal(nal.decoded_frame_index, &nal_data)?;
+                } else if nal.nal_type == rpu_nal_type {
+                    el_report.add_rpu(nal.decoded_frame_index, &nal_data);
                 }
+            }
 
-                if nal.nal_type == NAL_UNSPEC62
-                    && (self.options.mode.is_some() || self.options.edit_config.is_some())
+            if self.dovi_writer.sl_writer.is_some() {
+                if (nal.nal_type == el_nal_type || nal.nuh_layer_id > 0) && self.options.discard_el
                 {
-                    let modified_data =
-                        convert_encoded_from_opts(&self.options, &chunk[nal.start..nal.end])?;
+                    continue;
+                }
 
-                    NALUnit::write_with_preset(
-                        sl_writer,
-                        &modified_data,
-                        self.options.start_code.into(),
-                        nal.nal_type,
-                        first_nal_of_frame,
-                    )?;
+                let nal_data = match self.parameter_sets.as_mut() {
+                    Some(parameter_sets) => {
+                        match parameter_sets.check_nal(nal.nal_type, &nal_data)? {
+                            Some(vps) => Cow::Owned(vps),
+                            None => nal_data,
+                        }
+                    }
+                    None => nal_data,
+                };
 
-                    continue;
+                let convert_rpu = nal.nal_type == rpu_nal_type && convert_rpus;
+
+                // Dolby NALUs found before the first slice of an access unit belong to the previous one
+                let dolby_nal = nal.nal_type == rpu_nal_type
+                    || nal.nal_type == el_nal_type
+                    || nal.nuh_layer_id > 0;
+
+                if !self.options.keep_rpu_position && !dolby_nal {
+                    if self.codec.is_slice(nal.nal_type) {
+                        self.write_held_nals()?;
+                    } else if first_nal_of_frame && !stream_start {
+                        self.write_held_nals()?;
+                        self.held_nals = Some(Vec::new());
+                    }
+                }
+
+                // The HDR10 SEI is derived from the RPU of the first frame, stop buffering after it
+                if self.codec.is_slice(nal.nal_type)
+                    && nal.nuh_layer_id == 0
+                    && self.hdr10_sei_nal.is_none()
+                    && self
+                        .hdr10_sei_frame
+                        .is_some_and(|frame| nal.decoded_frame_index > frame)
+                    && self.pending_nals.iter().any(|nal| nal.hdr10_sei)
+                {
+                    self.write_pending_nals()?;
                 }
 
-                NALUnit::write_with_preset(
-                    sl_writer,
-                    &chunk[nal.start..nal.end],
-                    self.options.start_code.into(),
+                if nal.nal_type == rpu_nal_type && self.held_nals.is_some() {
+                    self.moved_rpus += 1;
+                }
+
+                let hold = !dolby_nal && self.held_nals.is_some();
+
+                let first_nal_of_frame = match self.missing_aud(first_nal_of_frame, &nals[i..]) {
+                    Some(aud) => {
+                        self.write_sl_nal(
+                            hold,
+                            Cow::Owned(aud),
+                            self.options.start_code,
+                            self.codec.aud_nal_type(),
+                            true,
+                            false,
+                        )?;
+
+                        false
+                    }
+                    None => first_nal_of_frame,
+                };
+
+                // Before the first slice of IRAP frames
+                let first_nal_of_frame = if self.options.hdr10_sei.is_some()
+                    && nal.nuh_layer_id == 0
+                    && (NAL_BLA_W_LP..=NAL_IRAP_VCL23).contains(&nal.nal_type)
+                    && self.hdr10_sei_frame != Some(nal.decoded_frame_index)
+                {
+                    self.hdr10_sei_frame = Some(nal.decoded_frame_index);
+                    self.write_hdr10_sei(first_nal_of_frame)?;
+
+                    false
+                } else {
+                    first_nal_of_frame
+                };
+
+                self.write_sl_nal(
+                    hold,
+                    nal_data,
+                    self.options.start_code,
                     nal.nal_type,
                     first_nal_of_frame,
+                    convert_rpu,
                 )?;
 
+                // The HDR10 SEI can be derived once the first RPU is found
+                if nal.nal_type == rpu_nal_type
+                    && self.options.hdr10_sei.is_some()
+                    && self.hdr10_sei_nal.is_none()
+                {
+                    self.write_pending_nals()?;
+                }
+
                 continue;
             }
 
+            if let Some(ref mut alignment) = self.alignment {
+                let au_nal = if nal.nuh_layer_id > 0 || nal.nal_type == el_nal_type {
+                    AuNal::El
+                } else if nal.nal_type == rpu_nal_type {
+                    AuNal::Rpu
+                } else if nal.nal_type == self.codec.aud_nal_type() {
+                    AuNal::Aud
+                } else if self.codec.is_slice(nal.nal_type) {
+                    AuNal::BlSlice
+                } else {
+                    AuNal::Other
+                };
+
+                alignment.push(nal.decoded_frame_index, au_nal);
+            }
+
             match nal.nal_type {
-                NAL_UNSPEC63 => {
-                    if let Some(ref mut el_writer) = self.dovi_writer.el_writer {
+                // EL carried in its own layer, instead of UNSPEC63 NAL units
+                _ if nal.nuh_layer_id > 0 => {
+                    if self.dovi_writer.el_writer.is_some() {
+                        let el_data = if self.options.keep_el_prefix {
+                            nal_data
+                        } else {
+                            Cow::Owned(self.codec.base_layer_nal(&nal_data))
+                        };
+
+                        self.write_nal(
+                            NalTarget::El,
+                            el_data,
+                            WriteStartCodePreset::Four,
+                            nal.nal_type,
+                            false,
+                            false,
+                        )?;
+                    }
+                }
+                t if t == el_nal_type => {
+                    if self.dovi_writer.el_writer.is_some() {
+                        // The EL NAL header is removed unless kept untouched
+                        let el_start = if self.options.keep_el_prefix {
+                            nal.start
+                        } else {
+                            nal.start + header_len
+                        };
+
                         // Can't know for EL, always size 4
-                        NALUnit::write_with_preset(
-                            el_writer,
-                            &chunk[nal.start + 2..nal.end],
-                            WriteStartCodePreset::Four.into(),
+                        self.write_nal(
+                            NalTarget::El,
+                            Cow::Borrowed(&chunk[el_start..nal.end]),
+                            WriteStartCodePreset::Four,
                             nal.nal_type,
                             false,
+                            false,
                         )?;
                     }
                 }
-                NAL_UNSPEC62 => {
+                t if t == rpu_nal_type => {
                     self.previous_rpu_index = nal.decoded_frame_index;
                     let rpu_data = &chunk[nal.start..nal.end];
 
@@ -212,52 +607,50 @@ impl DoviProcessor {
                     // Mode 1: to MEL
                     // Mode 2: to 8.1
                     // Mode 3: 5 to 8.1
-                    if self.options.mode.is_some() || self.options.edit_config.is_some() {
-                        let modified_data = convert_encoded_from_opts(&self.options, rpu_data)?;
-
-                        if let Some(ref mut _rpu_writer) = self.dovi_writer.rpu_writer {
-                            // RPU for x265, remove 0x7C01
-                            self.rpu_nals.push(RpuNal {
-                                decoded_index: self.rpu_nals.len(),
-                                presentation_number: 0,
-                                data: modified_data[2..].to_owned(),
-                            });
-                        } else if let Some(ref mut el_writer) = self.dovi_writer.el_writer {
-                            // RPU should never be first NAL
-                            NALUnit::write_with_preset(
-                                el_writer,
-                                &modified_data,
-                                self.options.start_code.into(),
-                                nal.nal_type,
-                                false,
-                            )?;
-                        }
-                    } else if let Some(ref mut _rpu_writer) = self.dovi_writer.rpu_writer {
-                        // RPU for x265, remove 0x7C01
-                        self.rpu_nals.push(RpuNal {
-                            decoded_index: self.rpu_nals.len(),
-                            presentation_number: 0,
-                            data: rpu_data[2..].to_vec(),
-                        });
-                    } else if let Some(ref mut el_writer) = self.dovi_writer.el_writer {
+                    // Mode 4: to 8.4
+                    if self.dovi_writer.rpu_writer.is_some() || self.change_summary.is_some() {
+                        // Stored as HEVC NALU, the header is removed when writing for x265
+                        // The conversion is done for all the RPUs when flushing
+                        let payload = &rpu_data[header_len..];
+                        let mut data = Vec::with_capacity(HEVC_RPU_HEADER.len() + payload.len());
+                        data.extend_from_slice(HEVC_RPU_HEADER);
+                        data.extend_from_slice(payload);
+
+                        self.push_rpu_nal(nal.decoded_frame_index, data);
+                    }
+
+                    if self.dovi_writer.el_writer.is_some() {
                         // RPU should never be first NAL
-                        NALUnit::write_with_preset(
-                            el_writer,
-                            rpu_data,
-                            self.options.start_code.into(),
+                        self.write_nal(
+                            NalTarget::El,
+                            Cow::Borrowed(rpu_data),
+                            self.options.start_code,
                             nal.nal_type,
                             false,
+                            convert_rpus,
                         )?;
                     }
                 }
                 _ => {
+                    let aud = self.missing_aud(first_nal_of_frame, &nals[i..]);
+
                     if let Some(ref mut bl_writer) = self.dovi_writer.bl_writer {
-                        NALUnit::write_with_preset(
+                        if let Some(aud) = &aud {
+                            self.codec.write_nal(
+                                bl_writer,
+                                aud,
+                                self.options.start_code,
+                                self.codec.aud_nal_type(),
+                                true,
+                            )?;
+                        }
+
+                        self.codec.write_nal(
                             bl_writer,
-                            &chunk[nal.start..nal.end],
-                            self.options.start_code.into(),
+                            &nal_data,
+                            self.options.start_code,
                             nal.nal_type,
-                            first_nal_of_frame,
+                            first_nal_of_frame && aud.is_none(),
                         )?;
                     }
                 }
@@ -267,7 +660,350 @@ impl DoviProcessor {
         Ok(())
     }
 
-    fn flush_writer(&mut self, parser: &HevcParser) -> Result<()> {
+    /// AUD NALU to insert with `--add-aud`, when the access unit starting with the NALUs has none
+    fn missing_aud(&self, first_nal_of_frame: bool, nals: &[NALUnit]) -> Option<Vec<u8>> {
+        let add_aud = self.options.add_aud
+            && first_nal_of_frame
+            && nals[0].nal_type != self.codec.aud_nal_type();
+
+        add_aud.then(|| self.codec.aud_for_nals(nals))
+    }
+
+    /// Writes the NALU, or buffers it until the pending RPUs are converted.
+    /// Owned data is buffered without copying.
+    fn write_nal(
+        &mut self,
+        target: NalTarget,
+        data: Cow<[u8]>,
+        start_code: WriteStartCodePreset,
+        nal_type: u8,
+        first_nal: bool,
+        convert_rpu: bool,
+    ) -> Result<()> {
+        if !convert_rpu && self.pending_nals.is_empty() {
+            let codec = self.codec;
+
+            if let Some(writer) = self.dovi_writer.target_writer(target) {
+                codec.write_nal(writer, &data, start_code, nal_type, first_nal)?;
+            }
+
+            return Ok(());
+        }
+
+        self.pending_nals.push(PendingNal {
+            target,
+            data: data.into_owned(),
+            nal_type,
+            start_code,
+            first_nal,
+            convert_rpu,
+            hdr10_sei: false,
+        });
+
+        if convert_rpu {
+            self.pending_rpus += 1;
+
+            if self.pending_rpus >= RPU_BATCH_SIZE {
+                self.write_pending_nals()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes a single layer NALU, or holds it until the first slice of its access unit
+    fn write_sl_nal(
+        &mut self,
+        hold: bool,
+        data: Cow<[u8]>,
+        start_code: WriteStartCodePreset,
+        nal_type: u8,
+        first_nal: bool,
+        convert_rpu: bool,
+    ) -> Result<()> {
+        match &mut self.held_nals {
+            Some(held_nals) if hold => {
+                held_nals.push(PendingNal {
+                    target: NalTarget::SingleLayer,
+                    data: data.into_owned(),
+                    nal_type,
+                    start_code,
+                    first_nal,
+                    convert_rpu,
+                    hdr10_sei: false,
+                });
+
+                Ok(())
+            }
+            _ => self.write_nal(
+                NalTarget::SingleLayer,
+                data,
+                start_code,
+                nal_type,
+                first_nal,
+                convert_rpu,
+            ),
+        }
+    }
+
+    /// Writes the held NALUs of the access unit, after the Dolby NALUs moved before them
+    fn write_held_nals(&mut self) -> Result<()> {
+        if let Some(held_nals) = self.held_nals.take() {
+            for nal in held_nals {
+                self.write_nal(
+                    nal.target,
+                    Cow::Owned(nal.data),
+                    nal.start_code,
+                    nal.nal_type,
+                    nal.first_nal,
+                    nal.convert_rpu,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the HDR10 SEI NALU, or a placeholder until it is derived from the first RPU
+    fn write_hdr10_sei(&mut self, first_nal: bool) -> Result<()> {
+        match &self.hdr10_sei_nal {
+            Some(data) => self.write_nal(
+                NalTarget::SingleLayer,
+                Cow::Owned(data.clone()),
+                self.options.start_code,
+                NAL_SEI_PREFIX,
+                first_nal,
+                false,
+            ),
+            None => {
+                self.pending_nals.push(PendingNal {
+                    target: NalTarget::SingleLayer,
+                    data: Vec::new(),
+                    nal_type: NAL_SEI_PREFIX,
+                    start_code: self.options.start_code,
+                    first_nal,
+                    convert_rpu: false,
+                    hdr10_sei: true,
+                });
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Converts the pending RPUs in parallel, then writes the buffered NALUs in order
+    fn write_pending_nals(&mut self) -> Result<()> {
+        let options = &self.options;
+        let codec = self.codec;
+
+        let convert_start = Instant::now();
+        self.stats.converted_rpus += self.pending_rpus;
+
+        if let Some(ref mut lenient) = self.lenient {
+            let mut results: Vec<Option<Result<Vec<u8>>>> = self
+                .pending_nals
+                .par_iter()
+                .map(|nal| {
+                    nal.convert_rpu
+                        .then(|| convert_encoded_from_opts(options, codec, &nal.data))
+                })
+                .collect();
+
+            for (nal, res) in self.pending_nals.iter_mut().zip(results.iter_mut()) {
+                if let Some(res) = res.take() {
+                    match lenient.resolve(lenient.next_index(), res)? {
+                        Some(data) => nal.data = data,
+                        None => nal.data.clear(),
+                    }
+                }
+            }
+
+            // Skipped RPUs
+            self.pending_nals
+                .retain(|nal| !(nal.convert_rpu && nal.data.is_empty()));
+        } else {
+            self.pending_nals
+                .par_iter_mut()
+                .filter(|nal| nal.convert_rpu)
+                .try_for_each(|nal| -> Result<()> {
+                    nal.data = convert_encoded_from_opts(options, codec, &nal.data)?;
+                    Ok(())
+                })?;
+        }
+
+        self.stats.convert += convert_start.elapsed();
+
+        if let Some(hdr10_sei) = &options.hdr10_sei {
+            if self.hdr10_sei_nal.is_none() && self.pending_nals.iter().any(|nal| nal.hdr10_sei) {
+                let mut rpus = self
+                    .pending_nals
+                    .iter()
+                    .filter(|nal| nal.nal_type == codec.rpu_nal_type())
+                    .map(|nal| codec.parse_rpu_nalu(&nal.data))
+                    .peekable();
+
+                if rpus.peek().is_none() {
+                    bail!(
+                        "HDR10 SEI: no RPU in the first frame, use --master-display and --max-cll"
+                    );
+                }
+
+                let rpu = if options.lenient {
+                    rpus.find_map(Result::ok)
+                } else {
+                    rpus.next().transpose()?
+                };
+
+                self.hdr10_sei_nal = Some(hdr10_sei.sei_nal_from_rpu(rpu.as_ref())?);
+            }
+        }
+
+        for nal in self.pending_nals.drain(..) {
+            let data = match (nal.hdr10_sei, &self.hdr10_sei_nal) {
+                (true, Some(hdr10_sei_nal)) => hdr10_sei_nal,
+                _ => &nal.data,
+            };
+
+            if let Some(writer) = self.dovi_writer.target_writer(nal.target) {
+                codec.write_nal(writer, data, nal.start_code, nal.nal_type, nal.first_nal)?;
+            }
+        }
+
+        self.pending_rpus = 0;
+
+        Ok(())
+    }
+
+    /// Writes the RPUs of the frames ordered since the last call, and releases them.
+    /// Only the RPUs of the frames not yet ordered are kept in memory.
+    fn write_streamed_rpus(&mut self, frames: &[Frame]) -> Result<()> {
+        if self.streamed_frames >= frames.len() {
+            return Ok(());
+        }
+
+        let mut ordered_rpus = Vec::with_capacity(frames.len() - self.streamed_frames);
+        let field_count = if self.options.field_coded { 2 } else { 1 };
+
+        for frame in &frames[self.streamed_frames..] {
+            let matching_rpu = self
+                .rpu_nals
+                .iter()
+                .position(|rpu| rpu.decoded_index == frame.decoded_number as usize);
+
+            if let Some(i) = matching_rpu {
+                let mut rpu = self.rpu_nals.swap_remove(i);
+
+                // Only the first field of each pair is kept
+                if self.options.field_coded && frame.presentation_number % 2 == 1 {
+                    continue;
+                }
+
+                rpu.presentation_number = frame.presentation_number as usize / field_count;
+
+                ordered_rpus.push(rpu);
+            }
+        }
+
+        self.streamed_frames = frames.len();
+
+        if self.options.converts_rpus() {
+            let convert_start = Instant::now();
+            convert_rpu_nals(&self.options, &mut self.lenient, &mut ordered_rpus)?;
+
+            self.stats.convert += convert_start.elapsed();
+            self.stats.converted_rpus += ordered_rpus.len();
+        }
+
+        if let Some(ref mut rpu_writer) = self.dovi_writer.rpu_writer {
+            let write_start = Instant::now();
+
+            for rpu in ordered_rpus {
+                rpu_writer.write_rpu(&rpu.data)?;
+            }
+
+            // Makes the streamed RPUs available to the reading process
+            if let RpuWriter::Stream(_) = rpu_writer {
+                rpu_writer.flush()?;
+            }
+
+            self.stats.write += write_start.elapsed();
+        }
+
+        Ok(())
+    }
+
+    fn push_rpu_nal(&mut self, decoded_frame_index: u64, data: Vec<u8>) {
+        // The RPU list is drained when streaming, so the frame index is used
+        let decoded_index = if self.options.repair_rpus || self.options.stream_rpus {
+            let decoded_index = decoded_frame_index as usize;
+
+            // Also catches duplicates for the first frame
+            if self.rpu_nals.last().map(|rpu| rpu.decoded_index) == Some(decoded_index) {
+                warn!(
+                    "Unexpected RPU NALU found for frame {}. Discarding.",
+                    decoded_index
+                );
+
+                return;
+            }
+
+            decoded_index
+        } else {
+            self.rpu_nals.len()
+        };
+
+        trace!(
+            "RPU NALU of {} bytes, decoded frame {}",
+            data.len(),
+            decoded_frame_index
+        );
+
+        self.rpu_nals.push(RpuNal {
+            decoded_index,
+            presentation_number: 0,
+            data,
+        });
+    }
+
+    fn flush_writer(&mut self, frames: &[Frame]) -> Result<()> {
+        self.write_held_nals()?;
+        self.write_pending_nals()?;
+
+        if self.moved_rpus > 0 {
+            writeln!(
+                status_out(),
+                "Moved {} RPUs found before the slices of an access unit to the previous one",
+                self.moved_rpus
+            )
+            .ok();
+        }
+
+        if let Some(parameter_sets) = &self.parameter_sets {
+            if parameter_sets.rewritten_vps() > 0 {
+                writeln!(
+                    status_out(),
+                    "Rewrote {} VPS NALUs to describe the BL only",
+                    parameter_sets.rewritten_vps()
+                )
+                .ok();
+            }
+
+            if let Some(report) = parameter_sets.report() {
+                warn!("{}", report);
+            }
+        }
+
+        if let Some(ref mut alignment) = self.alignment {
+            alignment.finish();
+
+            // Reported once for all the segments, when demuxing in parallel
+            if self.byte_range.is_none() {
+                if let Some(report) = alignment.report() {
+                    warn!("{}", report);
+                }
+            }
+        }
+
         if let Some(ref mut bl_writer) = self.dovi_writer.bl_writer {
             bl_writer.flush()?;
         }
@@ -276,32 +1012,88 @@ impl DoviProcessor {
             el_writer.flush()?;
         }
 
-        // Reorder RPUs to display output order
-        if let Some(ref mut rpu_writer) = self.dovi_writer.rpu_writer {
-            let frames = parser.ordered_frames();
+        if let Some(ref mut sl_writer) = self.dovi_writer.sl_writer {
+            sl_writer.flush()?;
+        }
+
+        let convert_rpus = self.options.converts_rpus();
+
+        if let Some(el_report) = &self.el_report {
+            el_report.write(frames)?;
+        }
+
+        if self.options.stream_rpus {
+            self.write_streamed_rpus(frames)?;
+
+            if let Some(rpu) = self.rpu_nals.first() {
+                return Err(missing_frame_error(rpu.decoded_index));
+            }
 
+            if let Some(ref mut rpu_writer) = self.dovi_writer.rpu_writer {
+                rpu_writer.flush()?;
+            }
+        } else if self.dovi_writer.rpu_writer.is_some() || self.change_summary.is_some() {
+            // Reorder RPUs to display output order
             if frames.is_empty() {
                 bail!("No frames parsed!");
             }
 
-            print!("Reordering metadata... ");
-            stdout().flush().ok();
+            if self.options.repair_rpus {
+                self.rpu_nals = repair_rpu_nals(self.rpu_nals.drain(..), frames)?;
+            }
 
-            // Sort by matching frame POC
-            self.rpu_nals.sort_by_cached_key(|rpu| {
-                let matching_index = frames
-                    .iter()
-                    .position(|f| rpu.decoded_index == f.decoded_number as usize);
+            let segment = self.byte_range.is_some();
 
-                if let Some(i) = matching_index {
-                    frames[i].presentation_number
-                } else {
-                    panic!(
-                        "Missing frame/slices for metadata! Decoded index {}",
-                        rpu.decoded_index
+            if !segment {
+                write!(status_out(), "Reordering metadata... ").ok();
+                status_out().flush().ok();
+            }
+
+            // Sort by matching frame POC, or by the external frame order
+            let mut ordered_rpus = Vec::with_capacity(self.rpu_nals.len());
+
+            if let Some(frame_order) = &self.options.frame_order {
+                if frame_order.len() != frames.len() {
+                    warn!(
+                        "mismatched frame count. frame order {}, video {}",
+                        frame_order.len(),
+                        frames.len()
                     );
                 }
-            });
+            }
+
+            for rpu in self.rpu_nals.drain(..) {
+                let presentation_number = match &self.options.frame_order {
+                    Some(frame_order) => frame_order
+                        .presentation_number(rpu.decoded_index)
+                        .map(|number| number as u64),
+                    None => frames
+                        .iter()
+                        .find(|f| rpu.decoded_index == f.decoded_number as usize)
+                        .map(|f| f.presentation_number),
+                };
+
+                match presentation_number {
+                    Some(number) => ordered_rpus.push((number, rpu)),
+                    None if self.options.frame_order.is_some() => {
+                        bail!(
+                            "Invalid frame order: no entry for decoded frame {}",
+                            rpu.decoded_index
+                        )
+                    }
+                    None => return Err(missing_frame_error(rpu.decoded_index)),
+                }
+            }
+
+            ordered_rpus.sort_by_key(|(number, _)| *number);
+
+            // One RPU per frame for field pictures, from the first field of each pair
+            let step = if self.options.field_coded { 2 } else { 1 };
+            self.rpu_nals = ordered_rpus
+                .into_iter()
+                .step_by(step)
+                .map(|(_, rpu)| rpu)
+                .collect();
 
             // Set presentation number to new index
             self.rpu_nals
@@ -309,45 +1101,499 @@ impl DoviProcessor {
                 .enumerate()
                 .for_each(|(idx, rpu)| rpu.presentation_number = idx);
 
-            println!("Done.");
-
-            // Write data to file
-            for rpu in self.rpu_nals.iter_mut() {
-                // RPU file is always 4 bytes start code
-                NALUnit::write_with_preset(
-                    rpu_writer,
-                    &rpu.data,
-                    WriteStartCodePreset::Four.into(),
-                    NAL_UNSPEC62,
-                    true,
-                )?;
+            if !segment {
+                writeln!(status_out(), "Done.").ok();
+            }
+
+            report_profile_mix(&self.options, &self.rpu_nals);
+
+            if let Some(crc_mode) = self.options.crc_mode {
+                check_rpu_nals_crc32(crc_mode, &mut self.rpu_nals)?;
+            }
+
+            let original_rpus: Option<Vec<RpuNal>> = self.change_summary.is_some().then(|| {
+                self.rpu_nals
+                    .iter()
+                    .map(|rpu| RpuNal {
+                        decoded_index: rpu.decoded_index,
+                        presentation_number: rpu.presentation_number,
+                        data: rpu.data.clone(),
+                    })
+                    .collect()
+            });
+
+            if convert_rpus {
+                let convert_start = Instant::now();
+                convert_rpu_nals(&self.options, &mut self.lenient, &mut self.rpu_nals)?;
+
+                self.stats.convert += convert_start.elapsed();
+                self.stats.converted_rpus += self.rpu_nals.len();
             }
 
-            rpu_writer.flush()?;
+            if let (Some(summary), Some(original_rpus)) =
+                (self.change_summary.as_mut(), original_rpus)
+            {
+                summarize_rpu_nals(summary, &original_rpus, &self.rpu_nals)?;
+                writeln!(status_out(), "{}", summary).ok();
+            }
+
+            if let Some(ref mut rpu_writer) = self.dovi_writer.rpu_writer {
+                let write_start = Instant::now();
+
+                // Write data to file
+                for rpu in self.rpu_nals.iter() {
+                    rpu_writer.write_rpu(&rpu.data)?;
+                }
+
+                rpu_writer.flush()?;
+                self.stats.write += write_start.elapsed();
+            }
+        }
+
+        if let Some(hdr10plus_out) = self.dovi_writer.hdr10plus_out.take() {
+            self.write_hdr10plus_json(frames, &hdr10plus_out)?;
+        }
+
+        if let Some(lenient) = &self.lenient {
+            lenient.print_summary();
+        }
+
+        Ok(())
+    }
+
+    /// Statistics of the run, as a JSON event with `--progress json`
+    fn write_stats(&self) {
+        let report = self.stats.report(
+            self.progress_bar.position(),
+            self.rpus_found,
+            self.dovi_writer.wait_time(),
+        );
+
+        if self.json_progress.is_some() {
+            write_json_event(&report);
+        } else {
+            writeln!(status_out(), "{}", report).ok();
+        }
+    }
+
+    fn update_json_progress(&mut self, frames: usize) {
+        if let Some(ref mut json_progress) = self.json_progress {
+            json_progress.update(&self.progress_bar, frames, self.rpus_found);
+        }
+    }
+
+    fn finish_json_progress(&mut self, frames: usize) {
+        if let Some(ref mut json_progress) = self.json_progress {
+            json_progress.finish(&self.progress_bar, frames, self.rpus_found);
+        }
+    }
+
+    fn write_hdr10plus_json(&mut self, frames: &[Frame], path: &Path) -> Result<()> {
+        if self.hdr10plus_seis.is_empty() {
+            writeln!(
+                status_out(),
+                "No HDR10+ metadata found, the JSON file was not written."
+            )
+            .ok();
+            return Ok(());
+        }
+
+        let first_profile = &self.hdr10plus_seis[0].metadata.profile;
+        if self
+            .hdr10plus_seis
+            .iter()
+            .any(|sei| &sei.metadata.profile != first_profile)
+        {
+            bail!("HDR10+ metadata profile is not the same for every frame");
         }
 
+        // Reorder HDR10+ metadata to display output order
+        let mut seis = Vec::with_capacity(self.hdr10plus_seis.len());
+
+        for sei in self.hdr10plus_seis.drain(..) {
+            let presentation_number = frames
+                .iter()
+                .find(|f| sei.decoded_index == f.decoded_number as usize)
+                .map(|f| f.presentation_number);
+
+            match presentation_number {
+                Some(number) => seis.push((number, sei.metadata)),
+                None => bail!(
+                    "Missing frame/slices for HDR10+ metadata! Decoded index {}",
+                    sei.decoded_index
+                ),
+            }
+        }
+
+        seis.sort_by_key(|(number, _)| *number);
+
+        writeln!(status_out(), "Writing HDR10+ JSON file...").ok();
+        status_out().flush().ok();
+
+        let list: Vec<&Hdr10PlusMetadata> = seis.iter().map(|(_, metadata)| metadata).collect();
+        let json = generate_json(&list, env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+
+        let writer = BufWriter::new(create_output_file(path)?);
+        serde_json::to_writer_pretty(writer, &json)?;
+
         Ok(())
     }
 }
 
+/// The RPU was found in an access unit without any slice, the input is likely truncated or corrupted
+fn missing_frame_error(decoded_index: usize) -> anyhow::Error {
+    anyhow!(
+        "Missing frame/slices for metadata! Decoded index {}.\n\
+        The RPU has no matching frame, the input may be truncated or corrupted.\n\
+        Use `extract-rpu --repair` to discard the RPUs without a frame.",
+        decoded_index
+    )
+}
+
+/// Verifies the CRC32 of the presentation ordered RPUs, then fixes or replaces the corrupt ones
+fn check_rpu_nals_crc32(crc_mode: CrcMode, rpu_nals: &mut [RpuNal]) -> Result<()> {
+    writeln!(status_out(), "Verifying RPU CRC32...").ok();
+    status_out().flush().ok();
+
+    let results: Vec<Option<RpuCrcError>> = rpu_nals
+        .par_iter_mut()
+        .map(|rpu| check_rpu_crc32(crc_mode, rpu))
+        .collect();
+
+    let corrupt_count = results.iter().flatten().count();
+
+    if corrupt_count == 0 {
+        writeln!(status_out(), "All RPUs have a valid CRC32.").ok();
+        return Ok(());
+    }
+
+    for (frame, error) in results.iter().enumerate() {
+        match error {
+            Some(RpuCrcError::Mismatch) => {
+                writeln!(status_out(), "  Frame {}: CRC32 mismatch", frame).ok();
+            }
+            Some(RpuCrcError::Invalid(e)) => {
+                writeln!(status_out(), "  Frame {}: invalid RPU: {}", frame, e).ok();
+            }
+            None => (),
+        }
+    }
+
+    match crc_mode {
+        CrcMode::Check => {
+            warn!("{} RPUs failed the CRC32 check.", corrupt_count);
+        }
+        CrcMode::Fix => {
+            let fixed_count = results
+                .iter()
+                .filter(|e| matches!(e, Some(RpuCrcError::Mismatch)))
+                .count();
+
+            writeln!(status_out(), "Fixed the CRC32 of {} RPUs.", fixed_count).ok();
+
+            if fixed_count != corrupt_count {
+                warn!(
+                    "{} RPUs could not be parsed and were kept as is.",
+                    corrupt_count - fixed_count
+                );
+            }
+        }
+        CrcMode::Replace => {
+            let first_valid = match results.iter().position(Option::is_none) {
+                Some(i) => i,
+                None => bail!("No RPU with a valid CRC32 found to replace the corrupt RPUs"),
+            };
+
+            for i in 0..rpu_nals.len() {
+                if results[i].is_some() {
+                    // Corrupt RPUs at the start use the first valid RPU
+                    rpu_nals[i].data = if i > 0 {
+                        duplicated_rpu_data(Codec::Hevc, &rpu_nals[i - 1].data)?
+                    } else {
+                        rpu_nals[first_valid].data.clone()
+                    };
+                }
+            }
+
+            writeln!(
+                status_out(),
+                "Replaced {} corrupt RPUs with the previous valid RPU.",
+                corrupt_count
+            )
+            .ok();
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses the RPU to verify the CRC32, and recomputes it in `Fix` mode
+fn check_rpu_crc32(crc_mode: CrcMode, rpu: &mut RpuNal) -> Option<RpuCrcError> {
+    if DoviRpu::parse_unspec62_nalu(&rpu.data).is_ok() {
+        return None;
+    }
+
+    match DoviRpu::parse_unspec62_nalu_ignore_crc32(&rpu.data) {
+        Ok(parsed_rpu) => {
+            if crc_mode == CrcMode::Fix {
+                match parsed_rpu.write_hevc_unspec62_nalu() {
+                    Ok(encoded_rpu) => rpu.data = encoded_rpu,
+                    Err(e) => return Some(RpuCrcError::Invalid(e.to_string())),
+                }
+            }
+
+            Some(RpuCrcError::Mismatch)
+        }
+        Err(e) => Some(RpuCrcError::Invalid(e.to_string())),
+    }
+}
+
+/// Converts the extracted RPUs in parallel
+/// Reports the profile changes of the RPUs in presentation order, before any conversion
+fn report_profile_mix(options: &CliOptions, rpu_nals: &[RpuNal]) {
+    let variants: Vec<Option<ProfileVariant>> = rpu_nals
+        .par_iter()
+        .map(|rpu| {
+            Codec::Hevc
+                .parse_rpu_nalu(&rpu.data)
+                .ok()
+                .map(|dovi_rpu| ProfileVariant::from_rpu(&dovi_rpu))
+        })
+        .collect();
+
+    let mut profile_mix = ProfileMix::default();
+    variants
+        .into_iter()
+        .for_each(|variant| profile_mix.push(variant));
+
+    if profile_mix.is_mixed() {
+        warn!("{}", profile_mix.report());
+
+        match options.normalize {
+            Some(normalize) => writeln!(status_out(), "Normalizing the RPUs to {}", normalize),
+            None => writeln!(
+                status_out(),
+                "Use --normalize to convert the RPUs to a single profile"
+            ),
+        }
+        .ok();
+    }
+}
+
+/// Compares the converted RPUs to the originals, by presentation number.
+/// The RPUs dropped by the lenient parsing are removed frames.
+fn summarize_rpu_nals(
+    summary: &mut ChangeSummary,
+    original_rpus: &[RpuNal],
+    converted_rpus: &[RpuNal],
+) -> Result<()> {
+    let mut converted_rpus = converted_rpus.iter().peekable();
+
+    for original in original_rpus {
+        let converted = converted_rpus
+            .next_if(|rpu| rpu.presentation_number == original.presentation_number)
+            .map(|rpu| DoviRpu::parse_unspec62_nalu(&rpu.data))
+            .transpose()?;
+
+        // Invalid RPUs are only reported by the conversion
+        if let Ok(rpu) = DoviRpu::parse_unspec62_nalu(&original.data) {
+            summary.add(original.presentation_number, &rpu, converted.as_ref())?;
+        }
+    }
+
+    Ok(())
+}
+
+fn convert_rpu_nals(
+    options: &CliOptions,
+    lenient: &mut Option<LenientParser>,
+    rpu_nals: &mut Vec<RpuNal>,
+) -> Result<()> {
+    let lenient = match lenient {
+        Some(lenient) => lenient,
+        None => {
+            return rpu_nals.par_iter_mut().try_for_each(|rpu| -> Result<()> {
+                rpu.data = convert_encoded_from_opts(options, Codec::Hevc, &rpu.data)?;
+
+                Ok(())
+            })
+        }
+    };
+
+    let results: Vec<Result<Vec<u8>>> = rpu_nals
+        .par_iter()
+        .map(|rpu| convert_encoded_from_opts(options, Codec::Hevc, &rpu.data))
+        .collect();
+
+    let mut converted = Vec::with_capacity(rpu_nals.len());
+
+    for (mut rpu, res) in rpu_nals.drain(..).zip(results) {
+        if let Some(data) = lenient.resolve(rpu.presentation_number, res)? {
+            rpu.data = data;
+            converted.push(rpu);
+        }
+    }
+
+    *rpu_nals = converted;
+
+    Ok(())
+}
+
+/// Matches the RPUs to the parsed frames, discarding the RPUs without a frame.
+/// The frames missing an RPU are filled with the RPU of the previous frame,
+/// so that the number of RPUs is the same as the number of frames.
+fn repair_rpu_nals(
+    rpu_nals: impl Iterator<Item = RpuNal>,
+    frames: &[Frame],
+) -> Result<Vec<RpuNal>> {
+    let mut frame_rpus: Vec<Option<RpuNal>> = frames.iter().map(|_| None).collect();
+
+    // Lookups in both directions, the first frame is kept for duplicate numbers
+    let mut presentation_numbers: HashMap<u64, u64> = HashMap::with_capacity(frames.len());
+    let mut decoded_indices: Vec<Option<usize>> = vec![None; frames.len()];
+
+    for frame in frames {
+        presentation_numbers
+            .entry(frame.decoded_number)
+            .or_insert(frame.presentation_number);
+
+        if let Some(index) = decoded_indices.get_mut(frame.presentation_number as usize) {
+            index.get_or_insert(frame.decoded_number as usize);
+        }
+    }
+
+    for rpu in rpu_nals {
+        let presentation_number = presentation_numbers
+            .get(&(rpu.decoded_index as u64))
+            .map(|&number| number as usize);
+
+        match presentation_number {
+            Some(number) if number < frame_rpus.len() => frame_rpus[number] = Some(rpu),
+            _ => {
+                warn!(
+                    "Missing frame/slices for metadata, decoded index {}. Discarding.",
+                    rpu.decoded_index
+                );
+            }
+        }
+    }
+
+    let first_rpu = match frame_rpus.iter().flatten().next() {
+        Some(rpu) => rpu.data.clone(),
+        None => bail!("No RPU found to repair the metadata"),
+    };
+
+    let mut filled = 0;
+    let mut repaired: Vec<RpuNal> = Vec::with_capacity(frame_rpus.len());
+
+    for (presentation_number, rpu) in frame_rpus.into_iter().enumerate() {
+        let decoded_index = decoded_indices[presentation_number].unwrap_or(presentation_number);
+
+        let data = match rpu {
+            Some(rpu) => rpu.data,
+            None => {
+                filled += 1;
+
+                match repaired.last() {
+                    Some(previous) => duplicated_rpu_data(Codec::Hevc, &previous.data)?,
+                    // Missing RPUs at the start, use the first available
+                    None => first_rpu.clone(),
+                }
+            }
+        };
+
+        repaired.push(RpuNal {
+            decoded_index,
+            presentation_number,
+            data,
+        });
+    }
+
+    if filled > 0 {
+        warn!(
+            "Filled {} frames missing an RPU with the previous frame's RPU.",
+            filled
+        );
+    }
+
+    Ok(repaired)
+}
+
 impl IoProcessor for DoviProcessor {
     fn input(&self) -> &std::path::PathBuf {
         &self.input
     }
 
-    fn update_progress(&mut self, delta: u64) {
-        self.progress_bar.inc(delta);
+    fn update_progress(&mut self, _delta: u64) {
+        // Progress is updated by the input reader
     }
 
-    fn process_nals(&mut self, _parser: &HevcParser, nals: &[NALUnit], chunk: &[u8]) -> Result<()> {
+    fn process_nals(&mut self, parser: &HevcParser, nals: &[NALUnit], chunk: &[u8]) -> Result<()> {
         self.write_nals(chunk, nals)?;
         self.payload_count += 1;
 
+        if self.options.stream_rpus {
+            self.write_streamed_rpus(parser.ordered_frames())?;
+        }
+
+        self.update_json_progress(parser.ordered_frames().len());
+
         Ok(())
     }
 
     fn finalize(&mut self, parser: &HevcParser) -> Result<()> {
-        self.progress_bar.finish_and_clear();
-        self.flush_writer(parser)
+        // The hidden bar keeps the bytes read for the final JSON event
+        if self.json_progress.is_none() && self.byte_range.is_none() {
+            self.progress_bar.finish_and_clear();
+        }
+
+        self.stats.frames = parser.ordered_frames().len();
+        self.flush_writer(parser.ordered_frames())?;
+        self.finish_json_progress(parser.ordered_frames().len());
+
+        Ok(())
+    }
+}
+
+impl NalIoProcessor for DoviProcessor {
+    fn process_nals(
+        &mut self,
+        frame_order: &FrameOrder,
+        nals: &[NALUnit],
+        chunk: &[u8],
+    ) -> Result<()> {
+        self.write_nals(chunk, nals)?;
+        self.payload_count += 1;
+
+        if self.options.stream_rpus {
+            self.write_streamed_rpus(frame_order.ordered_frames())?;
+        }
+
+        self.update_json_progress(frame_order.ordered_frames().len());
+
+        Ok(())
+    }
+
+    fn finalize(&mut self, frame_order: &FrameOrder) -> Result<()> {
+        // The hidden bar keeps the bytes read for the final JSON event
+        if self.json_progress.is_none() && self.byte_range.is_none() {
+            self.progress_bar.finish_and_clear();
+        }
+
+        self.stats.frames = frame_order.ordered_frames().len();
+        self.flush_writer(frame_order.ordered_frames())?;
+        self.finish_json_progress(frame_order.ordered_frames().len());
+
+        Ok(())
+    }
+
+    fn corrupt_nal(&mut self, position: u64, size: usize, error: Error) -> Result<()> {
+        match self.lenient {
+            Some(ref mut lenient) => {
+                lenient.skip_corrupt_nal(position, size, &error);
+                Ok(())
+            }
+            None => Err(error),
+        }
     }
 }
@@ -1,17 +1,26 @@
-use anyhow::{bail, ensure, Result};
-use serde_json::Value;
+use anyhow::{anyhow, bail, ensure, Result};
+use serde_json::{Map, Value};
 use std::fs::File;
-use std::io::{stdout, Read, Write};
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
+use super::rpu_stream::{self, status_out};
+use super::scene_file::SceneCuts;
+use super::timecode::{parse_fps, TimecodeResolver};
+use super::{write_rpu_file, DoviRpu};
 use crate::commands::GenerateArgs;
 use dolby_vision::rpu::extension_metadata::blocks::{
-    ExtMetadataBlock, ExtMetadataBlockLevel1, ExtMetadataBlockLevel6,
+    ExtMetadataBlock, ExtMetadataBlockLevel1, ExtMetadataBlockLevel2, ExtMetadataBlockLevel5,
+    ExtMetadataBlockLevel6,
 };
-use dolby_vision::rpu::generate::{GenerateConfig, ShotFrameEdit, VideoShot};
-use dolby_vision::utils::nits_to_pq;
+use dolby_vision::rpu::generate::{GenerateConfig, GenerateProfile, ShotFrameEdit, VideoShot};
+use dolby_vision::utils::{nits_to_pq, pq_to_pq12};
 use dolby_vision::xml::{CmXmlParser, XmlParserOpts};
 
+/// L1 average of the static content, the BT.2408 HDR reference white
+const STATIC_CONTENT_AVG_NITS: f64 = 203.0;
+
 #[derive(Default)]
 pub struct Generator {
     json_path: Option<PathBuf>,
@@ -22,10 +31,26 @@ pub struct Generator {
     canvas_height: Option<u16>,
     madvr_path: Option<PathBuf>,
     use_custom_targets: bool,
+    scene_file: Option<PathBuf>,
+    hlg: bool,
+    length: Option<usize>,
+    static_content: Option<StaticContent>,
+    fps: Option<f64>,
+    timestamps: Option<PathBuf>,
+    chapters: Option<PathBuf>,
 
     pub config: Option<GenerateConfig>,
 }
 
+/// Static metadata of the `--static-content` preset, from the CLI flags
+#[derive(Debug, Default)]
+struct StaticContent {
+    /// Min, avg and max in nits
+    level1: Option<[f64; 3]>,
+    level5: Option<ExtMetadataBlockLevel5>,
+    level6: Option<ExtMetadataBlockLevel6>,
+}
+
 impl Generator {
     pub fn from_args(args: GenerateArgs) -> Result<Generator> {
         let GenerateArgs {
@@ -37,14 +62,41 @@ impl Generator {
             canvas_height,
             madvr_file,
             use_custom_targets,
+            scene_file,
+            hlg,
+            length,
+            static_content,
+            l1,
+            l5,
+            l6,
+            fps,
+            timestamps,
+            chapters,
         } = args;
 
+        ensure!(
+            length.is_none() || hlg || static_content,
+            "generate: --length requires --hlg or --static-content"
+        );
+
+        let static_content = if static_content {
+            Some(StaticContent::from_args(l1, l5, l6)?)
+        } else {
+            None
+        };
+
+        let fps = fps.as_deref().map(parse_fps).transpose()?;
+
         let out_path = if let Some(out_path) = rpu_out {
             out_path
         } else {
             PathBuf::from("RPU_generated.bin".to_string())
         };
 
+        if rpu_stream::is_stdio(&out_path) {
+            rpu_stream::use_stdout();
+        }
+
         let generator = Generator {
             json_path: json_file,
             rpu_out: out_path,
@@ -54,6 +106,13 @@ impl Generator {
             canvas_height,
             madvr_path: madvr_file,
             use_custom_targets,
+            scene_file,
+            hlg,
+            length,
+            static_content,
+            fps,
+            timestamps,
+            chapters,
             config: None,
         };
 
@@ -66,9 +125,103 @@ impl Generator {
     }
 
     pub fn execute(&mut self) -> Result<()> {
-        let config = if let Some(json_path) = &self.json_path {
-            let json_file = File::open(json_path)?;
-            let mut config: GenerateConfig = serde_json::from_reader(&json_file)?;
+        let scene_cuts = self
+            .scene_file
+            .as_deref()
+            .map(SceneCuts::from_path)
+            .transpose()?;
+
+        self.config = Some(self.build_config(scene_cuts.as_ref(), None)?);
+
+        if let Some(config) = &self.config {
+            writeln!(status_out(), "Generating metadata...").ok();
+
+            if scene_cuts.is_some() || rpu_stream::is_stdio(&self.rpu_out) {
+                let mut rpus = config.generate_rpu_list()?;
+
+                if let Some(scene_cuts) = &scene_cuts {
+                    self.apply_scene_cuts(config, scene_cuts, &mut rpus)?;
+                }
+
+                write_rpu_file(&self.rpu_out, GenerateConfig::encode_rpus(&mut rpus))?;
+            } else {
+                config.write_rpus(&self.rpu_out)?;
+            }
+
+            writeln!(
+                status_out(),
+                "Generated metadata for {} frames",
+                config.length
+            )
+            .ok();
+        } else {
+            bail!("No generation config to execute!");
+        }
+
+        writeln!(status_out(), "Done.").ok();
+
+        Ok(())
+    }
+
+    /// Generates the RPUs in memory, for `default_length` frames when the sources don't set the length
+    pub fn generate_rpus(&mut self, default_length: usize) -> Result<Vec<DoviRpu>> {
+        let scene_cuts = self
+            .scene_file
+            .as_deref()
+            .map(SceneCuts::from_path)
+            .transpose()?;
+
+        let config = self.build_config(scene_cuts.as_ref(), Some(default_length))?;
+
+        writeln!(status_out(), "Generating metadata...").ok();
+        let mut rpus = config.generate_rpu_list()?;
+
+        if let Some(scene_cuts) = &scene_cuts {
+            self.apply_scene_cuts(&config, scene_cuts, &mut rpus)?;
+        }
+
+        writeln!(status_out(), "Generated metadata for {} frames", rpus.len()).ok();
+        self.config = Some(config);
+
+        Ok(rpus)
+    }
+
+    fn build_config(
+        &self,
+        scene_cuts: Option<&SceneCuts>,
+        default_length: Option<usize>,
+    ) -> Result<GenerateConfig> {
+        let config = if self.json_path.is_some()
+            || self.hlg
+            || self.static_content.is_some()
+            || default_length.is_some()
+        {
+            let mut config: GenerateConfig = match &self.json_path {
+                Some(json_path) => {
+                    let mut value: Value = serde_json::from_reader(&File::open(json_path)?)?;
+                    TimecodeResolver::new(
+                        self.fps,
+                        self.timestamps.as_deref(),
+                        self.chapters.as_deref(),
+                        &value,
+                    )?
+                    .resolve_generate_config(&mut value)?;
+
+                    serde_json::from_value(value)?
+                }
+                None => GenerateConfig {
+                    length: self.length.or(default_length).unwrap_or_default(),
+                    ..Default::default()
+                },
+            };
+
+            if self.hlg {
+                config.profile = GenerateProfile::Profile84;
+            }
+
+            if let Some(static_content) = &self.static_content {
+                static_content.apply(&mut config);
+            }
 
             if let Some(hdr10plus_path) = &self.hdr10plus_path {
                 parse_hdr10plus_for_l1(hdr10plus_path, &mut config)?;
@@ -77,6 +230,13 @@ impl Generator {
             } else if config.length == 0 && !config.shots.is_empty() {
                 // Set length from sum of shot durations
                 config.length = config.shots.iter().map(|s| s.duration).sum();
+            } else if config.length == 0 {
+                // Set length from the scene file
+                if let Some(frame_count) = scene_cuts.and_then(|s| s.frame_count) {
+                    config.length = frame_count;
+                } else if let Some(default_length) = default_length {
+                    config.length = default_length;
+                }
             }
 
             ensure!(
@@ -100,25 +260,35 @@ impl Generator {
             bail!("Missing configuration or XML file!");
         };
 
-        self.config = Some(config);
-
-        if let Some(config) = &self.config {
-            println!("Generating metadata...");
-
-            config.write_rpus(&self.rpu_out)?;
+        Ok(config)
+    }
 
-            println!("Generated metadata for {} frames", config.length);
-        } else {
-            bail!("No generation config to execute!");
+    /// The luma levels of the scene file are only used as L1 metadata
+    /// when no other source sets it, and for a PQ base layer.
+    fn apply_scene_cuts(
+        &self,
+        config: &GenerateConfig,
+        scene_cuts: &SceneCuts,
+        rpus: &mut [DoviRpu],
+    ) -> Result<()> {
+        scene_cuts.apply(rpus)?;
+
+        let has_level1 = config.shots.iter().any(|shot| {
+            shot.metadata_blocks
+                .iter()
+                .chain(shot.frame_edits.iter().flat_map(|e| &e.metadata_blocks))
+                .any(|block| block.level() == 1)
+        });
+
+        if scene_cuts.has_luma_levels() && !has_level1 && !self.hlg {
+            scene_cuts.apply_luma_levels(rpus)?;
         }
 
-        println!("Done.");
-
         Ok(())
     }
 
     fn config_from_xml(&self, xml_path: &Path) -> Result<GenerateConfig> {
-        println!("Parsing XML metadata...");
+        writeln!(status_out(), "Parsing XML metadata...").ok();
 
         let parser_opts = XmlParserOpts {
             canvas_width: self.canvas_width,
@@ -131,9 +301,132 @@ impl Generator {
     }
 }
 
+impl StaticContent {
+    fn from_args(l1: Option<String>, l5: Option<String>, l6: Option<String>) -> Result<Self> {
+        let level1 = l1
+            .as_deref()
+            .map(|l1| -> Result<[f64; 3]> {
+                let [min, avg, max] = parse_values::<f64, 3>("--l1", l1, "min,avg,max")?;
+
+                ensure!(
+                    min >= 0.0 && min <= avg && avg <= max && max <= 10_000.0,
+                    "generate: invalid --l1 {}, expected min <= avg <= max, up to 10000 nits",
+                    l1
+                );
+
+                Ok([min, avg, max])
+            })
+            .transpose()?;
+
+        let level5 = l5
+            .as_deref()
+            .map(|l5| -> Result<ExtMetadataBlockLevel5> {
+                let [left, right, top, bottom] =
+                    parse_values::<u16, 4>("--l5", l5, "left,right,top,bottom")?;
+
+                let level5 = ExtMetadataBlockLevel5::from_offsets(left, right, top, bottom);
+
+                ensure!(
+                    level5.validate().is_ok(),
+                    "generate: invalid --l5 {}, offsets up to 8191 pixels",
+                    l5
+                );
+
+                Ok(level5)
+            })
+            .transpose()?;
+
+        let level6 = l6
+            .as_deref()
+            .map(|l6| -> Result<ExtMetadataBlockLevel6> {
+                let [max_mdl, min_mdl, max_cll, max_fall] =
+                    parse_values::<f64, 4>("--l6", l6, "MaxMDL,MinMDL,MaxCLL,MaxFALL")?;
+
+                let level6 = ExtMetadataBlockLevel6 {
+                    max_display_mastering_luminance: max_mdl.round() as u16,
+                    // In units of 0.0001 nits
+                    min_display_mastering_luminance: (min_mdl * 10_000.0).round() as u16,
+                    max_content_light_level: max_cll.round() as u16,
+                    max_frame_average_light_level: max_fall.round() as u16,
+                };
+
+                ensure!(
+                    level6.validate().is_ok() && max_mdl > 0.0 && max_fall <= max_cll.max(max_mdl),
+                    "generate: invalid --l6 {}",
+                    l6
+                );
+
+                Ok(level6)
+            })
+            .transpose()?;
+
+        Ok(Self {
+            level1,
+            level5,
+            level6,
+        })
+    }
+
+    /// Sets the static metadata, with the same L1 metadata for every frame.
+    ///
+    /// Without `--l1`, the L1 max is the MaxCLL, or the mastering display max luminance,
+    /// so that the content is never tone mapped above its peak.
+    fn apply(&self, config: &mut GenerateConfig) {
+        if let Some(level5) = &self.level5 {
+            config.level5 = level5.clone();
+        }
+
+        if let Some(level6) = &self.level6 {
+            config.level6 = level6.clone();
+        }
+
+        let level6 = &config.level6;
+
+        let [min, avg, max] = match self.level1 {
+            Some(level1) => level1,
+            None => {
+                let max = match level6.max_content_light_level {
+                    0 => level6.max_display_mastering_luminance as f64,
+                    max_cll => max_cll as f64,
+                };
+                let avg = match level6.max_frame_average_light_level {
+                    0 => STATIC_CONTENT_AVG_NITS,
+                    max_fall => max_fall as f64,
+                };
+
+                [0.0, avg.min(max), max]
+            }
+        };
+
+        let to_pq = |nits: f64| pq_to_pq12(nits_to_pq(nits));
+        let level1 = ExtMetadataBlockLevel1::from_stats(to_pq(min), to_pq(max), to_pq(avg));
+
+        config
+            .default_metadata_blocks
+            .push(ExtMetadataBlock::Level1(level1));
+    }
+}
+
+/// Comma separated values of a CLI flag
+fn parse_values<T: FromStr, const N: usize>(
+    flag: &str,
+    value: &str,
+    format: &str,
+) -> Result<[T; N]> {
+    let values: Vec<T> = value
+        .split(',')
+        .map(|v| v.trim().parse::<T>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| anyhow!("generate: invalid {} {}, expected {}", flag, value, format))?;
+
+    values
+        .try_into()
+        .map_err(|_| anyhow!("generate: invalid {} {}, expected {}", flag, value, format))
+}
+
 fn parse_hdr10plus_for_l1(hdr10plus_path: &Path, config: &mut GenerateConfig) -> Result<()> {
-    println!("Parsing HDR10+ JSON file...");
-    stdout().flush().ok();
+    writeln!(status_out(), "Parsing HDR10+ JSON file...").ok();
+    status_out().flush().ok();
 
     let mut s = String::new();
     File::open(hdr10plus_path)?.read_to_string(&mut s)?;
@@ -193,17 +486,24 @@ fn parse_hdr10plus_for_l1(hdr10plus_path: &Path, config: &mut GenerateConfig) ->
                     let max_rgb = maxscl.iter().filter_map(|e| e.as_u64()).max().unwrap();
 
                     let min_pq = 0;
-                    let max_pq =
-                        (nits_to_pq((max_rgb as f64 / 10.0).round()) * 4095.0).round() as u16;
-                    let avg_pq =
-                        (nits_to_pq((avg_rgb as f64 / 10.0).round()) * 4095.0).round() as u16;
+                    let max_pq = pq_to_pq12(nits_to_pq((max_rgb as f64 / 10.0).round()));
+                    let avg_pq = pq_to_pq12(nits_to_pq((avg_rgb as f64 / 10.0).round()));
+
+                    let mut metadata_blocks = vec![ExtMetadataBlock::Level1(
+                        ExtMetadataBlockLevel1::from_stats(min_pq, max_pq, avg_pq),
+                    )];
+
+                    // The shot config L2 blocks replace the trim from the curve
+                    let bezier_curve = map.get("BezierCurveData").and_then(|v| v.as_object());
+
+                    if let Some(level2) = bezier_curve.and_then(level2_from_bezier_curve) {
+                        metadata_blocks.push(ExtMetadataBlock::Level2(level2));
+                    }
 
                     let mut shot = VideoShot {
                         start: frame_no,
                         duration: scene_frame_lengths[current_shot_id],
-                        metadata_blocks: vec![ExtMetadataBlock::Level1(
-                            ExtMetadataBlockLevel1::from_stats(min_pq, max_pq, avg_pq),
-                        )],
+                        metadata_blocks,
                         ..Default::default()
                     };
 
@@ -230,13 +530,71 @@ fn parse_hdr10plus_for_l1(hdr10plus_path: &Path, config: &mut GenerateConfig) ->
     Ok(())
 }
 
+/// Approximates the 100 nits L2 trim from the HDR10+ Bezier tone mapping curve.
+///
+/// The curve is fitted as `(slope * x) ^ power` at a quarter and three quarters of the input range.
+fn level2_from_bezier_curve(bezier_curve: &Map<String, Value>) -> Option<ExtMetadataBlockLevel2> {
+    let knee_x = bezier_curve.get("KneePointX")?.as_u64()? as f64 / 4095.0;
+    let knee_y = bezier_curve.get("KneePointY")?.as_u64()? as f64 / 4095.0;
+
+    // Control points, the curve starts at 0 and ends at 1
+    let mut points = vec![0.0];
+    points.extend(
+        bezier_curve
+            .get("Anchors")?
+            .as_array()?
+            .iter()
+            .filter_map(|v| v.as_u64())
+            .map(|v| v as f64 / 1023.0),
+    );
+    points.push(1.0);
+
+    let tone_map = |x: f64| -> f64 {
+        let y = if x <= knee_x {
+            x * knee_y / knee_x
+        } else {
+            let t = (x - knee_x) / (1.0 - knee_x);
+            knee_y + (1.0 - knee_y) * bernstein_polynomial(&points, t)
+        };
+
+        y.max(f64::EPSILON)
+    };
+
+    let (x1, x2) = (0.25, 0.75);
+    let (y1, y2) = (tone_map(x1), tone_map(x2));
+
+    let power = (y2 / y1).ln() / (x2 / x1).ln();
+    let slope = (y1.ln() / power - x1.ln()).exp();
+
+    // Trims are offset by 0.5, neutral at 2048
+    let to_trim = |v: f64| ((v - 0.5) * 4096.0).round().clamp(0.0, 4095.0) as u16;
+
+    Some(ExtMetadataBlockLevel2 {
+        trim_slope: to_trim(slope),
+        trim_power: to_trim(power),
+        ..ExtMetadataBlockLevel2::from_nits(100)
+    })
+}
+
+fn bernstein_polynomial(points: &[f64], t: f64) -> f64 {
+    let n = points.len() - 1;
+    let mut binomial = 1.0;
+
+    points.iter().enumerate().fold(0.0, |sum, (k, p)| {
+        let term = binomial * t.powi(k as i32) * (1.0 - t).powi((n - k) as i32) * p;
+        binomial = binomial * (n - k) as f64 / (k + 1) as f64;
+
+        sum + term
+    })
+}
+
 pub fn generate_metadata_from_madvr(
     madvr_path: &Path,
     use_custom_targets: bool,
     config: &mut GenerateConfig,
 ) -> Result<()> {
-    println!("Parsing madVR measurement file...");
-    stdout().flush().ok();
+    writeln!(status_out(), "Parsing madVR measurement file...").ok();
+    status_out().flush().ok();
 
     let madvr_info = madvr_parse::MadVRMeasurements::parse_file(madvr_path)?;
 
@@ -251,8 +609,8 @@ pub fn generate_metadata_from_madvr(
 
     for (i, scene) in madvr_info.scenes.iter().enumerate() {
         let min_pq = 0;
-        let max_pq = (scene.max_pq * 4095.0).round() as u16;
-        let avg_pq = (scene.avg_pq * 4095.0).round() as u16;
+        let max_pq = pq_to_pq12(scene.max_pq);
+        let avg_pq = pq_to_pq12(scene.avg_pq);
 
         let mut shot = VideoShot {
             start: scene.start as usize,
@@ -271,8 +629,8 @@ pub fn generate_metadata_from_madvr(
 
             frames.iter().enumerate().for_each(|(i, f)| {
                 let min_pq = 0;
-                let max_pq = (f.target_pq * 4095.0).round() as u16;
-                let avg_pq = (scene.avg_pq * 4095.0).round() as u16;
+                let max_pq = pq_to_pq12(f.target_pq);
+                let avg_pq = pq_to_pq12(scene.avg_pq);
 
                 let frame_edit = ShotFrameEdit {
                     edit_offset: i,
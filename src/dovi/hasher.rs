@@ -0,0 +1,114 @@
+use std::io::{stdout, BufWriter, Write};
+use std::path::PathBuf;
+
+use anyhow::Result;
+use crc::{Crc, CRC_64_XZ};
+
+use dolby_vision::rpu::extension_metadata::{DmData, WithExtMetadataBlocks};
+use utilities_dovi::parse_rpu_file;
+
+use crate::commands::HashArgs;
+
+use super::{input_from_either, DoviRpu};
+
+const CRC64: Crc<u64> = Crc::<u64>::new(&CRC_64_XZ);
+
+pub struct Hasher {
+    input: PathBuf,
+    output: Option<PathBuf>,
+}
+
+impl Hasher {
+    pub fn hash(args: HashArgs) -> Result<()> {
+        let HashArgs {
+            input,
+            input_pos,
+            output,
+        } = args;
+
+        let input = input_from_either("hash", input, input_pos)?;
+        let hasher = Hasher { input, output };
+
+        println!("Parsing RPU file...");
+        stdout().flush().ok();
+
+        if let Some(rpus) = parse_rpu_file(&hasher.input)? {
+            hasher.execute(&rpus)?;
+        }
+
+        Ok(())
+    }
+
+    fn execute(&self, rpus: &[DoviRpu]) -> Result<()> {
+        let frame_hashes = rpus
+            .iter()
+            .map(Self::rpu_hash)
+            .collect::<Result<Vec<u64>>>()?;
+
+        if let Some(output) = &self.output {
+            let mut writer = BufWriter::new(super::create_output_file(output)?);
+
+            for (frame, hash) in frame_hashes.iter().enumerate() {
+                writeln!(writer, "{} {:016x}", frame, hash)?;
+            }
+
+            writer.flush()?;
+        }
+
+        let mut unique_hashes = frame_hashes.clone();
+        unique_hashes.sort_unstable();
+        unique_hashes.dedup();
+
+        println!("Frames: {}", frame_hashes.len());
+        println!("Unique frame metadata: {}", unique_hashes.len());
+        println!("RPU hash: {:016x}", Self::file_hash(&frame_hashes));
+
+        Ok(())
+    }
+
+    /// Hash of the whole file, from the hashes of its frames.
+    /// It depends on the frame order.
+    pub fn file_hash(frame_hashes: &[u64]) -> u64 {
+        let mut digest = CRC64.digest();
+        frame_hashes
+            .iter()
+            .for_each(|hash| digest.update(&hash.to_be_bytes()));
+
+        digest.finalize()
+    }
+
+    /// Hash of the parsed metadata of an RPU.
+    ///
+    /// The CRC32, the trailing alignment bits and the order of the extension blocks
+    /// only depend on the encoding, so they are not part of the hash.
+    pub fn rpu_hash(rpu: &DoviRpu) -> Result<u64> {
+        let mut rpu = rpu.clone();
+
+        rpu.rpu_data_crc32 = 0;
+        rpu.remaining.clear();
+
+        if let Some(vdr_dm_data) = rpu.vdr_dm_data.as_mut() {
+            let dm_data = [
+                vdr_dm_data.cmv29_metadata.as_mut(),
+                vdr_dm_data.cmv40_metadata.as_mut(),
+            ];
+
+            for dm_data in dm_data.into_iter().flatten() {
+                let blocks = match dm_data {
+                    DmData::V29(meta) => meta.blocks_mut(),
+                    DmData::V40(meta) => meta.blocks_mut(),
+                };
+
+                let mut keyed_blocks = blocks
+                    .drain(..)
+                    .map(|block| Ok((block.level(), serde_json::to_string(&block)?, block)))
+                    .collect::<Result<Vec<_>>>()?;
+
+                keyed_blocks.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+                blocks.extend(keyed_blocks.into_iter().map(|(_, _, block)| block));
+            }
+        }
+
+        Ok(CRC64.checksum(&serde_json::to_vec(&rpu)?))
+    }
+}
@@ -0,0 +1,348 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{bail, ensure, Result};
+
+use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlock;
+use dolby_vision::rpu::extension_metadata::primaries::PREDEFINED_COLORSPACE_PRIMARIES;
+use dolby_vision::utils::add_start_code_emulation_prevention_3_byte;
+use hevc_parser::hevc::NAL_SEI_PREFIX;
+
+use super::sei_filter::sei_payloads;
+use super::DoviRpu;
+
+const MASTERING_DISPLAY_COLOUR_VOLUME: u8 = 137;
+const CONTENT_LIGHT_LEVEL_INFO: u8 = 144;
+
+/// MDCV chromaticities are in increments of 0.00002
+const CHROMATICITY_SCALE: f64 = 50000.0;
+
+/// DCI-P3 D65, when the RPU has no L9 metadata
+const DEFAULT_PRIMARY_INDEX: usize = 0;
+
+/// Names of the first predefined primaries, matched from the MDCV values
+const PRIMARIES_NAMES: &[&str] = &["Display P3", "BT.709", "BT.2020"];
+
+/// Mastering display colour volume, in the x265 `--master-display` format:
+/// `G(x,y)B(x,y)R(x,y)WP(x,y)L(max,min)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MasteringDisplay {
+    /// Green, blue and red primaries, in increments of 0.00002
+    pub primaries: [(u16, u16); 3],
+    pub white_point: (u16, u16),
+
+    /// In units of 0.0001 nits
+    pub max_luminance: u32,
+    pub min_luminance: u32,
+}
+
+/// Content light level, in the x265 `--max-cll` format: `MaxCLL,MaxFALL`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentLightLevel {
+    pub max_cll: u16,
+    pub max_fall: u16,
+}
+
+/// Static HDR10 metadata to insert as SEI messages.
+/// The values not set are derived from the L6 and L9 metadata of the first RPU.
+#[derive(Debug, Clone, Default)]
+pub struct Hdr10SeiOpts {
+    pub mastering_display: Option<MasteringDisplay>,
+    pub content_light_level: Option<ContentLightLevel>,
+}
+
+impl Hdr10SeiOpts {
+    /// The SEI NAL unit, if every value is set without the RPU
+    pub fn static_sei_nal(&self) -> Option<Vec<u8>> {
+        match (self.mastering_display, self.content_light_level) {
+            (Some(mastering_display), Some(content_light_level)) => {
+                Some(encode_sei_nal(&mastering_display, &content_light_level))
+            }
+            _ => None,
+        }
+    }
+
+    /// The SEI NAL unit, with the missing values from the RPU
+    pub fn sei_nal_from_rpu(&self, rpu: Option<&DoviRpu>) -> Result<Vec<u8>> {
+        let vdr_dm_data = rpu.and_then(|rpu| rpu.vdr_dm_data.as_ref());
+
+        let level6 = match vdr_dm_data.and_then(|dm| dm.get_block(6)) {
+            Some(ExtMetadataBlock::Level6(level6)) => Some(level6),
+            _ => None,
+        };
+
+        let mastering_display = match (self.mastering_display, level6) {
+            (Some(mastering_display), _) => mastering_display,
+            (None, Some(level6)) => {
+                let primary_index = match vdr_dm_data.and_then(|dm| dm.get_block(9)) {
+                    Some(ExtMetadataBlock::Level9(level9))
+                        if (level9.source_primary_index as usize)
+                            < PREDEFINED_COLORSPACE_PRIMARIES.len() =>
+                    {
+                        level9.source_primary_index as usize
+                    }
+                    _ => DEFAULT_PRIMARY_INDEX,
+                };
+
+                MasteringDisplay::from_l6(
+                    primary_index,
+                    level6.max_display_mastering_luminance,
+                    level6.min_display_mastering_luminance,
+                )
+            }
+            (None, None) => {
+                bail!("HDR10 SEI: no L6 metadata in the RPU, use --master-display and --max-cll")
+            }
+        };
+
+        let content_light_level = match (self.content_light_level, level6) {
+            (Some(content_light_level), _) => content_light_level,
+            (None, Some(level6)) => ContentLightLevel {
+                max_cll: level6.max_content_light_level,
+                max_fall: level6.max_frame_average_light_level,
+            },
+            (None, None) => {
+                bail!("HDR10 SEI: no L6 metadata in the RPU, use --master-display and --max-cll")
+            }
+        };
+
+        Ok(encode_sei_nal(&mastering_display, &content_light_level))
+    }
+}
+
+/// Mastering display and content light level messages of a SEI NAL unit, including the NAL header
+pub fn parse_hdr10_sei(
+    data: &[u8],
+) -> Result<(Option<MasteringDisplay>, Option<ContentLightLevel>)> {
+    let mut mastering_display = None;
+    let mut content_light_level = None;
+
+    for (payload_type, payload) in sei_payloads(data)? {
+        match payload_type {
+            p if p == MASTERING_DISPLAY_COLOUR_VOLUME as u64 => {
+                mastering_display = Some(MasteringDisplay::from_sei_payload(&payload)?);
+            }
+            p if p == CONTENT_LIGHT_LEVEL_INFO as u64 => {
+                content_light_level = Some(ContentLightLevel::from_sei_payload(&payload)?);
+            }
+            _ => (),
+        }
+    }
+
+    Ok((mastering_display, content_light_level))
+}
+
+impl MasteringDisplay {
+    /// L6 luminances are in nits for the max, and 0.0001 nits for the min
+    fn from_l6(primary_index: usize, max_luminance: u16, min_luminance: u16) -> Self {
+        let chromaticity = |v: f64| (v * CHROMATICITY_SCALE).round() as u16;
+        let p = &PREDEFINED_COLORSPACE_PRIMARIES[primary_index];
+
+        Self {
+            primaries: [
+                (chromaticity(p[2]), chromaticity(p[3])),
+                (chromaticity(p[4]), chromaticity(p[5])),
+                (chromaticity(p[0]), chromaticity(p[1])),
+            ],
+            white_point: (chromaticity(p[6]), chromaticity(p[7])),
+            max_luminance: max_luminance as u32 * 10000,
+            min_luminance: min_luminance as u32,
+        }
+    }
+
+    fn from_sei_payload(payload: &[u8]) -> Result<Self> {
+        ensure!(
+            payload.len() >= 24,
+            "Invalid mastering display SEI payload size: {}",
+            payload.len()
+        );
+
+        let u16_at = |i: usize| u16::from_be_bytes([payload[i], payload[i + 1]]);
+        let u32_at = |i: usize| {
+            u32::from_be_bytes([payload[i], payload[i + 1], payload[i + 2], payload[i + 3]])
+        };
+
+        Ok(Self {
+            primaries: [
+                (u16_at(0), u16_at(2)),
+                (u16_at(4), u16_at(6)),
+                (u16_at(8), u16_at(10)),
+            ],
+            white_point: (u16_at(12), u16_at(14)),
+            max_luminance: u32_at(16),
+            min_luminance: u32_at(20),
+        })
+    }
+
+    /// Name of the primaries, when they match predefined primaries
+    pub fn primaries_name(&self) -> Option<&'static str> {
+        let close = |a: (u16, u16), b: (u16, u16)| {
+            (a.0 as i32 - b.0 as i32).abs() <= 2 && (a.1 as i32 - b.1 as i32).abs() <= 2
+        };
+
+        PRIMARIES_NAMES
+            .iter()
+            .enumerate()
+            .find(|(index, _)| {
+                let predefined = Self::from_l6(*index, 0, 0);
+
+                self.primaries
+                    .iter()
+                    .zip(predefined.primaries.iter())
+                    .all(|(a, b)| close(*a, *b))
+                    && close(self.white_point, predefined.white_point)
+            })
+            .map(|(_, name)| *name)
+    }
+}
+
+impl ContentLightLevel {
+    fn from_sei_payload(payload: &[u8]) -> Result<Self> {
+        ensure!(
+            payload.len() >= 4,
+            "Invalid content light level SEI payload size: {}",
+            payload.len()
+        );
+
+        Ok(Self {
+            max_cll: u16::from_be_bytes([payload[0], payload[1]]),
+            max_fall: u16::from_be_bytes([payload[2], payload[3]]),
+        })
+    }
+}
+
+impl fmt::Display for MasteringDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [g, b, r] = self.primaries;
+
+        write!(
+            f,
+            "G({},{})B({},{})R({},{})WP({},{})L({},{})",
+            g.0,
+            g.1,
+            b.0,
+            b.1,
+            r.0,
+            r.1,
+            self.white_point.0,
+            self.white_point.1,
+            self.max_luminance,
+            self.min_luminance
+        )
+    }
+}
+
+impl FromStr for MasteringDisplay {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut values = Vec::with_capacity(10);
+        let mut rest = s.trim();
+
+        for label in ["G", "B", "R", "WP", "L"] {
+            let inner = rest
+                .strip_prefix(label)
+                .and_then(|r| r.strip_prefix('('))
+                .and_then(|r| r.split_once(')'));
+
+            let (inner, next) = match inner {
+                Some(split) => split,
+                None => bail!(
+                    "Invalid mastering display {}, expected G(x,y)B(x,y)R(x,y)WP(x,y)L(max,min)",
+                    s
+                ),
+            };
+
+            let (a, b) = match inner.split_once(',') {
+                Some(pair) => pair,
+                None => bail!("Invalid mastering display {}: {}({})", s, label, inner),
+            };
+
+            values.push(a.trim().parse::<u32>()?);
+            values.push(b.trim().parse::<u32>()?);
+
+            rest = next;
+        }
+
+        ensure!(rest.is_empty(), "Invalid mastering display {}", s);
+
+        let chromaticity = |v: u32| -> Result<u16> {
+            ensure!(
+                v <= CHROMATICITY_SCALE as u32,
+                "Invalid mastering display chromaticity {}, max 50000",
+                v
+            );
+
+            Ok(v as u16)
+        };
+
+        let mastering_display = Self {
+            primaries: [
+                (chromaticity(values[0])?, chromaticity(values[1])?),
+                (chromaticity(values[2])?, chromaticity(values[3])?),
+                (chromaticity(values[4])?, chromaticity(values[5])?),
+            ],
+            white_point: (chromaticity(values[6])?, chromaticity(values[7])?),
+            max_luminance: values[8],
+            min_luminance: values[9],
+        };
+
+        ensure!(
+            mastering_display.min_luminance < mastering_display.max_luminance,
+            "Invalid mastering display luminance: min {} is not below max {}",
+            mastering_display.min_luminance,
+            mastering_display.max_luminance
+        );
+
+        Ok(mastering_display)
+    }
+}
+
+impl FromStr for ContentLightLevel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (max_cll, max_fall) = match s.split_once(',') {
+            Some(pair) => pair,
+            None => bail!("Invalid content light level {}, expected MaxCLL,MaxFALL", s),
+        };
+
+        Ok(Self {
+            max_cll: max_cll.trim().parse()?,
+            max_fall: max_fall.trim().parse()?,
+        })
+    }
+}
+
+/// A SEI prefix NAL unit with the MDCV and CLL messages, including the NAL header
+fn encode_sei_nal(
+    mastering_display: &MasteringDisplay,
+    content_light_level: &ContentLightLevel,
+) -> Vec<u8> {
+    let mut rbsp = Vec::with_capacity(36);
+
+    rbsp.extend_from_slice(&[MASTERING_DISPLAY_COLOUR_VOLUME, 24]);
+
+    for (x, y) in mastering_display.primaries {
+        rbsp.extend_from_slice(&x.to_be_bytes());
+        rbsp.extend_from_slice(&y.to_be_bytes());
+    }
+
+    rbsp.extend_from_slice(&mastering_display.white_point.0.to_be_bytes());
+    rbsp.extend_from_slice(&mastering_display.white_point.1.to_be_bytes());
+    rbsp.extend_from_slice(&mastering_display.max_luminance.to_be_bytes());
+    rbsp.extend_from_slice(&mastering_display.min_luminance.to_be_bytes());
+
+    rbsp.extend_from_slice(&[CONTENT_LIGHT_LEVEL_INFO, 4]);
+    rbsp.extend_from_slice(&content_light_level.max_cll.to_be_bytes());
+    rbsp.extend_from_slice(&content_light_level.max_fall.to_be_bytes());
+
+    // rbsp_trailing_bits()
+    rbsp.push(0x80);
+
+    let mut data = vec![NAL_SEI_PREFIX << 1, 0x01];
+    data.extend(rbsp);
+    add_start_code_emulation_prevention_3_byte(&mut data);
+
+    data
+}
@@ -0,0 +1,192 @@
+use anyhow::Result;
+use hdr10plus::metadata::{BezierCurve, DistributionMaxRgb, Hdr10PlusMetadata};
+
+use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlock;
+use dolby_vision::utils::{pq12_to_pq, pq_to_nits};
+
+use super::DoviRpu;
+
+/// Percentages of the maxRGB distribution, for 9 percentiles
+const DISTRIBUTION_INDEXES: &[u8] = &[1, 5, 10, 25, 50, 75, 90, 95, 99];
+
+/// Number of Bezier curve anchors, the maximum allowed
+const BEZIER_CURVE_ANCHORS: usize = 9;
+
+/// Target max PQ of the 100 nits trim used for the Bezier curve
+const TRIM_TARGET_MAX_PQ: u16 = 2081;
+
+/// HDR10+ profile of the metadata derived from the RPUs
+#[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Hdr10PlusProfile {
+    /// Luminance statistics only
+    A,
+    /// Also a Bezier tone mapping curve, from the 100 nits trim
+    B,
+}
+
+/// Statistics of a scene, from the L1 and 100 nits trim of its frames
+struct SceneStats {
+    frames: usize,
+    min_pq: u16,
+    max_pq: u16,
+    avg_pq_sum: f64,
+    /// Slope and power of the 100 nits trim
+    trim: Option<(f64, f64)>,
+}
+
+/// Derives the ST 2094-40 metadata of every frame from the RPUs.
+///
+/// The statistics are computed per scene, from the scene cut flags:
+/// - MaxSCL is the scene L1 max for every component, and the average maxRGB the average of the L1 avg.
+/// - The maxRGB percentiles are interpolated in PQ, linearly from the L1 min to avg up to the 50th percentile,
+///   then quadratically from the avg to the max at the 99th percentile.
+/// - For profile B, the Bezier curve approximates the `(slope * x) ^ power` curve of the scene 100 nits trim,
+///   from the L2 metadata or the L8 metadata for CM v4.0. The curve is neutral without trim.
+///   The anchors are the curve values at regular intervals, and the targeted display is 100 nits.
+pub fn hdr10plus_from_rpus(
+    rpus: &[DoviRpu],
+    profile: Hdr10PlusProfile,
+) -> Result<Vec<Hdr10PlusMetadata>> {
+    let mut scenes: Vec<SceneStats> = Vec::new();
+
+    for rpu in rpus {
+        let vdr_dm_data = rpu.vdr_dm_data.as_ref();
+
+        let scene_cut = vdr_dm_data.is_some_and(|dm| dm.scene_refresh_flag == 1);
+
+        let (min_pq, avg_pq, max_pq) = match vdr_dm_data.and_then(|dm| dm.get_block(1)) {
+            Some(ExtMetadataBlock::Level1(l1)) => (l1.min_pq, l1.avg_pq, l1.max_pq),
+            _ => (0, 0, 0),
+        };
+
+        let trim = vdr_dm_data.and_then(|dm| {
+            let level2 = dm.level_blocks_iter(2).find_map(|block| match block {
+                ExtMetadataBlock::Level2(l2) if l2.target_max_pq == TRIM_TARGET_MAX_PQ => {
+                    Some((l2.trim_slope, l2.trim_power))
+                }
+                _ => None,
+            });
+
+            let level8 = || {
+                dm.level_blocks_iter(8).find_map(|block| match block {
+                    ExtMetadataBlock::Level8(l8) if l8.target_display_index == 1 => {
+                        Some((l8.trim_slope, l8.trim_power))
+                    }
+                    _ => None,
+                })
+            };
+
+            level2
+                .or_else(level8)
+                .map(|(slope, power)| (from_trim(slope), from_trim(power)))
+        });
+
+        match scenes.last_mut() {
+            Some(scene) if !scene_cut => {
+                scene.frames += 1;
+                scene.min_pq = scene.min_pq.min(min_pq);
+                scene.max_pq = scene.max_pq.max(max_pq);
+                scene.avg_pq_sum += avg_pq as f64;
+                scene.trim = scene.trim.or(trim);
+            }
+            _ => scenes.push(SceneStats {
+                frames: 1,
+                min_pq,
+                max_pq,
+                avg_pq_sum: avg_pq as f64,
+                trim,
+            }),
+        }
+    }
+
+    let mut list = Vec::with_capacity(rpus.len());
+
+    for scene in scenes {
+        let metadata = scene.metadata(profile);
+        metadata.validate()?;
+
+        list.extend(std::iter::repeat_n(metadata, scene.frames));
+    }
+
+    Ok(list)
+}
+
+impl SceneStats {
+    fn metadata(&self, profile: Hdr10PlusProfile) -> Hdr10PlusMetadata {
+        let min = pq12_to_pq(self.min_pq);
+        let max = pq12_to_pq(self.max_pq);
+        let avg = (self.avg_pq_sum / self.frames as f64 / 4095.0).clamp(min, max);
+
+        let max_rgb = to_luminance(max);
+
+        let distribution_maxrgb = DISTRIBUTION_INDEXES
+            .iter()
+            .map(|&percentage| {
+                let p = percentage as f64;
+
+                let pq = if p <= 50.0 {
+                    min + (avg - min) * p / 50.0
+                } else {
+                    avg + (max - avg) * ((p - 50.0) / 49.0).powi(2)
+                };
+
+                DistributionMaxRgb {
+                    percentage,
+                    percentile: to_luminance(pq),
+                }
+            })
+            .collect();
+
+        let (profile_name, bezier_curve) = match profile {
+            Hdr10PlusProfile::A => ("A", None),
+            Hdr10PlusProfile::B => ("B", Some(bezier_curve(self.trim.unwrap_or((1.0, 1.0))))),
+        };
+
+        Hdr10PlusMetadata {
+            profile: profile_name.to_string(),
+            itu_t_t35_country_code: 0xB5,
+            itu_t_t35_terminal_provider_code: 0x3C,
+            itu_t_t35_terminal_provider_oriented_code: 0x01,
+            application_identifier: 4,
+            application_version: 1,
+            num_windows: 1,
+            targeted_system_display_maximum_luminance: if bezier_curve.is_some() { 100 } else { 0 },
+            maxscl: [max_rgb; 3],
+            average_maxrgb: to_luminance(avg),
+            num_distribution_maxrgb_percentiles: DISTRIBUTION_INDEXES.len() as u8,
+            distribution_maxrgb,
+            tone_mapping_flag: bezier_curve.is_some(),
+            bezier_curve,
+            ..Default::default()
+        }
+    }
+}
+
+/// Bezier curve without knee, with the anchors sampled from the trim curve
+fn bezier_curve((slope, power): (f64, f64)) -> BezierCurve {
+    let bezier_curve_anchors = (1..=BEZIER_CURVE_ANCHORS)
+        .map(|i| {
+            let x = i as f64 / (BEZIER_CURVE_ANCHORS + 1) as f64;
+            let y = (slope * x).max(0.0).powf(power).clamp(0.0, 1.0);
+
+            (y * 1023.0).round() as u16
+        })
+        .collect();
+
+    BezierCurve {
+        knee_point_x: 0,
+        knee_point_y: 0,
+        num_bezier_curve_anchors: BEZIER_CURVE_ANCHORS as u8,
+        bezier_curve_anchors,
+    }
+}
+
+/// Trims are offset by 0.5, neutral at 2048
+fn from_trim(value: u16) -> f64 {
+    value as f64 / 4096.0 + 0.5
+}
+
+/// Luminance in units of 0.1 nits, from normalized PQ
+fn to_luminance(pq: f64) -> u32 {
+    (pq_to_nits(pq) * 10.0).round().clamp(0.0, 100_000.0) as u32
+}
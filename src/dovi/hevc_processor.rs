@@ -0,0 +1,163 @@
+use std::io::Read;
+
+use anyhow::Result;
+
+use hevc_parser::io::{processor::HevcProcessorOpts, IoFormat, IoProcessor};
+use hevc_parser::HevcParser;
+
+use super::start_code::get_offsets;
+
+/// Same as the `hevc_parser` HEVC stream processor,
+/// with the start codes found by the SIMD scan of `start_code::get_offsets`.
+///
+/// Read errors are returned, instead of ending the stream.
+pub struct HevcProcessor {
+    opts: HevcProcessorOpts,
+
+    format: IoFormat,
+    parser: HevcParser,
+
+    chunk_size: usize,
+
+    main_buf: Vec<u8>,
+    sec_buf: Vec<u8>,
+    consumed: usize,
+
+    chunk: Vec<u8>,
+    end: Vec<u8>,
+    offsets: Vec<usize>,
+
+    last_buffered_frame: u64,
+}
+
+impl HevcProcessor {
+    pub fn new(format: IoFormat, opts: HevcProcessorOpts, chunk_size: usize) -> Self {
+        let sec_buf = if format == IoFormat::RawStdin {
+            vec![0; 50_000]
+        } else {
+            Vec::new()
+        };
+
+        Self {
+            opts,
+            format,
+            parser: HevcParser::default(),
+
+            chunk_size,
+            main_buf: vec![0; chunk_size],
+            sec_buf,
+            consumed: 0,
+
+            chunk: Vec::with_capacity(chunk_size),
+            end: Vec::with_capacity(chunk_size),
+            offsets: Vec::with_capacity(2048),
+
+            last_buffered_frame: 0,
+        }
+    }
+
+    /// Fully parse the input stream
+    pub fn process_io(
+        &mut self,
+        reader: &mut dyn Read,
+        processor: &mut dyn IoProcessor,
+    ) -> Result<()> {
+        self.parse_nalus(reader, processor)?;
+
+        self.parser.finish();
+
+        processor.finalize(&self.parser)?;
+
+        Ok(())
+    }
+
+    /// Parses the NAL units until the end of the stream,
+    /// or until a complete frame is parsed with `buffer_frame`.
+    pub fn parse_nalus(
+        &mut self,
+        reader: &mut dyn Read,
+        processor: &mut dyn IoProcessor,
+    ) -> Result<()> {
+        loop {
+            let mut read_bytes = reader.read(&mut self.main_buf)?;
+            if read_bytes == 0 && self.end.is_empty() && self.chunk.is_empty() {
+                break;
+            }
+
+            if self.format == IoFormat::RawStdin {
+                self.chunk.extend_from_slice(&self.main_buf[..read_bytes]);
+
+                loop {
+                    let num = reader.read(&mut self.sec_buf)?;
+                    if num > 0 {
+                        read_bytes += num;
+
+                        self.chunk.extend_from_slice(&self.sec_buf[..num]);
+
+                        if read_bytes >= self.chunk_size {
+                            break;
+                        }
+                    } else {
+                        break;
+                    }
+                }
+            } else if read_bytes < self.chunk_size {
+                self.chunk.extend_from_slice(&self.main_buf[..read_bytes]);
+            } else {
+                self.chunk.extend_from_slice(&self.main_buf);
+            }
+
+            get_offsets(&self.chunk, &mut self.offsets);
+
+            if self.offsets.is_empty() {
+                continue;
+            }
+
+            let last = if read_bytes < self.chunk_size {
+                *self.offsets.last().unwrap()
+            } else {
+                let last = self.offsets.pop().unwrap();
+
+                self.end.clear();
+                self.end.extend_from_slice(&self.chunk[last..]);
+
+                last
+            };
+
+            let nals =
+                self.parser
+                    .split_nals(&self.chunk, &self.offsets, last, self.opts.parse_nals)?;
+
+            processor.process_nals(&self.parser, &nals, &self.chunk)?;
+
+            self.chunk.clear();
+
+            if !self.end.is_empty() {
+                self.chunk.extend_from_slice(&self.end);
+                self.end.clear()
+            }
+
+            self.consumed += read_bytes;
+
+            if self.consumed >= 100_000_000 {
+                processor.update_progress(1);
+                self.consumed = 0;
+            }
+
+            if self.opts.buffer_frame {
+                let next_frame = nals.iter().map(|nal| nal.decoded_frame_index).max();
+
+                if let Some(number) = next_frame {
+                    if number > self.last_buffered_frame {
+                        self.last_buffered_frame = number;
+
+                        // Stop reading
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
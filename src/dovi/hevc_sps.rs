@@ -0,0 +1,325 @@
+use anyhow::{ensure, Result};
+use bitvec_helpers::bitvec_reader::BitVecReader;
+use hevc_parser::utils::clear_start_code_emulation_prevention_3_byte;
+
+/// Unspecified colour description value
+const UNSPECIFIED: u8 = 2;
+
+/// Values of the SPS used in the decoder configuration and stream info
+pub struct SpsInfo {
+    pub vps_id: u8,
+    pub sps_id: u8,
+    /// Profile, tier and level bytes, copied as is
+    pub general_ptl: [u8; 12],
+    pub max_sub_layers_minus1: u8,
+    pub temporal_id_nesting_flag: bool,
+    pub chroma_format_idc: u8,
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth_luma_minus8: u8,
+    pub bit_depth_chroma_minus8: u8,
+
+    /// From the VUI, when the video signal type is present
+    pub video_signal: Option<VideoSignal>,
+}
+
+/// VUI video signal type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VideoSignal {
+    pub full_range: bool,
+    pub colour_primaries: u8,
+    pub transfer_characteristics: u8,
+    pub matrix_coeffs: u8,
+}
+
+impl SpsInfo {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let bytes = clear_start_code_emulation_prevention_3_byte(data);
+        ensure!(bytes.len() > 15, "Invalid SPS");
+
+        let mut general_ptl = [0; 12];
+        general_ptl.copy_from_slice(&bytes[3..15]);
+
+        // NAL header
+        let mut reader = BitVecReader::new(bytes);
+        reader.skip_n(16);
+
+        let vps_id = reader.get_n(4);
+
+        let max_sub_layers_minus1: u8 = reader.get_n(3);
+        let temporal_id_nesting_flag = reader.get()?;
+
+        // General profile, tier and level
+        reader.skip_n(96);
+
+        let sub_layers = max_sub_layers_minus1 as usize;
+        let mut sub_layer_flags = Vec::with_capacity(sub_layers);
+
+        for _ in 0..sub_layers {
+            sub_layer_flags.push((reader.get()?, reader.get()?));
+        }
+
+        if sub_layers > 0 {
+            reader.skip_n(2 * (8 - sub_layers));
+        }
+
+        for (profile_present, level_present) in sub_layer_flags {
+            if profile_present {
+                reader.skip_n(88);
+            }
+
+            if level_present {
+                reader.skip_n(8);
+            }
+        }
+
+        let sps_id = reader.get_ue()? as u8;
+
+        let chroma_format_idc = reader.get_ue()? as u8;
+
+        if chroma_format_idc == 3 {
+            // separate_colour_plane_flag
+            reader.get()?;
+        }
+
+        let mut width = reader.get_ue()? as u32;
+        let mut height = reader.get_ue()? as u32;
+
+        if reader.get()? {
+            let (sub_width, sub_height) = match chroma_format_idc {
+                1 => (2, 2),
+                2 => (2, 1),
+                _ => (1, 1),
+            };
+
+            let left = reader.get_ue()? as u32;
+            let right = reader.get_ue()? as u32;
+            let top = reader.get_ue()? as u32;
+            let bottom = reader.get_ue()? as u32;
+
+            width -= (left + right) * sub_width;
+            height -= (top + bottom) * sub_height;
+        }
+
+        let bit_depth_luma_minus8 = reader.get_ue()? as u8;
+        let bit_depth_chroma_minus8 = reader.get_ue()? as u8;
+
+        // Only informative, an unsupported SPS doesn't fail the parsing
+        let video_signal = parse_video_signal(&mut reader, max_sub_layers_minus1).unwrap_or(None);
+
+        Ok(Self {
+            vps_id,
+            sps_id,
+            general_ptl,
+            max_sub_layers_minus1,
+            temporal_id_nesting_flag,
+            chroma_format_idc,
+            width,
+            height,
+            bit_depth_luma_minus8,
+            bit_depth_chroma_minus8,
+            video_signal,
+        })
+    }
+}
+
+/// Parses the rest of the SPS after the bit depths, up to the VUI video signal type
+fn parse_video_signal(
+    reader: &mut BitVecReader,
+    max_sub_layers_minus1: u8,
+) -> Result<Option<VideoSignal>> {
+    let log2_max_poc_lsb = reader.get_ue()? + 4;
+
+    let sub_layer_ordering_info_present_flag = reader.get()?;
+    let first_sub_layer = if sub_layer_ordering_info_present_flag {
+        0
+    } else {
+        max_sub_layers_minus1
+    };
+
+    for _ in first_sub_layer..=max_sub_layers_minus1 {
+        // max_dec_pic_buffering_minus1, max_num_reorder_pics, max_latency_increase_plus1
+        reader.get_ue()?;
+        reader.get_ue()?;
+        reader.get_ue()?;
+    }
+
+    // Coding and transform block sizes, transform hierarchy depths
+    for _ in 0..6 {
+        reader.get_ue()?;
+    }
+
+    // scaling_list_enabled_flag, sps_scaling_list_data_present_flag
+    if reader.get()? && reader.get()? {
+        skip_scaling_list_data(reader)?;
+    }
+
+    // amp_enabled_flag, sample_adaptive_offset_enabled_flag
+    reader.skip_n(2);
+
+    // pcm_enabled_flag
+    if reader.get()? {
+        reader.skip_n(8);
+        reader.get_ue()?;
+        reader.get_ue()?;
+        reader.skip_n(1);
+    }
+
+    let num_short_term_ref_pic_sets = reader.get_ue()? as usize;
+    let mut num_delta_pocs: Vec<u64> = Vec::with_capacity(num_short_term_ref_pic_sets);
+
+    for idx in 0..num_short_term_ref_pic_sets {
+        let inter_ref_pic_set_prediction_flag = idx != 0 && reader.get()?;
+
+        if inter_ref_pic_set_prediction_flag {
+            // delta_rps_sign, abs_delta_rps_minus1
+            reader.skip_n(1);
+            reader.get_ue()?;
+
+            let mut count = 0;
+
+            for _ in 0..=num_delta_pocs[idx - 1] {
+                let used_by_curr_pic_flag = reader.get()?;
+                let use_delta_flag = used_by_curr_pic_flag || reader.get()?;
+
+                if use_delta_flag {
+                    count += 1;
+                }
+            }
+
+            num_delta_pocs.push(count);
+        } else {
+            let num_negative_pics = reader.get_ue()?;
+            let num_positive_pics = reader.get_ue()?;
+
+            for _ in 0..num_negative_pics + num_positive_pics {
+                // delta_poc_minus1, used_by_curr_pic_flag
+                reader.get_ue()?;
+                reader.skip_n(1);
+            }
+
+            num_delta_pocs.push(num_negative_pics + num_positive_pics);
+        }
+    }
+
+    // long_term_ref_pics_present_flag
+    if reader.get()? {
+        let num_long_term_ref_pics_sps = reader.get_ue()?;
+
+        for _ in 0..num_long_term_ref_pics_sps {
+            reader.skip_n(log2_max_poc_lsb as usize + 1);
+        }
+    }
+
+    // sps_temporal_mvp_enabled_flag, strong_intra_smoothing_enabled_flag
+    reader.skip_n(2);
+
+    // vui_parameters_present_flag
+    if !reader.get()? {
+        return Ok(None);
+    }
+
+    // aspect_ratio_info_present_flag
+    if reader.get()? {
+        let aspect_ratio_idc: u8 = reader.get_n(8);
+
+        // EXTENDED_SAR
+        if aspect_ratio_idc == 255 {
+            reader.skip_n(32);
+        }
+    }
+
+    // overscan_info_present_flag
+    if reader.get()? {
+        reader.skip_n(1);
+    }
+
+    // video_signal_type_present_flag
+    if !reader.get()? {
+        return Ok(None);
+    }
+
+    // video_format
+    reader.skip_n(3);
+
+    let full_range = reader.get()?;
+
+    let mut video_signal = VideoSignal {
+        full_range,
+        colour_primaries: UNSPECIFIED,
+        transfer_characteristics: UNSPECIFIED,
+        matrix_coeffs: UNSPECIFIED,
+    };
+
+    // colour_description_present_flag
+    if reader.get()? {
+        video_signal.colour_primaries = reader.get_n(8);
+        video_signal.transfer_characteristics = reader.get_n(8);
+        video_signal.matrix_coeffs = reader.get_n(8);
+    }
+
+    Ok(Some(video_signal))
+}
+
+fn skip_scaling_list_data(reader: &mut BitVecReader) -> Result<()> {
+    for size_id in 0..4 {
+        let step = if size_id == 3 { 3 } else { 1 };
+
+        for _ in (0..6).step_by(step) {
+            // scaling_list_pred_mode_flag
+            if !reader.get()? {
+                // scaling_list_pred_matrix_id_delta
+                reader.get_ue()?;
+            } else {
+                let coef_num = 64.min(1 << (4 + (size_id << 1)));
+
+                if size_id > 1 {
+                    // scaling_list_dc_coef_minus8
+                    reader.get_se()?;
+                }
+
+                for _ in 0..coef_num {
+                    // scaling_list_delta_coef
+                    reader.get_se()?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+impl VideoSignal {
+    pub fn primaries_name(&self) -> String {
+        match self.colour_primaries {
+            1 => String::from("BT.709"),
+            9 => String::from("BT.2020"),
+            11 => String::from("DCI-P3"),
+            12 => String::from("Display P3"),
+            UNSPECIFIED => String::from("unspecified"),
+            v => format!("unknown ({})", v),
+        }
+    }
+
+    pub fn transfer_name(&self) -> String {
+        match self.transfer_characteristics {
+            1 | 14 | 15 => String::from("SDR"),
+            16 => String::from("PQ"),
+            18 => String::from("HLG"),
+            UNSPECIFIED => String::from("unspecified"),
+            v => format!("unknown ({})", v),
+        }
+    }
+
+    pub fn matrix_name(&self) -> String {
+        match self.matrix_coeffs {
+            0 => String::from("RGB"),
+            1 => String::from("BT.709"),
+            9 => String::from("BT.2020 non-constant"),
+            10 => String::from("BT.2020 constant"),
+            14 => String::from("ICtCp"),
+            UNSPECIFIED => String::from("unspecified"),
+            v => format!("unknown ({})", v),
+        }
+    }
+}
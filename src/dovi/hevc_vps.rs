@@ -0,0 +1,218 @@
+use anyhow::{bail, ensure, Result};
+use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
+use hevc_parser::utils::{
+    add_start_code_emulation_prevention_3_byte, clear_start_code_emulation_prevention_3_byte,
+};
+
+/// Values of the VPS describing the layers of the stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VpsInfo {
+    pub vps_id: u8,
+    pub max_layers_minus1: u8,
+    pub max_layer_id: u8,
+    pub num_layer_sets_minus1: u64,
+    pub extension_flag: bool,
+}
+
+impl VpsInfo {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        VpsRewriter::new(data)?.run(false).map(|(info, _)| info)
+    }
+
+    /// Whether the VPS describes layers other than the base layer
+    pub fn has_layers(&self) -> bool {
+        self.max_layers_minus1 > 0
+            || self.max_layer_id > 0
+            || self.num_layer_sets_minus1 > 0
+            || self.extension_flag
+    }
+}
+
+/// Rewrites the VPS NALU to describe the base layer only, without layer sets nor extension.
+/// The NAL header is included, as well as the emulation prevention bytes.
+pub fn base_layer_vps(data: &[u8]) -> Result<Vec<u8>> {
+    let (_, mut vps) = VpsRewriter::new(data)?.run(true)?;
+    add_start_code_emulation_prevention_3_byte(&mut vps);
+
+    Ok(vps)
+}
+
+/// Parses the VPS, copying it unless rewritten
+struct VpsRewriter {
+    reader: BitVecReader,
+    writer: BitVecWriter,
+}
+
+impl VpsRewriter {
+    fn new(data: &[u8]) -> Result<Self> {
+        let bytes = clear_start_code_emulation_prevention_3_byte(data);
+        ensure!(bytes.len() > 18, "Invalid VPS");
+
+        Ok(Self {
+            reader: BitVecReader::new(bytes),
+            writer: BitVecWriter::new(),
+        })
+    }
+
+    /// Copies the bits, returning the last 32 at most
+    fn copy(&mut self, n: usize) -> Result<u64> {
+        ensure!(self.reader.available() >= n, "Invalid VPS, too short");
+
+        let mut value: u64 = 0;
+        let mut remaining = n;
+
+        while remaining > 0 {
+            let bits = remaining.min(32);
+
+            value = self.reader.get_n(bits);
+            self.writer.write_n(&value.to_be_bytes(), bits);
+
+            remaining -= bits;
+        }
+
+        Ok(value)
+    }
+
+    fn copy_ue(&mut self) -> Result<u64> {
+        let value = self.reader.get_ue()?;
+        self.writer.write_ue(value);
+
+        Ok(value)
+    }
+
+    /// Writes the value when rewriting, otherwise the original one
+    fn replace(&mut self, n: usize, rewrite: bool) -> Result<u64> {
+        ensure!(self.reader.available() >= n, "Invalid VPS, too short");
+
+        let value: u64 = self.reader.get_n(n);
+        let written = if rewrite { 0 } else { value };
+        self.writer.write_n(&written.to_be_bytes(), n);
+
+        Ok(value)
+    }
+
+    fn run(mut self, rewrite: bool) -> Result<(VpsInfo, Vec<u8>)> {
+        // NAL header
+        self.copy(16)?;
+
+        let vps_id = self.copy(4)? as u8;
+
+        // vps_base_layer_internal_flag, vps_base_layer_available_flag
+        self.copy(2)?;
+
+        let max_layers_minus1 = self.replace(6, rewrite)? as u8;
+        let max_sub_layers_minus1 = self.copy(3)? as usize;
+
+        // vps_temporal_id_nesting_flag, vps_reserved_0xffff_16bits
+        self.copy(17)?;
+
+        // General profile, tier and level
+        self.copy(96)?;
+
+        let mut sub_layer_flags = Vec::with_capacity(max_sub_layers_minus1);
+
+        for _ in 0..max_sub_layers_minus1 {
+            sub_layer_flags.push((self.copy(1)? == 1, self.copy(1)? == 1));
+        }
+
+        if max_sub_layers_minus1 > 0 {
+            self.copy(2 * (8 - max_sub_layers_minus1))?;
+        }
+
+        for (profile_present, level_present) in sub_layer_flags {
+            if profile_present {
+                self.copy(88)?;
+            }
+
+            if level_present {
+                self.copy(8)?;
+            }
+        }
+
+        let sub_layer_ordering_info_present = self.copy(1)? == 1;
+        let first_sub_layer = if sub_layer_ordering_info_present {
+            0
+        } else {
+            max_sub_layers_minus1
+        };
+
+        for _ in first_sub_layer..=max_sub_layers_minus1 {
+            // vps_max_dec_pic_buffering_minus1, vps_max_num_reorder_pics, vps_max_latency_increase_plus1
+            self.copy_ue()?;
+            self.copy_ue()?;
+            self.copy_ue()?;
+        }
+
+        let max_layer_id = self.replace(6, rewrite)? as u8;
+
+        let num_layer_sets_minus1 = self.reader.get_ue()?;
+        self.writer
+            .write_ue(if rewrite { 0 } else { num_layer_sets_minus1 });
+
+        // layer_id_included_flag
+        let layer_id_flags = num_layer_sets_minus1 as usize * (max_layer_id as usize + 1);
+
+        if rewrite {
+            ensure!(
+                self.reader.available() >= layer_id_flags,
+                "Invalid VPS, too short"
+            );
+            self.reader.skip_n(layer_id_flags);
+        } else {
+            for _ in 0..layer_id_flags {
+                self.copy(1)?;
+            }
+        }
+
+        let timing_info_present = self.copy(1)? == 1;
+
+        if timing_info_present {
+            // vps_num_units_in_tick, vps_time_scale
+            self.copy(64)?;
+
+            let poc_proportional_to_timing = self.copy(1)? == 1;
+
+            if poc_proportional_to_timing {
+                self.copy_ue()?;
+            }
+
+            let num_hrd_parameters = self.copy_ue()?;
+
+            if num_hrd_parameters > 0 {
+                if rewrite {
+                    bail!("Cannot rewrite a VPS with HRD parameters");
+                }
+
+                // The extension flag follows the HRD parameters, not parsed
+                let info = VpsInfo {
+                    vps_id,
+                    max_layers_minus1,
+                    max_layer_id,
+                    num_layer_sets_minus1,
+                    extension_flag: false,
+                };
+
+                return Ok((info, Vec::new()));
+            }
+        }
+
+        let extension_flag = self.replace(1, rewrite)? == 1;
+
+        // rbsp_trailing_bits, the extension data is dropped
+        self.writer.write(true);
+
+        while !self.writer.is_aligned() {
+            self.writer.write(false);
+        }
+
+        let info = VpsInfo {
+            vps_id,
+            max_layers_minus1,
+            max_layer_id,
+            num_layer_sets_minus1,
+            extension_flag,
+        };
+
+        Ok((info, self.writer.as_slice().to_vec()))
+    }
+}
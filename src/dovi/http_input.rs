@@ -0,0 +1,636 @@
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use std::process::{Child, ChildStderr, ChildStdout, Command, Stdio};
+use std::time::Duration;
+
+use anyhow::{bail, ensure, format_err, Context, Result};
+
+/// Redirects followed before giving up
+const MAX_REDIRECTS: usize = 5;
+
+/// Requests retried from the position after a connection error
+const MAX_RETRIES: usize = 3;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Whether the input is an HTTP(S) URL rather than a file path
+pub fn is_url(input: &Path) -> bool {
+    input
+        .to_str()
+        .is_some_and(|input| input.starts_with("http://") || input.starts_with("https://"))
+}
+
+/// Remote input streamed with a single HTTP request, processed while downloaded.
+///
+/// Seeking drops the response, the next read requests a range from the new position.
+/// A connection closed before the end of the body is also resumed with a range request.
+///
+/// HTTPS requests are made with the `curl` command, which handles the TLS.
+pub struct HttpReader {
+    /// URL after the redirects
+    url: String,
+    /// Unknown for chunked responses
+    size: Option<u64>,
+    position: u64,
+
+    /// Response body from the position
+    body: Option<Body>,
+}
+
+impl HttpReader {
+    /// Requests the resource, following the redirects
+    pub fn open(url: &Path) -> Result<Self> {
+        let url = url
+            .to_str()
+            .ok_or_else(|| format_err!("Invalid URL {}", url.display()))?;
+
+        let mut reader = Self {
+            url: url.to_string(),
+            size: None,
+            position: 0,
+            body: None,
+        };
+
+        reader.body = reader
+            .request(0)
+            .with_context(|| format!("Can't open input URL {}", url))?;
+
+        Ok(reader)
+    }
+
+    pub fn size(&self) -> Option<u64> {
+        self.size
+    }
+
+    /// Requests the bytes from `start`, with a range request past the first byte.
+    /// Returns no body when `start` is past the end.
+    fn request(&mut self, start: u64) -> Result<Option<Body>> {
+        for _ in 0..=MAX_REDIRECTS {
+            let url = Url::parse(&self.url)?;
+
+            let connection = if url.https {
+                Connection::curl(&url, start)?
+            } else {
+                Connection::tcp(&url, start)?
+            };
+
+            let mut reader = BufReader::new(connection);
+            let (status, headers) = read_head(&mut reader)?;
+
+            let header = |name: &str| {
+                headers
+                    .iter()
+                    .find(|(header, _)| header == name)
+                    .map(|(_, value)| value.as_str())
+            };
+
+            let chunked = header("transfer-encoding").is_some_and(|encoding| {
+                encoding
+                    .rsplit(',')
+                    .next()
+                    .is_some_and(|encoding| encoding.trim().eq_ignore_ascii_case("chunked"))
+            });
+
+            let content_length = header("content-length")
+                .map(|length| length.parse::<u64>())
+                .transpose()
+                .map_err(|_| format_err!("Invalid Content-Length"))?;
+
+            // curl decodes the chunks, the body then ends with its output
+            let framing = match (chunked, content_length) {
+                (true, _) if url.https => Framing::Close,
+                (true, _) => Framing::Chunked {
+                    remaining: 0,
+                    done: false,
+                },
+                (false, Some(length)) => Framing::Length(length),
+                (false, None) => Framing::Close,
+            };
+
+            match status {
+                200 => {
+                    ensure!(
+                        start == 0,
+                        "The server doesn't support range requests, can't read from byte {}",
+                        start
+                    );
+
+                    self.size = if chunked { None } else { content_length };
+
+                    return Ok(Some(Body { reader, framing }));
+                }
+                206 => {
+                    let (range_start, size) = header("content-range")
+                        .and_then(parse_content_range)
+                        .ok_or_else(|| format_err!("Invalid Content-Range"))?;
+
+                    ensure!(
+                        range_start == start,
+                        "Unexpected range from byte {}, requested from {}",
+                        range_start,
+                        start
+                    );
+
+                    if size.is_some() {
+                        self.size = size;
+                    }
+
+                    return Ok(Some(Body { reader, framing }));
+                }
+                // Range not satisfiable, past the end
+                416 => return Ok(None),
+                301 | 302 | 303 | 307 | 308 => {
+                    let location = header("location")
+                        .ok_or_else(|| format_err!("HTTP redirect without Location"))?;
+
+                    self.url = url.join(location);
+                }
+                _ => bail!("HTTP error {} for {}", status, self.url),
+            }
+        }
+
+        bail!("Too many HTTP redirects for {}", self.url)
+    }
+}
+
+/// Response body, delimited by its length, its chunks or the end of the connection
+struct Body {
+    reader: BufReader<Connection>,
+    framing: Framing,
+}
+
+enum Framing {
+    Length(u64),
+    Chunked { remaining: u64, done: bool },
+    Close,
+}
+
+impl Read for Body {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        match &mut self.framing {
+            Framing::Length(remaining) => {
+                if *remaining == 0 {
+                    return Ok(0);
+                }
+
+                let max = (*remaining).min(buf.len() as u64) as usize;
+                let n = read_some(&mut self.reader, &mut buf[..max])?;
+                *remaining -= n as u64;
+
+                Ok(n)
+            }
+            Framing::Chunked { remaining, done } => {
+                if *done {
+                    return Ok(0);
+                }
+
+                if *remaining == 0 {
+                    *remaining = read_chunk_size(&mut self.reader)?;
+
+                    if *remaining == 0 {
+                        // Trailers, until the empty line
+                        while !read_line(&mut self.reader)?.is_empty() {}
+
+                        *done = true;
+                        return Ok(0);
+                    }
+                }
+
+                let max = (*remaining).min(buf.len() as u64) as usize;
+                let n = read_some(&mut self.reader, &mut buf[..max])?;
+                *remaining -= n as u64;
+
+                // The chunk data ends with a CRLF
+                if *remaining == 0 && !read_line(&mut self.reader)?.is_empty() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Invalid HTTP chunk",
+                    ));
+                }
+
+                Ok(n)
+            }
+            Framing::Close => self.reader.read(buf),
+        }
+    }
+}
+
+/// Reads at least one byte, the connection closing early is an error
+fn read_some<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    match reader.read(buf)? {
+        0 => Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "HTTP connection closed before the end of the body",
+        )),
+        n => Ok(n),
+    }
+}
+
+/// Reads a line without its line ending, failing at the end of the connection
+fn read_line<R: BufRead>(reader: &mut R) -> io::Result<String> {
+    let mut line = String::new();
+
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Incomplete HTTP response",
+        ));
+    }
+
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Parses the hexadecimal chunk size line, ignoring the extensions
+fn read_chunk_size<R: BufRead>(reader: &mut R) -> io::Result<u64> {
+    let line = read_line(reader)?;
+    let size = line.split(';').next().unwrap_or_default().trim();
+
+    u64::from_str_radix(size, 16).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid HTTP chunk size {}", line),
+        )
+    })
+}
+
+/// HTTP response, with the status line and the headers
+enum Connection {
+    Tcp(TcpStream),
+    /// HTTPS response written by curl, with its errors on stderr
+    Curl {
+        child: Child,
+        stdout: ChildStdout,
+        stderr: ChildStderr,
+    },
+}
+
+impl Connection {
+    fn tcp(url: &Url, start: u64) -> Result<Self> {
+        let mut stream = TcpStream::connect((url.host.as_str(), url.port))?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+
+        let range = if start > 0 {
+            format!("Range: bytes={}-\r\n", start)
+        } else {
+            String::new()
+        };
+
+        write!(
+            stream,
+            "GET {} HTTP/1.1\r\nHost: {}\r\n{}User-Agent: {}\r\nConnection: close\r\n\r\n",
+            url.path,
+            url.host_header(),
+            range,
+            user_agent()
+        )?;
+        stream.flush()?;
+
+        Ok(Self::Tcp(stream))
+    }
+
+    /// Same request as over TCP, the redirects are followed by the reader
+    fn curl(url: &Url, start: u64) -> Result<Self> {
+        let timeout = TIMEOUT.as_secs().to_string();
+
+        let mut command = Command::new("curl");
+        command
+            .args(["--silent", "--show-error", "--include", "--http1.1"])
+            .arg("--suppress-connect-headers")
+            .args(["--connect-timeout", &timeout])
+            .args(["--speed-limit", "1", "--speed-time", &timeout]);
+
+        if start > 0 {
+            command.arg("--range").arg(format!("{}-", start));
+        }
+
+        let mut child = command
+            .arg("--user-agent")
+            .arg(user_agent())
+            .arg("--url")
+            .arg(format!("https://{}{}", url.host_header(), url.path))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format_err!("Failed to run curl for HTTPS, is it in the PATH? {}", e))?;
+
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+
+        match (stdout, stderr) {
+            (Some(stdout), Some(stderr)) => Ok(Self::Curl {
+                child,
+                stdout,
+                stderr,
+            }),
+            _ => bail!("No output for curl"),
+        }
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.read(buf),
+            Self::Curl {
+                child,
+                stdout,
+                stderr,
+            } => {
+                let n = stdout.read(buf)?;
+
+                // A failed request is reported at the end of the output
+                if n == 0 && !buf.is_empty() {
+                    let status = child.wait()?;
+
+                    if !status.success() {
+                        let mut error = String::new();
+                        stderr.read_to_string(&mut error).ok();
+
+                        let error = match error.trim() {
+                            "" => format!("curl failed, {}", status),
+                            error => error.to_string(),
+                        };
+
+                        return Err(io::Error::other(error));
+                    }
+                }
+
+                Ok(n)
+            }
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // The rest of the response is not needed, after a seek
+        if let Self::Curl { child, .. } = self {
+            child.kill().ok();
+            child.wait().ok();
+        }
+    }
+}
+
+fn user_agent() -> String {
+    format!("dovi_tool/{}", env!("CARGO_PKG_VERSION"))
+}
+
+impl Read for HttpReader {
+    /// Fills the buffer until the end of the input, like a file.
+    /// The HEVC processor takes a short read as the end of the input.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut filled = 0;
+
+        while filled < buf.len() {
+            match self.read_body(&mut buf[filled..])? {
+                0 => break,
+                n => filled += n,
+            }
+        }
+
+        Ok(filled)
+    }
+}
+
+impl HttpReader {
+    /// Reads from the response, requesting the rest from the position after a seek or an error
+    fn read_body(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut retries = 0;
+
+        loop {
+            if buf.is_empty() || self.size.is_some_and(|size| self.position >= size) {
+                return Ok(0);
+            }
+
+            if self.body.is_none() {
+                self.body = match self.request(self.position) {
+                    Ok(Some(body)) => Some(body),
+                    Ok(None) => return Ok(0),
+                    Err(e) => return Err(io::Error::other(format!("{:#}", e))),
+                };
+            }
+
+            match self.body.as_mut().unwrap().read(buf) {
+                Ok(n) => {
+                    self.position += n as u64;
+                    return Ok(n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => {
+                    // Connection closed or failed, the rest is requested again from the position
+                    self.body = None;
+                    retries += 1;
+
+                    if retries > MAX_RETRIES {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Seek for HttpReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => offset_position(self.position, offset),
+            SeekFrom::End(offset) => {
+                let size = self.size.ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::Unsupported,
+                        "Can't seek from the end of a chunked HTTP response",
+                    )
+                })?;
+
+                offset_position(size, offset)
+            }
+        };
+
+        let position = position.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative position",
+            )
+        })?;
+
+        if position != self.position {
+            self.body = None;
+            self.position = position;
+        }
+
+        Ok(position)
+    }
+}
+
+fn offset_position(base: u64, offset: i64) -> Option<u64> {
+    if offset >= 0 {
+        base.checked_add(offset as u64)
+    } else {
+        base.checked_sub(offset.unsigned_abs())
+    }
+}
+
+/// Reads the status code and the headers, with lowercase names.
+/// The informational responses before the final one are skipped.
+fn read_head<R: BufRead>(reader: &mut R) -> Result<(u16, Vec<(String, String)>)> {
+    loop {
+        let line = read_line(reader)?;
+
+        let status = line
+            .split_whitespace()
+            .nth(1)
+            .filter(|_| line.starts_with("HTTP/"))
+            .and_then(|status| status.parse::<u16>().ok())
+            .ok_or_else(|| format_err!("Invalid HTTP response: {}", line))?;
+
+        let mut headers = Vec::new();
+
+        loop {
+            let header = read_line(reader)?;
+
+            if header.is_empty() {
+                break;
+            }
+
+            if let Some((name, value)) = header.split_once(':') {
+                headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+            }
+        }
+
+        if !(100..200).contains(&status) {
+            return Ok((status, headers));
+        }
+    }
+}
+
+/// Parses the start and the size of `bytes start-end/size`, the size being `*` when unknown
+fn parse_content_range(value: &str) -> Option<(u64, Option<u64>)> {
+    let (range, size) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let (start, _) = range.split_once('-')?;
+
+    let size = match size {
+        "*" => None,
+        size => Some(size.parse().ok()?),
+    };
+
+    Some((start.parse().ok()?, size))
+}
+
+struct Url {
+    https: bool,
+    host: String,
+    port: u16,
+    /// Path with the query
+    path: String,
+}
+
+impl Url {
+    fn parse(url: &str) -> Result<Self> {
+        let (https, rest) = match url.strip_prefix("https://") {
+            Some(rest) => (true, rest),
+            None => (
+                false,
+                url.strip_prefix("http://")
+                    .ok_or_else(|| format_err!("Invalid URL {}", url))?,
+            ),
+        };
+
+        let (authority, path) = match rest.find(['/', '?']) {
+            Some(i) if rest[i..].starts_with('?') => (&rest[..i], format!("/{}", &rest[i..])),
+            Some(i) => (&rest[..i], rest[i..].to_string()),
+            None => (rest, "/".to_string()),
+        };
+
+        ensure!(
+            !authority.is_empty() && !authority.contains('@'),
+            "Invalid URL host {}",
+            url
+        );
+
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => {
+                let port = port
+                    .parse::<u16>()
+                    .map_err(|_| format_err!("Invalid URL port {}", url))?;
+
+                (host, port)
+            }
+            _ => (authority, if https { 443 } else { 80 }),
+        };
+
+        Ok(Self {
+            https,
+            host: host
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_string(),
+            port,
+            path: path.replace(' ', "%20"),
+        })
+    }
+
+    fn host_header(&self) -> String {
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+
+        if self.port == self.default_port() {
+            host
+        } else {
+            format!("{}:{}", host, self.port)
+        }
+    }
+
+    fn default_port(&self) -> u16 {
+        if self.https {
+            443
+        } else {
+            80
+        }
+    }
+
+    fn scheme(&self) -> &'static str {
+        if self.https {
+            "https"
+        } else {
+            "http"
+        }
+    }
+
+    /// Resolves a redirect location, absolute or relative to the URL
+    fn join(&self, location: &str) -> String {
+        if location.starts_with("http://") || location.starts_with("https://") {
+            location.to_string()
+        } else if location.starts_with('/') {
+            format!("{}://{}{}", self.scheme(), self.host_header(), location)
+        } else {
+            let dir = match self
+                .path
+                .split('?')
+                .next()
+                .and_then(|path| path.rsplit_once('/'))
+            {
+                Some((dir, _)) => dir,
+                None => "",
+            };
+
+            format!(
+                "{}://{}{}/{}",
+                self.scheme(),
+                self.host_header(),
+                dir,
+                location
+            )
+        }
+    }
+}
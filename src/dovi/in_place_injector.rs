@@ -0,0 +1,517 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use anyhow::{bail, ensure, Context, Result};
+use indicatif::ProgressBar;
+use log::warn;
+
+use hevc_parser::hevc::{Frame, NALUnit};
+use hevc_parser::io::{processor, IoProcessor};
+use hevc_parser::HevcParser;
+use processor::HevcProcessorOpts;
+
+use crate::commands::InjectRpuArgs;
+
+use super::avc_parser::AvcParser;
+use super::codec::Codec;
+use super::hevc_processor::HevcProcessor;
+use super::injection_check::InjectionCheck;
+use super::nal_parser::{FrameOrder, NalIoProcessor, NalProcessor};
+use super::rpu_injector::{prepare_rpus, MismatchFix};
+use super::rpu_stream::{self, parse_rpu_input, status_out};
+use super::vvc_parser::VvcParser;
+use super::{deterministic, input_from_either, open_hevc_input, CliOptions, DoviRpu};
+use super::{InputFormat, IoFormat};
+
+/// Replaces the RPU NAL units of a Dolby Vision file, without rewriting the rest of the file.
+///
+/// The new RPUs are written over the existing ones, padded with trailing zero bytes.
+/// The file is only shifted after the first RPU larger than the existing one.
+pub struct InPlaceInjector {
+    input: PathBuf,
+    codec: Codec,
+    progress_bar: ProgressBar,
+
+    /// Decoded frame of the RPU NAL units, in stream order
+    rpu_frames: Vec<u64>,
+    /// Presentation number of the frames, by decoded number
+    presentation_numbers: HashMap<u64, usize>,
+}
+
+/// Byte range of an RPU NAL unit in the file, from the NAL header to the trailing zero bytes
+#[derive(Debug, Clone, Copy)]
+struct NalRange {
+    start: u64,
+    end: u64,
+}
+
+struct Replacement {
+    range: NalRange,
+    nal: Vec<u8>,
+}
+
+/// Reader finding the RPU NAL units in the bytes read through it
+struct RpuNalScanner<R: Read> {
+    inner: R,
+    codec: Codec,
+
+    position: u64,
+    zero_run: usize,
+
+    /// Start and bytes of the NAL header being read
+    header: Option<(u64, Vec<u8>)>,
+    /// Start of the current NAL, if it's an RPU
+    current: Option<u64>,
+
+    nals: Vec<NalRange>,
+}
+
+impl InPlaceInjector {
+    pub fn inject(args: InjectRpuArgs, cli_options: CliOptions) -> Result<()> {
+        let InjectRpuArgs {
+            input,
+            input_pos,
+            rpu_in,
+            fix,
+            verify,
+            ..
+        } = args;
+
+        let input = input_from_either("inject-rpu", input, input_pos)?;
+
+        ensure!(
+            !rpu_stream::is_stdio(&input) && input.is_file(),
+            "inject-rpu: --in-place requires an input file"
+        );
+
+        let codec = Codec::from_input(cli_options.input_codec, &input);
+
+        let mut injector = InPlaceInjector {
+            progress_bar: super::initialize_progress_bar(&IoFormat::Raw, &input)?,
+            input,
+            codec,
+            rpu_frames: Vec::new(),
+            presentation_numbers: HashMap::new(),
+        };
+
+        writeln!(status_out(), "Parsing RPU file...").ok();
+        status_out().flush().ok();
+
+        let mut rpus = prepare_rpus(&cli_options, parse_rpu_input(&rpu_in)?)?;
+
+        writeln!(status_out(), "Finding the RPUs of the input video...").ok();
+        status_out().flush().ok();
+
+        let rpu_nals = injector.scan(cli_options.read_buffer)?;
+        let frame_count = injector.presentation_numbers.len();
+
+        injector.fix_rpu_count(&mut rpus, frame_count, fix)?;
+
+        let replacements = injector.replacements(&rpu_nals, &rpus)?;
+
+        writeln!(
+            status_out(),
+            "Replacing {} RPUs in place...",
+            replacements.len()
+        )
+        .ok();
+        status_out().flush().ok();
+
+        injector.write_replacements(&replacements, cli_options.write_buffer)?;
+
+        // The input is the output
+        deterministic::record_output(&injector.input);
+
+        if verify {
+            InjectionCheck::check(&injector.input, codec, &rpus, cli_options.read_buffer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Parses the frame order, and finds the byte ranges of the RPU NAL units in stream order
+    fn scan(&mut self, chunk_size: usize) -> Result<Vec<NalRange>> {
+        let reader = open_hevc_input(
+            &IoFormat::Raw,
+            &self.input,
+            InputFormat::AnnexB,
+            &self.progress_bar,
+            chunk_size,
+        )?;
+
+        let mut scanner = RpuNalScanner::new(reader, self.codec);
+
+        match self.codec {
+            Codec::Hevc => {
+                let mut processor =
+                    HevcProcessor::new(IoFormat::Raw, HevcProcessorOpts::default(), chunk_size);
+
+                processor.process_io(&mut scanner, self)?;
+            }
+            Codec::Avc => {
+                NalProcessor::<AvcParser>::new(chunk_size).process_io(&mut scanner, self)?
+            }
+            Codec::Vvc => {
+                NalProcessor::<VvcParser>::new(chunk_size).process_io(&mut scanner, self)?
+            }
+        }
+
+        let rpu_nals = scanner.finish();
+
+        ensure!(
+            rpu_nals.len() == self.rpu_frames.len(),
+            "inject-rpu: found {} RPU NAL units, but {} were parsed",
+            rpu_nals.len(),
+            self.rpu_frames.len()
+        );
+
+        Ok(rpu_nals)
+    }
+
+    fn add_rpu_frames(&mut self, nals: &[NALUnit]) {
+        let rpu_nal_type = self.codec.rpu_nal_type();
+
+        self.rpu_frames.extend(
+            nals.iter()
+                .filter(|nal| nal.nal_type == rpu_nal_type)
+                .map(|nal| nal.decoded_frame_index),
+        );
+    }
+
+    fn set_frames(&mut self, ordered_frames: &[Frame]) {
+        self.progress_bar.finish_and_clear();
+
+        self.presentation_numbers = ordered_frames
+            .iter()
+            .map(|frame| (frame.decoded_number, frame.presentation_number as usize))
+            .collect();
+    }
+
+    /// Matches the RPU count to the frame count, without cutting the video
+    fn fix_rpu_count(
+        &self,
+        rpus: &mut Vec<DoviRpu>,
+        frame_count: usize,
+        fix: MismatchFix,
+    ) -> Result<()> {
+        if rpus.len() == frame_count {
+            return Ok(());
+        }
+
+        ensure!(
+            fix != MismatchFix::Fail,
+            "inject-rpu: mismatched lengths. video {}, RPU {}",
+            frame_count,
+            rpus.len()
+        );
+
+        warn!(
+            "mismatched lengths. video {}, RPU {}",
+            frame_count,
+            rpus.len()
+        );
+
+        if rpus.len() > frame_count {
+            writeln!(
+                status_out(),
+                "Metadata will be skipped at the end to match video length\n"
+            )
+            .ok();
+
+            rpus.truncate(frame_count);
+        } else {
+            ensure!(
+                fix != MismatchFix::Truncate,
+                "inject-rpu: --fix truncate cannot be used with --in-place, the video can't be cut in place"
+            );
+
+            writeln!(
+                status_out(),
+                "Metadata will be duplicated at the end to match video length\n"
+            )
+            .ok();
+
+            if let Some(last_rpu) = rpus.last().cloned() {
+                rpus.resize(frame_count, last_rpu);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The new RPU NAL unit of every existing one, which must be the only RPU of its frame
+    fn replacements(&self, rpu_nals: &[NalRange], rpus: &[DoviRpu]) -> Result<Vec<Replacement>> {
+        let mut rpu_counts: HashMap<u64, usize> = HashMap::new();
+        for decoded_number in &self.rpu_frames {
+            *rpu_counts.entry(*decoded_number).or_default() += 1;
+        }
+
+        let mut decoded_numbers: Vec<u64> = self.presentation_numbers.keys().copied().collect();
+        decoded_numbers.sort_unstable();
+
+        for decoded_number in decoded_numbers {
+            match rpu_counts.get(&decoded_number) {
+                Some(1) => (),
+                Some(count) => bail!(
+                    "inject-rpu: frame {} has {} RPUs, --in-place requires exactly one per frame",
+                    decoded_number,
+                    count
+                ),
+                None => bail!(
+                    "inject-rpu: frame {} has no RPU, --in-place requires a Dolby Vision input",
+                    decoded_number
+                ),
+            }
+        }
+
+        rpu_nals
+            .iter()
+            .zip(&self.rpu_frames)
+            .map(|(range, decoded_number)| {
+                let presentation_number = match self.presentation_numbers.get(decoded_number) {
+                    Some(presentation_number) => *presentation_number,
+                    None => bail!(
+                        "inject-rpu: RPU outside of any frame at byte {}",
+                        range.start
+                    ),
+                };
+
+                Ok(Replacement {
+                    range: *range,
+                    nal: self.codec.write_rpu_nalu(&rpus[presentation_number])?,
+                })
+            })
+            .collect()
+    }
+
+    fn write_replacements(&self, replacements: &[Replacement], buffer_size: usize) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.input)
+            .with_context(|| format!("Can't open input file {}", self.input.display()))?;
+
+        let file_len = file.metadata()?.len();
+
+        let first_grown = replacements
+            .iter()
+            .position(|replacement| replacement.growth() > 0)
+            .unwrap_or(replacements.len());
+
+        if first_grown < replacements.len() {
+            let grown = replacements[first_grown..]
+                .iter()
+                .filter(|replacement| replacement.growth() > 0)
+                .count();
+
+            writeln!(
+                status_out(),
+                "{} RPUs are larger, shifting the last {} bytes of the file",
+                grown,
+                file_len - replacements[first_grown].range.start
+            )
+            .ok();
+            status_out().flush().ok();
+
+            shift_tail(
+                &mut file,
+                &replacements[first_grown..],
+                file_len,
+                buffer_size,
+            )?;
+        }
+
+        for replacement in &replacements[..first_grown] {
+            write_at(&mut file, replacement.range.start, &replacement.padded())?;
+        }
+
+        file.flush()?;
+
+        Ok(())
+    }
+}
+
+impl Replacement {
+    fn capacity(&self) -> u64 {
+        self.range.end - self.range.start
+    }
+
+    /// Bytes past the existing NAL unit
+    fn growth(&self) -> u64 {
+        (self.nal.len() as u64).saturating_sub(self.capacity())
+    }
+
+    /// The NAL unit, with trailing zero bytes up to the size of the existing one
+    fn padded(&self) -> Vec<u8> {
+        let mut data = self.nal.clone();
+
+        if (data.len() as u64) < self.capacity() {
+            data.resize(self.capacity() as usize, 0);
+        }
+
+        data
+    }
+}
+
+impl<R: Read> RpuNalScanner<R> {
+    fn new(inner: R, codec: Codec) -> Self {
+        Self {
+            inner,
+            codec,
+            position: 0,
+            zero_run: 0,
+            header: None,
+            current: None,
+            nals: Vec::new(),
+        }
+    }
+
+    fn scan(&mut self, data: &[u8]) {
+        let header_len = self.codec.nal_header_len();
+
+        for &byte in data {
+            let position = self.position;
+            self.position += 1;
+
+            // Start code, the zero bytes before it are not part of the previous NAL unit
+            if byte == 1 && self.zero_run >= 2 {
+                if let Some(start) = self.current.take() {
+                    self.nals.push(NalRange {
+                        start,
+                        end: position - self.zero_run as u64,
+                    });
+                }
+
+                self.header = Some((position + 1, Vec::with_capacity(header_len)));
+                self.zero_run = 0;
+
+                continue;
+            }
+
+            if let Some((start, header)) = self.header.as_mut() {
+                header.push(byte);
+
+                if header.len() == header_len {
+                    if self.codec.nal_type(header) == self.codec.rpu_nal_type() {
+                        self.current = Some(*start);
+                    }
+
+                    self.header = None;
+                }
+            }
+
+            self.zero_run = if byte == 0 { self.zero_run + 1 } else { 0 };
+        }
+    }
+
+    /// The RPU NAL units found, once the whole input is read
+    fn finish(mut self) -> Vec<NalRange> {
+        if let Some(start) = self.current.take() {
+            self.nals.push(NalRange {
+                start,
+                end: self.position - self.zero_run as u64,
+            });
+        }
+
+        self.nals
+    }
+}
+
+impl<R: Read> Read for RpuNalScanner<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.scan(&buf[..read]);
+
+        Ok(read)
+    }
+}
+
+/// Writes the replacements from the last one, shifting the data after each one by the growth so far
+fn shift_tail(
+    file: &mut File,
+    replacements: &[Replacement],
+    file_len: u64,
+    buffer_size: usize,
+) -> Result<()> {
+    let mut shift: u64 = replacements.iter().map(Replacement::growth).sum();
+    file.set_len(file_len + shift)?;
+
+    let mut buf = vec![0; buffer_size];
+
+    for (i, replacement) in replacements.iter().enumerate().rev() {
+        let segment_end = replacements
+            .get(i + 1)
+            .map_or(file_len, |next| next.range.start);
+
+        // From the end, the destination is after the source
+        let mut position = segment_end;
+        while position > replacement.range.end {
+            let len = (position - replacement.range.end).min(buf.len() as u64) as usize;
+            position -= len as u64;
+
+            file.seek(SeekFrom::Start(position))?;
+            file.read_exact(&mut buf[..len])?;
+            write_at(file, position + shift, &buf[..len])?;
+        }
+
+        shift -= replacement.growth();
+        write_at(file, replacement.range.start + shift, &replacement.padded())?;
+    }
+
+    Ok(())
+}
+
+fn write_at(file: &mut File, position: u64, data: &[u8]) -> Result<()> {
+    file.seek(SeekFrom::Start(position))?;
+    file.write_all(data)?;
+
+    Ok(())
+}
+
+impl IoProcessor for InPlaceInjector {
+    fn input(&self) -> &PathBuf {
+        &self.input
+    }
+
+    fn update_progress(&mut self, _delta: u64) {
+        // Progress is updated by the input reader
+    }
+
+    fn process_nals(
+        &mut self,
+        _parser: &HevcParser,
+        nals: &[NALUnit],
+        _chunk: &[u8],
+    ) -> Result<()> {
+        self.add_rpu_frames(nals);
+
+        Ok(())
+    }
+
+    fn finalize(&mut self, parser: &HevcParser) -> Result<()> {
+        self.set_frames(parser.ordered_frames());
+
+        Ok(())
+    }
+}
+
+impl NalIoProcessor for InPlaceInjector {
+    fn process_nals(
+        &mut self,
+        _frame_order: &FrameOrder,
+        nals: &[NALUnit],
+        _chunk: &[u8],
+    ) -> Result<()> {
+        self.add_rpu_frames(nals);
+
+        Ok(())
+    }
+
+    fn finalize(&mut self, frame_order: &FrameOrder) -> Result<()> {
+        self.set_frames(frame_order.ordered_frames());
+
+        Ok(())
+    }
+}
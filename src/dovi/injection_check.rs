@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use indicatif::ProgressBar;
+use rayon::prelude::*;
+
+use hevc_parser::hevc::{Frame, NALUnit};
+use hevc_parser::io::{processor, IoProcessor};
+use hevc_parser::HevcParser;
+use processor::HevcProcessorOpts;
+
+use super::avc_parser::AvcParser;
+use super::codec::Codec;
+use super::hasher::Hasher;
+use super::hevc_processor::HevcProcessor;
+use super::nal_parser::{FrameOrder, NalIoProcessor, NalProcessor};
+use super::rpu_stream::status_out;
+use super::vvc_parser::VvcParser;
+use super::{open_hevc_input, DoviRpu, InputFormat, IoFormat};
+
+/// Maximum number of frames listed in the report
+const MAX_LISTED_ISSUES: usize = 20;
+
+/// Sanity check of an injected stream, read back once written.
+///
+/// Every access unit must have exactly one RPU, equal to the injected RPU of its presentation frame.
+/// The RPUs are compared by their hash, as with `hash`.
+pub struct InjectionCheck {
+    input: PathBuf,
+    codec: Codec,
+    progress_bar: ProgressBar,
+
+    /// Hashes of the injected RPUs, in presentation order
+    expected: Vec<u64>,
+    /// Hashes of the RPUs found, by decoded frame
+    found: HashMap<u64, Vec<Result<u64, String>>>,
+
+    /// Issues by decoded frame, if specific to a frame
+    issues: Vec<(Option<u64>, String)>,
+}
+
+impl InjectionCheck {
+    /// Reads the injected stream and compares its RPUs to the injected ones
+    pub fn check(output: &Path, codec: Codec, rpus: &[DoviRpu], chunk_size: usize) -> Result<()> {
+        writeln!(status_out(), "Verifying the injected RPUs...").ok();
+        status_out().flush().ok();
+
+        let expected = rpus
+            .par_iter()
+            .map(Hasher::rpu_hash)
+            .collect::<Result<Vec<u64>>>()?;
+
+        let mut check = InjectionCheck {
+            input: output.to_path_buf(),
+            codec,
+            progress_bar: super::initialize_progress_bar(&IoFormat::Raw, output)?,
+            expected,
+            found: HashMap::new(),
+            issues: Vec::new(),
+        };
+
+        let mut reader = open_hevc_input(
+            &IoFormat::Raw,
+            output,
+            InputFormat::AnnexB,
+            &check.progress_bar,
+            chunk_size,
+        )?;
+
+        match codec {
+            Codec::Hevc => {
+                let mut processor =
+                    HevcProcessor::new(IoFormat::Raw, HevcProcessorOpts::default(), chunk_size);
+
+                processor.process_io(&mut reader, &mut check)?;
+            }
+            Codec::Avc => {
+                NalProcessor::<AvcParser>::new(chunk_size).process_io(&mut reader, &mut check)?
+            }
+            Codec::Vvc => {
+                NalProcessor::<VvcParser>::new(chunk_size).process_io(&mut reader, &mut check)?
+            }
+        }
+
+        check.report()
+    }
+
+    fn add_rpus(&mut self, nals: &[NALUnit], chunk: &[u8]) {
+        let rpu_nal_type = self.codec.rpu_nal_type();
+
+        for nal in nals.iter().filter(|nal| nal.nal_type == rpu_nal_type) {
+            let hash = self
+                .codec
+                .parse_rpu_nalu(&chunk[nal.start..nal.end])
+                .and_then(|rpu| Hasher::rpu_hash(&rpu))
+                .map_err(|e| e.to_string());
+
+            self.found
+                .entry(nal.decoded_frame_index)
+                .or_default()
+                .push(hash);
+        }
+    }
+
+    fn check_frames(&mut self, ordered_frames: &[Frame]) {
+        self.progress_bar.finish_and_clear();
+
+        if ordered_frames.len() != self.expected.len() {
+            self.issues.push((
+                None,
+                format!(
+                    "mismatched frame count: output {} frames, {} RPUs injected",
+                    ordered_frames.len(),
+                    self.expected.len()
+                ),
+            ));
+        }
+
+        let mut frames: Vec<&Frame> = ordered_frames.iter().collect();
+        frames.sort_by_key(|frame| frame.decoded_number);
+
+        for frame in frames {
+            let decoded_number = frame.decoded_number;
+            let presentation_number = frame.presentation_number as usize;
+
+            let hashes = self.found.remove(&decoded_number).unwrap_or_default();
+
+            let message = match hashes.as_slice() {
+                [] => Some("no RPU".to_string()),
+                [Err(e)] => Some(format!("invalid RPU: {}", e)),
+                [Ok(hash)] => match self.expected.get(presentation_number) {
+                    Some(expected) if expected == hash => None,
+                    Some(_) => Some(format!(
+                        "the RPU differs from the injected RPU of presentation frame {}",
+                        presentation_number
+                    )),
+                    None => Some(format!(
+                        "no RPU was injected for presentation frame {}",
+                        presentation_number
+                    )),
+                },
+                _ => Some(format!("{} RPUs instead of one", hashes.len())),
+            };
+
+            if let Some(message) = message {
+                self.issues.push((Some(decoded_number), message));
+            }
+        }
+
+        let mut orphans: Vec<u64> = self.found.keys().copied().collect();
+        orphans.sort_unstable();
+
+        for decoded_number in orphans {
+            self.issues
+                .push((Some(decoded_number), "RPU outside of any frame".to_string()));
+        }
+    }
+
+    fn report(&self) -> Result<()> {
+        if self.issues.is_empty() {
+            writeln!(
+                status_out(),
+                "Verified {} frames, each with its injected RPU",
+                self.expected.len()
+            )
+            .ok();
+
+            return Ok(());
+        }
+
+        let mut report = String::from("Injected stream issues, in decoded order:");
+
+        for (decoded_number, message) in self.issues.iter().take(MAX_LISTED_ISSUES) {
+            match decoded_number {
+                Some(decoded_number) => {
+                    report.push_str(&format!("\n  frame {}: {}", decoded_number, message))
+                }
+                None => report.push_str(&format!("\n  {}", message)),
+            }
+        }
+
+        if self.issues.len() > MAX_LISTED_ISSUES {
+            report.push_str("\n  ...");
+        }
+
+        bail!(
+            "{}\ninject-rpu: verification failed, {} issues found",
+            report,
+            self.issues.len()
+        );
+    }
+}
+
+impl IoProcessor for InjectionCheck {
+    fn input(&self) -> &PathBuf {
+        &self.input
+    }
+
+    fn update_progress(&mut self, _delta: u64) {
+        // Progress is updated by the input reader
+    }
+
+    fn process_nals(&mut self, _parser: &HevcParser, nals: &[NALUnit], chunk: &[u8]) -> Result<()> {
+        self.add_rpus(nals, chunk);
+
+        Ok(())
+    }
+
+    fn finalize(&mut self, parser: &HevcParser) -> Result<()> {
+        self.check_frames(parser.ordered_frames());
+
+        Ok(())
+    }
+}
+
+impl NalIoProcessor for InjectionCheck {
+    fn process_nals(
+        &mut self,
+        _frame_order: &FrameOrder,
+        nals: &[NALUnit],
+        chunk: &[u8],
+    ) -> Result<()> {
+        self.add_rpus(nals, chunk);
+
+        Ok(())
+    }
+
+    fn finalize(&mut self, frame_order: &FrameOrder) -> Result<()> {
+        self.check_frames(frame_order.ordered_frames());
+
+        Ok(())
+    }
+}
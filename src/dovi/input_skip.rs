@@ -0,0 +1,161 @@
+use std::io::{Cursor, Read, Write};
+
+use anyhow::{bail, Result};
+
+use super::codec::Codec;
+use super::rpu_stream::status_out;
+use super::start_code::get_offsets;
+
+const NAL_START_CODE_3_LEN: usize = 3;
+
+/// Discards the input until the next random access point, after skipping at least `skip_frames` frames.
+///
+/// The input can start anywhere in a NAL unit, so decoding resumes at the next access unit
+/// starting with parameter sets, including its access unit delimiter.
+/// The frames are counted from the NAL headers only, without parsing the slices.
+pub fn skip_to_random_access(
+    mut reader: Box<dyn Read>,
+    codec: Codec,
+    skip_frames: u64,
+    chunk_size: usize,
+) -> Result<Box<dyn Read>> {
+    let scan = next_random_access(&mut reader, codec, skip_frames, chunk_size, false)?;
+
+    match scan.point {
+        Some((offset, remaining)) => {
+            writeln!(
+                status_out(),
+                "Skipped {} frames, resuming at the next random access point ({} bytes discarded).",
+                scan.frames,
+                offset
+            )
+            .ok();
+
+            Ok(Box::new(Cursor::new(remaining).chain(reader)))
+        }
+        None => bail!(
+            "No random access point found after skipping {} frames.\n\
+            The parameter sets may only be present at the start of the stream.",
+            scan.frames
+        ),
+    }
+}
+
+/// Offset of the first random access point of the input, to split it at an access unit.
+///
+/// As the input starts at an arbitrary offset, parameter sets found first are skipped:
+/// their access unit delimiter may be before the start of the input.
+pub fn find_random_access(
+    mut reader: Box<dyn Read>,
+    codec: Codec,
+    chunk_size: usize,
+) -> Result<Option<u64>> {
+    let scan = next_random_access(&mut reader, codec, 0, chunk_size, true)?;
+
+    Ok(scan.point.map(|(offset, _)| offset))
+}
+
+struct RandomAccessScan {
+    /// Frames before the random access point, or in the whole input
+    frames: u64,
+    /// Bytes before the access unit of the random access point, and the input read from it
+    point: Option<(u64, Vec<u8>)>,
+}
+
+fn next_random_access(
+    reader: &mut dyn Read,
+    codec: Codec,
+    skip_frames: u64,
+    chunk_size: usize,
+    mid_stream: bool,
+) -> Result<RandomAccessScan> {
+    let mut buf = vec![0; chunk_size];
+    let mut chunk: Vec<u8> = Vec::with_capacity(chunk_size * 2);
+    let mut offsets = Vec::with_capacity(2048);
+
+    let mut discarded = 0;
+    let mut frames = 0;
+    let mut first_nal = mid_stream;
+
+    // Start code offset of the AUD preceding the current NAL
+    let mut aud_offset: Option<usize> = None;
+
+    loop {
+        let read_bytes = reader.read(&mut buf)?;
+
+        if read_bytes == 0 {
+            return Ok(RandomAccessScan {
+                frames,
+                point: None,
+            });
+        }
+
+        chunk.extend_from_slice(&buf[..read_bytes]);
+        get_offsets(&chunk, &mut offsets);
+
+        // Offset of the first NAL unit with an incomplete header
+        let mut incomplete_offset = None;
+
+        for &offset in &offsets {
+            let nal_start = offset + NAL_START_CODE_3_LEN;
+
+            // NAL header and first payload byte
+            if nal_start + codec.nal_header_len() >= chunk.len() {
+                incomplete_offset = Some(offset);
+                break;
+            }
+
+            let data = &chunk[nal_start..];
+            let nal_type = codec.nal_type(data);
+
+            let unknown_start = first_nal && aud_offset.is_none();
+            first_nal = false;
+
+            // The enhancement layers are part of the base layer's access unit
+            if codec.nuh_layer_id(data) > 0 {
+                aud_offset = None;
+                continue;
+            }
+
+            if frames >= skip_frames && codec.is_parameter_set_start(nal_type) && !unknown_start {
+                let start = aud_offset.unwrap_or(offset);
+
+                // Include the zero_byte of 4 bytes start codes
+                let start = if start > 0 && chunk[start - 1] == 0 {
+                    start - 1
+                } else {
+                    start
+                };
+
+                return Ok(RandomAccessScan {
+                    frames,
+                    point: Some(((discarded + start) as u64, chunk.split_off(start))),
+                });
+            }
+
+            if nal_type == codec.aud_nal_type() {
+                aud_offset = Some(offset);
+            } else {
+                aud_offset = None;
+
+                if codec.starts_picture(nal_type, data) {
+                    frames += 1;
+                }
+            }
+        }
+
+        // Keep the NAL units not yet handled, and a possible start code split between chunks
+        let keep_offset = aud_offset
+            .or(incomplete_offset)
+            .unwrap_or_else(|| chunk.len().saturating_sub(NAL_START_CODE_3_LEN));
+        let keep_offset = keep_offset.saturating_sub(1);
+
+        if aud_offset.is_some() {
+            // Handled again with the next chunk
+            aud_offset = None;
+        }
+
+        discarded += keep_offset;
+        chunk.drain(..keep_offset);
+    }
+}
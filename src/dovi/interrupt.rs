@@ -0,0 +1,138 @@
+use std::io::{self, Cursor, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::Result;
+
+use super::codec::Codec;
+use super::start_code::get_offsets;
+
+const NAL_START_CODE_3_LEN: usize = 3;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static HANDLER_SET: AtomicBool = AtomicBool::new(false);
+
+/// Stops reading the input on Ctrl-C, so that the outputs are finalized.
+/// A second Ctrl-C exits immediately.
+///
+/// The handler is only set once, for commands processing multiple inputs.
+pub fn set_interrupt_handler() -> Result<()> {
+    if HANDLER_SET.swap(true, Ordering::SeqCst) {
+        return Ok(());
+    }
+
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+
+        eprintln!("\nInterrupted, finalizing the outputs. Press Ctrl-C again to exit immediately.");
+    })?;
+
+    Ok(())
+}
+
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Stops the input as on Ctrl-C, to cancel a job of the `--serve` mode
+pub fn interrupt() {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Clears the interruption before the next job of the `--serve` mode
+pub fn reset_interrupt() {
+    INTERRUPTED.store(false, Ordering::SeqCst);
+}
+
+/// Ends the stream at the next access unit once interrupted,
+/// so that the outputs only contain complete frames.
+pub struct InterruptibleReader {
+    inner: Box<dyn Read>,
+    codec: Codec,
+    chunk_size: usize,
+
+    /// Remaining data of the last access unit, once interrupted
+    tail: Option<Cursor<Vec<u8>>>,
+}
+
+impl InterruptibleReader {
+    pub fn new(inner: Box<dyn Read>, codec: Codec, chunk_size: usize) -> Self {
+        Self {
+            inner,
+            codec,
+            chunk_size,
+            tail: None,
+        }
+    }
+
+    /// Reads the input until the start of the next access unit:
+    /// an AUD, the parameter sets or the first slice of a picture.
+    fn read_access_unit_end(&mut self) -> io::Result<Vec<u8>> {
+        let header_len = self.codec.nal_header_len();
+
+        let mut buf = vec![0; self.chunk_size];
+        let mut data = Vec::with_capacity(self.chunk_size);
+        let mut offsets = Vec::new();
+
+        // Only the new data is scanned, with the start codes overlapping the previous read
+        let mut scan_start = 0;
+
+        loop {
+            let read_bytes = self.inner.read(&mut buf)?;
+
+            if read_bytes == 0 {
+                return Ok(data);
+            }
+
+            data.extend_from_slice(&buf[..read_bytes]);
+            get_offsets(&data[scan_start..], &mut offsets);
+
+            let mut next_scan_start = data.len().saturating_sub(NAL_START_CODE_3_LEN);
+            let mut access_unit_start = None;
+
+            for offset in offsets.iter().map(|offset| scan_start + offset) {
+                let nal = &data[offset + NAL_START_CODE_3_LEN..];
+
+                // Incomplete NAL header, scanned again after the next read
+                if nal.len() <= header_len {
+                    next_scan_start = offset;
+                    break;
+                }
+
+                if self.codec.starts_access_unit(self.codec.nal_type(nal), nal) {
+                    access_unit_start = Some(offset);
+                    break;
+                }
+            }
+
+            if let Some(offset) = access_unit_start {
+                // Also drop the zero_byte of 4 bytes start codes
+                let end = if offset > 0 && data[offset - 1] == 0 {
+                    offset - 1
+                } else {
+                    offset
+                };
+
+                data.truncate(end);
+
+                return Ok(data);
+            }
+
+            scan_start = next_scan_start;
+        }
+    }
+}
+
+impl Read for InterruptibleReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.tail.is_none() && is_interrupted() {
+            self.tail = Some(Cursor::new(self.read_access_unit_end()?));
+        }
+
+        match self.tail {
+            Some(ref mut tail) => tail.read(buf),
+            None => self.inner.read(buf),
+        }
+    }
+}
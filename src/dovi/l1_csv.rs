@@ -0,0 +1,153 @@
+use std::fs::read_to_string;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, ensure, Result};
+
+use dolby_vision::rpu::extension_metadata::blocks::{ExtMetadataBlock, ExtMetadataBlockLevel1};
+use dolby_vision::utils::{nits_to_pq, pq_to_pq12};
+
+use super::DoviRpu;
+
+/// Units of the L1 values in the CSV file
+#[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum L1CsvUnits {
+    /// 12 bit PQ codes
+    Pq,
+    /// Luminance in nits
+    Nits,
+}
+
+/// Per-scene L1 metadata imported from a CSV file.
+///
+/// One `scene_start,min,mid,max` row per scene, with an optional header row.
+/// Every scene lasts until the start of the next one, or the end of the RPUs.
+#[derive(Debug, Default)]
+pub struct L1Csv {
+    /// First frame and L1 metadata of every scene, sorted
+    pub scenes: Vec<(usize, ExtMetadataBlockLevel1)>,
+}
+
+impl L1Csv {
+    pub fn from_path(path: &Path, units: L1CsvUnits) -> Result<Self> {
+        let content = read_to_string(path)?;
+
+        Self::parse(&content, units)
+    }
+
+    pub fn parse(content: &str, units: L1CsvUnits) -> Result<Self> {
+        let mut scenes = Vec::new();
+
+        let rows = content
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+        for (i, (line_number, line)) in rows.enumerate() {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+
+            // Header row
+            if i == 0 && fields[0].parse::<usize>().is_err() {
+                continue;
+            }
+
+            ensure!(
+                fields.len() == 4,
+                "Invalid L1 CSV line {}: expected scene_start,min,mid,max",
+                line_number
+            );
+
+            let scene_start = fields[0]
+                .parse::<usize>()
+                .map_err(|e| anyhow!("Invalid L1 CSV line {}: scene start {}", line_number, e))?;
+
+            let mut values = [0; 3];
+
+            for (value, field) in values.iter_mut().zip(&fields[1..]) {
+                *value = parse_value(field, units)
+                    .map_err(|e| anyhow!("Invalid L1 CSV line {}: {}", line_number, e))?;
+            }
+
+            let [min_pq, avg_pq, max_pq] = values;
+
+            ensure!(
+                min_pq <= avg_pq && avg_pq <= max_pq,
+                "Invalid L1 CSV line {}: expected min <= mid <= max",
+                line_number
+            );
+
+            scenes.push((
+                scene_start,
+                ExtMetadataBlockLevel1::from_stats(min_pq, max_pq, avg_pq),
+            ));
+        }
+
+        ensure!(!scenes.is_empty(), "Invalid L1 CSV: no scenes");
+
+        scenes.sort_by_key(|(start, _)| *start);
+
+        if let Some(duplicate) = scenes.windows(2).find(|w| w[0].0 == w[1].0) {
+            bail!(
+                "Invalid L1 CSV: duplicate scene starting at frame {}",
+                duplicate[0].0
+            );
+        }
+
+        Ok(Self { scenes })
+    }
+
+    /// Replaces the L1 metadata of every frame of the scenes, adding it if missing.
+    /// The frames before the first scene are left untouched.
+    pub fn apply(&self, rpus: &mut [DoviRpu]) -> Result<()> {
+        if let Some((last, _)) = self.scenes.last() {
+            ensure!(
+                *last < rpus.len(),
+                "Invalid L1 CSV: scene starting at frame {} but only {} RPUs",
+                last,
+                rpus.len()
+            );
+        }
+
+        let ends = self
+            .scenes
+            .iter()
+            .skip(1)
+            .map(|(start, _)| *start)
+            .chain([rpus.len()]);
+
+        for ((start, level1), end) in self.scenes.iter().zip(ends) {
+            for rpu in &mut rpus[*start..end] {
+                if let Some(ref mut vdr_dm_data) = rpu.vdr_dm_data {
+                    rpu.modified = true;
+
+                    vdr_dm_data.replace_metadata_block(ExtMetadataBlock::Level1(level1.clone()))?;
+                }
+            }
+        }
+
+        println!("Applied L1 metadata to {} scenes", self.scenes.len());
+
+        Ok(())
+    }
+}
+
+fn parse_value(field: &str, units: L1CsvUnits) -> Result<u16> {
+    match units {
+        L1CsvUnits::Pq => {
+            let pq = field.parse::<u16>()?;
+            ensure!(pq <= 4095, "PQ value {} out of range, max 4095", pq);
+
+            Ok(pq)
+        }
+        L1CsvUnits::Nits => {
+            let nits = field.parse::<f64>()?;
+            ensure!(
+                nits.is_finite() && (0.0..=10_000.0).contains(&nits),
+                "nits value {} out of range, 0 to 10000",
+                nits
+            );
+
+            Ok(pq_to_pq12(nits_to_pq(nits)))
+        }
+    }
+}
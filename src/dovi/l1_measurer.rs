@@ -0,0 +1,531 @@
+use std::fs::File;
+use std::io::{stdin, stdout, BufReader, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+
+use anyhow::{anyhow, bail, ensure, Result};
+
+use dolby_vision::rpu::extension_metadata::blocks::{ExtMetadataBlock, ExtMetadataBlockLevel1};
+use dolby_vision::rpu::generate::{GenerateConfig, ShotFrameEdit, VideoShot};
+use dolby_vision::utils::pq_to_pq12;
+use utilities_dovi::parse_rpu_file;
+
+use crate::commands::MeasureL1Args;
+
+use super::scene_file::SceneCuts;
+use super::{write_rpu_file, DoviRpu};
+
+/// Limited range 10 bit luma
+const LIMITED_RANGE_MIN: f64 = 64.0;
+const LIMITED_RANGE_MAX: f64 = 940.0;
+
+/// Maximum number of mismatched frames listed when validating
+const MAX_LISTED_MISMATCHES: usize = 10;
+
+pub struct L1Measurer {
+    input: Option<PathBuf>,
+    frames: Option<PathBuf>,
+    video: Option<PathBuf>,
+    width: usize,
+    height: usize,
+    full_range: bool,
+    overwrite: bool,
+    tolerance: u16,
+    sample: Option<usize>,
+    scene_file: Option<PathBuf>,
+    rpu_out: PathBuf,
+}
+
+/// Measured brightness of a frame, as 12 bit PQ codes
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FrameStats {
+    min_pq: u16,
+    max_pq: u16,
+    avg_pq: u16,
+}
+
+impl L1Measurer {
+    pub fn measure(args: MeasureL1Args) -> Result<()> {
+        let MeasureL1Args {
+            input,
+            input_pos,
+            frames,
+            video,
+            width,
+            height,
+            full_range,
+            overwrite,
+            tolerance,
+            sample,
+            scene_file,
+            rpu_out,
+        } = args;
+
+        let input = input.or(input_pos);
+
+        ensure!(
+            width > 0 && height > 0,
+            "measure-l1: invalid frame size {}x{}",
+            width,
+            height
+        );
+        ensure!(
+            !overwrite || input.is_some(),
+            "measure-l1: --overwrite requires an input RPU"
+        );
+        ensure!(
+            sample.is_none_or(|sample| sample > 0),
+            "measure-l1: invalid sample interval 0"
+        );
+        ensure!(
+            sample.is_none() || (input.is_some() && !overwrite),
+            "measure-l1: --sample is only used when validating an input RPU"
+        );
+        ensure!(
+            input.is_none() || scene_file.is_none(),
+            "measure-l1: --scene-file is only used when generating a new RPU"
+        );
+
+        let rpu_out = rpu_out.unwrap_or_else(|| match &input {
+            Some(input) => PathBuf::from(format!(
+                "{}{}",
+                input.file_stem().unwrap().to_str().unwrap(),
+                "_measured.bin"
+            )),
+            None => PathBuf::from("RPU_measured.bin"),
+        });
+
+        let measurer = L1Measurer {
+            input,
+            frames,
+            video,
+            width,
+            height,
+            full_range,
+            overwrite,
+            tolerance,
+            sample,
+            scene_file,
+            rpu_out,
+        };
+
+        match &measurer.input {
+            Some(input) => {
+                println!("Parsing RPU file...");
+                stdout().flush().ok();
+
+                if let Some(mut rpus) = parse_rpu_file(input)? {
+                    let stats = measurer.measure_frames(Some(rpus.len()))?;
+
+                    if measurer.overwrite {
+                        measurer.overwrite_l1(&mut rpus, &all_frames(stats))?;
+                    } else if measurer.sample.is_some() {
+                        measurer.validate_scenes(&rpus, &stats)?;
+                    } else {
+                        measurer.validate_l1(&rpus, &all_frames(stats))?;
+                    }
+                }
+            }
+            None => {
+                let stats = measurer.measure_frames(None)?;
+                measurer.generate(&all_frames(stats))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Measures the decoded frames, until the end of the frames or the expected count.
+    /// With `--sample`, only every Nth frame is measured and the others are `None`.
+    fn measure_frames(&self, expected_count: Option<usize>) -> Result<Vec<Option<FrameStats>>> {
+        println!("Measuring frames...");
+        stdout().flush().ok();
+
+        let mut decoder = match &self.video {
+            Some(video) => Some(spawn_ffmpeg(video)?),
+            None => None,
+        };
+
+        let mut reader: Box<dyn Read> = match (&mut decoder, &self.frames) {
+            (Some(child), _) => Box::new(BufReader::new(child.stdout.take().unwrap())),
+            (None, Some(frames)) if frames == Path::new("-") => Box::new(BufReader::new(stdin())),
+            (None, Some(frames)) => Box::new(BufReader::new(File::open(frames)?)),
+            (None, None) => bail!("measure-l1: no decoded frames, use --frames or --video"),
+        };
+
+        // 2 bytes per 10 bit sample
+        let mut frame = vec![0; self.width * self.height * 2];
+        let mut stats = Vec::with_capacity(expected_count.unwrap_or_default());
+
+        loop {
+            if let Some(expected_count) = expected_count {
+                if stats.len() == expected_count {
+                    break;
+                }
+            }
+
+            if !read_frame(&mut reader, &mut frame)? {
+                break;
+            }
+
+            let sampled = stats.len() % self.sample.unwrap_or(1) == 0;
+            stats.push(if sampled {
+                Some(self.frame_stats(&frame))
+            } else {
+                None
+            });
+        }
+
+        drop(reader);
+
+        if let Some(mut child) = decoder {
+            // The remaining frames were not read when the expected count is reached
+            if expected_count != Some(stats.len()) {
+                let status = child.wait()?;
+                ensure!(
+                    status.success(),
+                    "ffmpeg failed decoding the video: {}",
+                    status
+                );
+            } else {
+                child.kill().ok();
+                child.wait().ok();
+            }
+        }
+
+        if let Some(expected_count) = expected_count {
+            ensure!(
+                stats.len() == expected_count,
+                "mismatched frame count: frames {}, RPU {}",
+                stats.len(),
+                expected_count
+            );
+        }
+
+        ensure!(!stats.is_empty(), "measure-l1: no frames to measure");
+
+        match self.sample {
+            Some(sample) if sample > 1 => println!(
+                "Measured {} of {} frames, one every {}",
+                stats.iter().flatten().count(),
+                stats.len(),
+                sample
+            ),
+            _ => println!("Measured {} frames", stats.len()),
+        }
+
+        Ok(stats)
+    }
+
+    /// Min, max and average PQ of the luma samples.
+    fn frame_stats(&self, frame: &[u8]) -> FrameStats {
+        let mut min = u16::MAX;
+        let mut max = 0;
+        let mut sum = 0_u64;
+
+        for sample in frame.chunks_exact(2) {
+            let luma = u16::from_le_bytes([sample[0], sample[1]]) & 0x3FF;
+
+            min = min.min(luma);
+            max = max.max(luma);
+            sum += luma as u64;
+        }
+
+        let avg = sum as f64 / (frame.len() / 2) as f64;
+
+        FrameStats {
+            min_pq: self.luma_to_pq(min as f64),
+            max_pq: self.luma_to_pq(max as f64),
+            avg_pq: self.luma_to_pq(avg),
+        }
+    }
+
+    fn luma_to_pq(&self, luma: f64) -> u16 {
+        let normalized = if self.full_range {
+            luma / 1023.0
+        } else {
+            (luma - LIMITED_RANGE_MIN) / (LIMITED_RANGE_MAX - LIMITED_RANGE_MIN)
+        };
+
+        pq_to_pq12(normalized.clamp(0.0, 1.0))
+    }
+
+    fn overwrite_l1(&self, rpus: &mut [DoviRpu], stats: &[FrameStats]) -> Result<()> {
+        for (rpu, frame_stats) in rpus.iter_mut().zip(stats) {
+            if let Some(ref mut vdr_dm_data) = rpu.vdr_dm_data {
+                rpu.modified = true;
+
+                vdr_dm_data
+                    .replace_metadata_block(ExtMetadataBlock::Level1(frame_stats.to_level1()))?;
+            }
+        }
+
+        println!("Overwrote the L1 metadata of {} frames", rpus.len());
+
+        let data = GenerateConfig::encode_rpus(rpus);
+        write_rpu_file(&self.rpu_out, data)
+    }
+
+    /// Compares the existing L1 metadata to the measurements, failing on mismatches.
+    fn validate_l1(&self, rpus: &[DoviRpu], stats: &[FrameStats]) -> Result<()> {
+        let mismatches: Vec<(usize, ExtMetadataBlockLevel1, ExtMetadataBlockLevel1)> = rpus
+            .iter()
+            .zip(stats)
+            .enumerate()
+            .filter_map(|(i, (rpu, frame_stats))| {
+                let existing = match rpu.vdr_dm_data.as_ref()?.get_block(1) {
+                    Some(ExtMetadataBlock::Level1(level1)) => level1.clone(),
+                    _ => ExtMetadataBlockLevel1::default(),
+                };
+                let measured = frame_stats.to_level1();
+
+                let differs = |a: u16, b: u16| a.max(b) - a.min(b) > self.tolerance;
+
+                if differs(existing.max_pq, measured.max_pq)
+                    || differs(existing.avg_pq, measured.avg_pq)
+                {
+                    Some((i, existing, measured))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        if mismatches.is_empty() {
+            println!(
+                "L1 metadata matches the measurements, within {} PQ codes",
+                self.tolerance
+            );
+
+            return Ok(());
+        }
+
+        for (i, existing, measured) in mismatches.iter().take(MAX_LISTED_MISMATCHES) {
+            println!(
+                "Frame {}: L1 max {} avg {}, measured max {} avg {}",
+                i, existing.max_pq, existing.avg_pq, measured.max_pq, measured.avg_pq
+            );
+        }
+
+        if mismatches.len() > MAX_LISTED_MISMATCHES {
+            println!("...");
+        }
+
+        bail!(
+            "L1 metadata differs from the measurements in {} of {} frames, by more than {} PQ codes.\n\
+            Use --overwrite to replace it with the measurements.",
+            mismatches.len(),
+            rpus.len(),
+            self.tolerance
+        )
+    }
+
+    /// Compares the existing L1 metadata to the sampled measurements, per scene.
+    ///
+    /// The scenes are split by the scene cut flags of the RPU. A scene is flagged when the
+    /// average max or avg PQ of its sampled frames differs by more than the tolerance,
+    /// which usually means that the metadata belongs to a different cut of the video.
+    fn validate_scenes(&self, rpus: &[DoviRpu], stats: &[Option<FrameStats>]) -> Result<()> {
+        let mut scene_starts: Vec<usize> = rpus
+            .iter()
+            .enumerate()
+            .filter(|(i, rpu)| {
+                *i == 0
+                    || rpu
+                        .vdr_dm_data
+                        .as_ref()
+                        .is_some_and(|dm| dm.scene_refresh_flag == 1)
+            })
+            .map(|(i, _)| i)
+            .collect();
+        scene_starts.push(rpus.len());
+
+        let mut checked_scenes = 0;
+        let mut mismatches = Vec::new();
+
+        for (scene, bounds) in scene_starts.windows(2).enumerate() {
+            let (start, end) = (bounds[0], bounds[1]);
+
+            // Sums of (existing max, existing avg, measured max, measured avg)
+            let mut sums = [0_u64; 4];
+            let mut sampled = 0;
+
+            for (rpu, frame_stats) in rpus[start..end].iter().zip(&stats[start..end]) {
+                let (frame_stats, vdr_dm_data) = match (frame_stats, rpu.vdr_dm_data.as_ref()) {
+                    (Some(frame_stats), Some(vdr_dm_data)) => (frame_stats, vdr_dm_data),
+                    _ => continue,
+                };
+
+                let existing = match vdr_dm_data.get_block(1) {
+                    Some(ExtMetadataBlock::Level1(level1)) => level1.clone(),
+                    _ => ExtMetadataBlockLevel1::default(),
+                };
+                let measured = frame_stats.to_level1();
+
+                sums[0] += existing.max_pq as u64;
+                sums[1] += existing.avg_pq as u64;
+                sums[2] += measured.max_pq as u64;
+                sums[3] += measured.avg_pq as u64;
+                sampled += 1;
+            }
+
+            if sampled == 0 {
+                continue;
+            }
+
+            checked_scenes += 1;
+
+            let [existing_max, existing_avg, measured_max, measured_avg] =
+                sums.map(|sum| (sum as f64 / sampled as f64).round() as u16);
+
+            let differs = |a: u16, b: u16| a.max(b) - a.min(b) > self.tolerance;
+
+            if differs(existing_max, measured_max) || differs(existing_avg, measured_avg) {
+                mismatches.push((
+                    scene,
+                    start,
+                    end - 1,
+                    [existing_max, existing_avg, measured_max, measured_avg],
+                ));
+            }
+        }
+
+        if mismatches.is_empty() {
+            println!(
+                "L1 metadata matches the measurements in {} scenes, within {} PQ codes",
+                checked_scenes, self.tolerance
+            );
+
+            return Ok(());
+        }
+
+        for (scene, start, end, values) in mismatches.iter().take(MAX_LISTED_MISMATCHES) {
+            println!(
+                "Scene {} (frames {}-{}): L1 max {} avg {}, measured max {} avg {}",
+                scene, start, end, values[0], values[1], values[2], values[3]
+            );
+        }
+
+        if mismatches.len() > MAX_LISTED_MISMATCHES {
+            println!("...");
+        }
+
+        bail!(
+            "L1 metadata differs from the measurements in {} of {} scenes, by more than {} PQ codes.\n\
+            The metadata may belong to a different cut of the video.",
+            mismatches.len(),
+            checked_scenes,
+            self.tolerance
+        )
+    }
+
+    /// Generates a profile 8.1 RPU with the measured L1 metadata, one shot per scene.
+    fn generate(&self, stats: &[FrameStats]) -> Result<()> {
+        let scene_starts = match &self.scene_file {
+            Some(scene_file) => {
+                let scene_cuts = SceneCuts::from_path(scene_file)?;
+
+                if let Some(frame_count) = scene_cuts.frame_count {
+                    ensure!(
+                        frame_count == stats.len(),
+                        "mismatched frame count: scene file {}, frames {}",
+                        frame_count,
+                        stats.len()
+                    );
+                }
+
+                ensure!(
+                    scene_cuts.frames.iter().all(|frame| *frame < stats.len()),
+                    "Invalid scene file: scene cut after the last frame {}",
+                    stats.len() - 1
+                );
+
+                scene_cuts.frames
+            }
+            None => vec![0],
+        };
+
+        let shots = scene_starts
+            .iter()
+            .enumerate()
+            .map(|(i, &start)| {
+                let end = scene_starts.get(i + 1).copied().unwrap_or(stats.len());
+
+                VideoShot {
+                    start,
+                    duration: end - start,
+                    frame_edits: stats[start..end]
+                        .iter()
+                        .enumerate()
+                        .map(|(edit_offset, frame_stats)| ShotFrameEdit {
+                            edit_offset,
+                            metadata_blocks: vec![ExtMetadataBlock::Level1(
+                                frame_stats.to_level1(),
+                            )],
+                        })
+                        .collect(),
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        let config = GenerateConfig {
+            length: stats.len(),
+            shots,
+            ..Default::default()
+        };
+
+        let mut rpus = config.generate_rpu_list()?;
+
+        println!("Generated {} RPUs", rpus.len());
+
+        let data = GenerateConfig::encode_rpus(&mut rpus);
+        write_rpu_file(&self.rpu_out, data)
+    }
+}
+
+impl FrameStats {
+    fn to_level1(self) -> ExtMetadataBlockLevel1 {
+        ExtMetadataBlockLevel1::from_stats(self.min_pq, self.max_pq, self.avg_pq)
+    }
+}
+
+/// Measurements of every frame, when not sampling
+fn all_frames(stats: Vec<Option<FrameStats>>) -> Vec<FrameStats> {
+    stats.into_iter().flatten().collect()
+}
+
+/// Decodes the video to raw 10 bit luma frames on stdout
+fn spawn_ffmpeg(video: &Path) -> Result<Child> {
+    Command::new("ffmpeg")
+        .args(["-v", "error", "-nostdin", "-i"])
+        .arg(video)
+        .args(["-f", "rawvideo", "-pix_fmt", "gray10le", "-"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow!("Failed to run ffmpeg, is it in the PATH? {}", e))
+}
+
+/// Reads a complete frame, returns false at the end of the frames
+fn read_frame(reader: &mut dyn Read, frame: &mut [u8]) -> Result<bool> {
+    let mut filled = 0;
+
+    while filled < frame.len() {
+        match reader.read(&mut frame[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    match filled {
+        0 => Ok(false),
+        n if n == frame.len() => Ok(true),
+        n => bail!(
+            "Incomplete frame: {} bytes, expected {}. Check the frame size and pixel format",
+            n,
+            frame.len()
+        ),
+    }
+}
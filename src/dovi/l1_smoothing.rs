@@ -0,0 +1,167 @@
+use std::ops::Range;
+
+use anyhow::{ensure, Result};
+use serde::{Deserialize, Serialize};
+
+use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlock;
+
+use super::DoviRpu;
+
+/// Smoothing of the per-frame L1 metadata within every scene,
+/// to remove the single frame spikes of measured metadata.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct L1Smoothing {
+    method: L1SmoothingMethod,
+
+    /// Number of frames of the median filter, odd
+    #[serde(default = "L1Smoothing::default_window")]
+    window: usize,
+
+    /// Scenes shorter than this are smoothed together with the previous scene
+    #[serde(default)]
+    min_scene_length: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum L1SmoothingMethod {
+    /// Average of the scene, constant L1 for every frame
+    Average,
+    /// Median of the neighbouring frames within the scene
+    Median,
+}
+
+impl L1Smoothing {
+    fn default_window() -> usize {
+        5
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        ensure!(
+            self.window % 2 == 1,
+            "Invalid L1 smoothing window {}, must be odd",
+            self.window
+        );
+
+        Ok(())
+    }
+
+    pub fn method(&self) -> L1SmoothingMethod {
+        self.method
+    }
+
+    /// Smooths the L1 metadata of the RPUs, in order.
+    /// The scenes are split by the scene cut flags. Returns the number of smoothed scenes.
+    pub fn apply(&self, rpus: &mut [&mut DoviRpu]) -> usize {
+        let scene_cuts: Vec<bool> = rpus
+            .iter()
+            .map(|rpu| {
+                rpu.vdr_dm_data
+                    .as_ref()
+                    .is_some_and(|dm| dm.scene_refresh_flag == 1)
+            })
+            .collect();
+
+        let groups = self.scene_groups(&scene_cuts);
+
+        for group in &groups {
+            let group_rpus = &mut rpus[group.clone()];
+
+            // (min, avg, max) of the frames with L1 metadata
+            let values: Vec<[u16; 3]> = group_rpus
+                .iter()
+                .filter_map(|rpu| level1_values(rpu))
+                .collect();
+
+            let mut smoothed = self.smooth_values(&values).into_iter();
+
+            for rpu in group_rpus.iter_mut() {
+                if let Some(ref mut vdr_dm_data) = rpu.vdr_dm_data {
+                    if let Some(ExtMetadataBlock::Level1(level1)) = vdr_dm_data.get_block_mut(1) {
+                        if let Some([min_pq, avg_pq, max_pq]) = smoothed.next() {
+                            level1.min_pq = min_pq;
+                            level1.avg_pq = avg_pq;
+                            level1.max_pq = max_pq;
+
+                            rpu.modified = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        groups.len()
+    }
+
+    /// Frame ranges smoothed together: the scenes, with the short scenes merged into the previous one
+    pub fn scene_groups(&self, scene_cuts: &[bool]) -> Vec<Range<usize>> {
+        let mut groups: Vec<Range<usize>> = Vec::new();
+        let mut start = 0;
+
+        for end in 1..=scene_cuts.len() {
+            if end < scene_cuts.len() && !scene_cuts[end] {
+                continue;
+            }
+
+            let short_scene = end - start < self.min_scene_length;
+
+            match groups.last_mut() {
+                Some(previous) if short_scene => previous.end = end,
+                _ => groups.push(start..end),
+            }
+
+            start = end;
+        }
+
+        groups
+    }
+
+    /// Smoothed (min, avg, max) values of a group of frames
+    pub fn smooth_values(&self, values: &[[u16; 3]]) -> Vec<[u16; 3]> {
+        if values.is_empty() {
+            return Vec::new();
+        }
+
+        match self.method {
+            L1SmoothingMethod::Average => {
+                let mut average = [0; 3];
+
+                for (cmp, value) in average.iter_mut().enumerate() {
+                    let sum: u64 = values.iter().map(|v| v[cmp] as u64).sum();
+                    *value = ((sum as f64) / values.len() as f64).round() as u16;
+                }
+
+                vec![average; values.len()]
+            }
+            L1SmoothingMethod::Median => {
+                let radius = self.window / 2;
+
+                (0..values.len())
+                    .map(|i| {
+                        // Truncated at the bounds of the group, lower median for an even length
+                        let window =
+                            &values[i.saturating_sub(radius)..(i + radius + 1).min(values.len())];
+
+                        let mut median = [0; 3];
+
+                        for (cmp, value) in median.iter_mut().enumerate() {
+                            let mut sorted: Vec<u16> = window.iter().map(|v| v[cmp]).collect();
+                            sorted.sort_unstable();
+
+                            *value = sorted[(sorted.len() - 1) / 2];
+                        }
+
+                        median
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+fn level1_values(rpu: &DoviRpu) -> Option<[u16; 3]> {
+    match rpu.vdr_dm_data.as_ref()?.get_block(1)? {
+        ExtMetadataBlock::Level1(level1) => Some([level1.min_pq, level1.avg_pq, level1.max_pq]),
+        _ => None,
+    }
+}
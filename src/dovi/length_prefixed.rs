@@ -0,0 +1,108 @@
+use std::io::{self, BufRead, BufReader, ErrorKind, Read};
+
+const ANNEXB_START_CODE: &[u8] = &[0, 0, 0, 1];
+
+/// Larger lengths are taken as corrupt, rather than allocated
+pub const MAX_NAL_SIZE: usize = 256 * 1024 * 1024;
+
+/// Converts a stream of 4 bytes length-prefixed NAL units (hvcC style)
+/// to an Annex B stream, replacing each length by a 4 bytes start code.
+pub struct LengthPrefixedReader<R: Read> {
+    inner: R,
+    /// Bytes left in the input, when its size is known
+    remaining: Option<u64>,
+
+    buffer: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read> LengthPrefixedReader<R> {
+    /// `input_len` is the size of the input, to reject the lengths past its end
+    pub fn new(inner: R, input_len: Option<u64>) -> Self {
+        Self {
+            inner,
+            remaining: input_len,
+            buffer: Vec::with_capacity(100_000),
+            pos: 0,
+        }
+    }
+
+    /// Reads the next NAL unit into the buffer.
+    /// Returns false at the end of the stream.
+    fn fill_next_nal(&mut self) -> io::Result<bool> {
+        let mut size_bytes = [0; 4];
+        let mut read = 0;
+
+        while read < size_bytes.len() {
+            match self.inner.read(&mut size_bytes[read..]) {
+                Ok(0) if read == 0 => return Ok(false),
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        ErrorKind::UnexpectedEof,
+                        "Truncated NAL unit length",
+                    ))
+                }
+                Ok(n) => read += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        let size = u32::from_be_bytes(size_bytes) as usize;
+        let remaining = self
+            .remaining
+            .map(|remaining| remaining.saturating_sub(size_bytes.len() as u64));
+
+        if size > MAX_NAL_SIZE || remaining.is_some_and(|remaining| size as u64 > remaining) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid NAL unit length {}, the input is corrupt", size),
+            ));
+        }
+
+        self.remaining = remaining.map(|remaining| remaining - size as u64);
+
+        self.buffer.clear();
+        self.buffer.extend_from_slice(ANNEXB_START_CODE);
+        self.buffer.resize(ANNEXB_START_CODE.len() + size, 0);
+        self.inner
+            .read_exact(&mut self.buffer[ANNEXB_START_CODE.len()..])?;
+
+        self.pos = 0;
+
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for LengthPrefixedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.buffer.len() && !self.fill_next_nal()? {
+            return Ok(0);
+        }
+
+        let remaining = &self.buffer[self.pos..];
+        let len = remaining.len().min(buf.len());
+
+        buf[..len].copy_from_slice(&remaining[..len]);
+        self.pos += len;
+
+        Ok(len)
+    }
+}
+
+/// Detects length-prefixed NAL units from the start of the stream.
+/// Annex B streams start with a start code, hvcC NAL units start with their length.
+pub fn is_length_prefixed<R: Read>(reader: &mut BufReader<R>) -> io::Result<bool> {
+    let data = reader.fill_buf()?;
+
+    if data.len() < 6 || data.starts_with(&[0, 0, 1]) || data.starts_with(ANNEXB_START_CODE) {
+        return Ok(false);
+    }
+
+    let size = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+
+    // forbidden_zero_bit must be 0, nuh_temporal_id_plus1 must not be 0
+    let valid_nal_header = (data[4] & 0x80) == 0 && (data[5] & 0x07) != 0;
+
+    Ok(size > 1 && valid_nal_header)
+}
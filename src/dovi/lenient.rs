@@ -0,0 +1,221 @@
+use std::io::{Read, Write};
+
+use anyhow::{Error, Result};
+use log::warn;
+
+use hevc_parser::io::IoProcessor;
+use hevc_parser::HevcParser;
+
+use super::codec::Codec;
+use super::general_read_write::DoviProcessor;
+use super::nal_parser::{catch_parse_panic, NalIoProcessor};
+use super::rpu_stream::status_out;
+use super::start_code::get_offsets;
+
+/// Handles the unparseable RPUs with `--lenient`, instead of aborting.
+///
+/// An unparseable RPU is replaced by the previous frame's RPU,
+/// or skipped when there is no previous RPU. The frames are reported at the end.
+///
+/// The NAL units that can't be parsed are skipped up to the next start code,
+/// and the skipped bytes reported at the end.
+pub struct LenientParser {
+    codec: Codec,
+
+    /// Last RPU that could be parsed, encoded
+    previous_rpu: Option<Vec<u8>>,
+
+    /// Number of RPUs resolved
+    resolved: usize,
+
+    replaced: Vec<usize>,
+    skipped: Vec<usize>,
+
+    corrupt_nals: usize,
+    skipped_bytes: u64,
+}
+
+/// Same as `HevcProcessor::process_io`, parsing the NAL units one at a time
+/// so that the corrupt ones can be skipped.
+pub struct LenientHevcProcessor {
+    parser: HevcParser,
+    chunk_size: usize,
+}
+
+impl LenientParser {
+    pub fn new(codec: Codec) -> Self {
+        Self {
+            codec,
+            previous_rpu: None,
+            resolved: 0,
+            replaced: Vec::new(),
+            skipped: Vec::new(),
+            corrupt_nals: 0,
+            skipped_bytes: 0,
+        }
+    }
+
+    /// Skips a NAL unit that can't be parsed, of `size` bytes at `position` in the input
+    pub fn skip_corrupt_nal(&mut self, position: u64, size: usize, error: &Error) {
+        warn!(
+            "Byte {}: corrupt NAL unit, skipped {} bytes up to the next start code: {}",
+            position, size, error
+        );
+
+        self.corrupt_nals += 1;
+        self.skipped_bytes += size as u64;
+    }
+
+    /// The encoded RPU for the frame, from the result of parsing its RPU.
+    /// `None` when the RPU is skipped.
+    pub fn resolve(&mut self, frame: usize, res: Result<Vec<u8>>) -> Result<Option<Vec<u8>>> {
+        self.resolved += 1;
+
+        let error = match res {
+            Ok(data) => {
+                self.previous_rpu = Some(data.clone());
+                return Ok(Some(data));
+            }
+            Err(e) => e,
+        };
+
+        match &self.previous_rpu {
+            Some(previous_rpu) => {
+                warn!(
+                    "Frame {}: unparseable RPU, replaced by the previous frame's RPU: {}",
+                    frame, error
+                );
+
+                self.replaced.push(frame);
+                Ok(Some(duplicated_rpu_data(self.codec, previous_rpu)?))
+            }
+            None => {
+                warn!("Frame {}: unparseable RPU, skipped: {}", frame, error);
+
+                self.skipped.push(frame);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Index of the next RPU, when the RPUs are resolved in decoded order
+    pub fn next_index(&self) -> usize {
+        self.resolved
+    }
+
+    /// Prints the number of unparseable RPUs and corrupt NAL units, if any
+    pub fn print_summary(&self) {
+        let count = self.replaced.len() + self.skipped.len();
+
+        if count > 0 {
+            writeln!(
+                status_out(),
+                "Lenient parsing: {} unparseable RPUs, {} replaced by the previous frame's RPU, {} skipped.",
+                count,
+                self.replaced.len(),
+                self.skipped.len()
+            )
+            .ok();
+        }
+
+        if self.corrupt_nals > 0 {
+            writeln!(
+                status_out(),
+                "Lenient parsing: {} corrupt NAL units, {} bytes skipped.",
+                self.corrupt_nals,
+                self.skipped_bytes
+            )
+            .ok();
+        }
+    }
+}
+
+impl LenientHevcProcessor {
+    pub fn new(chunk_size: usize) -> Self {
+        Self {
+            parser: HevcParser::default(),
+            chunk_size,
+        }
+    }
+
+    /// Fully parse the input stream
+    pub fn process_io(
+        &mut self,
+        reader: &mut dyn Read,
+        processor: &mut DoviProcessor,
+    ) -> Result<()> {
+        let mut buf = vec![0; self.chunk_size];
+        let mut chunk: Vec<u8> = Vec::with_capacity(self.chunk_size * 2);
+        let mut offsets = Vec::with_capacity(2048);
+
+        // Offset of the chunk in the input
+        let mut position = 0;
+
+        loop {
+            let read_bytes = reader.read(&mut buf)?;
+            let eof = read_bytes == 0;
+
+            chunk.extend_from_slice(&buf[..read_bytes]);
+
+            get_offsets(&chunk, &mut offsets);
+
+            // The last NAL can be incomplete until the end of the stream
+            let last = if eof {
+                chunk.len()
+            } else if offsets.len() > 1 {
+                offsets.pop().unwrap()
+            } else {
+                continue;
+            };
+
+            let mut nals = Vec::with_capacity(offsets.len());
+
+            for (index, &offset) in offsets.iter().enumerate() {
+                let end = offsets.get(index + 1).copied().unwrap_or(last);
+
+                // `HevcParser` takes the rest of the data for a NAL unit ending at its offset
+                let nal_last = if end == chunk.len() { offset } else { end };
+
+                let parser = &mut self.parser;
+                let res = catch_parse_panic(|| {
+                    parser.split_nals(&chunk, &offsets[index..=index], nal_last, true)
+                });
+
+                match res {
+                    Ok(mut parsed) => nals.append(&mut parsed),
+                    Err(e) => processor.corrupt_nal(position + offset as u64, end - offset, e)?,
+                }
+            }
+
+            if !nals.is_empty() {
+                IoProcessor::process_nals(processor, &self.parser, &nals, &chunk)?;
+            }
+
+            if eof {
+                break;
+            }
+
+            chunk.drain(..last);
+            position += last as u64;
+        }
+
+        self.parser.finish();
+
+        IoProcessor::finalize(processor, &self.parser)
+    }
+}
+
+/// Copies the RPU data of a frame for a following frame, which cannot be a scene cut
+pub fn duplicated_rpu_data(codec: Codec, data: &[u8]) -> Result<Vec<u8>> {
+    let mut rpu = codec.parse_rpu_nalu(data)?;
+
+    match rpu.vdr_dm_data {
+        Some(ref mut vdr_dm_data) if vdr_dm_data.scene_refresh_flag == 1 => {
+            vdr_dm_data.set_scene_cut(false);
+            rpu.modified = true;
+
+            codec.write_rpu_nalu(&rpu)
+        }
+        _ => Ok(data.to_vec()),
+    }
+}
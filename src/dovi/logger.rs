@@ -0,0 +1,107 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use anyhow::Result;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use super::create_output_file;
+use super::rpu_stream::diagnostics_out;
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Logger of the diagnostics: warnings, errors and the debug messages of `-v` and `-vv`.
+///
+/// The records are written to the status output, prefixed by their level,
+/// and to the log file with the time elapsed since the start, unless `--deterministic`.
+pub struct Logger {
+    level: LevelFilter,
+    file: Option<Mutex<File>>,
+    start: Option<Instant>,
+}
+
+impl Logger {
+    /// Sets the global logger. `--quiet` also discards the status messages
+    pub fn init(
+        quiet: bool,
+        verbose: u64,
+        log_file: Option<&Path>,
+        deterministic: bool,
+    ) -> Result<()> {
+        let level = if quiet {
+            LevelFilter::Warn
+        } else {
+            match verbose {
+                0 => LevelFilter::Info,
+                1 => LevelFilter::Debug,
+                _ => LevelFilter::Trace,
+            }
+        };
+
+        let file = log_file
+            .map(create_output_file)
+            .transpose()?
+            .map(Mutex::new);
+
+        let logger = Logger {
+            level,
+            file,
+            start: (!deterministic).then(Instant::now),
+        };
+
+        QUIET.store(quiet, Ordering::SeqCst);
+
+        log::set_boxed_logger(Box::new(logger))?;
+        log::set_max_level(level);
+
+        Ok(())
+    }
+}
+
+/// Whether the status messages are discarded
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::SeqCst)
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let prefix = match record.level() {
+            Level::Error => "Error: ",
+            Level::Warn => "Warning: ",
+            Level::Info => "",
+            Level::Debug => "Debug: ",
+            Level::Trace => "Trace: ",
+        };
+
+        writeln!(diagnostics_out(), "{}{}", prefix, record.args()).ok();
+
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                if let Some(start) = self.start {
+                    write!(file, "[{:>10.3}s] ", start.elapsed().as_secs_f64()).ok();
+                }
+
+                writeln!(file, "{:<5} {}", record.level(), record.args()).ok();
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                file.flush().ok();
+            }
+        }
+    }
+}
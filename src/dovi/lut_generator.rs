@@ -0,0 +1,542 @@
+use std::fs;
+use std::io::{stdout, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, ensure, Context, Result};
+use rayon::prelude::*;
+
+use dolby_vision::rpu::extension_metadata::blocks::{ExtMetadataBlock, ExtMetadataBlockLevel2};
+use dolby_vision::rpu::reshaping::Reshaper;
+use dolby_vision::rpu::vdr_dm_data::VdrDmData;
+use dolby_vision::utils::{nits_to_pq, pq12_to_pq, pq_to_nits};
+use utilities_dovi::parse_rpu_file;
+
+use crate::commands::GenerateLutArgs;
+
+use super::{input_from_either, DoviRpu};
+
+type Matrix = [[f64; 3]; 3];
+
+/// BT.2020 non-constant luminance coefficients
+const KR: f64 = 0.2627;
+const KB: f64 = 0.0593;
+
+const BT2020_TO_BT709: Matrix = [
+    [1.6605, -0.5876, -0.0728],
+    [-0.1246, 1.1329, -0.0083],
+    [-0.0182, -0.1006, 1.1187],
+];
+
+pub struct LutGenerator {
+    input: PathBuf,
+    output: PathBuf,
+    target_nits: u16,
+    size: usize,
+    average: bool,
+    transfer: LutTransfer,
+    reshape_only: bool,
+}
+
+#[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum LutTransfer {
+    /// BT.2020 PQ
+    Pq,
+    /// BT.709 gamma 2.4
+    Sdr,
+}
+
+/// Consecutive frames, starting at a scene cut
+struct Shot {
+    start: usize,
+    frames: usize,
+}
+
+/// Maps the base layer to the target display, with the metadata of a single RPU.
+///
+/// The display management is an approximation, not the Dolby Vision algorithm:
+/// the BT.2390 EETF compresses the L1 range into the target range, followed by the
+/// slope/offset/power and saturation gain L2 trims of the target.
+pub struct DisplayMapping {
+    reshaper: Reshaper,
+
+    bl_bit_depth: u64,
+    bl_full_range: bool,
+
+    ycc_to_rgb: Matrix,
+    ycc_to_rgb_offset: [f64; 3],
+    /// RPU RGB to Dolby LMS, then back to BT.2020 RGB
+    rgb_to_bt2020: Matrix,
+
+    source_min_pq: f64,
+    source_max_pq: f64,
+    target_max_pq: f64,
+    target_nits: f64,
+
+    trim: Option<Trim>,
+    transfer: LutTransfer,
+    reshape_only: bool,
+}
+
+struct Trim {
+    slope: f64,
+    offset: f64,
+    power: f64,
+    saturation_gain: f64,
+}
+
+impl LutGenerator {
+    pub fn generate(args: GenerateLutArgs) -> Result<()> {
+        let GenerateLutArgs {
+            input,
+            input_pos,
+            output,
+            target_nits,
+            size,
+            average,
+            transfer,
+            reshape_only,
+        } = args;
+
+        let input = input_from_either("generate-lut", input, input_pos)?;
+
+        ensure!(
+            (2..=256).contains(&size),
+            "generate-lut: the LUT size must be between 2 and 256, got {}",
+            size
+        );
+        ensure!(target_nits > 0, "generate-lut: invalid target display");
+
+        let output =
+            output.unwrap_or_else(|| PathBuf::from(if average { "LUT.cube" } else { "LUTs" }));
+
+        let generator = LutGenerator {
+            input,
+            output,
+            target_nits,
+            size,
+            average,
+            transfer,
+            reshape_only,
+        };
+
+        println!("Parsing RPU file...");
+        stdout().flush().ok();
+
+        if let Some(rpus) = parse_rpu_file(&generator.input)? {
+            generator.execute(&rpus)?;
+        }
+
+        println!("Done.");
+
+        Ok(())
+    }
+
+    fn execute(&self, rpus: &[DoviRpu]) -> Result<()> {
+        let shots = shots(rpus);
+
+        println!("Generating LUTs for {} shots...", shots.len());
+        stdout().flush().ok();
+
+        // The metadata of the first frame is used for the whole shot
+        let luts = shots
+            .par_iter()
+            .map(|shot| {
+                let mapping = DisplayMapping::new(
+                    &rpus[shot.start],
+                    self.target_nits,
+                    self.transfer,
+                    self.reshape_only,
+                )
+                .with_context(|| format!("generate-lut: shot starting at frame {}", shot.start))?;
+                Ok(self.lut(&mapping))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if self.average {
+            let mut average = vec![[0.0; 3]; self.size.pow(3)];
+
+            for (shot, lut) in shots.iter().zip(&luts) {
+                let weight = shot.frames as f64 / rpus.len() as f64;
+
+                for (avg, value) in average.iter_mut().zip(lut) {
+                    avg.iter_mut()
+                        .zip(value)
+                        .for_each(|(a, v)| *a += v * weight);
+                }
+            }
+
+            let title = format!("Average of {} shots, {}", shots.len(), self.target_name());
+
+            self.write_cube(&self.output, &title, &average)?;
+        } else {
+            fs::create_dir_all(&self.output)?;
+
+            for (i, (shot, lut)) in shots.iter().zip(&luts).enumerate() {
+                let title = format!(
+                    "Shot {}, frames {}-{}, {}",
+                    i + 1,
+                    shot.start,
+                    shot.start + shot.frames - 1,
+                    self.target_name()
+                );
+                let path = self.output.join(format!("shot_{:05}.cube", i + 1));
+
+                self.write_cube(&path, &title, lut)?;
+            }
+        }
+
+        // The converted RPU expects the reshaped signal as base layer
+        if self.reshape_only && rpus.first().is_some_and(|rpu| rpu.dovi_profile == 5) {
+            println!(
+                "Profile 5: apply the LUTs to the IPTPQc2 base layer and re-encode it as BT.2020 PQ, \
+                 with the RPU converted with mode 3"
+            );
+        }
+
+        Ok(())
+    }
+
+    fn target_name(&self) -> String {
+        if self.reshape_only {
+            String::from("reshaped")
+        } else {
+            format!("{} nits", self.target_nits)
+        }
+    }
+
+    /// Output values of the grid, with the red index changing fastest
+    fn lut(&self, mapping: &DisplayMapping) -> Vec<[f64; 3]> {
+        let max = (self.size - 1) as f64;
+        let mut lut = Vec::with_capacity(self.size.pow(3));
+
+        for b in 0..self.size {
+            for g in 0..self.size {
+                for r in 0..self.size {
+                    lut.push(mapping.map([r as f64 / max, g as f64 / max, b as f64 / max]));
+                }
+            }
+        }
+
+        lut
+    }
+
+    fn write_cube(&self, path: &Path, title: &str, lut: &[[f64; 3]]) -> Result<()> {
+        let mut writer = BufWriter::new(super::create_output_file(path)?);
+
+        writeln!(writer, "TITLE \"{}\"", title)?;
+        writeln!(
+            writer,
+            "# Generated by {} {}",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        )?;
+        writeln!(writer, "# Input: base layer, BT.2020 PQ")?;
+        writeln!(
+            writer,
+            "# Output: {}",
+            match self.transfer {
+                _ if self.reshape_only => "BT.2020 PQ, without display management",
+                LutTransfer::Pq => "BT.2020 PQ",
+                LutTransfer::Sdr => "BT.709 gamma 2.4",
+            }
+        )?;
+        writeln!(writer, "LUT_3D_SIZE {}", self.size)?;
+
+        for [r, g, b] in lut {
+            writeln!(writer, "{:.6} {:.6} {:.6}", r, g, b)?;
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+impl DisplayMapping {
+    pub fn new(
+        rpu: &DoviRpu,
+        target_nits: u16,
+        transfer: LutTransfer,
+        reshape_only: bool,
+    ) -> Result<Self> {
+        let vdr_dm_data = match &rpu.vdr_dm_data {
+            Some(vdr_dm_data) => vdr_dm_data,
+            None => bail!("RPU without DM metadata"),
+        };
+
+        let reshaper = rpu.reshaper()?;
+
+        // The LMS conversion of profile 8.1 is from BT.2020
+        let mut p81_dm_data = VdrDmData::default();
+        p81_dm_data.set_p81_coeffs();
+
+        let lms_to_bt2020 = invert(&rgb_to_lms_matrix(&p81_dm_data))?;
+        let rgb_to_bt2020 = multiply(&lms_to_bt2020, &rgb_to_lms_matrix(vdr_dm_data));
+
+        // The L1 range of the shot, or the mastering display range
+        let (source_min_pq, source_max_pq) = match vdr_dm_data.get_block(1) {
+            Some(ExtMetadataBlock::Level1(level1)) => (level1.min_pq, level1.max_pq),
+            _ => (vdr_dm_data.source_min_pq, vdr_dm_data.source_max_pq),
+        };
+
+        let trim_target_pq = ExtMetadataBlockLevel2::from_nits(target_nits).target_max_pq;
+
+        let trim = vdr_dm_data
+            .level_blocks_iter(2)
+            .find_map(|block| match block {
+                ExtMetadataBlock::Level2(l2) if l2.target_max_pq == trim_target_pq => Some(Trim {
+                    slope: l2.trim_slope as f64 / 4096.0 + 0.5,
+                    offset: l2.trim_offset as f64 / 4096.0 - 0.5,
+                    power: l2.trim_power as f64 / 4096.0 + 0.5,
+                    saturation_gain: l2.trim_saturation_gain as f64 / 4096.0 + 0.5,
+                }),
+                _ => None,
+            });
+
+        Ok(Self {
+            reshaper,
+            bl_bit_depth: rpu.header.bl_bit_depth_minus8 + 8,
+            bl_full_range: rpu.header.bl_video_full_range_flag,
+            ycc_to_rgb: ycc_to_rgb_matrix(vdr_dm_data),
+            ycc_to_rgb_offset: [
+                vdr_dm_data.ycc_to_rgb_offset0,
+                vdr_dm_data.ycc_to_rgb_offset1,
+                vdr_dm_data.ycc_to_rgb_offset2,
+            ]
+            .map(|offset| offset as f64 / (1 << 28) as f64),
+            rgb_to_bt2020,
+            source_min_pq: pq12_to_pq(source_min_pq),
+            source_max_pq: pq12_to_pq(source_max_pq),
+            target_max_pq: nits_to_pq(target_nits as f64),
+            target_nits: target_nits as f64,
+            trim,
+            transfer,
+            reshape_only,
+        })
+    }
+
+    /// Maps a base layer BT.2020 PQ RGB value to the output signal
+    pub fn map(&self, rgb: [f64; 3]) -> [f64; 3] {
+        let bl = self.bl_code_values(rgb);
+        let vdr = self.reshaper.reshape(bl);
+
+        // Non linear RGB, then linear BT.2020 RGB in nits
+        let vdr_rgb = apply(
+            &self.ycc_to_rgb,
+            [0, 1, 2].map(|i| vdr[i] - self.ycc_to_rgb_offset[i]),
+        );
+        let linear = apply(
+            &self.rgb_to_bt2020,
+            vdr_rgb.map(|v| pq_to_nits(v.clamp(0.0, 1.0))),
+        )
+        .map(|v| v.max(0.0));
+
+        if self.reshape_only {
+            return linear.map(|v| nits_to_pq(v).clamp(0.0, 1.0));
+        }
+
+        let mapped = self.tone_map(linear);
+
+        match self.transfer {
+            LutTransfer::Pq => mapped.map(nits_to_pq),
+            LutTransfer::Sdr => apply(&BT2020_TO_BT709, mapped)
+                .map(|v| (v / self.target_nits).clamp(0.0, 1.0).powf(1.0 / 2.4)),
+        }
+    }
+
+    /// Normalized Y'CbCr code values of the base layer
+    fn bl_code_values(&self, rgb: [f64; 3]) -> [f64; 3] {
+        let [r, g, b] = rgb;
+
+        let y = KR * r + (1.0 - KR - KB) * g + KB * b;
+        let cb = (b - y) / (2.0 * (1.0 - KB));
+        let cr = (r - y) / (2.0 * (1.0 - KR));
+
+        // Code values divided by 2^bit_depth, as the DM offsets
+        if self.bl_full_range {
+            let full_scale = (1 << self.bl_bit_depth) as f64;
+            let max_code = full_scale - 1.0;
+
+            [
+                y * max_code / full_scale,
+                cb * max_code / full_scale + 0.5,
+                cr * max_code / full_scale + 0.5,
+            ]
+        } else {
+            [
+                (16.0 + 219.0 * y) / 256.0,
+                (128.0 + 224.0 * cb) / 256.0,
+                (128.0 + 224.0 * cr) / 256.0,
+            ]
+        }
+    }
+
+    /// Tone maps the maximum component in PQ, keeping the ratios between components
+    fn tone_map(&self, rgb: [f64; 3]) -> [f64; 3] {
+        let max = rgb[0].max(rgb[1]).max(rgb[2]);
+
+        if max <= 0.0 {
+            return [0.0; 3];
+        }
+
+        let mut pq = self.eetf(nits_to_pq(max));
+
+        if let Some(trim) = &self.trim {
+            let x = pq / self.target_max_pq;
+            pq = (x * trim.slope + trim.offset)
+                .clamp(0.0, 1.0)
+                .powf(trim.power)
+                * self.target_max_pq;
+        }
+
+        let ratio = pq_to_nits(pq) / max;
+        let mut mapped = rgb.map(|v| v * ratio);
+
+        if let Some(trim) = &self.trim {
+            let luma = KR * mapped[0] + (1.0 - KR - KB) * mapped[1] + KB * mapped[2];
+            mapped = mapped.map(|v| luma + (v - luma) * trim.saturation_gain);
+        }
+
+        mapped.map(|v| v.clamp(0.0, self.target_nits))
+    }
+
+    /// BT.2390 EETF, from the source range to the target range, on normalized PQ values
+    fn eetf(&self, pq: f64) -> f64 {
+        let source_range = self.source_max_pq - self.source_min_pq;
+
+        // Nothing to compress
+        if source_range <= 0.0 || self.source_max_pq <= self.target_max_pq {
+            return pq.min(self.target_max_pq);
+        }
+
+        let e1 = ((pq - self.source_min_pq) / source_range).clamp(0.0, 1.0);
+        let max_lum = (self.target_max_pq - self.source_min_pq) / source_range;
+        let knee = (1.5 * max_lum - 0.5).max(0.0);
+
+        let e2 = if e1 < knee {
+            e1
+        } else {
+            let t = (e1 - knee) / (1.0 - knee);
+            let (t2, t3) = (t * t, t * t * t);
+
+            (2.0 * t3 - 3.0 * t2 + 1.0) * knee
+                + (t3 - 2.0 * t2 + t) * (1.0 - knee)
+                + (-2.0 * t3 + 3.0 * t2) * max_lum
+        };
+
+        // Values below the source minimum are kept
+        if pq < self.source_min_pq {
+            pq
+        } else {
+            e2 * source_range + self.source_min_pq
+        }
+    }
+}
+
+fn shots(rpus: &[DoviRpu]) -> Vec<Shot> {
+    let mut shots: Vec<Shot> = Vec::new();
+
+    for (i, rpu) in rpus.iter().enumerate() {
+        let scene_cut = rpu
+            .vdr_dm_data
+            .as_ref()
+            .map(|dm| dm.scene_refresh_flag == 1)
+            .unwrap_or(false);
+
+        match shots.last_mut() {
+            Some(shot) if !scene_cut => shot.frames += 1,
+            _ => shots.push(Shot {
+                start: i,
+                frames: 1,
+            }),
+        }
+    }
+
+    shots
+}
+
+fn ycc_to_rgb_matrix(dm: &VdrDmData) -> Matrix {
+    [
+        [
+            dm.ycc_to_rgb_coef0,
+            dm.ycc_to_rgb_coef1,
+            dm.ycc_to_rgb_coef2,
+        ],
+        [
+            dm.ycc_to_rgb_coef3,
+            dm.ycc_to_rgb_coef4,
+            dm.ycc_to_rgb_coef5,
+        ],
+        [
+            dm.ycc_to_rgb_coef6,
+            dm.ycc_to_rgb_coef7,
+            dm.ycc_to_rgb_coef8,
+        ],
+    ]
+    .map(|row| row.map(|coef| coef as f64 / 8192.0))
+}
+
+fn rgb_to_lms_matrix(dm: &VdrDmData) -> Matrix {
+    [
+        [
+            dm.rgb_to_lms_coef0,
+            dm.rgb_to_lms_coef1,
+            dm.rgb_to_lms_coef2,
+        ],
+        [
+            dm.rgb_to_lms_coef3,
+            dm.rgb_to_lms_coef4,
+            dm.rgb_to_lms_coef5,
+        ],
+        [
+            dm.rgb_to_lms_coef6,
+            dm.rgb_to_lms_coef7,
+            dm.rgb_to_lms_coef8,
+        ],
+    ]
+    .map(|row| row.map(|coef| coef as f64 / 16384.0))
+}
+
+fn apply(m: &Matrix, v: [f64; 3]) -> [f64; 3] {
+    m.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut m = [[0.0; 3]; 3];
+
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+
+    m
+}
+
+fn invert(m: &Matrix) -> Result<Matrix> {
+    let cofactor =
+        |r0: usize, r1: usize, c0: usize, c1: usize| m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+
+    let det = m[0][0] * cofactor(1, 2, 1, 2) - m[0][1] * cofactor(1, 2, 0, 2)
+        + m[0][2] * cofactor(1, 2, 0, 1);
+
+    ensure!(det.abs() > 1e-9, "singular color matrix");
+
+    Ok([
+        [
+            cofactor(1, 2, 1, 2) / det,
+            -cofactor(0, 2, 1, 2) / det,
+            cofactor(0, 1, 1, 2) / det,
+        ],
+        [
+            -cofactor(1, 2, 0, 2) / det,
+            cofactor(0, 2, 0, 2) / det,
+            -cofactor(0, 1, 0, 2) / det,
+        ],
+        [
+            cofactor(1, 2, 0, 1) / det,
+            -cofactor(0, 2, 0, 1) / det,
+            cofactor(0, 1, 0, 1) / det,
+        ],
+    ])
+}
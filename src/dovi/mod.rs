@@ -1,84 +1,252 @@
 use std::convert::TryInto;
-use std::io::Write;
+use std::io::{self, stdin, BufReader, Read, Seek, SeekFrom, Write};
 use std::path::PathBuf;
-use std::{fs::File, io::BufWriter, path::Path};
+use std::{fs::File, path::Path};
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, ensure, Context, Result};
 use indicatif::{ProgressBar, ProgressStyle};
+use log::debug;
 
 use dolby_vision::rpu::dovi_rpu::DoviRpu;
 
-use hevc_parser::hevc::{NALUnit, SeiMessage, NAL_UNSPEC62, USER_DATA_REGISTERED_ITU_T_35};
+use hevc_parser::hevc::{SeiMessage, USER_DATA_REGISTERED_ITU_T_35};
 use hevc_parser::io::{IoFormat, StartCodePreset};
+use hevc_parser::utils::clear_start_code_emulation_prevention_3_byte;
 
+use hdr10plus::metadata::Hdr10PlusMetadata;
+
+use self::codec::Codec;
 use self::editor::EditConfig;
+use self::frame_order_map::FrameOrderMap;
+use self::hdr10_sei::Hdr10SeiOpts;
+use self::http_input::HttpReader;
+use self::profile_mix::NormalizeProfile;
+use self::rpu_stream::{status_out, RpuWriter};
+use self::script_processor::SharedRpuProcessor;
+use self::sei_filter::SeiFilter;
 
+pub mod active_area_detector;
+pub mod aligner;
+pub mod au_alignment;
+pub mod avc_parser;
+pub mod batch;
+pub mod change_summary;
+pub mod chapters;
+pub mod checkpoint;
+pub mod codec;
+pub mod concatenator;
+pub mod conformer;
 pub mod converter;
 pub mod demuxer;
+pub mod deterministic;
+pub mod differ;
+pub mod dovi_adder;
+pub mod edit_presets;
 pub mod editor;
+pub mod el_analysis;
+pub mod el_report;
 pub mod exporter;
+pub mod frame_limit;
+pub mod frame_order_map;
 pub mod generator;
+pub mod hasher;
+pub mod hdr10_sei;
+pub mod hdr10plus_metadata;
+pub mod hevc_processor;
+pub mod hevc_sps;
+pub mod hevc_vps;
+pub mod http_input;
+pub mod in_place_injector;
+pub mod injection_check;
+pub mod input_skip;
+pub mod interrupt;
+pub mod l1_csv;
+pub mod l1_measurer;
+pub mod l1_smoothing;
+pub mod length_prefixed;
+pub mod lenient;
+pub mod logger;
+pub mod lut_generator;
+pub mod mp4_writer;
 pub mod muxer;
+pub mod nal_parser;
+pub mod overlay_subtitles;
+pub mod parameter_sets;
+pub mod patcher;
+pub mod plotter;
+pub mod previewer;
+pub mod profile_mix;
+pub mod progress;
+pub mod remover;
 pub mod rpu_extractor;
 pub mod rpu_info;
 pub mod rpu_injector;
+pub mod rpu_stream;
+pub mod run_stats;
+pub mod scene_file;
+pub mod script_processor;
+pub mod sei_filter;
+pub mod server;
+pub mod sha256;
+pub mod splitter;
+pub mod strict;
+pub mod threaded_writer;
+pub mod timecode;
+pub mod timestamps;
+pub mod verifier;
+pub mod vvc_parser;
+pub mod xml_differ;
+
+pub use utilities_dovi::start_code;
 
 mod general_read_write;
 
+/// Size of the chunks read from the input, when not set with `--read-buffer`
+pub const DEFAULT_READ_BUFFER: usize = 4 * 1024 * 1024;
+
+/// Size of the output buffers, when not set with `--write-buffer`
+pub const DEFAULT_WRITE_BUFFER: usize = 4 * 1024 * 1024;
+
+const MIN_BUFFER_SIZE: usize = 64 * 1024;
+
 #[derive(Debug, Clone)]
 pub struct CliOptions {
     pub mode: Option<u8>,
     pub crop: bool,
+    pub drop_l6: bool,
+    pub remove_mapping: bool,
+    pub min_pq: Option<u16>,
+    pub max_pq: Option<u16>,
+    pub retarget_trim: Option<u16>,
     pub discard_el: bool,
-    pub drop_hdr10plus: bool,
+    pub keep_el_prefix: bool,
+    pub keep_rpu_position: bool,
+    pub rewrite_vps: bool,
+    pub el_report: Option<PathBuf>,
+    pub repair_rpus: bool,
+    pub stream_rpus: bool,
+    pub crc_mode: Option<CrcMode>,
+    pub frame_order: Option<FrameOrderMap>,
+    pub normalize: Option<NormalizeProfile>,
+    pub lenient: bool,
+    pub strict: bool,
+    pub sei_filter: SeiFilter,
+    pub hdr10_sei: Option<Hdr10SeiOpts>,
     pub edit_config: Option<EditConfig>,
+    pub rpu_processor: Option<SharedRpuProcessor>,
     pub start_code: WriteStartCodePreset,
+    pub add_aud: bool,
+    pub field_coded: bool,
+    pub input_format: InputFormat,
+    pub input_codec: InputCodec,
+    pub skip_bytes: u64,
+    pub skip_frames: u64,
+    pub limit: Option<u64>,
+    pub progress: ProgressFormat,
+    pub stats: bool,
+    pub read_buffer: usize,
+    pub write_buffer: usize,
+}
+
+impl CliOptions {
+    /// Whether the RPUs are converted when rewriting them
+    pub fn converts_rpus(&self) -> bool {
+        self.mode.is_some() || self.edit_config.is_some() || self.rpu_processor.is_some()
+    }
 }
 
 #[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq)]
 pub enum WriteStartCodePreset {
     Four,
     AnnexB,
+    /// `AnnexB` start codes, with an AUD at the start of every access unit as required in MPEG-TS
+    Ts,
+}
+
+#[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum ProgressFormat {
+    Bar,
+    Json,
+}
+
+#[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum InputFormat {
+    Auto,
+    AnnexB,
+    Hvcc,
+}
+
+#[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum InputCodec {
+    Auto,
+    Hevc,
+    Avc,
+    Vvc,
+}
+
+/// How the RPUs with an invalid CRC32 are handled when extracting
+#[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum CrcMode {
+    /// Only report the corrupt RPUs
+    Check,
+    /// Recompute the CRC32 of the RPUs that can still be parsed
+    Fix,
+    /// Replace the corrupt RPUs with the previous valid RPU
+    Replace,
 }
 
 pub fn initialize_progress_bar(format: &IoFormat, input: &Path) -> Result<ProgressBar> {
-    let pb: ProgressBar;
-    let bytes_count;
+    let bytes_count = match format {
+        IoFormat::RawStdin => None,
+        // Unknown for chunked HTTP responses
+        _ if http_input::is_url(input) => HttpReader::open(input)?.size(),
+        _ => {
+            let file = File::open(input)
+                .with_context(|| format!("Can't open input file {}", input.display()))?;
 
-    if let IoFormat::RawStdin = format {
-        pb = ProgressBar::hidden();
-    } else {
-        let file = File::open(input).expect("No file found");
+            Some(file.metadata()?.len())
+        }
+    };
 
-        //Info for indicatif ProgressBar
-        let file_meta = file.metadata()?;
-        bytes_count = file_meta.len() / 100_000_000;
+    let pb = if let Some(bytes_count) = bytes_count {
+        let pb = ProgressBar::new(bytes_count);
+        pb.set_style(
+            ProgressStyle::default_bar()
+                .template("[{elapsed_precise}] {bar:60.cyan} {percent}% {bytes_per_sec} ETA {eta}"),
+        );
 
-        pb = ProgressBar::new(bytes_count);
+        pb
+    } else {
+        // Unknown length, only show the amount of bytes read
+        let pb = ProgressBar::new_spinner();
         pb.set_style(
-            ProgressStyle::default_bar().template("[{elapsed_precise}] {bar:60.cyan} {percent}%"),
+            ProgressStyle::default_spinner()
+                .template("[{elapsed_precise}] {spinner} {bytes} ({bytes_per_sec})"),
         );
-    }
+
+        pb
+    };
 
     Ok(pb)
 }
 
+/// Creates the output file, with the path in the error message
+pub fn create_output_file(path: &Path) -> Result<File> {
+    let file = File::create(path)
+        .with_context(|| format!("Can't create output file {}", path.display()))?;
+
+    deterministic::record_output(path);
+
+    Ok(file)
+}
+
+/// Writes the encoded RPUs to an RPU file, or as a framed RPU stream on stdout for `-`
 pub fn write_rpu_file(output_path: &Path, data: Vec<Vec<u8>>) -> Result<()> {
-    println!("Writing RPU file...");
-    let mut writer = BufWriter::with_capacity(
-        100_000,
-        File::create(output_path).expect("Can't create file"),
-    );
+    let mut writer = RpuWriter::create(output_path)?;
+    writeln!(status_out(), "Writing RPU file...").ok();
 
     for encoded_rpu in data {
-        // Remove 0x7C01
-        NALUnit::write_with_preset(
-            &mut writer,
-            &encoded_rpu[2..],
-            WriteStartCodePreset::Four.into(),
-            NAL_UNSPEC62,
-            true,
-        )?;
+        writer.write_rpu(&encoded_rpu)?;
     }
 
     writer.flush()?;
@@ -86,6 +254,16 @@ pub fn write_rpu_file(output_path: &Path, data: Vec<Vec<u8>>) -> Result<()> {
     Ok(())
 }
 
+/// Sets the RPU as the first frame of a scene
+pub fn set_scene_cut(rpu: &mut DoviRpu) {
+    if let Some(ref mut vdr_dm_data) = rpu.vdr_dm_data {
+        if vdr_dm_data.scene_refresh_flag != 1 {
+            vdr_dm_data.set_scene_cut(true);
+            rpu.modified = true;
+        }
+    }
+}
+
 pub fn is_st2094_40_sei(sei_payload: &[u8]) -> Result<bool> {
     if sei_payload.len() >= 4 {
         let sei = SeiMessage::from_bytes(sei_payload)?;
@@ -119,12 +297,39 @@ pub fn is_st2094_40_sei(sei_payload: &[u8]) -> Result<bool> {
     Ok(false)
 }
 
-pub fn convert_encoded_from_opts(opts: &CliOptions, data: &[u8]) -> Result<Vec<u8>> {
-    let mut dovi_rpu = DoviRpu::parse_unspec62_nalu(data)?;
+/// Parses the HDR10+ metadata from a ST2094-40 SEI NAL unit
+pub fn parse_st2094_40_sei(sei_payload: &[u8]) -> Result<Hdr10PlusMetadata> {
+    // NAL header, payload type and payload size
+    let header_len = 4;
+
+    if sei_payload.len() <= header_len {
+        bail!("Invalid ST2094-40 SEI length: {}", sei_payload.len());
+    }
+
+    let itu_t35_bytes = clear_start_code_emulation_prevention_3_byte(&sei_payload[header_len..]);
+
+    Hdr10PlusMetadata::parse(&itu_t35_bytes)
+}
+
+pub fn convert_encoded_from_opts(opts: &CliOptions, codec: Codec, data: &[u8]) -> Result<Vec<u8>> {
+    let mut dovi_rpu = codec.parse_rpu_nalu(data)?;
+
+    convert_rpu_from_opts(opts, &mut dovi_rpu)?;
+
+    codec.write_rpu_nalu(&dovi_rpu)
+}
+
+/// Applies the RPU processing options: the edit config, or the mode and manual edits
+pub fn convert_rpu_from_opts(opts: &CliOptions, dovi_rpu: &mut DoviRpu) -> Result<()> {
+    if let Some(normalize) = opts.normalize {
+        let mode = normalize.mode(dovi_rpu.dovi_profile)?;
+        dovi_rpu.convert_with_mode(mode)?;
+    }
 
     // Config overrides manual arguments
     if let Some(edit_config) = &opts.edit_config {
-        edit_config.execute_single_rpu(&mut dovi_rpu)?;
+        edit_config.execute_single_rpu(dovi_rpu)?;
+        edit_config.generate_trims(dovi_rpu)?;
     } else {
         if let Some(mode) = opts.mode {
             dovi_rpu.convert_with_mode(mode)?;
@@ -133,9 +338,185 @@ pub fn convert_encoded_from_opts(opts: &CliOptions, data: &[u8]) -> Result<Vec<u
         if opts.crop {
             dovi_rpu.crop()?;
         }
+
+        if opts.drop_l6 {
+            editor::drop_level6_metadata(dovi_rpu);
+        }
+
+        if opts.remove_mapping {
+            dovi_rpu.remove_mapping();
+        }
+
+        if opts.min_pq.is_some() || opts.max_pq.is_some() {
+            dovi_rpu.modified = true;
+
+            if let Some(ref mut vdr_dm_data) = dovi_rpu.vdr_dm_data {
+                vdr_dm_data.change_source_levels(opts.min_pq, opts.max_pq);
+            }
+        }
+    }
+
+    if let Some(target_nits) = opts.retarget_trim {
+        editor::retarget_level2_trim(dovi_rpu, target_nits)?;
+    }
+
+    if let Some(rpu_processor) = &opts.rpu_processor {
+        rpu_processor.process_rpu(dovi_rpu)?;
+    }
+
+    Ok(())
+}
+
+/// Parses a size in bytes, with an optional K, M or G suffix
+pub fn parse_byte_size(value: &str) -> Option<u64> {
+    let (digits, multiplier) = match value.chars().last() {
+        Some('K' | 'k') => (&value[..value.len() - 1], 1024),
+        Some('M' | 'm') => (&value[..value.len() - 1], 1024 * 1024),
+        Some('G' | 'g') => (&value[..value.len() - 1], 1024 * 1024 * 1024),
+        _ => (value, 1),
+    };
+
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|size| size.checked_mul(multiplier))
+}
+
+/// Parses a buffer size in bytes, with an optional K or M suffix
+pub fn parse_buffer_size(value: &str) -> Result<usize> {
+    let size = parse_byte_size(value)
+        .and_then(|size| usize::try_from(size).ok())
+        .ok_or_else(|| anyhow!("Invalid buffer size {}", value))?;
+
+    ensure!(
+        size >= MIN_BUFFER_SIZE,
+        "The buffer size must be at least 64K, got {}",
+        value
+    );
+
+    Ok(size)
+}
+
+/// Opens the HEVC input, converting length-prefixed NAL units to Annex B if needed.
+/// The progress bar is updated with the number of bytes read from the input.
+pub fn open_hevc_input(
+    format: &IoFormat,
+    input: &Path,
+    input_format: InputFormat,
+    progress_bar: &ProgressBar,
+    read_buffer: usize,
+) -> Result<Box<dyn Read>> {
+    open_hevc_input_at(format, input, input_format, progress_bar, 0, read_buffer)
+}
+
+/// Same as `open_hevc_input`, starting `skip_bytes` into the input.
+/// Files and URLs are seeked, stdin is read and discarded.
+///
+/// Only Annex B inputs can be resynchronized, so length-prefixed inputs can't be skipped.
+pub fn open_hevc_input_at(
+    format: &IoFormat,
+    input: &Path,
+    input_format: InputFormat,
+    progress_bar: &ProgressBar,
+    skip_bytes: u64,
+    read_buffer: usize,
+) -> Result<Box<dyn Read>> {
+    if let IoFormat::RawStdin = format {
+        let mut reader = BufReader::with_capacity(read_buffer, progress_bar.wrap_read(stdin()));
+        let length_prefixed = is_input_length_prefixed(&mut reader, input_format, skip_bytes)?;
+
+        if skip_bytes > 0 {
+            io::copy(&mut (&mut reader).take(skip_bytes), &mut io::sink())?;
+        }
+
+        Ok(length_prefixed_or_annexb(reader, length_prefixed, None))
+    } else if http_input::is_url(input) {
+        let reader = HttpReader::open(input)?;
+        let input_len = reader.size();
+
+        open_seekable_input(
+            reader,
+            input_len,
+            input_format,
+            progress_bar,
+            skip_bytes,
+            read_buffer,
+        )
+    } else {
+        let file = File::open(input)?;
+        let input_len = Some(file.metadata()?.len());
+
+        open_seekable_input(
+            file,
+            input_len,
+            input_format,
+            progress_bar,
+            skip_bytes,
+            read_buffer,
+        )
+    }
+}
+
+fn open_seekable_input<R: Read + Seek + 'static>(
+    input: R,
+    input_len: Option<u64>,
+    input_format: InputFormat,
+    progress_bar: &ProgressBar,
+    skip_bytes: u64,
+    read_buffer: usize,
+) -> Result<Box<dyn Read>> {
+    let mut reader = BufReader::with_capacity(read_buffer, progress_bar.wrap_read(input));
+    let length_prefixed = is_input_length_prefixed(&mut reader, input_format, skip_bytes)?;
+
+    if skip_bytes > 0 {
+        reader.seek(SeekFrom::Start(skip_bytes))?;
     }
 
-    dovi_rpu.write_hevc_unspec62_nalu()
+    Ok(length_prefixed_or_annexb(
+        reader,
+        length_prefixed,
+        input_len,
+    ))
+}
+
+fn is_input_length_prefixed<R: Read>(
+    reader: &mut BufReader<R>,
+    input_format: InputFormat,
+    skip_bytes: u64,
+) -> Result<bool> {
+    let length_prefixed = match input_format {
+        InputFormat::Auto => {
+            let length_prefixed = length_prefixed::is_length_prefixed(reader)?;
+            debug!(
+                "Detected input format: {}",
+                if length_prefixed { "hvcc" } else { "annex-b" }
+            );
+
+            length_prefixed
+        }
+        InputFormat::AnnexB => false,
+        InputFormat::Hvcc => true,
+    };
+
+    if length_prefixed && skip_bytes > 0 {
+        bail!("--skip-bytes is only supported for Annex B inputs, use --skip-frames instead");
+    }
+
+    Ok(length_prefixed)
+}
+
+fn length_prefixed_or_annexb<R: Read + 'static>(
+    reader: BufReader<R>,
+    length_prefixed: bool,
+    input_len: Option<u64>,
+) -> Box<dyn Read> {
+    if length_prefixed {
+        Box::new(length_prefixed::LengthPrefixedReader::new(
+            reader, input_len,
+        ))
+    } else {
+        Box::new(reader)
+    }
 }
 
 pub fn input_from_either(cmd: &str, in1: Option<PathBuf>, in2: Option<PathBuf>) -> Result<PathBuf> {
@@ -148,11 +529,18 @@ pub fn input_from_either(cmd: &str, in1: Option<PathBuf>, in2: Option<PathBuf>)
     }
 }
 
+impl WriteStartCodePreset {
+    /// Whether every access unit must start with an AUD
+    pub fn requires_aud(&self) -> bool {
+        *self == WriteStartCodePreset::Ts
+    }
+}
+
 impl From<WriteStartCodePreset> for StartCodePreset {
     fn from(p: WriteStartCodePreset) -> Self {
         match p {
             WriteStartCodePreset::Four => StartCodePreset::Four,
-            WriteStartCodePreset::AnnexB => StartCodePreset::AnnexB,
+            WriteStartCodePreset::AnnexB | WriteStartCodePreset::Ts => StartCodePreset::AnnexB,
         }
     }
 }
@@ -0,0 +1,739 @@
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, ensure, Result};
+use hevc_parser::hevc::{
+    NALUnit, NAL_AUD, NAL_BLA_W_LP, NAL_IRAP_VCL23, NAL_PPS, NAL_SPS, NAL_UNSPEC62, NAL_UNSPEC63,
+    NAL_VPS,
+};
+use hevc_parser::io::processor::HevcProcessorOpts;
+use hevc_parser::io::{IoFormat, IoProcessor};
+use hevc_parser::HevcParser;
+use indicatif::ProgressBar;
+
+use super::codec::Codec;
+use super::hevc_processor::HevcProcessor;
+use super::hevc_sps::SpsInfo;
+use super::{open_hevc_input, CliOptions, DoviRpu};
+
+/// UNSPEC63 NAL header prefixed to the muxed EL NAL units
+const EL_NALU_HEADER_LEN: usize = 2;
+
+const NAL_LENGTH_SIZE: usize = 4;
+
+const BL_TRACK_ID: u32 = 1;
+const EL_TRACK_ID: u32 = 2;
+
+/// Dolby Vision levels: (max width, max pixel rate)
+const DOVI_LEVELS: &[(u32, u64)] = &[
+    (1280, 22_118_400),
+    (1280, 27_648_000),
+    (1920, 49_766_400),
+    (1920, 62_208_000),
+    (1920, 124_416_000),
+    (3840, 199_065_600),
+    (3840, 248_832_000),
+    (3840, 398_131_200),
+    (3840, 497_664_000),
+    (3840, 995_328_000),
+    (7680, 995_328_000),
+    (7680, 1_990_656_000),
+    (7680, 3_981_312_000),
+];
+
+/// Frame rate of the output, as a fraction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameRate {
+    pub num: u32,
+    pub den: u32,
+}
+
+impl Default for FrameRate {
+    fn default() -> Self {
+        Self {
+            num: 24000,
+            den: 1001,
+        }
+    }
+}
+
+impl std::str::FromStr for FrameRate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (num, den) = match s.split_once('/') {
+            Some((num, den)) => (num.trim().parse()?, den.trim().parse()?),
+            None => {
+                // Decimal frame rates are kept to the millisecond
+                let value: f64 = s.trim().parse()?;
+                ensure!(value.is_finite(), "Invalid frame rate {}", s);
+
+                ((value * 1000.0).round() as u32, 1000)
+            }
+        };
+
+        ensure!(num > 0 && den > 0, "Invalid frame rate {}", s);
+
+        Ok(Self { num, den })
+    }
+}
+
+/// Writes the BL and EL of a dual layer HEVC stream as a dual-track MP4,
+/// the EL track referencing the BL track with the Dolby Vision configuration.
+///
+/// The samples are written to the `mdat` as they are parsed, and the `moov` is appended at the end.
+pub struct DualTrackMp4Writer {
+    input: PathBuf,
+    progress_bar: ProgressBar,
+    frame_rate: FrameRate,
+
+    writer: BufWriter<File>,
+    mdat_start: u64,
+    offset: u64,
+
+    bl: TrackSamples,
+    el: TrackSamples,
+
+    /// Decoded index of the samples being gathered
+    current_index: Option<u64>,
+
+    dovi_profile: Option<u8>,
+}
+
+#[derive(Default)]
+struct TrackSamples {
+    vps: Option<Vec<u8>>,
+    sps: Option<Vec<u8>>,
+    pps: Option<Vec<u8>>,
+
+    /// Length-prefixed NAL units of the current sample
+    current: Vec<u8>,
+
+    sizes: Vec<u32>,
+    offsets: Vec<u64>,
+}
+
+impl DualTrackMp4Writer {
+    /// Parses the input and writes the MP4 output
+    pub fn write(
+        format: &IoFormat,
+        input: &Path,
+        output: &Path,
+        frame_rate: FrameRate,
+        options: &CliOptions,
+        progress_bar: ProgressBar,
+    ) -> Result<()> {
+        let chunk_size = options.read_buffer;
+
+        let mut reader = open_hevc_input(
+            format,
+            input,
+            options.input_format,
+            &progress_bar,
+            chunk_size,
+        )?;
+        let mut writer = Self::create(
+            input,
+            output,
+            frame_rate,
+            options.write_buffer,
+            progress_bar,
+        )?;
+
+        let processor_opts = HevcProcessorOpts {
+            parse_nals: true,
+            ..Default::default()
+        };
+        let mut processor = HevcProcessor::new(format.clone(), processor_opts, chunk_size);
+
+        processor.process_io(&mut reader, &mut writer)
+    }
+
+    fn create(
+        input: &Path,
+        output: &Path,
+        frame_rate: FrameRate,
+        write_buffer: usize,
+        progress_bar: ProgressBar,
+    ) -> Result<Self> {
+        let mut writer = BufWriter::with_capacity(write_buffer, super::create_output_file(output)?);
+
+        let ftyp = boxed(b"ftyp", |b| {
+            b.extend_from_slice(b"isom");
+            b.extend_from_slice(&512_u32.to_be_bytes());
+            b.extend_from_slice(b"isomiso6mp41dby1");
+        });
+        writer.write_all(&ftyp)?;
+
+        // 64 bits size, set once all the samples are written
+        let mdat_start = ftyp.len() as u64;
+        writer.write_all(&1_u32.to_be_bytes())?;
+        writer.write_all(b"mdat")?;
+        writer.write_all(&0_u64.to_be_bytes())?;
+
+        Ok(Self {
+            input: input.to_path_buf(),
+            progress_bar,
+            frame_rate,
+            writer,
+            mdat_start,
+            offset: mdat_start + 16,
+            bl: TrackSamples::default(),
+            el: TrackSamples::default(),
+            current_index: None,
+            dovi_profile: None,
+        })
+    }
+
+    fn add_nal(&mut self, nal: &NALUnit, data: &[u8]) -> Result<()> {
+        if self.current_index != Some(nal.decoded_frame_index) {
+            self.write_current_samples()?;
+            self.current_index = Some(nal.decoded_frame_index);
+        }
+
+        match nal.nal_type {
+            NAL_AUD => {}
+            // EL carried in its own layer
+            _ if nal.nuh_layer_id > 0 => self.el.add_nal(&Codec::Hevc.base_layer_nal(data)),
+            NAL_UNSPEC63 => {
+                ensure!(data.len() > EL_NALU_HEADER_LEN, "Invalid EL NAL unit");
+                self.el.add_nal(&data[EL_NALU_HEADER_LEN..]);
+            }
+            NAL_UNSPEC62 => {
+                if self.dovi_profile.is_none() {
+                    self.dovi_profile = Some(DoviRpu::parse_unspec62_nalu(data)?.dovi_profile);
+                }
+
+                self.el.add_nal(data);
+            }
+            _ => self.bl.add_nal(data),
+        }
+
+        Ok(())
+    }
+
+    /// Writes the BL then the EL sample of the current access unit
+    fn write_current_samples(&mut self) -> Result<()> {
+        if self.current_index.is_none() {
+            return Ok(());
+        }
+
+        ensure!(
+            !self.bl.current.is_empty() && !self.el.current.is_empty(),
+            "Frame {} has no {}, the input must be a dual layer stream with the EL and RPU muxed",
+            self.bl.sizes.len(),
+            if self.bl.current.is_empty() {
+                "BL"
+            } else {
+                "EL"
+            }
+        );
+
+        for track in [&mut self.bl, &mut self.el] {
+            self.writer.write_all(&track.current)?;
+
+            track.sizes.push(track.current.len() as u32);
+            track.offsets.push(self.offset);
+
+            self.offset += track.current.len() as u64;
+            track.current.clear();
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self, parser: &HevcParser) -> Result<()> {
+        let sync_samples = self.sync_samples(parser);
+
+        self.write_current_samples()?;
+
+        let frames = parser.ordered_frames();
+        ensure!(
+            frames.len() == self.bl.sizes.len(),
+            "Mismatched frame count: parsed {}, samples {}",
+            frames.len(),
+            self.bl.sizes.len()
+        );
+
+        let dovi_profile = match self.dovi_profile {
+            Some(profile @ (4 | 7)) => profile,
+            Some(profile) => bail!(
+                "Dual-track MP4 output requires a dual layer profile 4 or 7 stream, found profile {}",
+                profile
+            ),
+            None => bail!("No RPU found in the input"),
+        };
+
+        // Presentation index of every sample, in decoding order
+        let mut presentation = vec![0; frames.len()];
+        for frame in frames {
+            let decoded = frame.decoded_number as usize;
+            ensure!(decoded < presentation.len(), "Invalid frame order");
+
+            presentation[decoded] = frame.presentation_number;
+        }
+
+        let moov = self.moov(dovi_profile, &presentation, &sync_samples)?;
+
+        self.writer.write_all(&moov)?;
+
+        let mdat_size = self.offset - self.mdat_start;
+        self.writer.seek(SeekFrom::Start(self.mdat_start + 8))?;
+        self.writer.write_all(&mdat_size.to_be_bytes())?;
+        self.writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Samples starting with an IRAP picture, 1-based
+    fn sync_samples(&self, parser: &HevcParser) -> Vec<u32> {
+        parser
+            .ordered_frames()
+            .iter()
+            .filter(|frame| {
+                frame.nals.iter().any(|nal| {
+                    nal.nuh_layer_id == 0 && (NAL_BLA_W_LP..=NAL_IRAP_VCL23).contains(&nal.nal_type)
+                })
+            })
+            .map(|frame| frame.decoded_number as u32 + 1)
+            .collect()
+    }
+
+    fn moov(
+        &self,
+        dovi_profile: u8,
+        presentation: &[u64],
+        sync_samples: &[u32],
+    ) -> Result<Vec<u8>> {
+        let bl_sps = self.bl.sps_info()?;
+        let el_sps = self.el.sps_info()?;
+
+        let FrameRate { num, den } = self.frame_rate;
+        let sample_count = self.bl.sizes.len() as u64;
+
+        // Delay so that the composition offsets are positive, removed by the edit list
+        let delay = presentation
+            .iter()
+            .enumerate()
+            .map(|(decoded, &pres)| (decoded as u64).saturating_sub(pres))
+            .max()
+            .unwrap_or(0);
+
+        let composition_offsets: Vec<u32> = presentation
+            .iter()
+            .enumerate()
+            .map(|(decoded, &pres)| ((pres + delay - decoded as u64) * den as u64) as u32)
+            .collect();
+
+        let duration = sample_count * den as u64;
+        ensure!(
+            duration + delay * den as u64 <= u32::MAX as u64,
+            "Video too long for the MP4 output"
+        );
+
+        let timing = TrackTiming {
+            timescale: num,
+            sample_delta: den,
+            duration: duration as u32,
+            media_time: (delay * den as u64) as u32,
+            composition_offsets: &composition_offsets,
+        };
+
+        let dovi_level = dovi_level(bl_sps.width, bl_sps.height, self.frame_rate);
+
+        let bl_entry = visual_sample_entry(b"hev1", &bl_sps, |b| {
+            b.extend(boxed(b"hvcC", |b| self.bl.hvcc(&bl_sps, b)));
+        });
+
+        let el_entry = visual_sample_entry(b"dvhe", &el_sps, |b| {
+            b.extend(boxed(b"hvcC", |b| self.el.hvcc(&el_sps, b)));
+            b.extend(boxed(b"dvcC", |b| {
+                dovi_configuration(b, dovi_profile, dovi_level)
+            }));
+        });
+
+        Ok(boxed(b"moov", |b| {
+            b.extend(full_box(b"mvhd", 0, 0, |b| {
+                b.extend_from_slice(&[0; 8]);
+                b.extend_from_slice(&num.to_be_bytes());
+                b.extend_from_slice(&timing.duration.to_be_bytes());
+                // Rate 1.0, volume 1.0
+                b.extend_from_slice(&0x0001_0000_u32.to_be_bytes());
+                b.extend_from_slice(&0x0100_u16.to_be_bytes());
+                b.extend_from_slice(&[0; 10]);
+                write_matrix(b);
+                b.extend_from_slice(&[0; 24]);
+                b.extend_from_slice(&(EL_TRACK_ID + 1).to_be_bytes());
+            }));
+
+            b.extend(trak(
+                BL_TRACK_ID,
+                None,
+                &bl_sps,
+                &timing,
+                &bl_entry,
+                &self.bl,
+                sync_samples,
+            ));
+            b.extend(trak(
+                EL_TRACK_ID,
+                Some(BL_TRACK_ID),
+                &el_sps,
+                &timing,
+                &el_entry,
+                &self.el,
+                sync_samples,
+            ));
+        }))
+    }
+}
+
+impl IoProcessor for DualTrackMp4Writer {
+    fn input(&self) -> &PathBuf {
+        &self.input
+    }
+
+    fn update_progress(&mut self, _delta: u64) {
+        // Progress is updated by the input reader
+    }
+
+    fn process_nals(&mut self, _parser: &HevcParser, nals: &[NALUnit], chunk: &[u8]) -> Result<()> {
+        for nal in nals {
+            self.add_nal(nal, &chunk[nal.start..nal.end])?;
+        }
+
+        Ok(())
+    }
+
+    fn finalize(&mut self, parser: &HevcParser) -> Result<()> {
+        self.finish(parser)?;
+        self.progress_bar.finish_and_clear();
+
+        Ok(())
+    }
+}
+
+impl TrackSamples {
+    fn add_nal(&mut self, data: &[u8]) {
+        let nal_type = data[0] >> 1;
+
+        let parameter_set = match nal_type {
+            NAL_VPS => Some(&mut self.vps),
+            NAL_SPS => Some(&mut self.sps),
+            NAL_PPS => Some(&mut self.pps),
+            _ => None,
+        };
+
+        // The first parameter sets are in the decoder configuration, the others stay in band
+        if let Some(parameter_set @ None) = parameter_set {
+            *parameter_set = Some(data.to_vec());
+        }
+
+        self.current
+            .extend_from_slice(&(data.len() as u32).to_be_bytes()[4 - NAL_LENGTH_SIZE..]);
+        self.current.extend_from_slice(data);
+    }
+
+    fn sps_info(&self) -> Result<SpsInfo> {
+        match &self.sps {
+            Some(sps) => SpsInfo::parse(sps),
+            None => bail!("No SPS found in the stream"),
+        }
+    }
+
+    /// HEVCDecoderConfigurationRecord
+    fn hvcc(&self, sps: &SpsInfo, b: &mut Vec<u8>) {
+        b.push(1);
+        b.extend_from_slice(&sps.general_ptl);
+        // min_spatial_segmentation_idc, parallelismType
+        b.extend_from_slice(&[0xF0, 0x00, 0xFC]);
+        b.push(0xFC | sps.chroma_format_idc);
+        b.push(0xF8 | sps.bit_depth_luma_minus8);
+        b.push(0xF8 | sps.bit_depth_chroma_minus8);
+        // avgFrameRate
+        b.extend_from_slice(&[0, 0]);
+        b.push(
+            ((sps.max_sub_layers_minus1 + 1) << 3)
+                | ((sps.temporal_id_nesting_flag as u8) << 2)
+                | (NAL_LENGTH_SIZE as u8 - 1),
+        );
+
+        let arrays: Vec<(u8, &Vec<u8>)> = [
+            (NAL_VPS, &self.vps),
+            (NAL_SPS, &self.sps),
+            (NAL_PPS, &self.pps),
+        ]
+        .into_iter()
+        .filter_map(|(nal_type, data)| data.as_ref().map(|data| (nal_type, data)))
+        .collect();
+
+        b.push(arrays.len() as u8);
+
+        for (nal_type, data) in arrays {
+            // Not complete, parameter sets can also be in band
+            b.push(nal_type);
+            b.extend_from_slice(&1_u16.to_be_bytes());
+            b.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            b.extend_from_slice(data);
+        }
+    }
+}
+
+struct TrackTiming<'a> {
+    timescale: u32,
+    sample_delta: u32,
+    duration: u32,
+    media_time: u32,
+    composition_offsets: &'a [u32],
+}
+
+fn trak(
+    track_id: u32,
+    reference: Option<u32>,
+    sps: &SpsInfo,
+    timing: &TrackTiming,
+    sample_entry: &[u8],
+    samples: &TrackSamples,
+    sync_samples: &[u32],
+) -> Vec<u8> {
+    boxed(b"trak", |b| {
+        // Enabled, in movie
+        b.extend(full_box(b"tkhd", 0, 3, |b| {
+            b.extend_from_slice(&[0; 8]);
+            b.extend_from_slice(&track_id.to_be_bytes());
+            b.extend_from_slice(&[0; 4]);
+            b.extend_from_slice(&timing.duration.to_be_bytes());
+            b.extend_from_slice(&[0; 16]);
+            write_matrix(b);
+            b.extend_from_slice(&(sps.width << 16).to_be_bytes());
+            b.extend_from_slice(&(sps.height << 16).to_be_bytes());
+        }));
+
+        if let Some(reference) = reference {
+            b.extend(boxed(b"tref", |b| {
+                b.extend(boxed(b"vdep", |b| {
+                    b.extend_from_slice(&reference.to_be_bytes())
+                }));
+            }));
+        }
+
+        b.extend(boxed(b"edts", |b| {
+            b.extend(full_box(b"elst", 0, 0, |b| {
+                b.extend_from_slice(&1_u32.to_be_bytes());
+                b.extend_from_slice(&timing.duration.to_be_bytes());
+                b.extend_from_slice(&timing.media_time.to_be_bytes());
+                b.extend_from_slice(&0x0001_0000_u32.to_be_bytes());
+            }));
+        }));
+
+        b.extend(boxed(b"mdia", |b| {
+            b.extend(full_box(b"mdhd", 0, 0, |b| {
+                b.extend_from_slice(&[0; 8]);
+                b.extend_from_slice(&timing.timescale.to_be_bytes());
+                b.extend_from_slice(&timing.duration.to_be_bytes());
+                // Undetermined language
+                b.extend_from_slice(&0x55C4_u16.to_be_bytes());
+                b.extend_from_slice(&[0; 2]);
+            }));
+
+            b.extend(full_box(b"hdlr", 0, 0, |b| {
+                b.extend_from_slice(&[0; 4]);
+                b.extend_from_slice(b"vide");
+                b.extend_from_slice(&[0; 12]);
+                b.extend_from_slice(b"VideoHandler\0");
+            }));
+
+            b.extend(boxed(b"minf", |b| {
+                b.extend(full_box(b"vmhd", 0, 1, |b| b.extend_from_slice(&[0; 8])));
+
+                b.extend(boxed(b"dinf", |b| {
+                    b.extend(full_box(b"dref", 0, 0, |b| {
+                        b.extend_from_slice(&1_u32.to_be_bytes());
+                        // Self contained
+                        b.extend(full_box(b"url ", 0, 1, |_| {}));
+                    }));
+                }));
+
+                b.extend(sample_table(timing, sample_entry, samples, sync_samples));
+            }));
+        }));
+    })
+}
+
+fn sample_table(
+    timing: &TrackTiming,
+    sample_entry: &[u8],
+    samples: &TrackSamples,
+    sync_samples: &[u32],
+) -> Vec<u8> {
+    let sample_count = samples.sizes.len() as u32;
+
+    boxed(b"stbl", |b| {
+        b.extend(full_box(b"stsd", 0, 0, |b| {
+            b.extend_from_slice(&1_u32.to_be_bytes());
+            b.extend_from_slice(sample_entry);
+        }));
+
+        b.extend(full_box(b"stts", 0, 0, |b| {
+            b.extend_from_slice(&1_u32.to_be_bytes());
+            b.extend_from_slice(&sample_count.to_be_bytes());
+            b.extend_from_slice(&timing.sample_delta.to_be_bytes());
+        }));
+
+        let mut ctts_runs: Vec<(u32, u32)> = Vec::new();
+        for &offset in timing.composition_offsets {
+            match ctts_runs.last_mut() {
+                Some((count, last)) if *last == offset => *count += 1,
+                _ => ctts_runs.push((1, offset)),
+            }
+        }
+
+        b.extend(full_box(b"ctts", 0, 0, |b| {
+            b.extend_from_slice(&(ctts_runs.len() as u32).to_be_bytes());
+
+            for (count, offset) in ctts_runs {
+                b.extend_from_slice(&count.to_be_bytes());
+                b.extend_from_slice(&offset.to_be_bytes());
+            }
+        }));
+
+        b.extend(full_box(b"stss", 0, 0, |b| {
+            b.extend_from_slice(&(sync_samples.len() as u32).to_be_bytes());
+            sync_samples
+                .iter()
+                .for_each(|sample| b.extend_from_slice(&sample.to_be_bytes()));
+        }));
+
+        // One sample per chunk, as the BL and EL samples are interleaved
+        b.extend(full_box(b"stsc", 0, 0, |b| {
+            b.extend_from_slice(&1_u32.to_be_bytes());
+            b.extend_from_slice(&1_u32.to_be_bytes());
+            b.extend_from_slice(&1_u32.to_be_bytes());
+            b.extend_from_slice(&1_u32.to_be_bytes());
+        }));
+
+        b.extend(full_box(b"stsz", 0, 0, |b| {
+            b.extend_from_slice(&0_u32.to_be_bytes());
+            b.extend_from_slice(&sample_count.to_be_bytes());
+            samples
+                .sizes
+                .iter()
+                .for_each(|size| b.extend_from_slice(&size.to_be_bytes()));
+        }));
+
+        if samples
+            .offsets
+            .iter()
+            .all(|offset| *offset <= u32::MAX as u64)
+        {
+            b.extend(full_box(b"stco", 0, 0, |b| {
+                b.extend_from_slice(&sample_count.to_be_bytes());
+                samples
+                    .offsets
+                    .iter()
+                    .for_each(|offset| b.extend_from_slice(&(*offset as u32).to_be_bytes()));
+            }));
+        } else {
+            b.extend(full_box(b"co64", 0, 0, |b| {
+                b.extend_from_slice(&sample_count.to_be_bytes());
+                samples
+                    .offsets
+                    .iter()
+                    .for_each(|offset| b.extend_from_slice(&offset.to_be_bytes()));
+            }));
+        }
+    })
+}
+
+fn visual_sample_entry<F>(fourcc: &[u8; 4], sps: &SpsInfo, children: F) -> Vec<u8>
+where
+    F: FnOnce(&mut Vec<u8>),
+{
+    boxed(fourcc, |b| {
+        b.extend_from_slice(&[0; 6]);
+        // data_reference_index
+        b.extend_from_slice(&1_u16.to_be_bytes());
+        b.extend_from_slice(&[0; 16]);
+        b.extend_from_slice(&(sps.width as u16).to_be_bytes());
+        b.extend_from_slice(&(sps.height as u16).to_be_bytes());
+        // 72 dpi
+        b.extend_from_slice(&0x0048_0000_u32.to_be_bytes());
+        b.extend_from_slice(&0x0048_0000_u32.to_be_bytes());
+        b.extend_from_slice(&[0; 4]);
+        // frame_count
+        b.extend_from_slice(&1_u16.to_be_bytes());
+        // compressorname
+        b.extend_from_slice(&[0; 32]);
+        b.extend_from_slice(&0x0018_u16.to_be_bytes());
+        b.extend_from_slice(&(-1_i16).to_be_bytes());
+
+        children(b);
+    })
+}
+
+/// DOVIDecoderConfigurationRecord of the EL track: no BL, with EL and RPU
+fn dovi_configuration(b: &mut Vec<u8>, dovi_profile: u8, dovi_level: u8) {
+    let bl_signal_compatibility_id: u8 = match dovi_profile {
+        4 => 2,
+        _ => 6,
+    };
+
+    let (rpu_present, el_present, bl_present) = (1_u16, 1_u16, 0_u16);
+
+    // dv_version_major, dv_version_minor
+    b.extend_from_slice(&[1, 0]);
+    b.extend_from_slice(
+        &(((dovi_profile as u16) << 9)
+            | ((dovi_level as u16) << 3)
+            | (rpu_present << 2)
+            | (el_present << 1)
+            | bl_present)
+            .to_be_bytes(),
+    );
+    b.push(bl_signal_compatibility_id << 4);
+    b.extend_from_slice(&[0; 19]);
+}
+
+/// Lowest Dolby Vision level for the resolution and frame rate
+fn dovi_level(width: u32, height: u32, frame_rate: FrameRate) -> u8 {
+    let pixel_rate = (width as u64 * height as u64 * frame_rate.num as u64) / frame_rate.den as u64;
+
+    DOVI_LEVELS
+        .iter()
+        .position(|(max_width, max_pixel_rate)| {
+            width <= *max_width && pixel_rate <= *max_pixel_rate
+        })
+        .map_or(DOVI_LEVELS.len(), |i| i + 1) as u8
+}
+
+fn write_matrix(b: &mut Vec<u8>) {
+    for value in [0x0001_0000_u32, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000] {
+        b.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+fn boxed<F>(fourcc: &[u8; 4], content: F) -> Vec<u8>
+where
+    F: FnOnce(&mut Vec<u8>),
+{
+    let mut b = vec![0; 4];
+    b.extend_from_slice(fourcc);
+
+    content(&mut b);
+
+    let size = b.len() as u32;
+    b[..4].copy_from_slice(&size.to_be_bytes());
+
+    b
+}
+
+fn full_box<F>(fourcc: &[u8; 4], version: u8, flags: u32, content: F) -> Vec<u8>
+where
+    F: FnOnce(&mut Vec<u8>),
+{
+    boxed(fourcc, |b| {
+        b.extend_from_slice(&((version as u32) << 24 | flags).to_be_bytes());
+        content(b);
+    })
+}
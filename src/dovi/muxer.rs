@@ -3,19 +3,22 @@ use std::fs::File;
 use std::io::{stdout, BufRead, BufReader, BufWriter, Write};
 use std::path::PathBuf;
 
-use anyhow::{bail, Result};
+use anyhow::{bail, ensure, Result};
 use indicatif::ProgressBar;
 use itertools::Itertools;
 
 use hevc_parser::io::{processor, FrameBuffer, IoProcessor, NalBuffer};
 use hevc_parser::HevcParser;
 use hevc_parser::{hevc::*, NALUStartCode};
-use processor::{HevcProcessor, HevcProcessorOpts};
+use processor::HevcProcessorOpts;
 
 use crate::commands::MuxArgs;
 
+use super::hevc_processor::HevcProcessor;
+use super::lenient::LenientParser;
 use super::{
-    convert_encoded_from_opts, is_st2094_40_sei, CliOptions, IoFormat, WriteStartCodePreset,
+    codec::Codec, convert_encoded_from_opts, open_hevc_input, CliOptions, IoFormat,
+    WriteStartCodePreset,
 };
 
 const EL_NALU_PREFIX: &[u8] = &[0x7E, 0x01];
@@ -42,6 +45,7 @@ pub struct ElHandler {
     buffers: VecDeque<FrameBuffer>,
 
     options: CliOptions,
+    lenient: Option<LenientParser>,
 }
 
 impl Muxer {
@@ -57,6 +61,11 @@ impl Muxer {
 
         cli_options.discard_el = discard;
 
+        ensure!(
+            !no_add_aud || !cli_options.start_code.requires_aud(),
+            "Muxer: --no-add-aud cannot be used with --start-code ts, every access unit needs an AUD"
+        );
+
         let bl_format = hevc_parser::io::format_from_path(&bl)?;
         let el_format = hevc_parser::io::format_from_path(&el)?;
 
@@ -78,11 +87,11 @@ impl Muxer {
             }
         };
 
-        let chunk_size = 100_000;
+        let chunk_size = cli_options.read_buffer;
 
         let writer = BufWriter::with_capacity(
-            chunk_size,
-            File::create(&output).expect("Can't create file"),
+            cli_options.write_buffer,
+            super::create_output_file(&output)?,
         );
 
         let el_file = File::open(&el)?;
@@ -97,6 +106,7 @@ impl Muxer {
             writer,
             buffers: VecDeque::new(),
             options: cli_options.clone(),
+            lenient: cli_options.lenient.then(|| LenientParser::new(Codec::Hevc)),
         };
 
         let progress_bar = super::initialize_progress_bar(&bl_format, &bl)?;
@@ -129,7 +139,7 @@ impl Muxer {
         println!("Rewriting file with interleaved EL NALUs..");
         stdout().flush().ok();
 
-        let chunk_size = 100_000;
+        let chunk_size = self.options.read_buffer;
 
         let mut processor = HevcProcessor::new(
             self.format.clone(),
@@ -137,13 +147,13 @@ impl Muxer {
             chunk_size,
         );
 
-        let stdin = std::io::stdin();
-        let mut reader = Box::new(stdin.lock()) as Box<dyn BufRead>;
-
-        if let IoFormat::Raw = self.format {
-            let file = File::open(&self.input)?;
-            reader = Box::new(BufReader::with_capacity(100_000, file));
-        }
+        let mut reader = open_hevc_input(
+            &self.format,
+            &self.input,
+            self.options.input_format,
+            &self.progress_bar,
+            chunk_size,
+        )?;
 
         processor.process_io(&mut reader, self)
     }
@@ -154,19 +164,21 @@ impl IoProcessor for Muxer {
         &self.input
     }
 
-    fn update_progress(&mut self, delta: u64) {
-        self.progress_bar.inc(delta);
+    fn update_progress(&mut self, _delta: u64) {
+        // Progress is updated by the input reader
     }
 
     fn process_nals(&mut self, parser: &HevcParser, nals: &[NALUnit], chunk: &[u8]) -> Result<()> {
         for nal in nals {
-            // Skip ST2094-40 SEI if desired
-            if self.options.drop_hdr10plus
-                && nal.nal_type == NAL_SEI_PREFIX
-                && is_st2094_40_sei(&chunk[nal.start..nal.end])?
+            // Skip filtered SEI messages
+            let nal_data = match self
+                .options
+                .sei_filter
+                .filter_nal(nal.nal_type, &chunk[nal.start..nal.end])?
             {
-                continue;
-            }
+                Some(data) => data,
+                None => continue,
+            };
 
             // First NALU of new frame
             // Write previous frame buffer
@@ -238,7 +250,7 @@ impl IoProcessor for Muxer {
                 self.frame_buffer.nals.push(NalBuffer {
                     nal_type: nal.nal_type,
                     start_code: nal.start_code,
-                    data: chunk[nal.start..nal.end].to_vec(),
+                    data: nal_data.into_owned(),
                 });
             }
         }
@@ -304,6 +316,10 @@ impl IoProcessor for Muxer {
 
         self.el_handler.writer.flush()?;
 
+        if let Some(lenient) = &self.el_handler.lenient {
+            lenient.print_summary();
+        }
+
         self.progress_bar.finish_and_clear();
 
         Ok(())
@@ -320,27 +336,38 @@ impl IoProcessor for ElHandler {
     fn process_nals(&mut self, _parser: &HevcParser, nals: &[NALUnit], chunk: &[u8]) -> Result<()> {
         let by_frame = nals.iter().group_by(|nal| nal.decoded_frame_index);
         for (frame_number, frame_nals) in &by_frame {
-            let nal_buffers = frame_nals
-                .filter(|nal| !self.options.discard_el || matches!(nal.nal_type, NAL_UNSPEC62)) // discard everything but RPU
-                .map(|nal| {
-                    let data = &chunk[nal.start..nal.end];
-                    let buf = if nal.nal_type != NAL_UNSPEC62 {
-                        let mut vec = Vec::from(EL_NALU_PREFIX);
-                        vec.extend(data);
-
-                        vec
-                    } else if self.options.mode.is_some() || self.options.edit_config.is_some() {
-                        convert_encoded_from_opts(&self.options, data).unwrap()
-                    } else {
-                        Vec::from(data)
-                    };
+            let mut nal_buffers = Vec::new();
 
-                    NalBuffer {
-                        nal_type: nal.nal_type,
-                        start_code: nal.start_code,
-                        data: buf,
+            // discard everything but RPU
+            for nal in frame_nals
+                .filter(|nal| !self.options.discard_el || matches!(nal.nal_type, NAL_UNSPEC62))
+            {
+                let data = &chunk[nal.start..nal.end];
+                let buf = if nal.nal_type != NAL_UNSPEC62 {
+                    let mut vec = Vec::from(EL_NALU_PREFIX);
+                    vec.extend(data);
+
+                    vec
+                } else if self.options.converts_rpus() {
+                    let res = convert_encoded_from_opts(&self.options, Codec::Hevc, data);
+
+                    match &mut self.lenient {
+                        Some(lenient) => match lenient.resolve(frame_number as usize, res)? {
+                            Some(data) => data,
+                            None => continue,
+                        },
+                        None => res?,
                     }
+                } else {
+                    Vec::from(data)
+                };
+
+                nal_buffers.push(NalBuffer {
+                    nal_type: nal.nal_type,
+                    start_code: nal.start_code,
+                    data: buf,
                 });
+            }
 
             // Existing incomplete frame
             let existing_frame = self
@@ -353,7 +380,7 @@ impl IoProcessor for ElHandler {
             } else {
                 let frame_buffer = FrameBuffer {
                     frame_number,
-                    nals: nal_buffers.collect(),
+                    nals: nal_buffers,
                 };
 
                 self.buffers.push_back(frame_buffer);
@@ -0,0 +1,260 @@
+use std::io::Read;
+use std::panic::{self, AssertUnwindSafe};
+
+use anyhow::{anyhow, Error, Result};
+use bitvec_helpers::bitvec_reader::BitVecReader;
+
+use hevc_parser::hevc::{Frame, NALUnit};
+use hevc_parser::utils::clear_start_code_emulation_prevention_3_byte;
+use hevc_parser::NALUStartCode;
+
+use super::start_code::get_offsets;
+
+const NAL_START_CODE_3: &[u8] = &[0, 0, 1];
+
+/// Annex B stream parser, for the codecs not handled by `hevc_parser`
+pub trait NalParser: Default {
+    /// Parses the NAL unit, the start, end and start code are already set
+    fn parse_nal(&mut self, data: &[u8], nal: NALUnit) -> Result<NALUnit>;
+
+    /// Called once the whole stream has been parsed
+    fn finish(&mut self);
+
+    fn frame_order(&self) -> &FrameOrder;
+
+    /// Parses the NAL units starting at the offsets, up to `last`.
+    /// `position` is the offset of `data` in the input, for the NAL units that can't be parsed.
+    fn split_nals(
+        &mut self,
+        data: &[u8],
+        offsets: &[usize],
+        last: usize,
+        position: u64,
+        processor: &mut dyn NalIoProcessor,
+    ) -> Result<Vec<NALUnit>> {
+        let mut nals = Vec::with_capacity(offsets.len());
+
+        for (index, offset) in offsets.iter().enumerate() {
+            let mut end = offsets.get(index + 1).copied().unwrap_or(last);
+
+            // Trailing zero bytes, or the first byte of a 4 bytes start code
+            while end > offset + NAL_START_CODE_3.len() && data[end - 1] == 0 {
+                end -= 1;
+            }
+
+            let start_code = if *offset > 0 && data[offset - 1] == 0 {
+                NALUStartCode::Length4
+            } else {
+                NALUStartCode::Length3
+            };
+
+            let nal = NALUnit {
+                start: offset + NAL_START_CODE_3.len(),
+                end,
+                start_code,
+                ..Default::default()
+            };
+
+            match catch_parse_panic(|| self.parse_nal(data, nal)) {
+                Ok(nal) => nals.push(nal),
+                Err(e) => processor.corrupt_nal(position + *offset as u64, end - offset, e)?,
+            }
+        }
+
+        Ok(nals)
+    }
+}
+
+/// Same as `hevc_parser::io::IoProcessor`, for the `NalParser` streams
+pub trait NalIoProcessor {
+    /// Called with the NAL units parsed from the current chunk
+    fn process_nals(
+        &mut self,
+        frame_order: &FrameOrder,
+        nals: &[NALUnit],
+        chunk: &[u8],
+    ) -> Result<()>;
+
+    /// Called once the whole stream has been parsed
+    fn finalize(&mut self, frame_order: &FrameOrder) -> Result<()>;
+
+    /// Called for a NAL unit that can't be parsed, at `position` in the input.
+    /// Aborts by default, otherwise the NAL unit is skipped up to the next start code.
+    fn corrupt_nal(&mut self, _position: u64, _size: usize, error: Error) -> Result<()> {
+        Err(error)
+    }
+}
+
+/// Base Annex B stream processor
+pub struct NalProcessor<P: NalParser> {
+    parser: P,
+    chunk_size: usize,
+}
+
+/// Groups the parsed NAL units into frames, and orders them by picture order count.
+///
+/// The `NALUnit` and `Frame` are the same as `hevc_parser`,
+/// with the NAL unit types of the parsed codec.
+#[derive(Default)]
+pub struct FrameOrder {
+    nals: Vec<NALUnit>,
+    frames: Vec<(i64, Frame)>,
+    ordered_frames: Vec<Frame>,
+
+    current_frame: Frame,
+    current_poc: i64,
+    current_has_slice: bool,
+
+    decoded_index: u64,
+    presentation_index: u64,
+}
+
+impl<P: NalParser> NalProcessor<P> {
+    pub fn new(chunk_size: usize) -> Self {
+        Self {
+            parser: P::default(),
+            chunk_size,
+        }
+    }
+
+    /// Fully parse the input stream
+    pub fn process_io(
+        &mut self,
+        reader: &mut dyn Read,
+        processor: &mut dyn NalIoProcessor,
+    ) -> Result<()> {
+        let mut buf = vec![0; self.chunk_size];
+        let mut chunk: Vec<u8> = Vec::with_capacity(self.chunk_size * 2);
+        let mut offsets = Vec::with_capacity(2048);
+
+        // Offset of the chunk in the input
+        let mut position = 0;
+
+        loop {
+            let read_bytes = reader.read(&mut buf)?;
+            let eof = read_bytes == 0;
+
+            chunk.extend_from_slice(&buf[..read_bytes]);
+
+            get_offsets(&chunk, &mut offsets);
+
+            // The last NAL can be incomplete until the end of the stream
+            let last = if eof {
+                chunk.len()
+            } else if offsets.len() > 1 {
+                offsets.pop().unwrap()
+            } else {
+                continue;
+            };
+
+            if !offsets.is_empty() {
+                let nals = self
+                    .parser
+                    .split_nals(&chunk, &offsets, last, position, processor)?;
+                processor.process_nals(self.parser.frame_order(), &nals, &chunk)?;
+            }
+
+            if eof {
+                break;
+            }
+
+            chunk.drain(..last);
+            position += last as u64;
+        }
+
+        self.parser.finish();
+
+        processor.finalize(self.parser.frame_order())
+    }
+}
+
+impl FrameOrder {
+    /// Adds the NAL unit to the current frame
+    pub fn push_nal(&mut self, nal: &mut NALUnit) {
+        nal.decoded_frame_index = self.decoded_index;
+
+        self.current_frame.nals.push(nal.clone());
+        self.nals.push(nal.clone());
+    }
+
+    /// Starts a new frame from its first slice
+    pub fn start_frame(&mut self, poc: i64, frame_type: u64) {
+        self.add_current_frame();
+
+        self.current_poc = poc;
+        self.current_frame.frame_type = frame_type;
+        self.current_has_slice = true;
+    }
+
+    /// Ends the current frame, if a slice was found for it
+    pub fn add_current_frame(&mut self) {
+        if self.current_has_slice {
+            self.current_frame.decoded_number = self.decoded_index;
+            self.decoded_index += 1;
+
+            let frame = std::mem::take(&mut self.current_frame);
+            self.frames.push((self.current_poc, frame));
+
+            self.current_has_slice = false;
+        }
+    }
+
+    /// Outputs the pending frames, when the picture order count is reset
+    pub fn reorder_frames(&mut self) {
+        self.frames.sort_by_key(|(poc, _)| *poc);
+
+        for (_, mut frame) in self.frames.drain(..) {
+            frame.presentation_number = self.presentation_index;
+            self.presentation_index += 1;
+
+            self.ordered_frames.push(frame);
+        }
+    }
+
+    pub fn finish(&mut self) {
+        self.add_current_frame();
+        self.reorder_frames();
+    }
+
+    /// Number of frames since the last reset of the picture order count
+    pub fn pending_frames(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn has_current_slice(&self) -> bool {
+        self.current_has_slice
+    }
+
+    /// Frames with their presentation number, in presentation order
+    pub fn ordered_frames(&self) -> &Vec<Frame> {
+        &self.ordered_frames
+    }
+
+    pub fn get_nals(&self) -> &Vec<NALUnit> {
+        &self.nals
+    }
+}
+
+/// Bit reader over the NAL payload, without emulation prevention bytes
+pub fn nal_reader(data: &[u8]) -> BitVecReader {
+    BitVecReader::new(clear_start_code_emulation_prevention_3_byte(data))
+}
+
+/// Runs the parsing of a NAL unit, with the panics on malformed data returned as errors
+pub fn catch_parse_panic<T>(parse: impl FnOnce() -> Result<T>) -> Result<T> {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+
+    let res = panic::catch_unwind(AssertUnwindSafe(parse));
+    panic::set_hook(hook);
+
+    res.unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+
+        Err(anyhow!("Malformed NAL unit: {}", message))
+    })
+}
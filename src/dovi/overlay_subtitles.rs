@@ -0,0 +1,202 @@
+use std::io::Write;
+
+use anyhow::Result;
+
+use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlock;
+use dolby_vision::utils::{pq12_to_pq, pq_to_nits};
+
+use super::DoviRpu;
+
+/// Header of the ASS overlay, white monospace text on a dark box at the top left
+const ASS_HEADER: &str = "[Script Info]
+; Dolby Vision metadata overlay
+ScriptType: v4.00+
+PlayResX: 1920
+PlayResY: 1080
+WrapStyle: 2
+ScaledBorderAndShadow: yes
+
+[V4+ Styles]
+Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding
+Style: Metadata,Monospace,30,&H00FFFFFF,&H000000FF,&H00000000,&H80000000,0,0,0,0,100,100,0,0,3,2,0,7,40,40,40,1
+
+[Events]
+Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubtitleFormat {
+    Srt,
+    Ass,
+}
+
+/// Consecutive frames of a scene with the same metadata, shown as one subtitle
+struct OverlayEvent {
+    scene: usize,
+    start: usize,
+    end: usize,
+    lines: Vec<String>,
+}
+
+/// Writes a subtitle track showing the metadata of every frame, for playing the video with the metadata overlaid.
+///
+/// The L1 values are in nits, the L2 trims are listed by target display and the L5 offsets in pixels.
+/// Consecutive frames of a scene with the same metadata are shown by a single subtitle.
+pub fn write_overlay(
+    rpus: &[DoviRpu],
+    fps: f64,
+    format: SubtitleFormat,
+    writer: &mut dyn Write,
+) -> Result<()> {
+    let mut events: Vec<OverlayEvent> = Vec::new();
+    let mut scene = 0;
+
+    for (frame, rpu) in rpus.iter().enumerate() {
+        let scene_cut = rpu
+            .vdr_dm_data
+            .as_ref()
+            .is_some_and(|dm| dm.scene_refresh_flag == 1);
+
+        if scene_cut && frame > 0 {
+            scene += 1;
+        }
+
+        let lines = frame_lines(rpu);
+
+        match events.last_mut() {
+            Some(event) if event.scene == scene && event.lines == lines => event.end = frame,
+            _ => events.push(OverlayEvent {
+                scene,
+                start: frame,
+                end: frame,
+                lines,
+            }),
+        }
+    }
+
+    if format == SubtitleFormat::Ass {
+        write!(writer, "{}", ASS_HEADER)?;
+    }
+
+    for (i, event) in events.iter().enumerate() {
+        let frames = if event.start == event.end {
+            format!("frame {}", event.start)
+        } else {
+            format!("frames {}-{}", event.start, event.end)
+        };
+
+        let mut lines = vec![format!("Scene {}, {}", event.scene, frames)];
+        lines.extend(event.lines.iter().cloned());
+
+        let start = event.start as f64 / fps;
+        let end = (event.end + 1) as f64 / fps;
+
+        match format {
+            SubtitleFormat::Srt => writeln!(
+                writer,
+                "{}\n{} --> {}\n{}\n",
+                i + 1,
+                srt_timestamp(start),
+                srt_timestamp(end),
+                lines.join("\n")
+            )?,
+            SubtitleFormat::Ass => writeln!(
+                writer,
+                "Dialogue: 0,{},{},Metadata,,0,0,0,,{}",
+                ass_timestamp(start),
+                ass_timestamp(end),
+                lines.join("\\N")
+            )?,
+        }
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn frame_lines(rpu: &DoviRpu) -> Vec<String> {
+    let vdr_dm_data = match &rpu.vdr_dm_data {
+        Some(vdr_dm_data) => vdr_dm_data,
+        None => return vec![String::from("No DM metadata")],
+    };
+
+    let mut lines = Vec::new();
+
+    match vdr_dm_data.get_block(1) {
+        Some(ExtMetadataBlock::Level1(l1)) => lines.push(format!(
+            "L1: min {:.4} / avg {:.2} / max {:.2} nits",
+            pq_nits(l1.min_pq),
+            pq_nits(l1.avg_pq),
+            pq_nits(l1.max_pq)
+        )),
+        _ => lines.push(String::from("L1: none")),
+    }
+
+    let mut l2_lines: Vec<String> = vdr_dm_data
+        .level_blocks_iter(2)
+        .filter_map(|block| match block {
+            ExtMetadataBlock::Level2(l2) => Some(format!(
+                "L2 {} nits: S {} O {} P {} CW {} SG {} MS {}",
+                pq_nits(l2.target_max_pq).round(),
+                l2.trim_slope,
+                l2.trim_offset,
+                l2.trim_power,
+                l2.trim_chroma_weight,
+                l2.trim_saturation_gain,
+                l2.ms_weight
+            )),
+            _ => None,
+        })
+        .collect();
+
+    if l2_lines.is_empty() {
+        lines.push(String::from("L2: none"));
+    } else {
+        lines.append(&mut l2_lines);
+    }
+
+    match vdr_dm_data.get_block(5) {
+        Some(ExtMetadataBlock::Level5(l5)) => {
+            let offsets = l5.get_offsets_vec();
+
+            lines.push(format!(
+                "L5: left {} / right {} / top {} / bottom {}",
+                offsets[0], offsets[1], offsets[2], offsets[3]
+            ));
+        }
+        _ => lines.push(String::from("L5: none")),
+    }
+
+    lines
+}
+
+fn pq_nits(pq: u16) -> f64 {
+    pq_to_nits(pq12_to_pq(pq))
+}
+
+/// `HH:MM:SS,mmm`
+fn srt_timestamp(seconds: f64) -> String {
+    let millis = (seconds * 1000.0).round() as u64;
+
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// `H:MM:SS.cc`
+fn ass_timestamp(seconds: f64) -> String {
+    let centis = (seconds * 100.0).round() as u64;
+
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        centis / 360_000,
+        centis / 6000 % 60,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
@@ -0,0 +1,122 @@
+use anyhow::Result;
+use bitvec_helpers::bitvec_reader::BitVecReader;
+use hevc_parser::hevc::{NAL_PPS, NAL_SPS, NAL_VPS};
+use hevc_parser::utils::clear_start_code_emulation_prevention_3_byte;
+
+use super::hevc_sps::SpsInfo;
+use super::hevc_vps::{base_layer_vps, VpsInfo};
+
+/// Consistency of the BL parameter sets of a single layer output, once the EL is discarded.
+///
+/// Every SPS must reference a VPS written before it, and every PPS an SPS.
+/// A VPS describing more than the base layer references the discarded EL layers,
+/// it can be rewritten to describe the base layer only.
+#[derive(Debug, Default)]
+pub struct ParameterSetCheck {
+    rewrite_vps: bool,
+
+    vps_ids: Vec<u8>,
+    sps_ids: Vec<u8>,
+
+    /// Distinct issues, in order of first occurrence
+    issues: Vec<String>,
+    rewritten_vps: usize,
+}
+
+impl ParameterSetCheck {
+    pub fn new(rewrite_vps: bool) -> Self {
+        Self {
+            rewrite_vps,
+            ..Default::default()
+        }
+    }
+
+    /// Checks a BL NALU, returning the rewritten VPS when it describes other layers
+    pub fn check_nal(&mut self, nal_type: u8, data: &[u8]) -> Result<Option<Vec<u8>>> {
+        match nal_type {
+            NAL_VPS => return self.check_vps(data),
+            NAL_SPS => {
+                let sps = SpsInfo::parse(data)?;
+
+                if !self.vps_ids.contains(&sps.vps_id) {
+                    self.add_issue(format!(
+                        "SPS {} references the VPS {}, not found before it",
+                        sps.sps_id, sps.vps_id
+                    ));
+                }
+
+                if !self.sps_ids.contains(&sps.sps_id) {
+                    self.sps_ids.push(sps.sps_id);
+                }
+            }
+            NAL_PPS => {
+                let mut reader =
+                    BitVecReader::new(clear_start_code_emulation_prevention_3_byte(data));
+                reader.skip_n(16);
+
+                let pps_id = reader.get_ue()?;
+                let sps_id = reader.get_ue()? as u8;
+
+                if !self.sps_ids.contains(&sps_id) {
+                    self.add_issue(format!(
+                        "PPS {} references the SPS {}, not found before it",
+                        pps_id, sps_id
+                    ));
+                }
+            }
+            _ => (),
+        }
+
+        Ok(None)
+    }
+
+    fn check_vps(&mut self, data: &[u8]) -> Result<Option<Vec<u8>>> {
+        let vps = VpsInfo::parse(data)?;
+
+        if !self.vps_ids.contains(&vps.vps_id) {
+            self.vps_ids.push(vps.vps_id);
+        }
+
+        if !vps.has_layers() {
+            return Ok(None);
+        }
+
+        if self.rewrite_vps {
+            self.rewritten_vps += 1;
+            return base_layer_vps(data).map(Some);
+        }
+
+        self.add_issue(format!(
+            "VPS {} describes {} layers, max layer id {}, the EL layers are discarded. Use --rewrite-vps to describe the BL only",
+            vps.vps_id,
+            vps.max_layers_minus1 + 1,
+            vps.max_layer_id
+        ));
+
+        Ok(None)
+    }
+
+    fn add_issue(&mut self, issue: String) {
+        if !self.issues.contains(&issue) {
+            self.issues.push(issue);
+        }
+    }
+
+    pub fn rewritten_vps(&self) -> usize {
+        self.rewritten_vps
+    }
+
+    pub fn report(&self) -> Option<String> {
+        if self.issues.is_empty() {
+            return None;
+        }
+
+        let mut report = String::from("Inconsistent BL parameter sets in the single layer output:");
+
+        for issue in &self.issues {
+            report.push_str(&format!("\n  {}", issue));
+        }
+
+        Some(report)
+    }
+}
@@ -0,0 +1,407 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{stdout, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use utilities_dovi::parse_rpu_file;
+
+use super::hasher::Hasher;
+use super::{write_rpu_file, DoviRpu};
+use crate::commands::{ApplyPatchArgs, CreatePatchArgs};
+
+const PATCH_VERSION: u8 = 1;
+
+/// Metadata changes from a source RPU file to a target RPU file, per frame.
+///
+/// The changes replace the values at JSON pointers of the RPUs serialized as JSON.
+/// `null` removes the field, and arrays of a different length are replaced whole.
+/// Frames without changes are left out.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpuPatch {
+    pub version: u8,
+    pub source: PatchedFile,
+    pub target: PatchedFile,
+    pub frames: Vec<FramePatch>,
+}
+
+/// Frame count and metadata hash of an RPU file, as printed by the `hash` command
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct PatchedFile {
+    pub frames: usize,
+    pub hash: String,
+}
+
+/// Changes to a range of consecutive frames
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FramePatch {
+    pub frame: usize,
+
+    #[serde(default = "default_length", skip_serializing_if = "is_single_frame")]
+    pub length: usize,
+
+    /// First source frame the changes apply to, when it's not the same frame.
+    /// Used for moved, duplicated and added frames.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<usize>,
+
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub changes: Map<String, Value>,
+}
+
+pub struct Patcher;
+
+impl Patcher {
+    pub fn create_patch(args: CreatePatchArgs) -> Result<()> {
+        let CreatePatchArgs {
+            input,
+            input2,
+            output,
+        } = args;
+
+        let output = output.unwrap_or_else(|| PathBuf::from("RPU_patch.json"));
+
+        println!("Parsing RPU files...");
+        stdout().flush().ok();
+
+        let source = parse_rpu_file(&input)?.unwrap_or_default();
+        let target = parse_rpu_file(&input2)?.unwrap_or_default();
+
+        let patch = RpuPatch::create(&source, &target)?;
+
+        let frames: usize = patch.frames.iter().map(|frame| frame.length).sum();
+        println!(
+            "Patched frames: {}, in {} ranges",
+            frames,
+            patch.frames.len()
+        );
+
+        patch.write(&output)?;
+
+        println!("Done.");
+
+        Ok(())
+    }
+
+    pub fn apply_patch(args: ApplyPatchArgs) -> Result<()> {
+        let ApplyPatchArgs {
+            input,
+            patch,
+            rpu_out,
+        } = args;
+
+        let rpu_out = rpu_out.unwrap_or_else(|| PathBuf::from("RPU_patched.bin"));
+
+        let patch = RpuPatch::from_path(&patch)?;
+
+        println!("Parsing RPU file...");
+        stdout().flush().ok();
+
+        let rpus = parse_rpu_file(&input)?.unwrap_or_default();
+
+        println!(
+            "Applying patch to {} frames...",
+            patch.frames.iter().map(|frame| frame.length).sum::<usize>()
+        );
+        stdout().flush().ok();
+
+        let rpus = patch.apply(&rpus)?;
+
+        let data = rpus
+            .iter()
+            .map(|rpu| rpu.write_hevc_unspec62_nalu())
+            .collect::<Result<Vec<_>>>()?;
+
+        write_rpu_file(&rpu_out, data)?;
+
+        println!("Done.");
+
+        Ok(())
+    }
+}
+
+impl RpuPatch {
+    pub fn create(source: &[DoviRpu], target: &[DoviRpu]) -> Result<Self> {
+        ensure!(
+            !source.is_empty(),
+            "create-patch: the original RPU file is empty"
+        );
+
+        let source_json = source
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // First source frame of each metadata, to copy the moved frames
+        let mut source_frames: HashMap<&str, usize> = HashMap::new();
+        for (frame, json) in source_json.iter().enumerate().rev() {
+            source_frames.insert(json, frame);
+        }
+
+        let mut frames: Vec<FramePatch> = Vec::new();
+
+        for (frame, rpu) in target.iter().enumerate() {
+            let json = serde_json::to_string(rpu)?;
+
+            if source_json.get(frame) == Some(&json) {
+                continue;
+            }
+
+            // Following the previous copied frame, or the first identical source frame
+            let copied_frame = frames
+                .last()
+                .filter(|last| last.changes.is_empty())
+                .and_then(|last| last.next_source_frame(frame))
+                .filter(|&source_frame| source_json.get(source_frame) == Some(&json))
+                .or_else(|| source_frames.get(json.as_str()).copied());
+
+            let (source_frame, changes) = match copied_frame {
+                Some(source_frame) => (source_frame, Map::new()),
+                None => {
+                    // The added frames are changes to the last source frame
+                    let source_frame = frame.min(source.len() - 1);
+
+                    let mut changes = Map::new();
+                    diff_values(
+                        "",
+                        &rpu_value(&source[source_frame])?,
+                        &rpu_value(rpu)?,
+                        &mut changes,
+                    );
+
+                    (source_frame, changes)
+                }
+            };
+
+            match frames.last_mut() {
+                Some(last)
+                    if last.next_source_frame(frame) == Some(source_frame)
+                        && last.changes == changes =>
+                {
+                    last.length += 1
+                }
+                _ => frames.push(FramePatch {
+                    frame,
+                    length: 1,
+                    source: Some(source_frame).filter(|source_frame| *source_frame != frame),
+                    changes,
+                }),
+            }
+        }
+
+        Ok(Self {
+            version: PATCH_VERSION,
+            source: PatchedFile::from_rpus(source)?,
+            target: PatchedFile::from_rpus(target)?,
+            frames,
+        })
+    }
+
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Can't open patch file {}", path.display()))?;
+
+        let patch: RpuPatch = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("Invalid patch file {}", path.display()))?;
+
+        ensure!(
+            patch.version == PATCH_VERSION,
+            "apply-patch: unsupported patch version {}",
+            patch.version
+        );
+
+        Ok(patch)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut writer = BufWriter::new(super::create_output_file(path)?);
+
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Applies the patch to the source RPUs, which must match the source of the patch.
+    /// The patched RPUs are verified against the target hash.
+    pub fn apply(&self, rpus: &[DoviRpu]) -> Result<Vec<DoviRpu>> {
+        let source = PatchedFile::from_rpus(rpus)?;
+
+        if source != self.source {
+            bail!(
+                "apply-patch: the patch was created for another RPU file. Expected {} frames with hash {}, got {} frames with hash {}",
+                self.source.frames,
+                self.source.hash,
+                source.frames,
+                source.hash
+            );
+        }
+
+        let mut patched: Vec<DoviRpu> = rpus.iter().take(self.target.frames).cloned().collect();
+
+        for frame_patch in &self.frames {
+            for i in 0..frame_patch.length {
+                let frame = frame_patch.frame + i;
+                let source_frame = frame_patch.source.unwrap_or(frame_patch.frame) + i;
+
+                let source_rpu = match rpus.get(source_frame) {
+                    Some(source_rpu) => source_rpu,
+                    None => bail!(
+                        "apply-patch: frame {}: source frame {} is out of range",
+                        frame,
+                        source_frame
+                    ),
+                };
+
+                let rpu = frame_patch.apply(source_rpu).with_context(|| {
+                    format!("apply-patch: frame {}: invalid patched RPU", frame)
+                })?;
+
+                match frame.cmp(&patched.len()) {
+                    Ordering::Less => patched[frame] = rpu,
+                    Ordering::Equal => patched.push(rpu),
+                    Ordering::Greater => bail!("apply-patch: frame {} is out of range", frame),
+                }
+            }
+        }
+
+        let target = PatchedFile::from_rpus(&patched)?;
+
+        ensure!(
+            target == self.target,
+            "apply-patch: the patched RPUs don't match the target hash {}, got {}",
+            self.target.hash,
+            target.hash
+        );
+
+        Ok(patched)
+    }
+}
+
+impl FramePatch {
+    /// Source frame of the frame, if it continues the range
+    fn next_source_frame(&self, frame: usize) -> Option<usize> {
+        if self.frame + self.length == frame {
+            Some(self.source.unwrap_or(self.frame) + self.length)
+        } else {
+            None
+        }
+    }
+
+    fn apply(&self, source_rpu: &DoviRpu) -> Result<DoviRpu> {
+        if self.changes.is_empty() {
+            return Ok(source_rpu.clone());
+        }
+
+        let mut value = serde_json::to_value(source_rpu)?;
+
+        for (pointer, new_value) in &self.changes {
+            set_pointer(&mut value, pointer, new_value.clone())
+                .with_context(|| format!("invalid change {}", pointer))?;
+        }
+
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+impl PatchedFile {
+    fn from_rpus(rpus: &[DoviRpu]) -> Result<Self> {
+        let frame_hashes = rpus
+            .iter()
+            .map(Hasher::rpu_hash)
+            .collect::<Result<Vec<u64>>>()?;
+
+        Ok(Self {
+            frames: rpus.len(),
+            hash: format!("{:016x}", Hasher::file_hash(&frame_hashes)),
+        })
+    }
+}
+
+/// The RPU as JSON, without the CRC32 computed when writing the patched RPUs
+fn rpu_value(rpu: &DoviRpu) -> Result<Value> {
+    let mut value = serde_json::to_value(rpu)?;
+
+    if let Some(map) = value.as_object_mut() {
+        map.remove("rpu_data_crc32");
+    }
+
+    Ok(value)
+}
+
+/// Records the changes from `value` to `value2`, keyed by JSON pointer
+fn diff_values(pointer: &str, value: &Value, value2: &Value, changes: &mut Map<String, Value>) {
+    match (value, value2) {
+        (Value::Object(map), Value::Object(map2)) => {
+            for (key, field) in map {
+                let field2 = map2.get(key).unwrap_or(&Value::Null);
+                diff_values(&child_pointer(pointer, key), field, field2, changes);
+            }
+
+            for (key, field2) in map2.iter().filter(|(key, _)| !map.contains_key(*key)) {
+                diff_values(&child_pointer(pointer, key), &Value::Null, field2, changes);
+            }
+        }
+        (Value::Array(list), Value::Array(list2)) if list.len() == list2.len() => {
+            for (i, (item, item2)) in list.iter().zip(list2.iter()).enumerate() {
+                diff_values(
+                    &child_pointer(pointer, &i.to_string()),
+                    item,
+                    item2,
+                    changes,
+                );
+            }
+        }
+        _ => {
+            if value != value2 {
+                changes.insert(pointer.to_string(), value2.clone());
+            }
+        }
+    }
+}
+
+/// JSON pointer of a field or array element, escaped as in RFC 6901
+fn child_pointer(pointer: &str, key: &str) -> String {
+    format!("{}/{}", pointer, key.replace('~', "~0").replace('/', "~1"))
+}
+
+/// Sets the value at the JSON pointer, removing the field for `null`
+fn set_pointer(root: &mut Value, pointer: &str, value: Value) -> Result<()> {
+    let (parent, key) = match pointer.rfind('/') {
+        Some(i) => (
+            &pointer[..i],
+            pointer[i + 1..].replace("~1", "/").replace("~0", "~"),
+        ),
+        None => bail!("not a JSON pointer"),
+    };
+
+    match root.pointer_mut(parent) {
+        Some(Value::Object(map)) => {
+            if value.is_null() {
+                map.remove(&key);
+            } else {
+                map.insert(key, value);
+            }
+        }
+        Some(Value::Array(list)) => match key.parse::<usize>().ok().and_then(|i| list.get_mut(i)) {
+            Some(item) => *item = value,
+            None => bail!("array index out of range"),
+        },
+        _ => bail!("no such field"),
+    }
+
+    Ok(())
+}
+
+fn default_length() -> usize {
+    1
+}
+
+fn is_single_frame(length: &usize) -> bool {
+    *length == 1
+}
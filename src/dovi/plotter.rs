@@ -0,0 +1,449 @@
+use std::io::{stdout, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use plotters::style::{register_font, FontStyle};
+use serde_json::json;
+
+use dolby_vision::rpu::extension_metadata::blocks::{ExtMetadataBlock, ExtMetadataBlockLevel2};
+use dolby_vision::utils::{pq12_to_pq, pq_to_nits};
+use utilities_dovi::parse_rpu_file;
+
+use super::{create_output_file, deterministic, input_from_either};
+use crate::commands::PlotArgs;
+
+use super::DoviRpu;
+
+const PLOT_FONT: &[u8] = include_bytes!("../../assets/fonts/DejaVuSansMono.ttf");
+const PLOT_SIZE: (u32, u32) = (3000, 1200);
+
+/// Drawn in the browser, with the chart data replacing `/*PLOT_DATA*/`
+const HTML_REPORT_TEMPLATE: &str = include_str!("../../assets/html/plot_report.html");
+
+const MAX_COLOR: RGBColor = RGBColor(65, 105, 225);
+const AVG_COLOR: RGBColor = RGBColor(75, 0, 130);
+const MIN_COLOR: RGBColor = RGBColor(0, 0, 0);
+const SCENE_CUT_COLOR: RGBColor = RGBColor(200, 200, 200);
+
+const L2_COLORS: [RGBColor; 6] = [
+    RGBColor(65, 105, 225),
+    RGBColor(220, 20, 60),
+    RGBColor(34, 139, 34),
+    RGBColor(255, 140, 0),
+    RGBColor(148, 0, 211),
+    RGBColor(0, 0, 0),
+];
+const L5_COLORS: [RGBColor; 4] = [
+    RGBColor(65, 105, 225),
+    RGBColor(220, 20, 60),
+    RGBColor(34, 139, 34),
+    RGBColor(255, 140, 0),
+];
+
+pub struct Plotter {
+    input: PathBuf,
+    output: PathBuf,
+    title: String,
+    plot_type: PlotType,
+    target_nits: u16,
+}
+
+#[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum PlotType {
+    /// L1 min/avg/max brightness
+    L1,
+    /// L2 trims of a target display
+    L2,
+    /// L5 active area offsets
+    L5,
+}
+
+/// L1 values for a single frame, as normalized PQ
+struct L1Point {
+    min: f64,
+    max: f64,
+    avg: f64,
+}
+
+type L1Value = fn(&L1Point) -> f64;
+type L2Value = fn(&ExtMetadataBlockLevel2) -> f64;
+
+/// A chart of values over the frames, with the scene cuts
+struct Chart {
+    caption: String,
+    y_desc: &'static str,
+    y_max: f64,
+    y_label: fn(&f64) -> String,
+    /// Values are normalized PQ, displayed in nits
+    pq_values: bool,
+    series: Vec<(String, RGBColor, Vec<f64>)>,
+}
+
+impl Plotter {
+    pub fn plot(args: PlotArgs) -> Result<()> {
+        let PlotArgs {
+            input,
+            input_pos,
+            output,
+            title,
+            plot_type,
+            target_nits,
+        } = args;
+
+        let input = input_from_either("plot", input, input_pos)?;
+
+        let output = output.unwrap_or_else(|| {
+            PathBuf::from(match plot_type {
+                PlotType::L1 => "L1_plot.png",
+                PlotType::L2 => "L2_plot.png",
+                PlotType::L5 => "L5_plot.png",
+            })
+        });
+        let title = title.unwrap_or_else(|| {
+            input
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default()
+        });
+
+        let plotter = Plotter {
+            input,
+            output,
+            title,
+            plot_type,
+            target_nits,
+        };
+
+        println!("Parsing RPU file...");
+        stdout().flush().ok();
+
+        if let Some(rpus) = parse_rpu_file(&plotter.input)? {
+            plotter.execute(&rpus)?;
+        }
+
+        println!("Done.");
+
+        Ok(())
+    }
+
+    fn execute(&self, rpus: &[DoviRpu]) -> Result<()> {
+        let chart = match self.plot_type {
+            PlotType::L1 => {
+                println!("Plotting L1 metadata...");
+                self.l1_chart(rpus)?
+            }
+            PlotType::L2 => {
+                println!("Plotting L2 metadata...");
+                self.l2_chart(rpus)?
+            }
+            PlotType::L5 => {
+                println!("Plotting L5 metadata...");
+                self.l5_chart(rpus)
+            }
+        };
+
+        let scene_cuts = scene_cuts(rpus);
+
+        if has_extension(&self.output, "html") {
+            return write_html_report(&self.output, &chart, &scene_cuts);
+        }
+
+        register_font("sans-serif", FontStyle::Normal, PLOT_FONT)
+            .map_err(|_| anyhow::anyhow!("plot: failed loading font"))?;
+
+        if has_extension(&self.output, "svg") {
+            let root = SVGBackend::new(&self.output, PLOT_SIZE).into_drawing_area();
+            draw(root, &chart, &scene_cuts)?;
+        } else {
+            let root = BitMapBackend::new(&self.output, PLOT_SIZE).into_drawing_area();
+            draw(root, &chart, &scene_cuts)?;
+        }
+
+        // Written by the plotters backend
+        deterministic::record_output(&self.output);
+
+        Ok(())
+    }
+
+    fn l1_chart(&self, rpus: &[DoviRpu]) -> Result<Chart> {
+        let points = l1_points(rpus)?;
+
+        let max_pq = points
+            .iter()
+            .map(|p| p.max)
+            .fold(0.0, f64::max)
+            .max(pq12_to_pq(2081));
+
+        // Round up the Y axis to the next 0.05 PQ step
+        let y_max = ((max_pq * 20.0).ceil() / 20.0).min(1.0);
+
+        let series: [(&str, RGBColor, L1Value); 3] = [
+            ("Maximum", MAX_COLOR, |p| p.max),
+            ("Average", AVG_COLOR, |p| p.avg),
+            ("Minimum", MIN_COLOR, |p| p.min),
+        ];
+
+        Ok(Chart {
+            caption: format!("{} - L1 plot", self.title),
+            y_desc: "nits (cd/m²)",
+            y_max,
+            y_label: |pq| format!("{:.2}", pq_to_nits(*pq)),
+            pq_values: true,
+            series: series
+                .iter()
+                .map(|(label, color, value)| {
+                    (
+                        label.to_string(),
+                        *color,
+                        points.iter().map(value).collect(),
+                    )
+                })
+                .collect(),
+        })
+    }
+
+    fn l2_chart(&self, rpus: &[DoviRpu]) -> Result<Chart> {
+        let target_max_pq = ExtMetadataBlockLevel2::from_nits(self.target_nits).target_max_pq;
+
+        let trims: Vec<Option<&ExtMetadataBlockLevel2>> = rpus
+            .iter()
+            .map(|rpu| {
+                rpu.vdr_dm_data.as_ref().and_then(|dm| {
+                    dm.level_blocks_iter(2).find_map(|block| match block {
+                        ExtMetadataBlock::Level2(l2) if l2.target_max_pq == target_max_pq => {
+                            Some(l2)
+                        }
+                        _ => None,
+                    })
+                })
+            })
+            .collect();
+
+        if trims.iter().all(Option::is_none) {
+            bail!(
+                "plot: no L2 metadata for the {} nits target",
+                self.target_nits
+            );
+        }
+
+        // Frames without trims for the target are plotted as neutral
+        let neutral = ExtMetadataBlockLevel2::default();
+
+        let fields: [(&str, L2Value); 6] = [
+            ("Slope", |l2| l2.trim_slope as f64),
+            ("Offset", |l2| l2.trim_offset as f64),
+            ("Power", |l2| l2.trim_power as f64),
+            ("Chroma weight", |l2| l2.trim_chroma_weight as f64),
+            ("Saturation gain", |l2| l2.trim_saturation_gain as f64),
+            ("MS weight", |l2| l2.ms_weight as f64),
+        ];
+
+        Ok(Chart {
+            caption: format!("{} - L2 plot, {} nits", self.title, self.target_nits),
+            y_desc: "trim value",
+            y_max: 4095.0,
+            y_label: |v| format!("{:.0}", v),
+            pq_values: false,
+            series: fields
+                .iter()
+                .zip(L2_COLORS)
+                .map(|((label, value), color)| {
+                    let values = trims
+                        .iter()
+                        .map(|l2| value(l2.unwrap_or(&neutral)))
+                        .collect();
+
+                    (label.to_string(), color, values)
+                })
+                .collect(),
+        })
+    }
+
+    fn l5_chart(&self, rpus: &[DoviRpu]) -> Chart {
+        // Frames without L5 metadata have no letterbox
+        let offsets: Vec<Vec<u16>> = rpus
+            .iter()
+            .map(
+                |rpu| match rpu.vdr_dm_data.as_ref().and_then(|dm| dm.get_block(5)) {
+                    Some(ExtMetadataBlock::Level5(l5)) => l5.get_offsets_vec(),
+                    _ => vec![0; 4],
+                },
+            )
+            .collect();
+
+        let max_offset = offsets.iter().flatten().copied().max().unwrap_or(0);
+
+        // Round up the Y axis to the next 100 pixels
+        let y_max = ((max_offset as f64 / 100.0).floor() + 1.0) * 100.0;
+
+        let labels = ["Left", "Right", "Top", "Bottom"];
+
+        Chart {
+            caption: format!("{} - L5 plot", self.title),
+            y_desc: "offset (pixels)",
+            y_max,
+            y_label: |v| format!("{:.0}", v),
+            pq_values: false,
+            series: labels
+                .iter()
+                .zip(L5_COLORS)
+                .enumerate()
+                .map(|(i, (label, color))| {
+                    let values = offsets.iter().map(|o| o[i] as f64).collect();
+                    (label.to_string(), color, values)
+                })
+                .collect(),
+        }
+    }
+}
+
+fn draw<DB>(root: DrawingArea<DB, Shift>, chart: &Chart, scene_cuts: &[usize]) -> Result<()>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    let frames = chart
+        .series
+        .first()
+        .map_or(0, |(_, _, values)| values.len());
+    let y_max = chart.y_max;
+
+    root.fill(&WHITE)?;
+
+    let mut chart_context = ChartBuilder::on(&root)
+        .caption(&chart.caption, ("sans-serif", 40))
+        .margin(30)
+        .x_label_area_size(60)
+        .y_label_area_size(120)
+        .build_cartesian_2d(0..frames, 0.0..y_max)?;
+
+    chart_context
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_desc("frames")
+        .y_desc(chart.y_desc)
+        .y_labels(20)
+        .y_label_formatter(&chart.y_label)
+        .label_style(("sans-serif", 20))
+        .axis_desc_style(("sans-serif", 24))
+        .draw()?;
+
+    chart_context.draw_series(
+        scene_cuts
+            .iter()
+            .map(|frame| PathElement::new(vec![(*frame, 0.0), (*frame, y_max)], SCENE_CUT_COLOR)),
+    )?;
+
+    for (label, color, values) in &chart.series {
+        let color = *color;
+
+        chart_context
+            .draw_series(LineSeries::new(
+                values.iter().enumerate().map(|(i, v)| (i, *v)),
+                color,
+            ))?
+            .label(label)
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+    }
+
+    chart_context
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperRight)
+        .label_font(("sans-serif", 24))
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+
+    root.present()?;
+
+    Ok(())
+}
+
+/// Writes the chart as a single self-contained HTML file,
+/// zoomable and showing the values of the hovered frame
+fn write_html_report(path: &Path, chart: &Chart, scene_cuts: &[usize]) -> Result<()> {
+    let y_ticks: Vec<(f64, String)> = (0..=20)
+        .map(|i| {
+            let value = chart.y_max * i as f64 / 20.0;
+            (value, (chart.y_label)(&value))
+        })
+        .collect();
+
+    let series: Vec<_> = chart
+        .series
+        .iter()
+        .map(|(label, color, values)| {
+            // The PQ values don't need more precision than 12 bits
+            let values: Vec<f64> = values
+                .iter()
+                .map(|v| (v * 10000.0).round() / 10000.0)
+                .collect();
+
+            json!({
+                "label": label,
+                "color": format!("#{:02x}{:02x}{:02x}", color.0, color.1, color.2),
+                "values": values,
+            })
+        })
+        .collect();
+
+    let data = json!({
+        "caption": chart.caption,
+        "yDesc": chart.y_desc,
+        "yMax": chart.y_max,
+        "yTicks": y_ticks,
+        "pqValues": chart.pq_values,
+        "series": series,
+        "sceneCuts": scene_cuts,
+    });
+
+    // Can't end the script element early
+    let data = serde_json::to_string(&data)?.replace("</", "<\\/");
+
+    create_output_file(path)?.write_all(
+        HTML_REPORT_TEMPLATE
+            .replace("/*PLOT_DATA*/", &data)
+            .as_bytes(),
+    )?;
+
+    Ok(())
+}
+
+fn l1_points(rpus: &[DoviRpu]) -> Result<Vec<L1Point>> {
+    rpus.iter()
+        .enumerate()
+        .map(|(i, rpu)| {
+            let block = rpu.vdr_dm_data.as_ref().and_then(|dm| dm.get_block(1));
+
+            match block {
+                Some(ExtMetadataBlock::Level1(l1)) => Ok(L1Point {
+                    min: pq12_to_pq(l1.min_pq),
+                    max: pq12_to_pq(l1.max_pq),
+                    avg: pq12_to_pq(l1.avg_pq),
+                }),
+                _ => bail!("plot: frame {} has no L1 metadata", i),
+            }
+        })
+        .collect()
+}
+
+fn scene_cuts(rpus: &[DoviRpu]) -> Vec<usize> {
+    rpus.iter()
+        .enumerate()
+        .filter(|(_, rpu)| {
+            rpu.vdr_dm_data
+                .as_ref()
+                .map(|dm| dm.scene_refresh_flag == 1)
+                .unwrap_or(false)
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .map(|ext| ext.eq_ignore_ascii_case(extension))
+        .unwrap_or(false)
+}
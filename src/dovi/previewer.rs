@@ -0,0 +1,264 @@
+use std::fs;
+use std::io::{stdout, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, ensure, Context, Result};
+use plotters::prelude::*;
+use rayon::prelude::*;
+
+use dolby_vision::utils::nits_to_pq;
+use utilities_dovi::parse_rpu_file;
+
+use crate::commands::PreviewArgs;
+
+use super::lut_generator::{DisplayMapping, LutTransfer};
+use super::{deterministic, input_from_either, DoviRpu};
+
+/// Grey patches of the bottom band, in nits
+const GREY_STEPS: [f64; 13] = [
+    0.0, 0.1, 1.0, 10.0, 50.0, 100.0, 203.0, 400.0, 600.0, 1000.0, 2000.0, 4000.0, 10000.0,
+];
+
+/// Brightness of the hue sweep, in nits
+const HUE_SWEEP_NITS: f64 = 1000.0;
+
+pub struct Previewer {
+    input: PathBuf,
+    output_dir: PathBuf,
+    frames: Vec<usize>,
+    target_nits: u16,
+    compare: Option<PathBuf>,
+    width: u32,
+    height: u32,
+}
+
+/// Horizontal bands of the test pattern, from top to bottom
+#[derive(Debug, Clone, Copy)]
+enum Band {
+    /// Grey ramp over the whole PQ range
+    Grey,
+    /// Ramp of a single primary over the whole PQ range
+    Primary(usize),
+    /// Fully saturated hues at `HUE_SWEEP_NITS`
+    HueSweep,
+    /// Grey patches of `GREY_STEPS`
+    GreySteps,
+}
+
+const BANDS: [Band; 6] = [
+    Band::Grey,
+    Band::Primary(0),
+    Band::Primary(1),
+    Band::Primary(2),
+    Band::HueSweep,
+    Band::GreySteps,
+];
+
+impl Previewer {
+    pub fn preview(args: PreviewArgs) -> Result<()> {
+        let PreviewArgs {
+            input,
+            input_pos,
+            output_dir,
+            frames,
+            target_nits,
+            compare,
+            width,
+            height,
+        } = args;
+
+        let input = input_from_either("preview", input, input_pos)?;
+
+        ensure!(target_nits > 0, "preview: invalid target display");
+        ensure!(
+            width >= 16 && height >= BANDS.len() as u32,
+            "preview: the test pattern must be at least 16x{} pixels",
+            BANDS.len()
+        );
+
+        let previewer = Previewer {
+            input,
+            output_dir: output_dir.unwrap_or_else(|| PathBuf::from("preview")),
+            frames,
+            target_nits,
+            compare,
+            width,
+            height,
+        };
+
+        println!("Parsing RPU file...");
+        stdout().flush().ok();
+
+        let rpus = match parse_rpu_file(&previewer.input)? {
+            Some(rpus) => rpus,
+            None => return Ok(()),
+        };
+
+        let compare_rpus = match &previewer.compare {
+            Some(compare) => match parse_rpu_file(compare)? {
+                Some(rpus) => Some(rpus),
+                None => return Ok(()),
+            },
+            None => None,
+        };
+
+        previewer.execute(&rpus, compare_rpus.as_deref())?;
+
+        println!("Done.");
+
+        Ok(())
+    }
+
+    fn execute(&self, rpus: &[DoviRpu], compare_rpus: Option<&[DoviRpu]>) -> Result<()> {
+        let frames = if self.frames.is_empty() {
+            scene_starts(rpus)
+        } else {
+            self.frames.clone()
+        };
+
+        let frame_count = compare_rpus.map_or(rpus.len(), |compare| compare.len().min(rpus.len()));
+
+        if let Some(&frame) = frames.iter().find(|&&frame| frame >= frame_count) {
+            bail!(
+                "preview: frame {} is out of range, the RPU file has {} frames",
+                frame,
+                frame_count
+            );
+        }
+
+        println!("Rendering {} frames...", frames.len());
+        stdout().flush().ok();
+
+        fs::create_dir_all(&self.output_dir)?;
+
+        frames.par_iter().try_for_each(|&frame| {
+            let mut images = vec![self.render(&rpus[frame], frame)?];
+
+            if let Some(compare_rpus) = compare_rpus {
+                images.push(self.render(&compare_rpus[frame], frame)?);
+            }
+
+            let path = self.output_dir.join(format!("frame_{:06}.png", frame));
+
+            self.write_png(&path, &images)
+        })?;
+
+        Ok(())
+    }
+
+    /// SDR pixels of the test pattern mapped to the target display, row by row
+    fn render(&self, rpu: &DoviRpu, frame: usize) -> Result<Vec<[u8; 3]>> {
+        let mapping = DisplayMapping::new(rpu, self.target_nits, LutTransfer::Sdr, false)
+            .with_context(|| format!("preview: frame {}", frame))?;
+
+        let width = self.width as usize;
+        let height = self.height as usize;
+        let mut pixels = Vec::with_capacity(width * height);
+
+        for (i, band) in BANDS.iter().enumerate() {
+            // The last band takes the remaining rows
+            let rows = if i == BANDS.len() - 1 {
+                height - (height / BANDS.len()) * i
+            } else {
+                height / BANDS.len()
+            };
+
+            let row: Vec<[u8; 3]> = (0..width)
+                .map(|x| {
+                    let position = x as f64 / (width - 1) as f64;
+
+                    mapping
+                        .map(band.value(position))
+                        .map(|v| (v * 255.0).round() as u8)
+                })
+                .collect();
+
+            for _ in 0..rows {
+                pixels.extend_from_slice(&row);
+            }
+        }
+
+        Ok(pixels)
+    }
+
+    /// Writes the images side by side
+    fn write_png(&self, path: &Path, images: &[Vec<[u8; 3]>]) -> Result<()> {
+        let size = (self.width * images.len() as u32, self.height);
+        let root = BitMapBackend::new(path, size).into_drawing_area();
+
+        for (i, pixels) in images.iter().enumerate() {
+            let x_offset = (i as u32 * self.width) as i32;
+
+            for (j, [r, g, b]) in pixels.iter().enumerate() {
+                let x = (j as u32 % self.width) as i32 + x_offset;
+                let y = (j as u32 / self.width) as i32;
+
+                root.draw_pixel((x, y), &RGBColor(*r, *g, *b))?;
+            }
+        }
+
+        root.present()?;
+
+        // Written by the plotters backend
+        deterministic::record_output(path);
+
+        Ok(())
+    }
+}
+
+impl Band {
+    /// Base layer BT.2020 PQ RGB value at the horizontal position, from 0 to 1
+    fn value(&self, position: f64) -> [f64; 3] {
+        match self {
+            Band::Grey => [position; 3],
+            Band::Primary(component) => {
+                let mut rgb = [0.0; 3];
+                rgb[*component] = position;
+
+                rgb
+            }
+            Band::HueSweep => {
+                let pq = nits_to_pq(HUE_SWEEP_NITS);
+
+                hue_to_rgb(position).map(|v| v * pq)
+            }
+            Band::GreySteps => {
+                let step =
+                    ((position * GREY_STEPS.len() as f64) as usize).min(GREY_STEPS.len() - 1);
+
+                [nits_to_pq(GREY_STEPS[step]); 3]
+            }
+        }
+    }
+}
+
+/// Fully saturated RGB of the hue, from 0 to 1 for a full turn
+fn hue_to_rgb(hue: f64) -> [f64; 3] {
+    let h = (hue * 6.0) % 6.0;
+    let x = 1.0 - ((h % 2.0) - 1.0).abs();
+
+    match h as usize {
+        0 => [1.0, x, 0.0],
+        1 => [x, 1.0, 0.0],
+        2 => [0.0, 1.0, x],
+        3 => [0.0, x, 1.0],
+        4 => [x, 0.0, 1.0],
+        _ => [1.0, 0.0, x],
+    }
+}
+
+/// The first frame, and the frames with the scene cut flag
+fn scene_starts(rpus: &[DoviRpu]) -> Vec<usize> {
+    rpus.iter()
+        .enumerate()
+        .filter(|(i, rpu)| {
+            *i == 0
+                || rpu
+                    .vdr_dm_data
+                    .as_ref()
+                    .map(|dm| dm.scene_refresh_flag == 1)
+                    .unwrap_or(false)
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
@@ -0,0 +1,131 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+use super::DoviRpu;
+
+/// Single profile to convert a mixed profile stream to
+#[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalizeProfile {
+    /// Profile 8.1, from profiles 5, 7 and 8
+    #[clap(name = "8.1")]
+    Profile81,
+    /// Profile 8.4, from profiles 5, 7 and 8
+    #[clap(name = "8.4")]
+    Profile84,
+    /// Profile 7 MEL, from profiles 7 and 8
+    #[clap(name = "7-mel")]
+    Profile7Mel,
+}
+
+impl NormalizeProfile {
+    /// Conversion mode of an RPU of the profile
+    pub fn mode(&self, dovi_profile: u8) -> Result<u8> {
+        let mode = match (self, dovi_profile) {
+            (Self::Profile81, 5) => 3,
+            (Self::Profile81, 7) => 2,
+            (Self::Profile81, 8) => 0,
+            (Self::Profile84, 5 | 7 | 8) => 4,
+            (Self::Profile7Mel, 7 | 8) => 1,
+            _ => bail!("Cannot normalize profile {} to {}", dovi_profile, self),
+        };
+
+        Ok(mode)
+    }
+}
+
+impl fmt::Display for NormalizeProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Profile81 => write!(f, "profile 8.1"),
+            Self::Profile84 => write!(f, "profile 8.4"),
+            Self::Profile7Mel => write!(f, "profile 7 MEL"),
+        }
+    }
+}
+
+/// Profile and mapping variant of an RPU
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileVariant {
+    profile: u8,
+    subprofile: Option<String>,
+    bl_bit_depth: u64,
+}
+
+impl ProfileVariant {
+    pub fn from_rpu(rpu: &DoviRpu) -> Self {
+        Self {
+            profile: rpu.dovi_profile,
+            subprofile: rpu.subprofile.clone(),
+            bl_bit_depth: rpu.header.bl_bit_depth_minus8 + 8,
+        }
+    }
+}
+
+impl fmt::Display for ProfileVariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "profile {}", self.profile)?;
+
+        if let Some(subprofile) = &self.subprofile {
+            write!(f, " {}", subprofile)?;
+        }
+
+        write!(f, ", {} bit BL", self.bl_bit_depth)
+    }
+}
+
+/// Frame ranges of the successive profile variants of a stream
+#[derive(Debug, Default)]
+pub struct ProfileMix {
+    /// First frame of every variant change
+    segments: Vec<(usize, ProfileVariant)>,
+    frame_count: usize,
+}
+
+impl ProfileMix {
+    /// Adds the variant of the next frame, or `None` to continue the current one
+    pub fn push(&mut self, variant: Option<ProfileVariant>) {
+        if let Some(variant) = variant {
+            let changed = self
+                .segments
+                .last()
+                .is_none_or(|(_, last)| last != &variant);
+
+            if changed {
+                self.segments.push((self.frame_count, variant));
+            }
+        }
+
+        self.frame_count += 1;
+    }
+
+    pub fn is_mixed(&self) -> bool {
+        self.segments.len() > 1
+    }
+
+    /// Frame ranges of the variants, inclusive
+    pub fn segments(&self) -> Vec<(usize, usize, &ProfileVariant)> {
+        let ends = self
+            .segments
+            .iter()
+            .skip(1)
+            .map(|(start, _)| *start)
+            .chain([self.frame_count]);
+
+        self.segments
+            .iter()
+            .zip(ends)
+            .map(|((start, variant), end)| (*start, end - 1, variant))
+            .collect()
+    }
+
+    pub fn report(&self) -> String {
+        let mut report = String::from("mixed profiles in the stream:");
+
+        for (start, end, variant) in self.segments() {
+            report.push_str(&format!("\n  frames {}-{}: {}", start, end, variant));
+        }
+
+        report
+    }
+}
@@ -0,0 +1,92 @@
+use std::io::{stderr, Write};
+use std::time::{Duration, Instant};
+
+use indicatif::ProgressBar;
+use serde::Serialize;
+
+use super::server;
+
+/// Minimum time between two progress events
+const EVENT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Progress events written as JSON lines on stderr, for the tools wrapping dovi_tool.
+/// The byte counts come from the hidden progress bar wrapping the input reader.
+///
+/// In the `--serve` mode, the events are written on stdout with the ID of the job.
+#[derive(Default)]
+pub struct JsonProgress {
+    last_event: Option<Instant>,
+}
+
+#[derive(Serialize)]
+struct ProgressEvent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<u64>,
+    event: &'static str,
+    bytes_read: u64,
+    /// Unknown when reading from stdin
+    total_bytes: Option<u64>,
+    frames: usize,
+    rpus: usize,
+    elapsed_secs: f64,
+    eta_secs: Option<f64>,
+}
+
+impl JsonProgress {
+    /// Writes a `progress` event, at most every 500 ms
+    pub fn update(&mut self, progress_bar: &ProgressBar, frames: usize, rpus: usize) {
+        let now = Instant::now();
+
+        if let Some(last_event) = self.last_event {
+            if now.duration_since(last_event) < EVENT_INTERVAL {
+                return;
+            }
+        }
+
+        self.last_event = Some(now);
+        write_event("progress", progress_bar, frames, rpus);
+    }
+
+    /// Writes the final `done` event
+    pub fn finish(&mut self, progress_bar: &ProgressBar, frames: usize, rpus: usize) {
+        write_event("done", progress_bar, frames, rpus);
+    }
+}
+
+fn write_event(event: &'static str, progress_bar: &ProgressBar, frames: usize, rpus: usize) {
+    let bytes_read = progress_bar.position();
+    let elapsed_secs = progress_bar.elapsed().as_secs_f64();
+
+    // Spinners have no length
+    let total_bytes = Some(progress_bar.length()).filter(|length| *length != !0);
+
+    let eta_secs = total_bytes
+        .filter(|_| bytes_read > 0)
+        .map(|total| elapsed_secs * total.saturating_sub(bytes_read) as f64 / bytes_read as f64);
+
+    let id = server::current_job();
+
+    let progress_event = ProgressEvent {
+        id,
+        event,
+        bytes_read,
+        total_bytes,
+        frames,
+        rpus,
+        elapsed_secs,
+        eta_secs,
+    };
+
+    write_json_event(&progress_event);
+}
+
+/// Writes a JSON line on stderr, or on stdout for the jobs of the `--serve` mode
+pub fn write_json_event<T: Serialize>(event: &T) {
+    if server::current_job().is_some() {
+        server::write_json_line(event);
+    } else if let Ok(line) = serde_json::to_string(event) {
+        let mut stderr = stderr();
+        writeln!(stderr, "{}", line).ok();
+        stderr.flush().ok();
+    }
+}
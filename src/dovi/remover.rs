@@ -0,0 +1,68 @@
+use anyhow::{bail, Result};
+use indicatif::ProgressBar;
+use std::path::PathBuf;
+
+use crate::commands::RemoveArgs;
+
+use super::{codec, general_read_write, input_from_either, CliOptions, IoFormat};
+
+use general_read_write::{DoviProcessor, DoviWriter};
+
+pub struct Remover {
+    format: IoFormat,
+    input: PathBuf,
+    output: PathBuf,
+}
+
+impl Remover {
+    pub fn from_args(args: RemoveArgs, options: &CliOptions) -> Result<Self> {
+        let RemoveArgs {
+            input,
+            input_pos,
+            output,
+        } = args;
+
+        let input = input_from_either("remove", input, input_pos)?;
+        let format = codec::format_from_path(&input, options.input_codec)?;
+
+        let output = match output {
+            Some(path) => path,
+            None => PathBuf::from("BL.hevc"),
+        };
+
+        Ok(Self {
+            format,
+            input,
+            output,
+        })
+    }
+
+    pub fn remove(args: RemoveArgs, options: CliOptions) -> Result<()> {
+        let remover = Remover::from_args(args, &options)?;
+        remover.process_input(options)
+    }
+
+    fn process_input(&self, options: CliOptions) -> Result<()> {
+        let pb = super::initialize_progress_bar(&self.format, &self.input)?;
+
+        match self.format {
+            IoFormat::Matroska => bail!("Remover: Matroska input is unsupported"),
+            _ => self.remove_from_raw_hevc(pb, options),
+        }
+    }
+
+    /// Only the BL is written, without the RPU and EL NAL units
+    fn remove_from_raw_hevc(&self, pb: ProgressBar, options: CliOptions) -> Result<()> {
+        let dovi_writer = DoviWriter::new(
+            Some(&self.output),
+            None,
+            None,
+            None,
+            None,
+            options.write_buffer,
+        )?;
+        let mut dovi_processor = DoviProcessor::new(options, self.input.clone(), dovi_writer, pb);
+
+        dovi_processor.read_write_from_io(&self.format)
+    }
+}
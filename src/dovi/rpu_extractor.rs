@@ -1,59 +1,152 @@
-use anyhow::{bail, Result};
-use indicatif::ProgressBar;
 use std::path::PathBuf;
 
+use anyhow::{bail, ensure, Result};
+use indicatif::ProgressBar;
+
 use crate::commands::ExtractRpuArgs;
 
-use super::{general_read_write, input_from_either, CliOptions, IoFormat};
+use super::batch::{self, BatchInputs, BatchJob};
+use super::frame_order_map::FrameOrderMap;
+use super::profile_mix::NormalizeProfile;
+use super::rpu_stream::{self, RpuWriter};
+use super::{general_read_write, CliOptions, CrcMode, IoFormat};
 use general_read_write::{DoviProcessor, DoviWriter};
 
 pub struct RpuExtractor {
-    format: IoFormat,
-    input: PathBuf,
-    rpu_out: PathBuf,
+    jobs: Vec<BatchJob>,
+    split_dir: Option<PathBuf>,
+    split_json: bool,
+    repair: bool,
+    stream: bool,
+    crc_mode: Option<CrcMode>,
+    frame_order: Option<FrameOrderMap>,
+    normalize: Option<NormalizeProfile>,
 }
 
 impl RpuExtractor {
-    pub fn from_args(args: ExtractRpuArgs) -> Result<Self> {
+    pub fn from_args(args: ExtractRpuArgs, options: &CliOptions) -> Result<Self> {
         let ExtractRpuArgs {
             input,
             input_pos,
             rpu_out,
+            output_template,
+            recursive,
+            extensions,
+            split_dir,
+            split_json,
+            repair,
+            stream,
+            crc,
+            frame_order,
+            normalize,
         } = args;
 
-        let input = input_from_either("extract-rpu", input, input_pos)?;
-        let format = hevc_parser::io::format_from_path(&input)?;
-
-        let rpu_out = match rpu_out {
-            Some(path) => path,
-            None => PathBuf::from("RPU.bin"),
+        let inputs = BatchInputs {
+            paths: if input.is_empty() { input_pos } else { input },
+            recursive,
+            extensions,
         };
 
-        Ok(Self {
-            format,
-            input,
+        let jobs = batch::jobs_from_inputs(
+            "extract-rpu",
+            inputs,
             rpu_out,
+            output_template,
+            "RPU.bin",
+            "{stem}.rpu.bin",
+            options.input_codec,
+        )?;
+
+        ensure!(
+            split_dir.is_none() || jobs.len() == 1,
+            "extract-rpu: --split-dir only supports a single input, got {}",
+            jobs.len()
+        );
+
+        ensure!(
+            frame_order.is_none() || jobs.len() == 1,
+            "extract-rpu: --frame-order only supports a single input, got {}",
+            jobs.len()
+        );
+
+        ensure!(
+            normalize.is_none() || matches!(options.mode, None | Some(0)),
+            "extract-rpu: --normalize cannot be used with --mode"
+        );
+
+        let frame_order = frame_order
+            .as_deref()
+            .map(FrameOrderMap::from_path)
+            .transpose()?;
+
+        Ok(Self {
+            jobs,
+            split_dir,
+            split_json,
+            repair,
+            stream,
+            crc_mode: crc,
+            frame_order,
+            normalize,
         })
     }
 
     pub fn extract_rpu(args: ExtractRpuArgs, options: CliOptions) -> Result<()> {
-        let rpu_extractor = RpuExtractor::from_args(args)?;
+        let rpu_extractor = RpuExtractor::from_args(args, &options)?;
         rpu_extractor.process_input(options)
     }
 
-    fn process_input(&self, options: CliOptions) -> Result<()> {
-        let pb = super::initialize_progress_bar(&self.format, &self.input)?;
+    fn process_input(&self, mut options: CliOptions) -> Result<()> {
+        options.repair_rpus = self.repair;
+        options.stream_rpus = self.stream;
+        options.crc_mode = self.crc_mode;
+        options.frame_order = self.frame_order.clone();
 
-        match self.format {
-            IoFormat::Matroska => bail!("Extractor: Matroska input is unsupported"),
-            _ => self.extract_rpu_from_el(pb, options),
+        if self.normalize.is_some() {
+            options.normalize = self.normalize;
+            options.mode.get_or_insert(0);
         }
+
+        batch::run_jobs(&self.jobs, |job, pb| match job.format {
+            IoFormat::Matroska => bail!("Extractor: Matroska input is unsupported"),
+            _ => self.extract_rpu_from_el(job, pb, options.clone()),
+        })
     }
 
-    fn extract_rpu_from_el(&self, pb: ProgressBar, options: CliOptions) -> Result<()> {
-        let dovi_writer = DoviWriter::new(None, None, Some(&self.rpu_out), None);
-        let mut dovi_processor = DoviProcessor::new(options, self.input.clone(), dovi_writer, pb);
+    fn extract_rpu_from_el(
+        &self,
+        job: &BatchJob,
+        pb: ProgressBar,
+        mut options: CliOptions,
+    ) -> Result<()> {
+        if let Some(split_dir) = &self.split_dir {
+            let dovi_writer = DoviWriter::new(None, None, None, None, None, options.write_buffer)?
+                .with_rpu_writer(RpuWriter::split(split_dir, self.split_json)?);
+            let mut dovi_processor =
+                DoviProcessor::new(options, job.input.clone(), dovi_writer, pb);
+
+            return dovi_processor.read_write_from_io(&job.format);
+        }
+
+        // Piped RPUs are written as soon as possible, unless every RPU is needed first
+        if rpu_stream::is_stdio(&job.output)
+            && !options.repair_rpus
+            && options.crc_mode.is_none()
+            && options.frame_order.is_none()
+        {
+            options.stream_rpus = true;
+        }
+
+        let dovi_writer = DoviWriter::new(
+            None,
+            None,
+            Some(&job.output),
+            None,
+            None,
+            options.write_buffer,
+        )?;
+        let mut dovi_processor = DoviProcessor::new(options, job.input.clone(), dovi_writer, pb);
 
-        dovi_processor.read_write_from_io(&self.format)
+        dovi_processor.read_write_from_io(&job.format)
     }
 }
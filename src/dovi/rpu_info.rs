@@ -1,12 +1,30 @@
-use std::io::{stdout, Write};
-use std::path::PathBuf;
+use std::io::{self, stdout, Write};
+use std::path::{Path, PathBuf};
 
 use anyhow::{bail, ensure, Result};
+use indicatif::ProgressBar;
+use serde::Serialize;
+
+use hevc_parser::hevc::{NALUnit, NAL_SEI_PREFIX, NAL_SPS, NAL_UNSPEC62, NAL_UNSPEC63};
+use hevc_parser::io::{processor, IoFormat, IoProcessor};
+use hevc_parser::HevcParser;
+use processor::HevcProcessorOpts;
 
 use dolby_vision::rpu::dovi_rpu::DoviRpu;
+use dolby_vision::rpu::extension_metadata::blocks::{ContentType, ExtMetadataBlock};
+use dolby_vision::utils::{pq12_to_pq, pq_to_nits};
 use utilities_dovi::parse_rpu_file;
 
-use super::input_from_either;
+use super::el_analysis::ElAnalysis;
+use super::hdr10_sei::{parse_hdr10_sei, ContentLightLevel, MasteringDisplay};
+use super::hevc_processor::HevcProcessor;
+use super::hevc_sps::SpsInfo;
+use super::http_input;
+use super::rpu_stream::status_out;
+use super::timecode::{format_timestamp, parse_fps};
+use super::{
+    input_from_either, is_st2094_40_sei, open_hevc_input, InputFormat, DEFAULT_READ_BUFFER,
+};
 use crate::commands::InfoArgs;
 
 pub struct RpuInfo {
@@ -15,28 +33,91 @@ pub struct RpuInfo {
     rpus: Option<Vec<DoviRpu>>,
 }
 
+/// Collects the RPUs, stream info and HDR10 metadata of a HEVC file, for the summary
+struct HevcSummary {
+    input: PathBuf,
+    progress_bar: ProgressBar,
+
+    rpus: Vec<DoviRpu>,
+    invalid_rpus: usize,
+    frames: usize,
+    has_hdr10plus: bool,
+
+    /// First SPS of the base layer
+    sps: Option<SpsInfo>,
+    mastering_display: Option<MasteringDisplay>,
+    content_light_level: Option<ContentLightLevel>,
+
+    el: Option<ElAnalysis>,
+}
+
+/// Scene of the scene list, from a scene cut flag to the next
+#[derive(Debug, Serialize)]
+struct Scene {
+    scene: usize,
+    start_frame: usize,
+    frames: usize,
+
+    /// `HH:MM:SS.mmm` timestamp of the start frame, with the frame rate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_time: Option<String>,
+    /// Duration in seconds, with the frame rate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration: Option<f64>,
+
+    /// Lowest min, average avg and highest max of the L1 metadata, as 12 bit PQ.
+    /// None when no frame of the scene has L1 metadata
+    l1: Option<SceneL1>,
+}
+
+#[derive(Debug, Serialize)]
+struct SceneL1 {
+    min_pq: u16,
+    avg_pq: u16,
+    max_pq: u16,
+}
+
 impl RpuInfo {
     pub fn info(args: InfoArgs) -> Result<()> {
+        RpuInfo::info_to(args, &mut stdout())
+    }
+
+    /// Writes the RPU or summary to `out`, and the status messages to the status output
+    pub fn info_to(args: InfoArgs, out: &mut dyn Write) -> Result<()> {
         let InfoArgs {
             input,
             input_pos,
             frame,
+            summary,
+            analyze_el,
+            scenes,
+            json,
+            fps,
         } = args;
 
-        if frame.is_none() {
-            bail!("No frame number to look up");
+        if frame.is_none() && !summary && !scenes {
+            bail!("No frame number to look up, or --summary or --scenes");
         }
 
         let input = input_from_either("info", input, input_pos)?;
 
+        if summary {
+            return RpuInfo::summary(&input, analyze_el, out);
+        }
+
+        if scenes {
+            let fps = fps.as_deref().map(parse_fps).transpose()?;
+            return RpuInfo::scenes(&input, json, fps, out);
+        }
+
         let mut info = RpuInfo {
             input,
             frame,
             rpus: None,
         };
 
-        println!("Parsing RPU file...");
-        stdout().flush().ok();
+        writeln!(status_out(), "Parsing RPU file...").ok();
+        status_out().flush().ok();
 
         info.rpus = parse_rpu_file(&info.input)?;
 
@@ -53,10 +134,485 @@ impl RpuInfo {
             let rpu = &rpus[f];
 
             if let Ok(rpu_serialized) = serde_json::to_string_pretty(&rpu) {
-                println!("{}", rpu_serialized);
+                writeln!(out, "{}", rpu_serialized)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn summary(input: &Path, analyze_el: bool, out: &mut dyn Write) -> Result<()> {
+        // Anything that isn't a HEVC file is parsed as a RPU file
+        match hevc_format(input) {
+            Ok(IoFormat::Matroska) => bail!("info: Matroska input is unsupported"),
+            Ok(format) => {
+                let summary = HevcSummary::parse(input, format, analyze_el)?;
+
+                print_summary(&summary.rpus, summary.frames, out)?;
+
+                if summary.invalid_rpus > 0 {
+                    writeln!(out, "  Invalid RPUs: {}", summary.invalid_rpus)?;
+                }
+
+                writeln!(
+                    out,
+                    "  HDR10+: {}",
+                    if summary.has_hdr10plus { "Yes" } else { "No" }
+                )?;
+
+                summary.print_stream_info(out)?;
+
+                if let Some(el) = &summary.el {
+                    el.print_summary(&summary.rpus, out)?;
+                }
+            }
+            Err(_) => {
+                ensure!(!analyze_el, "info: the EL analysis requires a HEVC input");
+
+                writeln!(status_out(), "Parsing RPU file...").ok();
+                status_out().flush().ok();
+
+                if let Some(rpus) = parse_rpu_file(input)? {
+                    print_summary(&rpus, rpus.len(), out)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Lists the scenes of the RPU file, or of the RPUs of a HEVC file
+    fn scenes(input: &Path, json: bool, fps: Option<f64>, out: &mut dyn Write) -> Result<()> {
+        let rpus = match hevc_format(input) {
+            Ok(IoFormat::Matroska) => bail!("info: Matroska input is unsupported"),
+            Ok(format) => HevcSummary::parse(input, format, false)?.rpus,
+            Err(_) => {
+                writeln!(status_out(), "Parsing RPU file...").ok();
+                status_out().flush().ok();
+
+                parse_rpu_file(input)?.unwrap_or_default()
+            }
+        };
+
+        let scenes = scene_list(&rpus, fps);
+
+        if json {
+            writeln!(out, "{}", serde_json::to_string_pretty(&scenes)?)?;
+        } else {
+            print_scenes(&scenes, out)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl HevcSummary {
+    fn parse(input: &Path, format: IoFormat, analyze_el: bool) -> Result<Self> {
+        writeln!(status_out(), "Parsing HEVC file...").ok();
+        status_out().flush().ok();
+
+        let mut summary = HevcSummary {
+            input: input.to_path_buf(),
+            progress_bar: super::initialize_progress_bar(&format, input)?,
+            rpus: Vec::new(),
+            invalid_rpus: 0,
+            frames: 0,
+            has_hdr10plus: false,
+            sps: None,
+            mastering_display: None,
+            content_light_level: None,
+            el: analyze_el.then(ElAnalysis::default),
+        };
+
+        let chunk_size = DEFAULT_READ_BUFFER;
+
+        let mut processor =
+            HevcProcessor::new(format.clone(), HevcProcessorOpts::default(), chunk_size);
+
+        let mut reader = open_hevc_input(
+            &format,
+            input,
+            InputFormat::Auto,
+            &summary.progress_bar,
+            chunk_size,
+        )?;
+
+        processor.process_io(&mut reader, &mut summary)?;
+
+        Ok(summary)
+    }
+}
+
+impl HevcSummary {
+    fn print_stream_info(&self, out: &mut dyn Write) -> io::Result<()> {
+        if let Some(sps) = &self.sps {
+            writeln!(
+                out,
+                "  Resolution: {}x{}, {} bit",
+                sps.width,
+                sps.height,
+                sps.bit_depth_luma_minus8 + 8
+            )?;
+
+            match &sps.video_signal {
+                Some(signal) => writeln!(
+                    out,
+                    "  Colour: {} primaries, {} transfer, {} matrix, {} range",
+                    signal.primaries_name(),
+                    signal.transfer_name(),
+                    signal.matrix_name(),
+                    if signal.full_range { "full" } else { "limited" }
+                )?,
+                None => writeln!(out, "  Colour: not signaled")?,
             }
         }
 
+        match &self.mastering_display {
+            Some(mastering_display) => match mastering_display.primaries_name() {
+                Some(name) => writeln!(
+                    out,
+                    "  Mastering display: {} primaries, max {} nits, min {} nits",
+                    name,
+                    mastering_display.max_luminance as f64 / 10000.0,
+                    mastering_display.min_luminance as f64 / 10000.0
+                )?,
+                None => writeln!(out, "  Mastering display: {}", mastering_display)?,
+            },
+            None => writeln!(out, "  Mastering display: None")?,
+        }
+
+        match &self.content_light_level {
+            Some(cll) => writeln!(
+                out,
+                "  Content light level: MaxCLL {} nits, MaxFALL {} nits",
+                cll.max_cll, cll.max_fall
+            )?,
+            None => writeln!(out, "  Content light level: None")?,
+        }
+
+        Ok(())
+    }
+}
+
+impl IoProcessor for HevcSummary {
+    fn input(&self) -> &PathBuf {
+        &self.input
+    }
+
+    fn update_progress(&mut self, _delta: u64) {
+        // Progress is updated by the input reader
+    }
+
+    fn process_nals(&mut self, _parser: &HevcParser, nals: &[NALUnit], chunk: &[u8]) -> Result<()> {
+        for nal in nals {
+            let data = &chunk[nal.start..nal.end];
+
+            if let Some(el) = self.el.as_mut() {
+                // EL wrapped in UNSPEC63 NAL units, or carried in its own layer
+                if nal.nal_type == NAL_UNSPEC63 {
+                    el.add_nal(nal.decoded_frame_index, &data[2..])?;
+                    continue;
+                } else if nal.nuh_layer_id > 0 {
+                    el.add_nal(nal.decoded_frame_index, data)?;
+                    continue;
+                }
+            }
+
+            match nal.nal_type {
+                NAL_UNSPEC62 => match DoviRpu::parse_unspec62_nalu(data) {
+                    Ok(rpu) => self.rpus.push(rpu),
+                    Err(_) => self.invalid_rpus += 1,
+                },
+                NAL_SPS if self.sps.is_none() && nal.nuh_layer_id == 0 => {
+                    self.sps = Some(SpsInfo::parse(data)?);
+                }
+                NAL_SEI_PREFIX => {
+                    if !self.has_hdr10plus {
+                        self.has_hdr10plus = is_st2094_40_sei(data)?;
+                    }
+
+                    if self.mastering_display.is_none() || self.content_light_level.is_none() {
+                        if let Ok((mastering_display, content_light_level)) = parse_hdr10_sei(data)
+                        {
+                            self.mastering_display = self.mastering_display.or(mastering_display);
+                            self.content_light_level =
+                                self.content_light_level.or(content_light_level);
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finalize(&mut self, parser: &HevcParser) -> Result<()> {
+        self.frames = parser.ordered_frames().len();
+        self.progress_bar.finish_and_clear();
+
         Ok(())
     }
 }
+
+/// Format of a HEVC input, URLs being raw HEVC. Fails for RPU files
+fn hevc_format(input: &Path) -> Result<IoFormat> {
+    if http_input::is_url(input) {
+        Ok(IoFormat::Raw)
+    } else {
+        hevc_parser::io::format_from_path(input)
+    }
+}
+
+fn print_summary(rpus: &[DoviRpu], frames: usize, out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "Summary:")?;
+    writeln!(out, "  Frames: {}", frames)?;
+
+    if rpus.len() != frames {
+        writeln!(out, "  RPUs: {}", rpus.len())?;
+    }
+
+    if rpus.is_empty() {
+        return writeln!(out, "  No RPU found.");
+    }
+
+    // Profile 7 includes the enhancement layer type, MEL or FEL
+    let profiles = unique_values(rpus.iter().map(|rpu| match &rpu.subprofile {
+        Some(subprofile) => format!("{} ({})", rpu.dovi_profile, subprofile),
+        None => rpu.dovi_profile.to_string(),
+    }));
+    writeln!(out, "  Profile: {}", profiles.join(", "))?;
+
+    let cm_versions = unique_values(rpus.iter().map(|rpu| match &rpu.vdr_dm_data {
+        Some(vdr_dm_data) if vdr_dm_data.cmv40_metadata.is_some() => "CM v4.0",
+        Some(_) => "CM v2.9",
+        None => "None",
+    }));
+    writeln!(out, "  CM version: {}", cm_versions.join(", "))?;
+
+    let scene_count = rpus
+        .iter()
+        .filter_map(|rpu| rpu.vdr_dm_data.as_ref())
+        .filter(|vdr_dm_data| vdr_dm_data.scene_refresh_flag == 1)
+        .count();
+    writeln!(out, "  Scene cuts: {}", scene_count)?;
+
+    // Only when present, the offsets usually vary per shot
+    let l3_values = unique_values(rpus.iter().filter_map(|rpu| {
+        match rpu.vdr_dm_data.as_ref().and_then(|dm| dm.get_block(3)) {
+            Some(ExtMetadataBlock::Level3(l3)) => Some(format!(
+                "min offset {:+}, avg offset {:+}, max offset {:+}",
+                l3.min_pq_offset as i32 - 2048,
+                l3.avg_pq_offset as i32 - 2048,
+                l3.max_pq_offset as i32 - 2048
+            )),
+            _ => None,
+        }
+    }));
+
+    if !l3_values.is_empty() {
+        print_level_values("L3", &l3_values, out)?;
+    }
+
+    let l4_values = unique_values(rpus.iter().filter_map(|rpu| {
+        match rpu.vdr_dm_data.as_ref().and_then(|dm| dm.get_block(4)) {
+            Some(ExtMetadataBlock::Level4(l4)) => Some(format!(
+                "anchor PQ {}, anchor power {}",
+                l4.anchor_pq, l4.anchor_power
+            )),
+            _ => None,
+        }
+    }));
+
+    if !l4_values.is_empty() {
+        print_level_values("L4", &l4_values, out)?;
+    }
+
+    let l6_values = unique_values(rpus.iter().map(|rpu| {
+        match rpu.vdr_dm_data.as_ref().and_then(|dm| dm.get_block(6)) {
+            Some(ExtMetadataBlock::Level6(l6)) => format!(
+                "MaxCLL {} nits, MaxFALL {} nits, mastering display min {} nits, max {} nits",
+                l6.max_content_light_level,
+                l6.max_frame_average_light_level,
+                l6.min_display_mastering_luminance as f64 / 10000.0,
+                l6.max_display_mastering_luminance
+            ),
+            _ => String::from("None"),
+        }
+    }));
+
+    print_level_values("L6", &l6_values, out)?;
+
+    let l11_values = unique_values(rpus.iter().map(|rpu| {
+        match rpu.vdr_dm_data.as_ref().and_then(|dm| dm.get_block(11)) {
+            Some(ExtMetadataBlock::Level11(l11)) => {
+                let content_type = match ContentType::from_value(l11.content_type) {
+                    Some(content_type) => content_type.name().to_string(),
+                    None => format!("Reserved content type {}", l11.content_type),
+                };
+
+                // WP * 375 + 6504
+                let whitepoint = match l11.whitepoint {
+                    0 => String::from("D65"),
+                    wp => format!("{}K", wp as u16 * 375 + 6504),
+                };
+
+                let mode = if l11.reference_mode_flag {
+                    ", reference mode"
+                } else {
+                    ""
+                };
+
+                format!("{}, {} whitepoint{}", content_type, whitepoint, mode)
+            }
+            _ => String::from("None"),
+        }
+    }));
+
+    print_level_values("L11", &l11_values, out)?;
+
+    let l254_values = unique_values(rpus.iter().map(|rpu| {
+        match rpu.vdr_dm_data.as_ref().and_then(|dm| dm.get_block(254)) {
+            Some(ExtMetadataBlock::Level254(l254)) => format!(
+                "DM mode {}, DM version index {}",
+                l254.dm_mode, l254.dm_version_index
+            ),
+            _ => String::from("None"),
+        }
+    }));
+
+    print_level_values("L254", &l254_values, out)
+}
+
+/// Scenes from the scene cut flags, the first frame always starts a scene
+fn scene_list(rpus: &[DoviRpu], fps: Option<f64>) -> Vec<Scene> {
+    let mut scenes: Vec<Scene> = Vec::new();
+    // Sums of the L1 values of the current scene, with the number of frames with L1
+    let mut l1_stats: Option<(u16, u64, u16, u64)> = None;
+
+    for (frame, rpu) in rpus.iter().enumerate() {
+        let vdr_dm_data = rpu.vdr_dm_data.as_ref();
+        let scene_cut = vdr_dm_data.is_some_and(|dm| dm.scene_refresh_flag == 1);
+
+        if scene_cut || scenes.is_empty() {
+            if let Some(scene) = scenes.last_mut() {
+                scene.l1 = scene_l1(l1_stats.take());
+            }
+
+            scenes.push(Scene {
+                scene: scenes.len(),
+                start_frame: frame,
+                frames: 0,
+                start_time: fps.map(|fps| format_timestamp(frame as f64 / fps)),
+                duration: None,
+                l1: None,
+            });
+        }
+
+        if let Some(scene) = scenes.last_mut() {
+            scene.frames += 1;
+        }
+
+        if let Some(ExtMetadataBlock::Level1(l1)) = vdr_dm_data.and_then(|dm| dm.get_block(1)) {
+            let (min, avg_sum, max, count) = l1_stats.get_or_insert((u16::MAX, 0, 0, 0));
+
+            *min = (*min).min(l1.min_pq);
+            *avg_sum += l1.avg_pq as u64;
+            *max = (*max).max(l1.max_pq);
+            *count += 1;
+        }
+    }
+
+    if let Some(scene) = scenes.last_mut() {
+        scene.l1 = scene_l1(l1_stats);
+    }
+
+    if let Some(fps) = fps {
+        for scene in &mut scenes {
+            // Rounded to the millisecond
+            scene.duration = Some((scene.frames as f64 / fps * 1000.0).round() / 1000.0);
+        }
+    }
+
+    scenes
+}
+
+fn scene_l1(l1_stats: Option<(u16, u64, u16, u64)>) -> Option<SceneL1> {
+    l1_stats.map(|(min_pq, avg_sum, max_pq, count)| SceneL1 {
+        min_pq,
+        avg_pq: (avg_sum as f64 / count as f64).round() as u16,
+        max_pq,
+    })
+}
+
+/// Table of the scenes, with the L1 values in nits
+fn print_scenes(scenes: &[Scene], out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "Scenes: {}", scenes.len())?;
+
+    if scenes.is_empty() {
+        return Ok(());
+    }
+
+    let timed = scenes[0].start_time.is_some();
+
+    write!(out, "{:>7} {:>8} {:>8}", "Scene", "Start", "Frames")?;
+    if timed {
+        write!(out, " {:>12} {:>9}", "Start time", "Duration")?;
+    }
+    writeln!(
+        out,
+        " {:>10} {:>10} {:>10}",
+        "Min nits", "Avg nits", "Max nits"
+    )?;
+
+    for scene in scenes {
+        write!(
+            out,
+            "{:>7} {:>8} {:>8}",
+            scene.scene, scene.start_frame, scene.frames
+        )?;
+
+        if let (Some(start_time), Some(duration)) = (&scene.start_time, scene.duration) {
+            write!(out, " {:>12} {:>8.3}s", start_time, duration)?;
+        }
+
+        match &scene.l1 {
+            Some(l1) => writeln!(
+                out,
+                " {:>10.4} {:>10.2} {:>10.2}",
+                pq_to_nits(pq12_to_pq(l1.min_pq)),
+                pq_to_nits(pq12_to_pq(l1.avg_pq)),
+                pq_to_nits(pq12_to_pq(l1.max_pq))
+            )?,
+            None => writeln!(out, " {:>10} {:>10} {:>10}", "-", "-", "-")?,
+        }
+    }
+
+    Ok(())
+}
+
+fn print_level_values(level: &str, values: &[String], out: &mut dyn Write) -> io::Result<()> {
+    if values.len() == 1 {
+        writeln!(out, "  {} metadata: {}", level, values[0])?;
+    } else {
+        writeln!(out, "  {} metadata, varying:", level)?;
+        for v in values {
+            writeln!(out, "    {}", v)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Distinct values, in order of first appearance
+fn unique_values<T: ToString>(values: impl Iterator<Item = T>) -> Vec<String> {
+    let mut unique: Vec<String> = Vec::new();
+
+    for value in values.map(|v| v.to_string()) {
+        if !unique.contains(&value) {
+            unique.push(value);
+        }
+    }
+
+    unique
+}
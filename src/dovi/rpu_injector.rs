@@ -1,30 +1,53 @@
-use std::fs::File;
-use std::io::{stdout, BufReader, BufWriter, Write};
+use std::collections::{HashMap, VecDeque};
+use std::io::{stdout, BufWriter, Write};
 use std::path::PathBuf;
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, ensure, Result};
 use indicatif::ProgressBar;
+use log::warn;
+use rayon::prelude::*;
 
 use hevc_parser::io::{processor, FrameBuffer, IoProcessor, NalBuffer};
 use hevc_parser::HevcParser;
 use hevc_parser::{hevc::*, NALUStartCode};
-use processor::{HevcProcessor, HevcProcessorOpts};
-
-use utilities_dovi::parse_rpu_file;
+use processor::HevcProcessorOpts;
 
 use crate::commands::InjectRpuArgs;
 
-use super::{input_from_either, is_st2094_40_sei, CliOptions, DoviRpu, IoFormat};
+use super::avc_parser::AvcParser;
+use super::codec::{self, Codec};
+use super::hevc_processor::HevcProcessor;
+use super::in_place_injector::InPlaceInjector;
+use super::injection_check::InjectionCheck;
+use super::nal_parser::{FrameOrder, NalIoProcessor, NalProcessor};
+use super::rpu_stream::{self, parse_rpu_input, status_out};
+use super::vvc_parser::VvcParser;
+use super::{
+    convert_rpu_from_opts, input_from_either, open_hevc_input, CliOptions, DoviRpu, IoFormat,
+};
+
+/// How to inject RPUs when their count differs from the video frame count
+#[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum MismatchFix {
+    /// Drops the extra RPUs, or the video frames after the last RPU
+    Truncate,
+    /// Drops the extra RPUs, or repeats the last RPU for the remaining frames
+    DuplicateLast,
+    /// Fails with a report of the mismatch
+    Fail,
+}
 
 pub struct RpuInjector {
     input: PathBuf,
-    rpu_in: PathBuf,
+    output: PathBuf,
     no_add_aud: bool,
+    fix: MismatchFix,
     options: CliOptions,
+    codec: Codec,
 
     rpus: Vec<DoviRpu>,
 
-    writer: BufWriter<File>,
+    writer: BufWriter<Box<dyn Write>>,
     progress_bar: ProgressBar,
     already_checked_for_rpu: bool,
 
@@ -32,8 +55,23 @@ pub struct RpuInjector {
     nals: Vec<NALUnit>,
     mismatched_length: bool,
 
+    /// Number of frames written in decoding order, when the video is truncated
+    frame_limit: Option<u64>,
+
     frame_buffer: FrameBuffer,
     last_metadata_written: Option<NalBuffer>,
+
+    /// Piped input, injected in a single pass as the presentation order of every GOP is known
+    streaming: bool,
+    /// Decoded frames waiting for their presentation order, when streaming
+    pending_frames: VecDeque<FrameBuffer>,
+    /// Ordered frames not yet written, by decoded number
+    ordered_frames: HashMap<u64, Frame>,
+    ordered_count: usize,
+
+    /// Reads back the output once written, to check its RPUs
+    verify: bool,
+    written_frames: usize,
 }
 
 impl RpuInjector {
@@ -44,28 +82,88 @@ impl RpuInjector {
             rpu_in,
             output,
             no_add_aud,
+            fix,
+            verify,
+            in_place: _,
         } = args;
 
         let input = input_from_either("inject-rpu", input, input_pos)?;
 
+        ensure!(
+            !(rpu_stream::is_stdio(&input) && rpu_stream::is_stdio(&rpu_in)),
+            "inject-rpu: the video and the RPU stream can't both be piped on stdin"
+        );
+
+        let mut injector = RpuInjector::new(input, output, no_add_aud, fix, cli_options)?;
+
+        ensure!(
+            !verify || !rpu_stream::is_stdio(&injector.output),
+            "inject-rpu: --verify cannot be used when writing to stdout"
+        );
+        injector.verify = verify;
+
+        writeln!(status_out(), "Parsing RPU file...").ok();
+        status_out().flush().ok();
+
+        // Assumes parsing returns on error
+        injector.set_rpus(parse_rpu_input(&rpu_in)?)?;
+
+        Ok(injector)
+    }
+
+    /// Injector without RPUs, set later with `set_rpus`
+    pub fn new(
+        input: PathBuf,
+        output: Option<PathBuf>,
+        no_add_aud: bool,
+        fix: MismatchFix,
+        cli_options: CliOptions,
+    ) -> Result<RpuInjector> {
+        let codec = Codec::from_input(cli_options.input_codec, &input);
+        let streaming = rpu_stream::is_stdio(&input);
+
         let output = match output {
             Some(path) => path,
-            None => PathBuf::from("injected_output.hevc"),
+            None => match codec {
+                Codec::Hevc => PathBuf::from("injected_output.hevc"),
+                Codec::Avc => PathBuf::from("injected_output.h264"),
+                Codec::Vvc => PathBuf::from("injected_output.h266"),
+            },
         };
 
-        let chunk_size = 100_000;
-        let progress_bar = super::initialize_progress_bar(&IoFormat::Raw, &input)?;
-
-        let writer = BufWriter::with_capacity(
-            chunk_size,
-            File::create(&output).expect("Can't create file"),
+        ensure!(
+            !no_add_aud || !cli_options.start_code.requires_aud(),
+            "inject-rpu: --no-add-aud cannot be used with --start-code ts, every access unit needs an AUD"
+        );
+        ensure!(
+            !streaming || fix != MismatchFix::Truncate,
+            "inject-rpu: --fix truncate is not supported with a piped input"
         );
 
-        let mut injector = RpuInjector {
+        let format = if streaming {
+            IoFormat::RawStdin
+        } else {
+            IoFormat::Raw
+        };
+
+        let progress_bar = super::initialize_progress_bar(&format, &input)?;
+
+        let writer: Box<dyn Write> = if rpu_stream::is_stdio(&output) {
+            rpu_stream::use_stdout();
+            Box::new(stdout())
+        } else {
+            Box::new(super::create_output_file(&output)?)
+        };
+
+        let writer = BufWriter::with_capacity(cli_options.write_buffer, writer);
+
+        Ok(RpuInjector {
             input,
-            rpu_in,
+            output,
             no_add_aud,
+            fix,
             options: cli_options,
+            codec,
             rpus: Vec::new(),
 
             writer,
@@ -75,108 +173,295 @@ impl RpuInjector {
             frames: Vec::new(),
             nals: Vec::new(),
             mismatched_length: false,
+            frame_limit: None,
 
             frame_buffer: FrameBuffer {
                 frame_number: 0,
                 nals: Vec::with_capacity(16),
             },
             last_metadata_written: None,
-        };
 
-        println!("Parsing RPU file...");
-        stdout().flush().ok();
+            streaming,
+            pending_frames: VecDeque::new(),
+            ordered_frames: HashMap::new(),
+            ordered_count: 0,
 
-        // Assumes parsing returns on error
-        injector.rpus = parse_rpu_file(&injector.rpu_in)?.unwrap();
+            verify: false,
+            written_frames: 0,
+        })
+    }
 
-        Ok(injector)
+    /// Sets the RPUs to inject, one per frame in presentation order, converting them with the options
+    pub fn set_rpus(&mut self, rpus: Vec<DoviRpu>) -> Result<()> {
+        self.rpus = prepare_rpus(&self.options, rpus)?;
+
+        Ok(())
     }
 
     pub fn inject_rpu(args: InjectRpuArgs, cli_options: CliOptions) -> Result<()> {
+        if args.in_place {
+            return InPlaceInjector::inject(args, cli_options);
+        }
+
         let input = input_from_either("inject-rpu", args.input.clone(), args.input_pos.clone())?;
-        let format = hevc_parser::io::format_from_path(&input)?;
+        let format = codec::format_from_path(&input, cli_options.input_codec)?;
 
-        if let IoFormat::Raw = format {
-            let mut injector = RpuInjector::from_args(args, cli_options)?;
+        let mut injector = match format {
+            IoFormat::Raw => {
+                let mut injector = RpuInjector::from_args(args, cli_options)?;
 
-            injector.process_input()?;
-            injector.interleave_rpu_nals()
-        } else {
-            bail!("RpuInjector: Must be a raw HEVC bitstream file")
+                injector.process_input()?;
+                injector.interleave_rpu_nals()?;
+                injector
+            }
+            IoFormat::RawStdin => {
+                let mut injector = RpuInjector::from_args(args, cli_options)?;
+                injector.stream_rpu_nals()?;
+                injector
+            }
+            _ => bail!("RpuInjector: Must be a raw HEVC, AVC or VVC bitstream file"),
+        };
+
+        if injector.verify {
+            injector.verify_output()?;
         }
+
+        Ok(())
     }
 
-    fn process_input(&mut self) -> Result<()> {
-        println!("Processing input video for frame order info...");
-        stdout().flush().ok();
+    /// Checks the RPUs of the written output, against the RPUs of the written frames
+    fn verify_output(&mut self) -> Result<()> {
+        // Releases the output file before reading it back
+        self.writer = BufWriter::new(Box::new(std::io::sink()));
 
-        let chunk_size = 100_000;
+        let injected_count = self.written_frames.min(self.rpus.len());
 
-        let mut processor =
-            HevcProcessor::new(IoFormat::Raw, HevcProcessorOpts::default(), chunk_size);
+        InjectionCheck::check(
+            &self.output,
+            self.codec,
+            &self.rpus[..injected_count],
+            self.options.read_buffer,
+        )
+    }
 
-        let file = File::open(&self.input)?;
-        let mut reader = Box::new(BufReader::with_capacity(100_000, file));
+    /// Number of frames of the video, once the input is processed
+    pub fn frame_count(&self) -> usize {
+        if self.options.field_coded {
+            self.frames.len() / 2
+        } else {
+            self.frames.len()
+        }
+    }
 
-        processor.process_io(&mut reader, self)
+    pub fn process_input(&mut self) -> Result<()> {
+        writeln!(
+            status_out(),
+            "Processing input video for frame order info..."
+        )
+        .ok();
+        status_out().flush().ok();
+
+        self.process_io()
+    }
+
+    /// Injects the RPUs in a single pass, for piped inputs.
+    /// The frames of every GOP are kept in memory until their presentation order is known.
+    pub fn stream_rpu_nals(&mut self) -> Result<()> {
+        writeln!(status_out(), "Injecting RPUs into the piped video...").ok();
+        status_out().flush().ok();
+
+        self.process_io()
     }
 
-    fn interleave_rpu_nals(&mut self) -> Result<()> {
-        let rpus = &self.rpus;
+    pub fn interleave_rpu_nals(&mut self) -> Result<()> {
+        let frame_count = self.frames.len();
+        let rpu_count = self.rpus.len();
+
+        self.mismatched_length = frame_count != rpu_count;
+
+        if self.mismatched_length {
+            if self.fix == MismatchFix::Fail {
+                bail!("{}", self.mismatch_report());
+            }
 
-        self.mismatched_length = if self.frames.len() != rpus.len() {
-            println!(
-                "\nWarning: mismatched lengths. video {}, RPU {}",
-                self.frames.len(),
-                rpus.len()
+            warn!(
+                "mismatched lengths. video {}, RPU {}",
+                frame_count, rpu_count
             );
 
-            if rpus.len() < self.frames.len() {
-                println!("Metadata will be duplicated at the end to match video length\n");
+            if rpu_count > frame_count {
+                writeln!(
+                    status_out(),
+                    "Metadata will be skipped at the end to match video length\n"
+                )
+                .ok();
+            } else if self.fix == MismatchFix::Truncate {
+                self.truncate_video()?;
+                writeln!(
+                    status_out(),
+                    "Video will be truncated to {} frames\n",
+                    rpu_count
+                )
+                .ok();
             } else {
-                println!("Metadata will be skipped at the end to match video length\n");
+                writeln!(
+                    status_out(),
+                    "Metadata will be duplicated at the end to match video length\n"
+                )
+                .ok();
+
+                let last_rpu = self.rpus.last().cloned();
+                if let Some(last_rpu) = last_rpu {
+                    self.rpus.resize(frame_count, last_rpu);
+                }
             }
+        }
+
+        writeln!(status_out(), "Rewriting file with interleaved RPU NALs..").ok();
+        status_out().flush().ok();
+
+        self.progress_bar = super::initialize_progress_bar(&IoFormat::Raw, &self.input)?;
+
+        self.process_io()
+    }
+
+    /// Limits the output to the frames with an RPU.
+    /// The video can only be cut where the decoding and presentation orders have the same frames.
+    fn truncate_video(&mut self) -> Result<()> {
+        let rpu_count = self.rpus.len() as u64;
+
+        ensure!(
+            rpu_count > 0,
+            "Cannot truncate the video to an empty RPU list"
+        );
+
+        let reordered_frame = self
+            .frames
+            .iter()
+            .find(|f| f.decoded_number < rpu_count && f.presentation_number >= rpu_count);
+
+        if let Some(frame) = reordered_frame {
+            bail!(
+                "Cannot truncate the video to {} frames: decoded frame {} is presented at frame {}, after the cut.\n{}",
+                rpu_count,
+                frame.decoded_number,
+                frame.presentation_number,
+                self.mismatch_report()
+            );
+        }
+
+        self.frame_limit = Some(rpu_count);
+
+        Ok(())
+    }
 
-            true
+    /// Describes where the RPU and video frame counts diverge
+    fn mismatch_report(&self) -> String {
+        let frame_count = self.frames.len();
+        let rpu_count = self.rpus.len();
+
+        let scene_cuts: Vec<usize> = self
+            .rpus
+            .iter()
+            .enumerate()
+            .filter(|(_, rpu)| {
+                rpu.vdr_dm_data
+                    .as_ref()
+                    .is_some_and(|vdr_dm_data| vdr_dm_data.scene_refresh_flag == 1)
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        let mut report = format!(
+            "Mismatched lengths: video {} frames, RPU {} frames",
+            frame_count, rpu_count
+        );
+
+        if rpu_count > frame_count {
+            let extra_scenes = scene_cuts.iter().filter(|i| **i >= frame_count).count();
+
+            report.push_str(&format!(
+                "\n  RPUs {} to {} have no video frame, {} more than the video",
+                frame_count,
+                rpu_count - 1,
+                rpu_count - frame_count
+            ));
+            report.push_str(&format!(
+                "\n  The extra RPUs contain {} scene cuts",
+                extra_scenes
+            ));
         } else {
-            false
-        };
+            report.push_str(&format!(
+                "\n  Video frames {} to {} have no RPU, {} fewer than the video",
+                rpu_count,
+                frame_count - 1,
+                frame_count - rpu_count
+            ));
+        }
 
-        println!("Rewriting file with interleaved RPU NALs..");
-        stdout().flush().ok();
+        if let Some(last_scene_cut) = scene_cuts.iter().rev().find(|i| **i < frame_count) {
+            report.push_str(&format!(
+                "\n  Last RPU scene cut within the video: frame {}",
+                last_scene_cut
+            ));
+        }
 
-        self.progress_bar = super::initialize_progress_bar(&IoFormat::Raw, &self.input)?;
+        report.push_str("\n  Use --fix truncate or --fix duplicate-last to inject anyway");
+
+        report
+    }
 
-        let chunk_size = 100_000;
+    /// Frames after the limit are not written
+    fn is_frame_written(&self, decoded_number: u64) -> bool {
+        self.frame_limit
+            .is_none_or(|frame_limit| decoded_number < frame_limit)
+    }
+
+    fn process_io(&mut self) -> Result<()> {
+        let chunk_size = self.options.read_buffer;
+
+        let format = if self.streaming {
+            IoFormat::RawStdin
+        } else {
+            IoFormat::Raw
+        };
 
-        let mut processor =
-            HevcProcessor::new(IoFormat::Raw, HevcProcessorOpts::default(), chunk_size);
+        let mut reader = open_hevc_input(
+            &format,
+            &self.input,
+            self.options.input_format,
+            &self.progress_bar,
+            chunk_size,
+        )?;
 
-        let file = File::open(&self.input)?;
-        let mut reader = Box::new(BufReader::with_capacity(chunk_size, file));
+        match self.codec {
+            Codec::Hevc => {
+                let mut processor =
+                    HevcProcessor::new(format, HevcProcessorOpts::default(), chunk_size);
 
-        processor.process_io(&mut reader, self)
+                processor.process_io(&mut reader, self)
+            }
+            Codec::Avc => NalProcessor::<AvcParser>::new(chunk_size).process_io(&mut reader, self),
+            Codec::Vvc => NalProcessor::<VvcParser>::new(chunk_size).process_io(&mut reader, self),
+        }
     }
 
     fn get_rpu_and_index_to_insert(
-        frames: &[Frame],
+        codec: Codec,
+        existing_frame: Option<&Frame>,
         rpus: &[DoviRpu],
         frame_buffer: &FrameBuffer,
         mismatched_length: bool,
         last_metadata: &Option<NalBuffer>,
     ) -> Result<(usize, NalBuffer)> {
-        let existing_frame = frames
-            .iter()
-            .find(|f| f.decoded_number == frame_buffer.frame_number);
-
         // If we have a RPU buffered frame, write it
         // Otherwise, write the same data as previous
         let rpu_nb = if let Some(frame) = existing_frame {
             if let Some(ref mut dovi_rpu) = rpus.get(frame.presentation_number as usize) {
-                let rpu_data = dovi_rpu.write_hevc_unspec62_nalu()?;
+                let rpu_data = codec.write_rpu_nalu(dovi_rpu)?;
 
                 Some(NalBuffer {
-                    nal_type: NAL_UNSPEC62,
+                    nal_type: codec.rpu_nal_type(),
                     start_code: NALUStartCode::Length4,
                     data: rpu_data,
                 })
@@ -194,9 +479,9 @@ impl RpuInjector {
 
         if let Some(rpu_nb) = rpu_nb {
             let insert_index = frame_buffer.nals.iter().rposition(|nb| {
-                let not_eos_eob = !matches!(nb.nal_type, NAL_EOS_NUT | NAL_EOB_NUT);
-                let is_el_nalu = matches!(nb.nal_type, NAL_UNSPEC63);
-                let is_slice = NALUnit::is_type_slice(nb.nal_type);
+                let not_eos_eob = !codec.is_end_of_stream(nb.nal_type);
+                let is_el_nalu = nb.nal_type == codec.el_nal_type();
+                let is_slice = codec.is_slice(nb.nal_type);
 
                 not_eos_eob && (is_slice || is_el_nalu)
             });
@@ -206,7 +491,7 @@ impl RpuInjector {
                 Ok((idx + 1, rpu_nb))
             } else {
                 bail!(
-                    "No slice or UNSPEC63 NALUs in decoded frame {}. Cannot insert RPU.",
+                    "No slice or EL NALUs in decoded frame {}. Cannot insert RPU.",
                     frame_buffer.frame_number
                 );
             }
@@ -217,168 +502,250 @@ impl RpuInjector {
             );
         }
     }
-}
 
-impl IoProcessor for RpuInjector {
-    fn input(&self) -> &PathBuf {
-        &self.input
-    }
-
-    fn update_progress(&mut self, delta: u64) {
-        if !self.already_checked_for_rpu {
-            self.already_checked_for_rpu = true;
-        }
-
-        self.progress_bar.inc(delta);
-    }
-
-    fn process_nals(&mut self, _parser: &HevcParser, nals: &[NALUnit], chunk: &[u8]) -> Result<()> {
-        // Second pass
-        if !self.frames.is_empty() && !self.nals.is_empty() {
-            let rpus = &self.rpus;
+    fn write_nals(&mut self, nals: &[NALUnit], chunk: &[u8]) -> Result<()> {
+        let codec = self.codec;
+        let aud_nal_type = codec.aud_nal_type();
+        let rpu_nal_type = codec.rpu_nal_type();
 
+        // Second pass, or single pass when streaming
+        if self.streaming || (!self.frames.is_empty() && !self.nals.is_empty()) {
             for nal in nals {
-                // Ignore HDR10+
-                if self.options.drop_hdr10plus
-                    && nal.nal_type == NAL_SEI_PREFIX
-                    && is_st2094_40_sei(&chunk[nal.start..nal.end])?
+                // Ignore filtered SEI messages
+                let nal_data = match self
+                    .options
+                    .sei_filter
+                    .filter_nal(nal.nal_type, &chunk[nal.start..nal.end])?
                 {
-                    continue;
-                }
+                    Some(data) => data,
+                    None => continue,
+                };
 
                 if self.frame_buffer.frame_number != nal.decoded_frame_index {
-                    // On new frame, write AUD
-                    if !self.no_add_aud {
-                        // Skip existing AUDs
-                        if nal.nal_type == NAL_AUD {
-                            continue;
-                        }
-
-                        if self.frame_buffer.frame_number != nal.decoded_frame_index {
-                            // Find existing frame for the current buffered frame
-                            let buffered_frame = self
-                                .frames
-                                .iter()
-                                .find(|f| f.decoded_number == self.frame_buffer.frame_number)
-                                .unwrap();
-
-                            self.frame_buffer.nals.insert(
-                                0,
-                                NalBuffer {
-                                    nal_type: NAL_AUD,
-                                    start_code: NALUStartCode::Length4,
-                                    data: hevc_parser::utils::aud_for_frame(buffered_frame, None),
-                                },
-                            );
-                        }
-                    }
-
-                    let (idx, rpu_nb) = Self::get_rpu_and_index_to_insert(
-                        &self.frames,
-                        rpus,
-                        &self.frame_buffer,
-                        self.mismatched_length,
-                        &self.last_metadata_written,
-                    )?;
-
-                    self.last_metadata_written = Some(rpu_nb.clone());
-                    self.frame_buffer.nals.insert(idx, rpu_nb);
-
-                    // Write NALUs for the frame
-                    for (i, nal_buf) in self.frame_buffer.nals.iter().enumerate() {
-                        let first_nal = i == 0;
-
-                        NALUnit::write_with_preset(
-                            &mut self.writer,
-                            &nal_buf.data,
-                            self.options.start_code.into(),
-                            nal_buf.nal_type,
-                            first_nal,
-                        )?;
+                    // Skip existing AUDs, one is written on new frame
+                    if !self.no_add_aud && nal.nal_type == aud_nal_type {
+                        continue;
                     }
 
-                    self.frame_buffer.frame_number = nal.decoded_frame_index;
-                    self.frame_buffer.nals.clear();
+                    self.end_frame(nal.decoded_frame_index)?;
                 }
 
                 // Ignore existing RPU
-                if nal.nal_type != NAL_UNSPEC62 {
+                if nal.nal_type != rpu_nal_type {
                     // Skip AUD NALUs if we're adding them
-                    if !self.no_add_aud && nal.nal_type == NAL_AUD {
+                    if !self.no_add_aud && nal.nal_type == aud_nal_type {
                         continue;
                     }
 
                     self.frame_buffer.nals.push(NalBuffer {
                         nal_type: nal.nal_type,
                         start_code: nal.start_code,
-                        data: chunk[nal.start..nal.end].to_vec(),
+                        data: nal_data.into_owned(),
                     });
                 }
             }
-        } else if !self.already_checked_for_rpu && nals.iter().any(|e| e.nal_type == NAL_UNSPEC62) {
+        } else if !self.already_checked_for_rpu && nals.iter().any(|e| e.nal_type == rpu_nal_type) {
             self.already_checked_for_rpu = true;
-            println!("\nWarning: Input file already has RPUs, they will be replaced.");
+            warn!("Input file already has RPUs, they will be replaced.");
         }
 
         Ok(())
     }
 
-    fn finalize(&mut self, parser: &HevcParser) -> Result<()> {
+    /// Writes the buffered frame, or queues it until its presentation order is known when streaming
+    fn end_frame(&mut self, next_frame_number: u64) -> Result<()> {
+        let frame_buffer = FrameBuffer {
+            frame_number: self.frame_buffer.frame_number,
+            nals: std::mem::replace(&mut self.frame_buffer.nals, Vec::with_capacity(16)),
+        };
+
+        self.frame_buffer.frame_number = next_frame_number;
+
+        if self.streaming {
+            self.pending_frames.push_back(frame_buffer);
+            return Ok(());
+        }
+
+        let frame = self
+            .frames
+            .iter()
+            .find(|f| f.decoded_number == frame_buffer.frame_number)
+            .cloned();
+
+        self.write_frame(frame_buffer, frame.as_ref())
+    }
+
+    /// Writes the NALUs of a decoded frame, with an AUD and its RPU
+    fn write_frame(&mut self, mut frame_buffer: FrameBuffer, frame: Option<&Frame>) -> Result<()> {
+        let codec = self.codec;
+
+        if !self.is_frame_written(frame_buffer.frame_number) {
+            return Ok(());
+        }
+
+        if !self.no_add_aud {
+            let frame = frame.ok_or_else(|| {
+                anyhow!(
+                    "No frame info for decoded frame {}",
+                    frame_buffer.frame_number
+                )
+            })?;
+
+            frame_buffer.nals.insert(
+                0,
+                NalBuffer {
+                    nal_type: codec.aud_nal_type(),
+                    start_code: NALUStartCode::Length4,
+                    data: codec.aud_for_frame(frame),
+                },
+            );
+        }
+
+        let (idx, rpu_nb) = Self::get_rpu_and_index_to_insert(
+            codec,
+            frame,
+            &self.rpus,
+            &frame_buffer,
+            self.mismatched_length,
+            &self.last_metadata_written,
+        )?;
+
+        self.last_metadata_written = Some(rpu_nb.clone());
+        frame_buffer.nals.insert(idx, rpu_nb);
+        self.written_frames += 1;
+
+        for (i, nal_buf) in frame_buffer.nals.iter().enumerate() {
+            let first_nal = i == 0;
+
+            codec.write_nal(
+                &mut self.writer,
+                &nal_buf.data,
+                self.options.start_code,
+                nal_buf.nal_type,
+                first_nal,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the queued frames whose presentation order is known, in decoding order
+    fn write_ordered_frames(&mut self, ordered_frames: &[Frame]) -> Result<()> {
+        for frame in &ordered_frames[self.ordered_count..] {
+            self.ordered_frames
+                .insert(frame.decoded_number, frame.clone());
+        }
+
+        self.ordered_count = ordered_frames.len();
+
+        while let Some(frame_buffer) = self.pending_frames.pop_front() {
+            let frame = match self.ordered_frames.remove(&frame_buffer.frame_number) {
+                Some(frame) => frame,
+                None => {
+                    self.pending_frames.push_front(frame_buffer);
+                    break;
+                }
+            };
+
+            self.extend_streamed_rpus(frame.presentation_number as usize)?;
+            self.write_frame(frame_buffer, Some(&frame))?;
+        }
+
+        Ok(())
+    }
+
+    /// Handles the video frames without RPU when streaming, as the frame count isn't known beforehand
+    fn extend_streamed_rpus(&mut self, presentation_number: usize) -> Result<()> {
+        if presentation_number < self.rpus.len() {
+            return Ok(());
+        }
+
+        let last_rpu = match (self.fix, self.rpus.last()) {
+            (MismatchFix::DuplicateLast, Some(last_rpu)) => last_rpu.clone(),
+            _ => bail!(
+                "Mismatched lengths: video frame {} has no RPU, RPU {} frames",
+                presentation_number,
+                self.rpus.len()
+            ),
+        };
+
+        if !self.mismatched_length {
+            self.mismatched_length = true;
+
+            warn!(
+                "mismatched lengths. Video longer than the RPU's {} frames, \
+                the metadata is duplicated at the end to match the video length",
+                self.rpus.len()
+            );
+        }
+
+        self.rpus.resize(presentation_number + 1, last_rpu);
+
+        Ok(())
+    }
+
+    fn finalize_stream(&mut self, ordered_frames: &[Frame]) -> Result<()> {
+        if !self.frame_buffer.nals.is_empty() {
+            self.end_frame(self.frame_buffer.frame_number + 1)?;
+        }
+
+        self.write_ordered_frames(ordered_frames)?;
+
+        if let Some(frame_buffer) = self.pending_frames.front() {
+            bail!(
+                "No frame order for decoded frame {}",
+                frame_buffer.frame_number
+            );
+        }
+
+        self.writer.flush()?;
+        self.progress_bar.finish_and_clear();
+
+        let frame_count = ordered_frames.len();
+
+        if frame_count < self.rpus.len() {
+            let message = format!(
+                "Mismatched lengths: video {} frames, RPU {} frames",
+                frame_count,
+                self.rpus.len()
+            );
+
+            if self.fix == MismatchFix::Fail {
+                bail!("{}", message);
+            }
+
+            warn!("{}. The metadata at the end was skipped", message);
+        }
+
+        Ok(())
+    }
+
+    fn finalize_pass(&mut self, ordered_frames: &[Frame], nals: &[NALUnit]) -> Result<()> {
+        if self.streaming {
+            return self.finalize_stream(ordered_frames);
+        }
+
         // First pass
         if self.frames.is_empty() && self.nals.is_empty() {
-            self.frames = parser.ordered_frames().clone();
-            self.nals = parser.get_nals().clone();
+            self.frames = ordered_frames.to_vec();
+            self.nals = nals.to_vec();
         } else {
-            let ordered_frames = parser.ordered_frames();
             let total_frames = ordered_frames.len();
 
             // Last slice wasn't considered (no AUD/EOS NALU at the end)
             if (self.frame_buffer.frame_number as usize) != total_frames
                 && !self.frame_buffer.nals.is_empty()
             {
-                let rpus = &self.rpus;
-
-                if !self.no_add_aud {
-                    let last_frame = ordered_frames
-                        .iter()
-                        .find(|f| f.decoded_number == self.frame_buffer.frame_number)
-                        .unwrap();
-
-                    self.frame_buffer.nals.insert(
-                        0,
-                        NalBuffer {
-                            nal_type: NAL_AUD,
-                            start_code: NALUStartCode::Length4,
-                            data: hevc_parser::utils::aud_for_frame(last_frame, None),
-                        },
-                    );
-                }
+                let frame_buffer = FrameBuffer {
+                    frame_number: self.frame_buffer.frame_number,
+                    nals: std::mem::take(&mut self.frame_buffer.nals),
+                };
 
-                let (idx, rpu_nb) = Self::get_rpu_and_index_to_insert(
-                    &self.frames,
-                    rpus,
-                    &self.frame_buffer,
-                    self.mismatched_length,
-                    &self.last_metadata_written,
-                )?;
-
-                self.last_metadata_written = Some(rpu_nb.clone());
-                self.frame_buffer.nals.insert(idx, rpu_nb);
-
-                // Write NALUs for the last frame
-                for (i, nal_buf) in self.frame_buffer.nals.iter().enumerate() {
-                    let first_nal = i == 0;
-
-                    NALUnit::write_with_preset(
-                        &mut self.writer,
-                        &nal_buf.data,
-                        self.options.start_code.into(),
-                        nal_buf.nal_type,
-                        first_nal,
-                    )?;
-                }
+                let last_frame = ordered_frames
+                    .iter()
+                    .find(|f| f.decoded_number == frame_buffer.frame_number);
 
-                self.frame_buffer.nals.clear();
+                self.write_frame(frame_buffer, last_frame)?;
             }
 
             // Second pass
@@ -390,3 +757,84 @@ impl IoProcessor for RpuInjector {
         Ok(())
     }
 }
+
+impl IoProcessor for RpuInjector {
+    fn input(&self) -> &PathBuf {
+        &self.input
+    }
+
+    fn update_progress(&mut self, _delta: u64) {
+        if !self.already_checked_for_rpu {
+            self.already_checked_for_rpu = true;
+        }
+    }
+
+    fn process_nals(&mut self, parser: &HevcParser, nals: &[NALUnit], chunk: &[u8]) -> Result<()> {
+        self.write_nals(nals, chunk)?;
+
+        if self.streaming {
+            self.write_ordered_frames(parser.ordered_frames())?;
+        }
+
+        Ok(())
+    }
+
+    fn finalize(&mut self, parser: &HevcParser) -> Result<()> {
+        self.finalize_pass(parser.ordered_frames(), parser.get_nals())
+    }
+}
+
+impl NalIoProcessor for RpuInjector {
+    fn process_nals(
+        &mut self,
+        frame_order: &FrameOrder,
+        nals: &[NALUnit],
+        chunk: &[u8],
+    ) -> Result<()> {
+        self.write_nals(nals, chunk)?;
+
+        if self.streaming {
+            self.write_ordered_frames(frame_order.ordered_frames())?;
+        }
+
+        Ok(())
+    }
+
+    fn finalize(&mut self, frame_order: &FrameOrder) -> Result<()> {
+        self.finalize_pass(frame_order.ordered_frames(), frame_order.get_nals())
+    }
+}
+
+/// Converts the RPUs with the options, one per picture in presentation order
+pub fn prepare_rpus(options: &CliOptions, mut rpus: Vec<DoviRpu>) -> Result<Vec<DoviRpu>> {
+    if options.converts_rpus() {
+        writeln!(status_out(), "Converting RPUs...").ok();
+        status_out().flush().ok();
+
+        rpus.par_iter_mut()
+            .try_for_each(|rpu| convert_rpu_from_opts(options, rpu))?;
+    }
+
+    Ok(if options.field_coded {
+        field_rpus(rpus)
+    } else {
+        rpus
+    })
+}
+
+/// Uses each frame RPU for both of its field pictures.
+/// The second field continues the scene of the first one.
+fn field_rpus(rpus: Vec<DoviRpu>) -> Vec<DoviRpu> {
+    rpus.into_iter()
+        .flat_map(|rpu| {
+            let mut second_field = rpu.clone();
+
+            if let Some(ref mut vdr_dm_data) = second_field.vdr_dm_data {
+                vdr_dm_data.set_scene_cut(false);
+                second_field.modified = true;
+            }
+
+            [rpu, second_field]
+        })
+        .collect()
+}
@@ -0,0 +1,313 @@
+use std::fs::{self, File};
+use std::io::{self, stderr, stdin, stdout, BufWriter, ErrorKind, Read, Stdout, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::{bail, ensure, Result};
+
+use dolby_vision::rpu::dovi_rpu::DoviRpu;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use hevc_parser::hevc::{NALUnit, NAL_UNSPEC62};
+use utilities_dovi::parse_rpu_file;
+#[cfg(feature = "zstd")]
+use utilities_dovi::zstd::ZstdEncoder;
+
+use super::logger::is_quiet;
+use super::{create_output_file, WriteStartCodePreset, DEFAULT_WRITE_BUFFER};
+
+/// Identifies a framed RPU stream, followed by the RPU frames
+pub const RPU_STREAM_MAGIC: &[u8; 4] = b"DVRP";
+
+/// Rejects corrupt frame lengths, an RPU is at most a few KB
+const MAX_FRAME_SIZE: usize = 1 << 20;
+
+/// Path of the RPU inputs and outputs streamed on stdin/stdout
+const STDIO_PATH: &str = "-";
+
+static STDOUT_STREAM: AtomicBool = AtomicBool::new(false);
+
+/// Whether the RPUs are streamed on stdin/stdout instead of a file
+pub fn is_stdio(path: &Path) -> bool {
+    path == Path::new(STDIO_PATH)
+}
+
+/// Marks stdout as used by the RPU stream, the status messages are then written on stderr
+pub fn use_stdout() {
+    STDOUT_STREAM.store(true, Ordering::SeqCst);
+}
+
+/// Output for the status messages: stdout, or stderr when stdout is the RPU stream.
+/// Discarded with `--quiet`.
+pub fn status_out() -> Box<dyn Write> {
+    if is_quiet() {
+        return Box::new(io::sink());
+    }
+
+    diagnostics_out()
+}
+
+/// Output for the logged diagnostics, the same as the status messages but never discarded
+pub fn diagnostics_out() -> Box<dyn Write> {
+    if STDOUT_STREAM.load(Ordering::SeqCst) {
+        Box::new(stderr())
+    } else {
+        Box::new(stdout())
+    }
+}
+
+/// Writes RPUs as a framed stream: the magic bytes, then every RPU NALU
+/// prefixed by its size as 4 bytes big endian.
+///
+/// The RPU NALUs are the same as in the RPU files: escaped, without the 0x7C01 header.
+pub struct RpuStreamWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> RpuStreamWriter<W> {
+    pub fn new(mut inner: W) -> io::Result<Self> {
+        inner.write_all(RPU_STREAM_MAGIC)?;
+
+        Ok(Self { inner })
+    }
+
+    pub fn write_rpu(&mut self, data: &[u8]) -> io::Result<()> {
+        self.inner.write_all(&(data.len() as u32).to_be_bytes())?;
+        self.inner.write_all(data)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reads the RPU NALUs of a framed stream
+pub struct RpuStreamReader<R: Read> {
+    inner: R,
+}
+
+impl<R: Read> RpuStreamReader<R> {
+    pub fn new(mut inner: R) -> Result<Self> {
+        let mut magic = [0; 4];
+
+        match inner.read_exact(&mut magic) {
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => bail!("Empty RPU stream"),
+            res => res?,
+        }
+
+        ensure!(
+            &magic == RPU_STREAM_MAGIC,
+            "Invalid RPU stream, expected the output of a command writing the RPUs to -"
+        );
+
+        Ok(Self { inner })
+    }
+
+    /// The next RPU NALU, or `None` at the end of the stream
+    pub fn read_rpu(&mut self) -> Result<Option<Vec<u8>>> {
+        let mut size_bytes = [0; 4];
+        let mut read = 0;
+
+        while read < size_bytes.len() {
+            match self.inner.read(&mut size_bytes[read..]) {
+                Ok(0) if read == 0 => return Ok(None),
+                Ok(0) => bail!("Truncated RPU stream frame length"),
+                Ok(n) => read += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let size = u32::from_be_bytes(size_bytes) as usize;
+        ensure!(
+            size <= MAX_FRAME_SIZE,
+            "Invalid RPU stream frame size {}",
+            size
+        );
+
+        let mut data = vec![0; size];
+
+        match self.inner.read_exact(&mut data) {
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => bail!("Truncated RPU stream frame"),
+            res => res?,
+        }
+
+        Ok(Some(data))
+    }
+
+    /// Parses every RPU until the end of the stream
+    pub fn parse_rpus(&mut self) -> Result<Vec<DoviRpu>> {
+        let mut rpus = Vec::new();
+
+        while let Some(data) = self.read_rpu()? {
+            match DoviRpu::parse_unspec62_nalu(&data) {
+                Ok(rpu) => rpus.push(rpu),
+                Err(e) => bail!(
+                    "Error parsing frame {} of the RPU stream: {}",
+                    rpus.len(),
+                    e
+                ),
+            }
+        }
+
+        ensure!(!rpus.is_empty(), "No RPU found");
+
+        Ok(rpus)
+    }
+}
+
+/// RPU output: an RPU file, a framed RPU stream on stdout, or one file per RPU
+pub enum RpuWriter {
+    File(BufWriter<File>),
+    /// Gzip compressed RPU file, for a `.gz` output
+    Gzip(GzEncoder<BufWriter<File>>),
+    /// Zstandard compressed RPU file, for a `.zst` output
+    #[cfg(feature = "zstd")]
+    Zstd(ZstdEncoder<BufWriter<File>>),
+    Stream(RpuStreamWriter<Stdout>),
+    Split(SplitRpuWriter),
+}
+
+/// Writes every RPU to its own RPU file in a directory, named by frame number.
+/// Optionally with the parsed RPU as JSON next to it.
+pub struct SplitRpuWriter {
+    dir: PathBuf,
+    json: bool,
+    frame: usize,
+}
+
+impl RpuWriter {
+    /// Creates the RPU file, or the stream on stdout for `-`.
+    /// The RPU file is compressed when the extension is `.gz` (gzip) or `.zst` (Zstandard).
+    pub fn create(path: &Path) -> Result<Self> {
+        Self::with_capacity(path, DEFAULT_WRITE_BUFFER)
+    }
+
+    /// Same as `create`, with the buffer size of the RPU file
+    pub fn with_capacity(path: &Path, capacity: usize) -> Result<Self> {
+        if is_stdio(path) {
+            use_stdout();
+            Ok(Self::Stream(RpuStreamWriter::new(stdout())?))
+        } else if has_extension(path, "gz") {
+            Ok(Self::Gzip(GzEncoder::new(
+                BufWriter::with_capacity(capacity, create_output_file(path)?),
+                Compression::default(),
+            )))
+        } else if has_extension(path, "zst") {
+            #[cfg(feature = "zstd")]
+            return Ok(Self::Zstd(ZstdEncoder::new(BufWriter::with_capacity(
+                capacity,
+                create_output_file(path)?,
+            ))?));
+
+            #[cfg(not(feature = "zstd"))]
+            bail!("Zstandard compressed RPU files require the zstd feature");
+        } else {
+            Ok(Self::File(BufWriter::with_capacity(
+                capacity,
+                create_output_file(path)?,
+            )))
+        }
+    }
+
+    /// Writes the RPUs to separate files in the directory, created if missing
+    pub fn split(dir: &Path, json: bool) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+
+        Ok(Self::Split(SplitRpuWriter {
+            dir: dir.to_path_buf(),
+            json,
+            frame: 0,
+        }))
+    }
+
+    /// Writes an RPU NALU, including the 0x7C01 header
+    pub fn write_rpu(&mut self, data: &[u8]) -> Result<()> {
+        match self {
+            Self::File(writer) => write_rpu_nal(writer, data)?,
+            Self::Gzip(writer) => write_rpu_nal(writer, data)?,
+            #[cfg(feature = "zstd")]
+            Self::Zstd(writer) => write_rpu_nal(writer, data)?,
+            Self::Stream(writer) => writer.write_rpu(&data[2..])?,
+            Self::Split(writer) => writer.write_rpu(data)?,
+        }
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        match self {
+            Self::File(writer) => writer.flush()?,
+            Self::Gzip(writer) => {
+                // Writes the gzip trailer, the RPU file is complete
+                writer.try_finish()?;
+                writer.get_mut().flush()?;
+            }
+            #[cfg(feature = "zstd")]
+            Self::Zstd(writer) => {
+                // Writes the end of the frame, the RPU file is complete
+                writer.try_finish()?;
+                writer.get_mut().flush()?;
+            }
+            Self::Stream(writer) => writer.flush()?,
+            Self::Split(_) => (),
+        }
+
+        Ok(())
+    }
+}
+
+/// RPU file is always 4 bytes start code
+fn write_rpu_nal<W: Write>(writer: &mut W, data: &[u8]) -> Result<()> {
+    NALUnit::write_with_preset(
+        writer,
+        &data[2..],
+        WriteStartCodePreset::Four.into(),
+        NAL_UNSPEC62,
+        true,
+    )?;
+
+    Ok(())
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+}
+
+/// Parses an RPU file, or the framed RPU stream on stdin for `-`
+pub fn parse_rpu_input(path: &Path) -> Result<Vec<DoviRpu>> {
+    if is_stdio(path) {
+        RpuStreamReader::new(stdin().lock())?.parse_rpus()
+    } else {
+        Ok(parse_rpu_file(path)?.unwrap_or_default())
+    }
+}
+
+impl SplitRpuWriter {
+    fn write_rpu(&mut self, data: &[u8]) -> Result<()> {
+        let name = format!("frame_{:06}", self.frame);
+
+        let mut writer =
+            BufWriter::new(create_output_file(&self.dir.join(format!("{}.rpu", name)))?);
+        NALUnit::write_with_preset(
+            &mut writer,
+            &data[2..],
+            WriteStartCodePreset::Four.into(),
+            NAL_UNSPEC62,
+            true,
+        )?;
+        writer.flush()?;
+
+        if self.json {
+            let rpu = DoviRpu::parse_unspec62_nalu(data)?;
+            let json_file = create_output_file(&self.dir.join(format!("{}.json", name)))?;
+
+            serde_json::to_writer_pretty(BufWriter::new(json_file), &rpu)?;
+        }
+
+        self.frame += 1;
+
+        Ok(())
+    }
+}
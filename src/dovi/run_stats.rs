@@ -0,0 +1,136 @@
+use std::fmt;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use super::server;
+
+/// Timing of a run of `extract-rpu`, `convert` or `demux`, for the `--stats` report.
+///
+/// The read, convert and write times are measured on the processing thread.
+/// The rest of the wall time is spent parsing the NAL units and reordering the frames.
+pub struct RunStats {
+    start: Instant,
+    read_nanos: Arc<AtomicU64>,
+
+    pub convert: Duration,
+    /// Waiting for the writer threads, and writing the RPUs
+    pub write: Duration,
+
+    pub frames: usize,
+    pub converted_rpus: usize,
+}
+
+/// Input reader measuring the time spent reading
+struct TimedReader {
+    inner: Box<dyn Read>,
+    read_nanos: Arc<AtomicU64>,
+}
+
+#[derive(Serialize)]
+pub struct StatsReport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<u64>,
+    event: &'static str,
+    bytes_read: u64,
+    frames: usize,
+    rpus: usize,
+    converted_rpus: usize,
+    elapsed_secs: f64,
+    mb_per_sec: f64,
+    read_secs: f64,
+    parse_secs: f64,
+    convert_secs: f64,
+    write_secs: f64,
+}
+
+impl RunStats {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            read_nanos: Arc::new(AtomicU64::new(0)),
+            convert: Duration::ZERO,
+            write: Duration::ZERO,
+            frames: 0,
+            converted_rpus: 0,
+        }
+    }
+
+    pub fn timed_reader(&self, inner: Box<dyn Read>) -> Box<dyn Read> {
+        Box::new(TimedReader {
+            inner,
+            read_nanos: Arc::clone(&self.read_nanos),
+        })
+    }
+
+    pub fn report(&self, bytes_read: u64, rpus: usize, writer_time: Duration) -> StatsReport {
+        let elapsed = self.start.elapsed();
+        let read = Duration::from_nanos(self.read_nanos.load(Ordering::Relaxed));
+        let write = self.write + writer_time;
+
+        let parse = elapsed
+            .saturating_sub(read)
+            .saturating_sub(self.convert)
+            .saturating_sub(write);
+
+        let elapsed_secs = elapsed.as_secs_f64();
+        let mb_per_sec = if elapsed_secs > 0.0 {
+            bytes_read as f64 / 1_000_000.0 / elapsed_secs
+        } else {
+            0.0
+        };
+
+        StatsReport {
+            id: server::current_job(),
+            event: "stats",
+            bytes_read,
+            frames: self.frames,
+            rpus,
+            converted_rpus: self.converted_rpus,
+            elapsed_secs,
+            mb_per_sec,
+            read_secs: read.as_secs_f64(),
+            parse_secs: parse.as_secs_f64(),
+            convert_secs: self.convert.as_secs_f64(),
+            write_secs: write.as_secs_f64(),
+        }
+    }
+}
+
+impl Read for TimedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let start = Instant::now();
+        let res = self.inner.read(buf);
+
+        self.read_nanos
+            .fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+
+        res
+    }
+}
+
+impl fmt::Display for StatsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Statistics:")?;
+        writeln!(
+            f,
+            "  {:.2} MB read in {:.2} s, {:.2} MB/s",
+            self.bytes_read as f64 / 1_000_000.0,
+            self.elapsed_secs,
+            self.mb_per_sec
+        )?;
+        writeln!(
+            f,
+            "  Frames: {}, RPUs: {}, converted: {}",
+            self.frames, self.rpus, self.converted_rpus
+        )?;
+        write!(
+            f,
+            "  Time: read {:.2} s, parse {:.2} s, convert {:.2} s, write {:.2} s",
+            self.read_secs, self.parse_secs, self.convert_secs, self.write_secs
+        )
+    }
+}
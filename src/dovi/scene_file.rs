@@ -0,0 +1,330 @@
+use std::fs::read_to_string;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, ensure, Result};
+use serde_json::Value;
+
+use dolby_vision::rpu::extension_metadata::blocks::{ExtMetadataBlock, ExtMetadataBlockLevel1};
+use dolby_vision::utils::pq_to_pq12;
+
+use super::rpu_stream::status_out;
+use super::DoviRpu;
+
+/// Scene cuts imported from an external scene file.
+///
+/// Supported formats:
+///   - Av1an `scenes.json`
+///   - x265 CSV log, from `--csv`, with the luma levels of the frames if logged
+///   - Plain list of the first frame number of every scene
+#[derive(Debug, Default)]
+pub struct SceneCuts {
+    /// First frame of every scene, sorted
+    pub frames: Vec<usize>,
+
+    /// Total number of frames, if specified by the scene file
+    pub frame_count: Option<usize>,
+
+    /// Min, avg and max luma of every frame as 12 bit PQ, from the x265 CSV log.
+    /// Empty when the scene file has no luma levels.
+    pub luma_levels: Vec<Option<[u16; 3]>>,
+}
+
+impl SceneCuts {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let content = read_to_string(path)?;
+        let trimmed = content.trim_start();
+
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+
+        let mut scene_cuts = if trimmed.starts_with('{') {
+            Self::parse_av1an_json(&content)?
+        } else if extension.as_deref() == Some("csv")
+            || trimmed.to_lowercase().starts_with("encode order")
+        {
+            Self::parse_x265_csv(&content)?
+        } else {
+            Self::parse_frame_list(&content)?
+        };
+
+        // The first frame always starts a scene
+        scene_cuts.frames.push(0);
+        scene_cuts.frames.sort_unstable();
+        scene_cuts.frames.dedup();
+
+        if let Some(frame_count) = scene_cuts.frame_count {
+            if let Some(last) = scene_cuts.frames.last() {
+                ensure!(
+                    *last < frame_count,
+                    "Invalid scene file: scene cut at frame {} but only {} frames",
+                    last,
+                    frame_count
+                );
+            }
+        }
+
+        Ok(scene_cuts)
+    }
+
+    fn parse_av1an_json(content: &str) -> Result<Self> {
+        let json: Value = serde_json::from_str(content)?;
+
+        let frame_count = json
+            .get("frames")
+            .and_then(Value::as_u64)
+            .map(|f| f as usize);
+
+        let frames = if let Some(scenes) = json.get("scenes").and_then(Value::as_array) {
+            scenes
+                .iter()
+                .map(|scene| {
+                    scene
+                        .get("start_frame")
+                        .and_then(Value::as_u64)
+                        .map(|f| f as usize)
+                        .ok_or_else(|| anyhow!("Invalid Av1an scene: {}", scene))
+                })
+                .collect::<Result<Vec<_>>>()?
+        } else if let Some(split_scenes) = json.get("split_scenes").and_then(Value::as_array) {
+            // Older Av1an versions only list the scene changes
+            split_scenes
+                .iter()
+                .filter_map(Value::as_u64)
+                .map(|f| f as usize)
+                .collect()
+        } else {
+            bail!("Invalid Av1an scenes file: no scenes list");
+        };
+
+        Ok(Self {
+            frames,
+            frame_count,
+            ..Default::default()
+        })
+    }
+
+    fn parse_x265_csv(content: &str) -> Result<Self> {
+        let mut lines = content.lines();
+
+        let header: Vec<String> = match lines.next() {
+            Some(line) => line.split(',').map(|e| e.trim().to_lowercase()).collect(),
+            None => bail!("Invalid x265 CSV log: empty file"),
+        };
+
+        let poc_idx = header.iter().position(|e| e == "poc");
+        let scenecut_idx = header.iter().position(|e| e == "scenecut");
+
+        let (poc_idx, scenecut_idx) = match (poc_idx, scenecut_idx) {
+            (Some(poc), Some(scenecut)) => (poc, scenecut),
+            _ => bail!("Invalid x265 CSV log: missing POC or Scenecut columns"),
+        };
+
+        // Logged by x265 for the source pictures, at the internal bit depth
+        let luma_idx: Option<Vec<usize>> = ["min luma level", "avg luma level", "max luma level"]
+            .iter()
+            .map(|column| header.iter().position(|e| e == column))
+            .collect();
+
+        let mut frames = Vec::new();
+        let mut frame_count = 0;
+        let mut luma_levels = Vec::new();
+
+        for line in lines {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+
+            // Skip the summary lines
+            let poc = fields.get(poc_idx).and_then(|e| e.parse::<usize>().ok());
+            let scenecut = fields.get(scenecut_idx).and_then(|e| e.parse::<u8>().ok());
+
+            if let (Some(poc), Some(scenecut)) = (poc, scenecut) {
+                frame_count = frame_count.max(poc + 1);
+
+                if scenecut == 1 {
+                    frames.push(poc);
+                }
+
+                if let Some(luma_idx) = &luma_idx {
+                    let levels: Option<Vec<f64>> = luma_idx
+                        .iter()
+                        .map(|i| fields.get(*i).and_then(|e| e.parse::<f64>().ok()))
+                        .collect();
+
+                    if luma_levels.len() <= poc {
+                        luma_levels.resize(poc + 1, None);
+                    }
+
+                    luma_levels[poc] = levels.map(|v| [v[0], v[1], v[2]].map(luma_level_to_pq));
+                }
+            }
+        }
+
+        ensure!(frame_count > 0, "Invalid x265 CSV log: no frames");
+
+        if luma_levels.iter().all(Option::is_none) {
+            luma_levels.clear();
+        } else {
+            luma_levels.resize(frame_count, None);
+        }
+
+        Ok(Self {
+            frames,
+            frame_count: Some(frame_count),
+            luma_levels,
+        })
+    }
+
+    fn parse_frame_list(content: &str) -> Result<Self> {
+        let frames = content
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default())
+            .flat_map(|line| line.split(|c: char| c == ',' || c.is_whitespace()))
+            .filter(|e| !e.is_empty())
+            .map(|e| {
+                e.parse::<usize>()
+                    .map_err(|_| anyhow!("Invalid scene file: frame number '{}'", e))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            frames,
+            frame_count: None,
+            ..Default::default()
+        })
+    }
+
+    /// Sets the scene cut flags, and recomputes the L1 metadata of every scene
+    /// from the min, max and average of the frames within the scene.
+    pub fn apply(&self, rpus: &mut [DoviRpu]) -> Result<()> {
+        if let Some(frame_count) = self.frame_count {
+            ensure!(
+                frame_count == rpus.len(),
+                "mismatched frame count: scene file {}, RPU {}",
+                frame_count,
+                rpus.len()
+            );
+        }
+
+        if let Some(last) = self.frames.last() {
+            ensure!(
+                *last < rpus.len(),
+                "Invalid scene cut at frame {}: only {} RPUs",
+                last,
+                rpus.len()
+            );
+        }
+
+        let ends = self.frames.iter().skip(1).copied().chain([rpus.len()]);
+
+        for (start, end) in self.frames.iter().copied().zip(ends) {
+            let scene = &mut rpus[start..end];
+            let level1 = scene_level1(scene);
+
+            for (i, rpu) in scene.iter_mut().enumerate() {
+                rpu.modified = true;
+
+                if let Some(ref mut vdr_dm_data) = rpu.vdr_dm_data {
+                    vdr_dm_data.set_scene_cut(i == 0);
+
+                    if let Some(level1) = &level1 {
+                        vdr_dm_data
+                            .replace_metadata_block(ExtMetadataBlock::Level1(level1.clone()))?;
+                    }
+                }
+            }
+        }
+
+        writeln!(status_out(), "Applied {} scene cuts", self.frames.len()).ok();
+
+        Ok(())
+    }
+
+    pub fn has_luma_levels(&self) -> bool {
+        !self.luma_levels.is_empty()
+    }
+
+    /// Sets the L1 metadata of every scene from the luma levels of its frames.
+    /// Must be called after `apply`, which validates the frame count.
+    pub fn apply_luma_levels(&self, rpus: &mut [DoviRpu]) -> Result<()> {
+        ensure!(
+            self.luma_levels.len() == rpus.len(),
+            "mismatched frame count: luma levels {}, RPU {}",
+            self.luma_levels.len(),
+            rpus.len()
+        );
+
+        let ends = self.frames.iter().skip(1).copied().chain([rpus.len()]);
+
+        for (start, end) in self.frames.iter().copied().zip(ends) {
+            let levels: Vec<[u16; 3]> = self.luma_levels[start..end]
+                .iter()
+                .flatten()
+                .copied()
+                .collect();
+
+            if levels.is_empty() {
+                continue;
+            }
+
+            let min_pq = levels.iter().map(|l| l[0]).min().unwrap_or_default();
+            let max_pq = levels.iter().map(|l| l[2]).max().unwrap_or_default();
+
+            let avg_sum: usize = levels.iter().map(|l| l[1] as usize).sum();
+            let avg_pq = (avg_sum as f64 / levels.len() as f64).round() as u16;
+
+            let level1 = ExtMetadataBlockLevel1::from_stats(min_pq, max_pq, avg_pq);
+
+            for rpu in &mut rpus[start..end] {
+                if let Some(ref mut vdr_dm_data) = rpu.vdr_dm_data {
+                    vdr_dm_data.replace_metadata_block(ExtMetadataBlock::Level1(level1.clone()))?;
+                }
+            }
+        }
+
+        writeln!(
+            status_out(),
+            "Set the L1 metadata of {} scenes from the x265 luma levels",
+            self.frames.len()
+        )
+        .ok();
+
+        Ok(())
+    }
+}
+
+/// 10 bit limited range PQ code value to 12 bit PQ.
+///
+/// Only a hint of the brightness: the luma is lower than the max RGB component of saturated colours.
+pub fn luma_level_to_pq(level: f64) -> u16 {
+    let normalized = (level - 64.0) / (940.0 - 64.0);
+    pq_to_pq12(normalized.clamp(0.0, 1.0))
+}
+
+fn scene_level1(scene: &[DoviRpu]) -> Option<ExtMetadataBlockLevel1> {
+    let blocks: Vec<&ExtMetadataBlockLevel1> = scene
+        .iter()
+        .filter_map(|rpu| rpu.vdr_dm_data.as_ref())
+        .filter_map(|vdr_dm_data| match vdr_dm_data.get_block(1) {
+            Some(ExtMetadataBlock::Level1(block)) => Some(block),
+            _ => None,
+        })
+        .collect();
+
+    if blocks.is_empty() {
+        return None;
+    }
+
+    let min_pq = blocks.iter().map(|b| b.min_pq).min().unwrap_or_default();
+    let max_pq = blocks.iter().map(|b| b.max_pq).max().unwrap_or_default();
+
+    let avg_sum: usize = blocks.iter().map(|b| b.avg_pq as usize).sum();
+    let avg_pq = (avg_sum as f64 / blocks.len() as f64).round() as u16;
+
+    Some(ExtMetadataBlockLevel1 {
+        min_pq,
+        max_pq,
+        avg_pq,
+    })
+}
@@ -0,0 +1,144 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use dolby_vision::rpu::processor::{RpuProcessor, RpuProcessorChain};
+
+use super::DoviRpu;
+
+/// External command transforming the RPUs, as JSON lines.
+///
+/// Every RPU is written to the stdin of the command as a single line of JSON,
+/// and replaced by the RPU read back from the next line of its stdout.
+pub struct ScriptProcessor {
+    command: String,
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+
+    processed: usize,
+    line: String,
+}
+
+/// Processors shared by the conversion of the RPUs, which can run on multiple threads.
+///
+/// The RPUs are converted in parallel batches, so the processors are not called in stream order.
+#[derive(Debug, Clone)]
+pub struct SharedRpuProcessor(Arc<Mutex<RpuProcessorChain>>);
+
+impl ScriptProcessor {
+    pub fn spawn(command: &str) -> Result<Self> {
+        let mut child = shell_command(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("--script: failed to run `{}`", command))?;
+
+        let stdin = child.stdin.take();
+        let stdout = child
+            .stdout
+            .take()
+            .map(BufReader::new)
+            .ok_or_else(|| anyhow!("--script: no stdout for `{}`", command))?;
+
+        Ok(Self {
+            command: command.to_string(),
+            child,
+            stdin,
+            stdout,
+            processed: 0,
+            line: String::new(),
+        })
+    }
+}
+
+impl RpuProcessor for ScriptProcessor {
+    fn process_rpu(&mut self, rpu: &mut DoviRpu) -> Result<()> {
+        let stdin = match self.stdin.as_mut() {
+            Some(stdin) => stdin,
+            None => bail!("--script: `{}` is closed", self.command),
+        };
+
+        let mut json = serde_json::to_vec(rpu)?;
+        json.push(b'\n');
+
+        stdin
+            .write_all(&json)
+            .and_then(|_| stdin.flush())
+            .with_context(|| {
+                format!(
+                    "--script: `{}` stopped reading, after {} RPUs",
+                    self.command, self.processed
+                )
+            })?;
+
+        self.line.clear();
+        if self.stdout.read_line(&mut self.line)? == 0 {
+            bail!(
+                "--script: `{}` exited, after {} RPUs",
+                self.command,
+                self.processed
+            );
+        }
+
+        *rpu = serde_json::from_str(&self.line).with_context(|| {
+            format!(
+                "--script: `{}` returned an invalid RPU, after {} RPUs",
+                self.command, self.processed
+            )
+        })?;
+
+        self.processed += 1;
+
+        Ok(())
+    }
+}
+
+impl Drop for ScriptProcessor {
+    fn drop(&mut self) {
+        // Closing stdin ends the script
+        self.stdin.take();
+        self.child.wait().ok();
+    }
+}
+
+impl SharedRpuProcessor {
+    /// Chain of the scripts, in the order of the arguments
+    pub fn from_scripts(scripts: &[String]) -> Result<Option<Self>> {
+        if scripts.is_empty() {
+            return Ok(None);
+        }
+
+        let mut chain = RpuProcessorChain::new();
+        for script in scripts {
+            chain.push(ScriptProcessor::spawn(script)?);
+        }
+
+        Ok(Some(Self(Arc::new(Mutex::new(chain)))))
+    }
+
+    pub fn process_rpu(&self, rpu: &mut DoviRpu) -> Result<()> {
+        let mut chain = self
+            .0
+            .lock()
+            .map_err(|_| anyhow!("RPU processor poisoned by a previous error"))?;
+
+        chain.process_rpu(rpu)
+    }
+}
+
+#[cfg(windows)]
+fn shell_command(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.args(["/C", command]);
+    cmd
+}
+
+#[cfg(not(windows))]
+fn shell_command(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", command]);
+    cmd
+}
@@ -0,0 +1,192 @@
+use std::borrow::Cow;
+
+use anyhow::{bail, Result};
+
+use dolby_vision::utils::{
+    add_start_code_emulation_prevention_3_byte, clear_start_code_emulation_prevention_3_byte,
+};
+use hevc_parser::hevc::{NAL_SEI_PREFIX, NAL_SEI_SUFFIX};
+
+const USER_DATA_REGISTERED_ITU_T_35: u64 = 4;
+const USER_DATA_UNREGISTERED: u64 = 5;
+const MASTERING_DISPLAY_COLOUR_VOLUME: u64 = 137;
+const CONTENT_LIGHT_LEVEL_INFO: u64 = 144;
+
+const ST2094_40_T35_HEADER: &[u8] = &[0xB5, 0x00, 0x3C, 0x00, 0x01, 0x04, 0x01];
+
+#[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeiPayload {
+    /// ST2094-40 dynamic metadata
+    Hdr10plus,
+    /// Mastering display colour volume
+    Mdcv,
+    /// Content light level information
+    Cll,
+    /// User data unregistered, usually encoder settings
+    UserDataUnregistered,
+}
+
+/// Removes the selected SEI messages from the SEI NAL units
+#[derive(Debug, Clone, Default)]
+pub struct SeiFilter {
+    drop: Vec<SeiPayload>,
+}
+
+struct SeiMessage {
+    payload_type: u64,
+    start: usize,
+    payload_start: usize,
+    end: usize,
+}
+
+impl SeiFilter {
+    pub fn new(drop: Vec<SeiPayload>) -> Self {
+        Self { drop }
+    }
+
+    /// Also drops the SEI messages of this payload type
+    pub fn add(&mut self, payload: SeiPayload) {
+        if !self.drop.contains(&payload) {
+            self.drop.push(payload);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.drop.is_empty()
+    }
+
+    /// Filters the SEI messages from a NAL unit, including the NAL header.
+    ///
+    /// Returns `None` when every message of the NAL unit is dropped,
+    /// otherwise the NAL unit data, rewritten if some messages were dropped.
+    pub fn filter_nal<'a>(&self, nal_type: u8, data: &'a [u8]) -> Result<Option<Cow<'a, [u8]>>> {
+        if self.is_empty() || !matches!(nal_type, NAL_SEI_PREFIX | NAL_SEI_SUFFIX) {
+            return Ok(Some(Cow::Borrowed(data)));
+        }
+
+        if data.len() < 3 {
+            bail!("Invalid SEI NAL unit length: {}", data.len());
+        }
+
+        let rbsp = sei_rbsp(data);
+        let messages = parse_sei_messages(&rbsp)?;
+
+        let kept: Vec<&SeiMessage> = messages
+            .iter()
+            .filter(|msg| !self.should_drop(msg, &rbsp))
+            .collect();
+
+        if kept.len() == messages.len() {
+            Ok(Some(Cow::Borrowed(data)))
+        } else if kept.is_empty() {
+            Ok(None)
+        } else {
+            let mut new_data = Vec::with_capacity(data.len());
+            new_data.extend_from_slice(&data[..2]);
+
+            for msg in kept {
+                new_data.extend_from_slice(&rbsp[msg.start..msg.end]);
+            }
+
+            // rbsp_trailing_bits()
+            new_data.push(0x80);
+            add_start_code_emulation_prevention_3_byte(&mut new_data);
+
+            Ok(Some(Cow::Owned(new_data)))
+        }
+    }
+
+    fn should_drop(&self, msg: &SeiMessage, rbsp: &[u8]) -> bool {
+        self.drop.iter().any(|kind| match kind {
+            SeiPayload::Hdr10plus => {
+                msg.payload_type == USER_DATA_REGISTERED_ITU_T_35
+                    && rbsp[msg.payload_start..msg.end].starts_with(ST2094_40_T35_HEADER)
+            }
+            SeiPayload::Mdcv => msg.payload_type == MASTERING_DISPLAY_COLOUR_VOLUME,
+            SeiPayload::Cll => msg.payload_type == CONTENT_LIGHT_LEVEL_INFO,
+            SeiPayload::UserDataUnregistered => msg.payload_type == USER_DATA_UNREGISTERED,
+        })
+    }
+}
+
+/// Payload type and payload bytes of the SEI messages of a NAL unit, including the NAL header
+pub fn sei_payloads(data: &[u8]) -> Result<Vec<(u64, Vec<u8>)>> {
+    if data.len() < 3 {
+        bail!("Invalid SEI NAL unit length: {}", data.len());
+    }
+
+    let rbsp = sei_rbsp(data);
+    let messages = parse_sei_messages(&rbsp)?;
+
+    Ok(messages
+        .iter()
+        .map(|msg| (msg.payload_type, rbsp[msg.payload_start..msg.end].to_vec()))
+        .collect())
+}
+
+/// Unescaped SEI payload of a NAL unit, without the NAL header
+fn sei_rbsp(data: &[u8]) -> Vec<u8> {
+    let mut rbsp = clear_start_code_emulation_prevention_3_byte(data);
+    rbsp.drain(..2);
+
+    rbsp
+}
+
+fn parse_sei_messages(rbsp: &[u8]) -> Result<Vec<SeiMessage>> {
+    let mut messages = Vec::new();
+    let mut pos = 0;
+
+    // more_rbsp_data(): the messages end at rbsp_trailing_bits(), the last non-zero byte.
+    // A 0x80 byte elsewhere is message data, such as the payload type 128.
+    let end = match rbsp.iter().rposition(|&byte| byte != 0) {
+        Some(last) if rbsp[last] == 0x80 => last,
+        _ => rbsp.len(),
+    };
+
+    while pos < end {
+        let start = pos;
+
+        let payload_type = read_sei_value(rbsp, &mut pos)?;
+        let payload_size = read_sei_value(rbsp, &mut pos)? as usize;
+
+        let payload_start = pos;
+        let end = payload_start + payload_size;
+
+        if end > rbsp.len() {
+            bail!(
+                "Invalid SEI message: payload size {} is larger than the NAL unit",
+                payload_size
+            );
+        }
+
+        messages.push(SeiMessage {
+            payload_type,
+            start,
+            payload_start,
+            end,
+        });
+
+        pos = end;
+    }
+
+    Ok(messages)
+}
+
+/// Reads a SEI payload type or size, coded as a sequence of 0xFF bytes
+fn read_sei_value(rbsp: &[u8], pos: &mut usize) -> Result<u64> {
+    let mut value = 0;
+
+    loop {
+        let byte = match rbsp.get(*pos) {
+            Some(byte) => *byte,
+            None => bail!("Invalid SEI message: unexpected end of NAL unit"),
+        };
+        *pos += 1;
+
+        value += byte as u64;
+
+        if byte != 0xFF {
+            return Ok(value);
+        }
+    }
+}
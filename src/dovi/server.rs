@@ -0,0 +1,260 @@
+use std::io::{stdin, stdout, BufRead, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread::{self, JoinHandle};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::interrupt::{interrupt, is_interrupted, reset_interrupt};
+use super::rpu_stream;
+
+/// Subcommands that can be run as jobs
+const JOB_COMMANDS: &[&str] = &["extract-rpu", "convert", "info"];
+
+static SERVING: AtomicBool = AtomicBool::new(false);
+static JOB_RUNNING: AtomicBool = AtomicBool::new(false);
+static JOB_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Runs a job from its command line arguments, returning the output of `info`
+pub type JobRunner = fn(Vec<String>) -> Result<Option<String>>;
+
+/// Resident mode for the GUI frontends, with one JSON request per line on stdin.
+///
+/// Requests:
+///   - `{"id": 1, "command": "extract-rpu", "args": ["-i", "video.hevc"], "options": ["-m", "2"]}`
+///     `args` are the arguments of the subcommand, `options` the global options before it.
+///   - `{"id": 1, "command": "cancel"}` stops the running job, finalizing its outputs.
+///   - `{"command": "quit"}` or the end of stdin cancels the running job and exits.
+///
+/// Only one job runs at a time. The replies are JSON lines on stdout, with the `id` of the job:
+/// `started`, the `progress` events, then `completed`, `failed` or `cancelled`.
+/// The status messages of the jobs are written on stderr.
+pub struct Server {
+    runner: JobRunner,
+    job: Option<JoinHandle<()>>,
+}
+
+#[derive(Deserialize)]
+struct Request {
+    id: Option<u64>,
+    command: String,
+
+    #[serde(default)]
+    args: Vec<String>,
+
+    #[serde(default)]
+    options: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Reply {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<u64>,
+    event: &'static str,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+
+    /// Output of the `info` jobs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<String>,
+}
+
+/// Ends the job when dropped, or on panic
+struct JobGuard {
+    id: u64,
+}
+
+impl Server {
+    pub fn serve(runner: JobRunner) -> Result<()> {
+        SERVING.store(true, Ordering::SeqCst);
+        rpu_stream::use_stdout();
+
+        let mut server = Server { runner, job: None };
+
+        write_reply(Reply::new(None, "ready"));
+
+        let stdin = stdin();
+
+        for line in stdin.lock().lines() {
+            let line = line?;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            server.join_finished_job();
+
+            let request: Request = match serde_json::from_str(&line) {
+                Ok(request) => request,
+                Err(e) => {
+                    write_reply(Reply::error(None, format!("Invalid request: {}", e)));
+                    continue;
+                }
+            };
+
+            match request.command.as_str() {
+                "quit" => break,
+                "cancel" => server.cancel(request.id),
+                command if JOB_COMMANDS.contains(&command) => server.start_job(request)?,
+                command => write_reply(Reply::error(
+                    request.id,
+                    format!("Unknown command '{}'", command),
+                )),
+            }
+        }
+
+        if is_job_running() {
+            interrupt();
+        }
+
+        if let Some(job) = server.job.take() {
+            job.join().ok();
+        }
+
+        Ok(())
+    }
+
+    fn start_job(&mut self, request: Request) -> Result<()> {
+        let id = match request.id {
+            Some(id) => id,
+            None => {
+                write_reply(Reply::error(None, String::from("Missing job id")));
+                return Ok(());
+            }
+        };
+
+        if is_job_running() {
+            write_reply(Reply::error(
+                Some(id),
+                format!("Job {} is still running", JOB_ID.load(Ordering::SeqCst)),
+            ));
+            return Ok(());
+        }
+
+        let mut args = vec![String::from(env!("CARGO_PKG_NAME"))];
+        args.extend(request.options);
+        args.push(request.command);
+        args.extend(request.args);
+
+        reset_interrupt();
+        JOB_ID.store(id, Ordering::SeqCst);
+        JOB_RUNNING.store(true, Ordering::SeqCst);
+
+        write_reply(Reply::new(Some(id), "started"));
+
+        let runner = self.runner;
+        let job = thread::Builder::new()
+            .name(format!("job {}", id))
+            .spawn(move || {
+                let guard = JobGuard { id };
+
+                let reply = match runner(args) {
+                    Ok(output) => Reply {
+                        output,
+                        ..Reply::new(Some(id), "completed")
+                    },
+                    Err(e) if is_interrupted() => Reply {
+                        message: Some(e.to_string()),
+                        ..Reply::new(Some(id), "cancelled")
+                    },
+                    Err(e) => Reply::error(Some(id), e.to_string()),
+                };
+
+                // A new job can be started once the client gets the reply
+                drop(guard);
+                write_reply(reply);
+            });
+
+        match job {
+            Ok(job) => self.job = Some(job),
+            Err(e) => {
+                JOB_RUNNING.store(false, Ordering::SeqCst);
+                return Err(e.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn cancel(&self, id: Option<u64>) {
+        let running_id = JOB_ID.load(Ordering::SeqCst);
+
+        if !is_job_running() {
+            write_reply(Reply::error(id, String::from("No job running")));
+        } else if id.is_some_and(|id| id != running_id) {
+            write_reply(Reply::error(
+                id,
+                format!("Job {} is not running", id.unwrap_or_default()),
+            ));
+        } else {
+            interrupt();
+        }
+    }
+
+    fn join_finished_job(&mut self) {
+        if !is_job_running() {
+            if let Some(job) = self.job.take() {
+                job.join().ok();
+            }
+        }
+    }
+}
+
+impl Reply {
+    fn new(id: Option<u64>, event: &'static str) -> Self {
+        Self {
+            id,
+            event,
+            message: None,
+            output: None,
+        }
+    }
+
+    fn error(id: Option<u64>, message: String) -> Self {
+        Self {
+            message: Some(message),
+            ..Self::new(id, "failed")
+        }
+    }
+}
+
+impl Drop for JobGuard {
+    fn drop(&mut self) {
+        JOB_RUNNING.store(false, Ordering::SeqCst);
+
+        if thread::panicking() {
+            write_reply(Reply::error(Some(self.id), String::from("Job panicked")));
+        }
+    }
+}
+
+fn is_job_running() -> bool {
+    JOB_RUNNING.load(Ordering::SeqCst)
+}
+
+/// ID of the running job, when serving
+pub fn current_job() -> Option<u64> {
+    if SERVING.load(Ordering::SeqCst) && is_job_running() {
+        Some(JOB_ID.load(Ordering::SeqCst))
+    } else {
+        None
+    }
+}
+
+/// Writes a JSON line on stdout, in one write so that the lines of the threads don't mix
+pub fn write_json_line<T: Serialize>(value: &T) {
+    if let Ok(mut line) = serde_json::to_string(value) {
+        line.push('\n');
+
+        let stdout = stdout();
+        let mut handle = stdout.lock();
+
+        handle.write_all(line.as_bytes()).ok();
+        handle.flush().ok();
+    }
+}
+
+fn write_reply(reply: Reply) {
+    write_json_line(&reply);
+}
@@ -0,0 +1,144 @@
+use std::fs::create_dir_all;
+use std::io::{stdout, Write};
+use std::path::PathBuf;
+
+use anyhow::{bail, ensure, Result};
+
+use dolby_vision::rpu::generate::GenerateConfig;
+use utilities_dovi::parse_rpu_file;
+
+use crate::commands::SplitArgs;
+
+use super::chapters::Chapters;
+use super::timecode::{parse_fps, FrameClock};
+use super::{input_from_either, set_scene_cut, write_rpu_file, DoviRpu};
+
+pub struct Splitter {
+    input: PathBuf,
+    mode: SplitMode,
+    output_dir: PathBuf,
+}
+
+enum SplitMode {
+    Frames(Vec<usize>),
+    Chapters { path: PathBuf, fps: f64 },
+    Scenes(usize),
+}
+
+impl Splitter {
+    pub fn split(args: SplitArgs) -> Result<()> {
+        let SplitArgs {
+            input,
+            input_pos,
+            frames,
+            chapters,
+            fps,
+            scenes,
+            output_dir,
+        } = args;
+
+        let input = input_from_either("split", input, input_pos)?;
+
+        let mode = if let Some(path) = chapters {
+            let fps = match fps {
+                Some(fps) => parse_fps(&fps)?,
+                None => bail!("split: --fps is required to split by chapters"),
+            };
+
+            SplitMode::Chapters { path, fps }
+        } else if let Some(scenes) = scenes {
+            ensure!(scenes > 0, "split: invalid number of scenes per part");
+
+            SplitMode::Scenes(scenes)
+        } else {
+            SplitMode::Frames(frames)
+        };
+
+        let splitter = Splitter {
+            input,
+            mode,
+            output_dir: output_dir.unwrap_or_default(),
+        };
+
+        splitter.execute()
+    }
+
+    fn execute(&self) -> Result<()> {
+        println!("Parsing RPU file...");
+        stdout().flush().ok();
+
+        let rpus = match parse_rpu_file(&self.input)? {
+            Some(rpus) if !rpus.is_empty() => rpus,
+            _ => bail!("No RPU found in file {}", self.input.display()),
+        };
+
+        let mut starts = match &self.mode {
+            SplitMode::Frames(frames) => frames.clone(),
+            SplitMode::Chapters { path, fps } => {
+                Chapters::from_path(path)?.start_frames(&FrameClock::Fps(*fps))?
+            }
+            SplitMode::Scenes(count) => scene_starts(&rpus).step_by(*count).collect(),
+        };
+
+        // The first part always starts at the first frame
+        starts.push(0);
+        starts.sort_unstable();
+        starts.dedup();
+
+        if let Some(last) = starts.last() {
+            ensure!(
+                *last < rpus.len(),
+                "Invalid split at frame {}: only {} RPUs",
+                last,
+                rpus.len()
+            );
+        }
+
+        if !self.output_dir.as_os_str().is_empty() {
+            create_dir_all(&self.output_dir)?;
+        }
+
+        let stem = self.input.file_stem().unwrap().to_str().unwrap();
+
+        let ends = starts.iter().skip(1).copied().chain([rpus.len()]);
+        let mut rpus = rpus;
+
+        for (i, (start, end)) in starts.iter().copied().zip(ends).enumerate() {
+            let part = &mut rpus[start..end];
+
+            // Every part must start with a scene cut
+            set_scene_cut(&mut part[0]);
+
+            let out_path = self.output_dir.join(format!("{}_{:03}.bin", stem, i + 1));
+
+            println!(
+                "Part {}: frames {}-{}, {}",
+                i + 1,
+                start,
+                end - 1,
+                out_path.display()
+            );
+
+            let data = GenerateConfig::encode_rpus(part);
+            write_rpu_file(&out_path, data)?;
+        }
+
+        println!("Split into {} parts", starts.len());
+
+        Ok(())
+    }
+}
+
+/// First frame of every scene, from the scene cut flags
+pub fn scene_starts(rpus: &[DoviRpu]) -> impl Iterator<Item = usize> + '_ {
+    rpus.iter()
+        .enumerate()
+        .filter(|(i, rpu)| {
+            *i == 0
+                || rpu
+                    .vdr_dm_data
+                    .as_ref()
+                    .is_some_and(|vdr_dm_data| vdr_dm_data.scene_refresh_flag == 1)
+        })
+        .map(|(i, _)| i)
+}
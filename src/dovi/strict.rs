@@ -0,0 +1,69 @@
+use anyhow::{bail, Result};
+
+use super::codec::Codec;
+
+/// Validates every RPU with `--strict`, failing on the first questionable RPU.
+///
+/// On top of the spec issues of each RPU, the stream must keep the profile of its first RPU.
+pub struct StrictChecker {
+    codec: Codec,
+
+    /// Profile and subprofile of the first RPU
+    first_profile: Option<(u8, Option<String>)>,
+}
+
+impl StrictChecker {
+    pub fn new(codec: Codec) -> Self {
+        Self {
+            codec,
+            first_profile: None,
+        }
+    }
+
+    /// Checks the RPU NAL unit of a decoded frame, including the NAL header
+    pub fn check(&mut self, frame: u64, data: &[u8]) -> Result<()> {
+        let rpu = match self.codec.parse_rpu_nalu(data) {
+            Ok(rpu) => rpu,
+            Err(e) => bail!(
+                "Strict validation failed for decoded frame {}: {}",
+                frame,
+                e
+            ),
+        };
+
+        let mut issues = rpu
+            .spec_issues()?
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+
+        let profile = (rpu.dovi_profile, rpu.subprofile);
+
+        match &self.first_profile {
+            Some(first_profile) if first_profile != &profile => issues.push(format!(
+                "dovi_profile (derived from the header): profile {}, the first RPU is profile {}",
+                profile_name(&profile),
+                profile_name(first_profile)
+            )),
+            Some(_) => (),
+            None => self.first_profile = Some(profile),
+        }
+
+        if !issues.is_empty() {
+            bail!(
+                "Strict validation failed for decoded frame {}:\n  {}",
+                frame,
+                issues.join("\n  ")
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn profile_name((profile, subprofile): &(u8, Option<String>)) -> String {
+    match subprofile {
+        Some(subprofile) => format!("{} {}", profile, subprofile),
+        None => profile.to_string(),
+    }
+}
@@ -0,0 +1,191 @@
+use std::fs::File;
+use std::io::{self, ErrorKind, Write};
+use std::mem;
+use std::sync::mpsc::{channel, sync_channel, Receiver, Sender, SyncSender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Buffers in use: one filled by the parser, one written by the thread
+const BUFFER_COUNT: usize = 2;
+
+enum Message {
+    Write(Vec<u8>),
+    Flush,
+}
+
+enum Reply {
+    /// Written buffer, cleared to be filled again
+    Buffer(Vec<u8>),
+    Flushed,
+    Error(io::Error),
+}
+
+/// Buffered file writer, writing from a dedicated thread.
+///
+/// The parsing thread fills a buffer while the previous one is written to disk,
+/// so parsing does not wait on slow destinations unless both buffers are full.
+pub struct ThreadedWriter {
+    buffer: Vec<u8>,
+    capacity: usize,
+
+    /// Buffers returned by the thread, ready to be filled
+    free_buffers: Vec<Vec<u8>>,
+
+    sender: Option<SyncSender<Message>>,
+    replies: Receiver<Reply>,
+    handle: Option<JoinHandle<()>>,
+
+    /// Time spent waiting for the thread
+    wait_time: Duration,
+}
+
+impl ThreadedWriter {
+    pub fn with_capacity(capacity: usize, file: File) -> io::Result<Self> {
+        let capacity = capacity.max(1);
+
+        let (sender, messages) = sync_channel(BUFFER_COUNT);
+        let (reply_sender, replies) = channel();
+
+        let handle = thread::Builder::new()
+            .name(String::from("writer"))
+            .spawn(move || write_messages(file, messages, reply_sender))?;
+
+        let free_buffers = (1..BUFFER_COUNT)
+            .map(|_| Vec::with_capacity(capacity))
+            .collect();
+
+        Ok(Self {
+            buffer: Vec::with_capacity(capacity),
+            capacity,
+            free_buffers,
+            sender: Some(sender),
+            replies,
+            handle: Some(handle),
+            wait_time: Duration::ZERO,
+        })
+    }
+
+    /// Time the writing thread waited for the disk, with both buffers full
+    pub fn wait_time(&self) -> Duration {
+        self.wait_time
+    }
+
+    fn send(&mut self, message: Message) -> io::Result<()> {
+        let sent = match &self.sender {
+            Some(sender) => sender.send(message).is_ok(),
+            None => false,
+        };
+
+        if sent {
+            Ok(())
+        } else {
+            Err(self.stopped_error())
+        }
+    }
+
+    /// Error of the stopped thread, or a generic error if it was already reported
+    fn stopped_error(&mut self) -> io::Error {
+        while let Ok(reply) = self.replies.try_recv() {
+            if let Reply::Error(e) = reply {
+                return e;
+            }
+        }
+
+        io::Error::new(ErrorKind::BrokenPipe, "output writer thread stopped")
+    }
+
+    /// Waits for the next reply of the thread
+    fn receive(&mut self) -> io::Result<Reply> {
+        match self.replies.recv() {
+            Ok(Reply::Error(e)) => Err(e),
+            Ok(reply) => Ok(reply),
+            Err(_) => Err(self.stopped_error()),
+        }
+    }
+
+    fn send_buffer(&mut self) -> io::Result<()> {
+        let start = Instant::now();
+
+        while self.free_buffers.is_empty() {
+            if let Reply::Buffer(buffer) = self.receive()? {
+                self.free_buffers.push(buffer);
+            }
+        }
+
+        self.wait_time += start.elapsed();
+
+        let next = self.free_buffers.pop().unwrap_or_default();
+        let buffer = mem::replace(&mut self.buffer, next);
+
+        self.send(Message::Write(buffer))
+    }
+}
+
+impl Write for ThreadedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+
+        if self.buffer.len() >= self.capacity {
+            self.send_buffer()?;
+        }
+
+        Ok(buf.len())
+    }
+
+    /// Returns once all the data is written to the file
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.buffer.is_empty() {
+            self.send_buffer()?;
+        }
+
+        self.send(Message::Flush)?;
+
+        let start = Instant::now();
+
+        loop {
+            match self.receive()? {
+                Reply::Buffer(buffer) => self.free_buffers.push(buffer),
+                Reply::Flushed => break,
+                Reply::Error(e) => return Err(e),
+            }
+        }
+
+        self.wait_time += start.elapsed();
+
+        Ok(())
+    }
+}
+
+impl Drop for ThreadedWriter {
+    fn drop(&mut self) {
+        // Errors are ignored, as with `BufWriter`
+        if !self.buffer.is_empty() {
+            self.send_buffer().ok();
+        }
+
+        self.sender.take();
+
+        if let Some(handle) = self.handle.take() {
+            handle.join().ok();
+        }
+    }
+}
+
+fn write_messages(mut file: File, messages: Receiver<Message>, replies: Sender<Reply>) {
+    for message in messages {
+        let result = match message {
+            Message::Write(mut buffer) => file.write_all(&buffer).map(|_| {
+                buffer.clear();
+                Reply::Buffer(buffer)
+            }),
+            Message::Flush => file.flush().map(|_| Reply::Flushed),
+        };
+
+        let failed = result.is_err();
+        let reply = result.unwrap_or_else(Reply::Error);
+
+        if replies.send(reply).is_err() || failed {
+            break;
+        }
+    }
+}
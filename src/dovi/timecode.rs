@@ -0,0 +1,375 @@
+use std::path::Path;
+
+use anyhow::{bail, ensure, Result};
+use serde_json::Value;
+
+use super::chapters::{parse_chapter_ref, Chapters};
+use super::timestamps::FrameTimestamps;
+
+/// Parses a frame rate, either as a decimal number or a fraction
+pub fn parse_fps(fps: &str) -> Result<f64> {
+    let value = if let Some((num, den)) = fps.split_once('/') {
+        num.trim().parse::<f64>()? / den.trim().parse::<f64>()?
+    } else {
+        fps.trim().parse::<f64>()?
+    };
+
+    ensure!(
+        value.is_finite() && value > 0.0,
+        "Invalid frame rate: {}",
+        fps
+    );
+
+    Ok(value)
+}
+
+/// Parses a `HH:MM:SS.mmm` timestamp, in seconds
+pub fn parse_timestamp(value: &str) -> Option<f64> {
+    let mut parts = value.split(':');
+
+    let hours = parts.next()?.parse::<u64>().ok()?;
+    let minutes = parts.next()?.parse::<u64>().ok()?;
+    let seconds = parts.next()?.parse::<f64>().ok()?;
+
+    if parts.next().is_some() {
+        return None;
+    }
+
+    Some((hours * 3600 + minutes * 60) as f64 + seconds)
+}
+
+/// Formats seconds as a `HH:MM:SS.mmm` timestamp
+pub fn format_timestamp(seconds: f64) -> String {
+    let millis = (seconds * 1000.0).round() as u64;
+
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// Timing of the frames of a video, to convert times to frame numbers
+#[derive(Debug, Clone)]
+pub enum FrameClock {
+    /// Constant frame rate
+    Fps(f64),
+    /// Variable frame rate, with the timestamp of every frame
+    Timestamps(FrameTimestamps),
+}
+
+impl FrameClock {
+    /// Frame starting the nearest to the time
+    pub fn frame_at(&self, seconds: f64) -> Result<usize> {
+        match self {
+            Self::Fps(fps) => Ok((seconds * fps).round() as usize),
+            Self::Timestamps(timestamps) => timestamps.frame_at(seconds),
+        }
+    }
+
+    /// Number of frames shown for the duration from the start of a frame.
+    /// With variable frame rate, it depends on the frames where the duration starts.
+    pub fn duration_frames(&self, start: usize, seconds: f64) -> Result<usize> {
+        match self {
+            Self::Fps(fps) => Ok((seconds * fps).round() as usize),
+            Self::Timestamps(timestamps) => {
+                let end = timestamps.frame_at(timestamps.time_of(start)? + seconds)?;
+
+                Ok(end.saturating_sub(start))
+            }
+        }
+    }
+}
+
+/// Converts the `HH:MM:SS.mmm` timecodes and chapter references of edit and generate configs to frame numbers.
+///
+/// The frames are timed by the frame rate or the timestamps file given,
+/// or else by the `fps` value or the `timestamps` path of the config.
+/// The chapters file is the one given, or the `chapters` path of the config.
+/// Frame numbers are left as is, so configs can mix both.
+///
+/// Durations in time, such as the duration of a generator shot, are counted from the frame where they start,
+/// so they match the frames of a variable frame rate video.
+pub struct TimecodeResolver {
+    clock: Option<FrameClock>,
+    chapters: Option<Chapters>,
+}
+
+impl TimecodeResolver {
+    pub fn new(
+        fps: Option<f64>,
+        timestamps: Option<&Path>,
+        chapters: Option<&Path>,
+        config: &Value,
+    ) -> Result<Self> {
+        let clock = match (fps, timestamps) {
+            (Some(_), Some(_)) => bail!("The frame rate and the timestamps file are exclusive"),
+            (Some(fps), None) => Some(FrameClock::Fps(fps)),
+            (None, Some(path)) => Some(FrameClock::Timestamps(FrameTimestamps::from_path(path)?)),
+            (None, None) => match (config.get("fps"), config.get("timestamps")) {
+                (Some(_), Some(_)) => bail!("fps and timestamps are exclusive in the config"),
+                (Some(Value::String(fps)), None) => Some(FrameClock::Fps(parse_fps(fps)?)),
+                (Some(Value::Number(fps)), None) => {
+                    Some(FrameClock::Fps(parse_fps(&fps.to_string())?))
+                }
+                (Some(value), None) => bail!("Invalid frame rate: {}", value),
+                (None, Some(Value::String(path))) => Some(FrameClock::Timestamps(
+                    FrameTimestamps::from_path(Path::new(path))?,
+                )),
+                (None, Some(value)) => bail!("Invalid timestamps file: {}", value),
+                (None, None) => None,
+            },
+        };
+
+        let chapters = match chapters {
+            Some(chapters) => Some(Chapters::from_path(chapters)?),
+            None => match config.get("chapters") {
+                Some(Value::String(path)) => Some(Chapters::from_path(Path::new(path))?),
+                Some(value) => bail!("Invalid chapters file: {}", value),
+                None => None,
+            },
+        };
+
+        Ok(Self { clock, chapters })
+    }
+
+    /// Frame number of a timecode, of the start of a chapter, or of a frame number string
+    pub fn frame(&self, value: &str) -> Result<usize> {
+        let value = value.trim();
+
+        if let Some((chapters, number)) = self.chapter(value)? {
+            return chapters.start_frame(number, self.clock_for(value)?);
+        }
+
+        if !value.contains(':') {
+            return Ok(value.parse()?);
+        }
+
+        let seconds = Self::timecode_seconds(value)?;
+
+        self.clock_for(&format!("Timecode {}", value))?
+            .frame_at(seconds)
+    }
+
+    fn timecode_seconds(value: &str) -> Result<f64> {
+        match parse_timestamp(value) {
+            Some(seconds) => Ok(seconds),
+            None => bail!("Invalid timecode {}, expected HH:MM:SS.mmm", value),
+        }
+    }
+
+    /// Frame timing to convert the value, named in the error
+    fn clock_for(&self, name: &str) -> Result<&FrameClock> {
+        match &self.clock {
+            Some(clock) => Ok(clock),
+            None => bail!(
+                "{} requires a frame rate, use --fps or set fps in the config. For variable frame rate, use --timestamps",
+                name
+            ),
+        }
+    }
+
+    /// Number of the referenced chapter, by name or as `Chapter N`
+    fn chapter(&self, value: &str) -> Result<Option<(&Chapters, usize)>> {
+        match &self.chapters {
+            Some(chapters) => Ok(chapters.find(value).map(|number| (chapters, number))),
+            None if parse_chapter_ref(value).is_some() => {
+                bail!(
+                    "{} requires a chapters file, use --chapters or set chapters in the config",
+                    value.trim()
+                )
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Converts a timecode string to a frame number
+    fn resolve_number(&self, value: &mut Value) -> Result<()> {
+        if let Value::String(s) = value {
+            *value = Value::from(self.frame(s)?);
+        }
+
+        Ok(())
+    }
+
+    /// Converts a duration timecode string to a number of frames, from the start frame
+    fn resolve_duration(&self, start: usize, value: &mut Value) -> Result<()> {
+        if let Value::String(s) = value {
+            let frames = if s.contains(':') {
+                let seconds = Self::timecode_seconds(s.trim())?;
+
+                self.clock_for(&format!("Timecode {}", s.trim()))?
+                    .duration_frames(start, seconds)?
+            } else {
+                self.frame(s)?
+            };
+
+            *value = Value::from(frames);
+        }
+
+        Ok(())
+    }
+
+    /// Converts the timecodes and chapters of a `start-end` range or single frame string.
+    /// A chapter alone is the range of all its frames, and ends the range as its last frame.
+    fn resolve_range(&self, range: &str) -> Result<String> {
+        if let Some((chapters, number)) = self.chapter(range)? {
+            let clock = self.clock_for(range.trim())?;
+
+            return Ok(format!(
+                "{}-{}",
+                chapters.start_frame(number, clock)?,
+                chapters.end_frame(number, clock)?
+            ));
+        }
+
+        let parts: Vec<&str> = range.split('-').collect();
+        let has_chapter = parts
+            .iter()
+            .map(|part| self.chapter(part))
+            .collect::<Result<Vec<_>>>()?
+            .iter()
+            .any(Option::is_some);
+
+        if !range.contains(':') && !has_chapter {
+            return Ok(range.to_string());
+        }
+
+        let frames = parts
+            .iter()
+            .enumerate()
+            .map(|(i, part)| match self.chapter(part)? {
+                Some((chapters, number)) if i > 0 => {
+                    chapters.end_frame(number, self.clock_for(part.trim())?)
+                }
+                _ => self.frame(part),
+            })
+            .map(|frame| frame.map(|frame| frame.to_string()))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(frames.join("-"))
+    }
+
+    /// Resolves the frame ranges of an editor config
+    pub fn resolve_edit_config(&self, config: &mut Value) -> Result<()> {
+        if let Some(Value::Array(ranges)) = config.get_mut("remove") {
+            for range in ranges.iter_mut() {
+                if let Value::String(s) = range {
+                    *s = self.resolve_range(s)?;
+                }
+            }
+        }
+
+        if let Some(Value::Array(duplicates)) = config.get_mut("duplicate") {
+            for duplicate in duplicates.iter_mut() {
+                for key in ["source", "offset"] {
+                    if let Some(value) = duplicate.get_mut(key) {
+                        self.resolve_number(value)?;
+                    }
+                }
+
+                // The duplicated frames are shown from the offset
+                let offset = duplicate.get("offset").and_then(Value::as_u64).unwrap_or(0);
+
+                if let Some(value) = duplicate.get_mut("length") {
+                    self.resolve_duration(offset as usize, value)?;
+                }
+            }
+        }
+
+        if let Some(Value::Array(replacements)) = config.get_mut("replace") {
+            for replacement in replacements.iter_mut() {
+                if let Some(Value::Array(range)) = replacement.get_mut("range") {
+                    for value in range.iter_mut() {
+                        self.resolve_number(value)?;
+                    }
+                }
+
+                if let Some(value) = replacement.get_mut("source_start") {
+                    self.resolve_number(value)?;
+                }
+            }
+        }
+
+        if let Some(Value::Object(edits)) = config
+            .get_mut("active_area")
+            .and_then(|active_area| active_area.get_mut("edits"))
+        {
+            let resolved = edits
+                .iter()
+                .map(|(range, preset)| Ok((self.resolve_range(range)?, preset.clone())))
+                .collect::<Result<_>>()?;
+
+            *edits = resolved;
+        }
+
+        for level in ["level3", "level4"] {
+            if let Some(Value::Object(ranges)) = config.get_mut(level) {
+                let resolved = ranges
+                    .iter()
+                    .map(|(range, block)| Ok((self.resolve_range(range)?, block.clone())))
+                    .collect::<Result<_>>()?;
+
+                *ranges = resolved;
+            }
+        }
+
+        if let Some(Value::Array(targets)) = config.get_mut("targets") {
+            for target in targets.iter_mut() {
+                if let Some(Value::Object(ranges)) = target.get_mut("trims") {
+                    let resolved = ranges
+                        .iter()
+                        .map(|(range, trim)| Ok((self.resolve_range(range)?, trim.clone())))
+                        .collect::<Result<_>>()?;
+
+                    *ranges = resolved;
+                }
+            }
+        }
+
+        if let Some(Value::Object(ranges)) = config.get_mut("remove_levels") {
+            let resolved = ranges
+                .iter()
+                .map(|(range, levels)| Ok((self.resolve_range(range)?, levels.clone())))
+                .collect::<Result<_>>()?;
+
+            *ranges = resolved;
+        }
+
+        Ok(())
+    }
+
+    /// Resolves the length and shot frames of a generator config
+    pub fn resolve_generate_config(&self, config: &mut Value) -> Result<()> {
+        if let Some(length) = config.get_mut("length") {
+            self.resolve_number(length)?;
+        }
+
+        if let Some(Value::Array(shots)) = config.get_mut("shots") {
+            for shot in shots.iter_mut() {
+                if let Some(value) = shot.get_mut("start") {
+                    self.resolve_number(value)?;
+                }
+
+                let start = shot.get("start").and_then(Value::as_u64).unwrap_or(0) as usize;
+
+                if let Some(value) = shot.get_mut("duration") {
+                    self.resolve_duration(start, value)?;
+                }
+
+                // Offsets from the start of the shot
+                if let Some(Value::Array(frame_edits)) = shot.get_mut("frame_edits") {
+                    for frame_edit in frame_edits.iter_mut() {
+                        if let Some(value) = frame_edit.get_mut("edit_offset") {
+                            self.resolve_duration(start, value)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+use std::fs::read_to_string;
+use std::path::Path;
+
+use anyhow::{bail, ensure, Result};
+
+use super::timecode::format_timestamp;
+
+/// Presentation timestamps of every frame of a variable frame rate video,
+/// from a Matroska timestamps v2 file, as written by `mkvextract timestamps_v2`
+#[derive(Debug, Clone)]
+pub struct FrameTimestamps {
+    /// Start time of every frame in seconds, increasing
+    starts: Vec<f64>,
+    /// End time of the last frame, one more frame of the last frame duration
+    end: f64,
+}
+
+impl FrameTimestamps {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let content = read_to_string(path)?;
+
+        Self::parse(&content)
+    }
+
+    /// Parses one timestamp in milliseconds per line, in presentation order.
+    /// The `# timestamp format v2` header and the other comment lines are ignored.
+    pub fn parse(content: &str) -> Result<Self> {
+        let lines = content.lines().map(str::trim).enumerate();
+
+        match lines.clone().find(|(_, line)| !line.is_empty()) {
+            Some((_, header)) if header.starts_with('#') => {
+                let header = header.to_ascii_lowercase();
+
+                ensure!(
+                    !header.contains("format") || header.contains("v2"),
+                    "Unsupported timestamps file, expected the Matroska timestamps v2 format: {}",
+                    header
+                );
+            }
+            _ => (),
+        }
+
+        let mut starts: Vec<f64> = Vec::new();
+
+        for (i, line) in lines {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let millis = match line.parse::<f64>() {
+                Ok(millis) if millis.is_finite() && millis >= 0.0 => millis,
+                _ => bail!("Invalid timestamp on line {}: {}", i + 1, line),
+            };
+            let start = millis / 1000.0;
+
+            if let Some(previous) = starts.last() {
+                ensure!(
+                    start > *previous,
+                    "Timestamps must be increasing, line {}: {}",
+                    i + 1,
+                    line
+                );
+            }
+
+            starts.push(start);
+        }
+
+        let end = match starts.as_slice() {
+            [] => bail!("No frame timestamps found"),
+            [.., previous, last] => last + (last - previous),
+            [last] => *last,
+        };
+
+        Ok(Self { starts, end })
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// Start time of a frame, or the end of the video for the frame after the last one
+    pub fn time_of(&self, frame: usize) -> Result<f64> {
+        match self.starts.get(frame) {
+            Some(start) => Ok(*start),
+            None if frame == self.frame_count() => Ok(self.end),
+            None => bail!(
+                "Frame {} is after the end of the {} frames of the timestamps file",
+                frame,
+                self.frame_count()
+            ),
+        }
+    }
+
+    /// Frame starting the nearest to the time, or the frame after the last one for the end of the video
+    pub fn frame_at(&self, seconds: f64) -> Result<usize> {
+        let after = self.starts.partition_point(|start| *start < seconds);
+
+        let nearest = match after.checked_sub(1) {
+            Some(before) if after < self.frame_count() => {
+                if seconds - self.starts[before] < self.starts[after] - seconds {
+                    before
+                } else {
+                    after
+                }
+            }
+            Some(before) => {
+                if seconds - self.starts[before] < self.end - seconds {
+                    before
+                } else {
+                    self.frame_count()
+                }
+            }
+            None => 0,
+        };
+
+        let last_duration = self.end - self.starts[self.frame_count() - 1];
+
+        ensure!(
+            nearest < self.frame_count() || seconds - self.end <= last_duration / 2.0,
+            "Timecode {} is after the end of the {} frames of the timestamps file",
+            format_timestamp(seconds),
+            self.frame_count()
+        );
+
+        Ok(nearest)
+    }
+}
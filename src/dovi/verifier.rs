@@ -0,0 +1,260 @@
+use std::io::{stdout, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use indicatif::ProgressBar;
+
+use hevc_parser::hevc::NALUnit;
+use hevc_parser::io::{processor, IoProcessor};
+use hevc_parser::HevcParser;
+use processor::HevcProcessorOpts;
+
+use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlock;
+use utilities_dovi::parse_rpu_file_results;
+
+use crate::commands::VerifyArgs;
+
+use super::hevc_processor::HevcProcessor;
+use super::{
+    input_from_either, open_hevc_input, DoviRpu, InputFormat, IoFormat, DEFAULT_READ_BUFFER,
+};
+
+pub struct Verifier {
+    input: PathBuf,
+    hevc: Option<PathBuf>,
+
+    issues: Vec<VerifyIssue>,
+}
+
+struct VerifyIssue {
+    frame: Option<usize>,
+    message: String,
+}
+
+/// Counts the frames of a HEVC file
+struct FrameCounter {
+    input: PathBuf,
+    progress_bar: ProgressBar,
+    frames: usize,
+}
+
+impl Verifier {
+    pub fn verify(args: VerifyArgs) -> Result<()> {
+        let VerifyArgs {
+            input,
+            input_pos,
+            hevc,
+        } = args;
+
+        let input = input_from_either("verify", input, input_pos)?;
+
+        let mut verifier = Verifier {
+            input,
+            hevc,
+            issues: Vec::new(),
+        };
+
+        verifier.execute()
+    }
+
+    fn execute(&mut self) -> Result<()> {
+        println!("Parsing RPU file...");
+        stdout().flush().ok();
+
+        let results = parse_rpu_file_results(&self.input)?;
+        let rpu_count = results.len();
+
+        println!("Verifying RPUs...");
+        stdout().flush().ok();
+
+        let mut reference: Option<(usize, DoviRpu)> = None;
+
+        for (frame, res) in results.into_iter().enumerate() {
+            match res {
+                Ok(rpu) => {
+                    self.verify_rpu(frame, &rpu, reference.as_ref());
+
+                    if reference.is_none() {
+                        reference = Some((frame, rpu));
+                    }
+                }
+                Err(e) => self.add_issue(Some(frame), format!("failed parsing RPU: {}", e)),
+            }
+        }
+
+        if let Some(hevc) = self.hevc.clone() {
+            let frame_count = FrameCounter::count_frames(&hevc)?;
+
+            if frame_count != rpu_count {
+                self.add_issue(
+                    None,
+                    format!(
+                        "mismatched frame count: video {}, RPU {}",
+                        frame_count, rpu_count
+                    ),
+                );
+            }
+        }
+
+        println!("Verified {} RPUs", rpu_count);
+
+        if self.issues.is_empty() {
+            println!("No issues found.");
+
+            Ok(())
+        } else {
+            for issue in &self.issues {
+                match issue.frame {
+                    Some(frame) => println!("  Frame {}: {}", frame, issue.message),
+                    None => println!("  {}", issue.message),
+                }
+            }
+
+            bail!("verify: {} issues found", self.issues.len());
+        }
+    }
+
+    fn verify_rpu(&mut self, frame: usize, rpu: &DoviRpu, reference: Option<&(usize, DoviRpu)>) {
+        if let Some((ref_frame, ref_rpu)) = reference {
+            if rpu.dovi_profile != ref_rpu.dovi_profile {
+                self.add_issue(
+                    Some(frame),
+                    format!(
+                        "inconsistent profile: {}, frame {} has profile {}",
+                        rpu.dovi_profile, ref_frame, ref_rpu.dovi_profile
+                    ),
+                );
+            }
+
+            let header = &rpu.header;
+            let ref_header = &ref_rpu.header;
+
+            let header_params = [
+                (
+                    "bl_bit_depth_minus8",
+                    header.bl_bit_depth_minus8,
+                    ref_header.bl_bit_depth_minus8,
+                ),
+                (
+                    "el_bit_depth_minus8",
+                    header.el_bit_depth_minus8,
+                    ref_header.el_bit_depth_minus8,
+                ),
+                (
+                    "vdr_bit_depth_minus_8",
+                    header.vdr_bit_depth_minus_8,
+                    ref_header.vdr_bit_depth_minus_8,
+                ),
+                (
+                    "coefficient_data_type",
+                    header.coefficient_data_type as u64,
+                    ref_header.coefficient_data_type as u64,
+                ),
+                (
+                    "mapping_color_space",
+                    header.mapping_color_space,
+                    ref_header.mapping_color_space,
+                ),
+                (
+                    "mapping_chroma_format_idc",
+                    header.mapping_chroma_format_idc,
+                    ref_header.mapping_chroma_format_idc,
+                ),
+            ];
+
+            for (name, value, ref_value) in header_params {
+                if value != ref_value {
+                    self.add_issue(
+                        Some(frame),
+                        format!(
+                            "inconsistent mapping parameter {}: {}, frame {} has {}",
+                            name, value, ref_frame, ref_value
+                        ),
+                    );
+                }
+            }
+        }
+
+        if let Some(vdr_dm_data) = &rpu.vdr_dm_data {
+            for block in vdr_dm_data.level_blocks_iter(1) {
+                if let ExtMetadataBlock::Level1(l1) = block {
+                    if l1.min_pq > l1.avg_pq || l1.avg_pq > l1.max_pq {
+                        self.add_issue(
+                            Some(frame),
+                            format!(
+                                "L1 values are not ordered as min <= avg <= max: min {}, avg {}, max {}",
+                                l1.min_pq, l1.avg_pq, l1.max_pq
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn add_issue(&mut self, frame: Option<usize>, message: String) {
+        self.issues.push(VerifyIssue { frame, message });
+    }
+}
+
+impl FrameCounter {
+    fn count_frames(input: &Path) -> Result<usize> {
+        let format = hevc_parser::io::format_from_path(input)?;
+
+        if let IoFormat::Matroska = format {
+            bail!("Verifier: Matroska input is unsupported");
+        }
+
+        println!("Counting HEVC frames...");
+        stdout().flush().ok();
+
+        let mut counter = FrameCounter {
+            input: input.to_path_buf(),
+            progress_bar: super::initialize_progress_bar(&format, input)?,
+            frames: 0,
+        };
+
+        let chunk_size = DEFAULT_READ_BUFFER;
+
+        let mut processor =
+            HevcProcessor::new(format.clone(), HevcProcessorOpts::default(), chunk_size);
+
+        let mut reader = open_hevc_input(
+            &format,
+            input,
+            InputFormat::Auto,
+            &counter.progress_bar,
+            chunk_size,
+        )?;
+
+        processor.process_io(&mut reader, &mut counter)?;
+
+        Ok(counter.frames)
+    }
+}
+
+impl IoProcessor for FrameCounter {
+    fn input(&self) -> &PathBuf {
+        &self.input
+    }
+
+    fn update_progress(&mut self, _delta: u64) {
+        // Progress is updated by the input reader
+    }
+
+    fn process_nals(
+        &mut self,
+        _parser: &HevcParser,
+        _nals: &[NALUnit],
+        _chunk: &[u8],
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn finalize(&mut self, parser: &HevcParser) -> Result<()> {
+        self.frames = parser.ordered_frames().len();
+        self.progress_bar.finish_and_clear();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,438 @@
+use anyhow::{bail, Result};
+use bitvec_helpers::bitvec_reader::BitVecReader;
+
+use hevc_parser::hevc::{Frame, NALUnit};
+
+use super::nal_parser::{nal_reader, FrameOrder, NalParser};
+
+pub const NAL_VVC_RADL: u8 = 2;
+pub const NAL_VVC_RASL: u8 = 3;
+pub const NAL_VVC_IDR_W_RADL: u8 = 7;
+pub const NAL_VVC_IDR_N_LP: u8 = 8;
+pub const NAL_VVC_GDR: u8 = 10;
+pub const NAL_VVC_RSV_IRAP_11: u8 = 11;
+pub const NAL_VVC_VPS: u8 = 14;
+pub const NAL_VVC_SPS: u8 = 15;
+pub const NAL_VVC_PPS: u8 = 16;
+pub const NAL_VVC_SUFFIX_APS: u8 = 18;
+pub const NAL_VVC_PH: u8 = 19;
+pub const NAL_VVC_AUD: u8 = 20;
+pub const NAL_VVC_EOS: u8 = 21;
+pub const NAL_VVC_EOB: u8 = 22;
+pub const NAL_VVC_SUFFIX_SEI: u8 = 24;
+pub const NAL_VVC_FD: u8 = 25;
+pub const NAL_VVC_RSV_NVCL_27: u8 = 27;
+
+/// Dolby Vision RPU, in an unspecified NAL unit type.
+/// Same as HEVC, the first of the two unspecified types allowed after the slices.
+pub const NAL_VVC_DV_RPU: u8 = 30;
+/// Dolby Vision enhancement layer, in an unspecified NAL unit type
+pub const NAL_VVC_DV_EL: u8 = 31;
+
+/// NAL header for written RPUs: nuh_layer_id 0, nal_unit_type 30, nuh_temporal_id_plus1 1
+pub const VVC_RPU_NAL_HEADER: [u8; 2] = [0x00, (NAL_VVC_DV_RPU << 3) | 1];
+
+// We don't want to parse large slices because the memory is copied
+const MAX_PARSE_SIZE: usize = 2048;
+
+/// Parses VVC Annex B streams, for frame decoding and presentation order.
+///
+/// Only single layer streams are supported.
+#[derive(Default)]
+pub struct VvcParser {
+    sps: Vec<VvcSps>,
+    pps: Vec<VvcPps>,
+
+    frame_order: FrameOrder,
+
+    // From the last picture header NAL unit
+    picture_header: Option<VvcPictureHeader>,
+
+    // Reset by end of sequence NAL units
+    in_sequence: bool,
+
+    prev_poc_msb: i64,
+    prev_poc_lsb: i64,
+}
+
+#[derive(Debug, Clone)]
+struct VvcSps {
+    sps_id: u8,
+    log2_max_pic_order_cnt_lsb: usize,
+}
+
+#[derive(Debug, Clone)]
+struct VvcPps {
+    pps_id: u8,
+    sps_id: u8,
+}
+
+#[derive(Debug, Clone)]
+struct VvcPictureHeader {
+    non_ref_pic_flag: bool,
+    inter_slice_allowed_flag: bool,
+    pic_order_cnt_lsb: i64,
+    log2_max_pic_order_cnt_lsb: usize,
+}
+
+impl VvcParser {
+    fn parse_nal_data(&mut self, data: &[u8], nal: &mut NALUnit) -> Result<()> {
+        let (start, end) = (nal.start, nal.end);
+
+        if start + 1 >= end {
+            return Ok(());
+        }
+
+        let nuh_layer_id = data[start] & 0x3F;
+        nal.nal_type = data[start + 1] >> 3;
+        let temporal_id = (data[start + 1] & 0x7).saturating_sub(1);
+
+        if nuh_layer_id > 0 {
+            bail!("Unsupported VVC stream: multiple layers");
+        }
+
+        let parsing_end = end.min(start + MAX_PARSE_SIZE);
+
+        match nal.nal_type {
+            0..=NAL_VVC_RSV_IRAP_11 => {
+                let mut reader = nal_reader(&data[start + 2..parsing_end]);
+                let picture_header_in_slice_header_flag = reader.get()?;
+
+                // With the picture header in the slice header, the picture has a single slice
+                let picture_header = if picture_header_in_slice_header_flag {
+                    Some(self.parse_picture_header(&mut reader)?)
+                } else if !self.frame_order.has_current_slice() {
+                    match self.picture_header.take() {
+                        Some(picture_header) => Some(picture_header),
+                        None => bail!("VVC slice without a picture header"),
+                    }
+                } else {
+                    None
+                };
+
+                if let Some(picture_header) = picture_header {
+                    self.start_picture(&picture_header, nal.nal_type, temporal_id);
+                }
+            }
+            NAL_VVC_DV_RPU | NAL_VVC_DV_EL | NAL_VVC_SUFFIX_APS | NAL_VVC_SUFFIX_SEI
+            | NAL_VVC_FD | NAL_VVC_RSV_NVCL_27 | NAL_VVC_EOB => {
+                // Dolby NALs are suffixed to the slices
+                // And the other suffix NALs are contained within the current AU
+            }
+            NAL_VVC_EOS => {
+                // The next picture starts a new coded video sequence
+                self.in_sequence = false;
+            }
+            _ => {
+                // Any other NAL starts a new picture unit
+                self.frame_order.add_current_frame();
+
+                match nal.nal_type {
+                    NAL_VVC_SPS => {
+                        let mut reader = nal_reader(&data[start + 2..parsing_end]);
+                        self.parse_sps(&mut reader)?;
+                    }
+                    NAL_VVC_PPS => {
+                        let mut reader = nal_reader(&data[start + 2..parsing_end]);
+                        self.parse_pps(&mut reader);
+                    }
+                    NAL_VVC_PH => {
+                        let mut reader = nal_reader(&data[start + 2..parsing_end]);
+                        self.picture_header = Some(self.parse_picture_header(&mut reader)?);
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse_sps(&mut self, reader: &mut BitVecReader) -> Result<()> {
+        let sps_id: u8 = reader.get_n(4);
+        reader.skip_n(4); // sps_video_parameter_set_id
+
+        let max_sublayers_minus1: u8 = reader.get_n(3);
+        reader.skip_n(2); // sps_chroma_format_idc
+
+        let log2_ctu_size_minus5: u8 = reader.get_n(2);
+        let ctb_size = 1_u64 << (log2_ctu_size_minus5 + 5);
+
+        let ptl_dpb_hrd_params_present_flag = reader.get()?;
+        if ptl_dpb_hrd_params_present_flag {
+            skip_profile_tier_level(reader, max_sublayers_minus1)?;
+        }
+
+        reader.get()?; // sps_gdr_enabled_flag
+
+        let ref_pic_resampling_enabled_flag = reader.get()?;
+        if ref_pic_resampling_enabled_flag {
+            reader.get()?; // sps_res_change_in_clvs_allowed_flag
+        }
+
+        let pic_width = reader.get_ue()?;
+        let pic_height = reader.get_ue()?;
+
+        let conformance_window_flag = reader.get()?;
+        if conformance_window_flag {
+            for _ in 0..4 {
+                reader.get_ue()?;
+            }
+        }
+
+        let subpic_info_present_flag = reader.get()?;
+        if subpic_info_present_flag {
+            skip_subpic_info(reader, pic_width, pic_height, ctb_size)?;
+        }
+
+        reader.get_ue()?; // sps_bitdepth_minus8
+        reader.get()?; // sps_entropy_coding_sync_enabled_flag
+        reader.get()?; // sps_entry_point_offsets_present_flag
+
+        let log2_max_pic_order_cnt_lsb_minus4: u8 = reader.get_n(4);
+
+        let sps = VvcSps {
+            sps_id,
+            log2_max_pic_order_cnt_lsb: log2_max_pic_order_cnt_lsb_minus4 as usize + 4,
+        };
+
+        self.sps.retain(|e| e.sps_id != sps_id);
+        self.sps.push(sps);
+
+        Ok(())
+    }
+
+    fn parse_pps(&mut self, reader: &mut BitVecReader) {
+        let pps_id = reader.get_n(6);
+        let sps_id = reader.get_n(4);
+
+        self.pps.retain(|e| e.pps_id != pps_id);
+        self.pps.push(VvcPps { pps_id, sps_id });
+    }
+
+    /// Parses the picture header structure, up to the picture order count
+    fn parse_picture_header(&self, reader: &mut BitVecReader) -> Result<VvcPictureHeader> {
+        let gdr_or_irap_pic_flag = reader.get()?;
+        let non_ref_pic_flag = reader.get()?;
+
+        if gdr_or_irap_pic_flag {
+            reader.get()?; // ph_gdr_pic_flag
+        }
+
+        let inter_slice_allowed_flag = reader.get()?;
+        if inter_slice_allowed_flag {
+            reader.get()?; // ph_intra_slice_allowed_flag
+        }
+
+        let pps_id = reader.get_ue()?;
+
+        let pps = match self.pps.iter().find(|pps| pps.pps_id as u64 == pps_id) {
+            Some(pps) => pps,
+            None => bail!("VVC picture header references a missing PPS, id {}", pps_id),
+        };
+
+        let sps = match self.sps.iter().find(|sps| sps.sps_id == pps.sps_id) {
+            Some(sps) => sps,
+            None => bail!("VVC PPS references a missing SPS, id {}", pps.sps_id),
+        };
+
+        let pic_order_cnt_lsb = reader.get_n::<u64>(sps.log2_max_pic_order_cnt_lsb) as i64;
+
+        Ok(VvcPictureHeader {
+            non_ref_pic_flag,
+            inter_slice_allowed_flag,
+            pic_order_cnt_lsb,
+            log2_max_pic_order_cnt_lsb: sps.log2_max_pic_order_cnt_lsb,
+        })
+    }
+
+    fn start_picture(&mut self, picture_header: &VvcPictureHeader, nal_type: u8, temporal_id: u8) {
+        self.frame_order.add_current_frame();
+
+        let is_irap_or_gdr = (NAL_VVC_IDR_W_RADL..=NAL_VVC_GDR).contains(&nal_type);
+        let is_idr = matches!(nal_type, NAL_VVC_IDR_W_RADL | NAL_VVC_IDR_N_LP);
+
+        // Start of a coded video sequence, the POC is reset
+        let poc_reset = is_idr || (is_irap_or_gdr && !self.in_sequence);
+
+        if poc_reset {
+            self.frame_order.reorder_frames();
+        }
+
+        if is_irap_or_gdr {
+            self.in_sequence = true;
+        }
+
+        let max_lsb = 1_i64 << picture_header.log2_max_pic_order_cnt_lsb;
+        let lsb = picture_header.pic_order_cnt_lsb;
+
+        let msb = if poc_reset {
+            0
+        } else if lsb < self.prev_poc_lsb && (self.prev_poc_lsb - lsb) >= max_lsb / 2 {
+            self.prev_poc_msb + max_lsb
+        } else if lsb > self.prev_poc_lsb && (lsb - self.prev_poc_lsb) > max_lsb / 2 {
+            self.prev_poc_msb - max_lsb
+        } else {
+            self.prev_poc_msb
+        };
+
+        let is_leading = matches!(nal_type, NAL_VVC_RADL | NAL_VVC_RASL);
+
+        if temporal_id == 0 && !is_leading && !picture_header.non_ref_pic_flag {
+            self.prev_poc_msb = msb;
+            self.prev_poc_lsb = lsb;
+        }
+
+        // Same as HEVC frame types: 2 = I, 0 = B.
+        // P pictures are not signaled in the picture header
+        let frame_type = if picture_header.inter_slice_allowed_flag {
+            0
+        } else {
+            2
+        };
+
+        self.frame_order.start_frame(msb + lsb, frame_type);
+    }
+}
+
+impl NalParser for VvcParser {
+    fn parse_nal(&mut self, data: &[u8], mut nal: NALUnit) -> Result<NALUnit> {
+        self.parse_nal_data(data, &mut nal)?;
+        self.frame_order.push_nal(&mut nal);
+
+        Ok(nal)
+    }
+
+    fn finish(&mut self) {
+        self.frame_order.finish();
+    }
+
+    fn frame_order(&self) -> &FrameOrder {
+        &self.frame_order
+    }
+}
+
+fn skip_profile_tier_level(reader: &mut BitVecReader, max_sublayers_minus1: u8) -> Result<()> {
+    // general_profile_idc, general_tier_flag, general_level_idc
+    // ptl_frame_only_constraint_flag, ptl_multilayer_enabled_flag
+    reader.skip_n(18);
+
+    // general_constraints_info()
+    let gci_present_flag = reader.get()?;
+    if gci_present_flag {
+        reader.skip_n(71);
+
+        let gci_num_additional_bits: u8 = reader.get_n(8);
+        reader.skip_n(gci_num_additional_bits as usize);
+    }
+
+    while !reader.is_aligned() {
+        reader.get()?; // gci_alignment_zero_bit
+    }
+
+    let mut sublayer_level_present_flags = Vec::with_capacity(max_sublayers_minus1 as usize);
+    for _ in 0..max_sublayers_minus1 {
+        sublayer_level_present_flags.push(reader.get()?);
+    }
+
+    while !reader.is_aligned() {
+        reader.get()?; // ptl_reserved_zero_bit
+    }
+
+    for present in sublayer_level_present_flags {
+        if present {
+            reader.skip_n(8); // sublayer_level_idc
+        }
+    }
+
+    let ptl_num_sub_profiles: u8 = reader.get_n(8);
+    reader.skip_n(ptl_num_sub_profiles as usize * 32); // general_sub_profile_idc
+
+    Ok(())
+}
+
+fn skip_subpic_info(
+    reader: &mut BitVecReader,
+    pic_width: u64,
+    pic_height: u64,
+    ctb_size: u64,
+) -> Result<()> {
+    let num_subpics_minus1 = reader.get_ue()?;
+
+    if num_subpics_minus1 > 0 {
+        let independent_subpics_flag = reader.get()?;
+        let subpic_same_size_flag = reader.get()?;
+
+        let width_bits = ceil_log2(pic_width.div_ceil(ctb_size));
+        let height_bits = ceil_log2(pic_height.div_ceil(ctb_size));
+
+        for i in 0..=num_subpics_minus1 {
+            if !subpic_same_size_flag || i == 0 {
+                if i > 0 && pic_width > ctb_size {
+                    reader.skip_n(width_bits); // sps_subpic_ctu_top_left_x
+                }
+
+                if i > 0 && pic_height > ctb_size {
+                    reader.skip_n(height_bits); // sps_subpic_ctu_top_left_y
+                }
+
+                if i < num_subpics_minus1 && pic_width > ctb_size {
+                    reader.skip_n(width_bits); // sps_subpic_width_minus1
+                }
+
+                if i < num_subpics_minus1 && pic_height > ctb_size {
+                    reader.skip_n(height_bits); // sps_subpic_height_minus1
+                }
+            }
+
+            if !independent_subpics_flag {
+                // sps_subpic_treated_as_pic_flag, sps_loop_filter_across_subpic_enabled_flag
+                reader.skip_n(2);
+            }
+        }
+    }
+
+    let subpic_id_len = reader.get_ue()? as usize + 1;
+
+    let subpic_id_mapping_explicitly_signalled_flag = reader.get()?;
+    if subpic_id_mapping_explicitly_signalled_flag {
+        let subpic_id_mapping_present_flag = reader.get()?;
+
+        if subpic_id_mapping_present_flag {
+            reader.skip_n((num_subpics_minus1 as usize + 1) * subpic_id_len);
+        }
+    }
+
+    Ok(())
+}
+
+fn ceil_log2(value: u64) -> usize {
+    if value <= 1 {
+        0
+    } else {
+        (64 - (value - 1).leading_zeros()) as usize
+    }
+}
+
+/// Access unit delimiter NAL unit, without start code
+pub fn vvc_aud_for_frame(frame: &Frame) -> Vec<u8> {
+    let irap_or_gdr_flag = frame
+        .nals
+        .iter()
+        .any(|nal| (NAL_VVC_IDR_W_RADL..=NAL_VVC_GDR).contains(&nal.nal_type));
+
+    let pic_type: u8 = match frame.frame_type {
+        2 => 0, // I
+        _ => 2, // B, P, I
+    };
+
+    vvc_aud(irap_or_gdr_flag, pic_type)
+}
+
+/// Access unit delimiter NAL unit from its fields, without start code
+pub fn vvc_aud(irap_or_gdr_flag: bool, pic_type: u8) -> Vec<u8> {
+    // aud_irap_or_gdr_flag, aud_pic_type, then rbsp_stop_one_bit
+    let payload = ((irap_or_gdr_flag as u8) << 7) | (pic_type << 4) | 0x08;
+
+    vec![0x00, (NAL_VVC_AUD << 3) | 1, payload]
+}
@@ -0,0 +1,237 @@
+use std::io::{stdout, Write};
+use std::path::Path;
+
+use anyhow::{bail, Result};
+
+use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlock;
+use dolby_vision::rpu::generate::VideoShot;
+use dolby_vision::xml::{CmXmlParser, XmlParserOpts};
+use utilities_dovi::parse_rpu_file;
+
+use super::DoviRpu;
+
+/// A compared metadata field, with its value in the RPU and in the XML.
+/// A value is `None` when the field is missing.
+struct FieldDelta {
+    field: String,
+    frame: usize,
+    value: Option<i32>,
+    reference: Option<i32>,
+}
+
+impl FieldDelta {
+    /// Difference between the values, `None` when the field is missing from one side
+    fn delta(&self) -> Option<u32> {
+        match (self.value, self.reference) {
+            (Some(a), Some(b)) => Some((a - b).unsigned_abs()),
+            _ => None,
+        }
+    }
+
+    fn exceeds(&self, tolerance: u16) -> bool {
+        self.delta().is_none_or(|delta| delta > tolerance as u32)
+    }
+
+    /// Whether the mismatch is worse than `other`, for the same field
+    fn is_worse_than(&self, other: &FieldDelta) -> bool {
+        match (self.delta(), other.delta()) {
+            (Some(delta), Some(other_delta)) => delta > other_delta,
+            (None, Some(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Compares the L1, L2 and L5 metadata of the RPUs to the XML metadata, per shot.
+///
+/// The L5 metadata is only compared when the canvas size is set, as it is required to compute it.
+/// Fails if any shot differs by more than the tolerance.
+pub fn diff_xml(
+    input: &Path,
+    xml: &Path,
+    parser_opts: XmlParserOpts,
+    tolerance: u16,
+    summary: bool,
+) -> Result<()> {
+    println!("Parsing RPU file...");
+    stdout().flush().ok();
+
+    let rpus = parse_rpu_file(input)?.unwrap_or_default();
+
+    println!("Parsing XML metadata...");
+    stdout().flush().ok();
+
+    let compare_l5 = parser_opts.canvas_width.is_some() && parser_opts.canvas_height.is_some();
+
+    let config = CmXmlParser::parse_file(xml, parser_opts)?.config;
+    let references = config.generate_rpu_list()?;
+
+    let frame_count = rpus.len().min(references.len());
+
+    if rpus.len() != references.len() {
+        println!(
+            "Frame count differs: {} in the RPU and {} in the XML, comparing the first {} frames",
+            rpus.len(),
+            references.len(),
+            frame_count
+        );
+    }
+
+    // The RPUs are generated in shot order, from the first shot
+    let mut shots: Vec<&VideoShot> = config.shots.iter().collect();
+    shots.sort_by_key(|shot| shot.start);
+
+    let first_frame = shots.first().map_or(0, |shot| shot.start);
+    let mut mismatching_shots = 0;
+
+    for (i, shot) in shots.iter().enumerate() {
+        let start = (shot.start - first_frame).min(frame_count);
+        let end = (start + shot.duration).min(frame_count);
+
+        // Largest mismatch of each field
+        let mut mismatches: Vec<FieldDelta> = Vec::new();
+
+        for frame in start..end {
+            let deltas = frame_deltas(frame, &rpus[frame], &references[frame], compare_l5);
+
+            for delta in deltas.into_iter().filter(|d| d.exceeds(tolerance)) {
+                match mismatches.iter_mut().find(|m| m.field == delta.field) {
+                    Some(existing) if delta.is_worse_than(existing) => *existing = delta,
+                    Some(_) => (),
+                    None => mismatches.push(delta),
+                }
+            }
+        }
+
+        if mismatches.is_empty() {
+            continue;
+        }
+
+        mismatching_shots += 1;
+
+        if !summary {
+            println!("Shot {} (ID {}), frames {}-{}:", i, shot.id, start, end - 1);
+
+            for mismatch in &mismatches {
+                println!(
+                    "  {}: {} -> {} (frame {})",
+                    mismatch.field,
+                    value_str(mismatch.value),
+                    value_str(mismatch.reference),
+                    mismatch.frame
+                );
+            }
+        }
+    }
+
+    if mismatching_shots == 0 {
+        println!(
+            "The RPU metadata matches the XML, within {} codes",
+            tolerance
+        );
+
+        return Ok(());
+    }
+
+    bail!(
+        "RPU metadata differs from the XML in {} of {} shots, by more than {} codes",
+        mismatching_shots,
+        shots.len(),
+        tolerance
+    )
+}
+
+/// Compares the fields present in either the RPU or the XML reference
+fn frame_deltas(
+    frame: usize,
+    rpu: &DoviRpu,
+    reference: &DoviRpu,
+    compare_l5: bool,
+) -> Vec<FieldDelta> {
+    let fields = metadata_fields(rpu, compare_l5);
+    let reference_fields = metadata_fields(reference, compare_l5);
+
+    let find = |fields: &[(String, i32)], name: &str| {
+        fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| *value)
+    };
+
+    let mut deltas: Vec<FieldDelta> = reference_fields
+        .iter()
+        .map(|(field, reference)| FieldDelta {
+            field: field.clone(),
+            frame,
+            value: find(&fields, field),
+            reference: Some(*reference),
+        })
+        .collect();
+
+    deltas.extend(
+        fields
+            .iter()
+            .filter(|(field, _)| find(&reference_fields, field).is_none())
+            .map(|(field, value)| FieldDelta {
+                field: field.clone(),
+                frame,
+                value: Some(*value),
+                reference: None,
+            }),
+    );
+
+    deltas
+}
+
+/// The L1, L2 and L5 fields of the RPU.
+/// The L2 trims are identified by their target max PQ, e.g. `L2[2081].trim_slope`.
+fn metadata_fields(rpu: &DoviRpu, compare_l5: bool) -> Vec<(String, i32)> {
+    let mut fields = Vec::new();
+
+    let vdr_dm_data = match &rpu.vdr_dm_data {
+        Some(vdr_dm_data) => vdr_dm_data,
+        None => return fields,
+    };
+
+    if let Some(ExtMetadataBlock::Level1(b)) = vdr_dm_data.get_block(1) {
+        fields.push(("L1.min_pq".to_string(), b.min_pq as i32));
+        fields.push(("L1.max_pq".to_string(), b.max_pq as i32));
+        fields.push(("L1.avg_pq".to_string(), b.avg_pq as i32));
+    }
+
+    for block in vdr_dm_data.level_blocks_iter(2) {
+        if let ExtMetadataBlock::Level2(b) = block {
+            let prefix = format!("L2[{}]", b.target_max_pq);
+
+            fields.extend(
+                [
+                    ("trim_slope", b.trim_slope as i32),
+                    ("trim_offset", b.trim_offset as i32),
+                    ("trim_power", b.trim_power as i32),
+                    ("trim_chroma_weight", b.trim_chroma_weight as i32),
+                    ("trim_saturation_gain", b.trim_saturation_gain as i32),
+                    ("ms_weight", b.ms_weight as i32),
+                ]
+                .into_iter()
+                .map(|(field, value)| (format!("{}.{}", prefix, field), value)),
+            );
+        }
+    }
+
+    if compare_l5 {
+        if let Some(ExtMetadataBlock::Level5(b)) = vdr_dm_data.get_block(5) {
+            let (left, right, top, bottom) = b.get_offsets();
+
+            fields.push(("L5.active_area_left_offset".to_string(), left as i32));
+            fields.push(("L5.active_area_right_offset".to_string(), right as i32));
+            fields.push(("L5.active_area_top_offset".to_string(), top as i32));
+            fields.push(("L5.active_area_bottom_offset".to_string(), bottom as i32));
+        }
+    }
+
+    fields
+}
+
+fn value_str(value: Option<i32>) -> String {
+    value.map_or_else(|| "missing".to_string(), |value| value.to_string())
+}
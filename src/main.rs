@@ -1,7 +1,8 @@
+use std::num::NonZeroU64;
 use std::path::PathBuf;
 
 use anyhow::Result;
-use clap::{Parser, ValueHint};
+use clap::{CommandFactory, ErrorKind, Parser, ValueHint};
 
 #[cfg(test)]
 mod tests;
@@ -11,20 +12,47 @@ use commands::Command;
 
 mod dovi;
 use dovi::{
+    active_area_detector::ActiveAreaDetector,
+    aligner::Aligner,
+    concatenator::Concatenator,
+    conformer::Conformer,
     converter::Converter,
     demuxer::Demuxer,
+    deterministic,
+    differ::Differ,
+    dovi_adder::DoviAdder,
     editor::{EditConfig, Editor},
     exporter::Exporter,
     generator::Generator,
+    hasher::Hasher,
+    l1_measurer::L1Measurer,
+    logger::Logger,
+    lut_generator::LutGenerator,
     muxer::Muxer,
+    parse_buffer_size,
+    patcher::Patcher,
+    plotter::Plotter,
+    previewer::Previewer,
+    remover::Remover,
     rpu_extractor::RpuExtractor,
     rpu_info::RpuInfo,
     rpu_injector::RpuInjector,
-    CliOptions, WriteStartCodePreset,
+    script_processor::SharedRpuProcessor,
+    sei_filter::{SeiFilter, SeiPayload},
+    server::Server,
+    splitter::Splitter,
+    verifier::Verifier,
+    CliOptions, InputCodec, InputFormat, ProgressFormat, WriteStartCodePreset,
 };
 
 #[derive(Parser, Debug)]
-#[clap(name = env!("CARGO_PKG_NAME"), about = "CLI tool combining multiple utilities for working with Dolby Vision", author = "quietvoid", version = env!("CARGO_PKG_VERSION"))]
+#[clap(
+    name = env!("CARGO_PKG_NAME"),
+    about = "CLI tool combining multiple utilities for working with Dolby Vision",
+    author = "quietvoid",
+    version = env!("CARGO_PKG_VERSION"),
+    override_usage = "dovi_tool [OPTIONS] <SUBCOMMAND>\n    dovi_tool --serve"
+)]
 struct Opt {
     #[clap(
         name = "mode",
@@ -35,7 +63,8 @@ struct Opt {
                      Mode 0: Parses the RPU, rewrites it untouched\n  \
                      Mode 1: Converts the RPU to be MEL compatible\n  \
                      Mode 2: Converts the RPU to be profile 8.1 compatible\n  \
-                     Mode 3: Converts profile 5 to 8.1"
+                     Mode 3: Converts profile 5 to 8.1\n  \
+                     Mode 4: Converts to profile 8.4, for a HLG base layer"
     )]
     mode: Option<u8>,
 
@@ -46,9 +75,42 @@ struct Opt {
     )]
     crop: bool,
 
+    #[clap(
+        long,
+        help = "Removes the L6 metadata block from every RPU, instead of only overwriting its values"
+    )]
+    drop_l6: bool,
+
+    #[clap(
+        long,
+        help = "Replaces the polynomial/MMR mapping of every RPU by an identity mapping, keeping the DM metadata"
+    )]
+    remove_mapping: bool,
+
+    #[clap(
+        long,
+        help = "Overrides the source min PQ of every RPU, as a 12-bit PQ value"
+    )]
+    min_pq: Option<u16>,
+
+    #[clap(
+        long,
+        help = "Overrides the source max PQ of every RPU, as a 12-bit PQ value"
+    )]
+    max_pq: Option<u16>,
+
     #[clap(long, help = "Ignore HDR10+ metadata when writing the output HEVC.")]
     drop_hdr10plus: bool,
 
+    #[clap(
+        arg_enum,
+        long,
+        multiple_occurrences = true,
+        use_value_delimiter = true,
+        help = "Drops the selected SEI messages when writing the output HEVC. Comma separated"
+    )]
+    drop_sei: Vec<SeiPayload>,
+
     #[clap(
         long,
         help = "Sets the edit JSON config file to use",
@@ -56,6 +118,18 @@ struct Opt {
     )]
     edit_config: Option<PathBuf>,
 
+    #[clap(
+        long,
+        multiple_occurrences = true,
+        help = "Runs a command on every converted RPU, as JSON lines on its stdin and stdout. See --help for more info",
+        long_help = "Runs a command on every converted RPU, after the other conversion options.\n\
+                     Each RPU is written to the stdin of the command as JSON on a single line, \
+                     and replaced by the RPU on the next line of its stdout. The command must flush its output after every line.\n\
+                     The RPUs are converted in parallel batches, so they are not always received in stream order.\n\
+                     Can be repeated, the commands run in order"
+    )]
+    script: Vec<String>,
+
     #[clap(
         arg_enum,
         long,
@@ -64,42 +138,313 @@ struct Opt {
     )]
     start_code: WriteStartCodePreset,
 
+    #[clap(
+        long,
+        help = "Inserts an AUD NALU at the start of every access unit missing one, when writing the BL or single layer video"
+    )]
+    add_aud: bool,
+
+    #[clap(
+        long,
+        help = "Field coded video: every frame is a pair of field pictures, each with its own RPU. extract-rpu keeps the RPU of the first field, inject-rpu uses each RPU for both fields"
+    )]
+    field_coded: bool,
+
+    #[clap(
+        arg_enum,
+        long,
+        help = "Format of the input HEVC NAL units. See --help for more info",
+        long_help = "Format of the input HEVC NAL units.\n  \
+                     auto: Detects the format from the start of the input\n  \
+                     annex-b: NAL units prefixed by start codes\n  \
+                     hvcc: NAL units prefixed by their 4 bytes length, as in MP4 samples",
+        default_value = "auto"
+    )]
+    input_format: InputFormat,
+
+    #[clap(
+        arg_enum,
+        long,
+        help = "Codec of the input video bitstream. See --help for more info",
+        long_help = "Codec of the input video bitstream.\n  \
+                     auto: AVC for .h264, .264, .avc and .jsv files, VVC for .h266, .266 and .vvc files, HEVC otherwise\n  \
+                     hevc: H.265/HEVC, RPU in UNSPEC62 NAL units\n  \
+                     avc: H.264/AVC (profile 9), RPU in NAL units of type 28\n  \
+                     vvc: H.266/VVC, RPU in NAL units of type 30",
+        default_value = "auto"
+    )]
+    input_codec: InputCodec,
+
+    #[clap(
+        long,
+        help = "Skips N bytes of the input, then resumes at the next random access point. Annex B input only",
+        default_value = "0"
+    )]
+    skip_bytes: u64,
+
+    #[clap(
+        long,
+        help = "Skips at least N frames of the input, then resumes at the next random access point",
+        default_value = "0"
+    )]
+    skip_frames: u64,
+
+    #[clap(
+        long,
+        help = "Stops after the first N decoded frames, then finalizes the outputs. Counted after the skipped frames"
+    )]
+    limit: Option<NonZeroU64>,
+
+    #[clap(
+        arg_enum,
+        long,
+        help = "Progress output. See --help for more info",
+        long_help = "Progress output.\n  \
+                     bar: Progress bar on the terminal\n  \
+                     json: JSON lines on stderr, with the bytes read, frames parsed, RPUs found and ETA",
+        default_value = "bar"
+    )]
+    progress: ProgressFormat,
+
+    #[clap(
+        long,
+        help = "Prints the bytes read, frames, RPUs, throughput and time spent reading, parsing, converting and writing at the end. \
+                A JSON event with --progress json"
+    )]
+    stats: bool,
+
+    #[clap(
+        long,
+        parse(try_from_str = parse_buffer_size),
+        help = "Size of the chunks read from the input, in bytes. Accepts K and M suffixes, at least 64K",
+        default_value = "4M"
+    )]
+    read_buffer: usize,
+
+    #[clap(
+        long,
+        parse(try_from_str = parse_buffer_size),
+        help = "Size of the output buffers, in bytes. Accepts K and M suffixes, at least 64K",
+        default_value = "4M"
+    )]
+    write_buffer: usize,
+
+    #[clap(
+        long,
+        help = "Replaces the unparseable RPUs with the previous frame's RPU, and skips the corrupt NAL units, instead of aborting. \
+                Reported at the end"
+    )]
+    lenient: bool,
+
+    #[clap(
+        long,
+        conflicts_with = "lenient",
+        help = "Fails on the first questionable RPU: reserved bits set, profile changes or inconsistent PQ values. \
+                Reports the frame, field and bit offset"
+    )]
+    strict: bool,
+
+    #[clap(
+        long,
+        help = "Stays resident and runs the extract-rpu, convert and info commands read as JSON lines on stdin. See --help for more info",
+        long_help = "Stays resident and runs the extract-rpu, convert and info commands read as JSON lines on stdin.\n\
+                     Requests:\n  \
+                     {\"id\": 1, \"command\": \"extract-rpu\", \"args\": [\"-i\", \"video.hevc\"], \"options\": [\"-m\", \"2\"]}\n  \
+                     {\"id\": 1, \"command\": \"cancel\"}\n  \
+                     {\"command\": \"quit\"}\n\
+                     The replies are JSON lines on stdout: started, progress, then completed, failed or cancelled"
+    )]
+    serve: bool,
+
+    #[clap(
+        short = 'q',
+        long,
+        conflicts_with = "verbose",
+        help = "Only prints the warnings and errors, without the status messages"
+    )]
+    quiet: bool,
+
+    #[clap(
+        short = 'v',
+        long,
+        parse(from_occurrences),
+        help = "Prints more diagnostics: -v for the debug messages, -vv for the trace messages"
+    )]
+    verbose: u64,
+
+    #[clap(
+        long,
+        help = "Also writes the diagnostics to a file, with their level and the elapsed time",
+        value_hint = ValueHint::FilePath
+    )]
+    log_file: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Guarantees byte-identical outputs across runs and platforms, and prints the SHA-256 of every output file at the end. \
+                The log file has no timestamps"
+    )]
+    deterministic: bool,
+
     #[clap(subcommand)]
-    cmd: Command,
+    cmd: Option<Command>,
 }
 
 fn main() -> Result<()> {
     let opt = Opt::parse();
 
+    Logger::init(
+        opt.quiet,
+        opt.verbose,
+        opt.log_file.as_deref(),
+        opt.deterministic,
+    )?;
+
+    if opt.serve {
+        if opt.cmd.is_some() {
+            Opt::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    "--serve cannot be used with a subcommand",
+                )
+                .exit();
+        }
+
+        return Server::serve(run_job);
+    }
+
+    run(opt)
+}
+
+/// Runs a job of the `--serve` mode, with the JSON progress. Returns the output of `info`
+fn run_job(args: Vec<String>) -> Result<Option<String>> {
+    let mut opt = Opt::try_parse_from(args)?;
+    opt.progress = ProgressFormat::Json;
+
+    match opt.cmd {
+        Some(Command::Info(args)) => {
+            let mut output = Vec::new();
+            RpuInfo::info_to(args, &mut output)?;
+
+            Ok(Some(String::from_utf8_lossy(&output).into_owned()))
+        }
+        _ => run(opt).map(|_| None),
+    }
+}
+
+fn run(opt: Opt) -> Result<()> {
+    let cmd = match opt.cmd {
+        Some(cmd) => cmd,
+        None => Opt::command()
+            .error(
+                ErrorKind::MissingSubcommand,
+                "A subcommand is required, or --serve",
+            )
+            .exit(),
+    };
+
     let edit_config = opt
         .edit_config
-        .as_ref()
+        .as_deref()
         .map(EditConfig::from_path)
         .and_then(Result::ok);
 
+    let rpu_processor = match SharedRpuProcessor::from_scripts(&opt.script) {
+        Ok(rpu_processor) => rpu_processor,
+        Err(e) => Opt::command().error(ErrorKind::InvalidValue, e).exit(),
+    };
+
+    let mut drop_sei = opt.drop_sei;
+    if opt.drop_hdr10plus && !drop_sei.contains(&SeiPayload::Hdr10plus) {
+        drop_sei.push(SeiPayload::Hdr10plus);
+    }
+
     let mut cli_options = CliOptions {
         mode: opt.mode,
         crop: opt.crop,
+        drop_l6: opt.drop_l6,
+        remove_mapping: opt.remove_mapping,
+        min_pq: opt.min_pq,
+        max_pq: opt.max_pq,
+        retarget_trim: None,
         discard_el: false,
-        drop_hdr10plus: opt.drop_hdr10plus,
+        keep_el_prefix: false,
+        keep_rpu_position: false,
+        rewrite_vps: false,
+        el_report: None,
+        repair_rpus: false,
+        stream_rpus: false,
+        crc_mode: None,
+        frame_order: None,
+        normalize: None,
+        lenient: opt.lenient,
+        strict: opt.strict,
+        sei_filter: SeiFilter::new(drop_sei),
+        hdr10_sei: None,
         edit_config,
+        rpu_processor,
         start_code: opt.start_code,
+        add_aud: opt.add_aud || opt.start_code.requires_aud(),
+        field_coded: opt.field_coded,
+        input_format: opt.input_format,
+        input_codec: opt.input_codec,
+        skip_bytes: opt.skip_bytes,
+        skip_frames: opt.skip_frames,
+        limit: opt.limit.map(NonZeroU64::get),
+        progress: opt.progress,
+        stats: opt.stats,
+        read_buffer: opt.read_buffer,
+        write_buffer: opt.write_buffer,
     };
 
-    // Set mode 0 by default if cropping, otherwise it has no effect
-    if cli_options.mode.is_none() && cli_options.crop {
+    // Set mode 0 by default if editing, otherwise it has no effect
+    let edit_rpus = cli_options.crop
+        || cli_options.drop_l6
+        || cli_options.remove_mapping
+        || cli_options.min_pq.is_some()
+        || cli_options.max_pq.is_some();
+
+    if cli_options.mode.is_none() && edit_rpus {
         cli_options.mode = Some(0);
     }
 
-    match opt.cmd {
+    if opt.deterministic {
+        deterministic::enable();
+    }
+
+    let res = match cmd {
+        Command::AddDv(args) => DoviAdder::add_dv(args, cli_options),
+        Command::Align(args) => Aligner::align(args),
+        Command::ApplyPatch(args) => Patcher::apply_patch(args),
+        Command::Concat(args) => Concatenator::concat(args),
+        Command::Conform(args) => Conformer::conform(args),
         Command::Demux(args) => Demuxer::demux(args, cli_options),
+        Command::DetectActiveArea(args) => ActiveAreaDetector::detect(args),
+        Command::Diff(args) => Differ::diff(args),
         Command::Editor(args) => Editor::edit(args),
         Command::Convert(args) => Converter::convert(args, cli_options),
+        Command::CreatePatch(args) => Patcher::create_patch(args),
         Command::ExtractRpu(args) => RpuExtractor::extract_rpu(args, cli_options),
         Command::InjectRpu(args) => RpuInjector::inject_rpu(args, cli_options),
         Command::Info(args) => RpuInfo::info(args),
         Command::Generate(args) => Generator::generate(args),
+        Command::GenerateLut(args) => LutGenerator::generate(args),
+        Command::Hash(args) => Hasher::hash(args),
         Command::Export(args) => Exporter::export(args),
+        Command::MeasureL1(args) => L1Measurer::measure(args),
         Command::Mux(args) => Muxer::mux_el(args, cli_options),
+        Command::Plot(args) => Plotter::plot(args),
+        Command::Preview(args) => Previewer::preview(args),
+        Command::Remove(args) => Remover::remove(args, cli_options),
+        Command::Split(args) => Splitter::split(args),
+        Command::Verify(args) => Verifier::verify(args),
+    };
+
+    res?;
+
+    if opt.deterministic {
+        deterministic::print_output_hashes()?;
     }
+
+    Ok(())
 }
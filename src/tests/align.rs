@@ -0,0 +1,93 @@
+use crate::dovi::aligner::{align_frames, AlignedSegment};
+use crate::dovi::conformer::ConformedFrame;
+
+fn sources(frames: &[ConformedFrame]) -> Vec<usize> {
+    frames.iter().map(|frame| frame.source).collect()
+}
+
+fn scene_cuts(frames: &[ConformedFrame]) -> Vec<usize> {
+    frames
+        .iter()
+        .enumerate()
+        .filter(|(_, frame)| frame.scene_cut)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+const SOURCE_CUTS: &[usize] = &[0, 10, 25, 31, 50, 62, 80];
+
+#[test]
+fn identical() {
+    let alignment = align_frames(SOURCE_CUTS, 90, SOURCE_CUTS, 90).unwrap();
+
+    assert_eq!(alignment.matched_scenes, 7);
+    assert_eq!(sources(&alignment.frames), (0..90).collect::<Vec<_>>());
+    assert_eq!(scene_cuts(&alignment.frames), SOURCE_CUTS);
+}
+
+#[test]
+fn prepended_logo() {
+    // 5 frames before the first scene
+    let target_cuts = [0, 5, 15, 30, 36, 55, 67, 85];
+    let alignment = align_frames(SOURCE_CUTS, 90, &target_cuts, 95).unwrap();
+
+    assert_eq!(
+        alignment.segments,
+        [AlignedSegment {
+            target_start: 0,
+            target_end: 94,
+            source_start: 0,
+            source_end: 89,
+            padded: 5,
+        }]
+    );
+
+    let frames = sources(&alignment.frames);
+    assert_eq!(frames[..7], [0, 0, 0, 0, 0, 0, 1]);
+    assert_eq!(frames[94], 89);
+    assert_eq!(scene_cuts(&alignment.frames), [0, 15, 30, 36, 55, 67, 85]);
+}
+
+#[test]
+fn removed_scene() {
+    // The scene 31-49 is cut from the target
+    let target_cuts = [0, 10, 25, 31, 43, 61];
+    let alignment = align_frames(SOURCE_CUTS, 90, &target_cuts, 71).unwrap();
+
+    assert_eq!(alignment.segments.len(), 2);
+    assert_eq!(alignment.segments[1].target_start, 31);
+    assert_eq!(alignment.segments[1].source_start, 50);
+
+    let frames = sources(&alignment.frames);
+    assert_eq!(frames.len(), 71);
+    assert_eq!(frames[30..32], [30, 50]);
+    assert_eq!(scene_cuts(&alignment.frames), target_cuts);
+}
+
+#[test]
+fn inserted_scene() {
+    // 8 frames inserted before the scene 25
+    let target_cuts = [0, 10, 25, 33, 39, 58, 70, 88];
+    let alignment = align_frames(SOURCE_CUTS, 90, &target_cuts, 98).unwrap();
+
+    assert_eq!(alignment.segments.len(), 2);
+    assert_eq!(alignment.segments[0].padded, 8);
+
+    // The inserted frames duplicate the last frame before them
+    let frames = sources(&alignment.frames);
+    assert_eq!(frames[24..34], [24, 24, 24, 24, 24, 24, 24, 24, 24, 25]);
+    assert_eq!(frames[97], 89);
+    assert_eq!(scene_cuts(&alignment.frames), [0, 10, 33, 39, 58, 70, 88]);
+}
+
+#[test]
+fn trimmed_end() {
+    let alignment = align_frames(SOURCE_CUTS, 90, &[0, 10, 25, 31, 50, 62], 70).unwrap();
+
+    assert_eq!(sources(&alignment.frames), (0..70).collect::<Vec<_>>());
+}
+
+#[test]
+fn no_match() {
+    assert!(align_frames(SOURCE_CUTS, 90, &[0, 7, 14], 21).is_err());
+}
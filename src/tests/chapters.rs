@@ -0,0 +1,72 @@
+use crate::dovi::chapters::Chapters;
+use crate::dovi::timecode::FrameClock;
+
+#[test]
+fn ogm() {
+    let chapters = Chapters::parse(
+        "CHAPTER01=00:00:00.000\n\
+        CHAPTER01NAME=Intro\n\
+        CHAPTER02=00:00:10.000\n\
+        CHAPTER02NAME=Opening Credits - Part 1\n\
+        CHAPTER03=00:01:00.500\n\
+        CHAPTER03NAME=Chapter 03\n",
+    )
+    .unwrap();
+
+    let clock = FrameClock::Fps(24.0);
+
+    assert_eq!(chapters.start_frames(&clock).unwrap(), [0, 240, 1452]);
+
+    assert_eq!(chapters.find("intro"), Some(1));
+    assert_eq!(chapters.find("Opening Credits - Part 1"), Some(2));
+    assert_eq!(chapters.find("Chapter 2"), Some(2));
+    assert_eq!(chapters.find("chapter 03"), Some(3));
+    assert_eq!(chapters.find("Outro"), None);
+
+    assert_eq!(chapters.end_frame(1, &clock).unwrap(), 239);
+    assert!(chapters.end_frame(3, &clock).is_err());
+    assert!(chapters.start_frame(4, &clock).is_err());
+    assert!(chapters.start_frame(0, &clock).is_err());
+}
+
+#[test]
+fn matroska() {
+    let chapters = Chapters::parse(
+        r#"<?xml version="1.0"?>
+<Chapters>
+  <EditionEntry>
+    <ChapterAtom>
+      <ChapterTimeStart>00:00:04.004000000</ChapterTimeStart>
+      <ChapterTimeEnd>00:00:08.008000000</ChapterTimeEnd>
+      <ChapterDisplay>
+        <ChapterString>Second</ChapterString>
+      </ChapterDisplay>
+    </ChapterAtom>
+    <ChapterAtom>
+      <ChapterTimeStart>00:00:00.000000000</ChapterTimeStart>
+      <ChapterDisplay>
+        <ChapterString>First</ChapterString>
+      </ChapterDisplay>
+    </ChapterAtom>
+  </EditionEntry>
+</Chapters>
+"#,
+    )
+    .unwrap();
+
+    let clock = FrameClock::Fps(24000.0 / 1001.0);
+
+    // Sorted by start time
+    assert_eq!(chapters.start_frames(&clock).unwrap(), [0, 96]);
+    assert_eq!(chapters.find("Second"), Some(2));
+
+    assert_eq!(chapters.end_frame(1, &clock).unwrap(), 95);
+    assert_eq!(chapters.end_frame(2, &clock).unwrap(), 191);
+}
+
+#[test]
+fn matroska_missing_start() {
+    let res = Chapters::parse("<Chapters><ChapterAtom></ChapterAtom></Chapters>");
+
+    assert!(res.is_err());
+}
@@ -0,0 +1,51 @@
+use crate::dovi::conformer::{conform_frames, ConformedFrame};
+
+fn sources(frames: &[ConformedFrame]) -> Vec<usize> {
+    frames.iter().map(|frame| frame.source).collect()
+}
+
+fn scene_cuts(frames: &[ConformedFrame]) -> Vec<usize> {
+    frames
+        .iter()
+        .enumerate()
+        .filter(|(_, frame)| frame.scene_cut)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+#[test]
+fn duplicate_25_to_30() {
+    let mut input = vec![false; 10];
+    input[0] = true;
+    input[3] = true;
+
+    let frames = conform_frames(&input, 25.0, 30.0);
+
+    // Every fifth frame is duplicated
+    assert_eq!(sources(&frames), [0, 1, 2, 2, 3, 4, 5, 6, 7, 7, 8, 9]);
+    assert_eq!(scene_cuts(&frames), [0, 4]);
+}
+
+#[test]
+fn drop_30_to_24() {
+    let mut input = vec![false; 10];
+    input[0] = true;
+    input[2] = true;
+    input[8] = true;
+
+    let frames = conform_frames(&input, 30.0, 24.0);
+
+    // One frame out of five is dropped, the scene cuts of dropped frames move to the next one
+    assert_eq!(sources(&frames), [0, 1, 3, 4, 5, 6, 8, 9]);
+    assert_eq!(scene_cuts(&frames), [0, 2, 6]);
+}
+
+#[test]
+fn ntsc_rates() {
+    let input = vec![false; 1001];
+    let frames = conform_frames(&input, 24000.0 / 1001.0, 24.0);
+
+    assert_eq!(frames.len(), 1002);
+    assert_eq!(frames.last().unwrap().source, 1000);
+    assert!(frames.windows(2).all(|w| w[1].source >= w[0].source));
+}
@@ -0,0 +1,55 @@
+use std::io::{Cursor, Read};
+
+use anyhow::Result;
+
+use crate::dovi::codec::Codec;
+use crate::dovi::frame_limit::FrameLimitReader;
+
+#[test]
+fn ends_at_access_unit() -> Result<()> {
+    let data = std::fs::read("assets/hevc_tests/regular.hevc")?;
+    let mut reader = FrameLimitReader::new(
+        Box::new(Cursor::new(data.clone())),
+        Codec::Hevc,
+        10,
+        100_000,
+    );
+
+    let mut read_data = Vec::new();
+    reader.read_to_end(&mut read_data)?;
+
+    assert!(read_data.len() < data.len());
+    assert!(data.starts_with(&read_data));
+
+    // Stopped before the AUD of the 11th frame, with its 4 bytes start code
+    assert_eq!(
+        &data[read_data.len()..read_data.len() + 6],
+        &[0, 0, 0, 1, 0x46, 0x01]
+    );
+
+    let aud_count = read_data
+        .windows(5)
+        .filter(|w| w == &[0, 0, 1, 0x46, 0x01])
+        .count();
+    assert_eq!(aud_count, 10);
+
+    Ok(())
+}
+
+#[test]
+fn limit_above_frame_count() -> Result<()> {
+    let data = std::fs::read("assets/hevc_tests/regular.hevc")?;
+    let mut reader = FrameLimitReader::new(
+        Box::new(Cursor::new(data.clone())),
+        Codec::Hevc,
+        1000,
+        100_000,
+    );
+
+    let mut read_data = Vec::new();
+    reader.read_to_end(&mut read_data)?;
+
+    assert_eq!(read_data, data);
+
+    Ok(())
+}
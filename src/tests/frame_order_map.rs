@@ -0,0 +1,43 @@
+use anyhow::Result;
+
+use crate::dovi::frame_order_map::FrameOrderMap;
+
+#[test]
+fn presentation_indices() -> Result<()> {
+    let map = FrameOrderMap::parse("0\n3\n1\n2\n")?;
+
+    assert_eq!(map.len(), 4);
+    assert_eq!(map.presentation_number(0), Some(0));
+    assert_eq!(map.presentation_number(1), Some(3));
+    assert_eq!(map.presentation_number(2), Some(1));
+    assert_eq!(map.presentation_number(3), Some(2));
+    assert_eq!(map.presentation_number(4), None);
+
+    Ok(())
+}
+
+#[test]
+fn ffprobe_pts() -> Result<()> {
+    // Timestamps not starting at zero, trailing CSV fields and comments
+    let map = FrameOrderMap::parse("# pts\n1001,\n4004,\n\n2002,\n3003,\n")?;
+
+    assert_eq!(map.len(), 4);
+    assert_eq!(map.presentation_number(1), Some(3));
+    assert_eq!(map.presentation_number(2), Some(1));
+
+    Ok(())
+}
+
+#[test]
+fn invalid_values() {
+    let err = FrameOrderMap::parse("0\n2\n2\n").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid frame order: decoded frames 1 and 2 have the same value 2"
+    );
+
+    let err = FrameOrderMap::parse("0\nN/A\n").unwrap_err();
+    assert_eq!(err.to_string(), "Invalid frame order, line 2: N/A");
+
+    assert!(FrameOrderMap::parse("# empty\n").is_err());
+}
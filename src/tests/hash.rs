@@ -0,0 +1,58 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use dolby_vision::rpu::dovi_rpu::DoviRpu;
+use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlock;
+
+use super::rpu::_parse_file;
+use crate::dovi::hasher::Hasher;
+
+#[test]
+fn independent_of_block_order() -> Result<()> {
+    let (original_data, dovi_rpu) =
+        _parse_file(PathBuf::from("./assets/tests/unordered_l8_blocks.bin"))?;
+
+    // Same metadata, encoded with the extension blocks in reverse order
+    let mut reordered_rpu = dovi_rpu.clone();
+    reordered_rpu.modified = true;
+
+    if let Some(vdr_dm_data) = reordered_rpu.vdr_dm_data.as_mut() {
+        // CM v2.9 and CM v4.0 blocks
+        for level in [1, 8] {
+            if let Some(blocks) = vdr_dm_data.metadata_blocks_mut(level) {
+                blocks.reverse();
+            }
+        }
+    }
+
+    let reordered_data = reordered_rpu.write_hevc_unspec62_nalu()?;
+    assert_ne!(&original_data[4..], &reordered_data[2..]);
+
+    let reparsed_rpu = DoviRpu::parse_unspec62_nalu(&reordered_data)?;
+    assert_eq!(
+        Hasher::rpu_hash(&dovi_rpu)?,
+        Hasher::rpu_hash(&reparsed_rpu)?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn changes_with_metadata() -> Result<()> {
+    let (_, dovi_rpu) = _parse_file(PathBuf::from("./assets/tests/profile8.bin"))?;
+
+    let mut edited_rpu = dovi_rpu.clone();
+
+    if let Some(ExtMetadataBlock::Level1(l1)) = edited_rpu
+        .vdr_dm_data
+        .as_mut()
+        .and_then(|vdr_dm_data| vdr_dm_data.get_block_mut(1))
+    {
+        l1.max_pq += 1;
+    }
+
+    assert_ne!(Hasher::rpu_hash(&dovi_rpu)?, Hasher::rpu_hash(&edited_rpu)?);
+
+    Ok(())
+}
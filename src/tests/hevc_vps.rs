@@ -0,0 +1,48 @@
+use anyhow::Result;
+
+use crate::dovi::hevc_vps::{base_layer_vps, VpsInfo};
+
+/// VPS of the `regular` assets, describing the base layer only
+const BASE_VPS: &str = "40010c01ffff222000000300900000030000030099959809";
+
+/// Same VPS describing two layers, with a layer set including both and extension data
+const LAYERED_VPS: &str = "40010c11ffff222000000300900000030000030099959815bf4b";
+
+fn from_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+#[test]
+fn parse() -> Result<()> {
+    let base = VpsInfo::parse(&from_hex(BASE_VPS))?;
+    assert!(!base.has_layers());
+
+    let layered = VpsInfo::parse(&from_hex(LAYERED_VPS))?;
+    assert_eq!(
+        layered,
+        VpsInfo {
+            vps_id: 0,
+            max_layers_minus1: 1,
+            max_layer_id: 1,
+            num_layer_sets_minus1: 1,
+            extension_flag: true,
+        }
+    );
+    assert!(layered.has_layers());
+
+    Ok(())
+}
+
+#[test]
+fn rewrite_base_layer() -> Result<()> {
+    let rewritten = base_layer_vps(&from_hex(LAYERED_VPS))?;
+    assert_eq!(rewritten, from_hex(BASE_VPS));
+
+    // Unchanged when describing the base layer only
+    assert_eq!(base_layer_vps(&from_hex(BASE_VPS))?, from_hex(BASE_VPS));
+
+    Ok(())
+}
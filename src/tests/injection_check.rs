@@ -0,0 +1,40 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use utilities_dovi::parse_rpu_file;
+
+use crate::dovi::codec::Codec;
+use crate::dovi::injection_check::InjectionCheck;
+use crate::dovi::DEFAULT_READ_BUFFER;
+
+#[test]
+fn injected_rpus() -> Result<()> {
+    let injected = Path::new("./assets/hevc_tests/regular_start_code_4.hevc");
+    let rpus = parse_rpu_file(Path::new("./assets/hevc_tests/regular_rpu.bin"))?.unwrap();
+
+    InjectionCheck::check(injected, Codec::Hevc, &rpus, DEFAULT_READ_BUFFER)?;
+
+    Ok(())
+}
+
+#[test]
+fn different_rpus() -> Result<()> {
+    let injected = Path::new("./assets/hevc_tests/regular_start_code_4.hevc");
+    let mut rpus = parse_rpu_file(Path::new("./assets/hevc_tests/regular_rpu.bin"))?.unwrap();
+
+    // Presentation frame 10 with the RPU of frame 0, one RPU missing at the end
+    rpus[10] = rpus[0].clone();
+    rpus.pop();
+
+    let err = InjectionCheck::check(injected, Codec::Hevc, &rpus, DEFAULT_READ_BUFFER)
+        .unwrap_err()
+        .to_string();
+
+    assert!(err.contains("mismatched frame count: output 259 frames, 258 RPUs injected"));
+    assert!(err.contains("the RPU differs from the injected RPU of presentation frame 10"));
+    assert!(err.contains("no RPU was injected for presentation frame 258"));
+    assert!(err.contains("inject-rpu: verification failed, 3 issues found"));
+
+    Ok(())
+}
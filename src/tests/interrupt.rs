@@ -0,0 +1,44 @@
+use std::io::{Cursor, Read};
+
+use anyhow::Result;
+
+use crate::dovi::codec::Codec;
+use crate::dovi::interrupt::{interrupt, InterruptibleReader};
+
+#[test]
+fn ends_at_access_unit() -> Result<()> {
+    let data = std::fs::read("assets/hevc_tests/regular.hevc")?;
+    let mut reader =
+        InterruptibleReader::new(Box::new(Cursor::new(data.clone())), Codec::Hevc, 100_000);
+
+    let mut read_data = vec![0; 20_000];
+    reader.read_exact(&mut read_data)?;
+
+    interrupt();
+    reader.read_to_end(&mut read_data)?;
+
+    assert!(read_data.len() > 20_000 && read_data.len() < data.len());
+    assert!(data.starts_with(&read_data));
+
+    // Stopped before the AUD of the next frame, with its 4 bytes start code
+    assert_eq!(
+        &data[read_data.len()..read_data.len() + 6],
+        &[0, 0, 0, 1, 0x46, 0x01]
+    );
+
+    // Same end with start codes and NAL headers split across the reads
+    for chunk_size in [1, 2, 3, 5, 7] {
+        let mut reader = InterruptibleReader::new(
+            Box::new(Cursor::new(data[20_000..].to_vec())),
+            Codec::Hevc,
+            chunk_size,
+        );
+
+        let mut small_reads = data[..20_000].to_vec();
+        reader.read_to_end(&mut small_reads)?;
+
+        assert_eq!(small_reads, read_data);
+    }
+
+    Ok(())
+}
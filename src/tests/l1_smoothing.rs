@@ -0,0 +1,66 @@
+use anyhow::Result;
+
+use crate::dovi::l1_smoothing::L1Smoothing;
+
+#[test]
+fn median_removes_spikes() -> Result<()> {
+    let smoothing: L1Smoothing = serde_json::from_str(r#"{ "method": "median", "window": 3 }"#)?;
+    smoothing.validate()?;
+
+    let values = [
+        [0, 1000, 2000],
+        [0, 1000, 3500],
+        [0, 1010, 2000],
+        [5, 1020, 2010],
+    ];
+    let smoothed = smoothing.smooth_values(&values);
+
+    assert_eq!(
+        smoothed,
+        [
+            [0, 1000, 2000],
+            [0, 1000, 2000],
+            [0, 1010, 2010],
+            [0, 1010, 2000]
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn average_per_scene() -> Result<()> {
+    let smoothing: L1Smoothing = serde_json::from_str(r#"{ "method": "average" }"#)?;
+
+    let smoothed = smoothing.smooth_values(&[[0, 1000, 2000], [2, 1001, 3000]]);
+    assert_eq!(smoothed, [[1, 1001, 2500], [1, 1001, 2500]]);
+
+    Ok(())
+}
+
+#[test]
+fn short_scenes_merged() -> Result<()> {
+    let smoothing: L1Smoothing =
+        serde_json::from_str(r#"{ "method": "average", "min_scene_length": 3 }"#)?;
+
+    // Scenes at 0, 4, 5 and 6
+    let scene_cuts = [true, false, false, false, true, true, true, false, false];
+
+    assert_eq!(smoothing.scene_groups(&scene_cuts), [0..6, 6..9]);
+
+    let smoothing: L1Smoothing = serde_json::from_str(r#"{ "method": "average" }"#)?;
+    assert_eq!(
+        smoothing.scene_groups(&scene_cuts),
+        [0..4, 4..5, 5..6, 6..9]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn invalid_window() -> Result<()> {
+    let smoothing: L1Smoothing = serde_json::from_str(r#"{ "method": "median", "window": 4 }"#)?;
+    assert!(smoothing.validate().is_err());
+
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use std::io::{Cursor, ErrorKind, Read};
+
+use anyhow::Result;
+
+use crate::dovi::length_prefixed::{LengthPrefixedReader, MAX_NAL_SIZE};
+
+#[test]
+fn converts_to_annexb() -> Result<()> {
+    let data = vec![0, 0, 0, 3, 0x40, 0x01, 0x0C, 0, 0, 0, 2, 0x46, 0x01];
+    let len = data.len() as u64;
+
+    let mut reader = LengthPrefixedReader::new(Cursor::new(data), Some(len));
+
+    let mut annexb = Vec::new();
+    reader.read_to_end(&mut annexb)?;
+
+    assert_eq!(
+        annexb,
+        vec![0, 0, 0, 1, 0x40, 0x01, 0x0C, 0, 0, 0, 1, 0x46, 0x01]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn length_past_input_end() {
+    let data = vec![0, 0, 0, 3, 0x40, 0x01, 0x0C, 0, 0, 0x10, 0, 0x46, 0x01];
+    let len = data.len() as u64;
+
+    let mut reader = LengthPrefixedReader::new(Cursor::new(data), Some(len));
+    let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!(
+        err.to_string(),
+        "Invalid NAL unit length 4096, the input is corrupt"
+    );
+}
+
+#[test]
+fn length_above_max_unknown_size() {
+    let size = (MAX_NAL_SIZE as u32 + 1).to_be_bytes();
+    let data = [&size[..], &[0x40, 0x01]].concat();
+
+    // Rejected before allocating the NAL unit
+    let mut reader = LengthPrefixedReader::new(Cursor::new(data), None);
+    let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+}
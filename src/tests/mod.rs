@@ -1 +1,19 @@
+mod align;
+mod chapters;
+mod conform;
+mod frame_limit;
+mod frame_order_map;
+mod hash;
+mod hevc_vps;
+mod injection_check;
+mod interrupt;
+mod l1_smoothing;
+mod length_prefixed;
+mod retarget_trim;
 mod rpu;
+mod sei_filter;
+mod sha256;
+mod start_code;
+mod strict;
+mod threaded_writer;
+mod timestamps;
@@ -0,0 +1,66 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use dolby_vision::rpu::dovi_rpu::DoviRpu;
+use dolby_vision::rpu::extension_metadata::blocks::{ExtMetadataBlock, ExtMetadataBlockLevel2};
+
+use utilities_dovi::parse_rpu_file;
+
+use crate::dovi::editor::retarget_level2_trim;
+
+fn level2_trims(rpu: &DoviRpu) -> Vec<ExtMetadataBlockLevel2> {
+    rpu.vdr_dm_data
+        .as_ref()
+        .unwrap()
+        .level_blocks_iter(2)
+        .filter_map(|block| match block {
+            ExtMetadataBlock::Level2(level2) => Some(level2.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn first_rpu() -> Result<DoviRpu> {
+    let rpus = parse_rpu_file(Path::new("./assets/tests/cmv2_9_xml_with_l5_rpu.bin"))?;
+
+    Ok(rpus.unwrap().remove(0))
+}
+
+#[test]
+fn interpolated_trim() -> Result<()> {
+    // Trims for 100, 600 and 1000 nits
+    let mut dovi_rpu = first_rpu()?;
+    assert_eq!(level2_trims(&dovi_rpu).len(), 3);
+
+    retarget_level2_trim(&mut dovi_rpu, 300)?;
+    assert!(dovi_rpu.modified);
+
+    let trims = level2_trims(&dovi_rpu);
+    assert_eq!(trims.len(), 4);
+
+    let trim = trims.iter().find(|t| t.target_max_pq == 2547).unwrap();
+    assert_eq!(trim.trim_slope, 2041);
+    assert_eq!(trim.ms_weight, 2048);
+
+    // Encodes fine
+    dovi_rpu.write_hevc_unspec62_nalu()?;
+
+    Ok(())
+}
+
+#[test]
+fn existing_trim() -> Result<()> {
+    let mut dovi_rpu = first_rpu()?;
+
+    retarget_level2_trim(&mut dovi_rpu, 600)?;
+    assert!(!dovi_rpu.modified);
+    assert_eq!(level2_trims(&dovi_rpu).len(), 3);
+
+    // At the source peak, no trim is needed
+    retarget_level2_trim(&mut dovi_rpu, 4000)?;
+    assert!(!dovi_rpu.modified);
+    assert_eq!(level2_trims(&dovi_rpu).len(), 3);
+
+    Ok(())
+}
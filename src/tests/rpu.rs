@@ -3,10 +3,12 @@ use std::{io::Read, path::PathBuf};
 
 use anyhow::Result;
 
+use bitvec_helpers::bitvec_reader::BitVecReader;
 use dolby_vision::rpu::dovi_rpu::DoviRpu;
 use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlock;
 use dolby_vision::rpu::extension_metadata::{ColorPrimaries, MasteringDisplayPrimaries};
 use dolby_vision::rpu::generate::GenerateConfig;
+use dolby_vision::rpu::rpu_data_nlq::NlqParams;
 use dolby_vision::rpu::{FEL_STR, MEL_STR};
 use hevc_parser::hevc::{NALUnit, NAL_UNSPEC62};
 
@@ -164,6 +166,69 @@ fn fel_to_profile8() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn fel_remove_nlq() -> Result<()> {
+    let (_, mut dovi_rpu) = _parse_file(PathBuf::from("./assets/tests/fel_orig.bin"))?;
+    let mapping = dovi_rpu.rpu_data_mapping.clone();
+
+    dovi_rpu.remove_nlq()?;
+    assert_eq!(dovi_rpu.dovi_profile, 8);
+    assert!(dovi_rpu.subprofile.is_none());
+
+    let parsed_data = dovi_rpu.write_hevc_unspec62_nalu()?;
+    let parsed_rpu = DoviRpu::parse_unspec62_nalu(&parsed_data)?;
+
+    assert_eq!(parsed_rpu.dovi_profile, 8);
+    assert!(parsed_rpu.rpu_data_nlq.is_none());
+    assert_eq!(
+        format!("{:?}", parsed_rpu.rpu_data_mapping),
+        format!("{:?}", mapping)
+    );
+
+    // Already without NLQ
+    assert!(dovi_rpu.remove_nlq().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn fel_set_nlq_params() -> Result<()> {
+    let (_, mut dovi_rpu) = _parse_file(PathBuf::from("./assets/tests/fel_orig.bin"))?;
+
+    let params = NlqParams {
+        nlq_offset: Some([500, 510, 520]),
+        linear_deadzone_threshold_int: Some([1, 2, 3]),
+        linear_deadzone_threshold: Some([1024, 1024, 1024]),
+        ..Default::default()
+    };
+
+    dovi_rpu.set_nlq_params(&params)?;
+    assert_eq!(dovi_rpu.subprofile.as_ref().unwrap(), FEL_STR);
+
+    let parsed_data = dovi_rpu.write_hevc_unspec62_nalu()?;
+    let parsed_rpu = DoviRpu::parse_unspec62_nalu(&parsed_data)?;
+    let nlq = parsed_rpu.rpu_data_nlq.as_ref().unwrap();
+
+    assert_eq!(nlq.nlq_offset, vec![[500, 510, 520]]);
+    assert_eq!(nlq.linear_deadzone_slope_int, vec![[0, 0, 0]]);
+    assert_eq!(nlq.linear_deadzone_slope, vec![[2048, 2048, 2048]]);
+    assert_eq!(nlq.linear_deadzone_threshold_int, vec![[1, 2, 3]]);
+    assert_eq!(nlq.linear_deadzone_threshold, vec![[1024, 1024, 1024]]);
+
+    // 10 bit EL
+    let invalid = NlqParams {
+        nlq_offset: Some([1024, 0, 0]),
+        ..Default::default()
+    };
+    assert!(dovi_rpu.set_nlq_params(&invalid).is_err());
+
+    // No NLQ in profile 8
+    let (_, mut p8_rpu) = _parse_file(PathBuf::from("./assets/tests/profile8.bin"))?;
+    assert!(p8_rpu.set_nlq_params(&params).is_err());
+
+    Ok(())
+}
+
 #[test]
 fn mel_conversions() -> Result<()> {
     let (original_data, mut dovi_rpu) = _parse_file(PathBuf::from("./assets/tests/mel_orig.bin"))?;
@@ -548,6 +613,16 @@ fn generate_default_cmv29() -> Result<()> {
         canvas_height: None,
         madvr_file: None,
         use_custom_targets: false,
+        scene_file: None,
+        hlg: false,
+        length: None,
+        static_content: false,
+        l1: None,
+        l5: None,
+        l6: None,
+        fps: None,
+        timestamps: None,
+        chapters: None,
     };
 
     let mut generator = Generator::from_args(args)?;
@@ -598,6 +673,16 @@ fn generate_default_cmv40() -> Result<()> {
         canvas_height: None,
         madvr_file: None,
         use_custom_targets: false,
+        scene_file: None,
+        hlg: false,
+        length: None,
+        static_content: false,
+        l1: None,
+        l5: None,
+        l6: None,
+        fps: None,
+        timestamps: None,
+        chapters: None,
     };
 
     let mut generator = Generator::from_args(args)?;
@@ -659,6 +744,16 @@ fn generate_full() -> Result<()> {
         canvas_height: None,
         madvr_file: None,
         use_custom_targets: false,
+        scene_file: None,
+        hlg: false,
+        length: None,
+        static_content: false,
+        l1: None,
+        l5: None,
+        l6: None,
+        fps: None,
+        timestamps: None,
+        chapters: None,
     };
 
     let mut generator = Generator::from_args(args)?;
@@ -744,6 +839,16 @@ fn generate_full_hdr10plus() -> Result<()> {
         canvas_height: None,
         madvr_file: None,
         use_custom_targets: false,
+        scene_file: None,
+        hlg: false,
+        length: None,
+        static_content: false,
+        l1: None,
+        l5: None,
+        l6: None,
+        fps: None,
+        timestamps: None,
+        chapters: None,
     };
 
     let mut generator = Generator::from_args(args)?;
@@ -1042,3 +1147,187 @@ fn trailing_bytes_rpu() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn profile8_to_p84() -> Result<()> {
+    let (_, mut dovi_rpu) = _parse_file(PathBuf::from("./assets/tests/profile8.bin"))?;
+    assert_eq!(dovi_rpu.dovi_profile, 8);
+
+    dovi_rpu.convert_with_mode(4)?;
+    let parsed_data = dovi_rpu.write_hevc_unspec62_nalu()?;
+
+    let dovi_rpu = DoviRpu::parse_unspec62_nalu(&parsed_data)?;
+    assert_eq!(dovi_rpu.dovi_profile, 8);
+
+    let header = &dovi_rpu.header;
+    assert_eq!(header.num_pivots_minus_2, [7, 0, 0]);
+    assert_eq!(header.pred_pivot_value[0].iter().sum::<u64>(), 1023);
+
+    let mapping = dovi_rpu.rpu_data_mapping.as_ref().unwrap();
+    let denom = (1 << header.coefficient_log2_denom) as f64;
+
+    // Evaluates the luma mapping for a 10 bit codeword
+    let map_luma = |codeword: u64| {
+        let mut pivot = header.pred_pivot_value[0][0];
+        let mut pivot_idx = 0;
+
+        for (i, delta) in header.pred_pivot_value[0].iter().enumerate().skip(1) {
+            if codeword < pivot + delta || i == header.pred_pivot_value[0].len() - 1 {
+                pivot_idx = i - 1;
+                break;
+            }
+
+            pivot += delta;
+        }
+
+        let x = codeword as f64 / 1024.0;
+
+        mapping.poly_coef_int[0][pivot_idx]
+            .iter()
+            .zip(&mapping.poly_coef[0][pivot_idx])
+            .enumerate()
+            .map(|(i, (int, frac))| (*int as f64 + *frac as f64 / denom) * x.powi(i as i32))
+            .sum::<f64>()
+            * 1024.0
+    };
+
+    // Black
+    assert!((map_luma(64) - 64.0).abs() < 2.0);
+
+    // HLG reference white (75%) is 203 nits, PQ 58%
+    let ref_white = map_luma(64 + (0.75 * 876.0) as u64);
+    let expected = 64.0 + dolby_vision::utils::nits_to_pq(203.0) * 876.0;
+    assert!((ref_white - expected).abs() < 2.0);
+
+    // Peak is 1000 nits
+    let peak = map_luma(940);
+    let expected = 64.0 + dolby_vision::utils::nits_to_pq(1000.0) * 876.0;
+    assert!((peak - expected).abs() < 2.0);
+
+    Ok(())
+}
+
+#[test]
+fn invalid_crc32_rpu() -> Result<()> {
+    let (original_data, _) = _parse_file(PathBuf::from("./assets/tests/profile8.bin"))?;
+
+    let mut corrupt_data = original_data.clone();
+    let crc32_end = corrupt_data.iter().rposition(|b| *b == 0x80).unwrap();
+    corrupt_data[crc32_end - 1] ^= 0xFF;
+
+    assert!(DoviRpu::parse_unspec62_nalu(&corrupt_data).is_err());
+
+    let dovi_rpu = DoviRpu::parse_unspec62_nalu_ignore_crc32(&corrupt_data)?;
+    assert!(dovi_rpu.modified);
+
+    // Writing recomputes the CRC32
+    let parsed_data = dovi_rpu.write_hevc_unspec62_nalu()?;
+    assert_eq!(&original_data[4..], &parsed_data[2..]);
+
+    Ok(())
+}
+
+#[test]
+fn spec_issues_valid() -> Result<()> {
+    for file in [
+        "profile8.bin",
+        "profile5.bin",
+        "fel_orig.bin",
+        "cmv40_full_rpu.bin",
+    ] {
+        let (_, dovi_rpu) = _parse_file(PathBuf::from("./assets/tests").join(file))?;
+        assert!(dovi_rpu.spec_issues()?.is_empty(), "{}", file);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn spec_issues_bit_offsets() -> Result<()> {
+    let (_, mut dovi_rpu) = _parse_file(PathBuf::from("./assets/tests/profile8.bin"))?;
+
+    dovi_rpu.modified = true;
+    dovi_rpu.header.reserved_zero_3bits = 4;
+
+    let vdr_dm_data = dovi_rpu.vdr_dm_data.as_mut().unwrap();
+    vdr_dm_data.source_min_pq = 3000;
+    vdr_dm_data.source_max_pq = 2000;
+
+    if let Some(ExtMetadataBlock::Level1(level1)) = vdr_dm_data.get_block_mut(1) {
+        level1.avg_pq = level1.max_pq + 10;
+    } else {
+        panic!("No L1 metadata");
+    }
+
+    if let Some(ExtMetadataBlock::Level6(level6)) = vdr_dm_data.get_block_mut(6) {
+        level6.max_content_light_level = 500;
+        level6.max_frame_average_light_level = 800;
+    } else {
+        panic!("No L6 metadata");
+    }
+
+    let dovi_rpu = DoviRpu::parse_rpu(&dovi_rpu.write_rpu()?)?;
+    let data = dovi_rpu.write_rpu()?;
+
+    let issues = dovi_rpu.spec_issues()?;
+    let fields = issues.iter().map(|issue| issue.field).collect::<Vec<_>>();
+    assert_eq!(
+        fields,
+        [
+            "header.reserved_zero_3bits",
+            "vdr_dm_data.source_min_pq",
+            "L1.avg_pq",
+            "L6.max_frame_average_light_level",
+        ]
+    );
+
+    // The offsets point to the values
+    let read_bits = |offset: usize, n: usize| -> u16 {
+        let mut reader = BitVecReader::new(data.clone());
+        reader.skip_n(offset);
+        reader.get_n(n)
+    };
+
+    let vdr_dm_data = dovi_rpu.vdr_dm_data.as_ref().unwrap();
+    let level1 = match vdr_dm_data.get_block(1) {
+        Some(ExtMetadataBlock::Level1(level1)) => level1,
+        _ => unreachable!(),
+    };
+
+    assert_eq!(read_bits(issues[0].bit_offset, 3), 4);
+    assert_eq!(read_bits(issues[1].bit_offset, 12), 3000);
+    assert_eq!(read_bits(issues[2].bit_offset, 12), level1.avg_pq);
+    assert_eq!(read_bits(issues[3].bit_offset, 16), 800);
+
+    Ok(())
+}
+
+#[test]
+fn serde_roundtrip() -> Result<()> {
+    let files = [
+        "profile4.bin",
+        "profile5.bin",
+        "profile8.bin",
+        "fel_rpu.bin",
+        "mel_rpu.bin",
+        "data_before_crc32.bin",
+        "cmv40_full_rpu.bin",
+        "mel_variable_l8_length13.bin",
+        "empty_dmv1_blocks.bin",
+    ];
+
+    for file in files {
+        let (original_data, dovi_rpu) = _parse_file(PathBuf::from("./assets/tests/").join(file))?;
+
+        let json = serde_json::to_string(&dovi_rpu)?;
+        let deserialized: DoviRpu = serde_json::from_str(&json)?;
+
+        assert_eq!(serde_json::to_string(&deserialized)?, json, "{}", file);
+        assert!(deserialized.modified);
+
+        let parsed_data = deserialized.write_hevc_unspec62_nalu()?;
+        assert_eq!(&original_data[4..], &parsed_data[2..], "{}", file);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use anyhow::Result;
+
+use hevc_parser::hevc::NAL_SEI_PREFIX;
+
+use crate::dovi::sei_filter::{sei_payloads, SeiFilter, SeiPayload};
+
+const SEI_HEADER: [u8; 2] = [NAL_SEI_PREFIX << 1, 0x01];
+
+fn sei_nal(escaped_messages: &[u8]) -> Vec<u8> {
+    let mut data = SEI_HEADER.to_vec();
+    data.extend_from_slice(escaped_messages);
+    data.push(0x80);
+
+    data
+}
+
+/// The payload type 128 (structure of pictures info) is coded as 0x80, like the trailing bits
+#[test]
+fn payload_type_128() -> Result<()> {
+    // SOP info, then content light level info
+    let data = sei_nal(&[0x80, 0x02, 0x12, 0x34, 0x90, 0x04, 0x03, 0xE8, 0x01, 0x90]);
+
+    assert_eq!(
+        sei_payloads(&data)?,
+        vec![(128, vec![0x12, 0x34]), (144, vec![0x03, 0xE8, 0x01, 0x90])]
+    );
+
+    let filter = SeiFilter::new(vec![SeiPayload::Cll]);
+    let filtered = filter.filter_nal(NAL_SEI_PREFIX, &data)?.unwrap();
+
+    assert_eq!(filtered.as_ref(), sei_nal(&[0x80, 0x02, 0x12, 0x34]));
+
+    // The SOP info is kept when it's the only message
+    let data = sei_nal(&[0x80, 0x02, 0x12, 0x34]);
+    assert_eq!(sei_payloads(&data)?, vec![(128, vec![0x12, 0x34])]);
+
+    Ok(())
+}
+
+/// The rewritten NAL unit is escaped up to the trailing bits
+#[test]
+fn escaped_rewrite() -> Result<()> {
+    // User data ending with 0x000001, then content light level info
+    let data = sei_nal(&[
+        0x05, 0x03, 0x00, 0x00, 0x03, 0x01, 0x90, 0x04, 0x03, 0xE8, 0x01, 0x90,
+    ]);
+
+    assert_eq!(sei_payloads(&data)?[0], (5, vec![0x00, 0x00, 0x01]));
+
+    let filter = SeiFilter::new(vec![SeiPayload::Cll]);
+    let filtered = filter.filter_nal(NAL_SEI_PREFIX, &data)?.unwrap();
+
+    assert_eq!(
+        filtered.as_ref(),
+        sei_nal(&[0x05, 0x03, 0x00, 0x00, 0x03, 0x01])
+    );
+
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use crate::dovi::sha256::{to_hex, Sha256};
+
+fn hex_digest(data: &[u8]) -> String {
+    let mut sha256 = Sha256::new();
+    sha256.update(data);
+
+    to_hex(&sha256.finalize())
+}
+
+#[test]
+fn known_digests() {
+    assert_eq!(
+        hex_digest(b""),
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    );
+    assert_eq!(
+        hex_digest(b"abc"),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+
+    // Two blocks with the padding
+    assert_eq!(
+        hex_digest(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+        "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+    );
+}
+
+#[test]
+fn chunked_updates() {
+    let data: Vec<u8> = (0..1000_u32).map(|i| (i % 251) as u8).collect();
+
+    let mut sha256 = Sha256::new();
+    for chunk in data.chunks(37) {
+        sha256.update(chunk);
+    }
+
+    assert_eq!(to_hex(&sha256.finalize()), hex_digest(&data));
+
+    let million_a = vec![b'a'; 1_000_000];
+    assert_eq!(
+        hex_digest(&million_a),
+        "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+    );
+}
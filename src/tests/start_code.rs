@@ -0,0 +1,52 @@
+use crate::dovi::start_code::get_offsets;
+
+/// Offsets of every `00 00 01` sequence, they can never overlap
+fn naive_offsets(data: &[u8]) -> Vec<usize> {
+    data.windows(3)
+        .enumerate()
+        .filter(|(_, w)| *w == [0, 0, 1])
+        .map(|(i, _)| i)
+        .collect()
+}
+
+#[test]
+fn matches_naive_scan() {
+    // Start codes at every alignment, including the SIMD block boundaries and the tail
+    let mut data = Vec::new();
+    let mut seed: u32 = 1;
+
+    for i in 0..2000 {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+
+        match i % 37 {
+            0 => data.extend_from_slice(&[0, 0, 1]),
+            5 => data.extend_from_slice(&[0, 0, 0, 1]),
+            // Zeros and ones without a start code
+            11 => data.extend_from_slice(&[0, 1, 1, 0]),
+            _ => data.push((seed >> 16) as u8 % 4),
+        }
+    }
+
+    let mut offsets = Vec::new();
+
+    for len in (data.len() - 70)..=data.len() {
+        let expected = naive_offsets(&data[..len]);
+        get_offsets(&data[..len], &mut offsets);
+
+        assert!(!expected.is_empty());
+        assert_eq!(offsets, expected);
+    }
+}
+
+#[test]
+fn block_boundaries() {
+    let mut offsets = Vec::new();
+
+    for start in 0..70 {
+        let mut data = vec![0xFF; 73];
+        data[start..start + 3].copy_from_slice(&[0, 0, 1]);
+
+        get_offsets(&data, &mut offsets);
+        assert_eq!(offsets, vec![start]);
+    }
+}
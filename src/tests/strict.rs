@@ -0,0 +1,33 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use super::rpu::_parse_file;
+use crate::dovi::codec::Codec;
+use crate::dovi::strict::StrictChecker;
+
+#[test]
+fn same_profile() -> Result<()> {
+    let (data, _) = _parse_file(PathBuf::from("./assets/tests/profile8.bin"))?;
+
+    let mut checker = StrictChecker::new(Codec::Hevc);
+    checker.check(0, &data)?;
+    checker.check(1, &data)?;
+
+    Ok(())
+}
+
+#[test]
+fn profile_mix() -> Result<()> {
+    let (fel_data, _) = _parse_file(PathBuf::from("./assets/tests/fel_orig.bin"))?;
+    let (mel_data, _) = _parse_file(PathBuf::from("./assets/tests/mel_orig.bin"))?;
+
+    let mut checker = StrictChecker::new(Codec::Hevc);
+    checker.check(0, &fel_data)?;
+
+    let error = checker.check(1, &mel_data).unwrap_err().to_string();
+    assert!(error.contains("Strict validation failed for decoded frame 1"));
+    assert!(error.contains("profile 7 MEL, the first RPU is profile 7 FEL"));
+
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use std::fs::{read, File};
+use std::io::Write;
+
+use anyhow::Result;
+
+use crate::dovi::threaded_writer::ThreadedWriter;
+
+#[test]
+fn ordered_blocks() -> Result<()> {
+    let temp = assert_fs::TempDir::new()?;
+    let path = temp.path().join("output.bin");
+
+    let chunks: Vec<Vec<u8>> = (0..1000_u32)
+        .map(|i| i.to_be_bytes().repeat(i as usize % 7 + 1))
+        .collect();
+
+    // Small buffers, for many blocks in flight
+    let mut writer = ThreadedWriter::with_capacity(64, File::create(&path)?)?;
+
+    for chunk in &chunks {
+        writer.write_all(chunk)?;
+    }
+
+    writer.flush()?;
+    assert_eq!(read(&path)?, chunks.concat());
+
+    // Written on drop
+    writer.write_all(b"end")?;
+    drop(writer);
+
+    assert!(read(&path)?.ends_with(b"end"));
+
+    Ok(())
+}
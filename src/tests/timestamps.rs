@@ -0,0 +1,57 @@
+use crate::dovi::timecode::FrameClock;
+use crate::dovi::timestamps::FrameTimestamps;
+
+#[test]
+fn parse() {
+    let timestamps =
+        FrameTimestamps::parse("# timestamp format v2\n0\n41.708\n83.417\n\n100\n116.667\n")
+            .unwrap();
+
+    assert_eq!(timestamps.frame_count(), 5);
+    assert!((timestamps.time_of(1).unwrap() - 0.041708).abs() < 1e-9);
+
+    // The end of the video, one more frame of the last duration
+    assert!((timestamps.time_of(5).unwrap() - 0.133334).abs() < 1e-9);
+    assert!(timestamps.time_of(6).is_err());
+}
+
+#[test]
+fn parse_invalid() {
+    assert!(FrameTimestamps::parse("# timestamp format v1\nassume 23.976\n").is_err());
+    assert!(FrameTimestamps::parse("# timestamp format v2\n").is_err());
+    assert!(FrameTimestamps::parse("# timestamp format v2\n0\n50\n40\n").is_err());
+    assert!(FrameTimestamps::parse("# timestamp format v2\n0\nabc\n").is_err());
+
+    // Header is optional
+    assert!(FrameTimestamps::parse("0\n40\n").is_ok());
+}
+
+#[test]
+fn frame_at() {
+    // 4 frames at 25 fps, then 4 at 50 fps
+    let timestamps =
+        FrameTimestamps::parse("# timecode format v2\n0\n40\n80\n120\n160\n180\n200\n220\n")
+            .unwrap();
+
+    assert_eq!(timestamps.frame_at(0.0).unwrap(), 0);
+    assert_eq!(timestamps.frame_at(0.079).unwrap(), 2);
+    assert_eq!(timestamps.frame_at(0.1).unwrap(), 3);
+    assert_eq!(timestamps.frame_at(0.175).unwrap(), 5);
+    assert_eq!(timestamps.frame_at(0.24).unwrap(), 8);
+
+    assert!(timestamps.frame_at(0.3).is_err());
+}
+
+#[test]
+fn duration_frames() {
+    let clock = FrameClock::Timestamps(
+        FrameTimestamps::parse("0\n40\n80\n120\n160\n180\n200\n220\n").unwrap(),
+    );
+
+    // 80 ms is 2 frames at 25 fps, 4 frames at 50 fps
+    assert_eq!(clock.duration_frames(0, 0.08).unwrap(), 2);
+    assert_eq!(clock.duration_frames(4, 0.08).unwrap(), 4);
+    assert_eq!(clock.duration_frames(2, 0.08).unwrap(), 2);
+
+    assert_eq!(FrameClock::Fps(25.0).duration_frames(4, 0.08).unwrap(), 2);
+}
@@ -0,0 +1,329 @@
+use std::path::Path;
+
+use anyhow::Result;
+use assert_cmd::Command;
+use assert_fs::prelude::*;
+use bitvec_helpers::bitvec_writer::BitVecWriter;
+use predicates::prelude::*;
+
+use dolby_vision::rpu::dovi_rpu::DoviRpu;
+
+const RPU_FILES: &[&str] = &[
+    "assets/tests/profile8.bin",
+    "assets/tests/fel_orig.bin",
+    "assets/tests/mel_orig.bin",
+    "assets/tests/fel_rpu.bin",
+    "assets/tests/cmv40_full_rpu.bin",
+];
+
+/// Decoding order of the frames: I P B P B
+/// (slice_type, pic_order_cnt_lsb, nal header)
+const FRAMES: &[(u64, u8, u8)] = &[
+    (7, 0, 0x65),
+    (5, 4, 0x41),
+    (6, 2, 0x01),
+    (5, 8, 0x41),
+    (6, 6, 0x01),
+];
+
+/// Decoded frame index for each presentation frame
+pub const PRESENTATION_ORDER: &[usize] = &[0, 2, 1, 4, 3];
+
+pub fn test_rpus() -> Result<Vec<DoviRpu>> {
+    let mut rpus = Vec::new();
+
+    for path in RPU_FILES {
+        let mut parsed = utilities_dovi::parse_rpu_file(Path::new(path))?.unwrap();
+        rpus.push(parsed.remove(0));
+    }
+
+    Ok(rpus)
+}
+
+pub fn finish_rbsp(mut writer: BitVecWriter) -> Vec<u8> {
+    // rbsp_trailing_bits()
+    writer.write(true);
+
+    while !writer.is_aligned() {
+        writer.write(false);
+    }
+
+    writer.as_slice().to_vec()
+}
+
+pub fn write_nal(stream: &mut Vec<u8>, data: &[u8]) {
+    stream.extend_from_slice(&[0, 0, 0, 1]);
+    stream.extend_from_slice(data);
+}
+
+/// Small AVC stream with POC type 0 and B frames, with an RPU per decoded frame
+fn avc_stream(rpus: Option<&[DoviRpu]>) -> Result<Vec<u8>> {
+    let mut stream = Vec::new();
+
+    let mut sps = BitVecWriter::new();
+    sps.write_n(&[66], 8); // profile_idc
+    sps.write_n(&[0], 8); // constraint flags
+    sps.write_n(&[30], 8); // level_idc
+    sps.write_ue(0); // seq_parameter_set_id
+    sps.write_ue(0); // log2_max_frame_num_minus4
+    sps.write_ue(0); // pic_order_cnt_type
+    sps.write_ue(0); // log2_max_pic_order_cnt_lsb_minus4
+    sps.write_ue(2); // max_num_ref_frames
+    sps.write(false); // gaps_in_frame_num_value_allowed_flag
+    sps.write_ue(0); // pic_width_in_mbs_minus1
+    sps.write_ue(0); // pic_height_in_map_units_minus1
+    sps.write(true); // frame_mbs_only_flag
+    sps.write(true); // direct_8x8_inference_flag
+    sps.write(false); // frame_cropping_flag
+    sps.write(false); // vui_parameters_present_flag
+
+    let mut pps = BitVecWriter::new();
+    pps.write_ue(0); // pic_parameter_set_id
+    pps.write_ue(0); // seq_parameter_set_id
+
+    let mut sps_nal = vec![0x67];
+    sps_nal.extend(finish_rbsp(sps));
+
+    let mut pps_nal = vec![0x68];
+    pps_nal.extend(finish_rbsp(pps));
+
+    for (i, (slice_type, poc_lsb, header)) in FRAMES.iter().enumerate() {
+        write_nal(&mut stream, &[0x09, 0xF0]);
+
+        if i == 0 {
+            write_nal(&mut stream, &sps_nal);
+            write_nal(&mut stream, &pps_nal);
+        }
+
+        let mut slice = BitVecWriter::new();
+        slice.write_ue(0); // first_mb_in_slice
+        slice.write_ue(*slice_type);
+        slice.write_ue(0); // pic_parameter_set_id
+        slice.write_n(&[i as u8], 4); // frame_num
+
+        if *header == 0x65 {
+            slice.write_ue(0); // idr_pic_id
+        }
+
+        slice.write_n(&[*poc_lsb], 4); // pic_order_cnt_lsb
+
+        let mut slice_nal = vec![*header];
+        slice_nal.extend(finish_rbsp(slice));
+        slice_nal.extend_from_slice(&[0xAB; 16]);
+
+        write_nal(&mut stream, &slice_nal);
+
+        if let Some(rpus) = rpus {
+            let data = rpus[i].write_hevc_unspec62_nalu()?;
+
+            let mut rpu_nal = vec![0x7C];
+            rpu_nal.extend_from_slice(&data[2..]);
+
+            write_nal(&mut stream, &rpu_nal);
+        }
+    }
+
+    Ok(stream)
+}
+
+pub fn encoded_rpus(rpus: &[DoviRpu]) -> Result<Vec<Vec<u8>>> {
+    rpus.iter()
+        .map(|rpu| rpu.write_hevc_unspec62_nalu())
+        .collect()
+}
+
+#[test]
+fn extract_rpu() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let rpus = test_rpus()?;
+
+    let input_file = temp.child("input.h264");
+    input_file.write_binary(&avc_stream(Some(&rpus))?)?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg("extract-rpu")
+        .arg(input_file.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let extracted = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(extracted.len(), rpus.len());
+
+    let expected: Vec<DoviRpu> = PRESENTATION_ORDER
+        .iter()
+        .map(|i| rpus[*i].clone())
+        .collect();
+    assert_eq!(encoded_rpus(&extracted)?, encoded_rpus(&expected)?);
+
+    Ok(())
+}
+
+#[test]
+fn extract_rpu_stream() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let rpus = test_rpus()?;
+
+    let input_file = temp.child("input.h264");
+    input_file.write_binary(&avc_stream(Some(&rpus))?)?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg("extract-rpu")
+        .arg(input_file.as_ref())
+        .arg("--stream")
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let extracted = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+
+    let expected: Vec<DoviRpu> = PRESENTATION_ORDER
+        .iter()
+        .map(|i| rpus[*i].clone())
+        .collect();
+    assert_eq!(encoded_rpus(&extracted)?, encoded_rpus(&expected)?);
+
+    Ok(())
+}
+
+#[test]
+fn convert_mode_2() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let rpus = test_rpus()?;
+
+    let input_file = temp.child("input.264");
+    input_file.write_binary(&avc_stream(Some(&rpus))?)?;
+
+    let output_file = temp.child("converted.h264");
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("--mode")
+        .arg("2")
+        .arg("convert")
+        .arg(input_file.as_ref())
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("extract-rpu")
+        .arg(output_file.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let extracted = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(extracted.len(), rpus.len());
+    assert!(extracted.iter().all(|rpu| rpu.dovi_profile == 8));
+
+    Ok(())
+}
+
+#[test]
+fn inject_rpu() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let rpus = test_rpus()?;
+
+    let input_file = temp.child("input.h264");
+    input_file.write_binary(&avc_stream(None)?)?;
+
+    let input_rpu = temp.child("input_RPU.bin");
+    let mut rpu_file = Vec::new();
+    for data in encoded_rpus(&rpus)? {
+        write_nal(&mut rpu_file, &data[2..]);
+    }
+    input_rpu.write_binary(&rpu_file)?;
+
+    let output_file = temp.child("injected.h264");
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("inject-rpu")
+        .arg(input_file.as_ref())
+        .arg("--rpu-in")
+        .arg(input_rpu.as_ref())
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .arg("--verify")
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("each with its injected RPU"));
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("extract-rpu")
+        .arg(output_file.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    // RPUs are injected in presentation order
+    let extracted = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(encoded_rpus(&extracted)?, encoded_rpus(&rpus)?);
+
+    Ok(())
+}
+
+#[test]
+fn convert_annexb() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let rpus = test_rpus()?;
+
+    let input_file = temp.child("input.264");
+    input_file.write_binary(&avc_stream(Some(&rpus))?)?;
+
+    let output_file = temp.child("converted.h264");
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("--start-code")
+        .arg("annex-b")
+        .arg("convert")
+        .arg(input_file.as_ref())
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let output = std::fs::read(output_file.path())?;
+    let mut nal_count = 0;
+
+    for i in 0..output.len() - 3 {
+        if output[i..i + 3] == [0, 0, 1] {
+            let four_sized = i > 0 && output[i - 1] == 0;
+            let nal_type = output[i + 3] & 0x1F;
+
+            // AUD, SPS, PPS and RPU, slices are never the first NAL unit
+            assert_eq!(four_sized, matches!(nal_type, 7 | 8 | 9 | 28));
+            nal_count += 1;
+        }
+    }
+
+    assert_eq!(nal_count, 17);
+
+    Ok(())
+}
@@ -0,0 +1,162 @@
+use std::path::Path;
+
+use anyhow::Result;
+use assert_cmd::Command;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlock;
+
+const SUBCOMMAND: &str = "add-dv";
+
+const STATIC_CONFIG: &str = r#"{
+    "cm_version": "V40",
+    "level6": {
+        "max_display_mastering_luminance": 1000,
+        "min_display_mastering_luminance": 1,
+        "max_content_light_level": 1000,
+        "max_frame_average_light_level": 400
+    }
+}"#;
+
+#[test]
+fn help() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd.arg(SUBCOMMAND).arg("--help").assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "dovi_tool add-dv [OPTIONS] [input_pos]",
+        ));
+    Ok(())
+}
+
+#[test]
+fn static_metadata() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_bl_start_code_4.hevc");
+    let config = temp.child("static.json");
+    config.write_str(STATIC_CONFIG)?;
+
+    let output_file = temp.child("output.hevc");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--json")
+        .arg(config.as_ref())
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Generated metadata for 259 frames",
+        ))
+        .stdout(predicate::str::contains("mismatched lengths").not());
+
+    let output_rpu = temp.child("RPU.bin");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("extract-rpu")
+        .arg(output_file.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert()
+        .success();
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 259);
+
+    let vdr_dm_data = rpus[0].vdr_dm_data.as_ref().unwrap();
+
+    if let Some(ExtMetadataBlock::Level6(level6)) = vdr_dm_data.get_block(6) {
+        assert_eq!(level6.max_content_light_level, 1000);
+        assert_eq!(level6.max_frame_average_light_level, 400);
+    } else {
+        panic!("No L6 metadata");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn same_as_generate_and_inject() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_bl_start_code_4.hevc");
+    let hdr10plus_json = Path::new("assets/tests/hdr10plus_metadata.json");
+
+    let config = temp.child("static.json");
+    config.write_str(STATIC_CONFIG)?;
+
+    let generated_rpu = temp.child("RPU.bin");
+    let injected_file = temp.child("injected.hevc");
+    let output_file = temp.child("output.hevc");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("generate")
+        .arg("--json")
+        .arg(config.as_ref())
+        .arg("--hdr10plus-json")
+        .arg(hdr10plus_json)
+        .arg("--rpu-out")
+        .arg(generated_rpu.as_ref())
+        .assert()
+        .success();
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("inject-rpu")
+        .arg(input_file)
+        .arg("--rpu-in")
+        .arg(generated_rpu.as_ref())
+        .arg("--output")
+        .arg(injected_file.as_ref())
+        .assert()
+        .success();
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--json")
+        .arg(config.as_ref())
+        .arg("--hdr10plus-json")
+        .arg(hdr10plus_json)
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    // The HDR10+ metadata sets the length, the last RPU is repeated
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Warning: mismatched lengths. video 259, RPU 9",
+        ));
+
+    output_file.assert(predicate::path::eq_file(injected_file.path()));
+
+    Ok(())
+}
+
+#[test]
+fn missing_source() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular_bl_start_code_4.hevc")
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "The following required arguments were not provided",
+    ));
+
+    Ok(())
+}
@@ -47,6 +47,31 @@ fn copy() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn batch() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_start_code_4.hevc");
+    temp.child("ep01.hevc").write_file(input_file)?;
+    temp.child("ep02.hevc").write_file(input_file)?;
+
+    let assert = cmd
+        .current_dir(temp.path())
+        .arg(SUBCOMMAND)
+        .arg("ep0?.hevc")
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    for name in ["ep01", "ep02"] {
+        temp.child(format!("{}.BL_EL_RPU.hevc", name))
+            .assert(predicate::path::eq_file(input_file));
+    }
+
+    Ok(())
+}
+
 #[test]
 fn copy_discard() -> Result<()> {
     let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
@@ -74,6 +99,182 @@ fn copy_discard() -> Result<()> {
     Ok(())
 }
 
+/// Replaces the VPS NALUs with one describing two layers, as if it was also describing the EL
+fn layered_vps(data: &[u8]) -> Vec<u8> {
+    let base_vps = hex_bytes("40010c01ffff222000000300900000030000030099959809");
+    let layered_vps = hex_bytes("40010c11ffff222000000300900000030000030099959815bf4b");
+
+    let mut output = Vec::with_capacity(data.len());
+    let mut i = 0;
+
+    while i < data.len() {
+        if data[i..].starts_with(&base_vps) {
+            output.extend_from_slice(&layered_vps);
+            i += base_vps.len();
+        } else {
+            output.push(data[i]);
+            i += 1;
+        }
+    }
+
+    output
+}
+
+fn hex_bytes(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+#[test]
+fn discard_layered_vps() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let muxed_el = std::fs::read("assets/hevc_tests/regular_start_code_4_muxed_el.hevc")?;
+    let non_el_file = Path::new("assets/hevc_tests/regular_start_code_4.hevc");
+
+    let input_file = temp.child("layered_vps.hevc");
+    input_file.write_binary(&layered_vps(&muxed_el))?;
+
+    let output_file = temp.child("BL_RPU.hevc");
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file.as_ref())
+        .arg("--discard")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stdout(predicate::str::contains(
+            "Inconsistent BL parameter sets in the single layer output:",
+        ))
+        .stdout(predicate::str::contains(
+            "VPS 0 describes 2 layers, max layer id 1, the EL layers are discarded",
+        ));
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file.as_ref())
+        .arg("--discard")
+        .arg("--rewrite-vps")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stdout(predicate::str::contains(
+            "Rewrote 2 VPS NALUs to describe the BL only",
+        ))
+        .stdout(predicate::str::contains("Inconsistent").not());
+
+    output_file.assert(predicate::path::eq_file(non_el_file));
+
+    Ok(())
+}
+
+#[test]
+fn rewrite_vps_requires_discard() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular.hevc")
+        .arg("--rewrite-vps")
+        .assert();
+
+    assert
+        .failure()
+        .stderr(predicate::str::contains("--discard"));
+
+    Ok(())
+}
+
+/// Moves the EL and RPU NALUs of every frame after the AUD of the next access unit,
+/// unless the sequence ends
+fn misplace_dolby_nals(data: &[u8]) -> Vec<u8> {
+    let starts: Vec<usize> = data
+        .windows(4)
+        .enumerate()
+        .filter(|(_, window)| window == &[0, 0, 0, 1])
+        .map(|(i, _)| i)
+        .collect();
+
+    let mut output = Vec::with_capacity(data.len());
+    let mut dolby_nals = Vec::new();
+
+    for (i, &start) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).copied().unwrap_or(data.len());
+        let nal = &data[start..end];
+
+        match nal[4] >> 1 {
+            62 | 63 => dolby_nals.extend_from_slice(nal),
+            35 => {
+                output.extend_from_slice(nal);
+                output.append(&mut dolby_nals);
+            }
+            36 => {
+                output.append(&mut dolby_nals);
+                output.extend_from_slice(nal);
+            }
+            _ => output.extend_from_slice(nal),
+        }
+    }
+
+    output.append(&mut dolby_nals);
+    output
+}
+
+#[test]
+fn discard_misplaced_rpus() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let muxed_el = std::fs::read("assets/hevc_tests/regular_start_code_4_muxed_el.hevc")?;
+    let non_el_file = Path::new("assets/hevc_tests/regular_start_code_4.hevc");
+
+    let input_file = temp.child("misplaced.hevc");
+    input_file.write_binary(&misplace_dolby_nals(&muxed_el))?;
+
+    let output_file = temp.child("BL_RPU.hevc");
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file.as_ref())
+        .arg("--discard")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stdout(predicate::str::contains(
+        "Moved 257 RPUs found before the slices of an access unit to the previous one",
+    ));
+
+    output_file.assert(predicate::path::eq_file(non_el_file));
+
+    // Written one frame late
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file.as_ref())
+        .arg("--discard")
+        .arg("--keep-rpu-position")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success();
+
+    output_file.assert(predicate::path::eq_file(non_el_file).not());
+
+    Ok(())
+}
+
 #[test]
 fn mode_lossless_discard() -> Result<()> {
     let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
@@ -103,6 +304,37 @@ fn mode_lossless_discard() -> Result<()> {
     Ok(())
 }
 
+/// The RPU with an invalid CRC32 is replaced by the previous one
+#[test]
+fn lenient() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_rpu_bad_crc.hevc");
+    let output_file = temp.child("BL_RPU.hevc");
+
+    let assert = cmd
+        .arg("--lenient")
+        .arg("--mode")
+        .arg("0")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+        "Lenient parsing: 1 unparseable RPUs, 1 replaced by the previous frame's RPU, 0 skipped.",
+    ));
+
+    output_file.assert(predicate::path::is_file());
+
+    Ok(())
+}
+
 /// Edit config with specific active area
 #[test]
 fn edit_config() -> Result<()> {
@@ -179,3 +411,537 @@ fn annexb() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn drop_hdr10plus() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_hdr10plus.hevc");
+    let expected_output = Path::new("assets/hevc_tests/regular_start_code_4.hevc");
+
+    let output_file = temp.child("hdr10plus_dropped.hevc");
+
+    let assert = cmd
+        .arg("--drop-sei")
+        .arg("hdr10plus")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_file
+        .assert(predicate::path::is_file())
+        .assert(predicate::path::eq_file(expected_output));
+
+    Ok(())
+}
+
+#[test]
+fn drop_sei_mdcv_cll() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let output_file = temp.child("sei_dropped.hevc");
+
+    let assert = cmd
+        .arg("--drop-sei")
+        .arg("mdcv,cll")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_file.assert(predicate::path::is_file());
+
+    // SEI prefix NAL header followed by the payload type
+    let data = std::fs::read(output_file.path())?;
+    let mdcv_sei = [0x4E, 0x01, 137];
+    let cll_sei = [0x4E, 0x01, 144];
+
+    assert!(!data.windows(3).any(|w| w == mdcv_sei || w == cll_sei));
+
+    Ok(())
+}
+
+#[test]
+fn drop_l6() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let output_file = temp.child("BL_EL_RPU.hevc");
+
+    let assert = cmd
+        .arg("--drop-l6")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_file.assert(predicate::path::is_file());
+
+    // Extract result
+    let output_rpu = temp.child("RPU.bin");
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("extract-rpu")
+        .arg(output_file.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+    output_rpu.assert(predicate::path::is_file());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 259);
+
+    rpus.iter().for_each(|rpu| {
+        let vdr_dm_data = rpu.vdr_dm_data.as_ref().unwrap();
+
+        assert!(vdr_dm_data.get_block(6).is_none());
+        assert!(vdr_dm_data.get_block(1).is_some());
+    });
+
+    Ok(())
+}
+
+#[test]
+fn source_pq_override() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let output_file = temp.child("BL_EL_RPU.hevc");
+
+    let assert = cmd
+        .arg("--min-pq")
+        .arg("7")
+        .arg("--max-pq")
+        .arg("3079")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    // Extract result
+    let output_rpu = temp.child("RPU.bin");
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("extract-rpu")
+        .arg(output_file.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 259);
+
+    rpus.iter().for_each(|rpu| {
+        let vdr_dm_data = rpu.vdr_dm_data.as_ref().unwrap();
+
+        assert_eq!(vdr_dm_data.source_min_pq, 7);
+        assert_eq!(vdr_dm_data.source_max_pq, 3079);
+    });
+
+    Ok(())
+}
+
+#[test]
+fn source_pq_override_invalid() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let output_file = temp.child("BL_EL_RPU.hevc");
+
+    let assert = cmd
+        .arg("--max-pq")
+        .arg("4096")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "source_min_pq and source_max_pq should be <= 4095",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn retarget_trim_invalid() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let output_file = temp.child("BL_EL_RPU.hevc");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--retarget-trim")
+        .arg("0")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert
+        .failure()
+        .stderr(predicate::str::contains("Invalid retarget trim 0 nits"));
+
+    Ok(())
+}
+
+#[test]
+fn insert_hdr10_sei_from_l6() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    // Profile 5 stream, from the BL with the profile 5 RPU on every frame
+    let rpu_data = std::fs::read("assets/tests/profile5.bin")?;
+    let input_rpu = temp.child("RPU.bin");
+    input_rpu.write_binary(&rpu_data.repeat(259))?;
+
+    let input_file = temp.child("p5.hevc");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("inject-rpu")
+        .arg("assets/hevc_tests/regular_bl_start_code_4.hevc")
+        .arg("--rpu-in")
+        .arg(input_rpu.as_ref())
+        .arg("--output")
+        .arg(input_file.as_ref())
+        .assert()
+        .success();
+
+    let output_file = temp.child("BL_RPU.hevc");
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("--mode")
+        .arg("3")
+        .arg(SUBCOMMAND)
+        .arg(input_file.as_ref())
+        .arg("--insert-hdr10-sei")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Warning: mode 3 only converts the RPUs",
+        ));
+
+    // DCI-P3 D65, L6 4000 nits max, 0.005 nits min, no MaxCLL and MaxFALL
+    let expected_sei: &[u8] = &[
+        0x00, 0x00, 0x01, 0x4E, 0x01, 0x89, 0x18, 0x33, 0xC2, 0x86, 0xC4, 0x1D, 0x4C, 0x0B, 0xB8,
+        0x84, 0xD0, 0x3E, 0x80, 0x3D, 0x13, 0x40, 0x42, 0x02, 0x62, 0x5A, 0x00, 0x00, 0x03, 0x00,
+        0x00, 0x32, 0x90, 0x04, 0x00, 0x00, 0x03, 0x00, 0x00, 0x80,
+    ];
+
+    let data = std::fs::read(output_file.path())?;
+    let inserted: Vec<usize> = data
+        .windows(expected_sei.len())
+        .enumerate()
+        .filter(|(_, window)| *window == expected_sei)
+        .map(|(i, _)| i + expected_sei.len())
+        .collect();
+
+    // One per IDR frame, right before the slice
+    assert_eq!(inserted.len(), 2);
+
+    for end in inserted {
+        assert_eq!(&data[end..end + 5], &[0x00, 0x00, 0x00, 0x01, 0x28]);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn insert_hdr10_sei_values() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_start_code_4.hevc");
+    let output_file = temp.child("BL_RPU.hevc");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--insert-hdr10-sei")
+        .arg("--master-display")
+        .arg("G(8500,39850)B(6550,2300)R(35400,14600)WP(15635,16450)L(10000000,1)")
+        .arg("--max-cll")
+        .arg("1000,400")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let expected_sei: &[u8] = &[
+        0x4E, 0x01, 0x89, 0x18, 0x21, 0x34, 0x9B, 0xAA, 0x19, 0x96, 0x08, 0xFC, 0x8A, 0x48, 0x39,
+        0x08, 0x3D, 0x13, 0x40, 0x42, 0x00, 0x98, 0x96, 0x80, 0x00, 0x00, 0x03, 0x00, 0x01, 0x90,
+        0x04, 0x03, 0xE8, 0x01, 0x90, 0x80,
+    ];
+
+    let data = std::fs::read(output_file.path())?;
+    let count = data
+        .windows(expected_sei.len())
+        .filter(|window| *window == expected_sei)
+        .count();
+
+    assert_eq!(count, 2);
+
+    Ok(())
+}
+
+#[test]
+fn insert_hdr10_sei_no_l6() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_start_code_4.hevc");
+    let output_file = temp.child("BL_RPU.hevc");
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(r#"{ "mode": 2, "drop_l6": true }"#)?;
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("--edit-config")
+        .arg(edit_config.as_ref())
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--insert-hdr10-sei")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "HDR10 SEI: no L6 metadata in the RPU, use --master-display and --max-cll",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn insert_hdr10_sei_no_rpu() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    // BL only, the NALUs must not be buffered until the end of the stream
+    let input_file = Path::new("assets/hevc_tests/regular_bl_start_code_4.hevc");
+    let output_file = temp.child("BL.hevc");
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--insert-hdr10-sei")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "HDR10 SEI: no RPU in the first frame, use --master-display and --max-cll",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn dry_run() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = temp.child("regular.hevc");
+    input_file.write_file(Path::new("assets/hevc_tests/regular.hevc"))?;
+
+    let assert = cmd
+        .current_dir(temp.path())
+        .arg("--mode")
+        .arg("4")
+        .arg(SUBCOMMAND)
+        .arg("regular.hevc")
+        .arg("--dry-run")
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Dry run, no output written. Summary of the changes:\n  Changed frames: 259 of 259\n  header: 259 frames\n",
+        ));
+
+    temp.child("BL_EL_RPU.hevc")
+        .assert(predicate::path::missing());
+
+    Ok(())
+}
+
+/// Injects the FEL RPU in every frame of the muxed EL stream
+fn fel_muxed_el(temp: &assert_fs::TempDir) -> Result<assert_fs::fixture::ChildPath> {
+    let rpu_data = std::fs::read("assets/tests/fel_orig.bin")?;
+    let input_rpu = temp.child("RPU.bin");
+    input_rpu.write_binary(&rpu_data.repeat(259))?;
+
+    let input_file = temp.child("fel.hevc");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("inject-rpu")
+        .arg("assets/hevc_tests/regular_start_code_4_muxed_el.hevc")
+        .arg("--rpu-in")
+        .arg(input_rpu.as_ref())
+        .arg("--output")
+        .arg(input_file.as_ref())
+        .assert()
+        .success();
+
+    Ok(input_file)
+}
+
+/// Pads the slices of the EL pictures with cabac_zero_words, as if they had residual data
+fn pad_el_slices(data: &[u8]) -> Vec<u8> {
+    let starts: Vec<usize> = data
+        .windows(4)
+        .enumerate()
+        .filter(|(_, window)| window == &[0, 0, 0, 1])
+        .map(|(i, _)| i)
+        .collect();
+
+    let mut output = Vec::with_capacity(data.len());
+
+    for (i, &start) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).copied().unwrap_or(data.len());
+        let nal = &data[start..end];
+
+        output.extend_from_slice(nal);
+
+        if nal[4] >> 1 == 63 && nal[6] >> 1 <= 31 {
+            output.extend_from_slice(&[0, 0, 3].repeat(100));
+        }
+    }
+
+    output
+}
+
+#[test]
+fn el_report() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = fel_muxed_el(&temp)?;
+    let output_file = temp.child("BL_RPU.hevc");
+    let report_file = temp.child("report.json");
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("--mode")
+        .arg("2")
+        .arg(SUBCOMMAND)
+        .arg(input_file.as_ref())
+        .arg("--discard")
+        .arg("--el-report")
+        .arg(report_file.as_ref())
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stdout(predicate::str::contains(
+        "EL contribution: no residual data in the EL of the FEL frames, the conversion is lossless",
+    ));
+
+    let report: serde_json::Value =
+        serde_json::from_reader(std::fs::File::open(report_file.path())?)?;
+
+    assert_eq!(report["frames"], 259);
+    assert_eq!(report["fel_frames"], 259);
+    assert_eq!(report["residual_frames"], 0);
+    assert_eq!(report["lossy_scenes"], 0);
+
+    let scene = &report["scenes"][0];
+    assert_eq!(scene["fel_frames"], 259);
+    assert_eq!(
+        scene["nlq_offset"],
+        serde_json::json!([[512, 512], [512, 512], [512, 512]])
+    );
+    assert_eq!(
+        scene["vdr_in_max"],
+        serde_json::json!([0.125, 0.125, 0.125])
+    );
+    assert_eq!(scene["lossless"], true);
+
+    Ok(())
+}
+
+#[test]
+fn el_report_residual() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let fel_file = fel_muxed_el(&temp)?;
+    let input_file = temp.child("fel_residual.hevc");
+    input_file.write_binary(&pad_el_slices(&std::fs::read(fel_file.path())?))?;
+
+    let output_file = temp.child("BL_RPU.hevc");
+    let report_file = temp.child("report.json");
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("--mode")
+        .arg("2")
+        .arg(SUBCOMMAND)
+        .arg(input_file.as_ref())
+        .arg("--discard")
+        .arg("--el-report")
+        .arg(report_file.as_ref())
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stdout(predicate::str::contains(
+            "EL contribution: 1 of 1 scenes are lossy, with EL residual data in 259 of 259 frames",
+        ))
+        .stdout(predicate::str::contains(
+            "  Scene 0 (frames 0-258): 259 residual frames",
+        ));
+
+    let report: serde_json::Value =
+        serde_json::from_reader(std::fs::File::open(report_file.path())?)?;
+
+    assert_eq!(report["residual_frames"], 259);
+    assert_eq!(report["lossy_scenes"], 1);
+    assert_eq!(report["scenes"][0]["lossless"], false);
+
+    Ok(())
+}
+
+#[test]
+fn el_report_requires_mode_2() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular_start_code_4_muxed_el.hevc")
+        .arg("--discard")
+        .arg("--el-report")
+        .arg("report.json")
+        .assert();
+
+    assert
+        .failure()
+        .stderr(predicate::str::contains("--el-report requires --mode 2"));
+
+    Ok(())
+}
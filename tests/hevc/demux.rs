@@ -43,7 +43,10 @@ fn demux() -> Result<()> {
         .arg(output_el.as_ref())
         .assert();
 
-    assert.success().stderr(predicate::str::is_empty());
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("misalignment").not());
 
     output_bl
         .assert(predicate::path::is_file())
@@ -56,6 +59,227 @@ fn demux() -> Result<()> {
     Ok(())
 }
 
+/// Same stream as `demux`, with the EL NAL units in layer 1 instead of UNSPEC63 NAL units
+#[test]
+fn layered_el() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_layered_el.hevc");
+    let expected_bl = Path::new("assets/hevc_tests/regular_bl_start_code_4.hevc");
+    let expected_el = Path::new("assets/hevc_tests/regular_start_code_4.hevc");
+
+    let output_bl = temp.child("BL.hevc");
+    let output_el = temp.child("EL.hevc");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--bl-out")
+        .arg(output_bl.as_ref())
+        .arg("--el-out")
+        .arg(output_el.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_bl.assert(predicate::path::eq_file(expected_bl));
+    output_el.assert(predicate::path::eq_file(expected_el));
+
+    Ok(())
+}
+
+/// The input is read in multiple chunks
+#[test]
+fn buffer_sizes() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_start_code_4_muxed_el.hevc");
+    let expected_bl = Path::new("assets/hevc_tests/regular_bl_start_code_4.hevc");
+    let expected_el = Path::new("assets/hevc_tests/regular_start_code_4.hevc");
+
+    let output_bl = temp.child("BL.hevc");
+    let output_el = temp.child("EL.hevc");
+
+    let assert = cmd
+        .arg("--read-buffer")
+        .arg("64K")
+        .arg("--write-buffer")
+        .arg("65536")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--bl-out")
+        .arg(output_bl.as_ref())
+        .arg("--el-out")
+        .arg(output_el.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_bl.assert(predicate::path::eq_file(expected_bl));
+    output_el.assert(predicate::path::eq_file(expected_el));
+
+    Ok(())
+}
+
+#[test]
+fn buffer_size_too_small() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+
+    let assert = cmd
+        .arg("--read-buffer")
+        .arg("4K")
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular_start_code_4_muxed_el.hevc")
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "The buffer size must be at least 64K, got 4K",
+    ));
+
+    Ok(())
+}
+
+/// The outputs start like the fully demuxed outputs
+#[test]
+fn limit() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_start_code_4_muxed_el.hevc");
+    let expected_bl = std::fs::read("assets/hevc_tests/regular_bl_start_code_4.hevc")?;
+    let expected_el = std::fs::read("assets/hevc_tests/regular_start_code_4.hevc")?;
+
+    let output_bl = temp.child("BL.hevc");
+    let output_el = temp.child("EL.hevc");
+
+    let assert = cmd
+        .arg("--limit")
+        .arg("10")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--bl-out")
+        .arg(output_bl.as_ref())
+        .arg("--el-out")
+        .arg(output_el.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let bl = std::fs::read(output_bl.path())?;
+    let el = std::fs::read(output_el.path())?;
+
+    assert!(bl.len() < expected_bl.len() && expected_bl.starts_with(&bl));
+    assert!(el.len() < expected_el.len() && expected_el.starts_with(&el));
+
+    Ok(())
+}
+
+/// The EL NAL units are written with their UNSPEC63 header
+#[test]
+fn keep_el_prefix() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_start_code_4_muxed_el.hevc");
+    let expected_bl = Path::new("assets/hevc_tests/regular_bl_start_code_4.hevc");
+    let expected_el = std::fs::read("assets/hevc_tests/regular_start_code_4.hevc")?;
+
+    let output_bl = temp.child("BL.hevc");
+    let output_el = temp.child("EL.hevc");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--keep-el-prefix")
+        .arg("--bl-out")
+        .arg(output_bl.as_ref())
+        .arg("--el-out")
+        .arg(output_el.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_bl
+        .assert(predicate::path::is_file())
+        .assert(predicate::path::eq_file(expected_bl));
+
+    let el = std::fs::read(output_el.path())?;
+    let el_start_code: &[u8] = &[0, 0, 0, 1, 0x7E, 0x01];
+
+    // Same EL otherwise
+    let mut el_without_prefix = Vec::new();
+    let mut prefix_count = 0;
+    let mut pos = 0;
+
+    while pos < el.len() {
+        if el[pos..].starts_with(el_start_code) {
+            prefix_count += 1;
+            el_without_prefix.extend_from_slice(&el_start_code[..4]);
+            pos += el_start_code.len();
+        } else {
+            el_without_prefix.push(el[pos]);
+            pos += 1;
+        }
+    }
+
+    assert_eq!(prefix_count, 795);
+    assert_eq!(el_without_prefix, expected_el);
+
+    Ok(())
+}
+
+/// An AUD allowing any slice type is inserted before every frame of the BL
+#[test]
+fn add_aud() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/no_aud_injected.hevc");
+    let expected_bl = std::fs::read("assets/hevc_tests/no_aud_bl.hevc")?;
+
+    let output_bl = temp.child("BL.hevc");
+    let output_el = temp.child("EL.hevc");
+
+    let assert = cmd
+        .arg("--add-aud")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--bl-out")
+        .arg(output_bl.as_ref())
+        .arg("--el-out")
+        .arg(output_el.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let bl = std::fs::read(output_bl.path())?;
+    let aud: &[u8] = &[0, 0, 0, 1, 0x46, 0x01, 0x50];
+
+    assert!(bl.starts_with(aud));
+
+    // Same BL as without AUDs otherwise
+    let mut bl_without_aud = Vec::new();
+    let mut aud_count = 0;
+    let mut pos = 0;
+
+    while pos < bl.len() {
+        if bl[pos..].starts_with(aud) {
+            aud_count += 1;
+            pos += aud.len();
+        } else {
+            bl_without_aud.push(bl[pos]);
+            pos += 1;
+        }
+    }
+
+    assert_eq!(aud_count, 259);
+    assert_eq!(bl_without_aud, expected_bl);
+
+    Ok(())
+}
+
 #[test]
 fn el_only() -> Result<()> {
     let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
@@ -122,6 +346,46 @@ fn mode_lossless_el_only() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn el_only_rpu_out() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_start_code_4_muxed_el.hevc");
+    let expected_el = Path::new("assets/hevc_tests/regular_start_code_4.hevc");
+    let expected_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let output_bl = temp.child("BL.hevc");
+    let output_el = temp.child("EL.hevc");
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--bl-out")
+        .arg(output_bl.as_ref())
+        .arg("--el-out")
+        .arg(output_el.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .arg("--el-only")
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_bl.assert(predicate::path::missing());
+
+    output_el
+        .assert(predicate::path::is_file())
+        .assert(predicate::path::eq_file(expected_el));
+
+    output_rpu
+        .assert(predicate::path::is_file())
+        .assert(predicate::path::eq_file(expected_rpu));
+
+    Ok(())
+}
+
 /// Edit config with specific active area
 #[test]
 fn edit_config() -> Result<()> {
@@ -211,3 +475,650 @@ fn annexb() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn hdr10plus_out() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_hdr10plus.hevc");
+    let expected_bl = Path::new("assets/hevc_tests/regular_bl_start_code_4.hevc");
+
+    let output_bl = temp.child("BL.hevc");
+    let output_el = temp.child("EL.hevc");
+    let output_json = temp.child("hdr10plus.json");
+
+    let assert = cmd
+        .arg("--drop-hdr10plus")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--bl-out")
+        .arg(output_bl.as_ref())
+        .arg("--el-out")
+        .arg(output_el.as_ref())
+        .arg("--hdr10plus-out")
+        .arg(output_json.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_bl
+        .assert(predicate::path::is_file())
+        .assert(predicate::path::eq_file(expected_bl));
+
+    output_json.assert(predicate::path::is_file());
+
+    let json: serde_json::Value =
+        serde_json::from_reader(std::fs::File::open(output_json.path())?)?;
+    assert_eq!(json["JSONInfo"]["HDR10plusProfile"], "A");
+    assert_eq!(json["SceneInfo"].as_array().unwrap().len(), 259);
+
+    Ok(())
+}
+
+/// Frames without HDR10+ SEI, or with a repeated SEI, keep the metadata of the other frames
+#[test]
+fn hdr10plus_out_missing_sei() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let data = std::fs::read("assets/hevc_tests/regular_hdr10plus.hevc")?;
+
+    // HDR10+ SEI NALUs with their start code, in decoding order
+    let starts: Vec<usize> = data
+        .windows(3)
+        .enumerate()
+        .filter(|(_, w)| w == &[0, 0, 1])
+        .map(|(i, _)| i)
+        .collect();
+    let seis: Vec<(usize, usize)> = starts
+        .iter()
+        .enumerate()
+        .map(|(i, &start)| (start, starts.get(i + 1).copied().unwrap_or(data.len())))
+        .filter(|&(start, _)| {
+            let nal = &data[start + 3..];
+            nal[0] >> 1 == 39 && nal[2] == 4 && nal[4..7] == [0xB5, 0x00, 0x3C]
+        })
+        .collect();
+
+    // The 10th frame has no metadata, and the 20th has it twice
+    let (removed_start, removed_end) = seis[10];
+    let (repeated_start, repeated_end) = seis[20];
+
+    let mut edited = data[..removed_start].to_vec();
+    edited.extend_from_slice(&data[removed_end..repeated_end]);
+    edited.extend_from_slice(&data[repeated_start..]);
+
+    let input_file = temp.child("missing_sei.hevc");
+    input_file.write_binary(&edited)?;
+
+    let luminance = |input: &Path, name: &str| -> Result<Vec<serde_json::Value>> {
+        let output_json = temp.child(name);
+
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+            .arg(SUBCOMMAND)
+            .arg(input)
+            .arg("--bl-out")
+            .arg(temp.child("BL.hevc").as_ref())
+            .arg("--el-out")
+            .arg(temp.child("EL.hevc").as_ref())
+            .arg("--hdr10plus-out")
+            .arg(output_json.as_ref())
+            .assert()
+            .success()
+            .stderr(predicate::str::is_empty());
+
+        let json: serde_json::Value =
+            serde_json::from_reader(std::fs::File::open(output_json.path())?)?;
+
+        Ok(json["SceneInfo"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|scene| scene["LuminanceParameters"].clone())
+            .collect())
+    };
+
+    let expected = luminance(
+        Path::new("assets/hevc_tests/regular_hdr10plus.hevc"),
+        "orig.json",
+    )?;
+    let edited = luminance(input_file.path(), "edited.json")?;
+
+    assert_eq!(expected.len(), 259);
+    assert_eq!(edited.len(), 258);
+
+    // Only the metadata of the frame without SEI is missing
+    let missing = expected
+        .iter()
+        .zip(edited.iter())
+        .position(|(expected, edited)| expected != edited)
+        .unwrap_or(edited.len());
+
+    assert_eq!(edited[..missing], expected[..missing]);
+    assert_eq!(edited[missing..], expected[missing + 1..]);
+
+    Ok(())
+}
+
+#[test]
+fn mp4_out() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    // Profile 7 stream, from the muxed EL stream with the MEL RPU on every frame
+    let rpu_data = std::fs::read("assets/tests/mel_orig.bin")?;
+    let input_rpu = temp.child("RPU.bin");
+    input_rpu.write_binary(&rpu_data.repeat(259))?;
+
+    let input_file = temp.child("p7.hevc");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("inject-rpu")
+        .arg("assets/hevc_tests/regular_start_code_4_muxed_el.hevc")
+        .arg("--rpu-in")
+        .arg(input_rpu.as_ref())
+        .arg("--output")
+        .arg(input_file.as_ref())
+        .assert()
+        .success();
+
+    let output_mp4 = temp.child("output.mp4");
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg(input_file.as_ref())
+        .arg("--mp4-out")
+        .arg(output_mp4.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let data = std::fs::read(output_mp4.path())?;
+    assert_eq!(&data[4..12], b"ftypisom");
+
+    let find_box = |fourcc: &[u8]| {
+        data.windows(4)
+            .enumerate()
+            .filter(|(_, window)| *window == fourcc)
+            .map(|(i, _)| i + 4)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(find_box(b"hev1").len(), 1);
+    assert_eq!(find_box(b"dvhe").len(), 1);
+    assert_eq!(find_box(b"vdep").len(), 1);
+
+    // Profile 7, level 1, RPU and EL present
+    let dvcc = find_box(b"dvcC");
+    assert_eq!(dvcc.len(), 1);
+    assert_eq!(&data[dvcc[0]..dvcc[0] + 5], &[1, 0, 0x0E, 0x0E, 0x60]);
+
+    // Both tracks have every frame
+    let stsz = find_box(b"stsz");
+    assert_eq!(stsz.len(), 2);
+
+    for pos in stsz {
+        let sample_count = u32::from_be_bytes(data[pos + 8..pos + 12].try_into()?);
+        assert_eq!(sample_count, 259);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn mp4_out_single_layer() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_start_code_4_muxed_el.hevc");
+    let output_mp4 = temp.child("output.mp4");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--mp4-out")
+        .arg(output_mp4.as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "Dual-track MP4 output requires a dual layer profile 4 or 7 stream, found profile 8",
+    ));
+
+    Ok(())
+}
+
+/// Access units of an Annex B stream with 4 byte start codes, split at the AUD NAL units
+fn access_units(data: &[u8]) -> Vec<Vec<&[u8]>> {
+    let starts: Vec<usize> = data
+        .windows(4)
+        .enumerate()
+        .filter(|(_, window)| window == &[0, 0, 0, 1])
+        .map(|(i, _)| i)
+        .collect();
+
+    let mut access_units: Vec<Vec<&[u8]>> = Vec::new();
+
+    for (i, &start) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).copied().unwrap_or(data.len());
+        let nal = &data[start..end];
+
+        match access_units.last_mut() {
+            Some(au) if nal[4] >> 1 != 35 => au.push(nal),
+            _ => access_units.push(vec![nal]),
+        }
+    }
+
+    access_units
+}
+
+#[test]
+fn misaligned_el() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let muxed_el = std::fs::read("assets/hevc_tests/regular_start_code_4_muxed_el.hevc")?;
+    let mut access_units = access_units(&muxed_el);
+
+    // No EL for frames 10 and 11
+    for au in &mut access_units[10..12] {
+        au.retain(|nal| nal[4] >> 1 != 63);
+    }
+
+    // EL of frame 20 repeated in its own access unit
+    let mut extra_el = vec![access_units[20][0]];
+    extra_el.extend(access_units[20].iter().filter(|nal| nal[4] >> 1 == 63));
+    access_units.insert(21, extra_el);
+
+    let input_file = temp.child("misaligned.hevc");
+    input_file.write_binary(&access_units.concat().concat())?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file.as_ref())
+        .arg("--bl-out")
+        .arg(temp.child("BL.hevc").as_ref())
+        .arg("--el-out")
+        .arg(temp.child("EL.hevc").as_ref())
+        .assert();
+
+    assert.success().stdout(predicate::str::contains(
+        "Warning: BL/EL access unit misalignment, in decoded order:\n  \
+        frames 10-11: missing EL\n  \
+        frame 21: EL without BL picture\n",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn misplaced_rpus() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let muxed_el = std::fs::read("assets/hevc_tests/regular_start_code_4_muxed_el.hevc")?;
+    let mut access_units = access_units(&muxed_el);
+
+    // RPU of frame 4 moved after the AUD of frame 5
+    let rpu_pos = access_units[4]
+        .iter()
+        .position(|nal| nal[4] >> 1 == 62)
+        .unwrap();
+    let rpu = access_units[4].remove(rpu_pos);
+    access_units[5].insert(1, rpu);
+
+    let input_file = temp.child("misplaced.hevc");
+    input_file.write_binary(&access_units.concat().concat())?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file.as_ref())
+        .arg("--bl-out")
+        .arg(temp.child("BL.hevc").as_ref())
+        .arg("--el-out")
+        .arg(temp.child("EL.hevc").as_ref())
+        .assert();
+
+    assert.success().stdout(predicate::str::contains(
+        "frame 5: RPU before the BL slices, belonging to the previous access unit",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn threads() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_start_code_4_muxed_el.hevc");
+    let expected_bl = Path::new("assets/hevc_tests/regular_bl_start_code_4.hevc");
+    let expected_el = Path::new("assets/hevc_tests/regular_start_code_4.hevc");
+    let expected_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let output_bl = temp.child("BL.hevc");
+    let output_el = temp.child("EL.hevc");
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--threads")
+        .arg("4")
+        .arg("--bl-out")
+        .arg(output_bl.as_ref())
+        .arg("--el-out")
+        .arg(output_el.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Demuxing 2 segments on 4 threads...",
+        ));
+
+    output_bl.assert(predicate::path::eq_file(expected_bl));
+    output_el.assert(predicate::path::eq_file(expected_el));
+    output_rpu.assert(predicate::path::eq_file(expected_rpu));
+
+    // Segment files removed
+    assert_eq!(std::fs::read_dir(temp.path())?.count(), 3);
+
+    Ok(())
+}
+
+#[test]
+fn threads_stdin() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("-")
+        .arg("--threads")
+        .arg("2")
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "Demuxer: --threads requires a seekable raw input file",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn checkpoint() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_start_code_4_muxed_el.hevc");
+    let expected_bl = Path::new("assets/hevc_tests/regular_bl_start_code_4.hevc");
+    let expected_el = Path::new("assets/hevc_tests/regular_start_code_4.hevc");
+    let expected_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let output_bl = temp.child("BL.hevc");
+    let output_el = temp.child("EL.hevc");
+    let output_rpu = temp.child("RPU.bin");
+    let checkpoint = temp.child("demux.checkpoint");
+
+    let assert = cmd
+        .arg("-v")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--checkpoint")
+        .arg(checkpoint.as_ref())
+        .arg("--checkpoint-interval")
+        .arg("20K")
+        .arg("--bl-out")
+        .arg(output_bl.as_ref())
+        .arg("--el-out")
+        .arg(output_el.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Debug: Checkpoint saved at byte 80095 of 88164",
+        ))
+        .stdout(predicate::str::contains(
+            "Debug: Checkpoint saved at byte 88164 of 88164",
+        ));
+
+    output_bl.assert(predicate::path::eq_file(expected_bl));
+    output_el.assert(predicate::path::eq_file(expected_el));
+    output_rpu.assert(predicate::path::eq_file(expected_rpu));
+
+    // Checkpoint and segment files removed
+    assert_eq!(std::fs::read_dir(temp.path())?.count(), 3);
+
+    Ok(())
+}
+
+#[test]
+fn checkpoint_resume() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_start_code_4_muxed_el.hevc");
+    let expected_bl = Path::new("assets/hevc_tests/regular_bl_start_code_4.hevc");
+    let expected_el = Path::new("assets/hevc_tests/regular_start_code_4.hevc");
+    let expected_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    // Random access point of the input
+    let offset = 80095;
+
+    let output_bl = temp.child("BL.hevc");
+    let output_el = temp.child("EL.hevc");
+    let output_rpu = temp.child("RPU.bin");
+    let checkpoint = temp.child("demux.checkpoint");
+
+    // Outputs of the input before the checkpoint
+    let first_segment = temp.child("first_segment.hevc");
+    first_segment.write_binary(&std::fs::read(input_file)?[..offset])?;
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(first_segment.as_ref())
+        .arg("--bl-out")
+        .arg(output_bl.as_ref())
+        .arg("--el-out")
+        .arg(output_el.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let outputs: Vec<serde_json::Value> = [&output_bl, &output_el, &output_rpu]
+        .iter()
+        .map(|output| {
+            serde_json::json!({
+                "path": output.path(),
+                "size": std::fs::metadata(output.path()).unwrap().len(),
+            })
+        })
+        .collect();
+
+    checkpoint.write_str(&serde_json::to_string(&serde_json::json!({
+        "input": input_file,
+        "input_size": 88164,
+        "offset": offset,
+        "outputs": outputs,
+    }))?)?;
+
+    // Data of the interrupted segment, discarded
+    for output in [&output_bl, &output_el, &output_rpu] {
+        let mut data = std::fs::read(output.path())?;
+        data.extend_from_slice(&[0, 0, 0, 1, 0x46, 0x01]);
+        output.write_binary(&data)?;
+    }
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--checkpoint")
+        .arg(checkpoint.as_ref())
+        .arg("--bl-out")
+        .arg(output_bl.as_ref())
+        .arg("--el-out")
+        .arg(output_el.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Resuming from the checkpoint at byte 80095 of 88164...",
+        ));
+
+    output_bl.assert(predicate::path::eq_file(expected_bl));
+    output_el.assert(predicate::path::eq_file(expected_el));
+    output_rpu.assert(predicate::path::eq_file(expected_rpu));
+
+    checkpoint.assert(predicate::path::missing());
+
+    Ok(())
+}
+
+#[test]
+fn checkpoint_different_input() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_start_code_4_muxed_el.hevc");
+
+    let output_bl = temp.child("BL.hevc");
+    let output_el = temp.child("EL.hevc");
+    let checkpoint = temp.child("demux.checkpoint");
+
+    checkpoint.write_str(&serde_json::to_string(&serde_json::json!({
+        "input": input_file,
+        "input_size": 1000,
+        "offset": 500,
+        "outputs": [],
+    }))?)?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--checkpoint")
+        .arg(checkpoint.as_ref())
+        .arg("--bl-out")
+        .arg(output_bl.as_ref())
+        .arg("--el-out")
+        .arg(output_el.as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "The checkpoint is for a different input",
+    ));
+
+    Ok(())
+}
+
+/// Annex B start codes, with an AUD inserted before every frame of the BL
+#[test]
+fn start_code_ts() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/no_aud_injected.hevc");
+
+    let output_bl = temp.child("BL.hevc");
+    let expected_bl = temp.child("BL_expected.hevc");
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("--start-code")
+        .arg("ts")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--bl-out")
+        .arg(output_bl.as_ref())
+        .arg("--el-out")
+        .arg(temp.child("EL.hevc").as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("--start-code")
+        .arg("annex-b")
+        .arg("--add-aud")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--bl-out")
+        .arg(expected_bl.as_ref())
+        .arg("--el-out")
+        .arg(temp.child("EL_expected.hevc").as_ref())
+        .assert()
+        .success();
+
+    let bl = std::fs::read(output_bl.path())?;
+    assert!(bl.starts_with(&[0, 0, 0, 1, 0x46, 0x01, 0x50]));
+
+    output_bl.assert(predicate::path::eq_file(expected_bl.path()));
+
+    Ok(())
+}
+
+#[test]
+fn deterministic_output_hashes() -> Result<()> {
+    let input_file = Path::new("assets/hevc_tests/regular_start_code_4_muxed_el.hevc");
+
+    let demux_hashes = |read_buffer: &str| -> Result<Vec<String>> {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+        let temp = assert_fs::TempDir::new().unwrap();
+
+        let output_bl = temp.child("BL.hevc");
+        let output_el = temp.child("EL.hevc");
+
+        let assert = cmd
+            .arg("--deterministic")
+            .arg("--read-buffer")
+            .arg(read_buffer)
+            .arg(SUBCOMMAND)
+            .arg(input_file)
+            .arg("--bl-out")
+            .arg(output_bl.as_ref())
+            .arg("--el-out")
+            .arg(output_el.as_ref())
+            .assert();
+
+        let output = assert
+            .success()
+            .stderr(predicate::str::is_empty())
+            .stdout(predicate::str::contains("Output SHA-256:"))
+            .get_output()
+            .stdout
+            .clone();
+
+        let output = String::from_utf8(output)?;
+        let lines: Vec<&str> = output
+            .lines()
+            .skip_while(|line| *line != "Output SHA-256:")
+            .skip(1)
+            .collect();
+
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("BL.hevc"));
+        assert!(lines[1].ends_with("EL.hevc"));
+
+        Ok(lines
+            .iter()
+            .map(|line| line.split_whitespace().next().unwrap().to_string())
+            .collect())
+    };
+
+    let hashes = demux_hashes("4M")?;
+    assert!(hashes.iter().all(|hash| hash.len() == 64));
+
+    // The read chunks don't change the outputs
+    assert_eq!(demux_hashes("64K")?, hashes);
+
+    Ok(())
+}
@@ -1,4 +1,5 @@
 use std::path::Path;
+use std::sync::atomic::Ordering;
 
 use anyhow::Result;
 use assert_cmd::Command;
@@ -49,6 +50,165 @@ fn extract_rpu() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn extract_rpu_http() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let expected_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let (url, range_requests) = super::serve_file_http(input_file, true);
+    let output_rpu = temp.child("RPU.bin");
+
+    // Streamed with a single request, the input is larger than the read buffer
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg("--read-buffer")
+        .arg("64K")
+        .arg(SUBCOMMAND)
+        .arg(&url)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_rpu.assert(predicate::path::eq_file(expected_rpu));
+    assert_eq!(range_requests.load(Ordering::SeqCst), 0);
+
+    // Seeked with a range request
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg("--skip-bytes")
+        .arg("30000")
+        .arg(SUBCOMMAND)
+        .arg(&url)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let expected_skipped = temp.child("RPU_skipped.bin");
+    cmd.arg("--skip-bytes")
+        .arg("30000")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-out")
+        .arg(expected_skipped.as_ref())
+        .assert()
+        .success();
+
+    output_rpu.assert(predicate::path::eq_file(expected_skipped.path()));
+    assert_eq!(range_requests.load(Ordering::SeqCst), 1);
+
+    Ok(())
+}
+
+#[test]
+fn extract_rpu_http_chunked() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let expected_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let (url, range_requests) = super::serve_file_http(input_file, true);
+    let url = url.replace("/file.hevc", "/chunked");
+    let output_rpu = temp.child("RPU.bin");
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg("--read-buffer")
+        .arg("64K")
+        .arg(SUBCOMMAND)
+        .arg(&url)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_rpu.assert(predicate::path::eq_file(expected_rpu));
+    assert_eq!(range_requests.load(Ordering::SeqCst), 0);
+
+    Ok(())
+}
+
+#[test]
+fn extract_rpu_http_redirect_without_ranges() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let expected_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let (url, range_requests) = super::serve_file_http(input_file, false);
+    let url = url.replace("/file.hevc", "/redirect");
+    let output_rpu = temp.child("RPU.bin");
+
+    // Streamed from the start
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(&url)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_rpu.assert(predicate::path::eq_file(expected_rpu));
+    assert_eq!(range_requests.load(Ordering::SeqCst), 0);
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg("--skip-bytes")
+        .arg("30000")
+        .arg(SUBCOMMAND)
+        .arg(&url)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "The server doesn't support range requests, can't read from byte 30000",
+    ));
+
+    Ok(())
+}
+
+/// HTTPS inputs are read with curl, a plain HTTP server fails the TLS handshake
+#[test]
+fn extract_rpu_https_curl_error() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let (url, range_requests) = super::serve_file_http(input_file, true);
+    let url = url.replace("http://", "https://");
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(&url)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            "Can't open input URL {}",
+            url
+        )))
+        .stderr(predicate::str::contains("curl: "));
+
+    assert_eq!(range_requests.load(Ordering::SeqCst), 0);
+
+    Ok(())
+}
+
 #[test]
 fn mode_mel() -> Result<()> {
     let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
@@ -77,6 +237,62 @@ fn mode_mel() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn stream() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let expected_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--stream")
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_rpu
+        .assert(predicate::path::is_file())
+        .assert(predicate::path::eq_file(expected_rpu));
+
+    Ok(())
+}
+
+#[test]
+fn stream_mode_mel() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let expected_rpu = Path::new("assets/hevc_tests/regular_rpu_mel.bin");
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg("--mode")
+        .arg("1")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--stream")
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_rpu
+        .assert(predicate::path::is_file())
+        .assert(predicate::path::eq_file(expected_rpu));
+
+    Ok(())
+}
+
 /// Edit config with specific active area
 #[test]
 fn edit_config() -> Result<()> {
@@ -113,3 +329,1437 @@ fn edit_config() -> Result<()> {
 
     Ok(())
 }
+
+/// Scripts editing the RPUs as JSON lines, run in order
+#[cfg(unix)]
+#[test]
+fn script() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg("--script")
+        .arg(r#"sed -u 's/"source_max_pq":[0-9]*/"source_max_pq":3500/'"#)
+        .arg("--script")
+        .arg(r#"sed -u 's/"source_min_pq":[0-9]*/"source_min_pq":42/'"#)
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 259);
+
+    for rpu in &rpus {
+        let vdr_dm_data = rpu.vdr_dm_data.as_ref().unwrap();
+        assert_eq!(vdr_dm_data.source_min_pq, 42);
+        assert_eq!(vdr_dm_data.source_max_pq, 3500);
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn script_exits() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let assert = cmd
+        .arg("--script")
+        .arg(r#"read line; echo "$line""#)
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular.hevc")
+        .arg("--rpu-out")
+        .arg(temp.child("RPU.bin").as_ref())
+        .assert();
+
+    assert
+        .failure()
+        .stderr(predicate::str::contains("after 1 RPUs"));
+
+    Ok(())
+}
+
+/// Length-prefixed NAL units, detected from the start of the input
+#[test]
+fn hvcc_input() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_hvcc.hevc");
+    let expected_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_rpu
+        .assert(predicate::path::is_file())
+        .assert(predicate::path::eq_file(expected_rpu));
+
+    Ok(())
+}
+
+#[test]
+fn hvcc_input_format() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_hvcc.hevc");
+    let expected_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg("--input-format")
+        .arg("hvcc")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_rpu
+        .assert(predicate::path::is_file())
+        .assert(predicate::path::eq_file(expected_rpu));
+
+    Ok(())
+}
+
+#[test]
+fn repair_duplicate_and_missing_rpu() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    // Frame 10 has no RPU, frame 20 has two
+    let input_file = Path::new("assets/hevc_tests/regular_rpu_gap_dup.hevc");
+    let expected_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--repair")
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Unexpected RPU NALU found for frame 20. Discarding.",
+        ))
+        .stdout(predicate::str::contains(
+            "Filled 1 frames missing an RPU with the previous frame's RPU.",
+        ));
+
+    output_rpu
+        .assert(predicate::path::is_file())
+        .assert(predicate::path::eq_file(expected_rpu));
+
+    Ok(())
+}
+
+#[test]
+fn output_dir_missing() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let output_rpu = temp.child("missing").child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .failure()
+        .stderr(predicate::str::contains("Can't create output file"))
+        .stderr(predicate::str::contains("panicked").not());
+
+    Ok(())
+}
+
+/// Frame 4 has an RPU with an invalid CRC32
+#[test]
+fn crc_check() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_rpu_bad_crc.hevc");
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--crc")
+        .arg("check")
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("Frame 4: CRC32 mismatch"))
+        .stdout(predicate::str::contains(
+            "Warning: 1 RPUs failed the CRC32 check.",
+        ));
+
+    output_rpu.assert(predicate::path::is_file());
+
+    Ok(())
+}
+
+/// Only the warning is printed, and also written to the log file
+#[test]
+fn crc_check_quiet_log_file() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_rpu_bad_crc.hevc");
+    let output_rpu = temp.child("RPU.bin");
+    let log_file = temp.child("dovi_tool.log");
+
+    let assert = cmd
+        .arg("--quiet")
+        .arg("--log-file")
+        .arg(log_file.as_ref())
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--crc")
+        .arg("check")
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::eq("Warning: 1 RPUs failed the CRC32 check.\n"));
+
+    output_rpu.assert(predicate::path::is_file());
+    log_file.assert(predicate::str::contains(
+        "WARN  1 RPUs failed the CRC32 check.",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn verbose_log_file() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let output_rpu = temp.child("RPU.bin");
+    let log_file = temp.child("dovi_tool.log");
+
+    let assert = cmd
+        .arg("-v")
+        .arg("--log-file")
+        .arg(log_file.as_ref())
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("Debug: Input codec: Hevc"))
+        .stdout(predicate::str::contains("Trace:").not());
+
+    log_file.assert(predicate::str::contains(
+        "DEBUG Detected input format: annex-b",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn crc_fix() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_rpu_bad_crc.hevc");
+    let expected_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--crc")
+        .arg("fix")
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("Fixed the CRC32 of 1 RPUs."));
+
+    output_rpu
+        .assert(predicate::path::is_file())
+        .assert(predicate::path::eq_file(expected_rpu));
+
+    Ok(())
+}
+
+#[test]
+fn crc_replace() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_rpu_bad_crc.hevc");
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--crc")
+        .arg("replace")
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Replaced 1 corrupt RPUs with the previous valid RPU.",
+        ));
+
+    output_rpu.assert(predicate::path::is_file());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 259);
+    assert_eq!(rpus[4].write_rpu()?, rpus[3].write_rpu()?);
+
+    Ok(())
+}
+
+/// Frame 4 can't be parsed when converting, because of its invalid CRC32
+#[test]
+fn lenient() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_rpu_bad_crc.hevc");
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg("--lenient")
+        .arg("--mode")
+        .arg("0")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Warning: Frame 4: unparseable RPU, replaced by the previous frame's RPU",
+        ))
+        .stdout(predicate::str::contains(
+            "Lenient parsing: 1 unparseable RPUs, 1 replaced by the previous frame's RPU, 0 skipped.",
+        ));
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 259);
+    assert_eq!(rpus[4].write_rpu()?, rpus[3].write_rpu()?);
+
+    Ok(())
+}
+
+/// A PPS with invalid data is inserted before the second frame
+#[test]
+fn lenient_corrupt_nal() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_corrupt_nal.hevc");
+    let expected_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg("--lenient")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Warning: Byte 3014: corrupt NAL unit, skipped 31 bytes up to the next start code",
+        ))
+        .stdout(predicate::str::contains(
+            "Lenient parsing: 1 corrupt NAL units, 31 bytes skipped.",
+        ));
+
+    output_rpu.assert(predicates::path::eq_file(expected_rpu));
+
+    Ok(())
+}
+
+#[test]
+fn lenient_disabled() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_rpu_bad_crc.hevc");
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg("--mode")
+        .arg("0")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "RPU CRC32 does not match the data",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn strict() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let expected_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg("--strict")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_rpu
+        .assert(predicate::path::is_file())
+        .assert(predicate::path::eq_file(expected_rpu));
+
+    Ok(())
+}
+
+#[test]
+fn strict_invalid() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_rpu_bad_crc.hevc");
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg("--strict")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "Strict validation failed for decoded frame 5: RPU CRC32 does not match the data",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn strict_lenient_conflict() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+
+    let assert = cmd
+        .arg("--strict")
+        .arg("--lenient")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .assert();
+
+    assert
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+
+    Ok(())
+}
+
+#[test]
+fn skip_frames() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let expected_rpus =
+        utilities_dovi::parse_rpu_file(Path::new("assets/hevc_tests/regular_rpu.bin"))?.unwrap();
+
+    // The second random access point is at frame 250
+    for (skip_arg, skip) in [("--skip-frames", "100"), ("--skip-bytes", "30000")] {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+        let output_rpu = temp.child("RPU.bin");
+
+        let assert = cmd
+            .arg(skip_arg)
+            .arg(skip)
+            .arg(SUBCOMMAND)
+            .arg(input_file)
+            .arg("--rpu-out")
+            .arg(output_rpu.as_ref())
+            .assert();
+
+        assert
+            .success()
+            .stderr(predicate::str::is_empty())
+            .stdout(predicate::str::contains(
+                "resuming at the next random access point",
+            ));
+
+        let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+        assert_eq!(rpus.len(), 9);
+
+        for (rpu, expected) in rpus.iter().zip(&expected_rpus[250..]) {
+            assert_eq!(rpu.write_rpu()?, expected.write_rpu()?);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn limit() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let expected_rpus =
+        utilities_dovi::parse_rpu_file(Path::new("assets/hevc_tests/regular_rpu.bin"))?.unwrap();
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg("--limit")
+        .arg("10")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 10);
+
+    for (rpu, expected) in rpus.iter().zip(&expected_rpus) {
+        assert_eq!(rpu.write_rpu()?, expected.write_rpu()?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn limit_after_skip_frames() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let expected_rpus =
+        utilities_dovi::parse_rpu_file(Path::new("assets/hevc_tests/regular_rpu.bin"))?.unwrap();
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg("--skip-frames")
+        .arg("100")
+        .arg("--limit")
+        .arg("5")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 5);
+
+    for (rpu, expected) in rpus.iter().zip(&expected_rpus[250..]) {
+        assert_eq!(rpu.write_rpu()?, expected.write_rpu()?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn skip_frames_no_random_access() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg("--skip-frames")
+        .arg("251")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "No random access point found after skipping 259 frames",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn skip_bytes_length_prefixed() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_hvcc.hevc");
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg("--skip-bytes")
+        .arg("30000")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "--skip-bytes is only supported for Annex B inputs",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn progress_json() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg("--progress")
+        .arg("json")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    let output = assert.success().get_output().clone();
+    let stderr = String::from_utf8(output.stderr)?;
+
+    let events = stderr
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<Vec<serde_json::Value>, _>>()?;
+
+    assert!(events.len() >= 2);
+    assert_eq!(events[0]["event"], "progress");
+
+    let done = events.last().unwrap();
+    assert_eq!(done["event"], "done");
+    assert_eq!(done["bytes_read"], 67872);
+    assert_eq!(done["total_bytes"], 67872);
+    assert_eq!(done["frames"], 259);
+    assert_eq!(done["rpus"], 259);
+    assert_eq!(done["eta_secs"], 0.0);
+
+    Ok(())
+}
+
+#[test]
+fn stats() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg("--stats")
+        .arg("-m")
+        .arg("2")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("Statistics:\n  0.07 MB read in "))
+        .stdout(predicate::str::contains(
+            "  Frames: 259, RPUs: 259, converted: 259\n  Time: read ",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn stats_json() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg("--stats")
+        .arg("--progress")
+        .arg("json")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    let output = assert.success().get_output().clone();
+    let stderr = String::from_utf8(output.stderr)?;
+
+    let stats: serde_json::Value = serde_json::from_str(stderr.lines().last().unwrap())?;
+
+    assert_eq!(stats["event"], "stats");
+    assert_eq!(stats["bytes_read"], 67872);
+    assert_eq!(stats["frames"], 259);
+    assert_eq!(stats["rpus"], 259);
+    assert_eq!(stats["converted_rpus"], 0);
+
+    let elapsed = stats["elapsed_secs"].as_f64().unwrap();
+    let times: f64 = ["read_secs", "parse_secs", "convert_secs", "write_secs"]
+        .iter()
+        .map(|key| stats[key].as_f64().unwrap())
+        .sum();
+
+    assert!((elapsed - times).abs() < 1e-3);
+
+    Ok(())
+}
+
+#[test]
+fn stream_api() -> Result<()> {
+    let mut input = std::fs::File::open("assets/hevc_tests/regular.hevc")?;
+    let expected_rpus =
+        utilities_dovi::parse_rpu_file(Path::new("assets/hevc_tests/regular_rpu.bin"))?.unwrap();
+
+    let mut decoded_rpus = Vec::new();
+    let frames = utilities_dovi::stream::process_hevc_rpus(&mut input, |stream_rpu| {
+        assert_eq!(stream_rpu.decoded_index as usize, decoded_rpus.len());
+        assert_eq!(&stream_rpu.data[..2], &[0x7C, 0x01]);
+
+        decoded_rpus.push(stream_rpu.rpu);
+        Ok(())
+    })?;
+
+    assert_eq!(frames.len(), 259);
+    assert_eq!(decoded_rpus.len(), 259);
+
+    // Reorder to presentation order
+    for (frame, expected) in frames.iter().zip(&expected_rpus) {
+        let rpu = &decoded_rpus[frame.decoded_number as usize];
+        assert_eq!(rpu.write_rpu()?, expected.write_rpu()?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn stream_api_async() -> Result<()> {
+    // Larger than a chunk, with NAL units split between the reads
+    let input = std::fs::read("assets/hevc_tests/regular.hevc")?.repeat(3);
+
+    let mut expected_rpus = Vec::new();
+    let expected_frames =
+        utilities_dovi::stream::process_hevc_rpus(&mut input.as_slice(), |stream_rpu| {
+            expected_rpus.push(stream_rpu.rpu.write_rpu()?);
+            Ok(())
+        })?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+
+    let mut decoded_rpus = Vec::new();
+    let frames = runtime.block_on(utilities_dovi::stream::process_hevc_rpus_async(
+        &mut input.as_slice(),
+        |stream_rpu| {
+            assert_eq!(stream_rpu.decoded_index as usize, decoded_rpus.len());
+
+            decoded_rpus.push(stream_rpu.rpu.write_rpu()?);
+            Ok(())
+        },
+    ))?;
+
+    assert_eq!(decoded_rpus.len(), 259 * 3);
+    assert_eq!(decoded_rpus, expected_rpus);
+
+    let presentation_order = |frames: &[hevc_parser::hevc::Frame]| -> Vec<u64> {
+        frames.iter().map(|frame| frame.decoded_number).collect()
+    };
+    assert_eq!(
+        presentation_order(&frames),
+        presentation_order(&expected_frames)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn batch_wildcard() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let expected_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    temp.child("ep01.hevc").write_file(input_file)?;
+    temp.child("ep02.hevc").write_file(input_file)?;
+
+    let assert = cmd
+        .current_dir(temp.path())
+        .arg("--progress")
+        .arg("json")
+        .arg(SUBCOMMAND)
+        .arg("*.hevc")
+        .arg("--output-template")
+        .arg("{dir}/{stem}.rpu.bin")
+        .assert();
+
+    assert
+        .success()
+        .stdout(predicate::str::contains("[2/2] ep02.hevc"))
+        .stdout(predicate::str::contains(
+            "Processed 2 of 2 inputs: 2 succeeded, 0 failed",
+        ));
+
+    for name in ["ep01", "ep02"] {
+        temp.child(format!("{}.rpu.bin", name))
+            .assert(predicate::path::eq_file(expected_rpu));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn batch_recursive_dir() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let expected_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    temp.child("library/movie.hevc").write_file(input_file)?;
+    temp.child("library/show/s01/ep01.hevc")
+        .write_file(input_file)?;
+    temp.child("library/show/s01/ep01.txt").touch()?;
+
+    let assert = cmd
+        .current_dir(temp.path())
+        .arg(SUBCOMMAND)
+        .arg("library")
+        .arg("--recursive")
+        .arg("--output-template")
+        .arg("rpus/{rel}/{stem}.rpu.bin")
+        .assert();
+
+    assert
+        .success()
+        .stdout(predicate::str::contains("[1/2] library/movie.hevc"))
+        .stdout(predicate::str::contains(
+            "Processed 2 of 2 inputs: 2 succeeded, 0 failed",
+        ));
+
+    temp.child("rpus/movie.rpu.bin")
+        .assert(predicate::path::eq_file(expected_rpu));
+    temp.child("rpus/show/s01/ep01.rpu.bin")
+        .assert(predicate::path::eq_file(expected_rpu));
+
+    Ok(())
+}
+
+#[test]
+fn batch_dir_extensions() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let expected_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    temp.child("library/movie.h265").write_file(input_file)?;
+    temp.child("library/movie.hevc").touch()?;
+    temp.child("library/show/ep01.h265")
+        .write_file(input_file)?;
+
+    // Not recursive, only the files of the directory
+    let assert = cmd
+        .current_dir(temp.path())
+        .arg(SUBCOMMAND)
+        .arg("library")
+        .arg("--extensions")
+        .arg(".h265,bin")
+        .arg("--output-template")
+        .arg("{dir}/{stem}.rpu.bin")
+        .assert();
+
+    assert.success();
+
+    temp.child("library/movie.rpu.bin")
+        .assert(predicate::path::eq_file(expected_rpu));
+    temp.child("library/show/ep01.rpu.bin")
+        .assert(predicate::path::missing());
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .current_dir(temp.path())
+        .arg(SUBCOMMAND)
+        .arg("library/show")
+        .arg("--extensions")
+        .arg("mkv")
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "No input file with extension mkv in library/show",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn batch_default_template() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let expected_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let input1 = temp.child("ep01.hevc");
+    let input2 = temp.child("ep02.hevc");
+    input1.write_file(input_file)?;
+    input2.write_file(input_file)?;
+
+    let assert = cmd
+        .current_dir(temp.path())
+        .arg(SUBCOMMAND)
+        .arg("-i")
+        .arg(input1.as_ref())
+        .arg("-i")
+        .arg(input2.as_ref())
+        .assert();
+
+    assert.success();
+
+    temp.child("ep01.rpu.bin")
+        .assert(predicate::path::eq_file(expected_rpu));
+    temp.child("ep02.rpu.bin")
+        .assert(predicate::path::eq_file(expected_rpu));
+
+    Ok(())
+}
+
+#[test]
+fn batch_failed_input() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let expected_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let bad_input = temp.child("ep01.hevc");
+    let good_input = temp.child("ep02.hevc");
+    bad_input.write_file(input_file)?;
+    good_input.write_file(input_file)?;
+
+    // The output can't be created
+    temp.child("ep01.rpu.bin").create_dir_all()?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(bad_input.as_ref())
+        .arg(good_input.as_ref())
+        .arg("--output-template")
+        .arg(format!("{}/{{stem}}.rpu.bin", temp.path().display()))
+        .assert();
+
+    // The other inputs are still processed
+    assert
+        .failure()
+        .stdout(predicate::str::contains(
+            "Processed 2 of 2 inputs: 1 succeeded, 1 failed",
+        ))
+        .stderr(predicate::str::contains("1 of 2 inputs failed"))
+        .stderr(predicate::str::contains("ep01.hevc"));
+
+    temp.child("ep02.rpu.bin")
+        .assert(predicate::path::eq_file(expected_rpu));
+
+    Ok(())
+}
+
+#[test]
+fn batch_same_output() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular.hevc")
+        .arg("assets/hevc_tests/regular_start_code_4.hevc")
+        .arg("--output-template")
+        .arg(temp.child("RPU.bin").as_ref())
+        .assert();
+
+    assert
+        .failure()
+        .stderr(predicate::str::contains("would both be written to"));
+
+    Ok(())
+}
+
+#[test]
+fn batch_rpu_out_conflict() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular.hevc")
+        .arg("assets/hevc_tests/regular_start_code_4.hevc")
+        .arg("--rpu-out")
+        .arg(temp.child("RPU.bin").as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "--output can't be used with multiple inputs",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn rpu_stream_stdout() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let expected_rpu = std::fs::read("assets/hevc_tests/regular_rpu.bin")?;
+
+    let output = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-out")
+        .arg("-")
+        .output()?;
+
+    assert!(output.status.success());
+
+    let stream = output.stdout;
+    assert_eq!(&stream[..4], b"DVRP");
+
+    // Same RPU NALUs as the RPU file, length prefixed instead of start codes
+    let mut rpu_file = Vec::with_capacity(stream.len());
+    let mut frames = 0;
+    let mut pos = 4;
+
+    while pos < stream.len() {
+        let size = u32::from_be_bytes(stream[pos..pos + 4].try_into()?) as usize;
+        pos += 4;
+
+        rpu_file.extend_from_slice(&[0, 0, 0, 1]);
+        rpu_file.extend_from_slice(&stream[pos..pos + size]);
+        pos += size;
+        frames += 1;
+    }
+
+    assert_eq!(frames, 259);
+    assert_eq!(rpu_file, expected_rpu);
+
+    Ok(())
+}
+
+#[test]
+fn field_coded() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg("--field-coded")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    // The pictures are field pairs, the RPU of the first field is kept
+    let fields =
+        utilities_dovi::parse_rpu_file(Path::new("assets/hevc_tests/regular_rpu.bin"))?.unwrap();
+    let frames = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+
+    assert_eq!(frames.len(), fields.len().div_ceil(2));
+
+    for (frame, field) in frames.iter().zip(fields.iter().step_by(2)) {
+        assert_eq!(
+            frame.write_hevc_unspec62_nalu()?,
+            field.write_hevc_unspec62_nalu()?
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn split_dir() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let expected_rpu = std::fs::read("assets/hevc_tests/regular_rpu.bin")?;
+
+    let split_dir = temp.child("RPUs");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--split-dir")
+        .arg(split_dir.as_ref())
+        .arg("--split-json")
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    // The RPU files are the RPU list, frame by frame
+    let mut rpu_files = Vec::new();
+
+    for frame in 0..259 {
+        let rpu_file = split_dir.child(format!("frame_{:06}.rpu", frame));
+        let rpus = utilities_dovi::parse_rpu_file(rpu_file.as_ref())?.unwrap();
+        assert_eq!(rpus.len(), 1);
+
+        rpu_files.extend(std::fs::read(rpu_file.path())?);
+
+        split_dir
+            .child(format!("frame_{:06}.json", frame))
+            .assert(predicate::str::contains("\"dovi_profile\": 8"));
+    }
+
+    assert_eq!(rpu_files, expected_rpu);
+    split_dir
+        .child("frame_000259.rpu")
+        .assert(predicate::path::missing());
+
+    Ok(())
+}
+
+#[test]
+fn split_dir_multiple_inputs() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular.hevc")
+        .arg("assets/hevc_tests/regular_start_code_4.hevc")
+        .arg("--split-dir")
+        .arg(temp.child("RPUs").as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "extract-rpu: --split-dir only supports a single input, got 2",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn remove_mapping() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    // FEL stream, from the BL with the FEL RPU on every frame
+    let rpu_data = std::fs::read("assets/tests/fel_orig.bin")?;
+    let input_rpu = temp.child("RPU.bin");
+    input_rpu.write_binary(&rpu_data.repeat(259))?;
+
+    let input_file = temp.child("fel.hevc");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("inject-rpu")
+        .arg("assets/hevc_tests/regular_bl_start_code_4.hevc")
+        .arg("--rpu-in")
+        .arg(input_rpu.as_ref())
+        .arg("--output")
+        .arg(input_file.as_ref())
+        .assert()
+        .success();
+
+    let output_rpu = temp.child("RPU_identity.bin");
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("--remove-mapping")
+        .arg(SUBCOMMAND)
+        .arg(input_file.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let original = utilities_dovi::parse_rpu_file(input_rpu.as_ref())?.unwrap();
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 259);
+
+    let rpu = &rpus[0];
+    let mapping = rpu.rpu_data_mapping.as_ref().unwrap();
+
+    assert_eq!(
+        rpu.header.pred_pivot_value,
+        [vec![0, 1023], vec![0, 1023], vec![0, 1023]]
+    );
+    assert_eq!(mapping.mapping_idc, [vec![0], vec![0], vec![0]]);
+    assert_eq!(
+        mapping.poly_coef_int,
+        [vec![vec![0, 1]], vec![vec![0, 1]], vec![vec![0, 1]]]
+    );
+
+    // Still FEL, with the same DM metadata
+    assert_eq!(rpu.subprofile.as_deref(), Some("FEL"));
+    assert_eq!(
+        serde_json::to_string(&rpu.vdr_dm_data)?,
+        serde_json::to_string(&original[0].vdr_dm_data)?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn frame_order() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+
+    let extract = |name: &str, order: Vec<usize>| -> Result<Vec<Vec<u8>>> {
+        let order_file = temp.child(format!("{}.txt", name));
+        let lines: Vec<String> = order.iter().map(|v| v.to_string()).collect();
+        order_file.write_str(&lines.join("\n"))?;
+
+        let output_rpu = temp.child(format!("{}.bin", name));
+
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+            .arg(SUBCOMMAND)
+            .arg(input_file)
+            .arg("--frame-order")
+            .arg(order_file.as_ref())
+            .arg("--rpu-out")
+            .arg(output_rpu.as_ref())
+            .assert()
+            .success()
+            .stderr(predicate::str::is_empty());
+
+        let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+
+        Ok(rpus.iter().map(|rpu| rpu.write_rpu().unwrap()).collect())
+    };
+
+    // Decoding order, then reversed
+    let decoded = extract("decoded", (0..259).collect())?;
+    let mut reversed = extract("reversed", (0..259).rev().collect())?;
+
+    assert_eq!(decoded.len(), 259);
+
+    reversed.reverse();
+    assert_eq!(decoded, reversed);
+
+    Ok(())
+}
+
+#[test]
+fn frame_order_mismatched_count() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let order_file = temp.child("order.txt");
+    order_file.write_str("0\n1\n2\n")?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular.hevc")
+        .arg("--frame-order")
+        .arg(order_file.as_ref())
+        .arg("--rpu-out")
+        .arg(temp.child("RPU.bin").as_ref())
+        .assert();
+
+    assert
+        .failure()
+        .stdout(predicate::str::contains(
+            "Warning: mismatched frame count. frame order 3, video 259",
+        ))
+        .stderr(predicate::str::contains(
+            "Invalid frame order: no entry for decoded frame 3",
+        ));
+
+    Ok(())
+}
+
+/// Profile 7 FEL RPUs for the first 100 frames, then profile 8.1
+fn mixed_profile_stream(temp: &assert_fs::TempDir) -> Result<assert_fs::fixture::ChildPath> {
+    let fel_rpu = std::fs::read("assets/tests/fel_orig.bin")?;
+    let p81_rpu = std::fs::read("assets/tests/profile8.bin")?;
+
+    let input_rpu = temp.child("RPU_mixed.bin");
+    input_rpu.write_binary(&[fel_rpu.repeat(100), p81_rpu.repeat(159)].concat())?;
+
+    let input_file = temp.child("mixed.hevc");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("inject-rpu")
+        .arg("assets/hevc_tests/regular_bl_start_code_4.hevc")
+        .arg("--rpu-in")
+        .arg(input_rpu.as_ref())
+        .arg("--output")
+        .arg(input_file.as_ref())
+        .assert()
+        .success();
+
+    Ok(input_file)
+}
+
+#[test]
+fn mixed_profiles_report() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let input_file = mixed_profile_stream(&temp)?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg(input_file.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Warning: mixed profiles in the stream:\n  \
+             frames 0-99: profile 7 FEL, 10 bit BL\n  \
+             frames 100-258: profile 8, 10 bit BL\n\
+             Use --normalize to convert the RPUs to a single profile",
+        ));
+
+    // Written as is
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus[0].dovi_profile, 7);
+    assert_eq!(rpus[258].dovi_profile, 8);
+
+    Ok(())
+}
+
+#[test]
+fn mixed_profiles_normalize() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let input_file = mixed_profile_stream(&temp)?;
+
+    for (normalize, profile, subprofile) in [("8.1", 8, None), ("7-mel", 7, Some("MEL"))] {
+        let output_rpu = temp.child(format!("RPU_{}.bin", normalize));
+
+        let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+            .arg(SUBCOMMAND)
+            .arg(input_file.as_ref())
+            .arg("--normalize")
+            .arg(normalize)
+            .arg("--rpu-out")
+            .arg(output_rpu.as_ref())
+            .assert();
+
+        assert
+            .success()
+            .stderr(predicate::str::is_empty())
+            .stdout(predicate::str::contains("Normalizing the RPUs to profile"));
+
+        let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+        assert_eq!(rpus.len(), 259);
+
+        for rpu in &rpus {
+            assert_eq!(rpu.dovi_profile, profile);
+            assert_eq!(rpu.subprofile.as_deref(), subprofile);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn normalize_conflicts_with_mode() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let assert = cmd
+        .arg("--mode")
+        .arg("2")
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular.hevc")
+        .arg("--normalize")
+        .arg("8.4")
+        .arg("--rpu-out")
+        .arg(temp.child("RPU.bin").as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "extract-rpu: --normalize cannot be used with --mode",
+    ));
+
+    Ok(())
+}
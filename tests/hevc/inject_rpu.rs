@@ -50,6 +50,154 @@ fn inject() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn inject_rpu_stream() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_bl_start_code_4.hevc");
+    let output_file = temp.child("injected_output.hevc");
+    let expected_bl_rpu = Path::new("assets/hevc_tests/regular_start_code_4.hevc");
+
+    let rpu_stream = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("extract-rpu")
+        .arg("assets/hevc_tests/regular.hevc")
+        .arg("--rpu-out")
+        .arg("-")
+        .output()?
+        .stdout;
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-in")
+        .arg("-")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .write_stdin(rpu_stream)
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_file
+        .assert(predicate::path::is_file())
+        .assert(predicate::path::eq_file(expected_bl_rpu));
+
+    Ok(())
+}
+
+#[test]
+fn piped_video() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+
+    let input_file = Path::new("assets/hevc_tests/regular_bl_start_code_4.hevc");
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+    let expected_bl_rpu = std::fs::read("assets/hevc_tests/regular_start_code_4.hevc")?;
+
+    let output = cmd
+        .arg(SUBCOMMAND)
+        .arg("-")
+        .arg("--rpu-in")
+        .arg(input_rpu)
+        .arg("--output")
+        .arg("-")
+        .write_stdin(std::fs::read(input_file)?)
+        .output()?;
+
+    assert!(output.status.success());
+    assert!(output.stdout == expected_bl_rpu);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Injecting RPUs into the piped video"));
+
+    Ok(())
+}
+
+#[test]
+fn piped_video_mismatch() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_bl_start_code_4.hevc");
+    let output_file = temp.child("injected_output.hevc");
+
+    // 100 RPUs for the 259 frames
+    let rpu_data = std::fs::read("assets/tests/mel_orig.bin")?;
+    let short_rpu = temp.child("RPU.bin");
+    short_rpu.write_binary(&rpu_data.repeat(100))?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("-")
+        .arg("--rpu-in")
+        .arg(short_rpu.as_ref())
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .arg("--fix")
+        .arg("fail")
+        .write_stdin(std::fs::read(input_file)?)
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "Mismatched lengths: video frame 103 has no RPU, RPU 100 frames",
+    ));
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("-")
+        .arg("--rpu-in")
+        .arg(short_rpu.as_ref())
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .write_stdin(std::fs::read(input_file)?)
+        .assert();
+
+    assert.success().stdout(predicate::str::contains(
+        "the metadata is duplicated at the end",
+    ));
+
+    let output_rpu = temp.child("RPU_out.bin");
+    let expected_rpu = temp.child("RPU_expected.bin");
+    expected_rpu.write_binary(&rpu_data.repeat(259))?;
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("extract-rpu")
+        .arg(output_file.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert()
+        .success();
+
+    output_rpu.assert(predicate::path::eq_file(expected_rpu.path()));
+
+    Ok(())
+}
+
+#[test]
+fn inject_rpu_stream_invalid() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_bl_start_code_4.hevc");
+    let output_file = temp.child("injected_output.hevc");
+
+    // An RPU file is not a framed stream
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-in")
+        .arg("-")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .write_stdin(std::fs::read("assets/hevc_tests/regular_rpu.bin")?)
+        .assert();
+
+    assert
+        .failure()
+        .stderr(predicate::str::contains("Invalid RPU stream"));
+
+    Ok(())
+}
+
 #[test]
 fn inject_aud() -> Result<()> {
     let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
@@ -139,3 +287,388 @@ fn annexb() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn fix_truncate() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_bl_start_code_4.hevc");
+
+    // 250 RPUs for the 259 frames
+    let rpu_data = std::fs::read("assets/tests/mel_orig.bin")?;
+    let input_rpu = temp.child("RPU.bin");
+    input_rpu.write_binary(&rpu_data.repeat(250))?;
+
+    let output_file = temp.child("injected_output.hevc");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-in")
+        .arg(input_rpu.as_ref())
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .arg("--fix")
+        .arg("truncate")
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Video will be truncated to 250 frames",
+        ));
+
+    let output_rpu = temp.child("RPU_out.bin");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("extract-rpu")
+        .arg(output_file.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert()
+        .success();
+
+    output_rpu.assert(predicate::path::eq_file(input_rpu.path()));
+
+    Ok(())
+}
+
+#[test]
+fn fix_fail() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_bl_start_code_4.hevc");
+
+    let rpu_data = std::fs::read("assets/tests/mel_orig.bin")?;
+    let input_rpu = temp.child("RPU.bin");
+    input_rpu.write_binary(&rpu_data.repeat(270))?;
+
+    let output_file = temp.child("injected_output.hevc");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-in")
+        .arg(input_rpu.as_ref())
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .arg("--fix")
+        .arg("fail")
+        .assert();
+
+    assert
+        .failure()
+        .stderr(predicate::str::contains(
+            "Mismatched lengths: video 259 frames, RPU 270 frames",
+        ))
+        .stderr(predicate::str::contains(
+            "RPUs 259 to 269 have no video frame, 11 more than the video",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn convert_mode_2() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_bl_start_code_4.hevc");
+
+    let rpu_data = std::fs::read("assets/tests/mel_orig.bin")?;
+    let input_rpu = temp.child("RPU.bin");
+    input_rpu.write_binary(&rpu_data.repeat(259))?;
+
+    let converted_data = std::fs::read("assets/tests/mel_to_81.bin")?;
+    let expected_rpu = temp.child("RPU_expected.bin");
+    expected_rpu.write_binary(&converted_data.repeat(259))?;
+
+    let output_file = temp.child("injected_output.hevc");
+
+    let assert = cmd
+        .arg("--mode")
+        .arg("2")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-in")
+        .arg(input_rpu.as_ref())
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("Converting RPUs..."));
+
+    let output_rpu = temp.child("RPU_out.bin");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("extract-rpu")
+        .arg(output_file.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert()
+        .success();
+
+    output_rpu.assert(predicate::path::eq_file(expected_rpu.path()));
+
+    Ok(())
+}
+
+#[test]
+fn field_coded() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_bl_start_code_4.hevc");
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let output_file = temp.child("injected_output.hevc");
+    let output_rpu = temp.child("RPU.bin");
+
+    // Every picture is a field, the extra RPUs are dropped
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("--field-coded")
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-in")
+        .arg(input_rpu)
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("extract-rpu")
+        .arg(output_file.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let frames = utilities_dovi::parse_rpu_file(input_rpu)?.unwrap();
+    let fields = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+
+    assert_eq!(fields.len(), frames.len());
+
+    // Both fields use the frame RPU, the second one is never a scene cut
+    for (i, field) in fields.iter().enumerate() {
+        let mut expected = frames[i / 2].clone();
+
+        if i % 2 == 1 {
+            expected.vdr_dm_data.as_mut().unwrap().set_scene_cut(false);
+            expected.modified = true;
+        }
+
+        assert_eq!(
+            field.write_hevc_unspec62_nalu()?,
+            expected.write_hevc_unspec62_nalu()?
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn verify() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_bl_start_code_4.hevc");
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let output_file = temp.child("injected_output.hevc");
+    let expected_bl_rpu = Path::new("assets/hevc_tests/regular_start_code_4.hevc");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-in")
+        .arg(input_rpu)
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .arg("--verify")
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Verified 259 frames, each with its injected RPU",
+        ));
+
+    output_file.assert(predicate::path::eq_file(expected_bl_rpu));
+
+    Ok(())
+}
+
+#[test]
+fn verify_piped_video_duplicated() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular_bl_start_code_4.hevc");
+    let output_file = temp.child("injected_output.hevc");
+
+    // 100 RPUs for the 259 frames, the last one is duplicated
+    let rpu_data = std::fs::read("assets/tests/mel_orig.bin")?;
+    let short_rpu = temp.child("RPU.bin");
+    short_rpu.write_binary(&rpu_data.repeat(100))?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("-")
+        .arg("--rpu-in")
+        .arg(short_rpu.as_ref())
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .arg("--verify")
+        .write_stdin(std::fs::read(input_file)?)
+        .assert();
+
+    assert.success().stdout(predicate::str::contains(
+        "Verified 259 frames, each with its injected RPU",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn verify_stdout() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+
+    let input_file = Path::new("assets/hevc_tests/regular_bl_start_code_4.hevc");
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file)
+        .arg("--rpu-in")
+        .arg(input_rpu)
+        .arg("--output")
+        .arg("-")
+        .arg("--verify")
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "inject-rpu: --verify cannot be used when writing to stdout",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn in_place() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+    let expected_file = Path::new("assets/hevc_tests/regular_start_code_4.hevc");
+
+    let input_file = temp.child("video.hevc");
+    input_file.write_binary(&std::fs::read(expected_file)?)?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file.as_ref())
+        .arg("--rpu-in")
+        .arg(input_rpu)
+        .arg("--in-place")
+        .arg("--verify")
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Verified 259 frames, each with its injected RPU",
+        ));
+
+    // Same RPUs, the file is unchanged
+    input_file.assert(predicate::path::eq_file(expected_file));
+
+    Ok(())
+}
+
+#[test]
+fn in_place_larger_rpus() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = temp.child("video.hevc");
+    input_file.write_binary(&std::fs::read(
+        "assets/hevc_tests/regular_start_code_4.hevc",
+    )?)?;
+
+    let edited_rpu = temp.child("RPU_edited.bin");
+    let extracted_rpu = temp.child("RPU_extracted.bin");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("editor")
+        .arg("assets/hevc_tests/regular_rpu.bin")
+        .arg("--json")
+        .arg("assets/editor_examples/cmv40_levels.json")
+        .arg("--rpu-out")
+        .arg(edited_rpu.as_ref())
+        .assert()
+        .success();
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg(input_file.as_ref())
+        .arg("--rpu-in")
+        .arg(edited_rpu.as_ref())
+        .arg("--in-place")
+        .arg("--verify")
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Verified 259 frames, each with its injected RPU",
+        ));
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("extract-rpu")
+        .arg(input_file.as_ref())
+        .arg("--rpu-out")
+        .arg(extracted_rpu.as_ref())
+        .assert()
+        .success();
+
+    extracted_rpu.assert(predicate::path::eq_file(edited_rpu.path()));
+
+    Ok(())
+}
+
+#[test]
+fn in_place_no_rpu() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = temp.child("video.hevc");
+    input_file.write_binary(&std::fs::read(
+        "assets/hevc_tests/regular_bl_start_code_4.hevc",
+    )?)?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_file.as_ref())
+        .arg("--rpu-in")
+        .arg("assets/hevc_tests/regular_rpu.bin")
+        .arg("--in-place")
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "inject-rpu: frame 0 has no RPU, --in-place requires a Dolby Vision input",
+    ));
+
+    input_file.assert(predicate::path::eq_file(Path::new(
+        "assets/hevc_tests/regular_bl_start_code_4.hevc",
+    )));
+
+    Ok(())
+}
@@ -1,5 +1,112 @@
+mod add_dv;
 mod convert;
 mod demux;
 mod extract_rpu;
 mod inject_rpu;
 mod mux;
+mod remove;
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+/// Serves the file over HTTP on a local port, with range requests unless `ranges` is false.
+/// `/redirect` redirects to the file, and `/chunked` serves it with chunked transfer encoding.
+///
+/// Returns the URL of the file, and the number of range requests served.
+pub fn serve_file_http(path: &Path, ranges: bool) -> (String, Arc<AtomicUsize>) {
+    let data = std::fs::read(path).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let range_requests = Arc::new(AtomicUsize::new(0));
+    let counter = range_requests.clone();
+
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let target = request_line
+                .split_whitespace()
+                .nth(1)
+                .unwrap_or("")
+                .to_string();
+
+            let mut range = None;
+            let mut line = String::new();
+
+            while reader.read_line(&mut line).unwrap() > 2 {
+                if let Some(value) = line.trim_end().strip_prefix("Range: bytes=") {
+                    let (start, end) = value.split_once('-').unwrap();
+                    let end = match end {
+                        "" => data.len() - 1,
+                        end => end.parse::<usize>().unwrap(),
+                    };
+
+                    range = Some((start.parse::<usize>().unwrap(), end));
+                }
+
+                line.clear();
+            }
+
+            let response = match range {
+                _ if target == "/redirect" => {
+                    "HTTP/1.1 302 Found\r\nLocation: /file.hevc\r\nContent-Length: 0\r\n\r\n"
+                        .as_bytes()
+                        .to_vec()
+                }
+                None if target == "/chunked" => {
+                    let mut response =
+                        b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+
+                    for chunk in data.chunks(10_000) {
+                        response
+                            .extend_from_slice(format!("{:x};ext=1\r\n", chunk.len()).as_bytes());
+                        response.extend_from_slice(chunk);
+                        response.extend_from_slice(b"\r\n");
+                    }
+
+                    response.extend_from_slice(b"0\r\nTrailer: value\r\n\r\n");
+
+                    response
+                }
+                Some((start, end)) if ranges => {
+                    counter.fetch_add(1, Ordering::SeqCst);
+
+                    let end = end.min(data.len() - 1);
+                    let mut response = format!(
+                        "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\nContent-Length: {}\r\n\r\n",
+                        start,
+                        end,
+                        data.len(),
+                        end - start + 1
+                    )
+                    .into_bytes();
+                    response.extend_from_slice(&data[start..=end]);
+
+                    response
+                }
+                _ => {
+                    let mut response =
+                        format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", data.len())
+                            .into_bytes();
+                    response.extend_from_slice(&data);
+
+                    response
+                }
+            };
+
+            stream.write_all(&response).ok();
+        }
+    });
+
+    (
+        format!("http://127.0.0.1:{}/file.hevc", port),
+        range_requests,
+    )
+}
@@ -199,3 +199,28 @@ fn edit_config() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn start_code_ts_no_add_aud() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let assert = cmd
+        .arg("--start-code")
+        .arg("ts")
+        .arg(SUBCOMMAND)
+        .arg("--bl")
+        .arg("assets/hevc_tests/no_aud_bl.hevc")
+        .arg("--el")
+        .arg("assets/hevc_tests/no_aud_injected.hevc")
+        .arg("--output")
+        .arg(temp.child("BL_EL_RPU.hevc").as_ref())
+        .arg("--no-add-aud")
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "Muxer: --no-add-aud cannot be used with --start-code ts",
+    ));
+
+    Ok(())
+}
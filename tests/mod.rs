@@ -2,8 +2,11 @@ use anyhow::Result;
 use assert_cmd::Command;
 use predicates::prelude::*;
 
+mod avc;
 mod hevc;
 mod rpu;
+mod serve;
+mod vvc;
 
 #[test]
 fn help() -> Result<()> {
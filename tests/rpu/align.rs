@@ -0,0 +1,166 @@
+use anyhow::Result;
+use assert_cmd::Command;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+const SUBCOMMAND: &str = "align";
+
+/// Scene lengths of the generated source RPU
+const SCENES: &[usize] = &[10, 15, 6, 19, 12, 18, 10];
+
+/// Generates an RPU with a different L1 max for every scene
+fn generate_source(temp: &assert_fs::TempDir) -> Result<assert_fs::fixture::ChildPath> {
+    let mut start = 0;
+    let shots: Vec<String> = SCENES
+        .iter()
+        .enumerate()
+        .map(|(i, duration)| {
+            let shot = format!(
+                r#"{{ "start": {}, "duration": {}, "metadata_blocks": [{{ "Level1": {{ "min_pq": 0, "max_pq": {}, "avg_pq": 1000 }} }}] }}"#,
+                start,
+                duration,
+                2000 + i * 100
+            );
+            start += duration;
+
+            shot
+        })
+        .collect();
+
+    let config = temp.child("generate.json");
+    config.write_str(&format!(
+        r#"{{ "cm_version": "V40", "length": {}, "level6": {{ "max_display_mastering_luminance": 1000, "min_display_mastering_luminance": 1, "max_content_light_level": 1000, "max_frame_average_light_level": 400 }}, "shots": [{}] }}"#,
+        start,
+        shots.join(", ")
+    ))?;
+
+    let source = temp.child("source.bin");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("generate")
+        .arg("--json")
+        .arg(config.as_ref())
+        .arg("--rpu-out")
+        .arg(source.as_ref())
+        .assert()
+        .success();
+
+    Ok(source)
+}
+
+#[test]
+fn help() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd.arg(SUBCOMMAND).arg("--help").assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("dovi_tool align [OPTIONS]"));
+    Ok(())
+}
+
+#[test]
+fn target_rpu_removed_scene() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source = generate_source(&temp)?;
+
+    // The target release has the scene 31-49 cut
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(r#"{ "remove": ["31-49"] }"#)?;
+
+    let target = temp.child("target.bin");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("editor")
+        .arg(source.as_ref())
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(target.as_ref())
+        .assert()
+        .success();
+
+    let output = temp.child("RPU_aligned.bin");
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg(source.as_ref())
+        .arg("--target-rpu")
+        .arg(target.as_ref())
+        .arg("--rpu-out")
+        .arg(output.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty()).stdout(
+        predicate::str::contains("Matched 6 of 6 target scenes to the source")
+            .and(predicate::str::contains(
+                "  Target frames 0-30: source frames 0-30\n  Target frames 31-70: source frames 50-89\n",
+            ))
+            .and(predicate::str::contains(
+                "Trimmed 19 source frames, padded 0 target frames",
+            )),
+    );
+
+    output.assert(predicate::path::eq_file(target.path()));
+
+    Ok(())
+}
+
+#[test]
+fn scene_file_inserted_scene() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source = generate_source(&temp)?;
+
+    // 8 frames inserted before the third scene
+    let scene_file = temp.child("scenes.txt");
+    scene_file.write_str("0\n10\n25\n33\n39\n58\n70\n88\n")?;
+
+    let output = temp.child("RPU_aligned.bin");
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg(source.as_ref())
+        .arg("--scene-file")
+        .arg(scene_file.as_ref())
+        .arg("--length")
+        .arg("98")
+        .arg("--rpu-out")
+        .arg(output.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty()).stdout(
+        predicate::str::contains(
+            "  Target frames 0-32: source frames 0-24, padded 8 frames\n  Target frames 33-97: source frames 25-89\n",
+        ),
+    );
+
+    let rpus = utilities_dovi::parse_rpu_file(output.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 98);
+
+    Ok(())
+}
+
+#[test]
+fn scene_file_requires_length() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source = generate_source(&temp)?;
+
+    let scene_file = temp.child("scenes.txt");
+    scene_file.write_str("0\n10\n25\n")?;
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg(source.as_ref())
+        .arg("--scene-file")
+        .arg(scene_file.as_ref())
+        .arg("--rpu-out")
+        .arg(temp.child("RPU_aligned.bin").as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "align: the scene file has no frame count, use --length",
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use std::path::Path;
+
+use anyhow::Result;
+use assert_cmd::Command;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+const SUBCOMMAND: &str = "concat";
+
+#[test]
+fn help() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd.arg(SUBCOMMAND).arg("--help").assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "dovi_tool concat [OPTIONS] <inputs>...",
+        ));
+    Ok(())
+}
+
+#[test]
+fn concat() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+    let output_rpu = temp.child("RPU_concat.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg(input_rpu)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_rpu.assert(predicate::path::is_file());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 518);
+
+    let scene_refresh_flag = |i: usize| rpus[i].vdr_dm_data.as_ref().unwrap().scene_refresh_flag;
+
+    assert_eq!(scene_refresh_flag(0), 1);
+    assert_eq!(scene_refresh_flag(259), 1);
+    assert_eq!(scene_refresh_flag(260), 0);
+
+    Ok(())
+}
+
+#[test]
+fn mismatched_profile() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let output_rpu = temp.child("RPU_concat.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular_rpu.bin")
+        .arg("assets/tests/fel_orig.bin")
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .failure()
+        .stderr(predicate::str::contains("Mismatched profile"));
+
+    output_rpu.assert(predicate::path::missing());
+
+    Ok(())
+}
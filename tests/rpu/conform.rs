@@ -0,0 +1,120 @@
+use std::path::Path;
+
+use anyhow::Result;
+use assert_cmd::Command;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+use dolby_vision::rpu::dovi_rpu::DoviRpu;
+
+const SUBCOMMAND: &str = "conform";
+
+fn scene_count(rpus: &[DoviRpu]) -> usize {
+    rpus.iter()
+        .filter(|rpu| rpu.vdr_dm_data.as_ref().unwrap().scene_refresh_flag == 1)
+        .count()
+}
+
+#[test]
+fn help() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd.arg(SUBCOMMAND).arg("--help").assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "dovi_tool conform [OPTIONS] --from-fps <FROM_FPS> --to-fps <TO_FPS> [input_pos]",
+        ));
+    Ok(())
+}
+
+#[test]
+fn duplicate_frames() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+    let output_rpu = temp.child("RPU_30.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--from-fps")
+        .arg("25")
+        .arg("--to-fps")
+        .arg("30")
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Conforming 259 frames at 25.000 fps to 311 frames at 30.000 fps",
+        ));
+
+    let original = utilities_dovi::parse_rpu_file(input_rpu)?.unwrap();
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 311);
+
+    assert_eq!(scene_count(&rpus), scene_count(&original));
+
+    // Third frame duplicated
+    assert_eq!(rpus[3].write_rpu()?, rpus[2].write_rpu()?);
+    assert_eq!(rpus[4].write_rpu()?, original[3].write_rpu()?);
+
+    Ok(())
+}
+
+#[test]
+fn drop_frames() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+    let output_rpu = temp.child("RPU_24.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--from-fps")
+        .arg("30000/1001")
+        .arg("--to-fps")
+        .arg("24000/1001")
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let original = utilities_dovi::parse_rpu_file(input_rpu)?.unwrap();
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 207);
+
+    assert_eq!(scene_count(&rpus), scene_count(&original));
+    assert_eq!(rpus[2].write_rpu()?, original[3].write_rpu()?);
+
+    Ok(())
+}
+
+#[test]
+fn invalid_fps() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular_rpu.bin")
+        .arg("--from-fps")
+        .arg("0")
+        .arg("--to-fps")
+        .arg("24")
+        .assert();
+
+    assert
+        .failure()
+        .stderr(predicate::str::contains("Invalid frame rate: 0"));
+
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use std::path::Path;
+
+use anyhow::Result;
+use assert_cmd::Command;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlock;
+
+const SUBCOMMAND: &str = "detect-active-area";
+
+#[test]
+fn help() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd.arg(SUBCOMMAND).arg("--help").assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "dovi_tool detect-active-area [OPTIONS] --frames <FRAMES> --width <WIDTH> --height <HEIGHT> [input_pos]",
+        ));
+    Ok(())
+}
+
+#[test]
+fn letterboxed_frames() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let (width, height) = (64, 36);
+    let frame_count = 259;
+
+    // Black bars of 4 lines at the top and bottom
+    let mut frame = vec![128_u8; width * height];
+    frame[..4 * width].fill(16);
+    frame[(height - 4) * width..].fill(16);
+
+    let frames = temp.child("frames.raw");
+    frames.write_binary(&frame.repeat(frame_count))?;
+
+    let output_rpu = temp.child("RPU_active_area.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--frames")
+        .arg(frames.as_ref())
+        .arg("--width")
+        .arg(width.to_string())
+        .arg("--height")
+        .arg(height.to_string())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_rpu.assert(predicate::path::is_file());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), frame_count);
+
+    for rpu in &rpus {
+        let block = rpu.vdr_dm_data.as_ref().unwrap().get_block(5).unwrap();
+
+        if let ExtMetadataBlock::Level5(b) = block {
+            assert_eq!(b.get_offsets_vec(), vec![0, 0, 4, 4]);
+        } else {
+            panic!("No L5 block");
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn mismatched_frame_count() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let frames = temp.child("frames.raw");
+    frames.write_binary(&vec![128_u8; 16 * 16 * 10])?;
+
+    let output_rpu = temp.child("RPU_active_area.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--frames")
+        .arg(frames.as_ref())
+        .arg("--width")
+        .arg("16")
+        .arg("--height")
+        .arg("16")
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "mismatched frame count: frames 10, RPU 259",
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,232 @@
+use std::path::Path;
+
+use anyhow::Result;
+use assert_cmd::Command;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+const SUBCOMMAND: &str = "diff";
+
+#[test]
+fn help() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd.arg(SUBCOMMAND).arg("--help").assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "dovi_tool diff [OPTIONS] <input> [input2]",
+        ));
+    Ok(())
+}
+
+#[test]
+fn identical() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let assert = cmd.arg(SUBCOMMAND).arg(input_rpu).arg(input_rpu).assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("The RPU files are identical."));
+    Ok(())
+}
+
+#[test]
+fn edited_active_area() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+    let edit_config = Path::new("assets/editor_examples/active_area_all.json");
+    let edited_rpu = temp.child("RPU.bin");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("editor")
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config)
+        .arg("--rpu-out")
+        .arg(edited_rpu.as_ref())
+        .assert()
+        .success();
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg(edited_rpu.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Frame 0:\n  L5.active_area_top_offset: 0 -> 210\n  L5.active_area_bottom_offset: 0 -> 210\n",
+        ))
+        .stdout(predicate::str::contains("L1.").not())
+        .stdout(predicate::str::contains(
+            "Summary:\n  Differing frames: 259\n  L5: 259 frames\n",
+        ));
+
+    // Summary only
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg(edited_rpu.as_ref())
+        .arg("--summary")
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("Frame 0:").not())
+        .stdout(predicate::str::contains(
+            "Summary:\n  Differing frames: 259\n  L5: 259 frames\n",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn converted_profile() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/tests/fel_orig.bin")
+        .arg("assets/tests/fel_to_81.bin")
+        .arg("--summary")
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Summary:\n  Differing frames: 1\n  dovi_profile: 1 frames\n  header: 1 frames\n  rpu_data_nlq: 1 frames\n",
+        ));
+    Ok(())
+}
+
+#[test]
+fn mismatched_frame_count() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular_rpu.bin")
+        .arg("assets/tests/fel_orig.bin")
+        .arg("--summary")
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Frame count differs: 259 and 1, comparing the first 1 frames",
+        ));
+    Ok(())
+}
+
+#[test]
+fn xml_matching() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/tests/cmv4_0_2_xml_with_l5_rpu.bin")
+        .arg("--xml")
+        .arg("assets/tests/cmv4_0_2.xml")
+        .arg("--canvas-width")
+        .arg("3840")
+        .arg("--canvas-height")
+        .arg("2160")
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "The RPU metadata matches the XML, within 0 codes",
+        ));
+    Ok(())
+}
+
+#[test]
+fn xml_l5_mismatch() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/tests/cmv4_0_2_xml_rpu.bin")
+        .arg("--xml")
+        .arg("assets/tests/cmv4_0_2.xml")
+        .arg("--canvas-width")
+        .arg("3840")
+        .arg("--canvas-height")
+        .arg("2160")
+        .assert();
+
+    assert
+        .failure()
+        .stdout(predicate::str::contains(
+            "Shot 1 (ID 06dbea53-d78e-4031-8a5b-e9a87d6fc2c9), frames 120-218:\n  \
+            L5.active_area_left_offset: 0 -> 480 (frame 120)\n  \
+            L5.active_area_right_offset: 0 -> 480 (frame 120)\n",
+        ))
+        .stderr(predicate::str::contains(
+            "RPU metadata differs from the XML in 3 of 3 shots, by more than 0 codes",
+        ));
+    Ok(())
+}
+
+#[test]
+fn xml_l1_tolerance() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/tests/cmv4_0_2_xml_rpu.bin");
+    let xml = Path::new("assets/tests/cmv4_0_2.xml");
+    let edit_config = Path::new("assets/editor_examples/l1_brightness.json");
+    let edited_rpu = temp.child("RPU.bin");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("editor")
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config)
+        .arg("--rpu-out")
+        .arg(edited_rpu.as_ref())
+        .assert()
+        .success();
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg(edited_rpu.as_ref())
+        .arg("--xml")
+        .arg(xml)
+        .assert();
+
+    assert.failure().stdout(predicate::str::contains(
+        "Shot 0 (ID 7b4b3d42-81c8-4767-aa38-f08a6f35994b), frames 0-119:\n  \
+            L1.max_pq: 2701 -> 2828 (frame 0)\n  \
+            L1.avg_pq: 1034 -> 1120 (frame 0)\n",
+    ));
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg(edited_rpu.as_ref())
+        .arg("--xml")
+        .arg(xml)
+        .arg("--tolerance")
+        .arg("150")
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "The RPU metadata matches the XML, within 150 codes",
+        ));
+    Ok(())
+}
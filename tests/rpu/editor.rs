@@ -5,7 +5,10 @@ use assert_cmd::Command;
 use assert_fs::prelude::*;
 use predicates::prelude::*;
 
+use dolby_vision::rpu::dovi_rpu::DoviRpu;
 use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlock;
+use dolby_vision::rpu::generate::TrimAlgorithm;
+use dolby_vision::utils::pq_to_nits;
 
 const SUBCOMMAND: &str = "editor";
 
@@ -18,7 +21,7 @@ fn help() -> Result<()> {
         .success()
         .stderr(predicate::str::is_empty())
         .stdout(predicate::str::contains(
-            "dovi_tool editor [OPTIONS] --json <json> [input_pos]",
+            "dovi_tool editor [OPTIONS] [input_pos]",
         ));
     Ok(())
 }
@@ -55,6 +58,38 @@ fn mode() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn mode_rpu_stream() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+
+    let input_rpu = Path::new("assets/tests/fel_orig.bin");
+    let edit_config = Path::new("assets/editor_examples/mode.json");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config)
+        .arg("--rpu-out")
+        .arg("-")
+        .assert();
+
+    let assert = assert
+        .success()
+        .stderr(predicate::str::contains("Writing RPU file..."));
+
+    let stream = &assert.get_output().stdout;
+    assert_eq!(&stream[..4], b"DVRP");
+
+    let size = u32::from_be_bytes(stream[4..8].try_into()?) as usize;
+    assert_eq!(stream.len(), 8 + size);
+
+    let rpu = DoviRpu::parse_unspec62_nalu(&stream[8..])?;
+    assert_eq!(rpu.dovi_profile, 8);
+
+    Ok(())
+}
+
 #[test]
 fn convert_to_cmv4() -> Result<()> {
     let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
@@ -108,6 +143,154 @@ fn convert_to_cmv4() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn cmv40_levels() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/tests/fel_orig.bin");
+    let edit_config = Path::new("assets/editor_examples/cmv40_levels.json");
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_rpu.assert(predicate::path::is_file());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 1);
+
+    let vdr_dm_data = rpus[0].vdr_dm_data.as_ref().unwrap();
+
+    // 2 L8, L9, L10, L11 and L254
+    assert_eq!(vdr_dm_data.metadata_blocks(3).unwrap().len(), 6);
+
+    let level8: Vec<_> = vdr_dm_data
+        .level_blocks_iter(8)
+        .filter_map(|b| match b {
+            ExtMetadataBlock::Level8(b) => Some(b),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(level8.len(), 2);
+    assert_eq!(level8[0].target_display_index, 1);
+    assert_eq!(level8[0].trim_slope, 2100);
+    assert_eq!(level8[1].target_display_index, 48);
+    assert_eq!(level8[1].length, 12);
+    assert_eq!(level8[1].trim_power, 1900);
+
+    if let Some(ExtMetadataBlock::Level9(level9)) = vdr_dm_data.get_block(9) {
+        assert_eq!(level9.length, 17);
+        assert_eq!(level9.source_primary_index, 255);
+        assert_eq!(level9.source_primary_red_x, 22314);
+        assert_eq!(level9.source_primary_white_y, 10781);
+    } else {
+        panic!("No L9 block");
+    }
+
+    if let Some(ExtMetadataBlock::Level10(level10)) = vdr_dm_data.get_block(10) {
+        assert_eq!(level10.target_display_index, 20);
+        assert_eq!(level10.target_max_pq, 2081);
+        assert_eq!(level10.target_primary_index, 2);
+    } else {
+        panic!("No L10 block");
+    }
+
+    if let Some(ExtMetadataBlock::Level11(level11)) = vdr_dm_data.get_block(11) {
+        assert_eq!(level11.content_type, 2);
+        assert!(!level11.reference_mode_flag);
+    } else {
+        panic!("No L11 block");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn level11_content_type() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/tests/cmv40_full_rpu.bin");
+    let edit_config = Path::new("assets/editor_examples/level11_content_type.json");
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 1);
+
+    let vdr_dm_data = rpus[0].vdr_dm_data.as_ref().unwrap();
+
+    if let Some(ExtMetadataBlock::Level11(level11)) = vdr_dm_data.get_block(11) {
+        assert_eq!(level11.content_type, 2);
+        assert_eq!(level11.whitepoint, 0);
+        assert!(!level11.reference_mode_flag);
+    } else {
+        panic!("No L11 block");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn level254() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/tests/fel_orig.bin");
+    let edit_config = Path::new("assets/editor_examples/level254.json");
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 1);
+
+    let vdr_dm_data = rpus[0].vdr_dm_data.as_ref().unwrap();
+
+    // Converted to CM v4.0, with the single L254 block replaced
+    assert_eq!(vdr_dm_data.level_blocks_iter(254).count(), 1);
+
+    if let Some(ExtMetadataBlock::Level254(level254)) = vdr_dm_data.get_block(254) {
+        assert_eq!(level254.dm_mode, 1);
+        assert_eq!(level254.dm_version_index, 2);
+    } else {
+        panic!("No L254 block");
+    }
+
+    Ok(())
+}
+
 #[test]
 fn active_area_specific() -> Result<()> {
     let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
@@ -160,3 +343,2028 @@ fn active_area_specific() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn scene_file_frame_list() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str("{}")?;
+
+    let scene_file = temp.child("scenes.txt");
+    scene_file.write_str("# Scene cuts\n100\n200\n")?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--scene-file")
+        .arg(scene_file.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_rpu.assert(predicate::path::is_file());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 259);
+
+    let level1 = |rpu: &DoviRpu| match rpu.vdr_dm_data.as_ref().unwrap().get_block(1) {
+        Some(ExtMetadataBlock::Level1(b)) => (b.min_pq, b.max_pq, b.avg_pq),
+        _ => panic!("No L1 block"),
+    };
+
+    for scene in [0..100, 100..200, 200..259] {
+        let first_l1 = level1(&rpus[scene.start]);
+
+        for (i, rpu) in rpus[scene].iter().enumerate() {
+            let vdr_dm_data = rpu.vdr_dm_data.as_ref().unwrap();
+
+            assert_eq!(vdr_dm_data.scene_refresh_flag, (i == 0) as u64);
+            assert_eq!(level1(rpu), first_l1);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn shift_delay() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(r#"{ "shift": 10 }"#)?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let original = utilities_dovi::parse_rpu_file(input_rpu)?.unwrap();
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 269);
+
+    let scene_cut = |rpu: &DoviRpu| rpu.vdr_dm_data.as_ref().unwrap().scene_refresh_flag;
+
+    // Padded with the first RPU, a single scene
+    for (i, rpu) in rpus[..10].iter().enumerate() {
+        assert_eq!(scene_cut(rpu), (i == 0) as u64);
+    }
+
+    for (rpu, original_rpu) in rpus[10..].iter().zip(original.iter()) {
+        assert_eq!(rpu.write_rpu()?, original_rpu.write_rpu()?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn shift_advance() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(r#"{ "shift": -10 }"#)?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let original = utilities_dovi::parse_rpu_file(input_rpu)?.unwrap();
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 249);
+
+    // The first remaining RPU starts a scene
+    assert_eq!(rpus[0].vdr_dm_data.as_ref().unwrap().scene_refresh_flag, 1);
+
+    for (rpu, original_rpu) in rpus[1..].iter().zip(original[11..].iter()) {
+        assert_eq!(rpu.write_rpu()?, original_rpu.write_rpu()?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn shift_advance_too_far() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(r#"{ "shift": -259 }"#)?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .failure()
+        .stderr(predicate::str::contains("invalid shift -259"));
+
+    Ok(())
+}
+
+#[test]
+fn drop_l6() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(r#"{ "drop_l6": true }"#)?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 259);
+
+    rpus.iter().for_each(|rpu| {
+        let vdr_dm_data = rpu.vdr_dm_data.as_ref().unwrap();
+        assert!(vdr_dm_data.get_block(6).is_none());
+    });
+
+    Ok(())
+}
+
+#[test]
+fn l1_max_nits() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(r#"{ "l1_max_nits": 100 }"#)?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 259);
+
+    // 100 nits is PQ code 2081
+    rpus.iter().for_each(|rpu| {
+        let vdr_dm_data = rpu.vdr_dm_data.as_ref().unwrap();
+
+        if let Some(ExtMetadataBlock::Level1(level1)) = vdr_dm_data.get_block(1) {
+            assert!(level1.max_pq <= 2081);
+            assert!(level1.min_pq <= level1.avg_pq && level1.avg_pq <= level1.max_pq);
+        } else {
+            panic!("No L1 block");
+        }
+    });
+
+    Ok(())
+}
+
+#[test]
+fn l1_scale() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(r#"{ "l1_scale": 0.5 }"#)?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let original = utilities_dovi::parse_rpu_file(input_rpu)?.unwrap();
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), original.len());
+
+    let level1 = |rpu: &DoviRpu| {
+        if let Some(ExtMetadataBlock::Level1(level1)) =
+            rpu.vdr_dm_data.as_ref().unwrap().get_block(1)
+        {
+            level1.clone()
+        } else {
+            panic!("No L1 block");
+        }
+    };
+
+    original.iter().zip(rpus.iter()).for_each(|(orig, rpu)| {
+        let orig_l1 = level1(orig);
+        let l1 = level1(rpu);
+
+        let orig_nits = pq_to_nits(orig_l1.max_pq as f64 / 4095.0);
+        let nits = pq_to_nits(l1.max_pq as f64 / 4095.0);
+
+        assert!((nits / orig_nits - 0.5).abs() < 0.01);
+    });
+
+    Ok(())
+}
+
+#[test]
+fn l1_scale_invalid() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(r#"{ "l1_scale": 0 }"#)?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .assert();
+
+    assert
+        .failure()
+        .stderr(predicate::str::contains("Invalid L1 scale 0"));
+
+    Ok(())
+}
+
+#[test]
+fn brightness_offset() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(r#"{ "brightness_offset": { "nits": -50, "level6": true } }"#)?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let original = utilities_dovi::parse_rpu_file(input_rpu)?.unwrap();
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), original.len());
+
+    original.iter().zip(rpus.iter()).for_each(|(orig, rpu)| {
+        let orig_dm = orig.vdr_dm_data.as_ref().unwrap();
+        let dm = rpu.vdr_dm_data.as_ref().unwrap();
+
+        if let (Some(ExtMetadataBlock::Level1(orig_l1)), Some(ExtMetadataBlock::Level1(l1))) =
+            (orig_dm.get_block(1), dm.get_block(1))
+        {
+            let orig_nits = pq_to_nits(orig_l1.max_pq as f64 / 4095.0);
+            let nits = pq_to_nits(l1.max_pq as f64 / 4095.0);
+
+            assert!((orig_nits - nits - 50.0).abs() < 2.0);
+            assert_eq!(l1.min_pq, 0);
+        } else {
+            panic!("No L1 block");
+        }
+
+        if let Some(ExtMetadataBlock::Level6(l6)) = dm.get_block(6) {
+            assert_eq!(l6.max_display_mastering_luminance, 1000);
+            assert_eq!(l6.max_content_light_level, 3898);
+            assert_eq!(l6.max_frame_average_light_level, 70);
+        } else {
+            panic!("No L6 block");
+        }
+    });
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    edit_config.write_str(r#"{ "brightness_offset": { "nits": 20000 } }"#)?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .failure()
+        .stderr(predicate::str::contains("Invalid brightness offset 20000"));
+
+    Ok(())
+}
+
+#[test]
+fn l1_csv_nits() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let l1_csv = temp.child("l1.csv");
+    l1_csv.write_str("scene_start,min,mid,max\n0,0,10,100\n100,0.005,100,1000\n")?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--l1-csv")
+        .arg(l1_csv.as_ref())
+        .arg("--l1-csv-units")
+        .arg("nits")
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("Applied L1 metadata to 2 scenes"));
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 259);
+
+    let level1 = |frame: usize| -> (u16, u16, u16) {
+        match rpus[frame].vdr_dm_data.as_ref().unwrap().get_block(1) {
+            Some(ExtMetadataBlock::Level1(level1)) => (level1.min_pq, level1.avg_pq, level1.max_pq),
+            _ => panic!("No L1 block"),
+        }
+    };
+
+    assert_eq!(level1(0), (0, 1227, 2081));
+    assert_eq!(level1(99), (0, 1227, 2081));
+    assert_eq!(level1(100), (12, 2081, 3079));
+    assert_eq!(level1(258), (12, 2081, 3079));
+
+    Ok(())
+}
+
+#[test]
+fn l1_csv_invalid() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let l1_csv = temp.child("l1.csv");
+    l1_csv.write_str("0,0,2081,1000\n")?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--l1-csv")
+        .arg(l1_csv.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "Invalid L1 CSV line 1: expected min <= mid <= max",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn remove_timecode() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(r#"{ "remove": ["00:00:01.000-00:00:02.000"] }"#)?;
+
+    let frames_config = temp.child("frames.json");
+    frames_config.write_str(r#"{ "remove": ["24-48"] }"#)?;
+
+    let output_rpu = temp.child("RPU.bin");
+    let expected_rpu = temp.child("RPU_expected.bin");
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--fps")
+        .arg("24")
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(frames_config.as_ref())
+        .arg("--rpu-out")
+        .arg(expected_rpu.as_ref())
+        .assert()
+        .success();
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 259 - 25);
+
+    output_rpu.assert(predicate::path::eq_file(expected_rpu.path()));
+
+    Ok(())
+}
+
+#[test]
+fn remove_timecode_timestamps() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    // 24 frames at 24 fps, then 48 fps
+    let timestamps = temp.child("timestamps.txt");
+    let frame_times: Vec<String> = (0..24)
+        .map(|i| i as f64 * 1000.0 / 24.0)
+        .chain((0..235).map(|i| 1000.0 + i as f64 * 1000.0 / 48.0))
+        .map(|ms| format!("{:.3}", ms))
+        .collect();
+    timestamps.write_str(&format!(
+        "# timestamp format v2\n{}\n",
+        frame_times.join("\n")
+    ))?;
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(r#"{ "remove": ["00:00:01.000-00:00:02.000"] }"#)?;
+
+    let frames_config = temp.child("frames.json");
+    frames_config.write_str(r#"{ "remove": ["24-72"] }"#)?;
+
+    let output_rpu = temp.child("RPU.bin");
+    let expected_rpu = temp.child("RPU_expected.bin");
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--timestamps")
+        .arg(timestamps.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(frames_config.as_ref())
+        .arg("--rpu-out")
+        .arg(expected_rpu.as_ref())
+        .assert()
+        .success();
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 259 - 49);
+
+    output_rpu.assert(predicate::path::eq_file(expected_rpu.path()));
+
+    Ok(())
+}
+
+#[test]
+fn remove_chapters() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let chapters = temp.child("chapters.xml");
+    chapters.write_str(
+        r#"<?xml version="1.0"?>
+<Chapters>
+  <EditionEntry>
+    <ChapterAtom>
+      <ChapterTimeStart>00:00:00.000000000</ChapterTimeStart>
+      <ChapterDisplay><ChapterString>Intro</ChapterString></ChapterDisplay>
+    </ChapterAtom>
+    <ChapterAtom>
+      <ChapterTimeStart>00:00:01.000000000</ChapterTimeStart>
+      <ChapterDisplay><ChapterString>Part 1</ChapterString></ChapterDisplay>
+    </ChapterAtom>
+    <ChapterAtom>
+      <ChapterTimeStart>00:00:02.000000000</ChapterTimeStart>
+      <ChapterDisplay><ChapterString>Part 2</ChapterString></ChapterDisplay>
+    </ChapterAtom>
+    <ChapterAtom>
+      <ChapterTimeStart>00:00:05.000000000</ChapterTimeStart>
+      <ChapterTimeEnd>00:00:06.000000000</ChapterTimeEnd>
+      <ChapterDisplay><ChapterString>Credits</ChapterString></ChapterDisplay>
+    </ChapterAtom>
+  </EditionEntry>
+</Chapters>
+"#,
+    )?;
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(&format!(
+        r#"{{ "fps": "24", "chapters": {:?}, "remove": ["Chapter 2", "Credits"] }}"#,
+        chapters.path()
+    ))?;
+
+    let frames_config = temp.child("frames.json");
+    frames_config.write_str(r#"{ "remove": ["24-47", "120-143"] }"#)?;
+
+    let output_rpu = temp.child("RPU.bin");
+    let expected_rpu = temp.child("RPU_expected.bin");
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(frames_config.as_ref())
+        .arg("--rpu-out")
+        .arg(expected_rpu.as_ref())
+        .assert()
+        .success();
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 259 - 48);
+
+    output_rpu.assert(predicate::path::eq_file(expected_rpu.path()));
+
+    Ok(())
+}
+
+#[test]
+fn remove_chapter_no_chapters() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(r#"{ "remove": ["Chapter 2"] }"#)?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular_rpu.bin")
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--fps")
+        .arg("24")
+        .arg("--rpu-out")
+        .arg(temp.child("RPU.bin").as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "Chapter 2 requires a chapters file, use --chapters or set chapters in the config",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn remove_timecode_no_fps() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(r#"{ "remove": ["00:00:01.000-00:00:02.000"] }"#)?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "Timecode 00:00:01.000 requires a frame rate",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn replace_range() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    // Alternate source, with different L1 metadata
+    let scale_config = temp.child("scale.json");
+    scale_config.write_str(r#"{ "l1_scale": 0.5 }"#)?;
+
+    let source_rpu = temp.child("source.bin");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(scale_config.as_ref())
+        .arg("--rpu-out")
+        .arg(source_rpu.as_ref())
+        .assert()
+        .success();
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(&format!(
+        r#"{{ "replace": [
+            {{ "source_rpu": {:?}, "range": [10, 19] }},
+            {{ "source_rpu": {:?}, "range": [100, 104], "source_start": 0 }}
+        ] }}"#,
+        source_rpu.path(),
+        source_rpu.path()
+    ))?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let original = utilities_dovi::parse_rpu_file(input_rpu)?.unwrap();
+    let source = utilities_dovi::parse_rpu_file(source_rpu.as_ref())?.unwrap();
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 259);
+
+    for (i, rpu) in rpus.iter().enumerate() {
+        let expected = match i {
+            10..=19 => &source[i],
+            100..=104 => &source[i - 100],
+            _ => &original[i],
+        };
+
+        assert_eq!(rpu.write_rpu()?, expected.write_rpu()?);
+    }
+
+    assert_ne!(rpus[10].write_rpu()?, original[10].write_rpu()?);
+
+    Ok(())
+}
+
+#[test]
+fn replace_out_of_bounds() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(
+        r#"{ "replace": [
+            { "source_rpu": "assets/hevc_tests/regular_rpu.bin", "range": [0, 9], "source_start": 250 }
+        ] }"#,
+    )?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "invalid replace range: frame 259 is out of bounds",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn nlq_remove() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/tests/fel_orig.bin");
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(r#"{ "nlq": { "remove": true } }"#)?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 1);
+
+    let rpu = &rpus[0];
+    assert_eq!(rpu.dovi_profile, 8);
+    assert!(rpu.rpu_data_nlq.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn nlq_params() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/tests/fel_orig.bin");
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(
+        r#"{ "nlq": { "nlq_offset": [500, 500, 500], "linear_deadzone_slope": [0, 0, 0] } }"#,
+    )?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    let nlq = rpus[0].rpu_data_nlq.as_ref().unwrap();
+
+    assert_eq!(rpus[0].dovi_profile, 7);
+    assert_eq!(nlq.nlq_offset, vec![[500, 500, 500]]);
+    assert_eq!(nlq.linear_deadzone_slope, vec![[0, 0, 0]]);
+
+    Ok(())
+}
+
+#[test]
+fn nlq_remove_and_edit() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/tests/fel_orig.bin");
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(r#"{ "nlq": { "remove": true, "nlq_offset": [0, 0, 0] } }"#)?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "Cannot both remove and edit the NLQ parameters",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn active_area_aspect_ratios() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+    let edit_config = Path::new("assets/editor_examples/active_area_aspect_ratios.json");
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 259);
+
+    let offsets = |frame: usize| match rpus[frame].vdr_dm_data.as_ref().unwrap().get_block(5) {
+        Some(ExtMetadataBlock::Level5(b)) => b.get_offsets_vec(),
+        _ => panic!("No L5 block"),
+    };
+
+    // 3840x2020, 3840x2076, 3840x1608
+    assert_eq!(offsets(0), vec![0, 0, 70, 70]);
+    assert_eq!(offsets(40), vec![0, 0, 42, 42]);
+    assert_eq!(offsets(150), vec![0, 0, 276, 276]);
+    assert_eq!(offsets(258), vec![0, 0, 0, 0]);
+
+    Ok(())
+}
+
+#[test]
+fn active_area_transition() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+    let edit_config = Path::new("assets/editor_examples/active_area_transition.json");
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 259);
+
+    let offsets = |frame: usize| match rpus[frame].vdr_dm_data.as_ref().unwrap().get_block(5) {
+        Some(ExtMetadataBlock::Level5(b)) => b.get_offsets_vec(),
+        _ => panic!("No L5 block"),
+    };
+
+    // From full to 3840x1608 over frames 0-100
+    assert_eq!(offsets(0), vec![0, 0, 0, 0]);
+    assert_eq!(offsets(25), vec![0, 0, 69, 69]);
+    assert_eq!(offsets(50), vec![0, 0, 138, 138]);
+    assert_eq!(offsets(100), vec![0, 0, 276, 276]);
+    assert_eq!(offsets(200), vec![0, 0, 276, 276]);
+
+    Ok(())
+}
+
+#[test]
+fn active_area_transition_all() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(
+        r#"{ "active_area": { "presets": [{ "id": 0, "left": 0, "right": 0, "top": 0, "bottom": 0 }, { "id": 1, "left": 0, "right": 0, "top": 276, "bottom": 276 }], "edits": { "all": { "from": 0, "to": 1 } } } }"#,
+    )?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular_rpu.bin")
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(temp.child("RPU.bin").as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "Active area transitions require a frame range",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn active_area_aspect_ratio_without_canvas() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(r#"{ "active_area": { "edits": { "all": { "preset": "1.85" } } } }"#)?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular_rpu.bin")
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(temp.child("RPU.bin").as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "The 1.85 aspect ratio preset requires the canvas_width and canvas_height",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn remove_levels() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/tests/cmv40_full_rpu.bin");
+    let edit_config = Path::new("assets/editor_examples/remove_levels.json");
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("Removing L3, L4, L11 metadata..."));
+
+    // Parsed with CRC32 validation
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 1);
+
+    let vdr_dm_data = rpus[0].vdr_dm_data.as_ref().unwrap();
+
+    for level in [3, 4, 11] {
+        assert!(vdr_dm_data.get_block(level).is_none());
+    }
+
+    for level in [1, 2, 5, 6, 8, 9, 10, 254] {
+        assert!(vdr_dm_data.get_block(level).is_some());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn remove_levels_ranges() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+    let edit_config = Path::new("assets/editor_examples/remove_levels_ranges.json");
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("Removing L5, L6, L11 metadata..."));
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 259);
+
+    for (frame, rpu) in rpus.iter().enumerate() {
+        let vdr_dm_data = rpu.vdr_dm_data.as_ref().unwrap();
+
+        assert!(vdr_dm_data.get_block(11).is_none());
+        assert_eq!(vdr_dm_data.get_block(5).is_none(), frame < 10);
+        assert_eq!(vdr_dm_data.get_block(6).is_none(), frame < 10);
+        assert!(vdr_dm_data.get_block(1).is_some());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn remove_levels_conflict() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(r#"{ "level11": "Game", "remove_levels": [11] }"#)?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular_rpu.bin")
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(temp.child("RPU.bin").as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "Cannot both set and remove the L11 metadata",
+    ));
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    edit_config.write_str(r#"{ "remove_levels": [254] }"#)?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular_rpu.bin")
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(temp.child("RPU.bin").as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "Invalid extension metadata level to remove: 254",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn extend() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--extend")
+        .arg("10")
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("Extending metadata by 10 frames."));
+
+    let original = utilities_dovi::parse_rpu_file(input_rpu)?.unwrap();
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 269);
+
+    for (rpu, original_rpu) in rpus.iter().zip(original.iter()) {
+        assert_eq!(rpu.write_rpu()?, original_rpu.write_rpu()?);
+    }
+
+    // Copies of the last RPU, continuing its scene
+    let last = original.last().unwrap();
+
+    for rpu in &rpus[259..] {
+        let vdr_dm_data = rpu.vdr_dm_data.as_ref().unwrap();
+        assert_eq!(vdr_dm_data.scene_refresh_flag, 0);
+
+        assert_eq!(
+            serde_json::to_string(&rpu.vdr_dm_data)?,
+            serde_json::to_string(&last.vdr_dm_data)?
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn extend_overrides_config() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(r#"{ "shift": 5, "extend": 20 }"#)?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--extend")
+        .arg("3")
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 259 + 5 + 3);
+
+    Ok(())
+}
+
+#[test]
+fn l1_smoothing_average() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(r#"{ "l1_smoothing": { "method": "average" } }"#)?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Smoothed the L1 metadata of 3 scenes, average",
+        ));
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 259);
+
+    let level1 = |rpu: &DoviRpu| match rpu.vdr_dm_data.as_ref().unwrap().get_block(1) {
+        Some(ExtMetadataBlock::Level1(level1)) => (level1.min_pq, level1.avg_pq, level1.max_pq),
+        _ => panic!("No L1 metadata"),
+    };
+
+    // Constant L1 within every scene
+    for pair in rpus.windows(2) {
+        if pair[1].vdr_dm_data.as_ref().unwrap().scene_refresh_flag == 0 {
+            assert_eq!(level1(&pair[0]), level1(&pair[1]));
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn l1_smoothing_invalid_window() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(r#"{ "l1_smoothing": { "method": "median", "window": 4 } }"#)?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular_rpu.bin")
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(temp.child("RPU.bin").as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "Invalid L1 smoothing window 4, must be odd",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn level3_offsets() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(
+        r#"{
+            "level3": {
+                "all": { "avg_pq_offset": 2068 },
+                "0-9": { "min_pq_offset": 2040, "max_pq_offset": 2000 }
+            }
+        }"#,
+    )?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("Setting L3 offsets..."));
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 259);
+
+    let level3 = |rpu: &DoviRpu| match rpu.vdr_dm_data.as_ref().unwrap().get_block(3) {
+        Some(ExtMetadataBlock::Level3(l3)) => {
+            (l3.min_pq_offset, l3.avg_pq_offset, l3.max_pq_offset)
+        }
+        _ => panic!("No L3 metadata"),
+    };
+
+    // The range replaces the block for every frame, missing offsets are neutral
+    assert!(rpus[..10]
+        .iter()
+        .all(|rpu| level3(rpu) == (2040, 2048, 2000)));
+    assert!(rpus[10..]
+        .iter()
+        .all(|rpu| level3(rpu) == (2048, 2068, 2048)));
+
+    // The L1 metadata is left untouched
+    let original_rpus = utilities_dovi::parse_rpu_file(input_rpu)?.unwrap();
+    let level1 = |rpu: &DoviRpu| match rpu.vdr_dm_data.as_ref().unwrap().get_block(1) {
+        Some(ExtMetadataBlock::Level1(l1)) => (l1.min_pq, l1.avg_pq, l1.max_pq),
+        _ => panic!("No L1 metadata"),
+    };
+    assert!(rpus
+        .iter()
+        .zip(original_rpus.iter())
+        .all(|(rpu, original)| level1(rpu) == level1(original)));
+
+    Ok(())
+}
+
+#[test]
+fn level3_invalid_offset() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(r#"{ "level3": { "0-9": { "max_pq_offset": 4096 } } }"#)?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular_rpu.bin")
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(temp.child("RPU.bin").as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "Invalid L3 offsets for 0-9, the values must fit in 12 bits",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn level4_anchors() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/tests/fel_orig.bin");
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(
+        r#"{
+            "level4": {
+                "all": { "anchor_pq": 1000, "anchor_power": 500 }
+            }
+        }"#,
+    )?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("Setting L4 anchors..."));
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    let vdr_dm_data = rpus[0].vdr_dm_data.as_ref().unwrap();
+
+    // The existing block is replaced
+    assert_eq!(vdr_dm_data.level_blocks_iter(4).count(), 1);
+
+    if let Some(ExtMetadataBlock::Level4(level4)) = vdr_dm_data.get_block(4) {
+        assert_eq!(level4.anchor_pq, 1000);
+        assert_eq!(level4.anchor_power, 500);
+    } else {
+        panic!("No L4 metadata");
+    }
+
+    // CM version unchanged
+    assert!(vdr_dm_data.cmv40_metadata.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn level4_ranges() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(
+        r#"{
+            "level4": {
+                "0-9": { "anchor_pq": 1200, "anchor_power": 600 }
+            }
+        }"#,
+    )?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+
+    let level4 = |rpu: &DoviRpu| match rpu.vdr_dm_data.as_ref().unwrap().get_block(4) {
+        Some(ExtMetadataBlock::Level4(l4)) => Some((l4.anchor_pq, l4.anchor_power)),
+        _ => None,
+    };
+
+    assert!(rpus[..10]
+        .iter()
+        .all(|rpu| level4(rpu) == Some((1200, 600))));
+    assert!(rpus[10..].iter().all(|rpu| level4(rpu).is_none()));
+
+    Ok(())
+}
+
+#[test]
+fn level4_invalid_anchor() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let edit_config = temp.child("edit.json");
+    edit_config
+        .write_str(r#"{ "level4": { "all": { "anchor_pq": 4096, "anchor_power": 0 } } }"#)?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular_rpu.bin")
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(temp.child("RPU.bin").as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "Invalid L4 anchors for all, the values must fit in 12 bits",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn custom_target_display() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(
+        r#"{
+            "targets": [
+                {
+                    "target_display_index": 20,
+                    "max_nits": 600,
+                    "min_nits": 0.005,
+                    "primaries": "BT2020",
+                    "trims": {
+                        "all": { "trim_slope": 1900 },
+                        "0-9": { "trim_slope": 2200, "trim_power": 1800 }
+                    }
+                }
+            ]
+        }"#,
+    )?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Setting 1 custom target displays...",
+        ));
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+
+    let target = |rpu: &DoviRpu| {
+        let vdr_dm_data = rpu.vdr_dm_data.as_ref().unwrap();
+
+        let level10 = vdr_dm_data.level_blocks_iter(10).find_map(|b| match b {
+            ExtMetadataBlock::Level10(l10) if l10.target_display_index == 20 => Some((
+                l10.target_max_pq,
+                l10.target_min_pq,
+                l10.target_primary_index,
+            )),
+            _ => None,
+        });
+        let level8 = vdr_dm_data.level_blocks_iter(8).find_map(|b| match b {
+            ExtMetadataBlock::Level8(l8) if l8.target_display_index == 20 => {
+                Some((l8.trim_slope, l8.trim_power))
+            }
+            _ => None,
+        });
+
+        (level10, level8)
+    };
+
+    // 600 nits and 0.005 nits in 12 bit PQ, BT.2020 primaries
+    let level10 = Some((2851, 62, 2));
+
+    assert!(rpus[..10]
+        .iter()
+        .all(|rpu| target(rpu) == (level10, Some((2200, 1800)))));
+    assert!(rpus[10..]
+        .iter()
+        .all(|rpu| target(rpu) == (level10, Some((1900, 2048)))));
+
+    Ok(())
+}
+
+#[test]
+fn custom_target_display_preset_index() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(r#"{ "targets": [{ "target_display_index": 1, "max_nits": 600 }] }"#)?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular_rpu.bin")
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(temp.child("RPU.bin").as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "Invalid target display index 1, it is a preset target display",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn generated_trims() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    // The original trims are replaced by the generated ones
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(
+        r#"{
+            "remove_levels": [2],
+            "trim_targets": [100],
+            "trim_algorithm": { "Knee": { "saturation": 0.2 } }
+        }"#,
+    )?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Generating trims from the L1 metadata...",
+        ));
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 259);
+
+    let algorithm = TrimAlgorithm::Knee {
+        knee: TrimAlgorithm::DEFAULT_KNEE,
+        saturation: 0.2,
+    };
+
+    for rpu in &rpus {
+        let vdr_dm_data = rpu.vdr_dm_data.as_ref().unwrap();
+
+        let level1 = match vdr_dm_data.get_block(1) {
+            Some(ExtMetadataBlock::Level1(level1)) => level1,
+            _ => panic!("No L1 metadata"),
+        };
+
+        let level2: Vec<_> = vdr_dm_data.level_blocks_iter(2).collect();
+        assert_eq!(level2.len(), 1);
+
+        match level2[0] {
+            ExtMetadataBlock::Level2(level2) => {
+                assert_eq!(level2.target_max_pq, 2081);
+                assert_eq!(
+                    (level2.trim_slope, level2.trim_power),
+                    algorithm.trims(level1, 2081)
+                );
+                assert_eq!(
+                    level2.trim_saturation_gain,
+                    algorithm.saturation_gain(level1, 2081)
+                );
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn generated_trims_invalid_knee() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let edit_config = temp.child("edit.json");
+    edit_config
+        .write_str(r#"{ "trim_targets": [100], "trim_algorithm": { "Knee": { "knee": 0 } } }"#)?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular_rpu.bin")
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(temp.child("RPU.bin").as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "Invalid trim knee 0, expected a value in (0, 1]",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn dry_run() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = temp.child("RPU.bin");
+    input_rpu.write_file(Path::new("assets/hevc_tests/regular_rpu.bin"))?;
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(r#"{ "remove": ["0-39"], "l1_max_nits": 100 }"#)?;
+
+    let assert = cmd
+        .current_dir(temp.path())
+        .arg(SUBCOMMAND)
+        .arg("RPU.bin")
+        .arg("--json")
+        .arg("edit.json")
+        .arg("--dry-run")
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Dry run, no output written. Summary of the changes:\n  Frames: 259 -> 219\n  Removed frames: 40\n",
+        ))
+        .stdout(predicate::str::contains("\n  L1: "))
+        .stdout(predicate::str::contains("\n    L1.max_pq: "));
+
+    temp.child("RPU_modified.bin")
+        .assert(predicate::path::missing());
+
+    Ok(())
+}
+
+#[test]
+fn mode_gzip_rpu() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/tests/fel_orig.bin");
+    let edit_config = Path::new("assets/editor_examples/mode.json");
+
+    let compressed_rpu = temp.child("RPU.bin.gz");
+    let output_rpu = temp.child("RPU.bin");
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config)
+        .arg("--rpu-out")
+        .arg(compressed_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let compressed = std::fs::read(compressed_rpu.path())?;
+    assert!(compressed.starts_with(&[0x1F, 0x8B]));
+
+    // Compressed input, decompressed output
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(compressed_rpu.as_ref())
+        .arg("--json")
+        .arg(edit_config)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(compressed_rpu.as_ref())?.unwrap();
+    let decompressed_rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+
+    assert_eq!(rpus.len(), 1);
+    assert_eq!(rpus[0].dovi_profile, 8);
+    assert_eq!(
+        rpus[0].write_hevc_unspec62_nalu()?,
+        decompressed_rpus[0].write_hevc_unspec62_nalu()?
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn mode_zstd_rpu() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/tests/fel_orig.bin");
+    let edit_config = Path::new("assets/editor_examples/mode.json");
+
+    let compressed_rpu = temp.child("RPU.bin.zst");
+    let output_rpu = temp.child("RPU.bin");
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config)
+        .arg("--rpu-out")
+        .arg(compressed_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let compressed = std::fs::read(compressed_rpu.path())?;
+    assert!(compressed.starts_with(&[0x28, 0xB5, 0x2F, 0xFD]));
+
+    // Compressed input, decompressed output
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(compressed_rpu.as_ref())
+        .arg("--json")
+        .arg(edit_config)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(compressed_rpu.as_ref())?.unwrap();
+    let decompressed_rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+
+    assert_eq!(rpus.len(), 1);
+    assert_eq!(rpus[0].dovi_profile, 8);
+    assert_eq!(
+        rpus[0].write_hevc_unspec62_nalu()?,
+        decompressed_rpus[0].write_hevc_unspec62_nalu()?
+    );
+
+    // Compressed by the zstd command
+    let rpus = utilities_dovi::parse_rpu_file(Path::new("assets/tests/fel_orig.bin.zst"))?.unwrap();
+    let orig_rpus = utilities_dovi::parse_rpu_file(input_rpu)?.unwrap();
+
+    assert_eq!(
+        rpus[0].write_hevc_unspec62_nalu()?,
+        orig_rpus[0].write_hevc_unspec62_nalu()?
+    );
+
+    Ok(())
+}
+
+#[cfg(not(feature = "zstd"))]
+#[test]
+fn mode_zstd_rpu_without_feature() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/tests/fel_orig.bin");
+    let edit_config = Path::new("assets/editor_examples/mode.json");
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--json")
+        .arg(edit_config)
+        .arg("--rpu-out")
+        .arg(temp.child("RPU.bin.zst").as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "Zstandard compressed RPU files require the zstd feature",
+    ));
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/tests/fel_orig.bin.zst")
+        .arg("--json")
+        .arg(edit_config)
+        .arg("--rpu-out")
+        .arg(temp.child("RPU.bin").as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "Zstandard compressed RPU files require the zstd feature",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn preset_fix_l6_from_l1() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/tests/cmv4_0_2_xml_rpu.bin");
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--preset")
+        .arg("fix-l6-from-l1")
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(input_rpu)?.unwrap();
+    let edited_rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(edited_rpus.len(), rpus.len());
+
+    let nits = |pq: u16| pq_to_nits(pq as f64 / 4095.0).round() as u16;
+    let (max_cll, max_fall) = rpus
+        .iter()
+        .filter_map(|rpu| match rpu.vdr_dm_data.as_ref()?.get_block(1)? {
+            ExtMetadataBlock::Level1(level1) => Some((nits(level1.max_pq), nits(level1.avg_pq))),
+            _ => None,
+        })
+        .fold((0, 0), |(max_cll, max_fall), (max, avg)| {
+            (max_cll.max(max), max_fall.max(avg))
+        });
+
+    assert!(max_cll > 0);
+
+    for (rpu, edited_rpu) in rpus.iter().zip(edited_rpus.iter()) {
+        let vdr_dm_data = rpu.vdr_dm_data.as_ref().unwrap();
+        let edited_vdr_dm_data = edited_rpu.vdr_dm_data.as_ref().unwrap();
+
+        match (vdr_dm_data.get_block(6), edited_vdr_dm_data.get_block(6)) {
+            (Some(ExtMetadataBlock::Level6(level6)), Some(ExtMetadataBlock::Level6(edited))) => {
+                assert_eq!(
+                    edited.max_display_mastering_luminance,
+                    level6.max_display_mastering_luminance
+                );
+                assert_eq!(edited.max_content_light_level, max_cll);
+                assert_eq!(edited.max_frame_average_light_level, max_fall);
+            }
+            _ => panic!("No L6 metadata"),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn preset_strip_cmv4() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/tests/cmv40_full_rpu.bin");
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--preset")
+        .arg("strip-cmv4")
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(input_rpu)?.unwrap();
+    let edited_rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+
+    let vdr_dm_data = rpus[0].vdr_dm_data.as_ref().unwrap();
+    let edited_vdr_dm_data = edited_rpus[0].vdr_dm_data.as_ref().unwrap();
+
+    assert!(vdr_dm_data.cmv40_metadata.is_some());
+    assert!(edited_vdr_dm_data.cmv40_metadata.is_none());
+    assert_eq!(
+        edited_vdr_dm_data.metadata_blocks(1).unwrap().len(),
+        vdr_dm_data.metadata_blocks(1).unwrap().len()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn preset_plex_compat_json_override() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/tests/fel_orig.bin");
+    let output_rpu = temp.child("RPU.bin");
+
+    let edit_config = temp.child("edit.json");
+    edit_config.write_str(r#"{ "level6_from_l1": false }"#)?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--preset")
+        .arg("plex-compat")
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(input_rpu)?.unwrap();
+    let edited_rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+
+    // Converted to 8.1 by the preset, the L6 metadata is untouched
+    assert_eq!(edited_rpus[0].dovi_profile, 8);
+
+    let level6 = |rpu: &DoviRpu| match rpu.vdr_dm_data.as_ref().unwrap().get_block(6) {
+        Some(ExtMetadataBlock::Level6(level6)) => Some(level6.max_content_light_level),
+        _ => None,
+    };
+    assert_eq!(level6(&edited_rpus[0]), level6(&rpus[0]));
+
+    Ok(())
+}
+
+#[test]
+fn preset_strip_cmv4_conflicting_json() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/tests/cmv40_full_rpu.bin");
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--preset")
+        .arg("strip-cmv4")
+        .arg("--json")
+        .arg("assets/editor_examples/convert_to_cmv4.json")
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "Cannot both remove and set CM v4.0 metadata",
+    ));
+
+    output_rpu.assert(predicate::path::missing());
+
+    Ok(())
+}
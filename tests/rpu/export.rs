@@ -1,7 +1,11 @@
 use anyhow::Result;
 use assert_cmd::Command;
+use assert_fs::prelude::*;
 use predicates::prelude::*;
 
+use dolby_vision::rpu::dovi_rpu::DoviRpu;
+use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlock;
+
 #[test]
 fn help() -> Result<()> {
     let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
@@ -15,3 +19,331 @@ fn help() -> Result<()> {
         ));
     Ok(())
 }
+
+#[test]
+fn xml_format() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = "assets/tests/cmv4_0_2_xml_rpu.bin";
+    let output_file = temp.child("RPU_export.xml");
+
+    let assert = cmd
+        .arg("export")
+        .arg(input_rpu)
+        .arg("--format")
+        .arg("xml")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_file.assert(predicate::path::is_file());
+    output_file.assert(predicate::str::contains("<Version>4.0.2</Version>"));
+
+    Ok(())
+}
+
+#[test]
+fn csv_format() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = "assets/tests/cmv4_0_2_xml_rpu.bin";
+    let output_file = temp.child("RPU_export.csv");
+
+    let assert = cmd
+        .arg("export")
+        .arg(input_rpu)
+        .arg("--format")
+        .arg("csv")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let csv = std::fs::read_to_string(output_file.path())?;
+    let lines: Vec<&str> = csv.lines().collect();
+
+    // Header and one row per frame
+    assert_eq!(lines.len(), 260);
+    assert!(lines[0].starts_with(
+        "frame,scene_cut,l1_min_nits,l1_avg_nits,l1_max_nits,l3_min_offset,l3_avg_offset,l3_max_offset,"
+    ));
+    assert!(lines[0].contains(",l2_2081_slope,l2_2081_offset,l2_2081_power,"));
+    assert!(lines[0].ends_with(",l5_left,l5_right,l5_top,l5_bottom"));
+
+    // No L2 in the first shot
+    assert_eq!(
+        lines[1],
+        "0,1,0.0000,7.1126,569.2454,2048,2048,2048,,,,,,,,,,,,,,,,,,,0,0,0,0"
+    );
+    assert_eq!(
+        lines[121],
+        "120,1,0.0000,10.0538,100.1020,2048,1434,1007,2013,2016,1339,2048,2048,2048,2059,2048,1955,2048,2048,2048,2049,2048,2047,2048,2048,2048,0,0,0,0"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn dm_json_format() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = "assets/tests/cmv4_0_2_xml_rpu.bin";
+    let output_file = temp.child("RPU_dm.json");
+
+    let assert = cmd
+        .arg("export")
+        .arg(input_rpu)
+        .arg("--format")
+        .arg("dm-json")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(output_file.path())?)?;
+    let frames = json.as_object().unwrap();
+
+    assert_eq!(frames.len(), 259);
+
+    // No L2 in the first shot
+    assert_eq!(
+        frames["0"],
+        serde_json::json!({ "scene_cut": true, "l1": [0, 1120, 2828] })
+    );
+    assert_eq!(frames["120"]["scene_cut"], true);
+    assert_eq!(
+        frames["120"]["l2"]["2081"],
+        serde_json::json!([2013, 2016, 1339, 2048, 2048, 2048])
+    );
+    assert_eq!(frames["121"]["scene_cut"], false);
+
+    Ok(())
+}
+
+#[test]
+fn hdr10plus_format() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = "assets/tests/cmv4_0_2_xml_rpu.bin";
+    let output_file = temp.child("hdr10plus.json");
+
+    let assert = cmd
+        .arg("export")
+        .arg(input_rpu)
+        .arg("--format")
+        .arg("hdr10plus")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(output_file.path())?)?;
+
+    assert_eq!(json["JSONInfo"]["HDR10plusProfile"], "B");
+    assert_eq!(json["SceneInfo"].as_array().unwrap().len(), 259);
+    assert_eq!(
+        json["SceneInfoSummary"]["SceneFirstFrameIndex"],
+        serde_json::json!([0, 120, 219])
+    );
+
+    // No L2 in the first shot, neutral curve
+    let frame = &json["SceneInfo"][0];
+    assert_eq!(
+        frame["BezierCurveData"]["Anchors"],
+        serde_json::json!([102, 205, 307, 409, 512, 614, 716, 818, 921])
+    );
+    assert_eq!(
+        frame["LuminanceParameters"]["MaxScl"],
+        serde_json::json!([5692, 5692, 5692])
+    );
+
+    // Curve from the 100 nits trim
+    let frame = &json["SceneInfo"][120];
+    assert_eq!(frame["TargetedSystemDisplayMaximumLuminance"], 100);
+    assert_eq!(
+        frame["BezierCurveData"]["Anchors"],
+        serde_json::json!([151, 268, 375, 476, 573, 666, 756, 845, 931])
+    );
+    assert_eq!(frame["LuminanceParameters"]["AverageRGB"], 101);
+    assert_eq!(
+        frame["LuminanceParameters"]["MaxScl"],
+        serde_json::json!([1001, 1001, 1001])
+    );
+
+    // Back to RPUs, the L1 metadata of the static scenes is close to the original
+    let generated_rpu = temp.child("RPU.bin");
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg("generate")
+        .arg("--json")
+        .arg("assets/generator_examples/no_duration.json")
+        .arg("--hdr10plus-json")
+        .arg(output_file.as_ref())
+        .arg("--rpu-out")
+        .arg(generated_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let original = utilities_dovi::parse_rpu_file(std::path::Path::new(input_rpu))?.unwrap();
+    let generated = utilities_dovi::parse_rpu_file(generated_rpu.as_ref())?.unwrap();
+    assert_eq!(generated.len(), 259);
+
+    let l1 = |rpu: &DoviRpu| match rpu.vdr_dm_data.as_ref().and_then(|dm| dm.get_block(1)) {
+        Some(ExtMetadataBlock::Level1(l1)) => (l1.avg_pq, l1.max_pq),
+        _ => panic!("no L1"),
+    };
+
+    for frame in [0, 120] {
+        let (avg_pq, max_pq) = l1(&original[frame]);
+        let (generated_avg_pq, generated_max_pq) = l1(&generated[frame]);
+
+        assert!((max_pq as i32 - generated_max_pq as i32).abs() <= 2);
+        assert!((avg_pq as i32 - generated_avg_pq as i32).abs() <= 8);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn hdr10plus_format_profile_a() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = "assets/tests/cmv4_0_2_xml_rpu.bin";
+    let output_file = temp.child("hdr10plus.json");
+
+    let assert = cmd
+        .arg("export")
+        .arg(input_rpu)
+        .arg("--format")
+        .arg("hdr10plus")
+        .arg("--hdr10plus-profile")
+        .arg("a")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(output_file.path())?)?;
+
+    assert_eq!(json["JSONInfo"]["HDR10plusProfile"], "A");
+
+    let frame = &json["SceneInfo"][120];
+    assert_eq!(frame["TargetedSystemDisplayMaximumLuminance"], 0);
+    assert!(frame.get("BezierCurveData").is_none());
+
+    Ok(())
+}
+
+#[test]
+fn srt_format() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = "assets/tests/cmv4_0_2_xml_rpu.bin";
+    let output_file = temp.child("RPU_export.srt");
+
+    let assert = cmd
+        .arg("export")
+        .arg(input_rpu)
+        .arg("--format")
+        .arg("srt")
+        .arg("--fps")
+        .arg("24000/1001")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let srt = std::fs::read_to_string(output_file.path())?;
+    let subtitles: Vec<&str> = srt.split("\n\n").filter(|s| !s.is_empty()).collect();
+
+    // Frames with the same metadata are merged, within a scene
+    assert_eq!(subtitles.len(), 5);
+    assert_eq!(
+        subtitles[0],
+        "1\n00:00:00,000 --> 00:00:05,005\nScene 0, frames 0-119\nL1: min 0.0000 / avg 7.11 / max 569.25 nits\nL2: none\nL5: left 0 / right 0 / top 0 / bottom 0"
+    );
+    assert_eq!(
+        subtitles[1],
+        "2\n00:00:05,005 --> 00:00:09,134\nScene 1, frames 120-218\nL1: min 0.0000 / avg 10.05 / max 100.10 nits\nL2 100 nits: S 2013 O 2016 P 1339 CW 2048 SG 2048 MS 2048\nL2 600 nits: S 2059 O 2048 P 1955 CW 2048 SG 2048 MS 2048\nL2 1001 nits: S 2049 O 2048 P 2047 CW 2048 SG 2048 MS 2048\nL5: left 0 / right 0 / top 0 / bottom 0"
+    );
+    assert!(subtitles[3].starts_with("4\n00:00:09,551 --> 00:00:09,593\nScene 2, frame 229\n"));
+
+    Ok(())
+}
+
+#[test]
+fn ass_format() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = "assets/tests/cmv4_0_2_xml_rpu.bin";
+    let output_file = temp.child("RPU_export.ass");
+
+    let assert = cmd
+        .arg("export")
+        .arg(input_rpu)
+        .arg("--format")
+        .arg("ass")
+        .arg("--fps")
+        .arg("24")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let ass = std::fs::read_to_string(output_file.path())?;
+    assert!(ass.starts_with("[Script Info]\n"));
+
+    let dialogues: Vec<&str> = ass
+        .lines()
+        .filter(|line| line.starts_with("Dialogue: "))
+        .collect();
+
+    assert_eq!(dialogues.len(), 5);
+    assert_eq!(
+        dialogues[0],
+        "Dialogue: 0,0:00:00.00,0:00:05.00,Metadata,,0,0,0,,Scene 0, frames 0-119\\NL1: min 0.0000 / avg 7.11 / max 569.25 nits\\NL2: none\\NL5: left 0 / right 0 / top 0 / bottom 0"
+    );
+    assert!(dialogues[4].starts_with(
+        "Dialogue: 0,0:00:09.58,0:00:10.79,Metadata,,0,0,0,,Scene 2, frames 230-258\\N"
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn subtitles_require_fps() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+
+    let assert = cmd
+        .arg("export")
+        .arg("assets/tests/cmv4_0_2_xml_rpu.bin")
+        .arg("--format")
+        .arg("srt")
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "export: --fps is required for the srt and ass formats",
+    ));
+
+    Ok(())
+}
@@ -5,6 +5,7 @@ use assert_cmd::Command;
 use assert_fs::prelude::*;
 use predicates::prelude::*;
 
+use dolby_vision::rpu::dovi_rpu::DoviRpu;
 use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlock;
 
 const SUBCOMMAND: &str = "generate";
@@ -68,6 +69,35 @@ fn generate_default_cmv29() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn generate_rpu_stream() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+
+    let generate_config = Path::new("assets/generator_examples/default_cmv29.json");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("--json")
+        .arg(generate_config)
+        .arg("--rpu-out")
+        .arg("-")
+        .assert();
+
+    // The status messages don't mix with the RPUs
+    let assert = assert
+        .success()
+        .stderr(predicate::str::contains("Generating metadata..."));
+
+    let stream = &assert.get_output().stdout;
+    assert_eq!(&stream[..4], b"DVRP");
+
+    let size = u32::from_be_bytes(stream[4..8].try_into()?) as usize;
+    let rpu = DoviRpu::parse_unspec62_nalu(&stream[8..8 + size])?;
+    assert_eq!(rpu.vdr_dm_data.unwrap().scene_refresh_flag, 1);
+
+    Ok(())
+}
+
 #[test]
 fn generate_default_cmv40() -> Result<()> {
     let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
@@ -358,6 +388,200 @@ fn generate_full_hdr10plus() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn generate_per_shot_metadata() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let generate_config = Path::new("assets/generator_examples/per_shot_metadata.json");
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("--json")
+        .arg(generate_config)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 72);
+
+    // Shots are sorted by start frame
+    let shot1_vdr_dm_data = rpus[0].vdr_dm_data.as_ref().unwrap();
+    assert_eq!(shot1_vdr_dm_data.scene_refresh_flag, 1);
+
+    if let ExtMetadataBlock::Level1(level1) = shot1_vdr_dm_data.get_block(1).unwrap() {
+        assert_eq!(level1.max_pq, 2081);
+        assert_eq!(level1.avg_pq, 1229);
+    }
+
+    if let ExtMetadataBlock::Level2(level2) = shot1_vdr_dm_data.get_block(2).unwrap() {
+        assert_eq!(level2.target_max_pq, 2851);
+        assert_eq!(level2.trim_power, 1800);
+    }
+
+    if let ExtMetadataBlock::Level5(level5) = shot1_vdr_dm_data.get_block(5).unwrap() {
+        assert_eq!(level5.get_offsets(), (0, 0, 0, 0));
+    }
+
+    if let ExtMetadataBlock::Level6(level6) = shot1_vdr_dm_data.get_block(6).unwrap() {
+        assert_eq!(level6.max_display_mastering_luminance, 1000);
+    }
+
+    let shot2_vdr_dm_data = rpus[24].vdr_dm_data.as_ref().unwrap();
+    assert_eq!(shot2_vdr_dm_data.scene_refresh_flag, 1);
+    assert_eq!(rpus[25].vdr_dm_data.as_ref().unwrap().scene_refresh_flag, 0);
+
+    if let ExtMetadataBlock::Level1(level1) = shot2_vdr_dm_data.get_block(1).unwrap() {
+        assert_eq!(level1.max_pq, 3079);
+        assert_eq!(level1.avg_pq, 1500);
+    }
+
+    assert_eq!(shot2_vdr_dm_data.level_blocks_iter(2).count(), 0);
+
+    if let ExtMetadataBlock::Level5(level5) = shot2_vdr_dm_data.get_block(5).unwrap() {
+        assert_eq!(level5.get_offsets(), (0, 0, 276, 276));
+    }
+
+    if let ExtMetadataBlock::Level6(level6) = shot2_vdr_dm_data.get_block(6).unwrap() {
+        assert_eq!(level6.max_display_mastering_luminance, 4000);
+        assert_eq!(level6.min_display_mastering_luminance, 50);
+        assert_eq!(level6.max_content_light_level, 3000);
+        assert_eq!(level6.max_frame_average_light_level, 500);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn generate_shots_not_contiguous() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let config: String =
+        std::fs::read_to_string("assets/generator_examples/per_shot_metadata.json")?
+            .replace("\"start\": 24", "\"start\": 30");
+    let generate_config = temp.child("config.json");
+    generate_config.write_str(&config)?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("--json")
+        .arg(generate_config.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "Shots are not contiguous: shot starts at frame 30, expected frame 24",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn generate_shots_timecodes() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let frames_config = Path::new("assets/generator_examples/per_shot_metadata.json");
+
+    // Frame rate from the config
+    let config: String = std::fs::read_to_string(frames_config)?
+        .replacen('{', r#"{ "fps": "24000/1000","#, 1)
+        .replace("\"start\": 24", "\"start\": \"00:00:01.000\"")
+        .replace("\"duration\": 48", "\"duration\": \"00:00:02.000\"");
+    let generate_config = temp.child("config.json");
+    generate_config.write_str(&config)?;
+
+    let output_rpu = temp.child("RPU.bin");
+    let expected_rpu = temp.child("RPU_expected.bin");
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg("--json")
+        .arg(generate_config.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg("--json")
+        .arg(frames_config)
+        .arg("--rpu-out")
+        .arg(expected_rpu.as_ref())
+        .assert()
+        .success();
+
+    output_rpu.assert(predicate::path::eq_file(expected_rpu.path()));
+
+    Ok(())
+}
+
+#[test]
+fn generate_shots_timestamps() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let frames_config = Path::new("assets/generator_examples/per_shot_metadata.json");
+
+    // 24 frames at 24 fps, then 48 frames at 48 fps: the second shot lasts 1 second but has 48 frames
+    let timestamps = temp.child("timestamps.txt");
+    let frame_times: Vec<String> = (0..24)
+        .map(|i| i as f64 * 1000.0 / 24.0)
+        .chain((0..48).map(|i| 1000.0 + i as f64 * 1000.0 / 48.0))
+        .map(|ms| format!("{:.3}", ms))
+        .collect();
+    timestamps.write_str(&format!(
+        "# timestamp format v2\n{}\n",
+        frame_times.join("\n")
+    ))?;
+
+    let config: String = std::fs::read_to_string(frames_config)?
+        .replacen(
+            '{',
+            &format!(r#"{{ "timestamps": {:?},"#, timestamps.path()),
+            1,
+        )
+        .replace("\"start\": 24", "\"start\": \"00:00:01.000\"")
+        .replace("\"duration\": 48", "\"duration\": \"00:00:01.000\"")
+        .replace("\"duration\": 24", "\"duration\": \"00:00:01.000\"");
+    let generate_config = temp.child("config.json");
+    generate_config.write_str(&config)?;
+
+    let output_rpu = temp.child("RPU.bin");
+    let expected_rpu = temp.child("RPU_expected.bin");
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg("--json")
+        .arg(generate_config.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg("--json")
+        .arg(frames_config)
+        .arg("--rpu-out")
+        .arg(expected_rpu.as_ref())
+        .assert()
+        .success();
+
+    output_rpu.assert(predicate::path::eq_file(expected_rpu.path()));
+
+    Ok(())
+}
+
 #[test]
 fn xml_cmv2_9_with_l5() -> Result<()> {
     let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
@@ -504,3 +728,376 @@ fn xml_cmv4_2_510() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn scene_file_x265_csv() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let generate_config = Path::new("assets/generator_examples/default_cmv40.json");
+
+    let scene_file = temp.child("x265_log.csv");
+    scene_file.write_str(
+        "Encode Order, Type, POC, QP, Bits, Scenecut\n\
+         0, I-SLICE, 0, 20.00, 1000, 1\n\
+         1, P-SLICE, 4, 22.00, 500, 0\n\
+         2, B-SLICE, 1, 24.00, 100, 0\n\
+         3, B-SLICE, 2, 24.00, 100, 0\n\
+         4, B-SLICE, 3, 24.00, 100, 0\n\
+         5, I-SLICE, 6, 20.00, 1000, 1\n\
+         6, B-SLICE, 5, 24.00, 100, 0\n\
+         7, P-SLICE, 9, 22.00, 500, 0\n\
+         8, B-SLICE, 7, 24.00, 100, 0\n\
+         9, B-SLICE, 8, 24.00, 100, 0\n",
+    )?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("--json")
+        .arg(generate_config)
+        .arg("--scene-file")
+        .arg(scene_file.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_rpu.assert(predicate::path::is_file());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 10);
+
+    let scene_cuts: Vec<usize> = rpus
+        .iter()
+        .enumerate()
+        .filter(|(_, rpu)| rpu.vdr_dm_data.as_ref().unwrap().scene_refresh_flag == 1)
+        .map(|(i, _)| i)
+        .collect();
+    assert_eq!(scene_cuts, vec![0, 6]);
+
+    Ok(())
+}
+
+#[test]
+fn scene_file_x265_csv_luma_levels() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let generate_config = Path::new("assets/generator_examples/default_cmv40.json");
+
+    let scene_file = temp.child("x265_log.csv");
+    scene_file.write_str(
+        "Encode Order, Type, POC, QP, Bits, Scenecut, Min Luma Level, Max Luma Level, Avg Luma Level\n\
+         0, I-SLICE, 0, 20.00, 1000, 1, 64.00, 721.00, 502.00\n\
+         1, P-SLICE, 4, 22.00, 500, 0, 64.00, 940.00, 502.00\n\
+         2, B-SLICE, 1, 24.00, 100, 0, 64.00, 721.00, 502.00\n\
+         3, B-SLICE, 2, 24.00, 100, 0, 64.00, 721.00, 502.00\n\
+         4, B-SLICE, 3, 24.00, 100, 0, 64.00, 721.00, 502.00\n\
+         5, I-SLICE, 6, 20.00, 1000, 1, 108.00, 721.00, 283.00\n\
+         6, B-SLICE, 5, 24.00, 100, 0, 64.00, 721.00, 502.00\n\
+         7, P-SLICE, 9, 22.00, 500, 0, 108.00, 721.00, 283.00\n\
+         8, B-SLICE, 7, 24.00, 100, 0, 108.00, 721.00, 283.00\n\
+         9, B-SLICE, 8, 24.00, 100, 0, 108.00, 721.00, 283.00\n",
+    )?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("--json")
+        .arg(generate_config)
+        .arg("--scene-file")
+        .arg(scene_file.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Set the L1 metadata of 2 scenes from the x265 luma levels",
+        ));
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 10);
+
+    let level1 = |frame: usize| match rpus[frame].vdr_dm_data.as_ref().unwrap().get_block(1) {
+        Some(ExtMetadataBlock::Level1(b)) => (b.min_pq, b.avg_pq, b.max_pq),
+        _ => panic!("No L1 block"),
+    };
+
+    // Limited range code values, scene min/avg/max
+    assert_eq!(level1(0), (0, 2048, 4095));
+    assert_eq!(level1(5), (0, 2048, 4095));
+    assert_eq!(level1(6), (12, 1024, 3071));
+    assert_eq!(level1(9), (12, 1024, 3071));
+
+    Ok(())
+}
+
+#[test]
+fn scene_file_av1an_json() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let generate_config = Path::new("assets/generator_examples/default_cmv40.json");
+
+    let scene_file = temp.child("scenes.json");
+    scene_file.write_str(
+        r#"{"scenes":[{"start_frame":0,"end_frame":3,"zone_overrides":null},{"start_frame":3,"end_frame":8,"zone_overrides":null},{"start_frame":8,"end_frame":10,"zone_overrides":null}],"frames":10}"#,
+    )?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("--json")
+        .arg(generate_config)
+        .arg("--scene-file")
+        .arg(scene_file.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 10);
+
+    let scene_cuts: Vec<usize> = rpus
+        .iter()
+        .enumerate()
+        .filter(|(_, rpu)| rpu.vdr_dm_data.as_ref().unwrap().scene_refresh_flag == 1)
+        .map(|(i, _)| i)
+        .collect();
+    assert_eq!(scene_cuts, vec![0, 3, 8]);
+
+    Ok(())
+}
+
+#[test]
+fn generate_hdr10plus_bezier_curve_trims() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let generate_config = Path::new("assets/generator_examples/no_duration.json");
+    let hdr10plus_json = Path::new("./assets/tests/hdr10plus_metadata_bezier.json");
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("--json")
+        .arg(generate_config)
+        .arg("--hdr10plus-json")
+        .arg(hdr10plus_json)
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 9);
+
+    let level2_100_nits = |frame: usize| {
+        rpus[frame]
+            .vdr_dm_data
+            .as_ref()
+            .unwrap()
+            .level_blocks_iter(2)
+            .find_map(|block| match block {
+                ExtMetadataBlock::Level2(level2) if level2.target_max_pq == 2081 => {
+                    Some(level2.clone())
+                }
+                _ => None,
+            })
+    };
+
+    // Linear curve, close to neutral trims
+    let shot1_level2 = level2_100_nits(0).unwrap();
+    assert_eq!(shot1_level2.trim_slope, 2052);
+    assert_eq!(shot1_level2.trim_offset, 2048);
+    assert_eq!(shot1_level2.trim_power, 2048);
+
+    // Compressive curve, brighter midtones
+    let shot2_level2 = level2_100_nits(3).unwrap();
+    assert_eq!(shot2_level2.trim_slope, 2486);
+    assert_eq!(shot2_level2.trim_offset, 2048);
+    assert_eq!(shot2_level2.trim_power, 1214);
+
+    // No curve, no trim
+    assert!(level2_100_nits(6).is_none());
+
+    Ok(())
+}
+
+#[test]
+fn hlg_profile84() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let scene_file = temp.child("scenes.txt");
+    scene_file.write_str("0\n4\n")?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("--hlg")
+        .arg("--length")
+        .arg("10")
+        .arg("--scene-file")
+        .arg(scene_file.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 10);
+
+    for rpu in &rpus {
+        assert_eq!(rpu.dovi_profile, 8);
+        assert_eq!(rpu.header.vdr_rpu_profile, 1);
+        assert!(!rpu.header.bl_video_full_range_flag);
+
+        let vdr_dm_data = rpu.vdr_dm_data.as_ref().unwrap();
+
+        if let Some(ExtMetadataBlock::Level1(level1)) = vdr_dm_data.get_block(1) {
+            assert_eq!(
+                (level1.min_pq, level1.max_pq, level1.avg_pq),
+                (0, 3079, 2378)
+            );
+        } else {
+            panic!("No L1 metadata");
+        }
+    }
+
+    assert_eq!(rpus[4].vdr_dm_data.as_ref().unwrap().scene_refresh_flag, 1);
+
+    Ok(())
+}
+
+#[test]
+fn static_content() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("--static-content")
+        .arg("--length")
+        .arg("10")
+        .arg("--l5")
+        .arg("0,0,138,138")
+        .arg("--l6")
+        .arg("1000,0.0001,600,200")
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 10);
+
+    for (i, rpu) in rpus.iter().enumerate() {
+        assert_eq!(rpu.dovi_profile, 8);
+
+        let vdr_dm_data = rpu.vdr_dm_data.as_ref().unwrap();
+        assert_eq!(vdr_dm_data.scene_refresh_flag, (i == 0) as u64);
+
+        // L1 from the MaxCLL and MaxFALL
+        if let Some(ExtMetadataBlock::Level1(level1)) = vdr_dm_data.get_block(1) {
+            assert_eq!(
+                (level1.min_pq, level1.max_pq, level1.avg_pq),
+                (0, 2851, 2372)
+            );
+        } else {
+            panic!("No L1 metadata");
+        }
+
+        if let Some(ExtMetadataBlock::Level5(level5)) = vdr_dm_data.get_block(5) {
+            assert_eq!(level5.get_offsets(), (0, 0, 138, 138));
+        } else {
+            panic!("No L5 metadata");
+        }
+
+        if let Some(ExtMetadataBlock::Level6(level6)) = vdr_dm_data.get_block(6) {
+            assert_eq!(level6.max_display_mastering_luminance, 1000);
+            assert_eq!(level6.min_display_mastering_luminance, 1);
+            assert_eq!(level6.max_content_light_level, 600);
+            assert_eq!(level6.max_frame_average_light_level, 200);
+        } else {
+            panic!("No L6 metadata");
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn static_content_l1() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("--static-content")
+        .arg("--length")
+        .arg("3")
+        .arg("--l1")
+        .arg("0,100,1000")
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    let vdr_dm_data = rpus[2].vdr_dm_data.as_ref().unwrap();
+
+    if let Some(ExtMetadataBlock::Level1(level1)) = vdr_dm_data.get_block(1) {
+        assert_eq!(
+            (level1.min_pq, level1.max_pq, level1.avg_pq),
+            (0, 3079, 2081)
+        );
+    } else {
+        panic!("No L1 metadata");
+    }
+
+    // Default mastering display
+    if let Some(ExtMetadataBlock::Level6(level6)) = vdr_dm_data.get_block(6) {
+        assert_eq!(level6.max_display_mastering_luminance, 1000);
+    } else {
+        panic!("No L6 metadata");
+    }
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("--static-content")
+        .arg("--length")
+        .arg("3")
+        .arg("--l1")
+        .arg("0,500,100")
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "generate: invalid --l1 0,500,100, expected min <= avg <= max",
+    ));
+
+    Ok(())
+}
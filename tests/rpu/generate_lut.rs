@@ -0,0 +1,272 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use assert_cmd::Command;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+const SUBCOMMAND: &str = "generate-lut";
+
+/// Values of the LUT points, skipping the header
+fn read_cube(path: &Path) -> Result<Vec<[f64; 3]>> {
+    let cube = fs::read_to_string(path)?;
+
+    cube.lines()
+        .filter(|line| line.starts_with(|c: char| c.is_ascii_digit()))
+        .map(|line| {
+            let values = line
+                .split(' ')
+                .map(str::parse)
+                .collect::<Result<Vec<f64>, _>>()?;
+
+            Ok([values[0], values[1], values[2]])
+        })
+        .collect()
+}
+
+#[test]
+fn help() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd.arg(SUBCOMMAND).arg("--help").assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "dovi_tool generate-lut [OPTIONS] [input_pos]",
+        ));
+    Ok(())
+}
+
+#[test]
+fn per_shot() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+    let output_dir = temp.child("LUTs");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--size")
+        .arg("9")
+        .arg("--output")
+        .arg(output_dir.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("Generating LUTs for 3 shots..."));
+
+    let first_shot = output_dir.child("shot_00001.cube");
+    first_shot.assert(predicate::str::starts_with("TITLE \"Shot 1, frames 0-"));
+    first_shot.assert(predicate::str::contains("LUT_3D_SIZE 9\n"));
+    output_dir
+        .child("shot_00003.cube")
+        .assert(predicate::path::is_file());
+    output_dir
+        .child("shot_00004.cube")
+        .assert(predicate::path::missing());
+
+    let lut = read_cube(first_shot.path())?;
+    assert_eq!(lut.len(), 9 * 9 * 9);
+
+    // Mapped to the 100 nits target, within the PQ range
+    let max_pq = 0.5081;
+    assert!(lut.iter().flatten().all(|v| (0.0..=max_pq).contains(v)));
+
+    Ok(())
+}
+
+#[test]
+fn identity_mapping() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/tests/profile8.bin");
+    let output_file = temp.child("LUT.cube");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--average")
+        .arg("--size")
+        .arg("5")
+        .arg("--target-nits")
+        .arg("1000")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let lut = read_cube(output_file.path())?;
+    assert_eq!(lut.len(), 5 * 5 * 5);
+
+    // Profile 8.1 mapping: the greys below the tone mapping knee are unchanged
+    for (i, input) in [0.0, 0.25, 0.5].iter().enumerate() {
+        let output = lut[i * (25 + 5 + 1)];
+        assert!(output.iter().all(|v| (v - input).abs() < 0.001));
+    }
+
+    // Highlights are compressed into the target range
+    let white = lut[lut.len() - 1];
+    assert!(white.iter().all(|v| (v - 0.7518).abs() < 0.001));
+
+    Ok(())
+}
+
+#[test]
+fn sdr_output() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/tests/fel_orig.bin");
+    let output_file = temp.child("LUT.cube");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--average")
+        .arg("--transfer")
+        .arg("sdr")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_file.assert(predicate::str::contains("# Output: BT.709 gamma 2.4"));
+
+    let lut = read_cube(output_file.path())?;
+    assert_eq!(lut.len(), 33 * 33 * 33);
+    assert!(lut.iter().flatten().all(|v| (0.0..=1.0).contains(v)));
+
+    // Near black and white with the FEL mapping, the target peak is the reference white
+    assert!(lut[0].iter().all(|v| *v < 0.01));
+    assert!(lut[lut.len() - 1].iter().all(|v| *v > 0.95));
+    assert!(lut[lut.len() - 1].contains(&1.0));
+
+    Ok(())
+}
+
+#[test]
+fn invalid_size() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/tests/profile8.bin")
+        .arg("--size")
+        .arg("1")
+        .arg("--output")
+        .arg(temp.child("LUTs").as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "the LUT size must be between 2 and 256, got 1",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn reshape_only_identity() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/tests/profile8.bin");
+    let output_file = temp.child("LUT.cube");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--average")
+        .arg("--reshape-only")
+        .arg("--size")
+        .arg("5")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_file.assert(predicate::str::contains(
+        "# Output: BT.2020 PQ, without display management",
+    ));
+
+    // Profile 8.1 identity mapping, the BL is already BT.2020 PQ
+    let lut = read_cube(output_file.path())?;
+    assert_eq!(lut.len(), 5 * 5 * 5);
+
+    for (i, output) in lut.iter().enumerate() {
+        let input = [i % 5, (i / 5) % 5, i / 25].map(|v| v as f64 / 4.0);
+        assert!(output
+            .iter()
+            .zip(&input)
+            .all(|(o, i)| (o - i).abs() < 0.001));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn reshape_only_profile5() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/tests/empty_dmv1_blocks.bin");
+    let output_file = temp.child("LUT.cube");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--average")
+        .arg("--reshape-only")
+        .arg("--size")
+        .arg("9")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Profile 5: apply the LUTs to the IPTPQc2 base layer",
+        ));
+
+    let lut = read_cube(output_file.path())?;
+    assert!(lut.iter().flatten().all(|v| (0.0..=1.0).contains(v)));
+
+    // The IPT black and neutral axis stay neutral
+    assert!(lut[0].iter().all(|v| *v < 0.001));
+
+    let grey = lut[4 * (81 + 9 + 1)];
+    assert!(grey.iter().all(|v| (v - grey[0]).abs() < 0.001));
+
+    Ok(())
+}
+
+#[test]
+fn reshape_only_conflicts() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/tests/profile8.bin")
+        .arg("--reshape-only")
+        .arg("--target-nits")
+        .arg("600")
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "cannot be used with '--target-nits <TARGET_NITS>'",
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use std::fs;
+
+use anyhow::Result;
+use assert_cmd::Command;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+const SUBCOMMAND: &str = "hash";
+
+#[test]
+fn help() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd.arg(SUBCOMMAND).arg("--help").assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "dovi_tool hash [OPTIONS] [input_pos]",
+        ));
+    Ok(())
+}
+
+#[test]
+fn frame_hashes() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let output_file = temp.child("hashes.txt");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular_rpu.bin")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("Frames: 259"))
+        .stdout(predicate::str::contains("Unique frame metadata: 7"))
+        .stdout(predicate::str::contains("RPU hash: 915d91d15882eb24"));
+
+    let hashes = fs::read_to_string(output_file.path())?;
+    let lines: Vec<&str> = hashes.lines().collect();
+
+    assert_eq!(lines.len(), 259);
+    assert_eq!(lines[0], "0 f10512ffe5d35a87");
+
+    // Only the scene cut flag differs from the first frame
+    assert!(lines[1].starts_with("1 "));
+    assert_ne!(lines[0][2..], lines[1][2..]);
+    assert_eq!(lines[1][2..], lines[2][2..]);
+
+    Ok(())
+}
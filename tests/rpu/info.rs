@@ -1,11 +1,16 @@
+use std::path::Path;
+
 use anyhow::Result;
 use assert_cmd::Command;
+use assert_fs::prelude::*;
 use predicates::prelude::*;
 
+const SUBCOMMAND: &str = "info";
+
 #[test]
 fn help() -> Result<()> {
     let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
-    let assert = cmd.arg("info").arg("--help").assert();
+    let assert = cmd.arg(SUBCOMMAND).arg("--help").assert();
 
     assert
         .success()
@@ -15,3 +20,353 @@ fn help() -> Result<()> {
         ));
     Ok(())
 }
+
+#[test]
+fn summary_rpu() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let input_file = Path::new("assets/tests/fel_orig.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("--summary")
+        .arg(input_file)
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("Frames: 1"))
+        .stdout(predicate::str::contains("Profile: 7 (FEL)"))
+        .stdout(predicate::str::contains("CM version: CM v2.9"))
+        .stdout(predicate::str::contains(
+            "L6 metadata: MaxCLL 1712 nits, MaxFALL 175 nits, mastering display min 0.0001 nits, max 1000 nits",
+        ))
+        .stdout(predicate::str::contains("L11 metadata: None"))
+        .stdout(predicate::str::contains(
+            "L4 metadata: anchor PQ 1354, anchor power 577",
+        ))
+        .stdout(predicate::str::contains("L3 metadata").not())
+        .stdout(predicate::str::contains("HDR10+").not());
+
+    Ok(())
+}
+
+#[test]
+fn summary_level11() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let input_file = Path::new("assets/tests/cmv40_full_rpu.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("--summary")
+        .arg(input_file)
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("CM version: CM v4.0"))
+        .stdout(predicate::str::contains(
+            "L3 metadata: min offset +0, avg offset +0, max offset +0",
+        ))
+        .stdout(predicate::str::contains(
+            "L11 metadata: Cinema, D65 whitepoint, reference mode",
+        ))
+        .stdout(predicate::str::contains(
+            "L254 metadata: DM mode 0, DM version index 2",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn summary_hevc() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let input_file = Path::new("assets/hevc_tests/regular_hdr10plus.hevc");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("--summary")
+        .arg(input_file)
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("Frames: 259"))
+        .stdout(predicate::str::contains("RPUs:").not())
+        .stdout(predicate::str::contains("Profile: 8"))
+        .stdout(predicate::str::contains("CM version: CM v4.0"))
+        .stdout(predicate::str::contains("Scene cuts: 3"))
+        .stdout(predicate::str::contains("HDR10+: Yes"));
+
+    Ok(())
+}
+
+#[test]
+fn summary_hevc_http() -> Result<()> {
+    let input_file = Path::new("assets/hevc_tests/regular_hdr10plus.hevc");
+    let (url, _) = crate::hevc::serve_file_http(input_file, true);
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd.arg(SUBCOMMAND).arg("--summary").arg(&url).assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("Frames: 259"))
+        .stdout(predicate::str::contains("Profile: 8"))
+        .stdout(predicate::str::contains("HDR10+: Yes"));
+
+    Ok(())
+}
+
+#[test]
+fn summary_stream_info() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("--summary")
+        .arg(input_file)
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("Resolution: 256x144, 10 bit"))
+        .stdout(predicate::str::contains(
+            "Colour: BT.2020 primaries, PQ transfer, BT.2020 non-constant matrix, limited range",
+        ))
+        .stdout(predicate::str::contains(
+            "Mastering display: BT.2020 primaries, max 1000 nits, min 0.0001 nits",
+        ))
+        .stdout(predicate::str::contains(
+            "Content light level: MaxCLL 1000 nits, MaxFALL 400 nits",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn summary_stream_info_custom_primaries() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let output_file = temp.child("BL_RPU.hevc");
+
+    // Without the original SEI messages, replaced by custom values
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("--drop-sei")
+        .arg("mdcv,cll")
+        .arg("convert")
+        .arg("assets/hevc_tests/regular_start_code_4.hevc")
+        .arg("--insert-hdr10-sei")
+        .arg("--master-display")
+        .arg("G(13250,34500)B(7500,3000)R(34000,16000)WP(15700,17550)L(40000000,50)")
+        .arg("--max-cll")
+        .arg("0,0")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("--summary")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Mastering display: G(13250,34500)B(7500,3000)R(34000,16000)WP(15700,17550)L(40000000,50)",
+        ))
+        .stdout(predicate::str::contains(
+            "Content light level: MaxCLL 0 nits, MaxFALL 0 nits",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn analyze_el_unused() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let input_file = Path::new("assets/hevc_tests/yusesope_regular_muxed.hevc");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("--summary")
+        .arg("--analyze-el")
+        .arg(input_file)
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "EL: 259 pictures, 256x144, average 23 bytes per picture",
+        ))
+        .stdout(predicate::str::contains(
+            "EL pictures with residual data: 0",
+        ))
+        .stdout(predicate::str::contains(
+            "EL type: unused, no NLQ data in the RPUs. Discarding the EL is lossless",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn analyze_el_fel_without_residual() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let edit_config = temp.child("duplicate.json");
+    edit_config.write_str(r#"{ "duplicate": [{ "source": 0, "offset": 0, "length": 258 }] }"#)?;
+
+    let fel_rpu = temp.child("RPU.bin");
+    let output_file = temp.child("output.hevc");
+
+    // FEL RPUs for every frame of the dual layer file
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("editor")
+        .arg("assets/tests/fel_orig.bin")
+        .arg("--json")
+        .arg(edit_config.as_ref())
+        .arg("--rpu-out")
+        .arg(fel_rpu.as_ref())
+        .assert()
+        .success();
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("inject-rpu")
+        .arg("assets/hevc_tests/yusesope_regular_muxed.hevc")
+        .arg("--rpu-in")
+        .arg(fel_rpu.as_ref())
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("--summary")
+        .arg("--analyze-el")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("Profile: 7 (FEL)"))
+        .stdout(predicate::str::contains(
+            "EL type: FEL NLQ data, but no residual coded in the EL. Discarding the EL is lossless",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn analyze_el_no_el() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("--summary")
+        .arg("--analyze-el")
+        .arg(input_file)
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("EL: None"));
+
+    Ok(())
+}
+
+#[test]
+fn analyze_el_rpu_input() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let input_file = Path::new("assets/tests/fel_orig.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("--summary")
+        .arg("--analyze-el")
+        .arg(input_file)
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "info: the EL analysis requires a HEVC input",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn scenes_rpu() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let input_file = Path::new("assets/tests/cmv4_0_2_xml_rpu.bin");
+
+    let assert = cmd.arg(SUBCOMMAND).arg("--scenes").arg(input_file).assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("Scenes: 3"))
+        .stdout(predicate::str::contains(
+            "  Scene    Start   Frames   Min nits   Avg nits   Max nits",
+        ))
+        .stdout(predicate::str::contains(
+            "      0        0      120     0.0000       7.11     569.25",
+        ))
+        .stdout(predicate::str::contains(
+            "      1      120       99     0.0000      10.05     100.10",
+        ))
+        .stdout(predicate::str::contains(
+            "      2      219       40     0.0000       2.53     632.88",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn scenes_json_fps() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("--scenes")
+        .arg("--json")
+        .arg("--fps")
+        .arg("24")
+        .arg(input_file)
+        .assert();
+
+    let output = assert.success().get_output().stdout.clone();
+    let output = String::from_utf8(output)?;
+
+    // After the status message
+    let json: serde_json::Value = serde_json::from_str(&output[output.find('[').unwrap()..])?;
+    let scenes = json.as_array().unwrap();
+
+    assert_eq!(scenes.len(), 3);
+    assert_eq!(
+        scenes[1],
+        serde_json::json!({
+            "scene": 1,
+            "start_frame": 120,
+            "frames": 101,
+            "start_time": "00:00:05.000",
+            "duration": 4.208,
+            "l1": { "min_pq": 0, "avg_pq": 1221, "max_pq": 2875 }
+        })
+    );
+
+    Ok(())
+}
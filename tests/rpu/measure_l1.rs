@@ -0,0 +1,317 @@
+use std::path::Path;
+
+use anyhow::Result;
+use assert_cmd::Command;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlock;
+
+const SUBCOMMAND: &str = "measure-l1";
+
+/// Raw 10 bit luma frame, black with a peak white line
+fn frame_with_peak(width: usize, height: usize, background: u16) -> Vec<u8> {
+    let mut samples = vec![background; width * height];
+    samples[..width].fill(940);
+
+    samples.iter().flat_map(|s| s.to_le_bytes()).collect()
+}
+
+#[test]
+fn help() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd.arg(SUBCOMMAND).arg("--help").assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "dovi_tool measure-l1 [OPTIONS] --width <WIDTH> --height <HEIGHT> [input_pos]",
+        ));
+    Ok(())
+}
+
+#[test]
+fn generate() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let (width, height) = (64, 36);
+
+    let frames = temp.child("frames.raw");
+    frames.write_binary(&frame_with_peak(width, height, 64).repeat(24))?;
+
+    let scene_file = temp.child("scenes.txt");
+    scene_file.write_str("0\n12\n")?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("--frames")
+        .arg(frames.as_ref())
+        .arg("--width")
+        .arg(width.to_string())
+        .arg("--height")
+        .arg(height.to_string())
+        .arg("--scene-file")
+        .arg(scene_file.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 24);
+
+    for (i, rpu) in rpus.iter().enumerate() {
+        let vdr_dm_data = rpu.vdr_dm_data.as_ref().unwrap();
+        assert_eq!(vdr_dm_data.scene_refresh_flag == 1, i == 0 || i == 12);
+
+        if let Some(ExtMetadataBlock::Level1(b)) = vdr_dm_data.get_block(1) {
+            assert_eq!(b.min_pq, 0);
+            assert_eq!(b.max_pq, 4095);
+            // Clamped to the minimum average
+            assert_eq!(b.avg_pq, 819);
+        } else {
+            panic!("No L1 block");
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn overwrite() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let (width, height) = (16, 16);
+
+    // Average of (940 + 15 * 502) / 16 = 529.375, 2175 as PQ code
+    let frames = temp.child("frames.raw");
+    frames.write_binary(&frame_with_peak(width, height, 502).repeat(259))?;
+
+    let output_rpu = temp.child("RPU_measured.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--frames")
+        .arg(frames.as_ref())
+        .arg("--width")
+        .arg(width.to_string())
+        .arg("--height")
+        .arg(height.to_string())
+        .arg("--overwrite")
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let rpus = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(rpus.len(), 259);
+
+    for rpu in &rpus {
+        if let Some(ExtMetadataBlock::Level1(b)) = rpu.vdr_dm_data.as_ref().unwrap().get_block(1) {
+            assert_eq!(b.max_pq, 4095);
+            assert_eq!(b.avg_pq, 2175);
+        } else {
+            panic!("No L1 block");
+        }
+    }
+
+    // The overwritten RPU validates against the same frames
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg(output_rpu.as_ref())
+        .arg("--frames")
+        .arg(frames.as_ref())
+        .arg("--width")
+        .arg(width.to_string())
+        .arg("--height")
+        .arg(height.to_string())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "L1 metadata matches the measurements",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn validate_mismatch() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let (width, height) = (16, 16);
+
+    let frames = temp.child("frames.raw");
+    frames.write_binary(&frame_with_peak(width, height, 64).repeat(259))?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--frames")
+        .arg(frames.as_ref())
+        .arg("--width")
+        .arg(width.to_string())
+        .arg("--height")
+        .arg(height.to_string())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "L1 metadata differs from the measurements in 259 of 259 frames",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn mismatched_frame_count() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let frames = temp.child("frames.raw");
+    frames.write_binary(&frame_with_peak(16, 16, 64).repeat(10))?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--frames")
+        .arg(frames.as_ref())
+        .arg("--width")
+        .arg("16")
+        .arg("--height")
+        .arg("16")
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "mismatched frame count: frames 10, RPU 259",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn validate_sampled_scenes() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let (width, height) = (16, 16);
+
+    let frames = temp.child("frames.raw");
+    frames.write_binary(&frame_with_peak(width, height, 64).repeat(24))?;
+
+    let scene_file = temp.child("scenes.txt");
+    scene_file.write_str("0\n12\n")?;
+
+    let generated_rpu = temp.child("RPU.bin");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg("--frames")
+        .arg(frames.as_ref())
+        .arg("--width")
+        .arg(width.to_string())
+        .arg("--height")
+        .arg(height.to_string())
+        .arg("--scene-file")
+        .arg(scene_file.as_ref())
+        .arg("--rpu-out")
+        .arg(generated_rpu.as_ref())
+        .assert()
+        .success();
+
+    // Same cut
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg(generated_rpu.as_ref())
+        .arg("--frames")
+        .arg(frames.as_ref())
+        .arg("--width")
+        .arg(width.to_string())
+        .arg("--height")
+        .arg(height.to_string())
+        .arg("--sample")
+        .arg("4")
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "Measured 6 of 24 frames, one every 4",
+        ))
+        .stdout(predicate::str::contains(
+            "L1 metadata matches the measurements in 2 scenes",
+        ));
+
+    // Different content in the second scene
+    let mut other_cut = frame_with_peak(width, height, 64).repeat(12);
+    other_cut.extend(frame_with_peak(width, height, 502).repeat(12));
+
+    let other_frames = temp.child("other_frames.raw");
+    other_frames.write_binary(&other_cut)?;
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(SUBCOMMAND)
+        .arg(generated_rpu.as_ref())
+        .arg("--frames")
+        .arg(other_frames.as_ref())
+        .arg("--width")
+        .arg(width.to_string())
+        .arg("--height")
+        .arg(height.to_string())
+        .arg("--sample")
+        .arg("4")
+        .assert();
+
+    assert
+        .failure()
+        .stdout(predicate::str::contains("Scene 1 (frames 12-23)"))
+        .stdout(predicate::str::contains("Scene 0").not())
+        .stderr(predicate::str::contains(
+            "L1 metadata differs from the measurements in 1 of 2 scenes",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn sample_requires_input() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let frames = temp.child("frames.raw");
+    frames.write_binary(&frame_with_peak(16, 16, 64))?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("--frames")
+        .arg(frames.as_ref())
+        .arg("--width")
+        .arg("16")
+        .arg("--height")
+        .arg("16")
+        .arg("--sample")
+        .arg("2")
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "measure-l1: --sample is only used when validating an input RPU",
+    ));
+
+    Ok(())
+}
@@ -1,4 +1,17 @@
+mod align;
+mod concat;
+mod conform;
+mod detect_active_area;
+mod diff;
 mod editor;
 mod export;
 mod generate;
+mod generate_lut;
+mod hash;
 mod info;
+mod measure_l1;
+mod patch;
+mod plot;
+mod preview;
+mod split;
+mod verify;
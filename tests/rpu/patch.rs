@@ -0,0 +1,163 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use assert_cmd::Command;
+use assert_fs::fixture::ChildPath;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+const CREATE_SUBCOMMAND: &str = "create-patch";
+const APPLY_SUBCOMMAND: &str = "apply-patch";
+
+const INPUT_RPU: &str = "assets/hevc_tests/regular_rpu.bin";
+
+fn edit(edit_config: &str, edited_rpu: &ChildPath) -> Result<()> {
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("editor")
+        .arg(INPUT_RPU)
+        .arg("--json")
+        .arg(Path::new("assets/editor_examples").join(edit_config))
+        .arg("--rpu-out")
+        .arg(edited_rpu.as_ref())
+        .assert()
+        .success();
+
+    Ok(())
+}
+
+/// Creates the patch from the input to the edited RPU, applies it and compares the result
+fn roundtrip(edit_config: &str) -> Result<(ChildPath, assert_fs::TempDir)> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let edited_rpu = temp.child("RPU_edited.bin");
+    let patch = temp.child("RPU_patch.json");
+    let patched_rpu = temp.child("RPU_patched.bin");
+
+    edit(edit_config, &edited_rpu)?;
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(CREATE_SUBCOMMAND)
+        .arg(INPUT_RPU)
+        .arg(edited_rpu.as_ref())
+        .arg("--output")
+        .arg(patch.as_ref())
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty());
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(APPLY_SUBCOMMAND)
+        .arg(INPUT_RPU)
+        .arg("--patch")
+        .arg(patch.as_ref())
+        .arg("--rpu-out")
+        .arg(patched_rpu.as_ref())
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty());
+
+    assert_eq!(fs::read(edited_rpu.path())?, fs::read(patched_rpu.path())?);
+
+    Ok((patch, temp))
+}
+
+#[test]
+fn help() -> Result<()> {
+    for (subcommand, usage) in [
+        (
+            CREATE_SUBCOMMAND,
+            "dovi_tool create-patch [OPTIONS] <input> <input2>",
+        ),
+        (
+            APPLY_SUBCOMMAND,
+            "dovi_tool apply-patch [OPTIONS] --patch <PATCH> <input>",
+        ),
+    ] {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+        let assert = cmd.arg(subcommand).arg("--help").assert();
+
+        assert
+            .success()
+            .stderr(predicate::str::is_empty())
+            .stdout(predicate::str::contains(usage));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn active_area() -> Result<()> {
+    let (patch, _temp) = roundtrip("active_area_all.json")?;
+
+    // The same L5 change for the frames of each scene, without the CRC32
+    patch.assert(predicate::str::contains(
+        "\"/vdr_dm_data/cmv29_metadata/ext_metadata_blocks/1/Level5/active_area_top_offset\": 210",
+    ));
+    patch.assert(predicate::str::contains("\"length\": 120"));
+    patch.assert(predicate::str::contains("rpu_data_crc32").not());
+    assert!(fs::metadata(patch.path())?.len() < 4096);
+
+    Ok(())
+}
+
+#[test]
+fn duplicated_frames() -> Result<()> {
+    let (patch, _temp) = roundtrip("duplicate.json")?;
+
+    // Copies of source frames, without changes
+    patch.assert(predicate::str::contains("\"frames\": 269"));
+    patch.assert(predicate::str::contains("\"changes\"").not());
+
+    Ok(())
+}
+
+#[test]
+fn removed_frames() -> Result<()> {
+    roundtrip("remove.json")?;
+
+    Ok(())
+}
+
+#[test]
+fn identical() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+    let patch = temp.child("RPU_patch.json");
+
+    let assert = cmd
+        .arg(CREATE_SUBCOMMAND)
+        .arg(INPUT_RPU)
+        .arg(INPUT_RPU)
+        .arg("--output")
+        .arg(patch.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stdout(predicate::str::contains("Patched frames: 0, in 0 ranges"));
+
+    patch.assert(predicate::str::contains("\"frames\": []"));
+
+    Ok(())
+}
+
+#[test]
+fn other_source() -> Result<()> {
+    let (patch, temp) = roundtrip("level6_metadata.json")?;
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg(APPLY_SUBCOMMAND)
+        .arg("assets/tests/fel_orig.bin")
+        .arg("--patch")
+        .arg(patch.as_ref())
+        .arg("--rpu-out")
+        .arg(temp.child("RPU_patched.bin").as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "apply-patch: the patch was created for another RPU file. Expected 259 frames",
+    ));
+
+    Ok(())
+}
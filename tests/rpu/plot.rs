@@ -0,0 +1,155 @@
+use anyhow::Result;
+use assert_cmd::Command;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+const SUBCOMMAND: &str = "plot";
+
+#[test]
+fn help() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd.arg(SUBCOMMAND).arg("--help").assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "dovi_tool plot [OPTIONS] [input_pos]",
+        ));
+    Ok(())
+}
+
+#[test]
+fn plot_svg() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = "assets/tests/fel_orig.bin";
+    let output_file = temp.child("L1_plot.svg");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_file.assert(predicate::path::is_file());
+    output_file.assert(predicate::str::contains("<svg"));
+
+    Ok(())
+}
+
+#[test]
+fn plot_html() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = "assets/tests/cmv4_0_2_xml_rpu.bin";
+    let output_file = temp.child("L2_plot.html");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--plot-type")
+        .arg("l2")
+        .arg("--target-nits")
+        .arg("600")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_file.assert(predicate::path::is_file());
+    output_file.assert(predicate::str::contains("<canvas"));
+    output_file.assert(predicate::str::contains(
+        r#""caption":"cmv4_0_2_xml_rpu.bin - L2 plot, 600 nits""#,
+    ));
+
+    // Self-contained, the data replaced the placeholder
+    output_file.assert(predicate::str::contains("/*PLOT_DATA*/").not());
+    output_file.assert(predicate::str::contains("<script src").not());
+
+    Ok(())
+}
+
+#[test]
+fn plot_l2_svg() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = "assets/tests/cmv4_0_2_xml_rpu.bin";
+    let output_file = temp.child("L2_plot.svg");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--plot-type")
+        .arg("l2")
+        .arg("--target-nits")
+        .arg("600")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_file.assert(predicate::path::is_file());
+    output_file.assert(predicate::str::contains("L2 plot, 600 nits"));
+
+    Ok(())
+}
+
+#[test]
+fn plot_l2_missing_target() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = "assets/tests/cmv4_0_2_xml_rpu.bin";
+    let output_file = temp.child("L2_plot.svg");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--plot-type")
+        .arg("l2")
+        .arg("--target-nits")
+        .arg("4000")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "plot: no L2 metadata for the 4000 nits target",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn plot_l5_svg() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = "assets/tests/cmv4_0_2_xml_with_l5_rpu.bin";
+    let output_file = temp.child("L5_plot.svg");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("-p")
+        .arg("l5")
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    output_file.assert(predicate::path::is_file());
+    output_file.assert(predicate::str::contains("L5 plot"));
+
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use assert_cmd::Command;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+const SUBCOMMAND: &str = "preview";
+
+/// Width and height from the PNG IHDR chunk
+fn png_size(path: &Path) -> Result<(u32, u32)> {
+    let data = fs::read(path)?;
+
+    assert_eq!(&data[..8], b"\x89PNG\r\n\x1a\n");
+
+    Ok((
+        u32::from_be_bytes([data[16], data[17], data[18], data[19]]),
+        u32::from_be_bytes([data[20], data[21], data[22], data[23]]),
+    ))
+}
+
+#[test]
+fn help() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd.arg(SUBCOMMAND).arg("--help").assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "dovi_tool preview [OPTIONS] [input_pos]",
+        ));
+    Ok(())
+}
+
+#[test]
+fn scene_starts() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+    let output_dir = temp.child("preview");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--width")
+        .arg("64")
+        .arg("--height")
+        .arg("36")
+        .arg("--output-dir")
+        .arg(output_dir.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("Rendering 3 frames..."));
+
+    let first_frame = output_dir.child("frame_000000.png");
+    assert_eq!(png_size(first_frame.path())?, (64, 36));
+
+    assert_eq!(fs::read_dir(output_dir.path())?.count(), 3);
+
+    Ok(())
+}
+
+#[test]
+fn compare() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let output_dir = temp.child("preview");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/tests/fel_orig.bin")
+        .arg("--compare")
+        .arg("assets/tests/profile8.bin")
+        .arg("--frames")
+        .arg("0")
+        .arg("--target-nits")
+        .arg("600")
+        .arg("--width")
+        .arg("64")
+        .arg("--height")
+        .arg("36")
+        .arg("--output-dir")
+        .arg(output_dir.as_ref())
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("Rendering 1 frames..."));
+
+    // Side by side
+    let frame = output_dir.child("frame_000000.png");
+    assert_eq!(png_size(frame.path())?, (128, 36));
+
+    Ok(())
+}
+
+#[test]
+fn frame_out_of_range() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/tests/profile8.bin")
+        .arg("--frames")
+        .arg("0,1")
+        .arg("--output-dir")
+        .arg(temp.child("preview").as_ref())
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "preview: frame 1 is out of range, the RPU file has 1 frames",
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,110 @@
+use std::path::Path;
+
+use anyhow::Result;
+use assert_cmd::Command;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+const SUBCOMMAND: &str = "split";
+
+#[test]
+fn help() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd.arg(SUBCOMMAND).arg("--help").assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "dovi_tool split [OPTIONS] [input_pos]",
+        ));
+    Ok(())
+}
+
+#[test]
+fn frames() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--frames")
+        .arg("100,200")
+        .arg("--output-dir")
+        .arg(temp.path())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let expected_lengths = [100, 100, 59];
+
+    for (i, expected_len) in expected_lengths.iter().enumerate() {
+        let part = temp.child(format!("regular_rpu_{:03}.bin", i + 1));
+        part.assert(predicate::path::is_file());
+
+        let rpus = utilities_dovi::parse_rpu_file(part.as_ref())?.unwrap();
+        assert_eq!(rpus.len(), *expected_len);
+
+        let vdr_dm_data = rpus[0].vdr_dm_data.as_ref().unwrap();
+        assert_eq!(vdr_dm_data.scene_refresh_flag, 1);
+    }
+
+    temp.child("regular_rpu_004.bin")
+        .assert(predicate::path::missing());
+
+    Ok(())
+}
+
+#[test]
+fn chapters() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+
+    let chapters = temp.child("chapters.txt");
+    chapters.write_str(
+        "CHAPTER01=00:00:00.000\nCHAPTER01NAME=Chapter 1\n\
+         CHAPTER02=00:00:05.005\nCHAPTER02NAME=Chapter 2\n",
+    )?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg(input_rpu)
+        .arg("--chapters")
+        .arg(chapters.as_ref())
+        .arg("--fps")
+        .arg("24000/1001")
+        .arg("--output-dir")
+        .arg(temp.path())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let first = utilities_dovi::parse_rpu_file(temp.child("regular_rpu_001.bin").as_ref())?;
+    let second = utilities_dovi::parse_rpu_file(temp.child("regular_rpu_002.bin").as_ref())?;
+
+    assert_eq!(first.unwrap().len(), 120);
+    assert_eq!(second.unwrap().len(), 139);
+
+    Ok(())
+}
+
+#[test]
+fn chapters_requires_fps() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular_rpu.bin")
+        .arg("--chapters")
+        .arg("chapters.txt")
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains("--fps"));
+
+    Ok(())
+}
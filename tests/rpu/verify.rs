@@ -0,0 +1,57 @@
+use anyhow::Result;
+use assert_cmd::Command;
+use predicates::prelude::*;
+
+const SUBCOMMAND: &str = "verify";
+
+#[test]
+fn help() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let assert = cmd.arg(SUBCOMMAND).arg("--help").assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains(
+            "dovi_tool verify [OPTIONS] [input_pos]",
+        ));
+    Ok(())
+}
+
+#[test]
+fn verify_with_hevc() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/hevc_tests/regular_rpu.bin")
+        .arg("--hevc")
+        .arg("assets/hevc_tests/regular.hevc")
+        .assert();
+
+    assert
+        .success()
+        .stderr(predicate::str::is_empty())
+        .stdout(predicate::str::contains("No issues found."));
+    Ok(())
+}
+
+#[test]
+fn mismatched_frame_count() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+
+    let assert = cmd
+        .arg(SUBCOMMAND)
+        .arg("assets/tests/fel_orig.bin")
+        .arg("--hevc")
+        .arg("assets/hevc_tests/regular.hevc")
+        .assert();
+
+    assert
+        .failure()
+        .stdout(predicate::str::contains(
+            "mismatched frame count: video 259, RPU 1",
+        ))
+        .stderr(predicate::str::contains("verify: 1 issues found"));
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use anyhow::Result;
+use assert_cmd::cargo::cargo_bin;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+use serde_json::{json, Value};
+
+#[test]
+fn jobs() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let input_file = Path::new("assets/hevc_tests/regular.hevc");
+    let expected_rpu = Path::new("assets/hevc_tests/regular_rpu.bin");
+    let output_rpu = temp.child("RPU.bin");
+
+    let mut child = Command::new(cargo_bin(env!("CARGO_PKG_NAME")))
+        .arg("--serve")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    let mut stdin = child.stdin.take().unwrap();
+    let mut replies = BufReader::new(child.stdout.take().unwrap())
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(&line.unwrap()).unwrap());
+
+    let mut send = |request: Value| writeln!(stdin, "{}", request);
+
+    assert_eq!(replies.next().unwrap(), json!({ "event": "ready" }));
+
+    send(json!({
+        "id": 1,
+        "command": "extract-rpu",
+        "args": [input_file, "-o", output_rpu.path()]
+    }))?;
+
+    assert_eq!(
+        replies.next().unwrap(),
+        json!({ "id": 1, "event": "started" })
+    );
+
+    // Progress events, then the end of the job
+    let end = replies
+        .by_ref()
+        .find(|reply| reply["event"] != "progress" && reply["event"] != "done")
+        .unwrap();
+    assert_eq!(end, json!({ "id": 1, "event": "completed" }));
+
+    output_rpu.assert(predicate::path::eq_file(expected_rpu));
+
+    send(json!({ "id": 2, "command": "info", "args": ["-s", output_rpu.path()] }))?;
+
+    assert_eq!(
+        replies.next().unwrap(),
+        json!({ "id": 2, "event": "started" })
+    );
+
+    let end = replies.next().unwrap();
+    assert_eq!(end["event"], "completed");
+    assert!(end["output"]
+        .as_str()
+        .unwrap()
+        .starts_with("Summary:\n  Frames: 259\n  Profile: 8\n"));
+
+    send(json!({ "id": 3, "command": "convert", "args": ["missing.hevc"] }))?;
+
+    assert_eq!(
+        replies.next().unwrap(),
+        json!({ "id": 3, "event": "started" })
+    );
+    assert_eq!(replies.next().unwrap()["event"], "failed");
+
+    send(json!({ "command": "quit" }))?;
+    assert!(child.wait()?.success());
+
+    Ok(())
+}
+
+#[test]
+fn invalid_requests() -> Result<()> {
+    let mut cmd = assert_cmd::Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+
+    let assert = cmd
+        .arg("--serve")
+        .write_stdin(
+            "not json\n\
+             {\"id\": 1, \"command\": \"editor\"}\n\
+             {\"command\": \"info\"}\n\
+             {\"id\": 2, \"command\": \"cancel\"}\n",
+        )
+        .assert();
+
+    assert.success().stdout(
+        "{\"event\":\"ready\"}\n\
+         {\"event\":\"failed\",\"message\":\"Invalid request: expected ident at line 1 column 2\"}\n\
+         {\"id\":1,\"event\":\"failed\",\"message\":\"Unknown command 'editor'\"}\n\
+         {\"event\":\"failed\",\"message\":\"Missing job id\"}\n\
+         {\"id\":2,\"event\":\"failed\",\"message\":\"No job running\"}\n",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn subcommand_conflict() -> Result<()> {
+    let mut cmd = assert_cmd::Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+
+    let assert = cmd
+        .arg("--serve")
+        .arg("hash")
+        .arg("assets/hevc_tests/regular_rpu.bin")
+        .assert();
+
+    assert.failure().stderr(predicate::str::contains(
+        "--serve cannot be used with a subcommand",
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,257 @@
+use anyhow::Result;
+use assert_cmd::Command;
+use assert_fs::prelude::*;
+use bitvec_helpers::bitvec_writer::BitVecWriter;
+use predicates::prelude::*;
+
+use dolby_vision::rpu::dovi_rpu::DoviRpu;
+
+use super::avc::{encoded_rpus, finish_rbsp, test_rpus, write_nal, PRESENTATION_ORDER};
+
+/// Decoding order of the frames: I P B P B
+/// (nal_unit_type, inter_slice_allowed_flag, ph_pic_order_cnt_lsb)
+const FRAMES: &[(u8, bool, u8)] = &[
+    (8, false, 0),
+    (0, true, 4),
+    (0, true, 2),
+    (0, true, 8),
+    (0, true, 6),
+];
+
+fn nal_header(nal_type: u8) -> Vec<u8> {
+    vec![0x00, (nal_type << 3) | 1]
+}
+
+fn write_picture_header(writer: &mut BitVecWriter, irap: bool, inter: bool, poc_lsb: u8) {
+    writer.write(irap); // ph_gdr_or_irap_pic_flag
+    writer.write(false); // ph_non_ref_pic_flag
+
+    if irap {
+        writer.write(false); // ph_gdr_pic_flag
+    }
+
+    writer.write(inter); // ph_inter_slice_allowed_flag
+    if inter {
+        writer.write(true); // ph_intra_slice_allowed_flag
+    }
+
+    writer.write_ue(0); // ph_pic_parameter_set_id
+    writer.write_n(&[poc_lsb], 4); // ph_pic_order_cnt_lsb
+}
+
+/// Small VVC stream with B frames, with an RPU per decoded frame.
+/// The IDR picture has the picture header in the slice header,
+/// the other pictures use picture header NAL units and two slices.
+fn vvc_stream(rpus: Option<&[DoviRpu]>, aud: bool) -> Result<Vec<u8>> {
+    let mut stream = Vec::new();
+
+    let mut sps = BitVecWriter::new();
+    sps.write_n(&[0], 4); // sps_seq_parameter_set_id
+    sps.write_n(&[0], 4); // sps_video_parameter_set_id
+    sps.write_n(&[0], 3); // sps_max_sublayers_minus1
+    sps.write_n(&[1], 2); // sps_chroma_format_idc
+    sps.write_n(&[2], 2); // sps_log2_ctu_size_minus5
+    sps.write(true); // sps_ptl_dpb_hrd_params_present_flag
+    sps.write_n(&[1], 7); // general_profile_idc
+    sps.write(false); // general_tier_flag
+    sps.write_n(&[51], 8); // general_level_idc
+    sps.write(true); // ptl_frame_only_constraint_flag
+    sps.write(false); // ptl_multilayer_enabled_flag
+    sps.write(false); // gci_present_flag
+    while !sps.is_aligned() {
+        sps.write(false); // gci_alignment_zero_bit
+    }
+    sps.write_n(&[0], 8); // ptl_num_sub_profiles
+    sps.write(false); // sps_gdr_enabled_flag
+    sps.write(false); // sps_ref_pic_resampling_enabled_flag
+    sps.write_ue(64); // sps_pic_width_max_in_luma_samples
+    sps.write_ue(64); // sps_pic_height_max_in_luma_samples
+    sps.write(false); // sps_conformance_window_flag
+    sps.write(false); // sps_subpic_info_present_flag
+    sps.write_ue(2); // sps_bitdepth_minus8
+    sps.write(false); // sps_entropy_coding_sync_enabled_flag
+    sps.write(false); // sps_entry_point_offsets_present_flag
+    sps.write_n(&[0], 4); // sps_log2_max_pic_order_cnt_lsb_minus4
+
+    let mut pps = BitVecWriter::new();
+    pps.write_n(&[0], 6); // pps_pic_parameter_set_id
+    pps.write_n(&[0], 4); // pps_seq_parameter_set_id
+
+    let mut sps_nal = nal_header(15);
+    sps_nal.extend(finish_rbsp(sps));
+
+    let mut pps_nal = nal_header(16);
+    pps_nal.extend(finish_rbsp(pps));
+
+    for (i, (nal_type, inter, poc_lsb)) in FRAMES.iter().enumerate() {
+        let irap = !*inter;
+
+        // AUD, allowing any slice type
+        if aud {
+            let mut aud_nal = nal_header(20);
+            aud_nal.push(((irap as u8) << 7) | 0x28);
+            write_nal(&mut stream, &aud_nal);
+        }
+
+        if i == 0 {
+            write_nal(&mut stream, &sps_nal);
+            write_nal(&mut stream, &pps_nal);
+        }
+
+        let slices = if irap {
+            let mut slice = BitVecWriter::new();
+            slice.write(true); // sh_picture_header_in_slice_header_flag
+            write_picture_header(&mut slice, irap, *inter, *poc_lsb);
+
+            vec![slice]
+        } else {
+            let mut ph = BitVecWriter::new();
+            write_picture_header(&mut ph, irap, *inter, *poc_lsb);
+
+            let mut ph_nal = nal_header(19);
+            ph_nal.extend(finish_rbsp(ph));
+            write_nal(&mut stream, &ph_nal);
+
+            (0..2)
+                .map(|_| {
+                    let mut slice = BitVecWriter::new();
+                    slice.write(false); // sh_picture_header_in_slice_header_flag
+
+                    slice
+                })
+                .collect()
+        };
+
+        for slice in slices {
+            let mut slice_nal = nal_header(*nal_type);
+            slice_nal.extend(finish_rbsp(slice));
+            slice_nal.extend_from_slice(&[0xAB; 16]);
+
+            write_nal(&mut stream, &slice_nal);
+        }
+
+        if let Some(rpus) = rpus {
+            let data = rpus[i].write_hevc_unspec62_nalu()?;
+
+            let mut rpu_nal = nal_header(30);
+            rpu_nal.extend_from_slice(&data[2..]);
+
+            write_nal(&mut stream, &rpu_nal);
+        }
+    }
+
+    Ok(stream)
+}
+
+#[test]
+fn extract_rpu() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let rpus = test_rpus()?;
+
+    let input_file = temp.child("input.h266");
+    input_file.write_binary(&vvc_stream(Some(&rpus), true)?)?;
+
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = cmd
+        .arg("extract-rpu")
+        .arg(input_file.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let extracted = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(extracted.len(), rpus.len());
+
+    let expected: Vec<DoviRpu> = PRESENTATION_ORDER
+        .iter()
+        .map(|i| rpus[*i].clone())
+        .collect();
+    assert_eq!(encoded_rpus(&extracted)?, encoded_rpus(&expected)?);
+
+    Ok(())
+}
+
+#[test]
+fn inject_rpu() -> Result<()> {
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let rpus = test_rpus()?;
+
+    // Not detected from the extension
+    let input_file = temp.child("input.bin");
+    input_file.write_binary(&vvc_stream(None, true)?)?;
+
+    let input_rpu = temp.child("input_RPU.bin");
+    let mut rpu_file = Vec::new();
+    for data in encoded_rpus(&rpus)? {
+        write_nal(&mut rpu_file, &data[2..]);
+    }
+    input_rpu.write_binary(&rpu_file)?;
+
+    let output_file = temp.child("injected.vvc");
+    let output_rpu = temp.child("RPU.bin");
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("--input-codec")
+        .arg("vvc")
+        .arg("inject-rpu")
+        .arg(input_file.as_ref())
+        .arg("--rpu-in")
+        .arg(input_rpu.as_ref())
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    let assert = Command::cargo_bin(env!("CARGO_PKG_NAME"))?
+        .arg("extract-rpu")
+        .arg(output_file.as_ref())
+        .arg("--rpu-out")
+        .arg(output_rpu.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    // RPUs are injected in presentation order
+    let extracted = utilities_dovi::parse_rpu_file(output_rpu.as_ref())?.unwrap();
+    assert_eq!(encoded_rpus(&extracted)?, encoded_rpus(&rpus)?);
+
+    Ok(())
+}
+
+#[test]
+fn add_aud() -> Result<()> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    let temp = assert_fs::TempDir::new().unwrap();
+
+    let rpus = test_rpus()?;
+
+    let input_file = temp.child("input.h266");
+    input_file.write_binary(&vvc_stream(Some(&rpus), false)?)?;
+
+    let output_file = temp.child("output.h266");
+
+    let assert = cmd
+        .arg("--add-aud")
+        .arg("convert")
+        .arg(input_file.as_ref())
+        .arg("--output")
+        .arg(output_file.as_ref())
+        .assert();
+
+    assert.success().stderr(predicate::str::is_empty());
+
+    // Same AUDs as the source stream, with the IRAP flag set from the first slice
+    assert_eq!(
+        std::fs::read(output_file.path())?,
+        vvc_stream(Some(&rpus), true)?
+    );
+
+    Ok(())
+}
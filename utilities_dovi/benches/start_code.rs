@@ -0,0 +1,55 @@
+//! Compares the start code scan against the `hevc_parser` one.
+//!
+//! Run with `cargo bench -p utilities_dovi --bench start_code`.
+
+use std::hint::black_box;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use hevc_parser::HevcParser;
+use utilities_dovi::start_code::get_offsets;
+
+const ITERATIONS: u32 = 20;
+
+fn main() {
+    let sample = Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets/hevc_tests/regular.hevc");
+    let sample = std::fs::read(sample).expect("Failed reading the sample file");
+
+    // ~64 MiB of real HEVC data
+    let data = sample.repeat((64 * 1024 * 1024) / sample.len());
+
+    let mut offsets = Vec::with_capacity(65536);
+
+    let mut parser = HevcParser::default();
+    let hevc_parser = measure(|| parser.get_offsets(black_box(&data), &mut offsets));
+    let expected = offsets.clone();
+
+    let simd = measure(|| get_offsets(black_box(&data), &mut offsets));
+    assert_eq!(offsets, expected);
+
+    report("hevc_parser", data.len(), hevc_parser);
+    report("start_code", data.len(), simd);
+
+    println!(
+        "start_code speedup: {:.2}x",
+        hevc_parser.as_secs_f64() / simd.as_secs_f64()
+    );
+}
+
+/// Best time of the iterations
+fn measure(mut f: impl FnMut()) -> Duration {
+    (0..ITERATIONS)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn report(name: &str, len: usize, elapsed: Duration) {
+    let throughput = len as f64 / elapsed.as_secs_f64() / (1024.0 * 1024.0);
+
+    println!("{name:<12} {elapsed:>10.2?} {throughput:>10.0} MiB/s");
+}
@@ -0,0 +1,8 @@
+fn main() {
+    // Links the system libzstd
+    #[cfg(feature = "zstd")]
+    pkg_config::Config::new()
+        .atleast_version("1.4.0")
+        .probe("libzstd")
+        .expect("libzstd not found, required by the zstd feature");
+}
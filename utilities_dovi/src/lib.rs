@@ -7,14 +7,60 @@ use std::{
     path::Path,
 };
 
+use flate2::read::MultiGzDecoder;
 use hevc_parser::{HevcParser, NALUStartCode};
 
+/// Should never be this large, avoid mistakes
+const MAX_RPU_FILE_SIZE: u64 = 250_000_000;
+
+const GZIP_MAGIC: &[u8] = &[0x1F, 0x8B];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xB5, 0x2F, 0xFD];
+
+pub mod start_code;
+pub mod stream;
+#[cfg(feature = "zstd")]
+pub mod zstd;
+
 pub fn parse_rpu_file(input: &Path) -> Result<Option<Vec<DoviRpu>>> {
+    let results = parse_rpu_file_results(input)?;
+
+    let count = results.len();
+    let mut warned = false;
+
+    let rpus: Vec<DoviRpu> = results
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, res)| {
+            if let Err(e) = &res {
+                if !warned {
+                    println!("Error parsing frame {}: {}", i, e);
+                    warned = true;
+                }
+            }
+
+            res.ok()
+        })
+        .collect();
+
+    if count > 0 && rpus.len() == count {
+        Ok(Some(rpus))
+    } else if count == 0 {
+        bail!("No RPU found");
+    } else {
+        bail!(
+            "Number of valid RPUs different from total: expected {} got {}",
+            count,
+            rpus.len()
+        );
+    }
+}
+
+/// Reads the content of an RPU file, decompressed when gzip or Zstandard compressed
+fn read_rpu_file(input: &Path) -> Result<Vec<u8>> {
     let rpu_file = File::open(input)?;
     let metadata = rpu_file.metadata()?;
 
-    // Should never be this large, avoid mistakes
-    if metadata.len() > 250_000_000 {
+    if metadata.len() > MAX_RPU_FILE_SIZE {
         bail!("Input file probably too large");
     }
 
@@ -24,6 +70,49 @@ pub fn parse_rpu_file(input: &Path) -> Result<Option<Vec<DoviRpu>>> {
     let mut data = vec![0; metadata.len() as usize];
     reader.read_exact(&mut data)?;
 
+    if data.starts_with(GZIP_MAGIC) {
+        let mut decompressed = Vec::with_capacity(data.len() * 4);
+
+        MultiGzDecoder::new(data.as_slice())
+            .take(MAX_RPU_FILE_SIZE + 1)
+            .read_to_end(&mut decompressed)?;
+
+        if decompressed.len() as u64 > MAX_RPU_FILE_SIZE {
+            bail!("Decompressed input file probably too large");
+        }
+
+        Ok(decompressed)
+    } else if data.starts_with(ZSTD_MAGIC) {
+        decompress_zstd(&data)
+    } else {
+        Ok(data)
+    }
+}
+
+#[cfg(feature = "zstd")]
+fn decompress_zstd(data: &[u8]) -> Result<Vec<u8>> {
+    let mut decompressed = Vec::with_capacity(data.len() * 4);
+
+    zstd::ZstdDecoder::new(data)?
+        .take(MAX_RPU_FILE_SIZE + 1)
+        .read_to_end(&mut decompressed)?;
+
+    if decompressed.len() as u64 > MAX_RPU_FILE_SIZE {
+        bail!("Decompressed input file probably too large");
+    }
+
+    Ok(decompressed)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn decompress_zstd(_data: &[u8]) -> Result<Vec<u8>> {
+    bail!("Zstandard compressed RPU files require the zstd feature")
+}
+
+/// Parses every RPU NALU in the file, keeping the result for each frame
+pub fn parse_rpu_file_results(input: &Path) -> Result<Vec<Result<DoviRpu>>> {
+    let data = read_rpu_file(input)?;
+
     let mut offsets = Vec::with_capacity(200_000);
     let mut parser = HevcParser::with_nalu_start_code(NALUStartCode::Length4);
 
@@ -33,11 +122,9 @@ pub fn parse_rpu_file(input: &Path) -> Result<Option<Vec<DoviRpu>>> {
         bail!("No NALU start codes found in the file. Maybe not a valid RPU?");
     }
 
-    let count = offsets.len();
     let last = *offsets.last().unwrap();
-    let mut warned = false;
 
-    let rpus: Vec<DoviRpu> = offsets
+    let results = offsets
         .iter()
         .enumerate()
         .map(|(index, offset)| {
@@ -52,28 +139,7 @@ pub fn parse_rpu_file(input: &Path) -> Result<Option<Vec<DoviRpu>>> {
 
             DoviRpu::parse_unspec62_nalu(&data[start..end])
         })
-        .enumerate()
-        .filter_map(|(i, res)| {
-            if let Err(e) = &res {
-                if !warned {
-                    println!("Error parsing frame {}: {}", i, e);
-                    warned = true;
-                }
-            }
-
-            res.ok()
-        })
         .collect();
 
-    if count > 0 && rpus.len() == count {
-        Ok(Some(rpus))
-    } else if count == 0 {
-        bail!("No RPU found");
-    } else {
-        bail!(
-            "Number of valid RPUs different from total: expected {} got {}",
-            count,
-            rpus.len()
-        );
-    }
+    Ok(results)
 }
@@ -0,0 +1,139 @@
+//! Annex B start code scanning.
+//!
+//! Start codes end with a 0x01 byte, so the SIMD paths look for 0x01 in blocks of
+//! 16 or 32 bytes and only check the two preceding bytes on a match.
+//! The remaining bytes are scanned with the scalar implementation.
+
+const NAL_START_CODE_3: &[u8] = &[0, 0, 1];
+
+/// Offsets of the 3 bytes start codes in the data
+pub fn get_offsets(data: &[u8], offsets: &mut Vec<usize>) {
+    offsets.clear();
+
+    let scanned = simd::scan(data, offsets);
+    scan_scalar(data, scanned, offsets);
+}
+
+/// Scans for the start codes ending at or after `from`
+fn scan_scalar(data: &[u8], from: usize, offsets: &mut Vec<usize>) {
+    let mut i = from.saturating_sub(NAL_START_CODE_3.len() - 1);
+
+    while i + NAL_START_CODE_3.len() <= data.len() {
+        if data[i + 2] > 1 {
+            i += 3;
+        } else if &data[i..i + 3] == NAL_START_CODE_3 {
+            offsets.push(i);
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+}
+
+/// Adds the start codes ending at the 0x01 bytes set in the block mask
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+fn push_matches(data: &[u8], block_start: usize, mut mask: u32, offsets: &mut Vec<usize>) {
+    while mask != 0 {
+        let end = block_start + mask.trailing_zeros() as usize;
+
+        if end >= 2 && data[end - 1] == 0 && data[end - 2] == 0 {
+            offsets.push(end - 2);
+        }
+
+        mask &= mask - 1;
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod simd {
+    use std::arch::x86_64::*;
+
+    use super::push_matches;
+
+    /// Returns the number of bytes scanned
+    pub fn scan(data: &[u8], offsets: &mut Vec<usize>) -> usize {
+        if is_x86_feature_detected!("avx2") {
+            unsafe { scan_avx2(data, offsets) }
+        } else {
+            // SSE2 is always available on x86_64
+            unsafe { scan_sse2(data, offsets) }
+        }
+    }
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn scan_avx2(data: &[u8], offsets: &mut Vec<usize>) -> usize {
+        let ones = _mm256_set1_epi8(1);
+        let mut i = 0;
+
+        while i + 32 <= data.len() {
+            let block = _mm256_loadu_si256(data.as_ptr().add(i) as *const __m256i);
+            let mask = _mm256_movemask_epi8(_mm256_cmpeq_epi8(block, ones)) as u32;
+
+            push_matches(data, i, mask, offsets);
+            i += 32;
+        }
+
+        i
+    }
+
+    #[target_feature(enable = "sse2")]
+    unsafe fn scan_sse2(data: &[u8], offsets: &mut Vec<usize>) -> usize {
+        let ones = _mm_set1_epi8(1);
+        let mut i = 0;
+
+        while i + 16 <= data.len() {
+            let block = _mm_loadu_si128(data.as_ptr().add(i) as *const __m128i);
+            let mask = _mm_movemask_epi8(_mm_cmpeq_epi8(block, ones)) as u32;
+
+            push_matches(data, i, mask, offsets);
+            i += 16;
+        }
+
+        i
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod simd {
+    use std::arch::aarch64::*;
+
+    use super::push_matches;
+
+    /// Returns the number of bytes scanned
+    pub fn scan(data: &[u8], offsets: &mut Vec<usize>) -> usize {
+        // NEON is always available on aarch64
+        unsafe { scan_neon(data, offsets) }
+    }
+
+    unsafe fn scan_neon(data: &[u8], offsets: &mut Vec<usize>) -> usize {
+        let ones = vdupq_n_u8(1);
+        let mut i = 0;
+
+        while i + 16 <= data.len() {
+            let block = vld1q_u8(data.as_ptr().add(i));
+
+            // No movemask, the mask is only built for the blocks with a match
+            if vmaxvq_u8(vceqq_u8(block, ones)) != 0 {
+                let mask = data[i..i + 16]
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, b)| **b == 1)
+                    .fold(0, |mask, (j, _)| mask | (1 << j));
+
+                push_matches(data, i, mask, offsets);
+            }
+
+            i += 16;
+        }
+
+        i
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod simd {
+    /// No SIMD path, everything is scanned by the scalar implementation
+    pub fn scan(_data: &[u8], _offsets: &mut Vec<usize>) -> usize {
+        0
+    }
+}
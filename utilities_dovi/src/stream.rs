@@ -0,0 +1,192 @@
+//! Callback based processing of Annex B HEVC streams,
+//! for the tools embedding the RPU extraction in their own pipelines.
+//!
+//! The callbacks are called in decoding order, with slices of the chunk being read.
+//! The frames returned at the end give the presentation order.
+//!
+//! With the `tokio` feature, the `_async` variants read from a tokio `AsyncRead`,
+//! such as a network stream, without blocking a thread while waiting for the data.
+
+use std::io::Read;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use dolby_vision::rpu::dovi_rpu::DoviRpu;
+use hevc_parser::hevc::{Frame, NALUnit, NAL_UNSPEC62};
+use hevc_parser::io::processor::{HevcProcessor, HevcProcessorOpts};
+use hevc_parser::io::{IoFormat, IoProcessor};
+use hevc_parser::HevcParser;
+
+/// RPU found in the stream
+pub struct StreamRpu<'a> {
+    /// Index of the frame in decoding order
+    pub decoded_index: u64,
+    /// UNSPEC62 NAL unit, without the start code
+    pub data: &'a [u8],
+    pub rpu: DoviRpu,
+}
+
+struct CallbackProcessor<F> {
+    input: PathBuf,
+    on_nal: F,
+    frames: Vec<Frame>,
+}
+
+impl<F> IoProcessor for CallbackProcessor<F>
+where
+    F: FnMut(&NALUnit, &[u8]) -> Result<()>,
+{
+    fn input(&self) -> &PathBuf {
+        &self.input
+    }
+
+    fn update_progress(&mut self, _delta: u64) {}
+
+    fn process_nals(&mut self, _parser: &HevcParser, nals: &[NALUnit], chunk: &[u8]) -> Result<()> {
+        for nal in nals {
+            (self.on_nal)(nal, &chunk[nal.start..nal.end])?;
+        }
+
+        Ok(())
+    }
+
+    fn finalize(&mut self, parser: &HevcParser) -> Result<()> {
+        self.frames = parser.ordered_frames().clone();
+
+        Ok(())
+    }
+}
+
+/// Processes the HEVC stream, calling `on_nal` for every NAL unit.
+/// The data is the NAL unit without its start code.
+///
+/// Returns the frames, ordered by presentation number.
+pub fn process_hevc_nals<F>(reader: &mut dyn Read, on_nal: F) -> Result<Vec<Frame>>
+where
+    F: FnMut(&NALUnit, &[u8]) -> Result<()>,
+{
+    let chunk_size = 100_000;
+
+    let mut processor = CallbackProcessor {
+        input: PathBuf::new(),
+        on_nal,
+        frames: Vec::new(),
+    };
+
+    // Fills the chunks from readers returning partial reads
+    let mut hevc_processor =
+        HevcProcessor::new(IoFormat::RawStdin, HevcProcessorOpts::default(), chunk_size);
+    hevc_processor.process_io(reader, &mut processor)?;
+
+    Ok(processor.frames)
+}
+
+/// Processes the HEVC stream, calling `on_rpu` for every parsed RPU.
+///
+/// Returns the frames, ordered by presentation number.
+pub fn process_hevc_rpus<F>(reader: &mut dyn Read, on_rpu: F) -> Result<Vec<Frame>>
+where
+    F: FnMut(StreamRpu) -> Result<()>,
+{
+    process_hevc_nals(reader, rpu_nal_callback(on_rpu))
+}
+
+/// Same as `process_hevc_nals`, reading from an async reader.
+///
+/// The NAL units of every chunk are parsed once it is read, on the calling task.
+#[cfg(feature = "tokio")]
+pub async fn process_hevc_nals_async<R, F>(reader: &mut R, mut on_nal: F) -> Result<Vec<Frame>>
+where
+    R: AsyncRead + Unpin,
+    F: FnMut(&NALUnit, &[u8]) -> Result<()>,
+{
+    let chunk_size = 100_000;
+
+    let mut parser = HevcParser::default();
+    let mut buf = vec![0; chunk_size];
+    let mut chunk = Vec::with_capacity(chunk_size);
+    let mut offsets = Vec::with_capacity(2048);
+
+    loop {
+        // Reads a chunk from the partial reads, until the end of the stream
+        let mut eof = false;
+        let chunk_end = chunk.len() + chunk_size;
+
+        while chunk.len() < chunk_end {
+            let n = reader.read(&mut buf).await?;
+
+            if n == 0 {
+                eof = true;
+                break;
+            }
+
+            chunk.extend_from_slice(&buf[..n]);
+        }
+
+        parser.get_offsets(&chunk, &mut offsets);
+
+        // The last NAL unit can continue in the next chunk
+        let last = match (eof, offsets.last()) {
+            (_, None) => None,
+            (true, Some(&last)) => Some(last),
+            (false, Some(_)) => offsets.pop(),
+        };
+
+        if let Some(last) = last.filter(|_| !offsets.is_empty()) {
+            let nals = parser.split_nals(&chunk, &offsets, last, true)?;
+
+            for nal in &nals {
+                on_nal(nal, &chunk[nal.start..nal.end])?;
+            }
+
+            chunk.drain(..last);
+        }
+
+        if eof {
+            break;
+        }
+    }
+
+    parser.finish();
+
+    Ok(parser.ordered_frames().clone())
+}
+
+/// Same as `process_hevc_rpus`, reading from an async reader.
+#[cfg(feature = "tokio")]
+pub async fn process_hevc_rpus_async<R, F>(reader: &mut R, on_rpu: F) -> Result<Vec<Frame>>
+where
+    R: AsyncRead + Unpin,
+    F: FnMut(StreamRpu) -> Result<()>,
+{
+    process_hevc_nals_async(reader, rpu_nal_callback(on_rpu)).await
+}
+
+/// NAL unit callback parsing the RPUs for `on_rpu`
+fn rpu_nal_callback<F>(mut on_rpu: F) -> impl FnMut(&NALUnit, &[u8]) -> Result<()>
+where
+    F: FnMut(StreamRpu) -> Result<()>,
+{
+    move |nal, data| {
+        if nal.nal_type != NAL_UNSPEC62 {
+            return Ok(());
+        }
+
+        let rpu = DoviRpu::parse_unspec62_nalu(data).map_err(|e| {
+            anyhow!(
+                "Failed parsing the RPU of decoded frame {}: {}",
+                nal.decoded_frame_index,
+                e
+            )
+        })?;
+
+        on_rpu(StreamRpu {
+            decoded_index: nal.decoded_frame_index,
+            data,
+            rpu,
+        })
+    }
+}
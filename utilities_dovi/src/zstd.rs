@@ -0,0 +1,260 @@
+//! Zstandard streaming compression, with the system `libzstd`.
+
+use std::ffi::{c_char, c_uint, c_void, CStr};
+use std::io::{self, Read, Write};
+use std::ptr::NonNull;
+
+#[repr(C)]
+struct InBuffer {
+    src: *const c_void,
+    size: usize,
+    pos: usize,
+}
+
+#[repr(C)]
+struct OutBuffer {
+    dst: *mut c_void,
+    size: usize,
+    pos: usize,
+}
+
+#[repr(C)]
+struct CCtx {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct DCtx {
+    _private: [u8; 0],
+}
+
+/// `ZSTD_EndDirective`
+const ZSTD_E_CONTINUE: c_uint = 0;
+const ZSTD_E_FLUSH: c_uint = 1;
+const ZSTD_E_END: c_uint = 2;
+
+extern "C" {
+    fn ZSTD_isError(code: usize) -> c_uint;
+    fn ZSTD_getErrorName(code: usize) -> *const c_char;
+
+    fn ZSTD_createCCtx() -> *mut CCtx;
+    fn ZSTD_freeCCtx(cctx: *mut CCtx) -> usize;
+    fn ZSTD_CStreamOutSize() -> usize;
+    fn ZSTD_compressStream2(
+        cctx: *mut CCtx,
+        output: *mut OutBuffer,
+        input: *mut InBuffer,
+        end_op: c_uint,
+    ) -> usize;
+
+    fn ZSTD_createDCtx() -> *mut DCtx;
+    fn ZSTD_freeDCtx(dctx: *mut DCtx) -> usize;
+    fn ZSTD_DStreamInSize() -> usize;
+    fn ZSTD_decompressStream(
+        dctx: *mut DCtx,
+        output: *mut OutBuffer,
+        input: *mut InBuffer,
+    ) -> usize;
+}
+
+fn check(code: usize) -> io::Result<usize> {
+    if unsafe { ZSTD_isError(code) } == 0 {
+        return Ok(code);
+    }
+
+    let name = unsafe { CStr::from_ptr(ZSTD_getErrorName(code)) };
+
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("zstd: {}", name.to_string_lossy()),
+    ))
+}
+
+/// Compresses the data written to it, at the default compression level.
+/// `try_finish` completes the frame, no data can be written after it.
+pub struct ZstdEncoder<W: Write> {
+    inner: W,
+    cctx: NonNull<CCtx>,
+
+    buffer: Vec<u8>,
+    finished: bool,
+}
+
+// The context is only used through `&mut self`
+unsafe impl<W: Write + Send> Send for ZstdEncoder<W> {}
+
+impl<W: Write> ZstdEncoder<W> {
+    pub fn new(inner: W) -> io::Result<Self> {
+        let cctx = NonNull::new(unsafe { ZSTD_createCCtx() })
+            .ok_or_else(|| io::Error::other("zstd: failed to create the compression context"))?;
+
+        Ok(Self {
+            inner,
+            cctx,
+            buffer: vec![0; unsafe { ZSTD_CStreamOutSize() }],
+            finished: false,
+        })
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Compresses the data, writing the output until the directive is complete.
+    /// Returns the number of bytes of `data` consumed.
+    fn compress(&mut self, data: &[u8], end_op: c_uint) -> io::Result<usize> {
+        if self.finished {
+            return Err(io::Error::other("zstd: the frame is already complete"));
+        }
+
+        let mut input = InBuffer {
+            src: data.as_ptr().cast(),
+            size: data.len(),
+            pos: 0,
+        };
+
+        loop {
+            let mut output = OutBuffer {
+                dst: self.buffer.as_mut_ptr().cast(),
+                size: self.buffer.len(),
+                pos: 0,
+            };
+
+            let remaining = check(unsafe {
+                ZSTD_compressStream2(self.cctx.as_ptr(), &mut output, &mut input, end_op)
+            })?;
+
+            self.inner.write_all(&self.buffer[..output.pos])?;
+
+            let done = if end_op == ZSTD_E_CONTINUE {
+                input.pos == input.size
+            } else {
+                remaining == 0
+            };
+
+            if done {
+                return Ok(input.pos);
+            }
+        }
+    }
+
+    /// Writes the end of the frame
+    pub fn try_finish(&mut self) -> io::Result<()> {
+        if !self.finished {
+            self.compress(&[], ZSTD_E_END)?;
+            self.finished = true;
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for ZstdEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.compress(buf, ZSTD_E_CONTINUE)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.finished {
+            self.compress(&[], ZSTD_E_FLUSH)?;
+        }
+
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for ZstdEncoder<W> {
+    fn drop(&mut self) {
+        unsafe { ZSTD_freeCCtx(self.cctx.as_ptr()) };
+    }
+}
+
+/// Decompresses the Zstandard frames read from the inner reader
+pub struct ZstdDecoder<R: Read> {
+    inner: R,
+    dctx: NonNull<DCtx>,
+
+    buffer: Vec<u8>,
+    pos: usize,
+    len: usize,
+
+    /// Output left in the context, the last output buffer being full
+    pending: bool,
+    /// Within a frame, the input can't end
+    in_frame: bool,
+}
+
+// The context is only used through `&mut self`
+unsafe impl<R: Read + Send> Send for ZstdDecoder<R> {}
+
+impl<R: Read> ZstdDecoder<R> {
+    pub fn new(inner: R) -> io::Result<Self> {
+        let dctx = NonNull::new(unsafe { ZSTD_createDCtx() })
+            .ok_or_else(|| io::Error::other("zstd: failed to create the decompression context"))?;
+
+        Ok(Self {
+            inner,
+            dctx,
+            buffer: vec![0; unsafe { ZSTD_DStreamInSize() }],
+            pos: 0,
+            len: 0,
+            pending: false,
+            in_frame: false,
+        })
+    }
+}
+
+impl<R: Read> Read for ZstdDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            if self.pos == self.len && !self.pending {
+                self.len = self.inner.read(&mut self.buffer)?;
+                self.pos = 0;
+
+                if self.len == 0 {
+                    if self.in_frame {
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "zstd: truncated data",
+                        ));
+                    }
+
+                    return Ok(0);
+                }
+            }
+
+            let mut input = InBuffer {
+                src: self.buffer[self.pos..self.len].as_ptr().cast(),
+                size: self.len - self.pos,
+                pos: 0,
+            };
+            let mut output = OutBuffer {
+                dst: buf.as_mut_ptr().cast(),
+                size: buf.len(),
+                pos: 0,
+            };
+
+            let hint = check(unsafe {
+                ZSTD_decompressStream(self.dctx.as_ptr(), &mut output, &mut input)
+            })?;
+
+            self.pos += input.pos;
+            self.pending = output.pos == output.size;
+            self.in_frame = hint != 0;
+
+            if output.pos > 0 {
+                return Ok(output.pos);
+            }
+        }
+    }
+}
+
+impl<R: Read> Drop for ZstdDecoder<R> {
+    fn drop(&mut self) {
+        unsafe { ZSTD_freeDCtx(self.dctx.as_ptr()) };
+    }
+}